@@ -0,0 +1,142 @@
+//! Cancellation of running jobs
+//!
+//! `POST /api/jobs/:id/cancel` publishes the job's ID on the Redis Pub/Sub
+//! channel [`JOB_CANCEL_CHANNEL`]. [`JobCancellations`] holds the agent's
+//! single subscription to the channel and broadcasts the IDs to the
+//! executors of its running jobs, each of which waits for its own job's ID
+//! with a [`JobCancellation`] and stops the step it is running.
+
+use anyhow::{bail, Context, Result};
+use futures::StreamExt;
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+use raibid_common::jobs::JOB_CANCEL_CHANNEL;
+
+/// Capacity of the broadcast channel
+const CHANNEL_CAPACITY: usize = 64;
+
+/// Delay before resubscribing after the subscription fails
+const RETRY_DELAY: Duration = Duration::from_secs(2);
+
+/// Fans the IDs of cancelled jobs out to the executors of running jobs
+#[derive(Debug, Clone)]
+pub struct JobCancellations {
+    sender: broadcast::Sender<String>,
+}
+
+impl Default for JobCancellations {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl JobCancellations {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(CHANNEL_CAPACITY);
+        Self { sender }
+    }
+
+    /// Wait for `job_id` to be cancelled from now on
+    pub fn watch(&self, job_id: impl Into<String>) -> JobCancellation {
+        JobCancellation {
+            job_id: job_id.into(),
+            receiver: self.sender.subscribe(),
+        }
+    }
+
+    /// Cancel `job_id` if it is running on this agent
+    pub fn cancel(&self, job_id: impl Into<String>) {
+        // No receivers means no job is running, so there is nothing to stop
+        let _ = self.sender.send(job_id.into());
+    }
+
+    /// Spawn the task subscribing to [`JOB_CANCEL_CHANNEL`] on the Redis at
+    /// `redis_url`, resubscribing whenever the connection drops
+    pub fn spawn_subscriber(&self, redis_url: &str) -> JoinHandle<()> {
+        let cancellations = self.clone();
+        let redis_url = redis_url.to_string();
+        tokio::spawn(async move {
+            loop {
+                if let Err(e) = cancellations.listen(&redis_url).await {
+                    warn!("Job cancellation subscription failed: {:#}", e);
+                }
+                tokio::time::sleep(RETRY_DELAY).await;
+            }
+        })
+    }
+
+    /// Subscribe and broadcast cancelled job IDs until the connection drops
+    async fn listen(&self, redis_url: &str) -> Result<()> {
+        let client = redis::Client::open(redis_url).context("Invalid Redis URL")?;
+        let mut pubsub = client
+            .get_async_connection()
+            .await
+            .context("Failed to connect to Redis")?
+            .into_pubsub();
+        pubsub
+            .subscribe(JOB_CANCEL_CHANNEL)
+            .await
+            .with_context(|| format!("Failed to subscribe to '{}'", JOB_CANCEL_CHANNEL))?;
+        info!(
+            "Subscribed to job cancellations on '{}'",
+            JOB_CANCEL_CHANNEL
+        );
+
+        let mut messages = pubsub.on_message();
+        while let Some(message) = messages.next().await {
+            match message.get_payload::<String>() {
+                Ok(job_id) => self.cancel(job_id),
+                Err(e) => warn!("Skipping unreadable job cancellation: {}", e),
+            }
+        }
+        bail!("Redis closed the connection")
+    }
+}
+
+/// Waits for one job to be cancelled
+#[derive(Debug)]
+pub struct JobCancellation {
+    job_id: String,
+    receiver: broadcast::Receiver<String>,
+}
+
+impl JobCancellation {
+    /// Resolve once the job is cancelled; never if cancellations stop
+    /// arriving
+    ///
+    /// Cancellations received while nothing was waiting are not lost, so a
+    /// job cancelled between two steps stops at the start of the next one.
+    pub async fn cancelled(&mut self) {
+        loop {
+            match self.receiver.recv().await {
+                Ok(job_id) if job_id == self.job_id => return,
+                Ok(_) => {}
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!("Missed {} job cancellations", skipped);
+                }
+                Err(RecvError::Closed) => std::future::pending().await,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_only_own_job_is_cancelled() {
+        let cancellations = JobCancellations::new();
+        let mut first = cancellations.watch("job-1");
+        let mut second = cancellations.watch("job-2");
+
+        cancellations.cancel("job-2");
+        let wait = Duration::from_millis(100);
+        assert!(tokio::time::timeout(wait, first.cancelled()).await.is_err());
+        assert!(tokio::time::timeout(wait, second.cancelled()).await.is_ok());
+    }
+}
@@ -0,0 +1,985 @@
+//! Pipeline definition loading
+//!
+//! Repositories describe their build pipeline in a `.raibid.yaml` file at the
+//! repository root. When the file provides a `steps:` list it replaces the
+//! default step sequence entirely; otherwise the agent falls back to
+//! [`default_steps`](crate::pipeline::default_steps).
+//!
+//! ```yaml
+//! steps:
+//!   - fmt
+//!   - clippy
+//!   - shell: ./scripts/generate-protos.sh
+//!   - make: release
+//!   - just: integration
+//!   - docker_build: registry.local/app:latest
+//! ```
+//!
+//! With a `scan_policy:`, each `docker_build` step is followed by a
+//! `security-scan` of the image it built (see [`crate::pipeline::scan`]):
+//!
+//! ```yaml
+//! scan_policy:
+//!   min_severity_to_fail: critical
+//!   ignore_unfixed: true
+//!   continue_on_failure: true
+//! ```
+//!
+//! A `matrix:` runs the steps once per toolchain and target combination:
+//!
+//! ```yaml
+//! matrix:
+//!   toolchains: [stable, "1.75"]
+//!   targets: [x86_64-unknown-linux-gnu, aarch64-unknown-linux-gnu]
+//!   exclude:
+//!     - toolchain: "1.75"
+//!       target: aarch64-unknown-linux-gnu
+//! ```
+//!
+//! `cross_compile_targets:` adds a `cross build --release` step for each
+//! target after the other steps (see [`crate::pipeline::cross`]):
+//!
+//! ```yaml
+//! cross_compile_targets:
+//!   - aarch64-unknown-linux-gnu
+//!   - x86_64-pc-windows-gnu
+//! ```
+//!
+//! `notifications:` posts the outcome of each job to Slack (see
+//! [`crate::notifications`]):
+//!
+//! ```yaml
+//! notifications:
+//!   slack_webhook_url: https://hooks.slack.com/services/T000/B000/XXXX
+//!   on_success: false
+//! ```
+
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::notifications::NotificationConfig;
+use crate::pipeline::cross::{cross_compile_steps, is_valid_target};
+use crate::pipeline::scan::ScanPolicy;
+use crate::pipeline::{default_steps, validate_script, BuildStep};
+
+/// File name of the pipeline definition in a repository
+pub const PIPELINE_FILE: &str = ".raibid.yaml";
+
+/// Pipeline definition parsed from `.raibid.yaml`
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct PipelineConfig {
+    /// Explicit step sequence (overrides the default steps when present)
+    #[serde(default)]
+    pub steps: Option<Vec<StepDefinition>>,
+
+    /// Maximum number of jobs for this repository running at once
+    #[serde(default)]
+    pub max_concurrent_jobs: Option<u32>,
+
+    /// Timeout for the whole pipeline in seconds
+    #[serde(default)]
+    pub timeout_secs: Option<u64>,
+
+    /// Timeout of each step in seconds; steps of a `raibid.yml` may set their
+    /// own instead
+    #[serde(default)]
+    pub default_step_timeout_secs: Option<u64>,
+
+    /// Container environment for `docker build` steps
+    #[serde(default)]
+    pub docker_build_env: Option<DockerBuildEnv>,
+
+    /// Host environment variables passed through to build steps
+    #[serde(default)]
+    pub env_var_allowlist: Vec<String>,
+
+    /// Compile through sccache (see [`crate::sccache`])
+    #[serde(default)]
+    pub use_sccache: bool,
+
+    /// Minimum line coverage of `coverage` steps in percent
+    #[serde(default)]
+    pub coverage_threshold: Option<u8>,
+
+    /// Add a `miri` step after the other steps (see
+    /// [`crate::pipeline::miri`])
+    #[serde(default)]
+    pub enable_miri: bool,
+
+    /// Fail `miri` steps instead of skipping them when Miri is unavailable
+    #[serde(default)]
+    pub miri_strict: bool,
+
+    /// Fail `outdated` steps instead of warning when any dependency is
+    /// outdated
+    #[serde(default)]
+    pub fail_on_outdated: bool,
+
+    /// Fail `udeps` steps instead of warning when any dependency is unused
+    #[serde(default)]
+    pub fail_on_unused_deps: bool,
+
+    /// Run the steps once per toolchain and target combination; at most
+    /// `max_concurrent_jobs` combinations run at once
+    #[serde(default)]
+    pub matrix: Option<MatrixConfig>,
+
+    /// Scan the image of each `docker_build` step with trivy
+    #[serde(default)]
+    pub scan_policy: Option<ScanPolicy>,
+
+    /// Targets to cross-compile release binaries for with cross-rs
+    #[serde(default)]
+    pub cross_compile_targets: Vec<String>,
+
+    /// Notify Slack when a job finishes
+    #[serde(default)]
+    pub notifications: Option<NotificationConfig>,
+}
+
+/// Container environment for docker builds
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DockerBuildEnv {
+    /// Builder image reference (e.g. `rust:1.75-slim`)
+    pub image: String,
+}
+
+/// Keys of matrix `include` and `exclude` entries
+pub const MATRIX_KEYS: [&str; 2] = ["toolchain", "target"];
+
+/// Toolchains and targets a pipeline is built with
+///
+/// Every toolchain is combined with every target, leaving out combinations
+/// matching an `exclude` entry and adding each `include` entry. An entry
+/// matches the combinations that have all of its keys.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct MatrixConfig {
+    /// Rust toolchains, e.g. `stable` or `1.75`
+    #[serde(default)]
+    pub toolchains: Vec<String>,
+    /// Target triples
+    #[serde(default)]
+    pub targets: Vec<String>,
+    /// Extra combinations, with `toolchain` and `target` keys
+    #[serde(default)]
+    pub include: Vec<BTreeMap<String, String>>,
+    /// Combinations to leave out, with `toolchain` and `target` keys
+    #[serde(default)]
+    pub exclude: Vec<BTreeMap<String, String>>,
+}
+
+impl MatrixConfig {
+    /// Every combination to run, in order
+    pub fn cells(&self) -> Vec<MatrixCell> {
+        let axis = |values: &[String]| -> Vec<Option<String>> {
+            if values.is_empty() {
+                vec![None]
+            } else {
+                values.iter().cloned().map(Some).collect()
+            }
+        };
+
+        let mut cells: Vec<MatrixCell> = axis(&self.toolchains)
+            .into_iter()
+            .flat_map(|toolchain| {
+                axis(&self.targets)
+                    .into_iter()
+                    .map(move |target| MatrixCell {
+                        toolchain: toolchain.clone(),
+                        target,
+                    })
+            })
+            .filter(|cell| !self.exclude.iter().any(|entry| cell.matches(entry)))
+            .collect();
+
+        for entry in &self.include {
+            let cell = MatrixCell {
+                toolchain: entry.get("toolchain").cloned(),
+                target: entry.get("target").cloned(),
+            };
+            if !cells.contains(&cell) {
+                cells.push(cell);
+            }
+        }
+        cells
+    }
+}
+
+/// One toolchain and target combination of a [`MatrixConfig`]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct MatrixCell {
+    /// Toolchain set as `RUSTUP_TOOLCHAIN`; the default toolchain if unset
+    pub toolchain: Option<String>,
+    /// Target passed to cargo with `--target`; the host if unset
+    pub target: Option<String>,
+}
+
+impl MatrixCell {
+    /// Name used in logs and to store the combination's result, e.g.
+    /// `stable/x86_64-unknown-linux-gnu`
+    pub fn name(&self) -> String {
+        format!(
+            "{}/{}",
+            self.toolchain.as_deref().unwrap_or("default"),
+            self.target.as_deref().unwrap_or("host")
+        )
+    }
+
+    /// Whether the combination has every key of a matrix entry
+    fn matches(&self, entry: &BTreeMap<String, String>) -> bool {
+        entry.iter().all(|(key, value)| {
+            let own = match key.as_str() {
+                "toolchain" => &self.toolchain,
+                "target" => &self.target,
+                _ => return false,
+            };
+            own.as_ref() == Some(value)
+        })
+    }
+}
+
+/// A problem found while validating a pipeline definition
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum PipelineConfigError {
+    #[error("steps[{index}]: unknown step '{name}'")]
+    UnknownStep { index: usize, name: String },
+
+    #[error("steps[{index}]: {reason}")]
+    InvalidStep { index: usize, reason: String },
+
+    #[error("max_concurrent_jobs must be greater than 0")]
+    InvalidMaxConcurrentJobs,
+
+    #[error("{field} must be greater than 0")]
+    InvalidTimeout { field: String },
+
+    #[error("docker_build_env.image: invalid image reference '{0}'")]
+    InvalidImage(String),
+
+    #[error("env_var_allowlist[{index}] must not be empty")]
+    EmptyAllowlistEntry { index: usize },
+
+    #[error("coverage_threshold must be at most 100, got {0}")]
+    InvalidCoverageThreshold(u8),
+
+    #[error("matrix.{field}[{index}] must not be empty")]
+    EmptyMatrixValue { field: String, index: usize },
+
+    #[error("matrix.{field}[{index}]: unknown key '{key}', expected 'toolchain' or 'target'")]
+    UnknownMatrixKey {
+        field: String,
+        index: usize,
+        key: String,
+    },
+
+    #[error("matrix excludes every combination")]
+    EmptyMatrix,
+
+    #[error("cross_compile_targets[{index}]: invalid target triple '{target}'")]
+    InvalidCrossTarget { index: usize, target: String },
+
+    #[error("notifications.slack_webhook_url: expected an http(s) URL, got '{0}'")]
+    InvalidWebhookUrl(String),
+}
+
+/// A step entry in the `steps:` list
+///
+/// Either the name of a pre-defined step (`test`, `build-release`, ...) or a
+/// single-key map describing a custom step.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(untagged)]
+pub enum StepDefinition {
+    /// Pre-defined step referenced by name
+    Named(String),
+    /// Custom step
+    Custom(CustomStep),
+}
+
+/// Custom step definition
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum CustomStep {
+    /// Run a shell script
+    Shell(String),
+    /// Run a Makefile target
+    Make(String),
+    /// Run a `just` recipe
+    Just(String),
+    /// Build the repository's Dockerfile into an image with this tag
+    DockerBuild(String),
+}
+
+impl CustomStep {
+    /// Key used for this step in `.raibid.yaml`
+    pub fn kind(&self) -> &str {
+        match self {
+            CustomStep::Shell(_) => "shell",
+            CustomStep::Make(_) => "make",
+            CustomStep::Just(_) => "just",
+            CustomStep::DockerBuild(_) => "docker_build",
+        }
+    }
+
+    /// Script, target, or recipe for this step
+    pub fn argument(&self) -> &str {
+        match self {
+            CustomStep::Shell(arg)
+            | CustomStep::Make(arg)
+            | CustomStep::Just(arg)
+            | CustomStep::DockerBuild(arg) => arg,
+        }
+    }
+}
+
+impl StepDefinition {
+    /// Convert this definition into an executable build step
+    pub fn to_build_step(&self) -> Result<BuildStep> {
+        match self {
+            StepDefinition::Named(name) => {
+                BuildStep::from_name(name).ok_or_else(|| anyhow!("Unknown build step: '{}'", name))
+            }
+            StepDefinition::Custom(CustomStep::Shell(script)) => {
+                validate_script(script)?;
+                Ok(BuildStep::Shell {
+                    script: script.clone(),
+                })
+            }
+            StepDefinition::Custom(CustomStep::Make(target)) => Ok(BuildStep::Make {
+                target: target.clone(),
+            }),
+            StepDefinition::Custom(CustomStep::Just(recipe)) => Ok(BuildStep::Just {
+                recipe: recipe.clone(),
+            }),
+            StepDefinition::Custom(CustomStep::DockerBuild(tag)) => {
+                if !is_valid_image_reference(tag) {
+                    return Err(anyhow!("invalid image reference '{}'", tag));
+                }
+                Ok(BuildStep::DockerBuild {
+                    tag: tag.clone(),
+                    context: ".".to_string(),
+                })
+            }
+        }
+    }
+}
+
+impl PipelineConfig {
+    /// Parse a pipeline definition from YAML
+    pub fn from_yaml(contents: &str) -> Result<Self> {
+        serde_yaml::from_str(contents).context("Failed to parse pipeline definition")
+    }
+
+    /// Load the pipeline definition from a checked-out repository
+    ///
+    /// Returns the default configuration if the repository has no `.raibid.yaml`.
+    pub fn load(repo_dir: &Path) -> Result<Self> {
+        let path = repo_dir.join(PIPELINE_FILE);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let contents = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        Self::from_yaml(&contents).with_context(|| format!("Invalid {}", path.display()))
+    }
+
+    /// Check the definition for errors without running anything
+    ///
+    /// Returns every problem found so they can be reported together.
+    pub fn validate(&self) -> Vec<PipelineConfigError> {
+        let mut errors = Vec::new();
+
+        for (index, step) in self.steps.iter().flatten().enumerate() {
+            match step {
+                StepDefinition::Named(name) if BuildStep::from_name(name).is_none() => {
+                    errors.push(PipelineConfigError::UnknownStep {
+                        index,
+                        name: name.clone(),
+                    });
+                }
+                StepDefinition::Named(_) => {}
+                StepDefinition::Custom(custom) => {
+                    if let Err(e) = step.to_build_step() {
+                        errors.push(PipelineConfigError::InvalidStep {
+                            index,
+                            reason: e.to_string(),
+                        });
+                    } else if custom.argument().trim().is_empty() {
+                        errors.push(PipelineConfigError::InvalidStep {
+                            index,
+                            reason: format!("'{}' requires a non-empty value", custom.kind()),
+                        });
+                    }
+                }
+            }
+        }
+
+        if self.max_concurrent_jobs == Some(0) {
+            errors.push(PipelineConfigError::InvalidMaxConcurrentJobs);
+        }
+
+        for (field, timeout) in [
+            ("timeout_secs", self.timeout_secs),
+            ("default_step_timeout_secs", self.default_step_timeout_secs),
+        ] {
+            if timeout == Some(0) {
+                errors.push(PipelineConfigError::InvalidTimeout {
+                    field: field.to_string(),
+                });
+            }
+        }
+
+        if let Some(ref env) = self.docker_build_env {
+            if !is_valid_image_reference(&env.image) {
+                errors.push(PipelineConfigError::InvalidImage(env.image.clone()));
+            }
+        }
+
+        for (index, var) in self.env_var_allowlist.iter().enumerate() {
+            if var.trim().is_empty() {
+                errors.push(PipelineConfigError::EmptyAllowlistEntry { index });
+            }
+        }
+
+        if let Some(threshold) = self.coverage_threshold.filter(|t| *t > 100) {
+            errors.push(PipelineConfigError::InvalidCoverageThreshold(threshold));
+        }
+
+        if let Some(ref matrix) = self.matrix {
+            errors.extend(validate_matrix(matrix));
+        }
+
+        for (index, target) in self.cross_compile_targets.iter().enumerate() {
+            if !is_valid_target(target) {
+                errors.push(PipelineConfigError::InvalidCrossTarget {
+                    index,
+                    target: target.clone(),
+                });
+            }
+        }
+
+        if let Some(url) = self
+            .notifications
+            .as_ref()
+            .and_then(|n| n.slack_webhook_url.as_ref())
+            .filter(|url| !url.starts_with("https://") && !url.starts_with("http://"))
+        {
+            errors.push(PipelineConfigError::InvalidWebhookUrl(url.clone()));
+        }
+
+        errors
+    }
+
+    /// Resolve the step sequence to execute
+    ///
+    /// `coverage` steps get the configured `coverage_threshold`, `miri`
+    /// steps `miri_strict`, `outdated` steps `fail_on_outdated` and `udeps`
+    /// steps `fail_on_unused_deps`. With `enable_miri` a `miri` step is
+    /// appended unless one is listed already, and with a `scan_policy` every
+    /// `docker_build` step is followed by a `security-scan` of its image. A
+    /// `cross:<target>` step is appended for each of the
+    /// `cross_compile_targets`.
+    pub fn resolve_steps(&self) -> Result<Vec<BuildStep>> {
+        let mut steps = match &self.steps {
+            Some(steps) => steps
+                .iter()
+                .map(StepDefinition::to_build_step)
+                .collect::<Result<Vec<_>>>()?,
+            None => default_steps(),
+        };
+
+        for step in &mut steps {
+            if let BuildStep::Coverage { min_percent } = step {
+                *min_percent = self.coverage_threshold;
+            }
+            if let BuildStep::Miri { strict } = step {
+                *strict = self.miri_strict;
+            }
+            if let BuildStep::Outdated { fail_on_outdated } = step {
+                *fail_on_outdated = self.fail_on_outdated;
+            }
+            if let BuildStep::Udeps {
+                fail_on_unused_deps,
+            } = step
+            {
+                *fail_on_unused_deps = self.fail_on_unused_deps;
+            }
+        }
+        if self.enable_miri && !steps.iter().any(|s| matches!(s, BuildStep::Miri { .. })) {
+            steps.push(BuildStep::Miri {
+                strict: self.miri_strict,
+            });
+        }
+        if let Some(ref policy) = self.scan_policy {
+            steps = steps
+                .into_iter()
+                .flat_map(|step| {
+                    let scan = match step {
+                        BuildStep::DockerBuild { ref tag, .. } => Some(BuildStep::SecurityScan {
+                            image: tag.clone(),
+                            policy: policy.clone(),
+                        }),
+                        _ => None,
+                    };
+                    std::iter::once(step).chain(scan)
+                })
+                .collect();
+        }
+        steps.extend(cross_compile_steps(&self.cross_compile_targets));
+        Ok(steps)
+    }
+
+    /// Timeout of each step, if any
+    pub fn default_step_timeout(&self) -> Option<Duration> {
+        self.default_step_timeout_secs.map(Duration::from_secs)
+    }
+}
+
+/// Check a build matrix for empty values and unknown keys
+fn validate_matrix(matrix: &MatrixConfig) -> Vec<PipelineConfigError> {
+    let mut errors = Vec::new();
+
+    for (field, values) in [
+        ("toolchains", &matrix.toolchains),
+        ("targets", &matrix.targets),
+    ] {
+        for (index, value) in values.iter().enumerate() {
+            if value.trim().is_empty() {
+                errors.push(PipelineConfigError::EmptyMatrixValue {
+                    field: field.to_string(),
+                    index,
+                });
+            }
+        }
+    }
+
+    for (field, entries) in [("include", &matrix.include), ("exclude", &matrix.exclude)] {
+        for (index, entry) in entries.iter().enumerate() {
+            for key in entry
+                .keys()
+                .filter(|key| !MATRIX_KEYS.contains(&key.as_str()))
+            {
+                errors.push(PipelineConfigError::UnknownMatrixKey {
+                    field: field.to_string(),
+                    index,
+                    key: key.clone(),
+                });
+            }
+        }
+    }
+
+    if matrix.cells().is_empty() {
+        errors.push(PipelineConfigError::EmptyMatrix);
+    }
+    errors
+}
+
+/// Check an OCI image reference: `[registry[:port]/]name[/name...][:tag][@digest]`
+fn is_valid_image_reference(image: &str) -> bool {
+    let (rest, digest) = match image.split_once('@') {
+        Some((rest, digest)) => (rest, Some(digest)),
+        None => (image, None),
+    };
+
+    if let Some(digest) = digest {
+        let valid_digest = matches!(
+            digest.split_once(':'),
+            Some((algo, hex)) if !algo.is_empty()
+                && hex.len() >= 32
+                && hex.chars().all(|c| c.is_ascii_hexdigit())
+        );
+        if !valid_digest {
+            return false;
+        }
+    }
+
+    // A tag is a ':' after the last '/' (a ':' before it is a registry port)
+    let last_slash = rest.rfind('/').map(|i| i + 1).unwrap_or(0);
+    let (name, tag) = match rest[last_slash..].find(':') {
+        Some(i) => (&rest[..last_slash + i], Some(&rest[last_slash + i + 1..])),
+        None => (rest, None),
+    };
+
+    if let Some(tag) = tag {
+        let valid_tag = !tag.is_empty()
+            && tag.len() <= 128
+            && !tag.starts_with(['.', '-'])
+            && tag
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '-'));
+        if !valid_tag {
+            return false;
+        }
+    }
+
+    let mut components: Vec<&str> = name.split('/').collect();
+    // The first component is a registry host if it looks like one
+    if components.len() > 1 && (components[0].contains(['.', ':']) || components[0] == "localhost")
+    {
+        components.remove(0);
+    }
+
+    !components.is_empty()
+        && components.iter().all(|component| {
+            !component.is_empty()
+                && component.split(['.', '_', '-']).all(|part| {
+                    !part.is_empty()
+                        && part
+                            .chars()
+                            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit())
+                })
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_pipeline_uses_default_steps() {
+        let config = PipelineConfig::default();
+        assert_eq!(config.resolve_steps().unwrap(), default_steps());
+    }
+
+    #[test]
+    fn test_steps_override_mixed() {
+        let yaml = r#"
+steps:
+  - fmt
+  - shell: ./scripts/generate.sh
+  - make: release
+  - just: integration
+  - test
+"#;
+        let config = PipelineConfig::from_yaml(yaml).unwrap();
+        let steps = config.resolve_steps().unwrap();
+
+        assert_eq!(
+            steps,
+            vec![
+                BuildStep::Fmt,
+                BuildStep::Shell {
+                    script: "./scripts/generate.sh".to_string()
+                },
+                BuildStep::Make {
+                    target: "release".to_string()
+                },
+                BuildStep::Just {
+                    recipe: "integration".to_string()
+                },
+                BuildStep::Test,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unknown_step_name() {
+        let config = PipelineConfig::from_yaml("steps:\n  - deploy\n").unwrap();
+        assert!(config.resolve_steps().is_err());
+    }
+
+    #[test]
+    fn test_unsafe_shell_step_rejected() {
+        let config = PipelineConfig::from_yaml("steps:\n  - shell: rm -rf /\n").unwrap();
+        assert!(config.resolve_steps().is_err());
+    }
+
+    #[test]
+    fn test_validate_valid_config() {
+        let yaml = r#"
+steps:
+  - clippy
+  - make: release
+max_concurrent_jobs: 2
+timeout_secs: 1800
+docker_build_env:
+  image: ghcr.io/raibid-labs/rust-builder:1.75
+env_var_allowlist:
+  - RUST_LOG
+use_sccache: true
+"#;
+        let config = PipelineConfig::from_yaml(yaml).unwrap();
+        assert!(config.validate().is_empty());
+        assert!(config.use_sccache);
+    }
+
+    #[test]
+    fn test_validate_reports_all_errors() {
+        let yaml = r#"
+steps:
+  - deploy
+  - make: ""
+max_concurrent_jobs: 0
+timeout_secs: 0
+default_step_timeout_secs: 0
+docker_build_env:
+  image: "Not A Valid/Image"
+env_var_allowlist:
+  - ""
+coverage_threshold: 120
+notifications:
+  slack_webhook_url: hooks.slack.com/services/T000
+"#;
+        let config = PipelineConfig::from_yaml(yaml).unwrap();
+        let errors = config.validate();
+
+        assert_eq!(errors.len(), 9);
+        assert_eq!(
+            errors[0],
+            PipelineConfigError::UnknownStep {
+                index: 0,
+                name: "deploy".to_string()
+            }
+        );
+        assert!(matches!(
+            errors[1],
+            PipelineConfigError::InvalidStep { index: 1, .. }
+        ));
+        assert!(errors.contains(&PipelineConfigError::InvalidMaxConcurrentJobs));
+        assert!(errors.contains(&PipelineConfigError::InvalidTimeout {
+            field: "default_step_timeout_secs".to_string()
+        }));
+        assert!(errors.contains(&PipelineConfigError::EmptyAllowlistEntry { index: 0 }));
+        assert!(errors.contains(&PipelineConfigError::InvalidCoverageThreshold(120)));
+        assert!(errors.contains(&PipelineConfigError::InvalidWebhookUrl(
+            "hooks.slack.com/services/T000".to_string()
+        )));
+    }
+
+    #[test]
+    fn test_coverage_threshold_applied() {
+        let config =
+            PipelineConfig::from_yaml("steps:\n  - test\n  - coverage\ncoverage_threshold: 75\n")
+                .unwrap();
+        assert!(config.validate().is_empty());
+        assert_eq!(
+            config.resolve_steps().unwrap(),
+            vec![
+                BuildStep::Test,
+                BuildStep::Coverage {
+                    min_percent: Some(75)
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn test_enable_miri() {
+        let config = PipelineConfig::default();
+        assert!(!config.enable_miri);
+        assert!(!config
+            .resolve_steps()
+            .unwrap()
+            .contains(&BuildStep::Miri { strict: false }));
+
+        let config = PipelineConfig::from_yaml("steps:\n  - test\nenable_miri: true\n").unwrap();
+        assert_eq!(
+            config.resolve_steps().unwrap(),
+            vec![BuildStep::Test, BuildStep::Miri { strict: false }]
+        );
+
+        let config =
+            PipelineConfig::from_yaml("steps:\n  - miri\n  - test\nmiri_strict: true\n").unwrap();
+        assert_eq!(
+            config.resolve_steps().unwrap(),
+            vec![BuildStep::Miri { strict: true }, BuildStep::Test]
+        );
+    }
+
+    #[test]
+    fn test_fail_on_outdated_applied() {
+        let config = PipelineConfig::from_yaml("steps:\n  - outdated\n").unwrap();
+        assert_eq!(
+            config.resolve_steps().unwrap(),
+            vec![BuildStep::Outdated {
+                fail_on_outdated: false
+            }]
+        );
+
+        let config =
+            PipelineConfig::from_yaml("steps:\n  - outdated\nfail_on_outdated: true\n").unwrap();
+        assert_eq!(
+            config.resolve_steps().unwrap(),
+            vec![BuildStep::Outdated {
+                fail_on_outdated: true
+            }]
+        );
+    }
+
+    #[test]
+    fn test_fail_on_unused_deps_applied() {
+        let config =
+            PipelineConfig::from_yaml("steps:\n  - udeps\nfail_on_unused_deps: true\n").unwrap();
+        assert_eq!(
+            config.resolve_steps().unwrap(),
+            vec![BuildStep::Udeps {
+                fail_on_unused_deps: true
+            }]
+        );
+    }
+
+    #[test]
+    fn test_scan_policy_adds_security_scan() {
+        let docker_build = BuildStep::DockerBuild {
+            tag: "registry.local/app:1.0".to_string(),
+            context: ".".to_string(),
+        };
+
+        let yaml = "steps:\n  - docker_build: registry.local/app:1.0\n  - test\n";
+        let config = PipelineConfig::from_yaml(yaml).unwrap();
+        assert_eq!(
+            config.resolve_steps().unwrap(),
+            vec![docker_build.clone(), BuildStep::Test]
+        );
+
+        let yaml = format!(
+            "{}scan_policy:\n  min_severity_to_fail: critical\n  ignore_unfixed: true\n",
+            yaml
+        );
+        let config = PipelineConfig::from_yaml(&yaml).unwrap();
+        assert_eq!(
+            config.resolve_steps().unwrap(),
+            vec![
+                docker_build,
+                BuildStep::SecurityScan {
+                    image: "registry.local/app:1.0".to_string(),
+                    policy: ScanPolicy {
+                        min_severity_to_fail: raibid_common::scan::Severity::Critical,
+                        ignore_unfixed: true,
+                        continue_on_failure: false,
+                    },
+                },
+                BuildStep::Test,
+            ]
+        );
+
+        let config = PipelineConfig::from_yaml("steps:\n  - docker_build: App:Latest\n").unwrap();
+        assert!(matches!(
+            config.validate().as_slice(),
+            [PipelineConfigError::InvalidStep { index: 0, .. }]
+        ));
+    }
+
+    #[test]
+    fn test_cross_compile_targets() {
+        let yaml = r#"
+steps:
+  - test
+cross_compile_targets:
+  - aarch64-unknown-linux-gnu
+  - x86_64-pc-windows-gnu
+"#;
+        let config = PipelineConfig::from_yaml(yaml).unwrap();
+        assert!(config.validate().is_empty());
+        assert_eq!(
+            config.resolve_steps().unwrap(),
+            vec![
+                BuildStep::Test,
+                BuildStep::CrossCompile {
+                    target: "aarch64-unknown-linux-gnu".to_string()
+                },
+                BuildStep::CrossCompile {
+                    target: "x86_64-pc-windows-gnu".to_string()
+                },
+            ]
+        );
+
+        let config = PipelineConfig::from_yaml("cross_compile_targets: [\"\"]\n").unwrap();
+        assert_eq!(
+            config.validate(),
+            vec![PipelineConfigError::InvalidCrossTarget {
+                index: 0,
+                target: String::new()
+            }]
+        );
+    }
+
+    #[test]
+    fn test_matrix_cells() {
+        let yaml = r#"
+matrix:
+  toolchains: [stable, "1.75"]
+  targets: [x86_64-unknown-linux-gnu, aarch64-unknown-linux-gnu]
+  exclude:
+    - toolchain: "1.75"
+      target: aarch64-unknown-linux-gnu
+  include:
+    - toolchain: nightly
+"#;
+        let config = PipelineConfig::from_yaml(yaml).unwrap();
+        assert!(config.validate().is_empty());
+
+        let names: Vec<String> = config
+            .matrix
+            .unwrap()
+            .cells()
+            .iter()
+            .map(MatrixCell::name)
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                "stable/x86_64-unknown-linux-gnu",
+                "stable/aarch64-unknown-linux-gnu",
+                "1.75/x86_64-unknown-linux-gnu",
+                "nightly/host",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validate_matrix() {
+        let yaml = r#"
+matrix:
+  toolchains: [stable, ""]
+  exclude:
+    - toolchain: stable
+    - os: windows
+"#;
+        let errors = PipelineConfig::from_yaml(yaml).unwrap().validate();
+        assert_eq!(
+            errors,
+            vec![
+                PipelineConfigError::EmptyMatrixValue {
+                    field: "toolchains".to_string(),
+                    index: 1
+                },
+                PipelineConfigError::UnknownMatrixKey {
+                    field: "exclude".to_string(),
+                    index: 1,
+                    key: "os".to_string()
+                },
+            ]
+        );
+
+        let config =
+            PipelineConfig::from_yaml("matrix:\n  toolchains: [stable]\n  exclude:\n    - {}\n")
+                .unwrap();
+        assert_eq!(config.validate(), vec![PipelineConfigError::EmptyMatrix]);
+    }
+
+    #[test]
+    fn test_image_reference_validation() {
+        assert!(is_valid_image_reference("rust"));
+        assert!(is_valid_image_reference("rust:1.75-slim"));
+        assert!(is_valid_image_reference(
+            "localhost:5000/raibid/builder:latest"
+        ));
+        assert!(is_valid_image_reference("gitea.local/raibid/builder"));
+        assert!(!is_valid_image_reference("Rust:latest"));
+        assert!(!is_valid_image_reference("rust:"));
+        assert!(!is_valid_image_reference("rust@sha256:xyz"));
+        assert!(!is_valid_image_reference(""));
+    }
+
+    #[test]
+    fn test_load_missing_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = PipelineConfig::load(dir.path()).unwrap();
+        assert!(config.steps.is_none());
+    }
+}
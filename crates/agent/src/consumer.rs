@@ -0,0 +1,1581 @@
+//! Job stream consumer
+//!
+//! Agents read jobs from the Redis job streams through a shared consumer
+//! group, so each job is delivered to exactly one agent. Every
+//! [`JobPriority`] has its own stream; the consumer only takes a job from a
+//! lower-priority stream when all higher-priority streams are empty.
+//!
+//! A delivered message stays in the group's pending entry list (PEL) until
+//! the agent acknowledges it; if the agent crashes mid-job the message would
+//! sit there forever. The consumer therefore periodically claims messages
+//! that have been pending for longer than the agent's
+//! `stale_message_threshold_ms` with `XAUTOCLAIM` and processes them itself,
+//! counting every earlier delivery, as recorded in the PEL, as a retry.
+//! While a job runs, or waits for a free slot after being delivered, its
+//! message is claimed again every [`KEEP_ALIVE_INTERVAL`] (or a third of the
+//! threshold, if shorter) to reset its idle time, so long builds and queued
+//! jobs are not mistaken for stale ones.
+//!
+//! Jobs may require capabilities (e.g. `gpu`) not every agent has. A
+//! consumer handing back a job it cannot run acknowledges and deletes its
+//! message and queues it again at the end of the same stream, leaving it for
+//! another agent. A job handed back [`MAX_HAND_BACKS`] times is moved to the
+//! dead letter stream and marked failed instead, so a job no agent can run
+//! does not go round forever.
+//!
+//! [`run_jobs`] drives a [`JobQueue`], running up to `max_concurrent_jobs`
+//! jobs at once and acknowledging each when its [`JobHandler`] is done.
+
+use anyhow::{anyhow, bail, Context, Result};
+use async_trait::async_trait;
+use chrono::Utc;
+use redis::aio::{ConnectionLike, MultiplexedConnection};
+use redis::streams::{StreamReadOptions, StreamReadReply};
+use redis::{AsyncCommands, Value};
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, Semaphore};
+use tokio::time::MissedTickBehavior;
+use tracing::{debug, info, warn, Instrument, Span};
+
+use crate::AgentConfig;
+use raibid_common::jobs::{
+    dead_letter_stream, job_key, DeadJob, JobPriority, JobStatus, QueuedJob, JOB_FIELD_STATUS,
+    STREAM_FIELD_HAND_BACKS,
+};
+
+/// Maximum number of messages claimed per `XAUTOCLAIM` call
+const AUTOCLAIM_BATCH_SIZE: usize = 10;
+
+/// How long `XREADGROUP` blocks waiting for a new job
+const READ_BLOCK: Duration = Duration::from_secs(5);
+
+/// Delay before polling again after a failed read
+const READ_ERROR_DELAY: Duration = Duration::from_secs(5);
+
+/// Longest interval between resets of the idle time of running jobs' messages
+pub const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Delay before polling again when only jobs this agent cannot run were read,
+/// giving agents that can run them a chance to
+const HAND_BACK_DELAY: Duration = Duration::from_secs(1);
+
+/// Times a job is handed back by agents lacking its required capabilities
+/// before it is dead-lettered
+///
+/// An agent with nothing else to read hands a job back about twice a second,
+/// so this gives a capable agent several minutes to free a slot.
+pub const MAX_HAND_BACKS: u32 = 1000;
+
+/// A job read from a job stream, with what is needed to acknowledge it
+#[derive(Debug, Clone)]
+pub struct ClaimedJob {
+    /// Stream the job was read from
+    pub stream: String,
+    /// Stream message ID
+    pub message_id: String,
+    /// The queued job
+    pub job: QueuedJob,
+}
+
+/// What to do with a job's stream message once the job has been handled
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JobOutcome {
+    /// The build passed; acknowledge the message
+    Succeeded,
+    /// The build ran and failed; acknowledge the message
+    Failed,
+    /// The job was cancelled while it ran; acknowledge the message
+    Cancelled,
+    /// The job could not run; leave the message pending so it is reclaimed
+    /// by orphan recovery and retried
+    Retry,
+}
+
+/// A source of jobs that can acknowledge them once handled
+#[async_trait]
+pub trait JobQueue: Send {
+    /// Wait for the next job, returning `None` if none arrived in time
+    async fn next_job(&mut self) -> Result<Option<ClaimedJob>>;
+
+    /// Acknowledge a handled job's message
+    async fn ack(&mut self, job: &ClaimedJob) -> Result<()>;
+
+    /// Reset the idle time of running jobs' messages, and of those of jobs
+    /// delivered but not yet returned by [`JobQueue::next_job`], so they are
+    /// not reclaimed as stale
+    async fn keep_alive(&mut self, jobs: &[ClaimedJob]) -> Result<()>;
+
+    /// How often [`JobQueue::keep_alive`] is called while jobs run
+    fn keep_alive_interval(&self) -> Duration {
+        KEEP_ALIVE_INTERVAL
+    }
+}
+
+/// Runs claimed jobs
+#[async_trait]
+pub trait JobHandler: Send + Sync + 'static {
+    /// Run a job to completion
+    async fn run(&self, job: &ClaimedJob) -> JobOutcome;
+}
+
+/// Reads jobs from the job streams as a member of the worker consumer group
+pub struct JobConsumer<C = MultiplexedConnection> {
+    conn: C,
+    /// Job streams, highest priority first
+    streams: Vec<String>,
+    group: String,
+    consumer_id: String,
+    /// Capabilities of the agent; jobs requiring others are handed back
+    capabilities: Vec<String>,
+    /// Where jobs handed back too often are moved
+    dead_letter_stream: String,
+    dead_letter_maxlen: usize,
+    recovery_interval: Duration,
+    /// Idle time in milliseconds after which a pending message is reclaimed
+    stale_after_ms: u64,
+    last_recovery: Option<Instant>,
+    /// Jobs already delivered to this consumer, returned before new reads
+    buffered: VecDeque<ClaimedJob>,
+    /// Whether a job was handed back since the last call to `next_job`
+    handed_back: bool,
+}
+
+impl JobConsumer {
+    /// Connect to Redis and join the worker consumer group on every job
+    /// stream, creating it if needed
+    pub async fn connect(config: &AgentConfig) -> Result<Self> {
+        let client = redis::Client::open(config.redis_url.as_str())
+            .with_context(|| format!("Invalid Redis URL '{}'", config.redis_url))?;
+        let conn = client
+            .get_multiplexed_tokio_connection()
+            .await
+            .context("Failed to connect to Redis")?;
+
+        let mut consumer = Self {
+            conn,
+            streams: priority_streams(&config.job_stream),
+            group: config.consumer_group.clone(),
+            consumer_id: config.agent_id.clone(),
+            capabilities: config.capabilities.clone(),
+            dead_letter_stream: dead_letter_stream(&config.job_stream),
+            dead_letter_maxlen: config.dead_letter_maxlen,
+            recovery_interval: Duration::from_secs(config.orphan_recovery_interval_secs),
+            stale_after_ms: config.stale_message_threshold_ms,
+            last_recovery: None,
+            buffered: VecDeque::new(),
+            handed_back: false,
+        };
+        for stream in consumer.streams.clone() {
+            consumer.ensure_group(&stream).await?;
+        }
+
+        Ok(consumer)
+    }
+
+    /// Get a handle to the Redis connection, shared with this consumer
+    pub fn connection(&self) -> MultiplexedConnection {
+        self.conn.clone()
+    }
+}
+
+impl<C: ConnectionLike + Send> JobConsumer<C> {
+    /// Get the consumer name this agent reads as
+    pub fn consumer_id(&self) -> &str {
+        &self.consumer_id
+    }
+
+    /// Create the consumer group (and stream) if they don't exist yet
+    async fn ensure_group(&mut self, stream: &str) -> Result<()> {
+        let created: redis::RedisResult<()> = self
+            .conn
+            .xgroup_create_mkstream(stream, &self.group, "$")
+            .await;
+
+        match created {
+            Ok(()) => {
+                info!("Created consumer group '{}' on '{}'", self.group, stream);
+                Ok(())
+            }
+            Err(e) if e.code() == Some("BUSYGROUP") => Ok(()),
+            Err(e) => {
+                Err(e).with_context(|| format!("Failed to create consumer group on '{}'", stream))
+            }
+        }
+    }
+
+    /// Claim jobs orphaned by crashed agents and queue them for processing
+    ///
+    /// Runs `XAUTOCLAIM` on each job stream, highest priority first, for
+    /// messages pending longer than `stale_after_ms`. Claimed jobs are
+    /// returned by [`JobConsumer::next_job`] before any new ones, with every
+    /// delivery before this one added to their retry count. Returns the
+    /// number of jobs reclaimed.
+    pub async fn reclaim_stale_messages(&mut self, stale_after_ms: u64) -> Result<u32> {
+        let mut reclaimed = 0;
+        for stream in self.streams.clone() {
+            reclaimed += self.reclaim_stream(&stream, stale_after_ms).await?;
+        }
+
+        self.last_recovery = Some(Instant::now());
+        if reclaimed > 0 {
+            info!("Reclaimed {} stale job messages", reclaimed);
+        }
+        Ok(reclaimed)
+    }
+
+    /// Claim stale jobs from one stream, following the `XAUTOCLAIM` cursor
+    /// until the whole PEL has been scanned
+    async fn reclaim_stream(&mut self, stream: &str, stale_after_ms: u64) -> Result<u32> {
+        let mut cursor = "0-0".to_string();
+        let mut reclaimed = 0;
+
+        loop {
+            let (next_cursor, entries) = autoclaim(
+                &mut self.conn,
+                stream,
+                &self.group,
+                &self.consumer_id,
+                stale_after_ms,
+                &cursor,
+            )
+            .await?;
+
+            for (message_id, fields) in entries {
+                let Some(fields) = fields else {
+                    // Trimmed from the stream while pending; nothing left to run
+                    debug!("Orphaned message {} no longer exists", message_id);
+                    self.ack_message(stream, &message_id).await?;
+                    continue;
+                };
+
+                let deliveries =
+                    delivery_count(&mut self.conn, stream, &self.group, &message_id).await?;
+                match reclaimed_job(&fields, deliveries) {
+                    Ok(job) if !can_run(&self.capabilities, &job) => {
+                        self.hand_back(stream, &message_id, &job, &fields).await?;
+                    }
+                    Ok(job) => {
+                        info!(
+                            "Reclaimed job {} ({}), retry {}",
+                            job.id, message_id, job.retry_count
+                        );
+                        self.buffered.push_back(ClaimedJob {
+                            stream: stream.to_string(),
+                            message_id,
+                            job,
+                        });
+                        reclaimed += 1;
+                    }
+                    Err(e) => {
+                        warn!("Dropping malformed job message {}: {:#}", message_id, e);
+                        self.ack_message(stream, &message_id).await?;
+                    }
+                }
+            }
+
+            if next_cursor == "0-0" {
+                break;
+            }
+            cursor = next_cursor;
+        }
+
+        Ok(reclaimed)
+    }
+
+    /// Wait for the next job
+    ///
+    /// Jobs requiring capabilities the agent lacks are handed back to the
+    /// stream instead of returned. Recovered orphans are returned first. Runs
+    /// orphan recovery when the recovery interval has elapsed.
+    ///
+    /// The streams are polled in strict priority order without blocking, so
+    /// a normal job is only taken when no high-priority job is waiting, and a
+    /// low job only when neither is. If all are empty, blocks on all streams
+    /// at once; should that deliver several jobs, the highest-priority one is
+    /// returned and the rest are kept for the following calls. Returns `None`
+    /// if no job arrived within the read timeout.
+    pub async fn next_job(&mut self) -> Result<Option<ClaimedJob>> {
+        if self.recovery_due() {
+            if let Err(e) = self.reclaim_stale_messages(self.stale_after_ms).await {
+                warn!("Reclaiming stale job messages failed: {:#}", e);
+            }
+        }
+
+        if let Some(job) = self.buffered.pop_front() {
+            return Ok(Some(job));
+        }
+
+        for stream in self.streams.clone() {
+            let mut jobs = self.read_group(&[stream], None).await?;
+            if !jobs.is_empty() {
+                return Ok(Some(jobs.remove(0)));
+            }
+        }
+
+        let streams = self.streams.clone();
+        let mut jobs = self
+            .read_group(&streams, Some(READ_BLOCK))
+            .await?
+            .into_iter();
+        let next = jobs.next();
+        self.buffered.extend(jobs);
+
+        if next.is_none() && std::mem::take(&mut self.handed_back) {
+            tokio::time::sleep(HAND_BACK_DELAY).await;
+        }
+        Ok(next)
+    }
+
+    /// Read at most one new job from each of `streams`, in the order given
+    ///
+    /// Malformed messages are acknowledged and dropped, and jobs the agent
+    /// cannot run are handed back.
+    async fn read_group(
+        &mut self,
+        streams: &[String],
+        block: Option<Duration>,
+    ) -> Result<Vec<ClaimedJob>> {
+        let mut options = StreamReadOptions::default()
+            .group(&self.group, &self.consumer_id)
+            .count(1);
+        if let Some(block) = block {
+            options = options.block(block.as_millis() as usize);
+        }
+
+        let ids = vec![">"; streams.len()];
+        let reply: Option<StreamReadReply> = self
+            .conn
+            .xread_options(streams, &ids, &options)
+            .await
+            .context("Failed to read from job stream")?;
+
+        // Redis does not promise to reply with the streams in request order
+        let mut keys = reply.map(|r| r.keys).unwrap_or_default();
+        keys.sort_by_key(|k| streams.iter().position(|s| *s == k.key));
+
+        let mut jobs = Vec::new();
+        for key in keys {
+            for entry in key.ids {
+                let job = stream_fields(&entry.map).and_then(|fields| {
+                    QueuedJob::from_stream_fields(&fields).map(|job| (job, fields))
+                });
+                match job {
+                    Ok((job, fields)) if !can_run(&self.capabilities, &job) => {
+                        self.hand_back(&key.key, &entry.id, &job, &fields).await?;
+                    }
+                    Ok((job, _)) => jobs.push(ClaimedJob {
+                        stream: key.key.clone(),
+                        message_id: entry.id,
+                        job,
+                    }),
+                    Err(e) => {
+                        warn!("Dropping malformed job message {}: {:#}", entry.id, e);
+                        self.ack_message(&key.key, &entry.id).await?;
+                    }
+                }
+            }
+        }
+
+        Ok(jobs)
+    }
+
+    /// Acknowledge a job, removing its message from the pending entry list
+    pub async fn ack(&mut self, job: &ClaimedJob) -> Result<()> {
+        self.ack_message(&job.stream, &job.message_id).await
+    }
+
+    /// Claim the messages of running jobs and of buffered ones again,
+    /// resetting their idle time
+    pub async fn keep_alive(&mut self, jobs: &[ClaimedJob]) -> Result<()> {
+        for stream in &self.streams {
+            let ids: Vec<&str> = jobs
+                .iter()
+                .chain(&self.buffered)
+                .filter(|job| job.stream == *stream)
+                .map(|job| job.message_id.as_str())
+                .collect();
+            if !ids.is_empty() {
+                touch_messages(&mut self.conn, stream, &self.group, &self.consumer_id, &ids)
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn ack_message(&mut self, stream: &str, message_id: &str) -> Result<()> {
+        let _: usize = self
+            .conn
+            .xack(stream, &self.group, &[message_id])
+            .await
+            .with_context(|| format!("Failed to acknowledge message {}", message_id))?;
+        Ok(())
+    }
+
+    /// Leave a job this agent cannot run for another agent
+    ///
+    /// Acknowledges and deletes the message and adds its fields again at the
+    /// end of `stream`, counting the hand-back, in one transaction, so the job
+    /// is delivered anew to the group without being lost in between. A job
+    /// already handed back [`MAX_HAND_BACKS`] times is dead-lettered instead.
+    async fn hand_back(
+        &mut self,
+        stream: &str,
+        message_id: &str,
+        job: &QueuedJob,
+        fields: &HashMap<String, String>,
+    ) -> Result<()> {
+        let hand_backs = hand_backs(fields);
+        if hand_backs >= MAX_HAND_BACKS {
+            return self.dead_letter(stream, message_id, job).await;
+        }
+
+        debug!(
+            "Handing back job {} ({}), missing capabilities: {}",
+            job.id,
+            message_id,
+            job.trigger
+                .missing_capabilities(&self.capabilities)
+                .join(", ")
+        );
+
+        let mut fields = fields.clone();
+        fields.insert(
+            STREAM_FIELD_HAND_BACKS.to_string(),
+            (hand_backs + 1).to_string(),
+        );
+        let fields: Vec<(&String, &String)> = fields.iter().collect();
+        let mut pipe = redis::pipe();
+        pipe.atomic()
+            .cmd("XACK")
+            .arg(stream)
+            .arg(&self.group)
+            .arg(message_id)
+            .ignore()
+            .cmd("XDEL")
+            .arg(stream)
+            .arg(message_id)
+            .ignore()
+            .cmd("XADD")
+            .arg(stream)
+            .arg("*")
+            .arg(&fields)
+            .ignore();
+        pipe.query_async::<_, ()>(&mut self.conn)
+            .await
+            .with_context(|| format!("Failed to hand back job {}", job.id))?;
+
+        self.handed_back = true;
+        Ok(())
+    }
+
+    /// Move a job no agent took to the dead letter stream and mark it failed,
+    /// removing its message in the same transaction
+    async fn dead_letter(&mut self, stream: &str, message_id: &str, job: &QueuedJob) -> Result<()> {
+        let reason = format!(
+            "No agent with capabilities {} took the job in {} hand-backs",
+            job.trigger.required_capabilities.join(", "),
+            MAX_HAND_BACKS
+        );
+        let fields = DeadJob::new(job, reason, Utc::now()).to_stream_fields()?;
+
+        let mut pipe = redis::pipe();
+        pipe.atomic()
+            .cmd("XACK")
+            .arg(stream)
+            .arg(&self.group)
+            .arg(message_id)
+            .ignore()
+            .cmd("XDEL")
+            .arg(stream)
+            .arg(message_id)
+            .ignore()
+            .cmd("XADD")
+            .arg(&self.dead_letter_stream)
+            .arg("MAXLEN")
+            .arg("~")
+            .arg(self.dead_letter_maxlen)
+            .arg("*")
+            .arg(&fields)
+            .ignore()
+            .cmd("HSET")
+            .arg(job_key(&job.id))
+            .arg(JOB_FIELD_STATUS)
+            .arg(JobStatus::Failed.as_str())
+            .ignore();
+        pipe.query_async::<_, ()>(&mut self.conn)
+            .await
+            .with_context(|| format!("Failed to dead-letter job {}", job.id))?;
+
+        warn!(
+            "No agent took job {} in {} hand-backs, moved to dead letters",
+            job.id, MAX_HAND_BACKS
+        );
+        Ok(())
+    }
+
+    fn recovery_due(&self) -> bool {
+        match self.last_recovery {
+            Some(last) => last.elapsed() >= self.recovery_interval,
+            None => true,
+        }
+    }
+
+    /// Process jobs with `handler` until `shutdown` resolves
+    ///
+    /// See [`run_jobs`].
+    pub async fn run<H, F>(
+        &mut self,
+        handler: Arc<H>,
+        max_concurrent_jobs: usize,
+        shutdown: F,
+    ) -> Result<()>
+    where
+        H: JobHandler,
+        F: Future<Output = ()>,
+    {
+        run_jobs(self, handler, max_concurrent_jobs, shutdown).await
+    }
+}
+
+#[async_trait]
+impl<C: ConnectionLike + Send> JobQueue for JobConsumer<C> {
+    async fn next_job(&mut self) -> Result<Option<ClaimedJob>> {
+        JobConsumer::next_job(self).await
+    }
+
+    async fn ack(&mut self, job: &ClaimedJob) -> Result<()> {
+        JobConsumer::ack(self, job).await
+    }
+
+    async fn keep_alive(&mut self, jobs: &[ClaimedJob]) -> Result<()> {
+        JobConsumer::keep_alive(self, jobs).await
+    }
+
+    fn keep_alive_interval(&self) -> Duration {
+        KEEP_ALIVE_INTERVAL.min(Duration::from_millis(self.stale_after_ms / 3))
+    }
+}
+
+/// Process jobs from `queue` with `handler` until `shutdown` resolves
+///
+/// A job is only claimed while one of the `max_concurrent_jobs` slots is
+/// free; each claimed job then runs in its own task holding that slot. When a
+/// task finishes it releases the slot and hands its outcome back here, where
+/// the message is acknowledged ([`JobOutcome::Succeeded`],
+/// [`JobOutcome::Failed`] and [`JobOutcome::Cancelled`]) or left pending
+/// ([`JobOutcome::Retry`]). The messages of running and buffered jobs are
+/// kept alive every [`JobQueue::keep_alive_interval`]. On shutdown no new
+/// jobs are claimed, and this waits for every in-flight job to finish and be
+/// settled before returning.
+pub async fn run_jobs<Q, H, F>(
+    queue: &mut Q,
+    handler: Arc<H>,
+    max_concurrent_jobs: usize,
+    shutdown: F,
+) -> Result<()>
+where
+    Q: JobQueue + ?Sized,
+    H: JobHandler,
+    F: Future<Output = ()>,
+{
+    let max_concurrent_jobs = max_concurrent_jobs.max(1);
+    let slots = Arc::new(Semaphore::new(max_concurrent_jobs));
+    let (done_tx, mut done_rx) = mpsc::unbounded_channel::<(ClaimedJob, JobOutcome)>();
+    let mut running: Vec<ClaimedJob> = Vec::new();
+    let mut keep_alive = tokio::time::interval(queue.keep_alive_interval());
+    keep_alive.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    tokio::pin!(shutdown);
+
+    loop {
+        let permit = tokio::select! {
+            _ = &mut shutdown => break,
+            Some((claimed, outcome)) = done_rx.recv() => {
+                settle(queue, &mut running, &claimed, outcome).await;
+                continue;
+            }
+            _ = keep_alive.tick() => {
+                keep_running_alive(queue, &running).await;
+                continue;
+            }
+            permit = slots.clone().acquire_owned() => {
+                permit.expect("job slot semaphore is never closed")
+            }
+        };
+
+        // Settle anything that finished while we were waiting for a slot
+        while let Ok((claimed, outcome)) = done_rx.try_recv() {
+            settle(queue, &mut running, &claimed, outcome).await;
+        }
+
+        // A read interrupted by shutdown may leave a delivered message
+        // pending; orphan recovery picks it up later
+        let next = tokio::select! {
+            _ = &mut shutdown => break,
+            next = queue.next_job() => next,
+        };
+
+        let claimed = match next {
+            Ok(Some(claimed)) => claimed,
+            Ok(None) => continue,
+            Err(e) => {
+                warn!("Failed to read next job: {:#}", e);
+                tokio::time::sleep(READ_ERROR_DELAY).await;
+                continue;
+            }
+        };
+
+        running.push(claimed.clone());
+        let handler = handler.clone();
+        let done_tx = done_tx.clone();
+        let span = job_span(&claimed.job);
+        tokio::spawn(
+            async move {
+                let outcome = handler.run(&claimed).await;
+                let _ = done_tx.send((claimed, outcome));
+                drop(permit);
+            }
+            .instrument(span),
+        );
+    }
+
+    let in_flight = max_concurrent_jobs - slots.available_permits();
+    if in_flight > 0 {
+        info!("Waiting for {} in-flight jobs to finish", in_flight);
+    }
+
+    // Every task holds a slot until it has reported its outcome
+    let all_slots = slots.acquire_many(max_concurrent_jobs as u32);
+    tokio::pin!(all_slots);
+    loop {
+        tokio::select! {
+            all = &mut all_slots => {
+                let _all = all.expect("job slot semaphore is never closed");
+                break;
+            }
+            Some((claimed, outcome)) = done_rx.recv() => {
+                settle(queue, &mut running, &claimed, outcome).await;
+            }
+            _ = keep_alive.tick() => keep_running_alive(queue, &running).await,
+        }
+    }
+    while let Ok((claimed, outcome)) = done_rx.try_recv() {
+        settle(queue, &mut running, &claimed, outcome).await;
+    }
+
+    Ok(())
+}
+
+/// Span a job runs in, carrying the ID of the request that queued it so the
+/// agent's logs can be correlated with the server's
+fn job_span(job: &QueuedJob) -> Span {
+    tracing::info_span!(
+        "job",
+        job_id = %job.id,
+        request_id = job.request_id.as_deref(),
+    )
+}
+
+/// Reset the idle time of the running jobs' messages, and of any the queue
+/// holds for later
+async fn keep_running_alive<Q: JobQueue + ?Sized>(queue: &mut Q, running: &[ClaimedJob]) {
+    if let Err(e) = queue.keep_alive(running).await {
+        warn!("Failed to keep running jobs' messages alive: {:#}", e);
+    }
+}
+
+/// Acknowledge a handled job's message, or leave it pending for retry
+async fn settle<Q: JobQueue + ?Sized>(
+    queue: &mut Q,
+    running: &mut Vec<ClaimedJob>,
+    claimed: &ClaimedJob,
+    outcome: JobOutcome,
+) {
+    running.retain(|job| job.stream != claimed.stream || job.message_id != claimed.message_id);
+    match outcome {
+        JobOutcome::Succeeded | JobOutcome::Failed | JobOutcome::Cancelled => {
+            if let Err(e) = queue.ack(claimed).await {
+                warn!("{:#}", e);
+            }
+        }
+        JobOutcome::Retry => {
+            debug!("Leaving message {} pending for retry", claimed.message_id);
+        }
+    }
+}
+
+/// Whether an agent with `capabilities` has every capability `job` requires
+fn can_run(capabilities: &[String], job: &QueuedJob) -> bool {
+    job.trigger.missing_capabilities(capabilities).is_empty()
+}
+
+/// How often a job's message was handed back, from its stream fields
+fn hand_backs(fields: &HashMap<String, String>) -> u32 {
+    fields
+        .get(STREAM_FIELD_HAND_BACKS)
+        .and_then(|count| count.parse().ok())
+        .unwrap_or(0)
+}
+
+/// Job streams for the base stream name, highest priority first
+fn priority_streams(base_stream: &str) -> Vec<String> {
+    JobPriority::ALL
+        .iter()
+        .map(|priority| priority.stream_key(base_stream))
+        .collect()
+}
+
+/// Claim up to [`AUTOCLAIM_BATCH_SIZE`] messages of `stream` pending longer
+/// than `min_idle_ms`, starting at `cursor`, for `consumer`
+async fn autoclaim<C>(
+    conn: &mut C,
+    stream: &str,
+    group: &str,
+    consumer: &str,
+    min_idle_ms: u64,
+    cursor: &str,
+) -> Result<(String, Vec<AutoclaimEntry>)>
+where
+    C: redis::aio::ConnectionLike + Send,
+{
+    let reply: Value = redis::cmd("XAUTOCLAIM")
+        .arg(stream)
+        .arg(group)
+        .arg(consumer)
+        .arg(min_idle_ms)
+        .arg(cursor)
+        .arg("COUNT")
+        .arg(AUTOCLAIM_BATCH_SIZE)
+        .query_async(conn)
+        .await
+        .context("XAUTOCLAIM failed")?;
+    parse_autoclaim_reply(reply)
+}
+
+/// Reset the idle time of pending messages `ids` of `stream`, keeping them
+/// with `consumer`
+///
+/// `XCLAIM` with `JUSTID` leaves the messages' delivery counts unchanged.
+async fn touch_messages<C>(
+    conn: &mut C,
+    stream: &str,
+    group: &str,
+    consumer: &str,
+    ids: &[&str],
+) -> Result<()>
+where
+    C: redis::aio::ConnectionLike + Send,
+{
+    redis::cmd("XCLAIM")
+        .arg(stream)
+        .arg(group)
+        .arg(consumer)
+        .arg(0)
+        .arg(ids)
+        .arg("JUSTID")
+        .query_async::<_, Vec<String>>(conn)
+        .await
+        .with_context(|| format!("Failed to keep messages of '{}' alive", stream))?;
+    Ok(())
+}
+
+/// Number of times pending message `message_id` was delivered, from
+/// `XPENDING`
+async fn delivery_count<C>(conn: &mut C, stream: &str, group: &str, message_id: &str) -> Result<u64>
+where
+    C: redis::aio::ConnectionLike + Send,
+{
+    let pending: Vec<Value> = redis::cmd("XPENDING")
+        .arg(stream)
+        .arg(group)
+        .arg(message_id)
+        .arg(message_id)
+        .arg(1)
+        .query_async(conn)
+        .await
+        .with_context(|| format!("Failed to read delivery count of message {}", message_id))?;
+
+    // Each entry is [id, consumer, idle ms, deliveries]
+    match pending.first() {
+        Some(Value::Bulk(entry)) if entry.len() == 4 => redis::from_redis_value(&entry[3])
+            .with_context(|| format!("Invalid delivery count of message {}", message_id)),
+        Some(entry) => bail!("Unexpected XPENDING entry: {:?}", entry),
+        None => Ok(1),
+    }
+}
+
+/// Job of a reclaimed message delivered `deliveries` times, this one
+/// included, counting every earlier delivery as a retry
+fn reclaimed_job(fields: &HashMap<String, String>, deliveries: u64) -> Result<QueuedJob> {
+    let mut job = QueuedJob::from_stream_fields(fields)?;
+    let earlier = u32::try_from(deliveries.saturating_sub(1)).unwrap_or(u32::MAX);
+    job.retry_count = job.retry_count.saturating_add(earlier);
+    Ok(job)
+}
+
+/// Stream entry fields and values, `None` if the entry was deleted
+type AutoclaimEntry = (String, Option<HashMap<String, String>>);
+
+/// Parse an `XAUTOCLAIM` reply into the next cursor and claimed entries
+///
+/// The reply is `[cursor, [[id, [field, value, ...]], ...]]`, with a third
+/// element listing deleted IDs on Redis 7+. Redis 6.2 instead returns deleted
+/// entries inline as nil.
+fn parse_autoclaim_reply(reply: Value) -> Result<(String, Vec<AutoclaimEntry>)> {
+    let Value::Bulk(mut parts) = reply else {
+        bail!("Unexpected XAUTOCLAIM reply: {:?}", reply);
+    };
+    if parts.len() < 2 {
+        bail!("XAUTOCLAIM reply has {} elements, expected 2+", parts.len());
+    }
+
+    let entries = parts.remove(1);
+    let cursor: String = redis::from_redis_value(&parts[0]).context("Invalid XAUTOCLAIM cursor")?;
+
+    let Value::Bulk(entries) = entries else {
+        bail!("Unexpected XAUTOCLAIM entries: {:?}", entries);
+    };
+
+    let claimed = entries
+        .into_iter()
+        .filter_map(|entry| match entry {
+            Value::Bulk(mut pair) if pair.len() == 2 => {
+                let fields = pair.pop()?;
+                let id: String = redis::from_redis_value(&pair[0]).ok()?;
+                match fields {
+                    Value::Nil => Some(Ok((id, None))),
+                    fields => Some(
+                        redis::from_redis_value::<HashMap<String, String>>(&fields)
+                            .map(|f| (id.clone(), Some(f)))
+                            .map_err(|e| anyhow!("Invalid fields for message {}: {}", id, e)),
+                    ),
+                }
+            }
+            _ => None,
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok((cursor, claimed))
+}
+
+/// Convert raw stream entry values to strings
+fn stream_fields(map: &HashMap<String, Value>) -> Result<HashMap<String, String>> {
+    map.iter()
+        .map(|(k, v)| {
+            let value = redis::from_redis_value(v)
+                .with_context(|| format!("Invalid value for field '{}'", k))?;
+            Ok((k.clone(), value))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use raibid_common::jobs::JobBuilder;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Mutex;
+    use tokio::sync::Barrier;
+
+    fn data(s: &str) -> Value {
+        Value::Data(s.as_bytes().to_vec())
+    }
+
+    #[test]
+    fn test_parse_autoclaim_reply() {
+        let reply = Value::Bulk(vec![
+            data("1700000000000-5"),
+            Value::Bulk(vec![
+                Value::Bulk(vec![
+                    data("1700000000000-1"),
+                    Value::Bulk(vec![data("job_id"), data("job-1")]),
+                ]),
+                Value::Bulk(vec![data("1700000000000-2"), Value::Nil]),
+                Value::Nil,
+            ]),
+            Value::Bulk(vec![]),
+        ]);
+
+        let (cursor, entries) = parse_autoclaim_reply(reply).unwrap();
+        assert_eq!(cursor, "1700000000000-5");
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].0, "1700000000000-1");
+        assert_eq!(entries[0].1.as_ref().unwrap()["job_id"], "job-1");
+        assert_eq!(entries[1], ("1700000000000-2".to_string(), None));
+    }
+
+    #[test]
+    fn test_parse_autoclaim_reply_invalid() {
+        assert!(parse_autoclaim_reply(Value::Nil).is_err());
+        assert!(parse_autoclaim_reply(Value::Bulk(vec![data("0-0")])).is_err());
+    }
+
+    #[test]
+    fn test_priority_streams() {
+        assert_eq!(
+            priority_streams("raibid:jobs"),
+            vec!["raibid:jobs:high", "raibid:jobs:normal", "raibid:jobs:low"]
+        );
+    }
+
+    #[test]
+    fn test_stream_fields() {
+        let mut map = HashMap::new();
+        map.insert("job_id".to_string(), data("job-1"));
+        assert_eq!(stream_fields(&map).unwrap()["job_id"], "job-1");
+    }
+
+    /// Pending message of the consumer group
+    struct PendingEntry {
+        id: String,
+        /// Consumer the message is pending with
+        consumer: String,
+        /// How long it has been pending in ms
+        idle_ms: u64,
+        /// How often it has been delivered
+        deliveries: u64,
+    }
+
+    /// Stream entry ID and fields
+    type FakeEntry = (String, Vec<(String, String)>);
+
+    /// Streams of one consumer group and job hashes
+    #[derive(Default)]
+    struct FakeState {
+        /// Entries of each stream, oldest first
+        streams: HashMap<String, Vec<FakeEntry>>,
+        /// Sequence number of the newest entry delivered to the group, per
+        /// stream
+        last_delivered: HashMap<String, u64>,
+        pending: Vec<PendingEntry>,
+        hashes: HashMap<String, HashMap<String, String>>,
+        last_id: u64,
+    }
+
+    /// Redis shared by the connections of several consumers, answering the
+    /// stream commands the consumer sends like Redis would, in and out of
+    /// `MULTI`/`EXEC` pipelines
+    #[derive(Clone, Default)]
+    struct FakeRedis(Arc<Mutex<FakeState>>);
+
+    fn sequence(id: &str) -> u64 {
+        id.rsplit('-').next().unwrap().parse().unwrap()
+    }
+
+    fn fields_value(fields: &[(String, String)]) -> Value {
+        Value::Bulk(
+            fields
+                .iter()
+                .flat_map(|(field, value)| [data(field), data(value)])
+                .collect(),
+        )
+    }
+
+    impl FakeRedis {
+        /// Queue `job` on the stream, returning its message ID
+        fn queue(&self, job: &QueuedJob) -> String {
+            let fields: Vec<(String, String)> = job
+                .to_stream_fields()
+                .unwrap()
+                .into_iter()
+                .map(|(field, value)| (field.to_string(), value))
+                .collect();
+            self.0.lock().unwrap().add(STREAM, fields)
+        }
+
+        /// Queue `job` as delivered `deliveries` times to a consumer that
+        /// left it pending for `idle_ms`, returning its message ID
+        fn deliver(&self, job: &QueuedJob, idle_ms: u64, deliveries: u64) -> String {
+            let id = self.queue(job);
+            let mut state = self.0.lock().unwrap();
+            state
+                .last_delivered
+                .insert(STREAM.to_string(), sequence(&id));
+            state.pending.push(PendingEntry {
+                id: id.clone(),
+                consumer: "agent-0".to_string(),
+                idle_ms,
+                deliveries,
+            });
+            id
+        }
+
+        /// Let `ms` pass for every pending message
+        fn age(&self, ms: u64) {
+            for entry in self.0.lock().unwrap().pending.iter_mut() {
+                entry.idle_ms += ms;
+            }
+        }
+
+        fn deliveries(&self, id: &str) -> u64 {
+            let state = self.0.lock().unwrap();
+            state
+                .pending
+                .iter()
+                .find(|e| e.id == id)
+                .unwrap()
+                .deliveries
+        }
+
+        fn pending_ids(&self) -> Vec<String> {
+            let state = self.0.lock().unwrap();
+            state.pending.iter().map(|e| e.id.clone()).collect()
+        }
+
+        /// Fields of the entries of `stream`, oldest first
+        fn entries(&self, stream: &str) -> Vec<HashMap<String, String>> {
+            let state = self.0.lock().unwrap();
+            state
+                .streams
+                .get(stream)
+                .into_iter()
+                .flatten()
+                .map(|(_, fields)| fields.iter().cloned().collect())
+                .collect()
+        }
+
+        fn hash_field(&self, key: &str, field: &str) -> Option<String> {
+            let state = self.0.lock().unwrap();
+            state.hashes.get(key)?.get(field).cloned()
+        }
+    }
+
+    impl FakeState {
+        fn add(&mut self, stream: &str, fields: Vec<(String, String)>) -> String {
+            self.last_id += 1;
+            let id = format!("1700000000000-{}", self.last_id);
+            let entries = self.streams.entry(stream.to_string()).or_default();
+            entries.push((id.clone(), fields));
+            id
+        }
+
+        fn fields(&self, stream: &str, id: &str) -> Option<&Vec<(String, String)>> {
+            let entries = self.streams.get(stream)?;
+            entries
+                .iter()
+                .find(|(entry, _)| entry == id)
+                .map(|(_, f)| f)
+        }
+
+        fn execute(&mut self, args: &[String]) -> Value {
+            match args[0].as_str() {
+                // XREADGROUP GROUP group consumer COUNT 1 [BLOCK ms] STREAMS
+                // stream... id...
+                "XREADGROUP" => {
+                    let start = args.iter().position(|a| a == "STREAMS").unwrap() + 1;
+                    let streams = &args[start..start + (args.len() - start) / 2];
+                    self.read_group(&args[3], streams)
+                }
+                // XAUTOCLAIM stream group consumer min-idle cursor COUNT n
+                "XAUTOCLAIM" => self.autoclaim(&args[1], &args[3], args[4].parse().unwrap()),
+                // XCLAIM stream group consumer min-idle id... JUSTID
+                "XCLAIM" => self.claim_ids(&args[3], &args[5..args.len() - 1]),
+                // XPENDING stream group start end count
+                "XPENDING" => self.pending(&args[3]),
+                // XACK stream group id
+                "XACK" => {
+                    let before = self.pending.len();
+                    self.pending.retain(|entry| entry.id != args[3]);
+                    Value::Int((before - self.pending.len()) as i64)
+                }
+                // XDEL stream id
+                "XDEL" => {
+                    let entries = self.streams.entry(args[1].clone()).or_default();
+                    let before = entries.len();
+                    entries.retain(|(id, _)| *id != args[2]);
+                    Value::Int((before - entries.len()) as i64)
+                }
+                // XADD stream [MAXLEN ~ n] * field value...
+                "XADD" => {
+                    let start = args.iter().position(|a| a == "*").unwrap() + 1;
+                    let fields = args[start..]
+                        .chunks(2)
+                        .map(|pair| (pair[0].clone(), pair[1].clone()))
+                        .collect();
+                    data(&self.add(&args[1], fields))
+                }
+                // HSET key field value
+                "HSET" => {
+                    let hash = self.hashes.entry(args[1].clone()).or_default();
+                    hash.insert(args[2].clone(), args[3].clone());
+                    Value::Int(1)
+                }
+                command => panic!("Unexpected command {}", command),
+            }
+        }
+
+        /// Deliver the oldest new entry of each of `streams` to `consumer`
+        fn read_group(&mut self, consumer: &str, streams: &[String]) -> Value {
+            let mut keys = Vec::new();
+            for stream in streams {
+                let last = self.last_delivered.get(stream).copied().unwrap_or(0);
+                let next = self.streams.get(stream).and_then(|entries| {
+                    entries.iter().find(|(id, _)| sequence(id) > last).cloned()
+                });
+                let Some((id, fields)) = next else {
+                    continue;
+                };
+
+                self.last_delivered.insert(stream.clone(), sequence(&id));
+                self.pending.push(PendingEntry {
+                    id: id.clone(),
+                    consumer: consumer.to_string(),
+                    idle_ms: 0,
+                    deliveries: 1,
+                });
+                let entry = Value::Bulk(vec![data(&id), fields_value(&fields)]);
+                keys.push(Value::Bulk(vec![data(stream), Value::Bulk(vec![entry])]));
+            }
+
+            if keys.is_empty() {
+                Value::Nil
+            } else {
+                Value::Bulk(keys)
+            }
+        }
+
+        fn autoclaim(&mut self, stream: &str, consumer: &str, min_idle: u64) -> Value {
+            let mut claimed = Vec::new();
+            for entry in self.pending.iter_mut() {
+                if entry.idle_ms < min_idle {
+                    continue;
+                }
+                entry.consumer = consumer.to_string();
+                entry.idle_ms = 0;
+                entry.deliveries += 1;
+                claimed.push(entry.id.clone());
+            }
+
+            let claimed = claimed
+                .iter()
+                .map(|id| {
+                    let fields = self
+                        .fields(stream, id)
+                        .map_or(Value::Nil, |fields| fields_value(fields));
+                    Value::Bulk(vec![data(id), fields])
+                })
+                .collect();
+            Value::Bulk(vec![data("0-0"), Value::Bulk(claimed), Value::Bulk(vec![])])
+        }
+
+        fn claim_ids(&mut self, consumer: &str, ids: &[String]) -> Value {
+            let claimed = self
+                .pending
+                .iter_mut()
+                .filter(|entry| ids.contains(&entry.id))
+                .map(|entry| {
+                    entry.consumer = consumer.to_string();
+                    entry.idle_ms = 0;
+                    data(&entry.id)
+                })
+                .collect();
+            Value::Bulk(claimed)
+        }
+
+        fn pending(&self, id: &str) -> Value {
+            let pending = self
+                .pending
+                .iter()
+                .filter(|entry| entry.id == id)
+                .map(|entry| {
+                    Value::Bulk(vec![
+                        data(&entry.id),
+                        data(&entry.consumer),
+                        Value::Int(entry.idle_ms as i64),
+                        Value::Int(entry.deliveries as i64),
+                    ])
+                })
+                .collect();
+            Value::Bulk(pending)
+        }
+    }
+
+    fn command_args(cmd: &redis::Cmd) -> Vec<String> {
+        cmd.args_iter()
+            .map(|arg| match arg {
+                redis::Arg::Simple(bytes) => String::from_utf8_lossy(bytes).to_string(),
+                redis::Arg::Cursor => "0".to_string(),
+            })
+            .collect()
+    }
+
+    impl redis::aio::ConnectionLike for FakeRedis {
+        fn req_packed_command<'a>(
+            &'a mut self,
+            cmd: &'a redis::Cmd,
+        ) -> redis::RedisFuture<'a, Value> {
+            let reply = self.0.lock().unwrap().execute(&command_args(cmd));
+            Box::pin(async { Ok(reply) })
+        }
+
+        fn req_packed_commands<'a>(
+            &'a mut self,
+            pipeline: &'a redis::Pipeline,
+            offset: usize,
+            count: usize,
+        ) -> redis::RedisFuture<'a, Vec<Value>> {
+            // All commands of a transaction run under one lock, like MULTI
+            let mut state = self.0.lock().unwrap();
+            let replies: Vec<Value> = pipeline
+                .cmd_iter()
+                .map(|cmd| state.execute(&command_args(cmd)))
+                .collect();
+            // Atomic pipelines skip the replies to MULTI and the queued
+            // commands, reading only that of EXEC
+            let replies = if offset > 0 {
+                vec![Value::Bulk(replies)]
+            } else {
+                replies.into_iter().take(count).collect()
+            };
+            Box::pin(async move { Ok(replies) })
+        }
+
+        fn get_db(&self) -> i64 {
+            0
+        }
+    }
+
+    const STREAM: &str = "raibid:jobs:normal";
+    const GROUP: &str = "raibid-workers";
+
+    /// Consumer `id` of the normal priority stream reading through `conn`
+    fn consumer(conn: &FakeRedis, id: &str, capabilities: &[&str]) -> JobConsumer<FakeRedis> {
+        JobConsumer {
+            conn: conn.clone(),
+            streams: vec![STREAM.to_string()],
+            group: GROUP.to_string(),
+            consumer_id: id.to_string(),
+            capabilities: capabilities.iter().map(|c| c.to_string()).collect(),
+            dead_letter_stream: dead_letter_stream("raibid:jobs"),
+            dead_letter_maxlen: 100,
+            recovery_interval: Duration::from_secs(60),
+            stale_after_ms: 300_000,
+            last_recovery: None,
+            buffered: VecDeque::new(),
+            handed_back: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stale_message_reclaimed() {
+        let trigger = JobBuilder::new("raibid-labs/raibid-cli").build().unwrap();
+        let mut conn = FakeRedis::default();
+        // Delivered twice, left behind by an agent that crashed 10 minutes
+        // ago
+        let orphan = conn.deliver(&QueuedJob::new("job-1", trigger.clone()), 600_000, 2);
+        // Still being built by a live agent
+        conn.deliver(&QueuedJob::new("job-2", trigger), 1_000, 1);
+
+        let (cursor, entries) = autoclaim(&mut conn, STREAM, GROUP, "agent-2", 300_000, "0-0")
+            .await
+            .unwrap();
+        assert_eq!(cursor, "0-0");
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].0, orphan);
+
+        let deliveries = delivery_count(&mut conn, STREAM, GROUP, &entries[0].0)
+            .await
+            .unwrap();
+        assert_eq!(deliveries, 3);
+        let job = reclaimed_job(entries[0].1.as_ref().unwrap(), deliveries).unwrap();
+        assert_eq!(job.id, "job-1");
+        assert_eq!(job.retry_count, 2);
+    }
+
+    #[tokio::test]
+    async fn test_kept_alive_message_not_reclaimed() {
+        let trigger = JobBuilder::new("raibid-labs/raibid-cli").build().unwrap();
+        let mut conn = FakeRedis::default();
+        let id = conn.deliver(&QueuedJob::new("job-1", trigger), 600_000, 1);
+
+        // A build running for 10 minutes on a live agent
+        touch_messages(&mut conn, STREAM, GROUP, "agent-1", &[id.as_str()])
+            .await
+            .unwrap();
+
+        let (_, entries) = autoclaim(&mut conn, STREAM, GROUP, "agent-2", 300_000, "0-0")
+            .await
+            .unwrap();
+        assert!(entries.is_empty());
+        assert_eq!(conn.deliveries(&id), 1);
+    }
+
+    #[tokio::test]
+    async fn test_buffered_jobs_not_reclaimed() {
+        let trigger = JobBuilder::new("raibid-labs/raibid-cli").build().unwrap();
+        let conn = FakeRedis::default();
+        for n in 1..=3 {
+            conn.deliver(
+                &QueuedJob::new(format!("job-{}", n), trigger.clone()),
+                600_000,
+                1,
+            );
+        }
+        let mut agent_1 = consumer(&conn, "agent-1", &[]);
+        let mut agent_2 = consumer(&conn, "agent-2", &[]);
+
+        // With one slot, agent-1 runs the first orphan and buffers the rest
+        let running = [agent_1.next_job().await.unwrap().unwrap()];
+        assert_eq!(running[0].job.id, "job-1");
+        assert_eq!(agent_1.buffered.len(), 2);
+
+        // The first job builds for longer than the stale threshold
+        for _ in 0..3 {
+            conn.age(100_000);
+            agent_1.keep_alive(&running).await.unwrap();
+        }
+
+        assert_eq!(agent_2.reclaim_stale_messages(300_000).await.unwrap(), 0);
+        assert!(agent_2.buffered.is_empty());
+        for n in 2..=3 {
+            let job = agent_1.next_job().await.unwrap().unwrap();
+            assert_eq!(job.job.id, format!("job-{}", n));
+            assert_eq!(conn.deliveries(&job.message_id), 2);
+        }
+    }
+
+    fn gpu_job(id: &str) -> QueuedJob {
+        let trigger = JobBuilder::new("raibid-labs/raibid-cli")
+            .require_capability("gpu")
+            .build()
+            .unwrap();
+        QueuedJob::new(id, trigger)
+    }
+
+    #[tokio::test]
+    async fn test_jobs_routed_by_capability() {
+        let conn = FakeRedis::default();
+        let mut rust = consumer(&conn, "agent-1", &["rust", "docker"]);
+        let mut gpu = consumer(&conn, "agent-2", &["rust", "gpu"]);
+        let streams = [STREAM.to_string()];
+        let original = conn.queue(&gpu_job("job-1"));
+
+        // Handed back: acknowledged, deleted and queued again for an agent
+        // with a GPU
+        assert!(rust.read_group(&streams, None).await.unwrap().is_empty());
+        assert!(rust.handed_back);
+        assert!(conn.pending_ids().is_empty());
+        let entries = conn.entries(STREAM);
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0][STREAM_FIELD_HAND_BACKS], "1");
+
+        let jobs = gpu.read_group(&streams, None).await.unwrap();
+        assert_eq!(jobs.len(), 1);
+        assert_eq!(jobs[0].job.id, "job-1");
+        assert_ne!(jobs[0].message_id, original);
+        assert_eq!(conn.pending_ids(), [jobs[0].message_id.clone()]);
+
+        // Jobs without requirements run anywhere
+        let trigger = JobBuilder::new("raibid-labs/raibid-cli").build().unwrap();
+        conn.queue(&QueuedJob::new("job-2", trigger));
+        let jobs = rust.read_group(&streams, None).await.unwrap();
+        assert_eq!(jobs[0].job.id, "job-2");
+    }
+
+    #[tokio::test]
+    async fn test_job_no_agent_can_run_dead_lettered() {
+        let conn = FakeRedis::default();
+        let mut rust = consumer(&conn, "agent-1", &["rust"]);
+        let streams = [STREAM.to_string()];
+        conn.queue(&gpu_job("job-1"));
+
+        // Each hand-back is counted on the queued entry
+        for hand_backs in 1..=3 {
+            assert!(rust.read_group(&streams, None).await.unwrap().is_empty());
+            let entries = conn.entries(STREAM);
+            assert_eq!(entries[0][STREAM_FIELD_HAND_BACKS], hand_backs.to_string());
+        }
+
+        // Once handed back too often, it leaves the stream for good
+        {
+            let mut state = conn.0.lock().unwrap();
+            let fields = &mut state.streams.get_mut(STREAM).unwrap()[0].1;
+            for (field, value) in fields.iter_mut() {
+                if field == STREAM_FIELD_HAND_BACKS {
+                    *value = MAX_HAND_BACKS.to_string();
+                }
+            }
+        }
+        assert!(rust.read_group(&streams, None).await.unwrap().is_empty());
+        assert!(conn.entries(STREAM).is_empty());
+        assert!(conn.pending_ids().is_empty());
+
+        let dead = conn.entries(&dead_letter_stream("raibid:jobs"));
+        assert_eq!(dead.len(), 1);
+        let dead = DeadJob::from_stream_fields(&dead[0]).unwrap();
+        assert_eq!(dead.id, "job-1");
+        assert_eq!(
+            dead.failure_reason,
+            format!(
+                "No agent with capabilities gpu took the job in {} hand-backs",
+                MAX_HAND_BACKS
+            )
+        );
+        assert_eq!(
+            conn.hash_field(&job_key("job-1"), JOB_FIELD_STATUS)
+                .as_deref(),
+            Some("failed")
+        );
+    }
+
+    fn claimed(n: usize) -> ClaimedJob {
+        ClaimedJob {
+            stream: "raibid:jobs:normal".to_string(),
+            message_id: format!("1700000000000-{}", n),
+            job: QueuedJob::new(
+                format!("job-{}", n),
+                JobBuilder::new("raibid-labs/raibid-cli").build().unwrap(),
+            ),
+        }
+    }
+
+    #[derive(Default)]
+    struct MockQueue {
+        jobs: VecDeque<ClaimedJob>,
+        acked: Arc<Mutex<Vec<String>>>,
+        kept_alive: Arc<Mutex<Vec<String>>>,
+    }
+
+    #[async_trait]
+    impl JobQueue for MockQueue {
+        async fn next_job(&mut self) -> Result<Option<ClaimedJob>> {
+            match self.jobs.pop_front() {
+                Some(job) => Ok(Some(job)),
+                None => {
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                    Ok(None)
+                }
+            }
+        }
+
+        async fn ack(&mut self, job: &ClaimedJob) -> Result<()> {
+            self.acked.lock().unwrap().push(job.message_id.clone());
+            Ok(())
+        }
+
+        async fn keep_alive(&mut self, jobs: &[ClaimedJob]) -> Result<()> {
+            let mut kept_alive = self.kept_alive.lock().unwrap();
+            kept_alive.extend(jobs.iter().map(|job| job.message_id.clone()));
+            Ok(())
+        }
+
+        fn keep_alive_interval(&self) -> Duration {
+            Duration::from_millis(10)
+        }
+    }
+
+    /// Handler whose jobs only finish once `barrier` jobs are running at once
+    struct BarrierHandler {
+        barrier: Barrier,
+        started: AtomicUsize,
+        finished: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl JobHandler for BarrierHandler {
+        async fn run(&self, _job: &ClaimedJob) -> JobOutcome {
+            self.started.fetch_add(1, Ordering::SeqCst);
+            self.barrier.wait().await;
+            self.finished.fetch_add(1, Ordering::SeqCst);
+            JobOutcome::Succeeded
+        }
+    }
+
+    #[tokio::test]
+    async fn test_run_jobs_concurrently() {
+        let mut queue = MockQueue {
+            jobs: VecDeque::from([claimed(1), claimed(2)]),
+            ..Default::default()
+        };
+        let acked = queue.acked.clone();
+        let handler = Arc::new(BarrierHandler {
+            barrier: Barrier::new(2),
+            started: AtomicUsize::new(0),
+            finished: AtomicUsize::new(0),
+        });
+
+        // Shut down as soon as both jobs are running; run_jobs must still
+        // wait for them to finish
+        let watched = handler.clone();
+        let shutdown = async move {
+            while watched.started.load(Ordering::SeqCst) < 2 {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+        };
+
+        tokio::time::timeout(
+            Duration::from_secs(5),
+            run_jobs(&mut queue, handler.clone(), 2, shutdown),
+        )
+        .await
+        .expect("jobs did not run concurrently")
+        .unwrap();
+
+        assert_eq!(handler.finished.load(Ordering::SeqCst), 2);
+        let mut acked = acked.lock().unwrap().clone();
+        acked.sort();
+        assert_eq!(acked, vec!["1700000000000-1", "1700000000000-2"]);
+    }
+
+    struct RetryHandler;
+
+    #[async_trait]
+    impl JobHandler for RetryHandler {
+        async fn run(&self, _job: &ClaimedJob) -> JobOutcome {
+            JobOutcome::Retry
+        }
+    }
+
+    #[tokio::test]
+    async fn test_run_jobs_retry_not_acked() {
+        let mut queue = MockQueue {
+            jobs: VecDeque::from([claimed(1)]),
+            ..Default::default()
+        };
+        let acked = queue.acked.clone();
+
+        let shutdown = tokio::time::sleep(Duration::from_millis(50));
+        run_jobs(&mut queue, Arc::new(RetryHandler), 1, shutdown)
+            .await
+            .unwrap();
+
+        assert!(queue.jobs.is_empty());
+        assert!(acked.lock().unwrap().is_empty());
+    }
+
+    struct SlowHandler;
+
+    #[async_trait]
+    impl JobHandler for SlowHandler {
+        async fn run(&self, _job: &ClaimedJob) -> JobOutcome {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            JobOutcome::Succeeded
+        }
+    }
+
+    #[tokio::test]
+    async fn test_run_jobs_keeps_running_jobs_alive() {
+        let mut queue = MockQueue {
+            jobs: VecDeque::from([claimed(1)]),
+            ..Default::default()
+        };
+        let kept_alive = queue.kept_alive.clone();
+
+        // Shut down while the job is still running
+        let shutdown = tokio::time::sleep(Duration::from_millis(20));
+        run_jobs(&mut queue, Arc::new(SlowHandler), 1, shutdown)
+            .await
+            .unwrap();
+
+        let kept_alive = kept_alive.lock().unwrap();
+        assert!(
+            kept_alive.len() >= 2,
+            "kept alive {} times",
+            kept_alive.len()
+        );
+        assert!(kept_alive.iter().all(|id| id == "1700000000000-1"));
+        assert_eq!(*queue.acked.lock().unwrap(), vec!["1700000000000-1"]);
+    }
+}
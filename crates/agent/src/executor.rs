@@ -0,0 +1,1653 @@
+//! Pipeline execution
+//!
+//! `PipelineExecutor` runs a job in its own workspace directory,
+//! `<workspace>/<job_id>`, so concurrent jobs never share a checkout:
+//! 1. Clone the repository at the requested branch/commit (see [`crate::git`])
+//! 2. Load and validate `raibid.yml`, falling back to `.raibid.yaml` and then
+//!    the default steps
+//! 3. Start sccache if the pipeline uses it (see [`crate::sccache`]), then run
+//!    each build step in order, stopping at the first failure unless the step
+//!    allows it
+//! 4. After a `test` step, collect the JUnit XML reports it left behind; after
+//!    a `bench` step, parse the benchmark results from its output; after a
+//!    `coverage` step, read its Cobertura report and check the threshold;
+//!    after a `deny` step, summarize the failures of each cargo-deny check;
+//!    after a `miri` step, summarize the undefined behavior Miri found;
+//!    after an `outdated` step, count the outdated dependencies;
+//!    after a `udeps` step, list the unused dependencies;
+//!    after a `security-scan` step, count the vulnerabilities trivy found
+//!
+//! The job's workspace is removed once the pipeline is done, even if it
+//! panics, unless `keep_workspace_on_failure` is set and the build failed.
+//!
+//! A job cancelled while it runs (see [`crate::cancellation`]) stops at the
+//! step it is in: the step's process gets SIGTERM, then SIGKILL if it has not
+//! exited after [`CANCEL_GRACE_PERIOD`], and no further steps run.
+//!
+//! A `.raibid.yaml` with a `matrix:` runs the steps once per toolchain and
+//! target combination, each in its own task with its own `CARGO_TARGET_DIR`,
+//! up to `max_concurrent_jobs` at a time.
+
+use anyhow::{bail, Context, Result};
+use std::path::{Path, PathBuf};
+use std::process::{ExitStatus, Output, Stdio};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::io::AsyncReadExt;
+use tokio::process::Child;
+use tokio::sync::Semaphore;
+use tracing::{info, warn};
+
+use crate::cancellation::{JobCancellation, JobCancellations};
+use crate::config::{MatrixCell, PipelineConfig};
+use crate::git::GitConfig;
+use crate::notifications::NotificationConfig;
+use crate::pipeline::config::{PipelineDefinition, PIPELINE_DEFINITION_FILE};
+use crate::pipeline::coverage::{read_coverage_report, tarpaulin_available};
+use crate::pipeline::cross::{
+    artifact_metadata, docker_running, ensure_cross, release_binaries, CrossTargetResult,
+};
+use crate::pipeline::deny::{cargo_deny_available, prepare_deny_config, DenyReport};
+use crate::pipeline::junit::collect_test_report;
+use crate::pipeline::miri::{ensure_miri, ub_errors, ub_summary};
+use crate::pipeline::outdated::{cargo_outdated_available, parse_outdated};
+use crate::pipeline::scan::{parse_trivy_report, trivy_available};
+use crate::pipeline::udeps::{parse_udeps, udeps_available};
+use crate::pipeline::{build_command_for_target, BuildStep, PipelineStep};
+use crate::sccache::{Sccache, SccacheConfig, SccacheStats};
+use raibid_common::artifacts::ArtifactMetadata;
+use raibid_common::benchmark::BenchResult;
+use raibid_common::coverage::CoverageReport;
+use raibid_common::dependencies::{OutdatedReport, UnusedDepsReport};
+use raibid_common::jobs::JobTrigger;
+use raibid_common::scan::ScanReport;
+use raibid_common::test_report::TestReport;
+
+/// How long a cancelled step's process has to exit after SIGTERM before it
+/// is killed
+pub const CANCEL_GRACE_PERIOD: Duration = Duration::from_secs(5);
+
+/// Context of errors in a job's pipeline definition, which running the job
+/// again cannot fix
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[error("Pipeline definition cannot be used")]
+pub struct InvalidPipeline;
+
+/// Outcome of a single build step
+#[derive(Debug, Clone)]
+pub struct StepResult {
+    /// Step name
+    pub name: String,
+    /// Whether the step exited successfully
+    pub success: bool,
+    /// Process exit code (None if killed by a signal)
+    pub exit_code: Option<i32>,
+    /// Wall-clock duration
+    pub duration: Duration,
+    /// Combined stdout and stderr, only the beginning when the rest was
+    /// stored elsewhere (see [`crate::log_storage`])
+    pub output: String,
+    /// Whether a failure of this step is allowed
+    pub continue_on_failure: bool,
+    /// Test results parsed from JUnit reports written by a `test` step
+    pub test_report: Option<TestReport>,
+    /// Benchmark results parsed from the output of a `bench` step
+    pub benchmarks: Option<Vec<BenchResult>>,
+    /// Line coverage read from the report of a `coverage` step
+    pub coverage: Option<CoverageReport>,
+    /// Outdated dependencies found by an `outdated` step
+    pub outdated: Option<OutdatedReport>,
+    /// Unused dependencies found by a `udeps` step
+    pub unused_deps: Option<UnusedDepsReport>,
+    /// Vulnerabilities found by a `security-scan` step
+    pub scan: Option<ScanReport>,
+    /// Target and binaries of a `cross:<target>` step
+    pub cross: Option<CrossTargetResult>,
+    /// Where the full output is stored, if it was too long to keep inline
+    pub log_url: Option<String>,
+    /// Whether the step was stopped because its job was cancelled
+    pub cancelled: bool,
+}
+
+impl StepResult {
+    /// Result of a step without an exit code or reports, as for one that was
+    /// skipped or did not finish
+    pub fn new(
+        name: String,
+        success: bool,
+        duration: Duration,
+        output: String,
+        continue_on_failure: bool,
+    ) -> Self {
+        Self {
+            name,
+            success,
+            exit_code: None,
+            duration,
+            output,
+            continue_on_failure,
+            test_report: None,
+            benchmarks: None,
+            coverage: None,
+            outdated: None,
+            unused_deps: None,
+            scan: None,
+            cross: None,
+            log_url: None,
+            cancelled: false,
+        }
+    }
+
+    /// Whether the step passed with outdated or unused dependencies
+    pub fn warning(&self) -> bool {
+        self.success
+            && (self.outdated.is_some_and(|report| report.total() > 0)
+                || self
+                    .unused_deps
+                    .as_ref()
+                    .is_some_and(|report| !report.is_empty()))
+    }
+}
+
+/// Outcome of a full pipeline run
+#[derive(Debug, Clone)]
+pub struct PipelineResult {
+    /// Results for each step that ran
+    pub steps: Vec<StepResult>,
+    /// sccache hits and misses of the pipeline, if it used sccache
+    pub cache_stats: Option<SccacheStats>,
+    /// Where to notify about the finished job, from the pipeline's config
+    pub notifications: Option<NotificationConfig>,
+}
+
+impl PipelineResult {
+    /// Whether every step succeeded or was allowed to fail
+    pub fn success(&self) -> bool {
+        self.steps
+            .iter()
+            .all(|s| s.success || s.continue_on_failure)
+    }
+
+    /// Whether the job was cancelled while the pipeline ran
+    pub fn cancelled(&self) -> bool {
+        self.steps.iter().any(|s| s.cancelled)
+    }
+
+    /// Test results of every step that produced them, merged
+    pub fn test_report(&self) -> Option<TestReport> {
+        self.steps
+            .iter()
+            .filter_map(|s| s.test_report.clone())
+            .reduce(|mut merged, report| {
+                merged.merge(report);
+                merged
+            })
+    }
+
+    /// Benchmark results of every step that produced them
+    pub fn benchmarks(&self) -> Option<Vec<BenchResult>> {
+        self.steps
+            .iter()
+            .filter_map(|s| s.benchmarks.clone())
+            .reduce(|mut all, results| {
+                all.extend(results);
+                all
+            })
+    }
+
+    /// Line coverage of the last step that reported it
+    pub fn coverage(&self) -> Option<CoverageReport> {
+        self.steps.iter().rev().find_map(|s| s.coverage)
+    }
+
+    /// Outdated dependencies found by the last step that checked them
+    pub fn outdated(&self) -> Option<OutdatedReport> {
+        self.steps.iter().rev().find_map(|s| s.outdated)
+    }
+
+    /// Unused dependencies found by the last step that checked them
+    pub fn unused_deps(&self) -> Option<UnusedDepsReport> {
+        self.steps.iter().rev().find_map(|s| s.unused_deps.clone())
+    }
+
+    /// Vulnerabilities found by the last step that scanned an image
+    pub fn scan(&self) -> Option<ScanReport> {
+        self.steps.iter().rev().find_map(|s| s.scan.clone())
+    }
+
+    /// Outcome of every cross-compilation target that was built
+    pub fn cross_results(&self) -> Vec<CrossTargetResult> {
+        self.steps.iter().filter_map(|s| s.cross.clone()).collect()
+    }
+
+    /// Binaries of every cross-compilation target, if the pipeline succeeded
+    pub fn artifacts(&self) -> Option<ArtifactMetadata> {
+        if !self.success() {
+            return None;
+        }
+        artifact_metadata(&self.cross_results())
+    }
+}
+
+/// Outcome of a matrix run
+#[derive(Debug, Clone)]
+pub struct MatrixPipelineResult {
+    /// Result of each combination, in matrix order
+    pub cells: Vec<(MatrixCell, PipelineResult)>,
+}
+
+impl MatrixPipelineResult {
+    /// Whether every combination succeeded
+    pub fn success(&self) -> bool {
+        self.cells.iter().all(|(_, result)| result.success())
+    }
+
+    /// Whether the job was cancelled while any combination ran
+    pub fn cancelled(&self) -> bool {
+        self.cells.iter().any(|(_, result)| result.cancelled())
+    }
+
+    /// Test results of every combination, merged
+    pub fn test_report(&self) -> Option<TestReport> {
+        self.cells
+            .iter()
+            .filter_map(|(_, result)| result.test_report())
+            .reduce(|mut merged, report| {
+                merged.merge(report);
+                merged
+            })
+    }
+}
+
+/// Outcome of [`PipelineExecutor::execute`]
+#[derive(Debug, Clone)]
+pub enum ExecutionResult {
+    /// The pipeline ran once
+    Pipeline(PipelineResult),
+    /// The pipeline ran once per matrix combination
+    Matrix(MatrixPipelineResult),
+}
+
+impl ExecutionResult {
+    /// Whether the pipeline, or every matrix combination, succeeded
+    pub fn success(&self) -> bool {
+        match self {
+            ExecutionResult::Pipeline(result) => result.success(),
+            ExecutionResult::Matrix(result) => result.success(),
+        }
+    }
+}
+
+/// Runs build pipelines in a workspace directory
+#[derive(Clone)]
+pub struct PipelineExecutor {
+    workspace: PathBuf,
+    job_id: Option<String>,
+    keep_workspace_on_failure: bool,
+    sccache: SccacheConfig,
+    git: GitConfig,
+    cancellations: Option<JobCancellations>,
+}
+
+impl PipelineExecutor {
+    /// Create an executor that checks out repositories into `workspace`
+    pub fn new(workspace: impl Into<PathBuf>) -> Self {
+        Self {
+            workspace: workspace.into(),
+            job_id: None,
+            keep_workspace_on_failure: false,
+            sccache: SccacheConfig::default(),
+            git: GitConfig::default(),
+            cancellations: None,
+        }
+    }
+
+    /// Check out the repository into the job's own subdirectory of the
+    /// workspace
+    pub fn with_job_id(mut self, job_id: impl Into<String>) -> Self {
+        self.job_id = Some(job_id.into());
+        self
+    }
+
+    /// Leave the checkout of failed builds in place for inspection
+    pub fn with_keep_workspace_on_failure(mut self, keep: bool) -> Self {
+        self.keep_workspace_on_failure = keep;
+        self
+    }
+
+    /// Use `config` for pipelines that compile through sccache
+    pub fn with_sccache(mut self, config: SccacheConfig) -> Self {
+        self.sccache = config;
+        self
+    }
+
+    /// Clone repositories as `config` says
+    pub fn with_git(mut self, config: GitConfig) -> Self {
+        self.git = config;
+        self
+    }
+
+    /// Stop the job when its ID is broadcast by `cancellations`
+    ///
+    /// Only takes effect with a job ID set.
+    pub fn with_cancellation(mut self, cancellations: JobCancellations) -> Self {
+        self.cancellations = Some(cancellations);
+        self
+    }
+
+    /// Get the workspace directory
+    pub fn workspace(&self) -> &Path {
+        &self.workspace
+    }
+
+    /// Directory the job is checked out and built in: `<workspace>/<job_id>`,
+    /// or the workspace itself when no job ID is set
+    pub fn job_workspace(&self) -> PathBuf {
+        match self.job_id {
+            Some(ref job_id) => self.workspace.join(job_id),
+            None => self.workspace.clone(),
+        }
+    }
+
+    /// Clone the repository and run its pipeline, then remove the checkout
+    ///
+    /// With `keep_workspace_on_failure`, the checkout of a build that ran and
+    /// failed is kept. Jobs that could not run are always cleaned up, since
+    /// they are retried in the same directory.
+    pub async fn execute(&self, trigger: &JobTrigger, repo_url: &str) -> Result<ExecutionResult> {
+        let cleanup = scopeguard::guard(self.job_workspace(), |workspace| {
+            remove_workspace(&workspace)
+        });
+
+        let result = self.run_pipeline(trigger, repo_url).await;
+        let failed = matches!(result, Ok(ref result) if !result.success());
+        if failed && self.keep_workspace_on_failure {
+            let workspace = scopeguard::ScopeGuard::into_inner(cleanup);
+            info!("Keeping workspace {} of failed build", workspace.display());
+        }
+        result
+    }
+
+    /// Clone the repository and run its pipeline in the job's workspace
+    async fn run_pipeline(&self, trigger: &JobTrigger, repo_url: &str) -> Result<ExecutionResult> {
+        self.clone_repository(trigger, repo_url).await?;
+        let workspace = self.job_workspace();
+
+        if let Some(definition) = PipelineDefinition::load(&workspace)
+            .await
+            .context(InvalidPipeline)?
+        {
+            info!("Using steps from {}", PIPELINE_DEFINITION_FILE);
+            let steps = definition.pipeline_steps().context(InvalidPipeline)?;
+            return self
+                .run_steps(&steps, &[])
+                .await
+                .map(ExecutionResult::Pipeline);
+        }
+
+        let config = PipelineConfig::load(&workspace).context(InvalidPipeline)?;
+        let steps: Vec<PipelineStep> = self
+            .validated_steps(&config)
+            .context(InvalidPipeline)?
+            .into_iter()
+            .map(|step| {
+                PipelineStep::from(step).with_default_timeout(config.default_step_timeout())
+            })
+            .collect();
+
+        if let Some(ref matrix) = config.matrix {
+            if config.use_sccache {
+                warn!("Matrix builds run without sccache");
+            }
+            let cells = matrix.cells();
+            // Without a limit every combination runs at once
+            let max_concurrent = config
+                .max_concurrent_jobs
+                .map_or(cells.len(), |max| max as usize);
+            return self
+                .run_matrix(&steps, cells, max_concurrent)
+                .await
+                .map(ExecutionResult::Matrix);
+        }
+
+        if config.use_sccache {
+            self.run_steps_with_sccache(&steps).await
+        } else {
+            self.run_steps(&steps, &[]).await
+        }
+        .map(|result| {
+            ExecutionResult::Pipeline(PipelineResult {
+                notifications: config.notifications,
+                ..result
+            })
+        })
+    }
+
+    /// Validate the pipeline definition and resolve its steps
+    ///
+    /// Fails with every validation error listed rather than stopping at the first.
+    pub fn validated_steps(&self, config: &PipelineConfig) -> Result<Vec<BuildStep>> {
+        let errors = config.validate();
+        if !errors.is_empty() {
+            let details: Vec<String> = errors.iter().map(|e| format!("  - {}", e)).collect();
+            bail!(
+                "Invalid pipeline definition ({} errors):\n{}",
+                errors.len(),
+                details.join("\n")
+            );
+        }
+
+        config.resolve_steps()
+    }
+
+    /// Clone `repo_url` into the workspace and check out the requested ref
+    async fn clone_repository(&self, trigger: &JobTrigger, repo_url: &str) -> Result<()> {
+        let workspace = self.job_workspace();
+        info!(
+            "Cloning {} ({}) into {}",
+            trigger.repo,
+            trigger.branch,
+            workspace.display()
+        );
+
+        self.git.checkout(trigger, repo_url, &workspace).await
+    }
+
+    /// Run steps compiling through sccache and record its hits and misses
+    ///
+    /// The steps run without sccache if it fails to start.
+    async fn run_steps_with_sccache(&self, steps: &[PipelineStep]) -> Result<PipelineResult> {
+        let sccache = match Sccache::start(&self.sccache).await {
+            Ok(sccache) => sccache,
+            Err(e) => {
+                warn!("Running without sccache: {:#}", e);
+                return self.run_steps(steps, &[]).await;
+            }
+        };
+
+        let before = sccache.stats().await;
+        let mut result = self.run_steps(steps, &sccache.env()).await?;
+
+        match (before, sccache.stats().await) {
+            (Ok(before), Ok(after)) => {
+                let stats = after.since(&before);
+                info!(
+                    "sccache: {} hits, {} misses ({:.0}% hit ratio)",
+                    stats.cache_hits,
+                    stats.cache_misses,
+                    stats.hit_ratio() * 100.0
+                );
+                result.cache_stats = Some(stats);
+            }
+            (Err(e), _) | (_, Err(e)) => warn!("Failed to read sccache statistics: {:#}", e),
+        }
+
+        Ok(result)
+    }
+
+    /// Run the steps once per matrix combination, each in its own task, with
+    /// at most `max_concurrent` running at once
+    async fn run_matrix(
+        &self,
+        steps: &[PipelineStep],
+        cells: Vec<MatrixCell>,
+        max_concurrent: usize,
+    ) -> Result<MatrixPipelineResult> {
+        info!(
+            "Running {} matrix combinations, {} at a time",
+            cells.len(),
+            max_concurrent.max(1)
+        );
+
+        let slots = Arc::new(Semaphore::new(max_concurrent.max(1)));
+        let steps: Arc<[PipelineStep]> = steps.into();
+        let mut tasks = Vec::with_capacity(cells.len());
+        for cell in cells {
+            let permit = slots
+                .clone()
+                .acquire_owned()
+                .await
+                .expect("matrix slot semaphore is never closed");
+            let (executor, steps) = (self.clone(), steps.clone());
+            tasks.push(tokio::spawn(async move {
+                let result = executor.run_cell(&steps, &cell).await;
+                drop(permit);
+                (cell, result)
+            }));
+        }
+
+        let mut results = Vec::with_capacity(tasks.len());
+        for task in tasks {
+            let (cell, result) = task.await.context("Matrix task panicked")?;
+            let result = result.with_context(|| format!("Failed to run matrix {}", cell.name()))?;
+            info!(
+                "Matrix {} {}",
+                cell.name(),
+                if result.success() {
+                    "succeeded"
+                } else {
+                    "failed"
+                }
+            );
+            results.push((cell, result));
+        }
+
+        Ok(MatrixPipelineResult { cells: results })
+    }
+
+    /// Run the steps for one matrix combination
+    ///
+    /// Build artifacts go to `target/matrix/<toolchain>-<target>` so that
+    /// combinations running at once do not share a target directory.
+    async fn run_cell(&self, steps: &[PipelineStep], cell: &MatrixCell) -> Result<PipelineResult> {
+        let target_dir = self
+            .job_workspace()
+            .join("target")
+            .join("matrix")
+            .join(cell.name().replace('/', "-"));
+        let mut env = vec![(
+            "CARGO_TARGET_DIR".to_string(),
+            target_dir.to_string_lossy().to_string(),
+        )];
+        if let Some(ref toolchain) = cell.toolchain {
+            env.push(("RUSTUP_TOOLCHAIN".to_string(), toolchain.clone()));
+        }
+
+        self.run_steps_for_target(steps, &env, cell.target.as_deref())
+            .await
+    }
+
+    /// Run steps in order, stopping at the first failure not allowed to fail
+    ///
+    /// `env` is added to the environment of every step.
+    async fn run_steps(
+        &self,
+        steps: &[PipelineStep],
+        env: &[(String, String)],
+    ) -> Result<PipelineResult> {
+        self.run_steps_for_target(steps, env, None).await
+    }
+
+    /// Run steps as [`run_steps`](Self::run_steps), compiling for `target`
+    async fn run_steps_for_target(
+        &self,
+        steps: &[PipelineStep],
+        env: &[(String, String)],
+        target: Option<&str>,
+    ) -> Result<PipelineResult> {
+        let mut results = Vec::with_capacity(steps.len());
+        let mut cancellation = self
+            .cancellations
+            .as_ref()
+            .zip(self.job_id.as_ref())
+            .map(|(cancellations, job_id)| cancellations.watch(job_id));
+
+        for step in steps {
+            let result = self
+                .run_step(step, env, target, cancellation.as_mut())
+                .await?;
+            let (success, cancelled) = (result.success, result.cancelled);
+            results.push(result);
+
+            if cancelled {
+                warn!(
+                    "Step '{}' cancelled, skipping remaining steps",
+                    step.step.name()
+                );
+                break;
+            }
+            if !success {
+                if step.continue_on_failure {
+                    warn!("Step '{}' failed, continuing", step.step.name());
+                } else {
+                    warn!(
+                        "Step '{}' failed, skipping remaining steps",
+                        step.step.name()
+                    );
+                    break;
+                }
+            }
+        }
+
+        Ok(PipelineResult {
+            steps: results,
+            cache_stats: None,
+            notifications: None,
+        })
+    }
+
+    /// Run a single build step, stopping it if `cancellation` resolves first
+    async fn run_step(
+        &self,
+        step: &PipelineStep,
+        env: &[(String, String)],
+        target: Option<&str>,
+        cancellation: Option<&mut JobCancellation>,
+    ) -> Result<StepResult> {
+        let name = step.step.name();
+        let workspace = self.job_workspace();
+        info!("Running step '{}'", name);
+
+        let started = Instant::now();
+        let unavailable = match step.step {
+            BuildStep::Coverage { .. } if !tarpaulin_available().await => {
+                Some("cargo-tarpaulin is not installed")
+            }
+            BuildStep::Deny { .. } if !cargo_deny_available().await => {
+                Some("cargo-deny is not installed")
+            }
+            BuildStep::Outdated { .. } if !cargo_outdated_available().await => {
+                Some("cargo-outdated is not installed")
+            }
+            BuildStep::Udeps { .. } if !udeps_available(&workspace, env).await => {
+                Some("cargo-udeps on the nightly toolchain is not installed")
+            }
+            BuildStep::Miri { .. } if !ensure_miri(&workspace, env).await => {
+                Some("miri is not installed")
+            }
+            BuildStep::SecurityScan { .. } if !trivy_available(env).await => {
+                Some("trivy is not installed")
+            }
+            BuildStep::CrossCompile { .. } if !ensure_cross(env).await => {
+                Some("cross is not installed")
+            }
+            BuildStep::CrossCompile { .. } if !docker_running(env).await => {
+                Some("Docker is not running")
+            }
+            _ => None,
+        };
+        if let Some(reason) = unavailable {
+            // Strict Miri steps fail rather than pass unchecked, and cross
+            // builds rather than produce no binaries
+            let strict = matches!(
+                step.step,
+                BuildStep::Miri { strict: true } | BuildStep::CrossCompile { .. }
+            );
+            let output = if strict {
+                warn!("Step '{}': {}", name, reason);
+                format!("Failed: {}", reason)
+            } else {
+                warn!("Step '{}': {}, skipping", name, reason);
+                format!("Skipped: {}", reason)
+            };
+            return Ok(StepResult::new(
+                name,
+                !strict,
+                started.elapsed(),
+                output,
+                step.continue_on_failure,
+            ));
+        }
+
+        let command_step = match &step.step {
+            BuildStep::Deny { config } => BuildStep::Deny {
+                config: Some(prepare_deny_config(&workspace, config.as_deref())?),
+            },
+            other => other.clone(),
+        };
+        let mut cmd = build_command_for_target(&command_step, &workspace, target)?;
+        cmd.envs(env.iter().map(|(k, v)| (k, v)))
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+
+        let mut child = cmd
+            .spawn()
+            .with_context(|| format!("Failed to run step '{}'", name))?;
+        let (mut stdout, mut stderr) = (Vec::new(), Vec::new());
+        let end = {
+            let mut stdout_pipe = child.stdout.take().expect("stdout is piped");
+            let mut stderr_pipe = child.stderr.take().expect("stderr is piped");
+            let finished = async {
+                tokio::try_join!(
+                    stdout_pipe.read_to_end(&mut stdout),
+                    stderr_pipe.read_to_end(&mut stderr),
+                    child.wait(),
+                )
+            };
+            let timed_out = async {
+                match step.timeout {
+                    Some(timeout) => tokio::time::sleep(timeout).await,
+                    None => std::future::pending().await,
+                }
+            };
+            let cancelled = async {
+                match cancellation {
+                    Some(cancellation) => cancellation.cancelled().await,
+                    None => std::future::pending().await,
+                }
+            };
+            tokio::select! {
+                finished = finished => StepEnd::Exited(finished.map(|(_, _, status)| status)),
+                _ = timed_out => StepEnd::TimedOut,
+                _ = cancelled => StepEnd::Cancelled,
+            }
+        };
+
+        let status = match end {
+            StepEnd::Exited(status) => {
+                status.with_context(|| format!("Failed to run step '{}'", name))?
+            }
+            // The process is killed when `child` is dropped
+            StepEnd::TimedOut => {
+                let timeout = step.timeout.unwrap_or_default();
+                warn!("Step '{}' timed out after {:?}", name, timeout);
+                return Ok(StepResult::new(
+                    name,
+                    false,
+                    started.elapsed(),
+                    format!("Step timed out after {:?}", timeout),
+                    step.continue_on_failure,
+                ));
+            }
+            StepEnd::Cancelled => {
+                warn!("Step '{}' cancelled, stopping it", name);
+                let status = terminate(&mut child)
+                    .await
+                    .with_context(|| format!("Failed to stop step '{}'", name))?;
+                let mut output = String::from_utf8_lossy(&stdout).to_string();
+                output.push_str(&String::from_utf8_lossy(&stderr));
+                output.push_str("\nStep cancelled\n");
+                return Ok(StepResult {
+                    exit_code: status.code(),
+                    cancelled: true,
+                    ..StepResult::new(
+                        name,
+                        false,
+                        started.elapsed(),
+                        output,
+                        step.continue_on_failure,
+                    )
+                });
+            }
+        };
+        let output = Output {
+            status,
+            stdout,
+            stderr,
+        };
+
+        let mut combined = String::from_utf8_lossy(&output.stdout).to_string();
+        combined.push_str(&String::from_utf8_lossy(&output.stderr));
+        let duration = started.elapsed();
+
+        // Reports are parsed whether or not the tests passed
+        let test_report = if step.step == BuildStep::Test {
+            let workspace = workspace.clone();
+            tokio::task::spawn_blocking(move || collect_test_report(&workspace))
+                .await
+                .context("Test report collection panicked")?
+        } else {
+            None
+        };
+        if let Some(ref report) = test_report {
+            info!(
+                "Step '{}': {} passed, {} failed, {} errored, {} skipped",
+                name,
+                report.passed(),
+                report.failures(),
+                report.errors(),
+                report.skipped()
+            );
+        }
+
+        let benchmarks = if step.step == BuildStep::Bench {
+            let results = BenchResult::parse_bencher_output(&combined);
+            info!("Step '{}': {} benchmarks", name, results.len());
+            Some(results)
+        } else {
+            None
+        };
+
+        if matches!(step.step, BuildStep::Deny { .. }) {
+            let report = DenyReport::parse(&combined);
+            let failed: Vec<_> = report.failed_checks().iter().map(|c| c.as_str()).collect();
+            if !failed.is_empty() {
+                warn!("Step '{}': {} checks failed", name, failed.join(", "));
+            }
+            combined.push('\n');
+            combined.push_str(&report.to_string());
+        }
+
+        if matches!(step.step, BuildStep::Miri { .. }) {
+            let errors = ub_errors(&combined);
+            if !errors.is_empty() {
+                warn!(
+                    "Step '{}': {} undefined behavior errors",
+                    name,
+                    errors.len()
+                );
+                let summary = ub_summary(&errors);
+                combined.push('\n');
+                combined.push_str(&summary);
+            }
+        }
+
+        // trivy logs to stderr, so only stdout holds the JSON report
+        let scan = match step.step {
+            BuildStep::SecurityScan { ref image, .. } => {
+                match parse_trivy_report(image, &String::from_utf8_lossy(&output.stdout)) {
+                    Ok(report) => {
+                        info!("Step '{}': {} in {}", name, report, image);
+                        combined.push_str(&format!("\nVulnerabilities: {}\n", report));
+                        Some(report)
+                    }
+                    Err(e) => {
+                        warn!("Step '{}': {:#}", name, e);
+                        None
+                    }
+                }
+            }
+            _ => None,
+        };
+
+        // cargo logs to stderr, so only stdout holds the JSON report
+        let outdated = match step.step {
+            BuildStep::Outdated { .. } => {
+                match parse_outdated(&String::from_utf8_lossy(&output.stdout)) {
+                    Ok(report) => {
+                        if report.total() > 0 {
+                            warn!("Step '{}': {} dependencies", name, report);
+                        } else {
+                            info!("Step '{}': dependencies are up to date", name);
+                        }
+                        combined.push_str(&format!("\nDependencies: {}\n", report));
+                        Some(report)
+                    }
+                    Err(e) => {
+                        warn!("Step '{}': {:#}", name, e);
+                        None
+                    }
+                }
+            }
+            _ => None,
+        };
+
+        // cargo logs to stderr, so only stdout holds the JSON report
+        let unused_deps = match step.step {
+            BuildStep::Udeps { .. } => {
+                match parse_udeps(&String::from_utf8_lossy(&output.stdout)) {
+                    Ok(report) => {
+                        if report.is_empty() {
+                            info!("Step '{}': every dependency is used", name);
+                        } else {
+                            warn!("Step '{}': {} dependencies", name, report);
+                        }
+                        combined.push_str(&format!("\nDependencies: {}\n", report));
+                        Some(report)
+                    }
+                    Err(e) => {
+                        warn!("Step '{}': {:#}", name, e);
+                        None
+                    }
+                }
+            }
+            _ => None,
+        };
+
+        let cross = match step.step {
+            BuildStep::CrossCompile { ref target } => {
+                let binaries = if output.status.success() {
+                    match release_binaries(&workspace, target) {
+                        Ok(binaries) => {
+                            info!("Step '{}': built {}", name, binaries.join(", "));
+                            binaries
+                        }
+                        Err(e) => {
+                            warn!("Step '{}': {:#}", name, e);
+                            Vec::new()
+                        }
+                    }
+                } else {
+                    Vec::new()
+                };
+                Some(CrossTargetResult {
+                    target: target.clone(),
+                    success: output.status.success(),
+                    binaries,
+                })
+            }
+            _ => None,
+        };
+
+        let mut success = output.status.success();
+        // Informational unless the pipeline enforces it; an unchecked
+        // report only fails an enforcing step
+        if let BuildStep::Outdated { fail_on_outdated } = step.step {
+            success = match outdated {
+                Some(report) => !fail_on_outdated || report.total() == 0,
+                None => !fail_on_outdated,
+            };
+        }
+        if let BuildStep::Udeps {
+            fail_on_unused_deps,
+        } = step.step
+        {
+            success = match unused_deps {
+                Some(ref report) => !fail_on_unused_deps || report.is_empty(),
+                None => !fail_on_unused_deps,
+            };
+        }
+        let coverage = match step.step {
+            BuildStep::Coverage { min_percent } if success => {
+                match read_coverage_report(&workspace) {
+                    Ok(report) => {
+                        info!("Step '{}': {:.1}% line coverage", name, report.percent());
+                        if let Some(min) = min_percent.filter(|min| !report.meets(*min)) {
+                            warn!(
+                                "Step '{}': coverage {:.1}% is below the {}% threshold",
+                                name,
+                                report.percent(),
+                                min
+                            );
+                            combined.push_str(&format!(
+                                "\nCoverage {:.1}% is below the {}% threshold\n",
+                                report.percent(),
+                                min
+                            ));
+                            success = false;
+                        }
+                        Some(report)
+                    }
+                    Err(e) => {
+                        warn!("Step '{}': {:#}", name, e);
+                        // A threshold cannot be checked without a report
+                        success = min_percent.is_none();
+                        None
+                    }
+                }
+            }
+            _ => None,
+        };
+
+        Ok(StepResult {
+            exit_code: output.status.code(),
+            test_report,
+            benchmarks,
+            coverage,
+            outdated,
+            unused_deps,
+            scan,
+            cross,
+            ..StepResult::new(name, success, duration, combined, step.continue_on_failure)
+        })
+    }
+}
+
+/// How a step's process stopped running
+enum StepEnd {
+    /// It exited, or could not be waited for
+    Exited(std::io::Result<ExitStatus>),
+    /// It ran longer than the step's timeout
+    TimedOut,
+    /// Its job was cancelled
+    Cancelled,
+}
+
+/// Stop a step's process with SIGTERM so it can clean up, then kill it if it
+/// is still running after [`CANCEL_GRACE_PERIOD`]
+async fn terminate(child: &mut Child) -> std::io::Result<ExitStatus> {
+    #[cfg(unix)]
+    if let Some(pid) = child.id() {
+        // SAFETY: kill(2) only sends a signal, and the child has not been
+        // reaped, so the PID still belongs to it
+        unsafe { libc::kill(pid as libc::pid_t, libc::SIGTERM) };
+        if let Ok(status) = tokio::time::timeout(CANCEL_GRACE_PERIOD, child.wait()).await {
+            return status;
+        }
+        warn!("Process {} still running after SIGTERM, killing it", pid);
+    }
+    child.kill().await?;
+    child.wait().await
+}
+
+/// Remove a job's workspace, logging failures
+fn remove_workspace(workspace: &Path) {
+    if let Err(e) = std::fs::remove_dir_all(workspace) {
+        if e.kind() != std::io::ErrorKind::NotFound {
+            warn!(
+                "Failed to clean up workspace {}: {}",
+                workspace.display(),
+                e
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validated_steps_lists_all_errors() {
+        let executor = PipelineExecutor::new("/tmp/raibid-test");
+        let config =
+            PipelineConfig::from_yaml("steps:\n  - deploy\nmax_concurrent_jobs: 0\n").unwrap();
+
+        let err = executor.validated_steps(&config).unwrap_err().to_string();
+        assert!(err.contains("2 errors"));
+        assert!(err.contains("unknown step 'deploy'"));
+        assert!(err.contains("max_concurrent_jobs"));
+    }
+
+    #[tokio::test]
+    async fn test_run_steps_stops_on_failure() {
+        let dir = tempfile::tempdir().unwrap();
+        let executor = PipelineExecutor::new(dir.path());
+
+        let steps: Vec<PipelineStep> = vec![
+            BuildStep::Shell {
+                script: "echo first".to_string(),
+            },
+            BuildStep::Shell {
+                script: "exit 3".to_string(),
+            },
+            BuildStep::Shell {
+                script: "echo never".to_string(),
+            },
+        ]
+        .into_iter()
+        .map(PipelineStep::from)
+        .collect();
+
+        let result = executor.run_steps(&steps, &[]).await.unwrap();
+        assert!(!result.success());
+        assert_eq!(result.steps.len(), 2);
+        assert!(result.steps[0].output.contains("first"));
+        assert_eq!(result.steps[1].exit_code, Some(3));
+    }
+
+    #[tokio::test]
+    async fn test_continue_on_failure_does_not_halt() {
+        let dir = tempfile::tempdir().unwrap();
+        let executor = PipelineExecutor::new(dir.path());
+
+        let definition = PipelineDefinition::from_yaml(
+            r#"
+steps:
+  - name: audit
+    command: sh
+    args: ["-c", "exit 1"]
+    continue_on_failure: true
+  - name: test
+    command: sh
+    args: ["-c", "echo tests passed"]
+"#,
+        )
+        .unwrap();
+
+        let result = executor
+            .run_steps(&definition.pipeline_steps().unwrap(), &[])
+            .await
+            .unwrap();
+        assert_eq!(result.steps.len(), 2);
+        assert!(!result.steps[0].success);
+        assert!(result.steps[1].output.contains("tests passed"));
+        assert!(result.success());
+    }
+
+    #[tokio::test]
+    async fn test_matrix_runs_every_combination() {
+        let dir = tempfile::tempdir().unwrap();
+        let executor = PipelineExecutor::new(dir.path());
+
+        let config = PipelineConfig::from_yaml(
+            r#"
+steps:
+  - shell: echo "$RUSTUP_TOOLCHAIN $CARGO_BUILD_TARGET"
+matrix:
+  toolchains: [stable, nightly]
+  targets: [x86_64-unknown-linux-gnu, aarch64-unknown-linux-gnu]
+"#,
+        )
+        .unwrap();
+        let steps: Vec<PipelineStep> = executor
+            .validated_steps(&config)
+            .unwrap()
+            .into_iter()
+            .map(PipelineStep::from)
+            .collect();
+
+        let result = executor
+            .run_matrix(&steps, config.matrix.unwrap().cells(), 2)
+            .await
+            .unwrap();
+        assert_eq!(result.cells.len(), 4);
+        assert!(result.success());
+        for (cell, result) in &result.cells {
+            let expected = format!(
+                "{} {}",
+                cell.toolchain.as_deref().unwrap(),
+                cell.target.as_deref().unwrap()
+            );
+            assert_eq!(result.steps.len(), 1);
+            assert_eq!(result.steps[0].output.trim(), expected);
+        }
+    }
+
+    #[test]
+    fn test_pipeline_test_report_merges_steps() {
+        let report = |tests| TestReport {
+            suites: vec![raibid_common::test_report::TestSuiteResult {
+                name: "suite".to_string(),
+                tests,
+                failures: 0,
+                errors: 0,
+                skipped: 0,
+                time_secs: 0.0,
+                cases: Vec::new(),
+            }],
+        };
+        let step = |test_report| StepResult {
+            exit_code: Some(0),
+            test_report,
+            ..StepResult::new(
+                "test".to_string(),
+                true,
+                Duration::ZERO,
+                String::new(),
+                false,
+            )
+        };
+
+        let result = PipelineResult {
+            steps: vec![step(None)],
+            cache_stats: None,
+            notifications: None,
+        };
+        assert_eq!(result.test_report(), None);
+
+        let result = PipelineResult {
+            steps: vec![step(Some(report(3))), step(None), step(Some(report(4)))],
+            cache_stats: None,
+            notifications: None,
+        };
+        assert_eq!(result.test_report().unwrap().tests(), 7);
+    }
+
+    #[test]
+    fn test_outdated_step_warns() {
+        let step = |success, outdated| StepResult {
+            exit_code: Some(0),
+            outdated,
+            ..StepResult::new(
+                "outdated".to_string(),
+                success,
+                Duration::ZERO,
+                String::new(),
+                false,
+            )
+        };
+        let report = |direct| OutdatedReport {
+            direct,
+            transitive: 0,
+        };
+
+        assert!(step(true, Some(report(2))).warning());
+        assert!(!step(true, Some(report(0))).warning());
+        assert!(!step(true, None).warning());
+        // Enforced with fail_on_outdated, the step fails instead
+        assert!(!step(false, Some(report(2))).warning());
+
+        let result = PipelineResult {
+            steps: vec![step(true, Some(report(1))), step(true, Some(report(3)))],
+            cache_stats: None,
+            notifications: None,
+        };
+        assert_eq!(result.outdated(), Some(report(3)));
+    }
+
+    #[tokio::test]
+    async fn test_step_timeout() {
+        let dir = tempfile::tempdir().unwrap();
+        let executor = PipelineExecutor::new(dir.path());
+
+        let definition = PipelineDefinition::from_yaml(
+            "steps:\n  - name: hang\n    command: sleep\n    args: [\"30\"]\n    timeout_secs: 1\n",
+        )
+        .unwrap();
+
+        let result = executor
+            .run_steps(&definition.pipeline_steps().unwrap(), &[])
+            .await
+            .unwrap();
+        assert!(!result.success());
+        assert_eq!(result.steps[0].exit_code, None);
+        assert!(result.steps[0].output.contains("timed out"));
+    }
+
+    #[tokio::test]
+    async fn test_default_step_timeout() {
+        let dir = tempfile::tempdir().unwrap();
+        let executor = PipelineExecutor::new(dir.path());
+
+        // The first step falls back to the 1 second default, the second
+        // overrides it
+        let definition = PipelineDefinition::from_yaml(
+            "default_step_timeout_secs: 1\nsteps:\n  - name: slow\n    command: sleep\n    args: [\"2\"]\n    continue_on_failure: true\n  - name: patient\n    command: sleep\n    args: [\"2\"]\n    timeout_secs: 10\n",
+        )
+        .unwrap();
+
+        let result = executor
+            .run_steps(&definition.pipeline_steps().unwrap(), &[])
+            .await
+            .unwrap();
+        assert!(!result.steps[0].success);
+        assert!(result.steps[0].output.contains("timed out after 1s"));
+        assert!(result.steps[1].success);
+    }
+
+    /// Run `yaml`'s steps as job `job-1`, cancelling it after half a second
+    async fn run_cancelled(yaml: &str) -> (PipelineResult, Duration) {
+        let dir = tempfile::tempdir().unwrap();
+        let cancellations = JobCancellations::new();
+        let executor = PipelineExecutor::new(dir.path())
+            .with_job_id("job-1")
+            .with_cancellation(cancellations.clone());
+        std::fs::create_dir_all(executor.job_workspace()).unwrap();
+        let steps = PipelineDefinition::from_yaml(yaml)
+            .unwrap()
+            .pipeline_steps()
+            .unwrap();
+
+        let started = Instant::now();
+        let run = tokio::spawn(async move { executor.run_steps(&steps, &[]).await });
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        cancellations.cancel("job-2");
+        cancellations.cancel("job-1");
+
+        let result = tokio::time::timeout(Duration::from_secs(20), run)
+            .await
+            .expect("cancelled job did not stop")
+            .unwrap()
+            .unwrap();
+        (result, started.elapsed())
+    }
+
+    #[tokio::test]
+    async fn test_cancel_running_step() {
+        let (result, elapsed) = run_cancelled(
+            "steps:\n  - name: echo\n    command: echo\n    args: [\"started\"]\n  - name: hang\n    command: sh\n    args: [\"-c\", \"echo building; exec sleep 30\"]\n  - name: never\n    command: echo\n    args: [\"unreachable\"]\n",
+        )
+        .await;
+
+        assert!(elapsed < CANCEL_GRACE_PERIOD, "took {:?}", elapsed);
+        assert!(!result.success());
+        assert!(result.cancelled());
+        assert_eq!(result.steps.len(), 2);
+        assert!(result.steps[0].success);
+        assert!(!result.steps[0].cancelled);
+
+        let hang = &result.steps[1];
+        assert!(hang.cancelled);
+        // Stopped by SIGTERM rather than exiting
+        assert_eq!(hang.exit_code, None);
+        assert!(hang.output.starts_with("building\n"), "{}", hang.output);
+        assert!(hang.output.contains("Step cancelled"));
+    }
+
+    #[tokio::test]
+    async fn test_cancel_kills_step_ignoring_sigterm() {
+        let (result, elapsed) = run_cancelled(
+            "steps:\n  - name: stubborn\n    command: sh\n    args: [\"-c\", \"trap '' TERM; while :; do sleep 0.1; done\"]\n",
+        )
+        .await;
+
+        assert!(elapsed >= CANCEL_GRACE_PERIOD, "took {:?}", elapsed);
+        assert!(result.steps[0].cancelled);
+        assert_eq!(result.steps[0].exit_code, None);
+    }
+
+    /// Create a git repository on branch `main` holding `.raibid.yaml`,
+    /// returning its path to clone from
+    fn git_repository(dir: &Path, config: &str) -> PathBuf {
+        let repo = dir.join("origin");
+        std::fs::create_dir_all(&repo).unwrap();
+        std::fs::write(repo.join(".raibid.yaml"), config).unwrap();
+        for args in [
+            &["init", "--quiet", "--initial-branch", "main"][..],
+            &["add", "."],
+            &[
+                "-c",
+                "user.name=raibid",
+                "-c",
+                "user.email=raibid@localhost",
+                "commit",
+                "--quiet",
+                "--message",
+                "Add pipeline",
+            ],
+        ] {
+            let status = std::process::Command::new("git")
+                .args(args)
+                .current_dir(&repo)
+                .status()
+                .unwrap();
+            assert!(status.success(), "git {:?} failed", args);
+        }
+        repo
+    }
+
+    fn trigger() -> JobTrigger {
+        raibid_common::jobs::JobBuilder::new("acme/app")
+            .branch("main")
+            .build()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_jobs_use_separate_workspaces() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = git_repository(dir.path(), "steps:\n  - shell: pwd\n");
+        let url = repo.to_string_lossy().to_string();
+        let workspaces = dir.path().join("workspaces");
+
+        let first = PipelineExecutor::new(&workspaces).with_job_id("job-1");
+        let second = PipelineExecutor::new(&workspaces).with_job_id("job-2");
+        assert_eq!(first.job_workspace(), workspaces.join("job-1"));
+        assert_ne!(first.job_workspace(), second.job_workspace());
+
+        let trigger = trigger();
+        let (a, b) = tokio::join!(
+            first.execute(&trigger, &url),
+            second.execute(&trigger, &url)
+        );
+        for (job_id, result) in [("job-1", a), ("job-2", b)] {
+            let ExecutionResult::Pipeline(result) = result.unwrap() else {
+                panic!("expected a single pipeline");
+            };
+            assert!(result.success());
+            let pwd = PathBuf::from(result.steps[0].output.trim());
+            assert!(
+                pwd.ends_with(Path::new("workspaces").join(job_id)),
+                "{:?}",
+                pwd
+            );
+            assert!(!workspaces.join(job_id).exists());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_keep_workspace_on_failure() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = git_repository(dir.path(), "steps:\n  - shell: exit 1\n");
+        let url = repo.to_string_lossy().to_string();
+
+        let removed = PipelineExecutor::new(dir.path()).with_job_id("job-1");
+        let result = removed.execute(&trigger(), &url).await.unwrap();
+        assert!(!result.success());
+        assert!(!removed.job_workspace().exists());
+
+        let kept = PipelineExecutor::new(dir.path())
+            .with_job_id("job-2")
+            .with_keep_workspace_on_failure(true);
+        let result = kept.execute(&trigger(), &url).await.unwrap();
+        assert!(!result.success());
+        assert!(kept.job_workspace().join(".raibid.yaml").exists());
+    }
+
+    #[tokio::test]
+    async fn test_invalid_pipeline_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = git_repository(dir.path(), "steps:\n  - frobnicate\n");
+        let url = repo.to_string_lossy().to_string();
+
+        let executor = PipelineExecutor::new(dir.path()).with_job_id("job-1");
+        let error = executor.execute(&trigger(), &url).await.unwrap_err();
+        assert!(error.is::<InvalidPipeline>(), "{:#}", error);
+        assert!(format!("{:#}", error).contains("unknown step 'frobnicate'"));
+
+        let missing = dir.path().join("missing").to_string_lossy().to_string();
+        let error = executor.execute(&trigger(), &missing).await.unwrap_err();
+        assert!(!error.is::<InvalidPipeline>(), "{:#}", error);
+    }
+
+    #[tokio::test]
+    async fn test_security_scan_with_mock_trivy() {
+        use crate::pipeline::scan::{tests::TRIVY_OUTPUT, ScanPolicy};
+        use raibid_common::scan::Severity;
+        use std::os::unix::fs::PermissionsExt;
+
+        // A trivy that reports TRIVY_OUTPUT and fails like a real scan
+        let dir = tempfile::tempdir().unwrap();
+        let bin = dir.path().join("bin");
+        std::fs::create_dir_all(&bin).unwrap();
+        let trivy = bin.join("trivy");
+        std::fs::write(
+            &trivy,
+            format!(
+                "#!/bin/sh\n[ \"$1\" = --version ] && exit 0\necho 'scanning' >&2\ncat <<'EOF'\n{}\nEOF\nexit 1\n",
+                TRIVY_OUTPUT
+            ),
+        )
+        .unwrap();
+        std::fs::set_permissions(&trivy, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let path = format!(
+            "{}:{}",
+            bin.display(),
+            std::env::var("PATH").unwrap_or_default()
+        );
+        let env = vec![("PATH".to_string(), path)];
+
+        let executor = PipelineExecutor::new(dir.path());
+        let steps = vec![PipelineStep::from(BuildStep::SecurityScan {
+            image: "registry.local/app:1.0".to_string(),
+            policy: ScanPolicy {
+                continue_on_failure: true,
+                ..Default::default()
+            },
+        })];
+
+        let result = executor.run_steps(&steps, &env).await.unwrap();
+        assert!(!result.steps[0].success);
+        assert!(result.success(), "a failed scan must not fail the pipeline");
+        assert!(result.steps[0].output.contains("1 CRITICAL, 3 HIGH"));
+
+        let scan = result.scan().unwrap();
+        assert_eq!(scan.image, "registry.local/app:1.0");
+        assert_eq!(scan.count(Severity::Critical), 1);
+        assert_eq!(scan.count(Severity::High), 3);
+    }
+
+    /// Environment putting a `cargo` running `script` first on the PATH
+    fn mock_cargo(dir: &Path, script: &str) -> Vec<(String, String)> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let bin = dir.join("bin");
+        std::fs::create_dir_all(&bin).unwrap();
+        let cargo = bin.join("cargo");
+        std::fs::write(&cargo, format!("#!/bin/sh\n{}", script)).unwrap();
+        std::fs::set_permissions(&cargo, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let path = format!(
+            "{}:{}",
+            bin.display(),
+            std::env::var("PATH").unwrap_or_default()
+        );
+        vec![("PATH".to_string(), path)]
+    }
+
+    #[tokio::test]
+    async fn test_udeps_with_mock_cargo_udeps() {
+        use crate::pipeline::udeps::tests::UDEPS_OUTPUT;
+
+        // A cargo-udeps that reports UDEPS_OUTPUT and fails like a real one
+        let dir = tempfile::tempdir().unwrap();
+        let env = mock_cargo(
+            dir.path(),
+            &format!(
+                "[ \"$1 $2\" = \"+nightly udeps\" ] || exit 101\n[ \"$3\" = --version ] && exit 0\necho '   Compiling raibid-agent' >&2\ncat <<'EOF'\n{}\nEOF\nexit 1\n",
+                UDEPS_OUTPUT
+            ),
+        );
+        let executor = PipelineExecutor::new(dir.path());
+        let udeps = |fail_on_unused_deps| {
+            vec![
+                PipelineStep::from(BuildStep::Udeps {
+                    fail_on_unused_deps,
+                }),
+                PipelineStep::from(BuildStep::Shell {
+                    script: "echo after".to_string(),
+                }),
+            ]
+        };
+
+        let result = executor.run_steps(&udeps(false), &env).await.unwrap();
+        assert!(result.success());
+        assert_eq!(
+            result.steps.len(),
+            2,
+            "a warning must not stop the pipeline"
+        );
+        assert!(result.steps[0].warning());
+        assert!(result.steps[0]
+            .output
+            .contains("Dependencies: 3 unused (mockito, rand, regex)"));
+        assert_eq!(
+            result.unused_deps().unwrap().crates,
+            vec!["mockito", "rand", "regex"]
+        );
+
+        let result = executor.run_steps(&udeps(true), &env).await.unwrap();
+        assert!(!result.success());
+        assert_eq!(result.steps.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_udeps_skipped_without_nightly() {
+        let dir = tempfile::tempdir().unwrap();
+        let env = mock_cargo(
+            dir.path(),
+            "echo \"error: toolchain 'nightly' is not installed\" >&2\nexit 1\n",
+        );
+        let executor = PipelineExecutor::new(dir.path());
+        let steps = vec![PipelineStep::from(BuildStep::Udeps {
+            fail_on_unused_deps: true,
+        })];
+
+        let result = executor.run_steps(&steps, &env).await.unwrap();
+        assert!(result.success());
+        assert!(result.steps[0].output.starts_with("Skipped"));
+        assert_eq!(result.unused_deps(), None);
+    }
+
+    #[tokio::test]
+    async fn test_udeps_finds_unused_dependency() {
+        let dir = tempfile::tempdir().unwrap();
+        let workspace = dir.path().join("app");
+        // Needs cargo-udeps on the nightly toolchain
+        std::fs::create_dir_all(&workspace).unwrap();
+        if !udeps_available(&workspace, &[]).await {
+            return;
+        }
+
+        // A path dependency keeps the project buildable offline
+        for (name, manifest_deps) in [
+            ("helper", ""),
+            ("app", "helper = { path = \"../helper\" }\n"),
+        ] {
+            let root = dir.path().join(name);
+            std::fs::create_dir_all(root.join("src")).unwrap();
+            std::fs::write(
+                root.join("Cargo.toml"),
+                format!(
+                    "[package]\nname = \"{}\"\nversion = \"0.1.0\"\nedition = \"2021\"\n\n[dependencies]\n{}",
+                    name, manifest_deps
+                ),
+            )
+            .unwrap();
+            std::fs::write(root.join("src/lib.rs"), "pub fn answer() -> u32 { 42 }\n").unwrap();
+        }
+
+        let executor = PipelineExecutor::new(&workspace);
+        let steps = vec![PipelineStep::from(BuildStep::Udeps {
+            fail_on_unused_deps: false,
+        })];
+        let result = executor.run_steps(&steps, &[]).await.unwrap();
+        assert!(result.steps[0].warning(), "{}", result.steps[0].output);
+        assert_eq!(result.unused_deps().unwrap().crates, vec!["helper"]);
+    }
+
+    #[tokio::test]
+    async fn test_sccache_hits_on_rebuild() {
+        // Needs sccache and cargo on the PATH
+        if std::process::Command::new("sccache")
+            .arg("--version")
+            .output()
+            .is_err()
+        {
+            return;
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        let workspace = dir.path().join("repo");
+        std::fs::create_dir_all(workspace.join("src")).unwrap();
+        std::fs::write(
+            workspace.join("Cargo.toml"),
+            "[package]\nname = \"sccache-test\"\nversion = \"0.1.0\"\nedition = \"2021\"\n",
+        )
+        .unwrap();
+        std::fs::write(
+            workspace.join("src/lib.rs"),
+            "pub fn answer() -> u32 { 42 }\n",
+        )
+        .unwrap();
+
+        let executor = PipelineExecutor::new(&workspace).with_sccache(SccacheConfig {
+            local_cache_dir: dir.path().join("cache"),
+            ..Default::default()
+        });
+        // Cleaning after each check makes the next one compile again
+        let steps: Vec<PipelineStep> = vec![
+            BuildStep::Check.into(),
+            BuildStep::Shell {
+                script: "cargo clean".to_string(),
+            }
+            .into(),
+        ];
+
+        let first = executor.run_steps_with_sccache(&steps).await.unwrap();
+        let second = executor.run_steps_with_sccache(&steps).await.unwrap();
+        assert!(first.success() && second.success());
+        assert!(second.cache_stats.unwrap().cache_hits > first.cache_stats.unwrap().cache_hits);
+    }
+
+    #[tokio::test]
+    async fn test_miri_detects_undefined_behavior() {
+        let dir = tempfile::tempdir().unwrap();
+        // Needs a nightly toolchain with Miri installed
+        if !crate::pipeline::miri::miri_available(dir.path(), &[]).await {
+            return;
+        }
+
+        std::fs::create_dir_all(dir.path().join("src")).unwrap();
+        std::fs::write(
+            dir.path().join("Cargo.toml"),
+            "[package]\nname = \"miri-test\"\nversion = \"0.1.0\"\nedition = \"2021\"\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("src/lib.rs"),
+            r#"
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn reads_past_end() {
+        let values = [1u8, 2, 3, 4];
+        let p = values.as_ptr();
+        let value = unsafe { *p.add(4) };
+        assert_ne!(value, 0);
+    }
+}
+"#,
+        )
+        .unwrap();
+
+        let executor = PipelineExecutor::new(dir.path());
+        let steps = vec![PipelineStep::from(BuildStep::Miri { strict: true })];
+
+        let result = executor.run_steps(&steps, &[]).await.unwrap();
+        assert!(!result.success());
+        assert!(result.steps[0].output.contains("Undefined Behavior"));
+        assert!(result.steps[0].output.contains("Miri found"));
+    }
+}
@@ -0,0 +1,382 @@
+//! Repository checkout
+//!
+//! The executor clones each job's repository with `git clone --branch`,
+//! shallowly (`--depth 1`) unless `use_shallow_clone` is off. A requested
+//! commit missing from a shallow clone is fetched on its own before it is
+//! checked out.
+//!
+//! With `sparse_paths`, the clone skips blobs and the working tree, then
+//! `git sparse-checkout init --cone` and `git sparse-checkout set <paths>`
+//! limit the checkout to those directories, so only their files are fetched.
+//! Files at the top of the repository, such as `.raibid.yaml`, are always
+//! checked out.
+//!
+//! Every git command is killed after `clone_timeout_secs`. A clone that fails
+//! with a network error is retried with the backoff of `retry`; other
+//! failures, timeouts included, are not.
+
+use anyhow::{Context, Result};
+use std::path::Path;
+use std::time::Duration;
+use thiserror::Error;
+use tokio::process::Command;
+use tracing::{info, warn};
+
+use raibid_common::infrastructure::RetryConfig;
+use raibid_common::jobs::JobTrigger;
+
+/// git errors caused by the network rather than the repository
+const NETWORK_ERRORS: &[&str] = &[
+    "could not resolve host",
+    "connection refused",
+    "connection timed out",
+    "connection reset",
+    "failed to connect",
+    "unable to access",
+    "the remote end hung up unexpectedly",
+    "early eof",
+    "rpc failed",
+];
+
+/// How repositories are cloned
+#[derive(Debug, Clone)]
+pub struct GitConfig {
+    /// Directories to check out instead of the whole repository
+    pub sparse_paths: Vec<String>,
+    /// Clone only the tip of the branch
+    pub use_shallow_clone: bool,
+    /// Seconds before a git command is killed
+    pub clone_timeout_secs: u64,
+    /// Backoff for clones failing with a network error
+    pub retry: RetryConfig,
+}
+
+impl Default for GitConfig {
+    fn default() -> Self {
+        Self {
+            sparse_paths: Vec::new(),
+            use_shallow_clone: true,
+            clone_timeout_secs: 600,
+            retry: RetryConfig::default(),
+        }
+    }
+}
+
+/// Why a git command failed
+#[derive(Debug, Error)]
+pub enum GitError {
+    #[error("Failed to run git: {0}")]
+    Spawn(#[from] std::io::Error),
+
+    #[error("git timed out after {0}s")]
+    Timeout(u64),
+
+    #[error("git failed: {0}")]
+    Failed(String),
+}
+
+impl GitError {
+    /// Whether the command failed to reach the remote
+    pub fn is_network(&self) -> bool {
+        match self {
+            GitError::Failed(stderr) => {
+                let stderr = stderr.to_lowercase();
+                NETWORK_ERRORS.iter().any(|error| stderr.contains(error))
+            }
+            _ => false,
+        }
+    }
+}
+
+impl GitConfig {
+    /// Command cloning `branch` of `repo_url` into `dest`
+    pub fn clone_command(&self, branch: &str, repo_url: &str, dest: &Path) -> Command {
+        let mut cmd = Command::new("git");
+        cmd.arg("clone").arg("--branch").arg(branch);
+        if self.use_shallow_clone {
+            cmd.arg("--depth").arg("1");
+        }
+        if !self.sparse_paths.is_empty() {
+            cmd.arg("--filter=blob:none").arg("--no-checkout");
+        }
+        cmd.arg(repo_url).arg(dest);
+        cmd
+    }
+
+    /// Commands limiting the checkout in `dest` to `sparse_paths`, none when
+    /// the whole repository is checked out
+    pub fn sparse_checkout_commands(&self, dest: &Path) -> Vec<Command> {
+        if self.sparse_paths.is_empty() {
+            return Vec::new();
+        }
+
+        let mut init = git(dest);
+        init.arg("sparse-checkout").arg("init").arg("--cone");
+        let mut set = git(dest);
+        set.arg("sparse-checkout")
+            .arg("set")
+            .args(&self.sparse_paths);
+        vec![init, set]
+    }
+
+    /// Clone the repository of `trigger` into `dest` and check out the
+    /// requested branch and commit
+    pub async fn checkout(&self, trigger: &JobTrigger, repo_url: &str, dest: &Path) -> Result<()> {
+        self.clone_repository(&trigger.branch, repo_url, dest)
+            .await
+            .with_context(|| format!("Failed to clone {}", trigger.repo))?;
+
+        for mut cmd in self.sparse_checkout_commands(dest) {
+            self.run(&mut cmd)
+                .await
+                .context("Failed to set up sparse checkout")?;
+        }
+
+        match trigger.commit {
+            Some(ref commit) => self
+                .checkout_commit(commit, dest)
+                .await
+                .with_context(|| format!("Failed to check out commit {}", commit)),
+            // A sparse clone has no working tree yet
+            None if !self.sparse_paths.is_empty() => self
+                .run(git(dest).arg("checkout").arg(&trigger.branch))
+                .await
+                .with_context(|| format!("Failed to check out {}", trigger.branch)),
+            None => Ok(()),
+        }
+    }
+
+    /// Clone, retrying network failures after removing what was cloned
+    async fn clone_repository(
+        &self,
+        branch: &str,
+        repo_url: &str,
+        dest: &Path,
+    ) -> Result<(), GitError> {
+        let mut attempt = 0;
+        loop {
+            let result = self
+                .run(&mut self.clone_command(branch, repo_url, dest))
+                .await;
+            match result {
+                Err(e) if e.is_network() && attempt + 1 < self.retry.max_attempts => {
+                    attempt += 1;
+                    let delay = self.retry.delay_for_attempt(attempt);
+                    warn!(
+                        "Clone of {} failed, retrying in {:?}: {}",
+                        repo_url, delay, e
+                    );
+                    remove_partial_clone(dest).await;
+                    tokio::time::sleep(delay).await;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Check out `commit`, fetching it first if a shallow clone lacks it
+    async fn checkout_commit(&self, commit: &str, dest: &Path) -> Result<(), GitError> {
+        let checkout = self.run(git(dest).arg("checkout").arg(commit)).await;
+        if checkout.is_ok() || !self.use_shallow_clone {
+            return checkout;
+        }
+
+        info!("Fetching commit {} missing from the shallow clone", commit);
+        self.run(
+            git(dest)
+                .arg("fetch")
+                .arg("--depth")
+                .arg("1")
+                .arg("origin")
+                .arg(commit),
+        )
+        .await?;
+        self.run(git(dest).arg("checkout").arg(commit)).await
+    }
+
+    /// Run a git command, killing it after `clone_timeout_secs`
+    async fn run(&self, cmd: &mut Command) -> Result<(), GitError> {
+        run_with_timeout(cmd, self.clone_timeout_secs).await
+    }
+}
+
+/// git command run in `dir`
+fn git(dir: &Path) -> Command {
+    let mut cmd = Command::new("git");
+    cmd.current_dir(dir);
+    cmd
+}
+
+/// Run `cmd`, killing it if it takes longer than `timeout_secs`
+async fn run_with_timeout(cmd: &mut Command, timeout_secs: u64) -> Result<(), GitError> {
+    let output = tokio::time::timeout(
+        Duration::from_secs(timeout_secs),
+        cmd.kill_on_drop(true).output(),
+    )
+    .await
+    .map_err(|_| GitError::Timeout(timeout_secs))??;
+
+    if !output.status.success() {
+        return Err(GitError::Failed(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Remove what a failed clone left in `dest`
+async fn remove_partial_clone(dest: &Path) {
+    if let Err(e) = tokio::fs::remove_dir_all(dest).await {
+        if e.kind() != std::io::ErrorKind::NotFound {
+            warn!("Failed to remove partial clone {}: {}", dest.display(), e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::OsStr;
+    use std::path::PathBuf;
+    use std::time::Instant;
+
+    fn args(cmd: &Command) -> Vec<&OsStr> {
+        cmd.as_std().get_args().collect()
+    }
+
+    #[test]
+    fn test_clone_command() {
+        let dest = Path::new("/tmp/job-1");
+        let config = GitConfig::default();
+        assert_eq!(
+            args(&config.clone_command("main", "http://git/acme/app.git", dest)),
+            [
+                "clone",
+                "--branch",
+                "main",
+                "--depth",
+                "1",
+                "http://git/acme/app.git",
+                "/tmp/job-1"
+            ]
+        );
+
+        let config = GitConfig {
+            use_shallow_clone: false,
+            sparse_paths: vec!["crates/agent".to_string()],
+            ..GitConfig::default()
+        };
+        assert_eq!(
+            args(&config.clone_command("main", "http://git/acme/app.git", dest)),
+            [
+                "clone",
+                "--branch",
+                "main",
+                "--filter=blob:none",
+                "--no-checkout",
+                "http://git/acme/app.git",
+                "/tmp/job-1"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_sparse_checkout_commands() {
+        let dest = Path::new("/tmp/job-1");
+        assert!(GitConfig::default()
+            .sparse_checkout_commands(dest)
+            .is_empty());
+
+        let config = GitConfig {
+            sparse_paths: vec!["crates/agent".to_string(), "docs".to_string()],
+            ..GitConfig::default()
+        };
+        let commands = config.sparse_checkout_commands(dest);
+        assert_eq!(commands.len(), 2);
+        assert_eq!(args(&commands[0]), ["sparse-checkout", "init", "--cone"]);
+        assert_eq!(
+            args(&commands[1]),
+            ["sparse-checkout", "set", "crates/agent", "docs"]
+        );
+        assert_eq!(commands[1].as_std().get_current_dir(), Some(dest));
+    }
+
+    #[test]
+    fn test_network_errors() {
+        let network = GitError::Failed(
+            "fatal: unable to access 'http://git/acme/app.git/': Could not resolve host: git"
+                .to_string(),
+        );
+        assert!(network.is_network());
+
+        let missing = GitError::Failed(
+            "fatal: Remote branch feature not found in upstream origin".to_string(),
+        );
+        assert!(!missing.is_network());
+        assert!(!GitError::Timeout(1).is_network());
+    }
+
+    #[tokio::test]
+    async fn test_timeout_kills_command() {
+        let started = Instant::now();
+        let err = run_with_timeout(Command::new("sleep").arg("10"), 1)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, GitError::Timeout(1)), "{}", err);
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    /// Create a git repository on branch `main` with files in `app/` and
+    /// `docs/`, returning its URL
+    fn git_repository(dir: &Path) -> String {
+        let repo = dir.join("origin");
+        for file in [".raibid.yaml", "app/main.rs", "docs/index.md"] {
+            let path = repo.join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, file).unwrap();
+        }
+        for args in [
+            &["init", "--quiet", "--initial-branch", "main"][..],
+            &["add", "."],
+            &[
+                "-c",
+                "user.name=raibid",
+                "-c",
+                "user.email=raibid@localhost",
+                "commit",
+                "--quiet",
+                "--message",
+                "Add files",
+            ],
+        ] {
+            let status = std::process::Command::new("git")
+                .args(args)
+                .current_dir(&repo)
+                .status()
+                .unwrap();
+            assert!(status.success(), "git {:?} failed", args);
+        }
+        format!("file://{}", repo.display())
+    }
+
+    #[tokio::test]
+    async fn test_sparse_checkout() {
+        let dir = tempfile::tempdir().unwrap();
+        let url = git_repository(dir.path());
+        let dest: PathBuf = dir.path().join("job-1");
+        let trigger = raibid_common::jobs::JobBuilder::new("acme/app")
+            .branch("main")
+            .build()
+            .unwrap();
+
+        let config = GitConfig {
+            sparse_paths: vec!["app".to_string()],
+            ..GitConfig::default()
+        };
+        config.checkout(&trigger, &url, &dest).await.unwrap();
+
+        assert!(dest.join("app/main.rs").exists());
+        assert!(dest.join(".raibid.yaml").exists());
+        assert!(!dest.join("docs").exists());
+    }
+}
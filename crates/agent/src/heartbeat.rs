@@ -0,0 +1,295 @@
+//! Agent registration and heartbeats
+//!
+//! When an API URL is configured the agent registers with raibid-server on
+//! startup and then sends a heartbeat every `heartbeat_interval_secs`, so the
+//! server can tell which agents are alive. If the server no longer knows the
+//! agent (e.g. it restarted), the agent registers again.
+//!
+//! Heartbeats report how many jobs the agent is running. When the server
+//! answers that the agent is draining, [`HeartbeatClient::drain_requested`]
+//! is set and the agent stops claiming jobs.
+
+use anyhow::{anyhow, Context, Result};
+use reqwest::{Client, RequestBuilder, StatusCode};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+
+use raibid_common::agents::{AgentHeartbeat, AgentRegistration, HeartbeatResponse};
+use raibid_common::auth::API_KEY_HEADER;
+
+use crate::{AgentConfig, AgentType};
+
+/// Request timeout for registration and heartbeat calls
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Client for the agent endpoints of the raibid-server API
+#[derive(Debug, Clone)]
+pub struct HeartbeatClient {
+    base_url: String,
+    api_key: Option<String>,
+    client: Client,
+    registration: AgentRegistration,
+    /// Jobs running, reported with each heartbeat
+    active_jobs: Arc<AtomicUsize>,
+    /// Whether the server asked the agent to drain
+    draining: Arc<watch::Sender<bool>>,
+}
+
+impl HeartbeatClient {
+    /// Create a client for `base_url` registering as `registration`
+    pub fn new(base_url: &str, registration: AgentRegistration) -> Result<Self> {
+        let client = Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .context("Failed to build HTTP client")?;
+
+        Ok(Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            api_key: None,
+            client,
+            registration,
+            active_jobs: Arc::new(AtomicUsize::new(0)),
+            draining: Arc::new(watch::channel(false).0),
+        })
+    }
+
+    /// Report the number of jobs in `counter` with each heartbeat
+    pub fn with_active_jobs(mut self, counter: Arc<AtomicUsize>) -> Self {
+        self.active_jobs = counter;
+        self
+    }
+
+    /// Becomes `true` once a heartbeat response says the agent is draining
+    pub fn drain_requested(&self) -> watch::Receiver<bool> {
+        self.draining.subscribe()
+    }
+
+    /// Send `key` in the API key header with every request
+    pub fn with_api_key(mut self, key: impl Into<String>) -> Self {
+        self.api_key = Some(key.into());
+        self
+    }
+
+    /// Client for the server in `config`, or `None` if no API URL is set
+    pub fn from_config(config: &AgentConfig) -> Result<Option<Self>> {
+        let Some(ref url) = config.api_url else {
+            return Ok(None);
+        };
+
+        let registration = AgentRegistration {
+            agent_id: config.agent_id.clone(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            capabilities: config.capabilities.clone(),
+            pod_name: config.pod_name.clone(),
+            drain_timeout_secs: config.drain_timeout_secs,
+        };
+        let client = Self::new(url, registration)?;
+        Ok(Some(match config.api_key {
+            Some(ref key) => client.with_api_key(key),
+            None => client,
+        }))
+    }
+
+    fn post(&self, path: &str) -> RequestBuilder {
+        let request = self.client.post(format!("{}{}", self.base_url, path));
+        match &self.api_key {
+            Some(key) => request.header(API_KEY_HEADER, key),
+            None => request,
+        }
+    }
+
+    /// Register the agent with the server
+    pub async fn register(&self) -> Result<()> {
+        let response = self
+            .post("/api/agents/register")
+            .json(&self.registration)
+            .send()
+            .await
+            .with_context(|| format!("Failed to connect to API server at {}", self.base_url))?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(anyhow!("Registration failed ({}): {}", status, body.trim()));
+        }
+
+        info!(
+            "Registered agent {} with {}",
+            self.registration.agent_id, self.base_url
+        );
+        Ok(())
+    }
+
+    /// Send a heartbeat, registering again if the server does not know the agent
+    pub async fn heartbeat(&self) -> Result<()> {
+        let path = format!("/api/agents/{}/heartbeat", self.registration.agent_id);
+        let heartbeat = AgentHeartbeat {
+            active_jobs: self.active_jobs.load(Ordering::Acquire) as u32,
+        };
+        let response = self
+            .post(&path)
+            .json(&heartbeat)
+            .send()
+            .await
+            .with_context(|| format!("Failed to connect to API server at {}", self.base_url))?;
+
+        match response.status() {
+            status if status.is_success() => {
+                debug!("Heartbeat sent ({} active jobs)", heartbeat.active_jobs);
+                // Older servers answer without a body
+                let reply: HeartbeatResponse = response.json().await.unwrap_or_default();
+                if reply.draining && !self.draining.send_replace(true) {
+                    info!("Server asked agent {} to drain", self.registration.agent_id);
+                }
+                Ok(())
+            }
+            StatusCode::NOT_FOUND => {
+                info!("Server does not know this agent, registering again");
+                self.register().await
+            }
+            status => {
+                let body = response.text().await.unwrap_or_default();
+                Err(anyhow!("Heartbeat failed ({}): {}", status, body.trim()))
+            }
+        }
+    }
+
+    /// Register, then send heartbeats every `interval` until the task is aborted
+    ///
+    /// Failures are logged and retried on the next tick; losing contact with
+    /// the server never stops the agent from processing jobs.
+    pub fn spawn(self, interval: Duration) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut registered = false;
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+
+                let result = if registered {
+                    self.heartbeat().await
+                } else {
+                    self.register().await
+                };
+                match result {
+                    Ok(()) => registered = true,
+                    Err(e) => warn!("{:#}", e),
+                }
+            }
+        })
+    }
+}
+
+impl AgentType {
+    /// Capabilities of agents of this type, the default of
+    /// [`AgentConfig::capabilities`]
+    pub fn capabilities(&self) -> Vec<String> {
+        match self {
+            AgentType::Rust => vec!["rust".to_string()],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn client(url: &str) -> HeartbeatClient {
+        let config = AgentConfig {
+            agent_id: "agent-1".to_string(),
+            api_url: Some(url.to_string()),
+            api_key: Some("rbd_secret".to_string()),
+            drain_timeout_secs: 600,
+            pod_name: Some("raibid-ci-agent-7d9f-x2k4p".to_string()),
+            ..Default::default()
+        };
+        HeartbeatClient::from_config(&config).unwrap().unwrap()
+    }
+
+    #[test]
+    fn test_from_config_without_api_url() {
+        let config = AgentConfig::default();
+        assert!(HeartbeatClient::from_config(&config).unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_register() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/api/agents/register")
+            .match_header(API_KEY_HEADER, "rbd_secret")
+            .match_body(mockito::Matcher::PartialJsonString(
+                r#"{
+                    "agent_id": "agent-1",
+                    "capabilities": ["rust"],
+                    "pod_name": "raibid-ci-agent-7d9f-x2k4p",
+                    "drain_timeout_secs": 600
+                }"#
+                .to_string(),
+            ))
+            .with_status(201)
+            .create_async()
+            .await;
+
+        client(&server.url()).register().await.unwrap();
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_heartbeat_reregisters_unknown_agent() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("POST", "/api/agents/agent-1/heartbeat")
+            .with_status(404)
+            .create_async()
+            .await;
+        let register = server
+            .mock("POST", "/api/agents/register")
+            .with_status(201)
+            .create_async()
+            .await;
+
+        client(&server.url()).heartbeat().await.unwrap();
+        register.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_heartbeat_error() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("POST", "/api/agents/agent-1/heartbeat")
+            .with_status(401)
+            .with_body(r#"{"error":"Invalid API key"}"#)
+            .create_async()
+            .await;
+
+        let err = client(&server.url()).heartbeat().await.unwrap_err();
+        assert!(err.to_string().contains("401"));
+    }
+
+    #[tokio::test]
+    async fn test_heartbeat_reports_jobs_and_drain() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/api/agents/agent-1/heartbeat")
+            .match_body(mockito::Matcher::Json(
+                serde_json::json!({"active_jobs": 2}),
+            ))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"draining":true}"#)
+            .create_async()
+            .await;
+
+        let client = client(&server.url()).with_active_jobs(Arc::new(AtomicUsize::new(2)));
+        let drain = client.drain_requested();
+        assert!(!*drain.borrow());
+
+        client.heartbeat().await.unwrap();
+        mock.assert_async().await;
+        assert!(*drain.borrow());
+    }
+}
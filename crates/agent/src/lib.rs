@@ -1,23 +1,141 @@
 //! raibid-agent
 //!
 //! CI agent runner that polls the job queue and executes builds.
-//! This crate will handle:
-//! - Job polling from Redis Streams
+//! This crate handles:
+//! - Job polling from Redis Streams, including recovery of jobs orphaned
+//!   by crashed agents
 //! - Build execution in isolated environments
-//! - Cache management for dependencies
-//! - Result reporting back to the server
+//! - Shallow and sparse clones of job repositories
+//! - Stopping jobs cancelled through the server while they run
+//! - Job status tracking and automatic retries of failed builds, with a dead
+//!   letter stream for jobs that fail every retry
+//! - Registration and heartbeats with the server, and draining when it asks
+//! - Publishing the agent's idle/busy status to the server over Redis Pub/Sub
+//! - Compilation caching with sccache
+//! - Matrix builds across toolchains and targets
+//! - Cross-compilation of release binaries with cross-rs
+//! - Trimming and pruning of job log streams
+//! - Storage of long step output in Redis, S3 or on disk
+//! - Slack notifications when jobs finish
 //!
-//! ## Status: Placeholder
-//! This crate is a placeholder for future implementation.
+//! Planned:
+//! - Result reporting back to the server
 
 #![allow(dead_code)]
 
+pub mod cancellation;
+pub mod config;
+pub mod consumer;
+pub mod executor;
+pub mod git;
+pub mod heartbeat;
+pub mod log_retention;
+pub mod log_storage;
+pub mod notifications;
+pub mod pipeline;
+pub mod retry;
+pub mod sccache;
+pub mod status;
+
+pub use cancellation::{JobCancellation, JobCancellations};
+pub use config::{MatrixCell, MatrixConfig, PipelineConfig, PipelineConfigError, StepDefinition};
+pub use consumer::{run_jobs, ClaimedJob, JobConsumer, JobHandler, JobOutcome, JobQueue};
+pub use executor::{
+    ExecutionResult, InvalidPipeline, MatrixPipelineResult, PipelineExecutor, PipelineResult,
+    StepResult,
+};
+pub use git::GitConfig;
+pub use heartbeat::HeartbeatClient;
+pub use log_retention::LogRetentionConfig;
+pub use log_storage::{LogBackend, LogStorageConfig};
+pub use notifications::NotificationConfig;
+pub use pipeline::config::PipelineDefinition;
+pub use pipeline::{build_command, BuildStep, PipelineStep};
+pub use retry::{RedisJobStore, RetryPolicy, RetryingHandler};
+pub use sccache::SccacheConfig;
+pub use status::{RedisStatusPublisher, StatusPublisher, StatusReportingHandler};
+
 use anyhow::Result;
+use async_trait::async_trait;
+use log_retention::trim_job_log;
+use notifications::notify_slack;
+use pipeline::bench::store_benchmarks;
+use pipeline::coverage::store_coverage;
+use pipeline::cross::{store_artifacts, store_cross_results};
+use pipeline::junit::store_test_report;
+use pipeline::matrix::store_matrix_results;
+use pipeline::outdated::store_outdated;
+use pipeline::scan::store_scan_report;
+use pipeline::udeps::store_unused_deps;
+use raibid_common::agents::{AgentStatus, DEFAULT_DRAIN_TIMEOUT_SECS};
+use raibid_common::jobs::{JobTrigger, QueuedJob};
+use redis::aio::MultiplexedConnection;
+use sccache::store_cache_stats;
+use status::publish_status;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::watch;
+use tracing::{error, info, warn};
 
 /// Agent configuration
 pub struct AgentConfig {
+    /// Unique agent ID, also used as the consumer name in the worker group
     pub agent_id: String,
     pub agent_type: AgentType,
+    /// What the agent can build with (e.g. `rust`, `docker`, `gpu`),
+    /// reported on registration; jobs requiring anything else are left for
+    /// other agents
+    pub capabilities: Vec<String>,
+    /// Redis connection URL
+    pub redis_url: String,
+    /// Base name of the job streams; jobs of each priority are queued on
+    /// `<job_stream>:high`, `<job_stream>:normal` and `<job_stream>:low`
+    pub job_stream: String,
+    /// Consumer group agents read the job stream with
+    pub consumer_group: String,
+    /// Base URL repositories are cloned from (`<git_base_url>/<owner>/<name>.git`)
+    pub git_base_url: String,
+    /// Directory job workspaces are created in, one `<job_id>` subdirectory
+    /// per job
+    pub workspace_dir: PathBuf,
+    /// Keep the workspace of failed builds instead of removing it
+    pub keep_workspace_on_failure: bool,
+    /// How often to reclaim jobs orphaned by crashed agents
+    pub orphan_recovery_interval_secs: u64,
+    /// How long in milliseconds a job message stays pending before another
+    /// agent reclaims it
+    pub stale_message_threshold_ms: u64,
+    /// Maximum number of jobs run at the same time
+    pub max_concurrent_jobs: usize,
+    /// How many times a failed build is queued again
+    pub max_retries: u32,
+    /// Wait before the first retry in milliseconds, doubled for each later one
+    pub retry_backoff_ms: u64,
+    /// Approximate number of jobs kept in the dead letter stream
+    pub dead_letter_maxlen: usize,
+    /// raibid-server URL to register with; the agent runs unregistered when
+    /// unset
+    pub api_url: Option<String>,
+    /// API key sent with registration and heartbeat requests
+    pub api_key: Option<String>,
+    /// How often to send a heartbeat to the server
+    pub heartbeat_interval_secs: u64,
+    /// How long a restart waits for the agent's running jobs to finish,
+    /// reported on registration
+    pub drain_timeout_secs: u64,
+    /// Kubernetes pod running the agent, reported on registration so the
+    /// server can restart it; the container hostname by default
+    pub pod_name: Option<String>,
+    /// How job repositories are cloned
+    pub git: GitConfig,
+    /// sccache server and storage for pipelines with `use_sccache` set
+    pub sccache: SccacheConfig,
+    /// How much job output is kept in Redis
+    pub log_retention: LogRetentionConfig,
+    /// Where step output too long to keep inline is stored
+    pub log_storage: LogStorageConfig,
 }
 
 /// Type of CI agent
@@ -32,30 +150,337 @@ impl Default for AgentConfig {
         Self {
             agent_id: uuid::Uuid::new_v4().to_string(),
             agent_type: AgentType::Rust,
+            capabilities: AgentType::Rust.capabilities(),
+            redis_url: "redis://127.0.0.1:6379".to_string(),
+            job_stream: "raibid:jobs".to_string(),
+            consumer_group: "raibid-workers".to_string(),
+            git_base_url: "http://gitea.raibid-ci.svc.cluster.local:3000".to_string(),
+            workspace_dir: std::env::temp_dir().join("raibid-agent"),
+            keep_workspace_on_failure: false,
+            orphan_recovery_interval_secs: 60,
+            stale_message_threshold_ms: 300_000,
+            max_concurrent_jobs: 1,
+            max_retries: 0,
+            retry_backoff_ms: 5000,
+            dead_letter_maxlen: retry::DEFAULT_DEAD_LETTER_MAXLEN,
+            api_url: None,
+            api_key: None,
+            heartbeat_interval_secs: 15,
+            drain_timeout_secs: DEFAULT_DRAIN_TIMEOUT_SECS,
+            pod_name: std::env::var("HOSTNAME").ok(),
+            git: GitConfig::default(),
+            sccache: SccacheConfig::default(),
+            log_retention: LogRetentionConfig::default(),
+            log_storage: LogStorageConfig::default(),
         }
     }
 }
 
 /// Start the CI agent
-pub async fn start_agent(_config: AgentConfig) -> Result<()> {
-    // Placeholder implementation
-    // Will be implemented in a future issue
-    anyhow::bail!("Agent implementation pending")
+///
+/// Registers with the server when an API URL is configured, recovers jobs
+/// orphaned by crashed agents, starts pruning old job logs, then processes up
+/// to `max_concurrent_jobs` jobs at a time until Ctrl-C or SIGTERM, waiting
+/// for in-flight jobs to finish before returning. When the server asks the
+/// agent to drain, it stops claiming jobs the same way but keeps running until
+/// it is stopped. The agent's idle, busy and finally offline status is
+/// published on the agent events channel.
+pub async fn start_agent(config: AgentConfig) -> Result<()> {
+    let mut consumer = JobConsumer::connect(&config).await?;
+
+    let running_jobs = Arc::new(AtomicUsize::new(0));
+    let heartbeat_client = HeartbeatClient::from_config(&config)?
+        .map(|client| client.with_active_jobs(running_jobs.clone()));
+    let drain = heartbeat_client
+        .as_ref()
+        .map(HeartbeatClient::drain_requested);
+    let heartbeat = heartbeat_client
+        .map(|client| client.spawn(Duration::from_secs(config.heartbeat_interval_secs)));
+
+    let pruner = config.log_retention.spawn_pruner(consumer.connection());
+
+    let cancellations = JobCancellations::new();
+    let canceller = cancellations.spawn_subscriber(&config.redis_url);
+
+    consumer
+        .reclaim_stale_messages(config.stale_message_threshold_ms)
+        .await?;
+
+    info!(
+        "Agent {} waiting for jobs on '{}:*' ({} concurrent)",
+        consumer.consumer_id(),
+        config.job_stream,
+        config.max_concurrent_jobs
+    );
+
+    let publisher = RedisStatusPublisher::new(consumer.connection());
+    publish_status(&publisher, consumer.consumer_id(), AgentStatus::Idle).await;
+
+    let pipeline = RetryingHandler::new(
+        PipelineJobHandler {
+            git_base_url: config.git_base_url.clone(),
+            workspace_dir: config.workspace_dir.clone(),
+            keep_workspace_on_failure: config.keep_workspace_on_failure,
+            git: config.git.clone(),
+            sccache: config.sccache.clone(),
+            max_log_entries: config.log_retention.max_log_entries,
+            log_storage: config.log_storage.clone(),
+            cancellations,
+            server_url: config.api_url.clone(),
+            conn: consumer.connection(),
+        },
+        RedisJobStore::new(consumer.connection(), &config.job_stream)
+            .with_dead_letter_maxlen(config.dead_letter_maxlen),
+        RetryPolicy::from_config(&config),
+    );
+    let handler = Arc::new(
+        StatusReportingHandler::new(pipeline, publisher.clone(), consumer.consumer_id())
+            .with_running_jobs(running_jobs),
+    );
+
+    let drained = AtomicBool::new(false);
+    let stop = async {
+        tokio::select! {
+            _ = shutdown_signal() => {}
+            _ = drain_requested(drain) => drained.store(true, Ordering::Relaxed),
+        }
+    };
+    let result = consumer
+        .run(handler, config.max_concurrent_jobs, stop)
+        .await;
+    if drained.load(Ordering::Relaxed) {
+        // Heartbeats go on reporting no jobs until the restart replaces us
+        info!(
+            "Agent {} drained, waiting to be restarted",
+            consumer.consumer_id()
+        );
+        shutdown_signal().await;
+    }
+    publish_status(&publisher, consumer.consumer_id(), AgentStatus::Offline).await;
+
+    for task in [heartbeat, pruner].into_iter().flatten() {
+        task.abort();
+    }
+    canceller.abort();
+    result?;
+
+    info!("Agent {} stopped", consumer.consumer_id());
+    Ok(())
+}
+
+/// Resolve once the server asks the agent to drain; never without a server
+async fn drain_requested(receiver: Option<watch::Receiver<bool>>) {
+    if let Some(mut receiver) = receiver {
+        if receiver.wait_for(|draining| *draining).await.is_ok() {
+            return;
+        }
+    }
+    std::future::pending().await
+}
+
+/// Resolve on Ctrl-C or, on Unix, SIGTERM (sent by Kubernetes on scale-down)
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            warn!("Failed to listen for Ctrl-C: {}", e);
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(e) => {
+                warn!("Failed to listen for SIGTERM: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
+    }
+
+    info!("Shutdown requested, no longer accepting jobs");
 }
 
-// Temporary implementation to avoid compilation errors
-mod uuid {
-    pub struct Uuid;
-    impl Uuid {
-        pub fn new_v4() -> Self {
-            Self
+/// Runs claimed jobs through the [`PipelineExecutor`] in a fresh workspace
+/// and stores their test results, benchmark results, outdated and unused
+/// dependencies, cross-compiled artifacts and cache statistics, or the result
+/// of each matrix combination, then notifies Slack if the pipeline asks for it
+/// and trims their log stream
+struct PipelineJobHandler {
+    git_base_url: String,
+    workspace_dir: PathBuf,
+    keep_workspace_on_failure: bool,
+    git: GitConfig,
+    sccache: SccacheConfig,
+    /// Approximate entries kept in a job's log stream, 0 for all
+    max_log_entries: u64,
+    log_storage: LogStorageConfig,
+    /// Cancelled job IDs received from the server
+    cancellations: JobCancellations,
+    /// raibid-server URL notifications link to
+    server_url: Option<String>,
+    conn: MultiplexedConnection,
+}
+
+impl PipelineJobHandler {
+    /// Move the output of long steps out of a pipeline run, logging
+    /// failures
+    async fn store_step_logs(&self, job_id: &str, result: &mut PipelineResult) {
+        let mut conn = self.conn.clone();
+        for step in &mut result.steps {
+            if let Err(e) = self
+                .log_storage
+                .store_step_log(&mut conn, job_id, step)
+                .await
+            {
+                warn!("{:#}", e);
+            }
         }
     }
-    impl std::fmt::Display for Uuid {
-        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-            write!(f, "00000000-0000-0000-0000-000000000000")
+
+    /// Store the reports of a pipeline run, logging failures
+    async fn store_results(&self, job_id: &str, trigger: &JobTrigger, result: &PipelineResult) {
+        let mut conn = self.conn.clone();
+        if let Some(report) = result.test_report() {
+            if let Err(e) = store_test_report(&mut conn, job_id, &report).await {
+                warn!("{:#}", e);
+            }
+        }
+        if let Some(benchmarks) = result.benchmarks() {
+            if let Err(e) = store_benchmarks(&mut conn, job_id, trigger, &benchmarks).await {
+                warn!("{:#}", e);
+            }
+        }
+        if let Some(coverage) = result.coverage() {
+            if let Err(e) = store_coverage(&mut conn, job_id, &coverage).await {
+                warn!("{:#}", e);
+            }
+        }
+        if let Some(outdated) = result.outdated() {
+            if let Err(e) = store_outdated(&mut conn, job_id, &outdated).await {
+                warn!("{:#}", e);
+            }
+        }
+        if let Some(unused_deps) = result.unused_deps() {
+            if let Err(e) = store_unused_deps(&mut conn, job_id, &unused_deps).await {
+                warn!("{:#}", e);
+            }
+        }
+        if let Some(scan) = result.scan() {
+            if let Err(e) = store_scan_report(&mut conn, job_id, &scan).await {
+                warn!("{:#}", e);
+            }
+        }
+        let cross_results = result.cross_results();
+        if !cross_results.is_empty() {
+            if let Err(e) = store_cross_results(&mut conn, job_id, &cross_results).await {
+                warn!("{:#}", e);
+            }
+        }
+        if let Some(artifacts) = result.artifacts() {
+            if let Err(e) = store_artifacts(&mut conn, job_id, &artifacts).await {
+                warn!("{:#}", e);
+            }
+        }
+        if let Some(ref stats) = result.cache_stats {
+            if let Err(e) = store_cache_stats(&mut conn, job_id, stats).await {
+                warn!("{:#}", e);
+            }
         }
     }
+
+    /// Notify about a finished pipeline run, logging failures
+    async fn notify(&self, job: &QueuedJob, result: &PipelineResult) {
+        if let Some(ref config) = result.notifications {
+            if let Err(e) = notify_slack(config, job, result, self.server_url.as_deref()).await {
+                warn!("{:#}", e);
+            }
+        }
+    }
+}
+
+/// Outcome of a job whose pipeline ran to completion or was cancelled
+fn job_outcome(job_id: &str, success: bool, cancelled: bool) -> JobOutcome {
+    if cancelled {
+        info!("Job {} cancelled", job_id);
+        JobOutcome::Cancelled
+    } else if success {
+        info!("Job {} succeeded", job_id);
+        JobOutcome::Succeeded
+    } else {
+        warn!("Job {} failed", job_id);
+        JobOutcome::Failed
+    }
+}
+
+#[async_trait]
+impl JobHandler for PipelineJobHandler {
+    async fn run(&self, claimed: &ClaimedJob) -> JobOutcome {
+        let job = &claimed.job;
+        let repo_url = format!(
+            "{}/{}.git",
+            self.git_base_url.trim_end_matches('/'),
+            job.trigger.repo
+        );
+
+        info!("Running job {} for {}", job.id, job.trigger.repo);
+        let outcome = match PipelineExecutor::new(&self.workspace_dir)
+            .with_job_id(&job.id)
+            .with_keep_workspace_on_failure(self.keep_workspace_on_failure)
+            .with_git(self.git.clone())
+            .with_sccache(self.sccache.clone())
+            .with_cancellation(self.cancellations.clone())
+            .execute(&job.trigger, &repo_url)
+            .await
+        {
+            Ok(ExecutionResult::Pipeline(mut result)) => {
+                self.store_step_logs(&job.id, &mut result).await;
+                self.store_results(&job.id, &job.trigger, &result).await;
+                self.notify(job, &result).await;
+                job_outcome(&job.id, result.success(), result.cancelled())
+            }
+            Ok(ExecutionResult::Matrix(result)) => {
+                let mut conn = self.conn.clone();
+                if let Err(e) = store_matrix_results(&mut conn, &job.id, &result).await {
+                    warn!("{:#}", e);
+                }
+                if let Some(report) = result.test_report() {
+                    if let Err(e) = store_test_report(&mut conn, &job.id, &report).await {
+                        warn!("{:#}", e);
+                    }
+                }
+                job_outcome(&job.id, result.success(), result.cancelled())
+            }
+            // Running it again would fail the same way
+            Err(e) if e.is::<InvalidPipeline>() => {
+                error!("Job {} failed: {:#}", job.id, e);
+                JobOutcome::Failed
+            }
+            Err(e) => {
+                error!("Job {} could not run: {:#}", job.id, e);
+                JobOutcome::Retry
+            }
+        };
+
+        if self.max_log_entries > 0 {
+            let mut conn = self.conn.clone();
+            if let Err(e) = trim_job_log(&mut conn, &job.id, self.max_log_entries).await {
+                warn!("Failed to trim log of job {}: {}", job.id, e);
+            }
+        }
+
+        outcome
+    }
 }
 
 #[cfg(test)]
@@ -66,5 +491,17 @@ mod tests {
     fn test_agent_config_default() {
         let config = AgentConfig::default();
         assert_eq!(config.agent_type, AgentType::Rust);
+        assert_eq!(config.orphan_recovery_interval_secs, 60);
+        assert_eq!(config.stale_message_threshold_ms, 300_000);
+        assert_eq!(config.max_concurrent_jobs, 1);
+        assert_eq!(config.max_retries, 0);
+        assert_eq!(config.retry_backoff_ms, 5000);
+        assert_eq!(config.api_url, None);
+        assert_eq!(config.heartbeat_interval_secs, 15);
+        assert_eq!(
+            config.log_retention.max_log_entries,
+            log_retention::DEFAULT_MAX_LOG_ENTRIES
+        );
+        assert_ne!(config.agent_id, AgentConfig::default().agent_id);
     }
 }
@@ -0,0 +1,282 @@
+//! Job log retention
+//!
+//! Build output is appended to one Redis stream per job
+//! (`raibid:logs:<job_id>`), which would otherwise grow without bound. After
+//! each job the agent trims its stream to `max_log_entries`, and once an hour
+//! it deletes the streams of jobs queued more than `max_age_secs` ago.
+
+use chrono::{DateTime, Utc};
+use redis::aio::MultiplexedConnection;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+
+use raibid_common::jobs::{
+    job_key, log_stream_job_id, log_stream_key, JOB_FIELD_CREATED_AT, LOG_STREAM_PREFIX,
+};
+
+/// Approximate number of entries kept in a job's log stream
+pub const DEFAULT_MAX_LOG_ENTRIES: u64 = 10_000;
+
+/// Age past which job logs are deleted (7 days)
+pub const DEFAULT_LOG_MAX_AGE_SECS: u64 = 7 * 24 * 60 * 60;
+
+/// How often old job logs are pruned
+pub const PRUNE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Keys requested per `SCAN` while pruning
+const SCAN_COUNT: usize = 100;
+
+/// How much job output is kept in Redis
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LogRetentionConfig {
+    /// Approximate number of entries kept per job, 0 to keep every entry
+    pub max_log_entries: u64,
+    /// Delete the logs of jobs queued longer ago than this, 0 to keep them
+    pub max_age_secs: u64,
+}
+
+impl Default for LogRetentionConfig {
+    fn default() -> Self {
+        Self {
+            max_log_entries: DEFAULT_MAX_LOG_ENTRIES,
+            max_age_secs: DEFAULT_LOG_MAX_AGE_SECS,
+        }
+    }
+}
+
+impl LogRetentionConfig {
+    /// Prune old job logs every [`PRUNE_INTERVAL`] until the task is aborted
+    ///
+    /// Returns `None` when `max_age_secs` is 0. Failures are logged and
+    /// retried on the next tick.
+    pub fn spawn_pruner(&self, conn: MultiplexedConnection) -> Option<JoinHandle<()>> {
+        if self.max_age_secs == 0 {
+            return None;
+        }
+
+        let max_age = chrono::Duration::seconds(self.max_age_secs as i64);
+        Some(tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(PRUNE_INTERVAL);
+            loop {
+                ticker.tick().await;
+
+                let mut conn = conn.clone();
+                match prune_logs(&mut conn, Utc::now() - max_age).await {
+                    Ok(0) => debug!("No job logs to prune"),
+                    Ok(pruned) => info!("Pruned {} job logs", pruned),
+                    Err(e) => warn!("Failed to prune job logs: {}", e),
+                }
+            }
+        }))
+    }
+}
+
+/// Trim a job's log stream to about `max_entries` entries, returning how
+/// many were removed
+///
+/// Uses `MAXLEN ~`, so Redis may keep somewhat more entries than asked.
+pub async fn trim_job_log<C>(
+    conn: &mut C,
+    job_id: &str,
+    max_entries: u64,
+) -> redis::RedisResult<u64>
+where
+    C: redis::aio::ConnectionLike + Send,
+{
+    redis::cmd("XTRIM")
+        .arg(log_stream_key(job_id))
+        .arg("MAXLEN")
+        .arg("~")
+        .arg(max_entries)
+        .query_async(conn)
+        .await
+}
+
+/// Delete the log streams of jobs queued before `older_than`, returning how
+/// many were deleted
+///
+/// A job's age is the `created_at` field of its job hash; streams of jobs
+/// without one are kept.
+pub async fn prune_logs<C>(conn: &mut C, older_than: DateTime<Utc>) -> redis::RedisResult<u64>
+where
+    C: redis::aio::ConnectionLike + Send,
+{
+    let pattern = format!("{}*", LOG_STREAM_PREFIX);
+    let mut cursor = 0u64;
+    let mut pruned = 0;
+
+    loop {
+        let (next, keys): (u64, Vec<String>) = redis::cmd("SCAN")
+            .arg(cursor)
+            .arg("MATCH")
+            .arg(&pattern)
+            .arg("COUNT")
+            .arg(SCAN_COUNT)
+            .query_async(conn)
+            .await?;
+
+        for key in keys {
+            let Some(job_id) = log_stream_job_id(&key) else {
+                continue;
+            };
+            let created_at: Option<String> = redis::cmd("HGET")
+                .arg(job_key(job_id))
+                .arg(JOB_FIELD_CREATED_AT)
+                .query_async(conn)
+                .await?;
+            let expired = created_at
+                .and_then(|t| DateTime::parse_from_rfc3339(&t).ok())
+                .is_some_and(|t| t < older_than);
+
+            if expired {
+                redis::cmd("DEL")
+                    .arg(&key)
+                    .query_async::<_, ()>(conn)
+                    .await?;
+                debug!("Pruned log stream of job {}", job_id);
+                pruned += 1;
+            }
+        }
+
+        if next == 0 {
+            return Ok(pruned);
+        }
+        cursor = next;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use redis::Value;
+    use std::collections::{BTreeMap, HashMap};
+
+    /// In-memory Redis supporting the commands used for log retention
+    #[derive(Default)]
+    struct FakeRedis {
+        streams: BTreeMap<String, Vec<String>>,
+        hashes: HashMap<String, HashMap<String, String>>,
+    }
+
+    impl FakeRedis {
+        fn execute(&mut self, args: &[String]) -> redis::RedisResult<Value> {
+            Ok(match args[0].as_str() {
+                "XADD" => {
+                    let stream = self.streams.entry(args[1].clone()).or_default();
+                    let id = format!("{}-0", stream.len() + 1);
+                    stream.push(id.clone());
+                    Value::Data(id.into_bytes())
+                }
+                "XLEN" => Value::Int(self.streams.get(&args[1]).map_or(0, Vec::len) as i64),
+                "XTRIM" => {
+                    let max: usize = args[4].parse().unwrap();
+                    let stream = self.streams.entry(args[1].clone()).or_default();
+                    let removed = stream.len().saturating_sub(max);
+                    stream.drain(..removed);
+                    Value::Int(removed as i64)
+                }
+                "SCAN" => {
+                    let prefix = args[3].trim_end_matches('*');
+                    let keys = self
+                        .streams
+                        .keys()
+                        .filter(|key| key.starts_with(prefix))
+                        .map(|key| Value::Data(key.clone().into_bytes()))
+                        .collect();
+                    Value::Bulk(vec![Value::Data(b"0".to_vec()), Value::Bulk(keys)])
+                }
+                "HGET" => match self.hashes.get(&args[1]).and_then(|h| h.get(&args[2])) {
+                    Some(value) => Value::Data(value.as_bytes().to_vec()),
+                    None => Value::Nil,
+                },
+                "DEL" => Value::Int(i64::from(self.streams.remove(&args[1]).is_some())),
+                other => panic!("unexpected command {}", other),
+            })
+        }
+
+        fn add_job(&mut self, job_id: &str, created_at: Option<DateTime<Utc>>, entries: usize) {
+            if let Some(created_at) = created_at {
+                self.hashes
+                    .entry(job_key(job_id))
+                    .or_default()
+                    .insert(JOB_FIELD_CREATED_AT.to_string(), created_at.to_rfc3339());
+            }
+            let stream = self.streams.entry(log_stream_key(job_id)).or_default();
+            stream.extend((1..=entries).map(|i| format!("{}-0", i)));
+        }
+    }
+
+    impl redis::aio::ConnectionLike for FakeRedis {
+        fn req_packed_command<'a>(
+            &'a mut self,
+            cmd: &'a redis::Cmd,
+        ) -> redis::RedisFuture<'a, Value> {
+            let args: Vec<String> = cmd
+                .args_iter()
+                .map(|arg| match arg {
+                    redis::Arg::Simple(bytes) => String::from_utf8_lossy(bytes).to_string(),
+                    redis::Arg::Cursor => "0".to_string(),
+                })
+                .collect();
+            let result = self.execute(&args);
+            Box::pin(async move { result })
+        }
+
+        fn req_packed_commands<'a>(
+            &'a mut self,
+            _pipeline: &'a redis::Pipeline,
+            _offset: usize,
+            _count: usize,
+        ) -> redis::RedisFuture<'a, Vec<Value>> {
+            Box::pin(async { Ok(Vec::new()) })
+        }
+
+        fn get_db(&self) -> i64 {
+            0
+        }
+    }
+
+    #[tokio::test]
+    async fn test_trim_job_log() {
+        let mut conn = FakeRedis::default();
+        for i in 0..200 {
+            redis::cmd("XADD")
+                .arg(log_stream_key("job-1"))
+                .arg("*")
+                .arg("message")
+                .arg(format!("line {}", i))
+                .query_async::<_, String>(&mut conn)
+                .await
+                .unwrap();
+        }
+
+        trim_job_log(&mut conn, "job-1", 100).await.unwrap();
+
+        let len: u64 = redis::cmd("XLEN")
+            .arg(log_stream_key("job-1"))
+            .query_async(&mut conn)
+            .await
+            .unwrap();
+        assert!(len <= 100, "{} entries left", len);
+    }
+
+    #[tokio::test]
+    async fn test_prune_logs() {
+        let now = Utc::now();
+        let mut conn = FakeRedis::default();
+        conn.add_job("old", Some(now - chrono::Duration::days(8)), 3);
+        conn.add_job("recent", Some(now - chrono::Duration::hours(1)), 3);
+        conn.add_job("unknown", None, 3);
+
+        let pruned = prune_logs(&mut conn, now - chrono::Duration::days(7))
+            .await
+            .unwrap();
+
+        assert_eq!(pruned, 1);
+        assert_eq!(
+            conn.streams.keys().collect::<Vec<_>>(),
+            vec!["raibid:logs:recent", "raibid:logs:unknown"]
+        );
+    }
+}
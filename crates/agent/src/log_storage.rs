@@ -0,0 +1,411 @@
+//! Storage of long step output
+//!
+//! A step's output is kept in its [`StepResult`]. When it is longer than
+//! `max_inline_bytes`, the full output is gzipped and stored in the
+//! configured [`LogBackend`], and only its first [`INLINE_PREFIX_BYTES`] stay
+//! inline, with `log_url` pointing at the rest.
+//!
+//! Output kept in Redis is served by the server at
+//! `GET /api/jobs/:id/steps/:step/full-log`. The URLs of output stored
+//! elsewhere are recorded in the job's `raibid:step-logs:<job_id>` hash, which
+//! that route redirects to. Either expires with the job's logs after
+//! [`DEFAULT_LOG_MAX_AGE_SECS`].
+
+use anyhow::{Context, Result};
+use aws_sdk_s3::presigning::PresigningConfig;
+use aws_sdk_s3::primitives::ByteStream;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use redis::aio::ConnectionLike;
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::Duration;
+use tracing::debug;
+
+use crate::executor::StepResult;
+use crate::log_retention::DEFAULT_LOG_MAX_AGE_SECS;
+use raibid_common::jobs::{full_log_key, step_logs_key};
+
+/// Output longer than this is stored outside the step result by default
+pub const DEFAULT_MAX_INLINE_BYTES: usize = 10 * 1024;
+
+/// Bytes of a stored output kept inline in the step result
+pub const INLINE_PREFIX_BYTES: usize = 2048;
+
+/// Where full step output is stored
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LogBackend {
+    /// Under `raibid:full-log:<job_id>:<step>`, served by the server
+    Redis,
+    /// As `<prefix>/<job_id>/<step>.log.gz` in an S3 bucket, linked by a URL
+    /// presigned for as long as job logs are kept
+    S3 { bucket: String, prefix: String },
+    /// As `<base_path>/<job_id>/<step>.log.gz`, for a volume shared with the
+    /// server
+    Filesystem { base_path: PathBuf },
+}
+
+/// How much step output is kept inline and where the rest goes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogStorageConfig {
+    /// Where full output is stored
+    pub backend: LogBackend,
+    /// Output longer than this is stored in `backend`
+    pub max_inline_bytes: usize,
+}
+
+impl Default for LogStorageConfig {
+    fn default() -> Self {
+        Self {
+            backend: LogBackend::Redis,
+            max_inline_bytes: DEFAULT_MAX_INLINE_BYTES,
+        }
+    }
+}
+
+impl LogStorageConfig {
+    /// Store the full output of `step` if it is longer than
+    /// `max_inline_bytes`, keeping its first [`INLINE_PREFIX_BYTES`] inline
+    ///
+    /// The step is left untouched when storing fails.
+    pub async fn store_step_log<C>(
+        &self,
+        conn: &mut C,
+        job_id: &str,
+        step: &mut StepResult,
+    ) -> Result<()>
+    where
+        C: ConnectionLike + Send,
+    {
+        if step.output.len() <= self.max_inline_bytes {
+            return Ok(());
+        }
+
+        let log = gzip(step.output.as_bytes()).context("Failed to compress step output")?;
+        let url = self
+            .upload(conn, job_id, &step.name, log)
+            .await
+            .with_context(|| format!("Failed to store the output of step {}", step.name))?;
+        debug!(
+            "Stored {} bytes of output of step {} at {}",
+            step.output.len(),
+            step.name,
+            url
+        );
+
+        step.output.truncate(inline_prefix_len(&step.output));
+        step.log_url = Some(url);
+        Ok(())
+    }
+
+    /// Store a gzipped log in the backend, returning its URL
+    async fn upload<C>(
+        &self,
+        conn: &mut C,
+        job_id: &str,
+        step: &str,
+        log: Vec<u8>,
+    ) -> Result<String>
+    where
+        C: ConnectionLike + Send,
+    {
+        let url = match self.backend {
+            LogBackend::Redis => {
+                redis::cmd("SET")
+                    .arg(full_log_key(job_id, step))
+                    .arg(log)
+                    .arg("EX")
+                    .arg(DEFAULT_LOG_MAX_AGE_SECS)
+                    .query_async::<_, ()>(conn)
+                    .await?;
+                // Served by the server itself
+                return Ok(full_log_path(job_id, step));
+            }
+            LogBackend::S3 {
+                ref bucket,
+                ref prefix,
+            } => upload_to_s3(bucket, &object_key(prefix, job_id, step), log).await?,
+            LogBackend::Filesystem { ref base_path } => {
+                let path = base_path.join(job_id).join(log_file_name(step));
+                tokio::fs::create_dir_all(base_path.join(job_id)).await?;
+                tokio::fs::write(&path, log).await?;
+                format!("file://{}", path.display())
+            }
+        };
+
+        redis::pipe()
+            .cmd("HSET")
+            .arg(step_logs_key(job_id))
+            .arg(step)
+            .arg(&url)
+            .ignore()
+            .cmd("EXPIRE")
+            .arg(step_logs_key(job_id))
+            .arg(DEFAULT_LOG_MAX_AGE_SECS)
+            .ignore()
+            .query_async::<_, ()>(conn)
+            .await
+            .context("Failed to record the log URL")?;
+        Ok(url)
+    }
+}
+
+/// Server path serving the full output of a step kept in Redis
+pub fn full_log_path(job_id: &str, step: &str) -> String {
+    format!("/api/jobs/{}/steps/{}/full-log", job_id, step)
+}
+
+/// File name of a step's stored output, with characters that cannot appear
+/// in a path segment replaced
+fn log_file_name(step: &str) -> String {
+    let name: String = step
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' => '_',
+            c => c,
+        })
+        .collect();
+    format!("{}.log.gz", name)
+}
+
+/// S3 object key of a step's stored output
+fn object_key(prefix: &str, job_id: &str, step: &str) -> String {
+    let prefix = prefix.trim_matches('/');
+    if prefix.is_empty() {
+        format!("{}/{}", job_id, log_file_name(step))
+    } else {
+        format!("{}/{}/{}", prefix, job_id, log_file_name(step))
+    }
+}
+
+/// Length of the inline prefix of `output`, cut at a character boundary
+fn inline_prefix_len(output: &str) -> usize {
+    let mut len = INLINE_PREFIX_BYTES.min(output.len());
+    while !output.is_char_boundary(len) {
+        len -= 1;
+    }
+    len
+}
+
+fn gzip(data: &[u8]) -> std::io::Result<Vec<u8>> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(data)?;
+    encoder.finish()
+}
+
+/// Upload a gzipped log to S3 with credentials from the environment,
+/// returning a presigned URL for it
+async fn upload_to_s3(bucket: &str, key: &str, log: Vec<u8>) -> Result<String> {
+    let config = aws_config::load_defaults(aws_config::BehaviorVersion::latest()).await;
+    let client = aws_sdk_s3::Client::new(&config);
+
+    client
+        .put_object()
+        .bucket(bucket)
+        .key(key)
+        .content_type("text/plain; charset=utf-8")
+        .content_encoding("gzip")
+        .body(ByteStream::from(log))
+        .send()
+        .await
+        .with_context(|| format!("Failed to upload s3://{}/{}", bucket, key))?;
+
+    let expires_in = PresigningConfig::expires_in(Duration::from_secs(DEFAULT_LOG_MAX_AGE_SECS))
+        .context("Invalid presigned URL lifetime")?;
+    let request = client
+        .get_object()
+        .bucket(bucket)
+        .key(key)
+        .presigned(expires_in)
+        .await
+        .with_context(|| format!("Failed to presign s3://{}/{}", bucket, key))?;
+    Ok(request.uri().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::read::GzDecoder;
+    use redis::Value;
+    use std::collections::HashMap;
+    use std::io::Read;
+
+    /// Connection keeping `SET` values and `HSET` fields
+    #[derive(Default)]
+    struct FakeRedis {
+        values: HashMap<String, Vec<u8>>,
+        hashes: HashMap<String, HashMap<String, String>>,
+    }
+
+    impl FakeRedis {
+        fn execute(&mut self, cmd: &redis::Cmd) {
+            let args: Vec<Vec<u8>> = cmd
+                .args_iter()
+                .map(|arg| match arg {
+                    redis::Arg::Simple(bytes) => bytes.to_vec(),
+                    redis::Arg::Cursor => b"0".to_vec(),
+                })
+                .collect();
+            let text = |i: usize| String::from_utf8_lossy(&args[i]).to_string();
+            match text(0).as_str() {
+                "SET" => {
+                    self.values.insert(text(1), args[2].clone());
+                }
+                "HSET" => {
+                    self.hashes
+                        .entry(text(1))
+                        .or_default()
+                        .insert(text(2), text(3));
+                }
+                "EXPIRE" => {}
+                other => panic!("unexpected command {}", other),
+            }
+        }
+    }
+
+    impl redis::aio::ConnectionLike for FakeRedis {
+        fn req_packed_command<'a>(
+            &'a mut self,
+            cmd: &'a redis::Cmd,
+        ) -> redis::RedisFuture<'a, Value> {
+            self.execute(cmd);
+            Box::pin(async { Ok(Value::Okay) })
+        }
+
+        fn req_packed_commands<'a>(
+            &'a mut self,
+            pipeline: &'a redis::Pipeline,
+            _offset: usize,
+            _count: usize,
+        ) -> redis::RedisFuture<'a, Vec<Value>> {
+            let replies = pipeline
+                .cmd_iter()
+                .map(|cmd| {
+                    self.execute(cmd);
+                    Value::Okay
+                })
+                .collect();
+            Box::pin(async { Ok(replies) })
+        }
+
+        fn get_db(&self) -> i64 {
+            0
+        }
+    }
+
+    fn step(output: String) -> StepResult {
+        StepResult {
+            name: "cross:aarch64-unknown-linux-gnu".to_string(),
+            success: true,
+            exit_code: Some(0),
+            duration: Duration::from_secs(1),
+            output,
+            continue_on_failure: false,
+            test_report: None,
+            benchmarks: None,
+            coverage: None,
+            outdated: None,
+            unused_deps: None,
+            scan: None,
+            cross: None,
+            log_url: None,
+            cancelled: false,
+        }
+    }
+
+    fn gunzip(data: &[u8]) -> String {
+        let mut output = String::new();
+        GzDecoder::new(data).read_to_string(&mut output).unwrap();
+        output
+    }
+
+    /// Test output of `lines` lines, with a multi-byte character straddling
+    /// the inline prefix
+    fn long_output(lines: usize) -> String {
+        let mut output = "x".repeat(INLINE_PREFIX_BYTES - 1);
+        output.push('✓');
+        for i in 0..lines {
+            output.push_str(&format!("\ntest case_{} ... ok", i));
+        }
+        output
+    }
+
+    #[test]
+    fn test_inline_prefix_len() {
+        assert_eq!(inline_prefix_len("short"), 5);
+        assert_eq!(inline_prefix_len(&long_output(0)), INLINE_PREFIX_BYTES - 1);
+    }
+
+    #[test]
+    fn test_object_key() {
+        assert_eq!(
+            object_key("ci/logs/", "job-1", "cross:x86_64-pc-windows-gnu"),
+            "ci/logs/job-1/cross_x86_64-pc-windows-gnu.log.gz"
+        );
+        assert_eq!(object_key("", "job-1", "test"), "job-1/test.log.gz");
+    }
+
+    #[tokio::test]
+    async fn test_short_output_stays_inline() {
+        let mut conn = FakeRedis::default();
+        let mut step = step("test result: ok".to_string());
+
+        LogStorageConfig::default()
+            .store_step_log(&mut conn, "job-1", &mut step)
+            .await
+            .unwrap();
+
+        assert_eq!(step.output, "test result: ok");
+        assert_eq!(step.log_url, None);
+        assert!(conn.values.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_store_in_redis() {
+        let mut conn = FakeRedis::default();
+        let output = long_output(1000);
+        let mut step = step(output.clone());
+
+        LogStorageConfig::default()
+            .store_step_log(&mut conn, "job-1", &mut step)
+            .await
+            .unwrap();
+
+        assert_eq!(step.output, output[..INLINE_PREFIX_BYTES - 1]);
+        assert_eq!(
+            step.log_url.as_deref(),
+            Some("/api/jobs/job-1/steps/cross:aarch64-unknown-linux-gnu/full-log")
+        );
+        let stored = &conn.values[&full_log_key("job-1", &step.name)];
+        assert_eq!(gunzip(stored), output);
+        assert!(conn.hashes.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_store_on_filesystem() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut conn = FakeRedis::default();
+        let output = long_output(1000);
+        let mut step = step(output.clone());
+        let config = LogStorageConfig {
+            backend: LogBackend::Filesystem {
+                base_path: dir.path().to_path_buf(),
+            },
+            max_inline_bytes: 4096,
+        };
+
+        config
+            .store_step_log(&mut conn, "job-1", &mut step)
+            .await
+            .unwrap();
+
+        let path = dir
+            .path()
+            .join("job-1/cross_aarch64-unknown-linux-gnu.log.gz");
+        assert_eq!(gunzip(&std::fs::read(&path).unwrap()), output);
+        assert!(step.output.len() < INLINE_PREFIX_BYTES);
+        let url = format!("file://{}", path.display());
+        assert_eq!(step.log_url.as_deref(), Some(url.as_str()));
+        assert_eq!(conn.hashes[&step_logs_key("job-1")][&step.name], url);
+    }
+}
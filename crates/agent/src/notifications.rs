@@ -0,0 +1,321 @@
+//! Job completion notifications
+//!
+//! A pipeline with `notifications:` in `.raibid.yaml` posts a message to a
+//! Slack incoming webhook when its job finishes:
+//!
+//! ```yaml
+//! notifications:
+//!   slack_webhook_url: https://hooks.slack.com/services/T000/B000/XXXX
+//!   on_success: false
+//!   mention: "<!subteam^S012345>"
+//! ```
+//!
+//! The message uses Slack's Block Kit layout and links to the job on
+//! raibid-server when the agent has a server URL configured.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::time::Duration;
+use tracing::debug;
+
+use crate::executor::PipelineResult;
+use raibid_common::jobs::QueuedJob;
+
+/// Request timeout for posting a notification
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Where and when to send job notifications
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct NotificationConfig {
+    /// Slack incoming webhook URL
+    #[serde(default)]
+    pub slack_webhook_url: Option<String>,
+    /// Notify when the job succeeded
+    #[serde(default = "default_true")]
+    pub on_success: bool,
+    /// Notify when the job failed
+    #[serde(default = "default_true")]
+    pub on_failure: bool,
+    /// Prepended to the message, e.g. `<@U012345>` or `<!channel>`
+    #[serde(default)]
+    pub mention: Option<String>,
+}
+
+impl Default for NotificationConfig {
+    fn default() -> Self {
+        Self {
+            slack_webhook_url: None,
+            on_success: true,
+            on_failure: true,
+            mention: None,
+        }
+    }
+}
+
+fn default_true() -> bool {
+    true
+}
+
+impl NotificationConfig {
+    /// Whether a job that `succeeded` is notified about
+    pub fn notifies(&self, succeeded: bool) -> bool {
+        if succeeded {
+            self.on_success
+        } else {
+            self.on_failure
+        }
+    }
+}
+
+/// Total wall-clock duration of the steps that ran
+pub fn pipeline_duration(result: &PipelineResult) -> Duration {
+    result.steps.iter().map(|step| step.duration).sum()
+}
+
+/// Duration as minutes and seconds, e.g. `2m 05s`
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    if secs < 60 {
+        format!("{}s", secs)
+    } else {
+        format!("{}m {:02}s", secs / 60, secs % 60)
+    }
+}
+
+/// Block Kit message for a finished job
+///
+/// `server_url` is the raibid-server base URL the message links to.
+pub fn slack_message(
+    config: &NotificationConfig,
+    job: &QueuedJob,
+    result: &PipelineResult,
+    server_url: Option<&str>,
+) -> Value {
+    let (emoji, status) = if result.success() {
+        (":white_check_mark:", "succeeded")
+    } else {
+        (":x:", "failed")
+    };
+    let mut headline = format!(
+        "{} Job `{}` for *{}* {}",
+        emoji, job.id, job.trigger.repo, status
+    );
+    if let Some(ref mention) = config.mention {
+        headline = format!("{} {}", mention, headline);
+    }
+
+    let mut blocks = vec![
+        json!({
+            "type": "section",
+            "text": { "type": "mrkdwn", "text": headline },
+        }),
+        json!({
+            "type": "section",
+            "fields": [
+                { "type": "mrkdwn", "text": format!("*Repository*\n{}", job.trigger.repo) },
+                { "type": "mrkdwn", "text": format!("*Branch*\n{}", job.trigger.branch) },
+                { "type": "mrkdwn", "text": format!("*Status*\n{} {}", emoji, status) },
+                {
+                    "type": "mrkdwn",
+                    "text": format!("*Duration*\n{}", format_duration(pipeline_duration(result))),
+                },
+            ],
+        }),
+    ];
+    if let Some(url) = server_url {
+        let link = format!("{}/api/jobs/{}/status", url.trim_end_matches('/'), job.id);
+        blocks.push(json!({
+            "type": "context",
+            "elements": [
+                { "type": "mrkdwn", "text": format!("<{}|View job {}>", link, job.id) },
+            ],
+        }));
+    }
+
+    json!({
+        // Shown in notifications and by clients without Block Kit support
+        "text": format!("Job {} for {} {}", job.id, job.trigger.repo, status),
+        "blocks": blocks,
+    })
+}
+
+/// Post a message about a finished job to the configured Slack webhook
+///
+/// Does nothing without a webhook URL, or if `config` does not notify about
+/// jobs with the job's outcome.
+pub async fn notify_slack(
+    config: &NotificationConfig,
+    job: &QueuedJob,
+    result: &PipelineResult,
+    server_url: Option<&str>,
+) -> Result<()> {
+    let Some(ref webhook_url) = config.slack_webhook_url else {
+        return Ok(());
+    };
+    if !config.notifies(result.success()) {
+        debug!("Not notifying Slack about job {}", job.id);
+        return Ok(());
+    }
+
+    let client = reqwest::Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .build()
+        .context("Failed to create HTTP client")?;
+    client
+        .post(webhook_url)
+        .json(&slack_message(config, job, result, server_url))
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .with_context(|| format!("Failed to notify Slack about job {}", job.id))?;
+
+    debug!("Notified Slack about job {}", job.id);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executor::StepResult;
+    use raibid_common::jobs::JobBuilder;
+
+    fn job() -> QueuedJob {
+        QueuedJob::new(
+            "job-1",
+            JobBuilder::new("raibid-labs/raibid-cli")
+                .branch("develop")
+                .build()
+                .unwrap(),
+        )
+    }
+
+    fn result(success: bool) -> PipelineResult {
+        PipelineResult {
+            steps: vec![StepResult {
+                name: "test".to_string(),
+                success,
+                exit_code: Some(if success { 0 } else { 101 }),
+                duration: Duration::from_secs(125),
+                output: String::new(),
+                continue_on_failure: false,
+                test_report: None,
+                benchmarks: None,
+                coverage: None,
+                outdated: None,
+                unused_deps: None,
+                scan: None,
+                cross: None,
+                log_url: None,
+                cancelled: false,
+            }],
+            cache_stats: None,
+            notifications: None,
+        }
+    }
+
+    #[test]
+    fn test_slack_message() {
+        let config = NotificationConfig {
+            mention: Some("<!channel>".to_string()),
+            ..Default::default()
+        };
+        let message = slack_message(&config, &job(), &result(false), Some("http://raibid:8080/"));
+
+        let headline = message["blocks"][0]["text"]["text"].as_str().unwrap();
+        assert_eq!(
+            headline,
+            "<!channel> :x: Job `job-1` for *raibid-labs/raibid-cli* failed"
+        );
+        let fields: Vec<&str> = message["blocks"][1]["fields"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|field| field["text"].as_str().unwrap())
+            .collect();
+        assert_eq!(
+            fields,
+            vec![
+                "*Repository*\nraibid-labs/raibid-cli",
+                "*Branch*\ndevelop",
+                "*Status*\n:x: failed",
+                "*Duration*\n2m 05s",
+            ]
+        );
+        assert_eq!(
+            message["blocks"][2]["elements"][0]["text"],
+            "<http://raibid:8080/api/jobs/job-1/status|View job job-1>"
+        );
+
+        // No link without a server
+        let message = slack_message(&config, &job(), &result(true), None);
+        assert_eq!(message["blocks"].as_array().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_notify_slack_posts_block_kit_message() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/services/T000/B000/XXXX")
+            .match_header("content-type", "application/json")
+            .match_body(mockito::Matcher::PartialJson(json!({
+                "text": "Job job-1 for raibid-labs/raibid-cli succeeded",
+                "blocks": [
+                    {
+                        "type": "section",
+                        "text": {
+                            "type": "mrkdwn",
+                            "text": ":white_check_mark: Job `job-1` for *raibid-labs/raibid-cli* succeeded",
+                        },
+                    },
+                ],
+            })))
+            .with_status(200)
+            .create_async()
+            .await;
+
+        let config = NotificationConfig {
+            slack_webhook_url: Some(format!("{}/services/T000/B000/XXXX", server.url())),
+            ..Default::default()
+        };
+        notify_slack(&config, &job(), &result(true), None)
+            .await
+            .unwrap();
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_notify_slack_skips_unwanted_outcomes() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server.mock("POST", "/hook").expect(0).create_async().await;
+
+        let config = NotificationConfig {
+            slack_webhook_url: Some(format!("{}/hook", server.url())),
+            on_success: false,
+            ..Default::default()
+        };
+        notify_slack(&config, &job(), &result(true), None)
+            .await
+            .unwrap();
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_notify_slack_error_status() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("POST", "/hook")
+            .with_status(404)
+            .with_body("no_service")
+            .create_async()
+            .await;
+
+        let config = NotificationConfig {
+            slack_webhook_url: Some(format!("{}/hook", server.url())),
+            ..Default::default()
+        };
+        assert!(notify_slack(&config, &job(), &result(false), None)
+            .await
+            .is_err());
+    }
+}
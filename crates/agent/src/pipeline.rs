@@ -0,0 +1,715 @@
+//! Build pipeline steps
+//!
+//! This module defines the build steps an agent can execute and how each step
+//! is turned into a process invocation inside the job workspace.
+
+pub mod bench;
+pub mod config;
+pub mod coverage;
+pub mod cross;
+pub mod deny;
+pub mod junit;
+pub mod matrix;
+pub mod miri;
+pub mod outdated;
+pub mod scan;
+pub mod udeps;
+
+use anyhow::{anyhow, Result};
+use std::collections::BTreeMap;
+use std::path::{Component, Path, PathBuf};
+use std::time::Duration;
+use tokio::process::Command;
+
+/// A single step in a build pipeline
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BuildStep {
+    /// `cargo fmt --check`
+    Fmt,
+    /// `cargo clippy -- -D warnings`
+    Clippy,
+    /// `cargo check`
+    Check,
+    /// `cargo build`
+    Build { release: bool },
+    /// `cargo test`
+    Test,
+    /// `cargo bench` with Bencher format output
+    Bench,
+    /// `cargo tarpaulin` with a Cobertura XML report
+    Coverage {
+        /// Fail the step if line coverage is below this percentage
+        min_percent: Option<u8>,
+    },
+    /// `cargo deny check` of licenses, advisories, bans, and sources
+    Deny {
+        /// `deny.toml` to check against, relative to the workspace
+        config: Option<PathBuf>,
+    },
+    /// `cargo miri test`, checking the tests for undefined behavior
+    Miri {
+        /// Fail the step rather than skip it if Miri cannot be installed
+        strict: bool,
+    },
+    /// `cargo outdated`, reporting outdated dependencies, see [`outdated`]
+    Outdated {
+        /// Fail the step rather than warn if any dependency is outdated
+        fail_on_outdated: bool,
+    },
+    /// `cargo +nightly udeps`, reporting unused dependencies, see [`udeps`]
+    Udeps {
+        /// Fail the step rather than warn if any dependency is unused
+        fail_on_unused_deps: bool,
+    },
+    /// `cross build --release` for another target, see [`cross`]
+    CrossCompile {
+        /// Target triple
+        target: String,
+    },
+    /// `docker build`
+    DockerBuild { tag: String, context: String },
+    /// `trivy image` scan of an image built by an earlier step
+    SecurityScan {
+        /// Image to scan
+        image: String,
+        /// Severities that fail the step
+        policy: scan::ScanPolicy,
+    },
+    /// Arbitrary shell script run with `sh -c`
+    Shell { script: String },
+    /// `make <target>`
+    Make { target: String },
+    /// `just <recipe>`
+    Just { recipe: String },
+    /// Arbitrary program defined in `raibid.yml`
+    Command {
+        name: String,
+        program: String,
+        args: Vec<String>,
+        /// Directory to run in, relative to the workspace
+        working_dir: Option<PathBuf>,
+        env: BTreeMap<String, String>,
+    },
+}
+
+impl BuildStep {
+    /// Get the step name used in logs and status reporting
+    pub fn name(&self) -> String {
+        match self {
+            BuildStep::Fmt => "fmt".to_string(),
+            BuildStep::Clippy => "clippy".to_string(),
+            BuildStep::Check => "check".to_string(),
+            BuildStep::Build { .. } => "build".to_string(),
+            BuildStep::Test => "test".to_string(),
+            BuildStep::Bench => "bench".to_string(),
+            BuildStep::Coverage { .. } => "coverage".to_string(),
+            BuildStep::Deny { .. } => "deny".to_string(),
+            BuildStep::Miri { .. } => "miri".to_string(),
+            BuildStep::Outdated { .. } => "outdated".to_string(),
+            BuildStep::Udeps { .. } => "udeps".to_string(),
+            BuildStep::CrossCompile { target } => format!("cross:{}", target),
+            BuildStep::DockerBuild { .. } => "docker-build".to_string(),
+            BuildStep::SecurityScan { .. } => "security-scan".to_string(),
+            BuildStep::Shell { .. } => "shell".to_string(),
+            BuildStep::Make { target } => format!("make:{}", target),
+            BuildStep::Just { recipe } => format!("just:{}", recipe),
+            BuildStep::Command { name, .. } => name.clone(),
+        }
+    }
+
+    /// Look up a pre-defined step by name
+    ///
+    /// Only steps that need no extra parameters can be referenced by name.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "fmt" => Some(BuildStep::Fmt),
+            "clippy" => Some(BuildStep::Clippy),
+            "check" => Some(BuildStep::Check),
+            "build" => Some(BuildStep::Build { release: false }),
+            "build-release" => Some(BuildStep::Build { release: true }),
+            "test" => Some(BuildStep::Test),
+            "bench" => Some(BuildStep::Bench),
+            "coverage" => Some(BuildStep::Coverage { min_percent: None }),
+            "deny" => Some(BuildStep::Deny { config: None }),
+            "miri" => Some(BuildStep::Miri { strict: false }),
+            "outdated" => Some(BuildStep::Outdated {
+                fail_on_outdated: false,
+            }),
+            "udeps" => Some(BuildStep::Udeps {
+                fail_on_unused_deps: false,
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// A build step together with how the executor should treat it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PipelineStep {
+    /// Step to run
+    pub step: BuildStep,
+    /// Kill the step if it runs longer than this
+    pub timeout: Option<Duration>,
+    /// Keep running later steps if this one fails
+    pub continue_on_failure: bool,
+}
+
+impl PipelineStep {
+    /// Use `timeout` unless the step has its own
+    pub fn with_default_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = self.timeout.or(timeout);
+        self
+    }
+}
+
+impl From<BuildStep> for PipelineStep {
+    /// A step without a timeout that fails the pipeline, unless it is a
+    /// security scan whose policy allows it to fail
+    fn from(step: BuildStep) -> Self {
+        let continue_on_failure = matches!(
+            step,
+            BuildStep::SecurityScan { ref policy, .. } if policy.continue_on_failure
+        );
+        Self {
+            step,
+            timeout: None,
+            continue_on_failure,
+        }
+    }
+}
+
+/// Default step sequence for Rust projects without a custom `steps:` list
+pub fn default_steps() -> Vec<BuildStep> {
+    vec![
+        BuildStep::Fmt,
+        BuildStep::Clippy,
+        BuildStep::Build { release: false },
+        BuildStep::Test,
+    ]
+}
+
+/// Construct the command for a build step, running in `workdir`
+pub fn build_command(step: &BuildStep, workdir: &Path) -> Result<Command> {
+    build_command_for_target(step, workdir, None)
+}
+
+/// Construct the command for a build step compiling for `target`
+///
+/// Compiling cargo steps get `--target`; other steps get `CARGO_BUILD_TARGET`
+/// so that cargo commands they run build for the same target.
+pub fn build_command_for_target(
+    step: &BuildStep,
+    workdir: &Path,
+    target: Option<&str>,
+) -> Result<Command> {
+    let mut cmd = match step {
+        BuildStep::Fmt => cargo(&["fmt", "--all", "--", "--check"], None),
+        BuildStep::Clippy => cargo(&["clippy", "--all-targets", "--", "-D", "warnings"], target),
+        BuildStep::Check => cargo(&["check", "--all-targets"], target),
+        BuildStep::Build { release } => {
+            let mut cmd = cargo(&["build"], target);
+            if *release {
+                cmd.arg("--release");
+            }
+            cmd
+        }
+        BuildStep::Test => cargo(&["test"], target),
+        // stderr is captured along with stdout, as with `2>&1`
+        BuildStep::Bench => cargo(
+            &[
+                "bench",
+                "--all-features",
+                "--",
+                "--output-format",
+                "bencher",
+            ],
+            target,
+        ),
+        BuildStep::Coverage { .. } => cargo(
+            &[
+                "tarpaulin",
+                "--out",
+                "Xml",
+                "--output-dir",
+                coverage::COVERAGE_OUTPUT_DIR,
+            ],
+            target,
+        ),
+        // cargo-deny checks the dependency graph for every target unless
+        // `[graph] targets` in the config narrows it
+        BuildStep::Deny { config } => {
+            let mut cmd = Command::new("cargo");
+            cmd.arg("deny").arg("check").arg("--config").arg(
+                config
+                    .as_deref()
+                    .unwrap_or(Path::new(deny::DENY_CONFIG_FILE)),
+            );
+            cmd
+        }
+        // `--target` goes after `test`, the subcommand Miri runs
+        BuildStep::Miri { .. } => {
+            let mut cmd = Command::new("cargo");
+            cmd.arg("miri").arg("test");
+            if let Some(target) = target {
+                cmd.arg("--target").arg(target);
+            }
+            cmd
+        }
+        // Exit code 0 even with outdated dependencies; the executor decides
+        // from the report whether the step fails
+        BuildStep::Outdated { .. } => {
+            let mut cmd = Command::new("cargo");
+            cmd.args(["outdated", "--exit-code", "0", "--format", "json"]);
+            cmd
+        }
+        // Exits with 1 when dependencies are unused; the executor decides
+        // from the report whether the step fails
+        BuildStep::Udeps { .. } => {
+            let mut cmd = Command::new("cargo");
+            cmd.args(["+nightly", "udeps", "--all-targets", "--output", "json"]);
+            cmd
+        }
+        // cross builds in a container for its own target, ignoring `target`
+        BuildStep::CrossCompile { target } => {
+            let mut cmd = Command::new("cross");
+            cmd.arg("build")
+                .arg("--target")
+                .arg(target)
+                .arg("--release");
+            cmd
+        }
+        BuildStep::DockerBuild { tag, context } => {
+            let mut cmd = Command::new("docker");
+            cmd.arg("build").arg("-t").arg(tag).arg(context);
+            cmd
+        }
+        BuildStep::SecurityScan { image, policy } => {
+            let mut cmd = Command::new("trivy");
+            cmd.args(scan::trivy_args(image, policy));
+            cmd
+        }
+        BuildStep::Shell { script } => {
+            validate_script(script)?;
+            let mut cmd = Command::new("sh");
+            cmd.arg("-c").arg(script);
+            cmd
+        }
+        BuildStep::Make { target } => {
+            let mut cmd = Command::new("make");
+            cmd.arg(target);
+            cmd
+        }
+        BuildStep::Just { recipe } => {
+            let mut cmd = Command::new("just");
+            cmd.arg(recipe);
+            cmd
+        }
+        BuildStep::Command {
+            program, args, env, ..
+        } => {
+            let mut cmd = Command::new(program);
+            cmd.args(args).envs(env);
+            cmd
+        }
+    };
+
+    if let Some(target) = target {
+        if matches!(
+            step,
+            BuildStep::DockerBuild { .. }
+                | BuildStep::Shell { .. }
+                | BuildStep::Make { .. }
+                | BuildStep::Just { .. }
+                | BuildStep::Command { .. }
+        ) {
+            cmd.env("CARGO_BUILD_TARGET", target);
+        }
+    }
+
+    match step {
+        BuildStep::Command {
+            working_dir: Some(dir),
+            ..
+        } => {
+            validate_working_dir(dir)?;
+            cmd.current_dir(workdir.join(dir));
+        }
+        _ => {
+            cmd.current_dir(workdir);
+        }
+    }
+    Ok(cmd)
+}
+
+/// Require a step working directory to stay inside the workspace
+pub fn validate_working_dir(dir: &Path) -> Result<()> {
+    let escapes = dir
+        .components()
+        .any(|c| !matches!(c, Component::Normal(_) | Component::CurDir));
+    if escapes {
+        return Err(anyhow!(
+            "working_dir '{}' must be a relative path inside the repository",
+            dir.display()
+        ));
+    }
+    Ok(())
+}
+
+/// `cargo` with `args`, passing `--target` after the subcommand if set
+fn cargo(args: &[&str], target: Option<&str>) -> Command {
+    let mut cmd = Command::new("cargo");
+    let (subcommand, rest) = args.split_first().expect("cargo subcommand");
+    cmd.arg(subcommand);
+    if let Some(target) = target {
+        cmd.arg("--target").arg(target);
+    }
+    cmd.args(rest);
+    cmd
+}
+
+/// Reject shell scripts that attempt to recursively delete the filesystem root
+///
+/// This is a guard against obvious mistakes, not a sandbox: the script still
+/// runs with the agent's permissions.
+pub fn validate_script(script: &str) -> Result<()> {
+    if script.trim().is_empty() {
+        return Err(anyhow!("Shell step script is empty"));
+    }
+
+    let statements = script.split([';', '\n', '&', '|']);
+    for statement in statements {
+        let mut tokens = statement.split_whitespace().peekable();
+
+        // Skip privilege escalation prefixes
+        while matches!(tokens.peek(), Some(&"sudo") | Some(&"doas") | Some(&"exec")) {
+            tokens.next();
+        }
+
+        if tokens.next() != Some("rm") {
+            continue;
+        }
+
+        let mut recursive = false;
+        let mut targets_root = false;
+        for token in tokens {
+            if token == "--recursive"
+                || (token.starts_with('-')
+                    && !token.starts_with("--")
+                    && token.contains(['r', 'R']))
+            {
+                recursive = true;
+            } else if matches!(token.trim_matches(['"', '\'']), "/" | "/*" | "/.") {
+                targets_root = true;
+            }
+        }
+
+        if recursive && targets_root {
+            return Err(anyhow!(
+                "Shell step rejected: script contains a recursive delete of '/' ({})",
+                statement.trim()
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn program_and_args(cmd: &Command) -> (String, Vec<String>) {
+        let std_cmd = cmd.as_std();
+        (
+            std_cmd.get_program().to_string_lossy().to_string(),
+            std_cmd
+                .get_args()
+                .map(|a| a.to_string_lossy().to_string())
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_build_command_cargo() {
+        let cmd = build_command(&BuildStep::Build { release: true }, Path::new("/tmp")).unwrap();
+        let (program, args) = program_and_args(&cmd);
+        assert_eq!(program, "cargo");
+        assert_eq!(args, vec!["build", "--release"]);
+
+        let cmd = build_command(&BuildStep::Bench, Path::new("/tmp")).unwrap();
+        let (_, args) = program_and_args(&cmd);
+        assert_eq!(
+            args,
+            vec![
+                "bench",
+                "--all-features",
+                "--",
+                "--output-format",
+                "bencher"
+            ]
+        );
+
+        let step = BuildStep::Coverage {
+            min_percent: Some(80),
+        };
+        let (_, args) = program_and_args(&build_command(&step, Path::new("/tmp")).unwrap());
+        assert_eq!(
+            args,
+            vec![
+                "tarpaulin",
+                "--out",
+                "Xml",
+                "--output-dir",
+                "target/coverage"
+            ]
+        );
+        assert_eq!(step.name(), "coverage");
+
+        let step = BuildStep::Outdated {
+            fail_on_outdated: true,
+        };
+        let (_, args) = program_and_args(&build_command(&step, Path::new("/tmp")).unwrap());
+        assert_eq!(
+            args,
+            vec!["outdated", "--exit-code", "0", "--format", "json"]
+        );
+        assert_eq!(step.name(), "outdated");
+
+        let step = BuildStep::Udeps {
+            fail_on_unused_deps: false,
+        };
+        let (_, args) = program_and_args(&build_command(&step, Path::new("/tmp")).unwrap());
+        assert_eq!(
+            args,
+            vec!["+nightly", "udeps", "--all-targets", "--output", "json"]
+        );
+        assert_eq!(step.name(), "udeps");
+    }
+
+    #[test]
+    fn test_deny_command() {
+        let step = BuildStep::Deny { config: None };
+        let (program, args) = program_and_args(&build_command(&step, Path::new("/tmp")).unwrap());
+        assert_eq!(program, "cargo");
+        assert_eq!(args, vec!["deny", "check", "--config", "deny.toml"]);
+        assert_eq!(step.name(), "deny");
+
+        let step = BuildStep::Deny {
+            config: Some(PathBuf::from(deny::GENERATED_DENY_CONFIG)),
+        };
+        let (_, args) = program_and_args(&build_command(&step, Path::new("/tmp")).unwrap());
+        assert_eq!(args[3], "target/raibid/deny.toml");
+    }
+
+    #[test]
+    fn test_miri_command() {
+        let step = BuildStep::Miri { strict: false };
+        let (program, args) = program_and_args(&build_command(&step, Path::new("/tmp")).unwrap());
+        assert_eq!(program, "cargo");
+        assert_eq!(args, vec!["miri", "test"]);
+        assert_eq!(step.name(), "miri");
+
+        let target = Some("aarch64-unknown-linux-gnu");
+        let cmd = build_command_for_target(&step, Path::new("/tmp"), target).unwrap();
+        assert_eq!(
+            program_and_args(&cmd).1,
+            vec!["miri", "test", "--target", "aarch64-unknown-linux-gnu"]
+        );
+    }
+
+    #[test]
+    fn test_cross_compile_command() {
+        let step = BuildStep::CrossCompile {
+            target: "aarch64-unknown-linux-gnu".to_string(),
+        };
+        let (program, args) = program_and_args(&build_command(&step, Path::new("/tmp")).unwrap());
+        assert_eq!(program, "cross");
+        assert_eq!(
+            args,
+            vec![
+                "build",
+                "--target",
+                "aarch64-unknown-linux-gnu",
+                "--release"
+            ]
+        );
+        assert_eq!(step.name(), "cross:aarch64-unknown-linux-gnu");
+    }
+
+    #[test]
+    fn test_security_scan_command() {
+        let step = BuildStep::SecurityScan {
+            image: "registry.local/app:1.0".to_string(),
+            policy: scan::ScanPolicy::default(),
+        };
+        let (program, args) = program_and_args(&build_command(&step, Path::new("/tmp")).unwrap());
+        assert_eq!(program, "trivy");
+        assert_eq!(args[0], "image");
+        assert!(args.contains(&"registry.local/app:1.0".to_string()));
+        assert_eq!(step.name(), "security-scan");
+        assert!(!PipelineStep::from(step).continue_on_failure);
+
+        let step = BuildStep::SecurityScan {
+            image: "registry.local/app:1.0".to_string(),
+            policy: scan::ScanPolicy {
+                continue_on_failure: true,
+                ..Default::default()
+            },
+        };
+        assert!(PipelineStep::from(step).continue_on_failure);
+    }
+
+    #[test]
+    fn test_build_command_for_target() {
+        let target = Some("aarch64-unknown-linux-gnu");
+        let cmd = build_command_for_target(&BuildStep::Clippy, Path::new("/tmp"), target).unwrap();
+        assert_eq!(
+            program_and_args(&cmd).1,
+            vec![
+                "clippy",
+                "--target",
+                "aarch64-unknown-linux-gnu",
+                "--all-targets",
+                "--",
+                "-D",
+                "warnings"
+            ]
+        );
+
+        // fmt does not compile
+        let cmd = build_command_for_target(&BuildStep::Fmt, Path::new("/tmp"), target).unwrap();
+        assert!(!program_and_args(&cmd).1.contains(&"--target".to_string()));
+
+        let step = BuildStep::Make {
+            target: "release".to_string(),
+        };
+        let cmd = build_command_for_target(&step, Path::new("/tmp"), target).unwrap();
+        let env: Vec<_> = cmd.as_std().get_envs().collect();
+        assert_eq!(
+            env,
+            vec![(
+                std::ffi::OsStr::new("CARGO_BUILD_TARGET"),
+                Some(std::ffi::OsStr::new("aarch64-unknown-linux-gnu"))
+            )]
+        );
+    }
+
+    #[test]
+    fn test_build_command_shell() {
+        let step = BuildStep::Shell {
+            script: "./scripts/generate.sh".to_string(),
+        };
+        let cmd = build_command(&step, Path::new("/tmp")).unwrap();
+        let (program, args) = program_and_args(&cmd);
+        assert_eq!(program, "sh");
+        assert_eq!(args, vec!["-c", "./scripts/generate.sh"]);
+    }
+
+    #[test]
+    fn test_build_command_make_and_just() {
+        let make = build_command(
+            &BuildStep::Make {
+                target: "release".to_string(),
+            },
+            Path::new("/tmp"),
+        )
+        .unwrap();
+        assert_eq!(
+            program_and_args(&make),
+            ("make".to_string(), vec!["release".to_string()])
+        );
+
+        let just = build_command(
+            &BuildStep::Just {
+                recipe: "ci".to_string(),
+            },
+            Path::new("/tmp"),
+        )
+        .unwrap();
+        assert_eq!(
+            program_and_args(&just),
+            ("just".to_string(), vec!["ci".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_build_command_custom() {
+        let step = BuildStep::Command {
+            name: "proto".to_string(),
+            program: "buf".to_string(),
+            args: vec!["generate".to_string()],
+            working_dir: Some(PathBuf::from("proto")),
+            env: BTreeMap::from([("BUF_CACHE_DIR".to_string(), "/cache".to_string())]),
+        };
+        let cmd = build_command(&step, Path::new("/tmp/ws")).unwrap();
+        assert_eq!(
+            program_and_args(&cmd),
+            ("buf".to_string(), vec!["generate".to_string()])
+        );
+        assert_eq!(
+            cmd.as_std().get_current_dir(),
+            Some(Path::new("/tmp/ws/proto"))
+        );
+        assert_eq!(step.name(), "proto");
+    }
+
+    #[test]
+    fn test_validate_working_dir() {
+        assert!(validate_working_dir(Path::new("crates/agent")).is_ok());
+        assert!(validate_working_dir(Path::new("./proto")).is_ok());
+        assert!(validate_working_dir(Path::new("../other")).is_err());
+        assert!(validate_working_dir(Path::new("/etc")).is_err());
+    }
+
+    #[test]
+    fn test_validate_script_rejects_root_delete() {
+        assert!(validate_script("rm -rf /").is_err());
+        assert!(validate_script("echo hi && sudo rm -fr /*").is_err());
+        assert!(validate_script("rm -r --no-preserve-root /").is_err());
+        assert!(validate_script("cd build; rm --recursive --force '/'").is_err());
+    }
+
+    #[test]
+    fn test_validate_script_allows_safe_scripts() {
+        assert!(validate_script("rm -rf ./target").is_ok());
+        assert!(validate_script("rm -rf --no-preserve-root ./target").is_ok());
+        assert!(validate_script("rm -f /tmp/build.lock").is_ok());
+        assert!(validate_script("cargo build --release").is_ok());
+        assert!(validate_script("   ").is_err());
+    }
+
+    #[test]
+    fn test_from_name() {
+        assert_eq!(BuildStep::from_name("test"), Some(BuildStep::Test));
+        assert_eq!(BuildStep::from_name("bench"), Some(BuildStep::Bench));
+        assert_eq!(
+            BuildStep::from_name("coverage"),
+            Some(BuildStep::Coverage { min_percent: None })
+        );
+        assert_eq!(
+            BuildStep::from_name("deny"),
+            Some(BuildStep::Deny { config: None })
+        );
+        assert_eq!(
+            BuildStep::from_name("miri"),
+            Some(BuildStep::Miri { strict: false })
+        );
+        assert_eq!(
+            BuildStep::from_name("outdated"),
+            Some(BuildStep::Outdated {
+                fail_on_outdated: false
+            })
+        );
+        assert_eq!(
+            BuildStep::from_name("udeps"),
+            Some(BuildStep::Udeps {
+                fail_on_unused_deps: false
+            })
+        );
+        assert_eq!(
+            BuildStep::from_name("build-release"),
+            Some(BuildStep::Build { release: true })
+        );
+        assert_eq!(BuildStep::from_name("deploy"), None);
+    }
+}
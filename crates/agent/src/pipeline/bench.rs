@@ -0,0 +1,39 @@
+//! Benchmark results
+//!
+//! A `bench` step runs `cargo bench` with Bencher format output, which the
+//! executor parses into [`BenchResult`]s. They are stored per job and as the
+//! latest results of the job's branch, which the server compares across
+//! branches.
+
+use anyhow::{Context, Result};
+
+use raibid_common::benchmark::BenchResult;
+use raibid_common::jobs::{benchmarks_key, latest_benchmarks_key, JobTrigger};
+
+/// Store a job's benchmark results in Redis as JSON
+///
+/// Also replaces the latest results of the repository branch the job ran on.
+pub async fn store_benchmarks<C>(
+    conn: &mut C,
+    job_id: &str,
+    trigger: &JobTrigger,
+    results: &[BenchResult],
+) -> Result<()>
+where
+    C: redis::aio::ConnectionLike + Send,
+{
+    let json = serde_json::to_string(results).context("Failed to encode benchmark results")?;
+    redis::pipe()
+        .atomic()
+        .cmd("SET")
+        .arg(benchmarks_key(job_id))
+        .arg(&json)
+        .ignore()
+        .cmd("SET")
+        .arg(latest_benchmarks_key(&trigger.repo, &trigger.branch))
+        .arg(&json)
+        .ignore()
+        .query_async::<_, ()>(conn)
+        .await
+        .with_context(|| format!("Failed to store benchmark results of job {}", job_id))
+}
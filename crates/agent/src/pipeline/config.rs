@@ -0,0 +1,559 @@
+//! `raibid.yml` pipeline definitions
+//!
+//! A `raibid.yml` at the repository root replaces the build steps with
+//! arbitrary commands. It takes precedence over `.raibid.yaml`
+//! ([`PipelineConfig`](crate::config::PipelineConfig)); without either file
+//! the default steps run.
+//!
+//! ```yaml
+//! default_step_timeout_secs: 600
+//! steps:
+//!   - name: generate
+//!     command: buf
+//!     args: [generate]
+//!     working_dir: proto
+//!   - name: test
+//!     command: cargo
+//!     args: [test, --workspace]
+//!     env:
+//!       RUST_BACKTRACE: "1"
+//!     timeout_secs: 1800
+//!   - name: audit
+//!     command: cargo
+//!     args: [audit]
+//!     continue_on_failure: true
+//!   - name: licenses
+//!     uses: deny
+//!     config: ci/deny.toml
+//!     continue_on_failure: true
+//! ```
+//!
+//! `uses` runs a pre-defined step such as `deny` or `coverage` instead of a
+//! command; it is reported under the pre-defined step's name.
+//!
+//! `cross_compile_targets` adds a `cross:<target>` step for each target
+//! after the listed steps (see [`crate::pipeline::cross`]):
+//!
+//! ```yaml
+//! steps:
+//!   - name: test
+//!     uses: test
+//! cross_compile_targets: [aarch64-unknown-linux-gnu]
+//! ```
+//!
+//! `fail_on_outdated` fails `outdated` steps when any dependency is outdated,
+//! rather than only warning (see [`crate::pipeline::outdated`]):
+//!
+//! ```yaml
+//! steps:
+//!   - name: outdated
+//!     uses: outdated
+//! fail_on_outdated: true
+//! ```
+//!
+//! `fail_on_unused_deps` likewise fails `udeps` steps when any dependency is
+//! unused (see [`crate::pipeline::udeps`]).
+//!
+//! Steps without their own `timeout_secs` are killed after
+//! `default_step_timeout_secs`; without either, a step may run indefinitely.
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use super::cross::{cross_compile_steps, is_valid_target};
+use super::{validate_working_dir, BuildStep, PipelineStep};
+
+/// File name of the pipeline definition in a repository
+pub const PIPELINE_DEFINITION_FILE: &str = "raibid.yml";
+
+/// Pipeline definition parsed from `raibid.yml`
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct PipelineDefinition {
+    /// Timeout in seconds of steps without their own `timeout_secs`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_step_timeout_secs: Option<u64>,
+
+    /// Steps to run, in order
+    pub steps: Vec<StepDefinition>,
+
+    /// Targets to cross-compile release binaries for after the steps
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub cross_compile_targets: Vec<String>,
+
+    /// Fail `outdated` steps if any dependency is outdated
+    #[serde(default)]
+    pub fail_on_outdated: bool,
+
+    /// Fail `udeps` steps if any dependency is unused
+    #[serde(default)]
+    pub fail_on_unused_deps: bool,
+}
+
+/// A single step in `raibid.yml`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct StepDefinition {
+    /// Step name used in logs and status reporting
+    pub name: String,
+
+    /// Program to run
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub command: String,
+
+    /// Pre-defined step to run instead of `command`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uses: Option<String>,
+
+    /// `deny.toml` for a `deny` step, relative to the repository root
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub config: Option<PathBuf>,
+
+    /// Arguments passed to the program
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub args: Vec<String>,
+
+    /// Directory to run in, relative to the repository root
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub working_dir: Option<PathBuf>,
+
+    /// Extra environment variables
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+
+    /// Kill the step if it runs longer than this many seconds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout_secs: Option<u64>,
+
+    /// Keep running later steps if this one fails
+    #[serde(default)]
+    pub continue_on_failure: bool,
+}
+
+impl StepDefinition {
+    /// Check the step for errors
+    pub fn validate(&self) -> Result<()> {
+        if self.name.trim().is_empty() {
+            bail!("name must not be empty");
+        }
+        match self.uses {
+            Some(ref uses) => {
+                if !self.command.is_empty() {
+                    bail!("step '{}': command and uses are exclusive", self.name);
+                }
+                if BuildStep::from_name(uses).is_none() {
+                    bail!("step '{}': unknown build step '{}'", self.name, uses);
+                }
+                if self.config.is_some() && uses != "deny" {
+                    bail!("step '{}': config is only supported by deny", self.name);
+                }
+            }
+            None => {
+                if self.command.trim().is_empty() {
+                    bail!("step '{}': command must not be empty", self.name);
+                }
+                if self.config.is_some() {
+                    bail!("step '{}': config requires uses: deny", self.name);
+                }
+            }
+        }
+        if self.timeout_secs == Some(0) {
+            bail!("step '{}': timeout_secs must be greater than 0", self.name);
+        }
+        if let Some(ref dir) = self.working_dir {
+            validate_working_dir(dir).with_context(|| format!("step '{}'", self.name))?;
+        }
+        if let Some(ref config) = self.config {
+            validate_working_dir(config).with_context(|| format!("step '{}'", self.name))?;
+        }
+        Ok(())
+    }
+
+    /// Convert this definition into an executable pipeline step
+    pub fn to_pipeline_step(&self) -> Result<PipelineStep> {
+        let step = match self.uses {
+            Some(ref uses) => match BuildStep::from_name(uses) {
+                Some(BuildStep::Deny { .. }) => BuildStep::Deny {
+                    config: self.config.clone(),
+                },
+                Some(step) => step,
+                None => bail!("step '{}': unknown build step '{}'", self.name, uses),
+            },
+            None => BuildStep::Command {
+                name: self.name.clone(),
+                program: self.command.clone(),
+                args: self.args.clone(),
+                working_dir: self.working_dir.clone(),
+                env: self.env.clone(),
+            },
+        };
+
+        Ok(PipelineStep {
+            step,
+            timeout: self.timeout_secs.map(Duration::from_secs),
+            continue_on_failure: self.continue_on_failure,
+        })
+    }
+}
+
+impl PipelineDefinition {
+    /// Parse a pipeline definition from YAML
+    pub fn from_yaml(contents: &str) -> Result<Self> {
+        serde_yaml::from_str(contents).context("Failed to parse pipeline definition")
+    }
+
+    /// Serialize the pipeline definition to YAML
+    pub fn to_yaml(&self) -> Result<String> {
+        serde_yaml::to_string(self).context("Failed to serialize pipeline definition")
+    }
+
+    /// Load `raibid.yml` from a checked-out repository
+    ///
+    /// Returns `None` if the repository has no `raibid.yml`.
+    pub async fn load(repo_dir: &Path) -> Result<Option<Self>> {
+        let path = repo_dir.join(PIPELINE_DEFINITION_FILE);
+        let contents = match tokio::fs::read_to_string(&path).await {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e).with_context(|| format!("Failed to read {}", path.display())),
+        };
+
+        Self::from_yaml(&contents)
+            .with_context(|| format!("Invalid {}", path.display()))
+            .map(Some)
+    }
+
+    /// Validate every step and resolve the steps to execute
+    pub fn pipeline_steps(&self) -> Result<Vec<PipelineStep>> {
+        if self.steps.is_empty() {
+            bail!("{} defines no steps", PIPELINE_DEFINITION_FILE);
+        }
+        if self.default_step_timeout_secs == Some(0) {
+            bail!(
+                "Invalid {}: default_step_timeout_secs must be greater than 0",
+                PIPELINE_DEFINITION_FILE
+            );
+        }
+
+        for (index, step) in self.steps.iter().enumerate() {
+            step.validate().with_context(|| {
+                format!("Invalid {} steps[{}]", PIPELINE_DEFINITION_FILE, index)
+            })?;
+        }
+        if let Some((index, target)) = self
+            .cross_compile_targets
+            .iter()
+            .enumerate()
+            .find(|(_, target)| !is_valid_target(target))
+        {
+            bail!(
+                "Invalid {} cross_compile_targets[{}]: invalid target triple '{}'",
+                PIPELINE_DEFINITION_FILE,
+                index,
+                target
+            );
+        }
+
+        let default_timeout = self.default_step_timeout_secs.map(Duration::from_secs);
+        let mut steps = self
+            .steps
+            .iter()
+            .map(|step| {
+                step.to_pipeline_step()
+                    .map(|step| step.with_default_timeout(default_timeout))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        for step in &mut steps {
+            if let BuildStep::Outdated { fail_on_outdated } = &mut step.step {
+                *fail_on_outdated = self.fail_on_outdated;
+            }
+            if let BuildStep::Udeps {
+                fail_on_unused_deps,
+            } = &mut step.step
+            {
+                *fail_on_unused_deps = self.fail_on_unused_deps;
+            }
+        }
+        steps.extend(
+            cross_compile_steps(&self.cross_compile_targets)
+                .into_iter()
+                .map(|step| PipelineStep::from(step).with_default_timeout(default_timeout)),
+        );
+        Ok(steps)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn step(name: &str, command: &str) -> StepDefinition {
+        StepDefinition {
+            name: name.to_string(),
+            command: command.to_string(),
+            uses: None,
+            config: None,
+            args: Vec::new(),
+            working_dir: None,
+            env: BTreeMap::new(),
+            timeout_secs: None,
+            continue_on_failure: false,
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let definition = PipelineDefinition {
+            steps: vec![
+                StepDefinition {
+                    args: vec!["test".to_string(), "--workspace".to_string()],
+                    working_dir: Some(PathBuf::from("crates/agent")),
+                    env: BTreeMap::from([("RUST_BACKTRACE".to_string(), "1".to_string())]),
+                    timeout_secs: Some(1800),
+                    ..step("test", "cargo")
+                },
+                StepDefinition {
+                    continue_on_failure: true,
+                    ..step("audit", "cargo")
+                },
+            ],
+            default_step_timeout_secs: Some(600),
+            cross_compile_targets: vec!["aarch64-unknown-linux-gnu".to_string()],
+            fail_on_outdated: true,
+            fail_on_unused_deps: true,
+        };
+
+        let yaml = definition.to_yaml().unwrap();
+        assert_eq!(PipelineDefinition::from_yaml(&yaml).unwrap(), definition);
+
+        let json = serde_json::to_string(&definition).unwrap();
+        assert_eq!(
+            serde_json::from_str::<PipelineDefinition>(&json).unwrap(),
+            definition
+        );
+    }
+
+    #[test]
+    fn test_from_yaml_defaults() {
+        let definition =
+            PipelineDefinition::from_yaml("steps:\n  - name: lint\n    command: make\n").unwrap();
+        assert_eq!(definition.steps, vec![step("lint", "make")]);
+    }
+
+    #[test]
+    fn test_from_yaml_rejects_unknown_fields() {
+        let yaml = "steps:\n  - name: lint\n    command: make\n    retries: 3\n";
+        assert!(PipelineDefinition::from_yaml(yaml).is_err());
+    }
+
+    #[test]
+    fn test_pipeline_steps() {
+        let definition = PipelineDefinition {
+            steps: vec![StepDefinition {
+                timeout_secs: Some(60),
+                continue_on_failure: true,
+                ..step("lint", "make")
+            }],
+            ..Default::default()
+        };
+
+        let steps = definition.pipeline_steps().unwrap();
+        assert_eq!(steps[0].step.name(), "lint");
+        assert_eq!(steps[0].timeout, Some(Duration::from_secs(60)));
+        assert!(steps[0].continue_on_failure);
+    }
+
+    #[test]
+    fn test_cross_compile_targets() {
+        let yaml = r#"
+default_step_timeout_secs: 600
+steps:
+  - name: lint
+    command: make
+cross_compile_targets:
+  - aarch64-unknown-linux-gnu
+  - aarch64-unknown-linux-gnu
+  - x86_64-pc-windows-gnu
+"#;
+        let steps = PipelineDefinition::from_yaml(yaml)
+            .unwrap()
+            .pipeline_steps()
+            .unwrap();
+        let names: Vec<String> = steps.iter().map(|s| s.step.name()).collect();
+        assert_eq!(
+            names,
+            vec![
+                "lint",
+                "cross:aarch64-unknown-linux-gnu",
+                "cross:x86_64-pc-windows-gnu"
+            ]
+        );
+        assert_eq!(steps[2].timeout, Some(Duration::from_secs(600)));
+
+        let definition = PipelineDefinition {
+            steps: vec![step("lint", "make")],
+            cross_compile_targets: vec!["x86_64 linux".to_string()],
+            ..Default::default()
+        };
+        assert!(definition.pipeline_steps().is_err());
+    }
+
+    #[test]
+    fn test_default_step_timeout() {
+        let yaml = "default_step_timeout_secs: 300
+steps:
+  - name: fmt
+    command: cargo
+  - name: integration
+    command: cargo
+    timeout_secs: 1200
+";
+        let steps = PipelineDefinition::from_yaml(yaml)
+            .unwrap()
+            .pipeline_steps()
+            .unwrap();
+        assert_eq!(steps[0].timeout, Some(Duration::from_secs(300)));
+        assert_eq!(steps[1].timeout, Some(Duration::from_secs(1200)));
+
+        let definition = PipelineDefinition {
+            default_step_timeout_secs: Some(0),
+            steps: vec![step("lint", "make")],
+            ..Default::default()
+        };
+        assert!(definition.pipeline_steps().is_err());
+    }
+
+    #[test]
+    fn test_pipeline_steps_invalid() {
+        assert!(PipelineDefinition::default().pipeline_steps().is_err());
+
+        for invalid in [
+            step("", "make"),
+            step("lint", " "),
+            StepDefinition {
+                timeout_secs: Some(0),
+                ..step("lint", "make")
+            },
+            StepDefinition {
+                working_dir: Some(PathBuf::from("../elsewhere")),
+                ..step("lint", "make")
+            },
+        ] {
+            let definition = PipelineDefinition {
+                steps: vec![invalid],
+                ..Default::default()
+            };
+            assert!(definition.pipeline_steps().is_err());
+        }
+    }
+
+    #[test]
+    fn test_fail_on_outdated() {
+        let yaml = "steps:\n  - name: deps\n    uses: outdated\nfail_on_outdated: true\n";
+        let steps = PipelineDefinition::from_yaml(yaml)
+            .unwrap()
+            .pipeline_steps()
+            .unwrap();
+        assert_eq!(steps[0].step.name(), "outdated");
+        assert_eq!(
+            steps[0].step,
+            BuildStep::Outdated {
+                fail_on_outdated: true
+            }
+        );
+
+        let yaml = "steps:\n  - name: deps\n    uses: outdated\n";
+        let steps = PipelineDefinition::from_yaml(yaml)
+            .unwrap()
+            .pipeline_steps()
+            .unwrap();
+        assert_eq!(
+            steps[0].step,
+            BuildStep::Outdated {
+                fail_on_outdated: false
+            }
+        );
+    }
+
+    #[test]
+    fn test_fail_on_unused_deps() {
+        let yaml = "steps:\n  - name: deps\n    uses: udeps\nfail_on_unused_deps: true\n";
+        let steps = PipelineDefinition::from_yaml(yaml)
+            .unwrap()
+            .pipeline_steps()
+            .unwrap();
+        assert_eq!(steps[0].step.name(), "udeps");
+        assert_eq!(
+            steps[0].step,
+            BuildStep::Udeps {
+                fail_on_unused_deps: true
+            }
+        );
+    }
+
+    #[test]
+    fn test_uses_deny() {
+        let yaml = "steps:\n  - name: licenses\n    uses: deny\n    config: ci/deny.toml\n    continue_on_failure: true\n";
+        let definition = PipelineDefinition::from_yaml(yaml).unwrap();
+        let steps = definition.pipeline_steps().unwrap();
+        assert_eq!(
+            steps[0].step,
+            BuildStep::Deny {
+                config: Some(PathBuf::from("ci/deny.toml"))
+            }
+        );
+        assert!(steps[0].continue_on_failure);
+
+        let yaml = definition.to_yaml().unwrap();
+        assert!(!yaml.contains("command"));
+        assert_eq!(PipelineDefinition::from_yaml(&yaml).unwrap(), definition);
+    }
+
+    #[test]
+    fn test_uses_invalid() {
+        let uses = |uses: &str| StepDefinition {
+            uses: Some(uses.to_string()),
+            ..step("lint", "")
+        };
+
+        for invalid in [
+            uses("deploy"),
+            StepDefinition {
+                command: "make".to_string(),
+                ..uses("deny")
+            },
+            StepDefinition {
+                config: Some(PathBuf::from("deny.toml")),
+                ..uses("test")
+            },
+            StepDefinition {
+                config: Some(PathBuf::from("deny.toml")),
+                ..step("lint", "make")
+            },
+            StepDefinition {
+                config: Some(PathBuf::from("/etc/deny.toml")),
+                ..uses("deny")
+            },
+        ] {
+            assert!(invalid.validate().is_err(), "{:?}", invalid);
+        }
+        assert!(uses("coverage").validate().is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_load() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(PipelineDefinition::load(dir.path()).await.unwrap(), None);
+
+        std::fs::write(
+            dir.path().join(PIPELINE_DEFINITION_FILE),
+            "steps:\n  - name: lint\n    command: make\n",
+        )
+        .unwrap();
+        let definition = PipelineDefinition::load(dir.path()).await.unwrap().unwrap();
+        assert_eq!(definition.steps.len(), 1);
+    }
+}
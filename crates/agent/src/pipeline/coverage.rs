@@ -0,0 +1,110 @@
+//! Code coverage
+//!
+//! A `coverage` step runs `cargo tarpaulin`, which writes a Cobertura XML
+//! report to [`COVERAGE_OUTPUT_DIR`]. The executor reads the totals of the
+//! report's `<coverage>` root into a [`CoverageReport`] and, when the step
+//! has a threshold, fails the step if coverage is below it. Workspaces
+//! without tarpaulin installed skip the step.
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::path::Path;
+use tokio::process::Command;
+
+use raibid_common::coverage::CoverageReport;
+use raibid_common::jobs::coverage_key;
+
+/// Directory tarpaulin writes its report to, relative to the workspace
+pub const COVERAGE_OUTPUT_DIR: &str = "target/coverage";
+
+/// File name of the Cobertura report written by tarpaulin
+pub const COBERTURA_FILE: &str = "cobertura.xml";
+
+/// Totals on the `<coverage>` root of a Cobertura report
+#[derive(Debug, Deserialize)]
+struct CoberturaTotals {
+    #[serde(rename = "@lines-covered")]
+    lines_covered: u64,
+    #[serde(rename = "@lines-valid")]
+    lines_valid: u64,
+}
+
+/// Whether `cargo tarpaulin` is installed
+pub async fn tarpaulin_available() -> bool {
+    Command::new("which")
+        .arg("cargo-tarpaulin")
+        .output()
+        .await
+        .map(|output| output.status.success())
+        .unwrap_or(false)
+}
+
+/// Parse the line totals of a Cobertura XML report
+pub fn parse_cobertura(xml: &str) -> Result<CoverageReport> {
+    let totals: CoberturaTotals =
+        quick_xml::de::from_str(xml).context("Invalid Cobertura <coverage>")?;
+    Ok(CoverageReport {
+        lines_covered: totals.lines_covered,
+        lines_valid: totals.lines_valid,
+    })
+}
+
+/// Read the report a `coverage` step left in `workspace`
+pub fn read_coverage_report(workspace: &Path) -> Result<CoverageReport> {
+    let path = workspace.join(COVERAGE_OUTPUT_DIR).join(COBERTURA_FILE);
+    let xml = std::fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    parse_cobertura(&xml).with_context(|| format!("Invalid {}", path.display()))
+}
+
+/// Store a job's coverage in Redis as JSON
+pub async fn store_coverage<C>(conn: &mut C, job_id: &str, report: &CoverageReport) -> Result<()>
+where
+    C: redis::aio::ConnectionLike + Send,
+{
+    let json = serde_json::to_string(report).context("Failed to encode coverage report")?;
+    redis::cmd("SET")
+        .arg(coverage_key(job_id))
+        .arg(json)
+        .query_async::<_, ()>(conn)
+        .await
+        .with_context(|| format!("Failed to store coverage of job {}", job_id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const REPORT: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE coverage SYSTEM "http://cobertura.sourceforge.net/xml/coverage-04.dtd">
+<coverage lines-covered="145" lines-valid="200" line-rate="0.725" branches-covered="0" branches-valid="0" branch-rate="0" complexity="0" version="1.9" timestamp="1700000000">
+  <sources><source>/workspace</source></sources>
+  <packages>
+    <package name="src" line-rate="0.725" branch-rate="0" complexity="0">
+      <classes/>
+    </package>
+  </packages>
+</coverage>
+"#;
+
+    #[test]
+    fn test_parse_cobertura() {
+        let report = parse_cobertura(REPORT).unwrap();
+        assert_eq!(report.lines_covered, 145);
+        assert_eq!(report.lines_valid, 200);
+        assert!((report.percent() - 72.5).abs() < 1e-9);
+
+        assert!(parse_cobertura("<coverage/>").is_err());
+    }
+
+    #[test]
+    fn test_read_coverage_report() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(read_coverage_report(dir.path()).is_err());
+
+        let output = dir.path().join(COVERAGE_OUTPUT_DIR);
+        std::fs::create_dir_all(&output).unwrap();
+        std::fs::write(output.join(COBERTURA_FILE), REPORT).unwrap();
+        assert_eq!(read_coverage_report(dir.path()).unwrap().lines_covered, 145);
+    }
+}
@@ -0,0 +1,304 @@
+//! Cross-compilation with cross-rs
+//!
+//! Each target of `cross_compile_targets` in `.raibid.yaml` or `raibid.yml`
+//! adds a `cross:<target>` step running `cross build --target <target>
+//! --release`, which builds inside a Docker container with the target's
+//! toolchain. If `cross` is missing the executor tries `cargo install cross`;
+//! when that fails, or Docker is not running, the step fails.
+//!
+//! Binaries end up in `target/<target>/release/`. The outcome of each target
+//! is stored as a [`CrossTargetResult`] in the job's cross hash, and once
+//! every target succeeded their binaries are recorded in the job's
+//! [`ArtifactMetadata`].
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tokio::process::Command;
+use tracing::{info, warn};
+
+use super::BuildStep;
+use raibid_common::artifacts::ArtifactMetadata;
+use raibid_common::jobs::{artifacts_key, cross_results_key};
+
+/// Stored outcome of cross-compiling for one target
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct CrossTargetResult {
+    /// Target triple
+    pub target: String,
+    /// Whether the build succeeded
+    pub success: bool,
+    /// Binaries built, as `<target>/<file name>`
+    pub binaries: Vec<String>,
+}
+
+/// One `cross:<target>` step per target, in order and without duplicates
+pub fn cross_compile_steps(targets: &[String]) -> Vec<BuildStep> {
+    let mut steps: Vec<BuildStep> = Vec::with_capacity(targets.len());
+    for target in targets {
+        let step = BuildStep::CrossCompile {
+            target: target.clone(),
+        };
+        if !steps.contains(&step) {
+            steps.push(step);
+        }
+    }
+    steps
+}
+
+/// Whether `target` looks like a target triple
+pub fn is_valid_target(target: &str) -> bool {
+    !target.is_empty()
+        && target
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+}
+
+/// Directory `cross build --release` puts the binaries for `target` in
+pub fn release_dir(workspace: &Path, target: &str) -> PathBuf {
+    workspace.join("target").join(target).join("release")
+}
+
+/// Binaries in the release directory of `target`, as `<target>/<file name>`
+///
+/// Executables are the files with an execute bit, or an `.exe` extension for
+/// Windows targets; build scripts and dependencies live in subdirectories and
+/// are not included.
+pub fn release_binaries(workspace: &Path, target: &str) -> Result<Vec<String>> {
+    let dir = release_dir(workspace, target);
+    let entries =
+        fs::read_dir(&dir).with_context(|| format!("Failed to read {}", dir.display()))?;
+
+    let mut binaries = Vec::new();
+    for entry in entries {
+        let entry = entry.with_context(|| format!("Failed to read {}", dir.display()))?;
+        let metadata = entry.metadata()?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        if metadata.is_file() && is_executable(&name, &metadata) {
+            binaries.push(format!("{}/{}", target, name));
+        }
+    }
+    binaries.sort();
+    Ok(binaries)
+}
+
+#[cfg(unix)]
+fn is_executable(name: &str, metadata: &fs::Metadata) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    name.ends_with(".exe") || metadata.permissions().mode() & 0o111 != 0
+}
+
+#[cfg(not(unix))]
+fn is_executable(name: &str, _metadata: &fs::Metadata) -> bool {
+    name.ends_with(".exe")
+}
+
+/// Binaries of every target, if all of them were built
+pub fn artifact_metadata(results: &[CrossTargetResult]) -> Option<ArtifactMetadata> {
+    if results.is_empty() || !results.iter().all(|result| result.success) {
+        return None;
+    }
+    Some(ArtifactMetadata {
+        binaries: results
+            .iter()
+            .flat_map(|result| result.binaries.iter().cloned())
+            .collect(),
+    })
+}
+
+/// Whether `cross` runs with `env`
+pub async fn cross_available(env: &[(String, String)]) -> bool {
+    Command::new("cross")
+        .arg("--version")
+        .envs(env.iter().map(|(k, v)| (k, v)))
+        .output()
+        .await
+        .map(|output| output.status.success())
+        .unwrap_or(false)
+}
+
+/// Make sure `cross` is available, running `cargo install cross` if needed
+///
+/// Returns whether cross can run.
+pub async fn ensure_cross(env: &[(String, String)]) -> bool {
+    if cross_available(env).await {
+        return true;
+    }
+
+    info!("cross is not installed, running `cargo install cross`");
+    let installed = Command::new("cargo")
+        .arg("install")
+        .arg("cross")
+        .envs(env.iter().map(|(k, v)| (k, v)))
+        .output()
+        .await;
+    match installed {
+        Ok(output) if output.status.success() => cross_available(env).await,
+        Ok(output) => {
+            warn!(
+                "Failed to install cross: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+            false
+        }
+        Err(e) => {
+            warn!("Failed to run cargo install: {}", e);
+            false
+        }
+    }
+}
+
+/// Whether the Docker daemon cross builds in is running
+pub async fn docker_running(env: &[(String, String)]) -> bool {
+    Command::new("docker")
+        .arg("info")
+        .envs(env.iter().map(|(k, v)| (k, v)))
+        .output()
+        .await
+        .map(|output| output.status.success())
+        .unwrap_or(false)
+}
+
+/// Store the outcome of every cross-compilation target in Redis
+pub async fn store_cross_results<C>(
+    conn: &mut C,
+    job_id: &str,
+    results: &[CrossTargetResult],
+) -> Result<()>
+where
+    C: redis::aio::ConnectionLike + Send,
+{
+    let fields = results
+        .iter()
+        .map(|result| serde_json::to_string(result).map(|json| (result.target.as_str(), json)))
+        .collect::<serde_json::Result<Vec<_>>>()
+        .context("Failed to encode cross-compilation results")?;
+
+    redis::cmd("HSET")
+        .arg(cross_results_key(job_id))
+        .arg(&fields)
+        .query_async::<_, ()>(conn)
+        .await
+        .with_context(|| {
+            format!(
+                "Failed to store cross-compilation results of job {}",
+                job_id
+            )
+        })
+}
+
+/// Store a job's artifacts in Redis as JSON
+pub async fn store_artifacts<C>(
+    conn: &mut C,
+    job_id: &str,
+    artifacts: &ArtifactMetadata,
+) -> Result<()>
+where
+    C: redis::aio::ConnectionLike + Send,
+{
+    let json = serde_json::to_string(artifacts).context("Failed to encode artifacts")?;
+    redis::cmd("SET")
+        .arg(artifacts_key(job_id))
+        .arg(json)
+        .query_async::<_, ()>(conn)
+        .await
+        .with_context(|| format!("Failed to store artifacts of job {}", job_id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn targets(targets: &[&str]) -> Vec<String> {
+        targets.iter().map(|t| t.to_string()).collect()
+    }
+
+    fn result(target: &str, success: bool, binaries: &[&str]) -> CrossTargetResult {
+        CrossTargetResult {
+            target: target.to_string(),
+            success,
+            binaries: binaries
+                .iter()
+                .map(|b| format!("{}/{}", target, b))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_cross_compile_steps() {
+        let steps = cross_compile_steps(&targets(&[
+            "aarch64-unknown-linux-gnu",
+            "x86_64-pc-windows-gnu",
+            "aarch64-unknown-linux-gnu",
+        ]));
+        let names: Vec<String> = steps.iter().map(BuildStep::name).collect();
+        assert_eq!(
+            names,
+            vec![
+                "cross:aarch64-unknown-linux-gnu",
+                "cross:x86_64-pc-windows-gnu"
+            ]
+        );
+        assert!(cross_compile_steps(&[]).is_empty());
+
+        assert!(is_valid_target("armv7-unknown-linux-gnueabihf"));
+        assert!(!is_valid_target(""));
+        assert!(!is_valid_target("x86_64 linux"));
+        assert!(!is_valid_target("../x86_64-unknown-linux-gnu"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_release_binaries() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let workspace = tempfile::tempdir().unwrap();
+        let target = "aarch64-unknown-linux-gnu";
+        let dir = release_dir(workspace.path(), target);
+        assert_eq!(
+            dir,
+            workspace
+                .path()
+                .join("target/aarch64-unknown-linux-gnu/release")
+        );
+
+        fs::create_dir_all(dir.join("build")).unwrap();
+        for (name, mode) in [("app", 0o755), ("app-cli", 0o755), ("app.d", 0o644)] {
+            fs::write(dir.join(name), "").unwrap();
+            fs::set_permissions(dir.join(name), fs::Permissions::from_mode(mode)).unwrap();
+        }
+
+        assert_eq!(
+            release_binaries(workspace.path(), target).unwrap(),
+            vec![
+                "aarch64-unknown-linux-gnu/app",
+                "aarch64-unknown-linux-gnu/app-cli"
+            ]
+        );
+        assert!(release_binaries(workspace.path(), "x86_64-pc-windows-gnu").is_err());
+    }
+
+    #[test]
+    fn test_artifact_metadata() {
+        let results = vec![
+            result("aarch64-unknown-linux-gnu", true, &["app"]),
+            result("x86_64-pc-windows-gnu", true, &["app.exe"]),
+        ];
+        assert_eq!(
+            artifact_metadata(&results).unwrap().binaries,
+            vec![
+                "aarch64-unknown-linux-gnu/app",
+                "x86_64-pc-windows-gnu/app.exe"
+            ]
+        );
+
+        // Only recorded once every target is built
+        let results = vec![
+            result("aarch64-unknown-linux-gnu", true, &["app"]),
+            result("x86_64-pc-windows-gnu", false, &[]),
+        ];
+        assert_eq!(artifact_metadata(&results), None);
+        assert_eq!(artifact_metadata(&[]), None);
+    }
+}
@@ -0,0 +1,340 @@
+//! Dependency policy checks
+//!
+//! A `deny` step runs `cargo deny check`, which checks dependency licenses,
+//! security advisories, banned crates, and crate sources against a
+//! `deny.toml`. Repositories without one are checked against
+//! [`DEFAULT_DENY_CONFIG`], written to [`GENERATED_DENY_CONFIG`]. The executor
+//! appends a per-check summary from [`DenyReport`] to the step output.
+//! Workspaces without cargo-deny installed skip the step.
+
+use anyhow::{Context, Result};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use tokio::process::Command;
+
+/// Configuration file `cargo deny` reads by default, relative to the
+/// workspace
+pub const DENY_CONFIG_FILE: &str = "deny.toml";
+
+/// Where the default configuration is written, relative to the workspace
+pub const GENERATED_DENY_CONFIG: &str = "target/raibid/deny.toml";
+
+/// Policy used for repositories without a `deny.toml`
+///
+/// Allows permissive licenses only, so GPL dependencies fail unless the
+/// repository adds its own configuration with an exception. With version 2
+/// every advisory, including unmaintained crates, is an error.
+pub const DEFAULT_DENY_CONFIG: &str = r#"# Generated by raibid-ci: the repository has no deny.toml
+
+[advisories]
+version = 2
+yanked = "deny"
+
+[licenses]
+version = 2
+allow = [
+    "MIT",
+    "Apache-2.0",
+    "Apache-2.0 WITH LLVM-exception",
+    "BSD-2-Clause",
+    "BSD-3-Clause",
+    "ISC",
+    "Unicode-DFS-2016",
+    "Unicode-3.0",
+    "Zlib",
+]
+confidence-threshold = 0.8
+
+[bans]
+multiple-versions = "warn"
+wildcards = "warn"
+
+[sources]
+unknown-registry = "deny"
+unknown-git = "warn"
+allow-registry = ["https://github.com/rust-lang/crates.io-index"]
+"#;
+
+/// Whether `cargo deny` is installed
+pub async fn cargo_deny_available() -> bool {
+    Command::new("which")
+        .arg("cargo-deny")
+        .output()
+        .await
+        .map(|output| output.status.success())
+        .unwrap_or(false)
+}
+
+/// Configuration file for a `deny` step, relative to `workspace`
+///
+/// Uses `config` (or `deny.toml`) if the repository has it, otherwise writes
+/// [`DEFAULT_DENY_CONFIG`] and returns its path.
+pub fn prepare_deny_config(workspace: &Path, config: Option<&Path>) -> Result<PathBuf> {
+    let config = config.unwrap_or(Path::new(DENY_CONFIG_FILE));
+    if workspace.join(config).is_file() {
+        return Ok(config.to_path_buf());
+    }
+
+    let generated = workspace.join(GENERATED_DENY_CONFIG);
+    if let Some(parent) = generated.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+    std::fs::write(&generated, DEFAULT_DENY_CONFIG)
+        .with_context(|| format!("Failed to write {}", generated.display()))?;
+    Ok(PathBuf::from(GENERATED_DENY_CONFIG))
+}
+
+/// One of the checks `cargo deny check` runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DenyCheck {
+    Licenses,
+    Advisories,
+    Bans,
+    Sources,
+}
+
+impl DenyCheck {
+    /// Every check, in the order they are reported
+    pub const ALL: [DenyCheck; 4] = [
+        DenyCheck::Licenses,
+        DenyCheck::Advisories,
+        DenyCheck::Bans,
+        DenyCheck::Sources,
+    ];
+
+    /// Name of the check in `cargo deny` output
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DenyCheck::Licenses => "licenses",
+            DenyCheck::Advisories => "advisories",
+            DenyCheck::Bans => "bans",
+            DenyCheck::Sources => "sources",
+        }
+    }
+
+    /// Check that reported a diagnostic code
+    ///
+    /// Older cargo-deny versions use codes such as `L001`, named after the
+    /// check; newer ones use names such as `rejected`.
+    fn from_code(code: &str) -> Option<Self> {
+        if let Some((prefix, number)) = code.as_bytes().split_first() {
+            if !number.is_empty() && number.iter().all(u8::is_ascii_digit) {
+                return match prefix {
+                    b'L' => Some(DenyCheck::Licenses),
+                    b'A' => Some(DenyCheck::Advisories),
+                    b'B' => Some(DenyCheck::Bans),
+                    b'S' => Some(DenyCheck::Sources),
+                    _ => None,
+                };
+            }
+        }
+
+        match code {
+            "rejected" | "unlicensed" | "no-license-field" | "parse-error" | "gather-failure" => {
+                Some(DenyCheck::Licenses)
+            }
+            "vulnerability"
+            | "unmaintained"
+            | "unsound"
+            | "notice"
+            | "yanked"
+            | "index-failure"
+            | "advisory-not-detected" => Some(DenyCheck::Advisories),
+            "banned"
+            | "duplicate"
+            | "wildcard"
+            | "not-allowed"
+            | "build-script-not-allowed"
+            | "unmatched-skip" => Some(DenyCheck::Bans),
+            "source-not-allowed" | "git-source-underspecified" | "unmatched-source" => {
+                Some(DenyCheck::Sources)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Errors of a `cargo deny check` run, grouped by check
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DenyReport {
+    pub licenses: Vec<String>,
+    pub advisories: Vec<String>,
+    pub bans: Vec<String>,
+    pub sources: Vec<String>,
+}
+
+impl DenyReport {
+    /// Collect the `error[<code>]: <message>` diagnostics of `cargo deny`
+    /// output
+    ///
+    /// Warnings are not failures and are left out.
+    pub fn parse(output: &str) -> Self {
+        let mut report = Self::default();
+        for line in output.lines() {
+            let Some(rest) = line.trim_start().strip_prefix("error[") else {
+                continue;
+            };
+            let Some((code, message)) = rest.split_once("]:") else {
+                continue;
+            };
+            if let Some(check) = DenyCheck::from_code(code) {
+                report
+                    .errors_mut(check)
+                    .push(format!("{}: {}", code, message.trim()));
+            }
+        }
+        report
+    }
+
+    /// Errors reported by `check`
+    pub fn errors(&self, check: DenyCheck) -> &[String] {
+        match check {
+            DenyCheck::Licenses => &self.licenses,
+            DenyCheck::Advisories => &self.advisories,
+            DenyCheck::Bans => &self.bans,
+            DenyCheck::Sources => &self.sources,
+        }
+    }
+
+    fn errors_mut(&mut self, check: DenyCheck) -> &mut Vec<String> {
+        match check {
+            DenyCheck::Licenses => &mut self.licenses,
+            DenyCheck::Advisories => &mut self.advisories,
+            DenyCheck::Bans => &mut self.bans,
+            DenyCheck::Sources => &mut self.sources,
+        }
+    }
+
+    /// Checks that reported errors
+    pub fn failed_checks(&self) -> Vec<DenyCheck> {
+        DenyCheck::ALL
+            .into_iter()
+            .filter(|check| !self.errors(*check).is_empty())
+            .collect()
+    }
+}
+
+impl fmt::Display for DenyReport {
+    /// Summary appended to the step output, one section per check
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "cargo-deny summary:")?;
+        for check in DenyCheck::ALL {
+            let errors = self.errors(check);
+            if errors.is_empty() {
+                writeln!(f, "  {}: ok", check.as_str())?;
+                continue;
+            }
+            writeln!(f, "  {}: FAILED ({})", check.as_str(), errors.len())?;
+            for error in errors {
+                writeln!(f, "    - {}", error)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `cargo deny check` output for a workspace depending on a GPL crate,
+    /// an unmaintained crate, and a banned crate
+    const OUTPUT: &str = r#"error[rejected]: failed to satisfy license requirements
+   ┌─ registry+https://github.com/rust-lang/crates.io-index#gpl-crate@1.0.0:4:12
+   │
+ 4 │ license = "GPL-3.0-only"
+   │            ━━━━━━━━━━━━
+   │            │
+   │            rejected: license is not explicitly allowed
+
+error[unmaintained]: `ansi_term` is unmaintained
+   ┌─ /workspace/Cargo.lock:4:1
+   │
+ 4 │ ansi_term 0.12.1 registry+https://github.com/rust-lang/crates.io-index
+   │ ---------------------------------------------------------------------- unmaintained advisory detected
+
+error[banned]: crate 'openssl = 0.10.64' is explicitly banned
+   ┌─ /workspace/deny.toml:12:6
+
+warning[duplicate]: found 2 duplicate entries for crate 'syn'
+
+advisories FAILED, bans FAILED, licenses FAILED, sources ok
+"#;
+
+    #[test]
+    fn test_parse_groups_errors_by_check() {
+        let report = DenyReport::parse(OUTPUT);
+        assert_eq!(
+            report.licenses,
+            vec!["rejected: failed to satisfy license requirements"]
+        );
+        assert_eq!(
+            report.advisories,
+            vec!["unmaintained: `ansi_term` is unmaintained"]
+        );
+        assert_eq!(
+            report.bans,
+            vec!["banned: crate 'openssl = 0.10.64' is explicitly banned"]
+        );
+        assert!(report.sources.is_empty());
+        assert_eq!(
+            report.failed_checks(),
+            vec![DenyCheck::Licenses, DenyCheck::Advisories, DenyCheck::Bans]
+        );
+
+        let summary = report.to_string();
+        assert!(summary.contains("  licenses: FAILED (1)\n"));
+        assert!(summary.contains("  sources: ok\n"));
+    }
+
+    #[test]
+    fn test_parse_legacy_codes() {
+        let report = DenyReport::parse(
+            "error[L001]: failed to satisfy license requirements\n\
+             error[A001]: Vulnerable crate\n\
+             error[S001]: detected git source",
+        );
+        assert_eq!(report.licenses.len(), 1);
+        assert_eq!(report.advisories.len(), 1);
+        assert_eq!(report.sources.len(), 1);
+        assert!(
+            DenyReport::parse("advisories ok, bans ok, licenses ok, sources ok")
+                .failed_checks()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_prepare_deny_config() {
+        let dir = tempfile::tempdir().unwrap();
+
+        // Missing: the default policy is generated
+        let path = prepare_deny_config(dir.path(), None).unwrap();
+        assert_eq!(path, PathBuf::from(GENERATED_DENY_CONFIG));
+        let generated = std::fs::read_to_string(dir.path().join(&path)).unwrap();
+        let policy: toml::Value = toml::from_str(&generated).unwrap();
+        let allowed = policy["licenses"]["allow"].as_array().unwrap();
+        assert!(allowed.contains(&toml::Value::from("MIT")));
+        assert!(allowed.contains(&toml::Value::from("Apache-2.0")));
+        assert!(!allowed.iter().any(|l| l.as_str().unwrap().contains("GPL")));
+
+        // The repository's own configuration is used as is
+        std::fs::write(
+            dir.path().join("deny.toml"),
+            "[licenses]\nversion = 2\nallow = [\"GPL-3.0\"]\n",
+        )
+        .unwrap();
+        assert_eq!(
+            prepare_deny_config(dir.path(), None).unwrap(),
+            PathBuf::from("deny.toml")
+        );
+
+        std::fs::create_dir(dir.path().join("ci")).unwrap();
+        std::fs::write(dir.path().join("ci/deny.toml"), "").unwrap();
+        assert_eq!(
+            prepare_deny_config(dir.path(), Some(Path::new("ci/deny.toml"))).unwrap(),
+            PathBuf::from("ci/deny.toml")
+        );
+    }
+}
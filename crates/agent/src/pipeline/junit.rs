@@ -0,0 +1,348 @@
+//! JUnit XML test reports
+//!
+//! After a `test` step the executor collects the JUnit XML reports test
+//! runners left in the workspace: everything under `target/nextest/` (written
+//! by `cargo nextest` with JUnit output enabled) and any `TEST-*.xml` file.
+//! Reports may have a `<testsuites>` or a single `<testsuite>` root; all of
+//! them are merged into one [`TestReport`].
+
+use anyhow::{Context, Result};
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use tracing::{debug, warn};
+
+use raibid_common::jobs::test_results_key;
+use raibid_common::test_report::{TestCaseResult, TestCaseStatus, TestReport, TestSuiteResult};
+
+/// Directory `cargo nextest` writes JUnit reports to, relative to the workspace
+pub const NEXTEST_REPORT_DIR: &str = "target/nextest";
+
+/// Build output directories never searched for reports
+const SKIPPED_DIRS: &[&str] = &[".git", "node_modules", "target/debug", "target/release"];
+
+/// A parsed JUnit XML report
+#[derive(Debug, Clone, Default, Deserialize, PartialEq)]
+pub struct JUnitReport {
+    #[serde(rename = "testsuite", default)]
+    pub suites: Vec<JUnitTestSuite>,
+}
+
+/// A `<testsuite>` element
+#[derive(Debug, Clone, Default, Deserialize, PartialEq)]
+pub struct JUnitTestSuite {
+    #[serde(rename = "@name", default)]
+    pub name: String,
+    #[serde(rename = "@tests", default)]
+    pub tests: Option<u32>,
+    #[serde(rename = "@failures", default)]
+    pub failures: Option<u32>,
+    #[serde(rename = "@errors", default)]
+    pub errors: Option<u32>,
+    #[serde(rename = "@skipped", default)]
+    pub skipped: Option<u32>,
+    #[serde(rename = "@time", default)]
+    pub time: Option<f64>,
+    #[serde(rename = "testcase", default)]
+    pub cases: Vec<JUnitTestCase>,
+}
+
+/// A `<testcase>` element
+#[derive(Debug, Clone, Default, Deserialize, PartialEq)]
+pub struct JUnitTestCase {
+    #[serde(rename = "@name", default)]
+    pub name: String,
+    #[serde(rename = "@classname", default)]
+    pub classname: Option<String>,
+    #[serde(rename = "@time", default)]
+    pub time: Option<f64>,
+    #[serde(default)]
+    pub failure: Option<JUnitFailure>,
+    #[serde(default)]
+    pub error: Option<JUnitFailure>,
+    #[serde(default)]
+    pub skipped: Option<JUnitFailure>,
+}
+
+/// A `<failure>`, `<error>` or `<skipped>` element
+#[derive(Debug, Clone, Default, Deserialize, PartialEq)]
+pub struct JUnitFailure {
+    #[serde(rename = "@message", default)]
+    pub message: Option<String>,
+    #[serde(rename = "$text", default)]
+    pub text: Option<String>,
+}
+
+impl JUnitReport {
+    /// Parse a report with a `<testsuites>` or `<testsuite>` root
+    pub fn parse(xml: &str) -> Result<Self> {
+        if root_element(xml)?.as_deref() == Some("testsuite") {
+            let suite: JUnitTestSuite =
+                quick_xml::de::from_str(xml).context("Invalid JUnit <testsuite>")?;
+            return Ok(Self {
+                suites: vec![suite],
+            });
+        }
+
+        quick_xml::de::from_str(xml).context("Invalid JUnit <testsuites>")
+    }
+}
+
+impl JUnitTestCase {
+    fn status(&self) -> TestCaseStatus {
+        if self.error.is_some() {
+            TestCaseStatus::Error
+        } else if self.failure.is_some() {
+            TestCaseStatus::Failed
+        } else if self.skipped.is_some() {
+            TestCaseStatus::Skipped
+        } else {
+            TestCaseStatus::Passed
+        }
+    }
+}
+
+impl From<JUnitTestCase> for TestCaseResult {
+    fn from(case: JUnitTestCase) -> Self {
+        let status = case.status();
+        let message = case
+            .error
+            .or(case.failure)
+            .and_then(|f| f.message.or(f.text))
+            .map(|m| m.trim().to_string());
+
+        Self {
+            name: case.name,
+            classname: case.classname,
+            time_secs: case.time,
+            status,
+            message,
+        }
+    }
+}
+
+impl From<JUnitTestSuite> for TestSuiteResult {
+    fn from(suite: JUnitTestSuite) -> Self {
+        let cases: Vec<TestCaseResult> = suite.cases.into_iter().map(Into::into).collect();
+        // Counts missing from the suite's attributes are taken from its cases
+        let count = |status| cases.iter().filter(|c| c.status == status).count() as u32;
+
+        Self {
+            name: suite.name,
+            tests: suite.tests.unwrap_or(cases.len() as u32),
+            failures: suite
+                .failures
+                .unwrap_or_else(|| count(TestCaseStatus::Failed)),
+            errors: suite.errors.unwrap_or_else(|| count(TestCaseStatus::Error)),
+            skipped: suite
+                .skipped
+                .unwrap_or_else(|| count(TestCaseStatus::Skipped)),
+            time_secs: suite.time.unwrap_or_default(),
+            cases,
+        }
+    }
+}
+
+impl From<JUnitReport> for TestReport {
+    fn from(report: JUnitReport) -> Self {
+        Self {
+            suites: report.suites.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+/// Name of the first element in `xml`
+fn root_element(xml: &str) -> Result<Option<String>> {
+    let mut reader = Reader::from_str(xml);
+    loop {
+        match reader.read_event().context("Invalid XML")? {
+            Event::Start(e) | Event::Empty(e) => {
+                return Ok(Some(String::from_utf8_lossy(e.name().as_ref()).to_string()))
+            }
+            Event::Eof => return Ok(None),
+            _ => {}
+        }
+    }
+}
+
+/// JUnit report files in `workspace`, sorted by path
+pub fn find_report_files(workspace: &Path) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    collect_report_files(workspace, Path::new(""), &mut files);
+    files.sort();
+    files
+}
+
+fn collect_report_files(dir: &Path, relative: &Path, files: &mut Vec<PathBuf>) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let relative = relative.join(entry.file_name());
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+
+        if file_type.is_dir() {
+            if !SKIPPED_DIRS
+                .iter()
+                .any(|skipped| relative == Path::new(skipped))
+            {
+                collect_report_files(&path, &relative, files);
+            }
+        } else if file_type.is_file() && is_report_file(&relative) {
+            files.push(path);
+        }
+    }
+}
+
+/// Whether a workspace-relative path matches `target/nextest/**/*.xml` or
+/// `**/TEST-*.xml`
+fn is_report_file(relative: &Path) -> bool {
+    if relative.extension().and_then(|e| e.to_str()) != Some("xml") {
+        return false;
+    }
+
+    relative.starts_with(NEXTEST_REPORT_DIR)
+        || relative
+            .file_name()
+            .and_then(|n| n.to_str())
+            .is_some_and(|n| n.starts_with("TEST-"))
+}
+
+/// Parse and merge every JUnit report in `workspace`
+///
+/// Returns `None` when there are no reports. Files that fail to parse are
+/// skipped with a warning.
+pub fn collect_test_report(workspace: &Path) -> Option<TestReport> {
+    let files = find_report_files(workspace);
+    if files.is_empty() {
+        return None;
+    }
+
+    let mut report = TestReport::default();
+    for file in files {
+        let parsed = std::fs::read_to_string(&file)
+            .with_context(|| format!("Failed to read {}", file.display()))
+            .and_then(|xml| JUnitReport::parse(&xml));
+
+        match parsed {
+            Ok(parsed) => {
+                debug!("Parsed test report {}", file.display());
+                report.merge(parsed.into());
+            }
+            Err(e) => warn!("Skipping test report {}: {:#}", file.display(), e),
+        }
+    }
+    Some(report)
+}
+
+/// Store a job's test report in Redis as JSON
+pub async fn store_test_report<C>(conn: &mut C, job_id: &str, report: &TestReport) -> Result<()>
+where
+    C: redis::aio::ConnectionLike + Send,
+{
+    let json = serde_json::to_string(report).context("Failed to encode test report")?;
+    redis::cmd("SET")
+        .arg(test_results_key(job_id))
+        .arg(json)
+        .query_async::<_, ()>(conn)
+        .await
+        .with_context(|| format!("Failed to store test results of job {}", job_id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIXTURE: &str = include_str!("../../tests/fixtures/junit.xml");
+
+    #[test]
+    fn test_parse_testsuites() {
+        let report: TestReport = JUnitReport::parse(FIXTURE).unwrap().into();
+        assert_eq!(report.suites.len(), 2);
+        assert_eq!(report.tests(), 5);
+        assert_eq!(report.failures(), 1);
+        assert_eq!(report.errors(), 1);
+        assert_eq!(report.skipped(), 1);
+        assert_eq!(report.passed(), 2);
+
+        let common = &report.suites[0];
+        assert_eq!(common.name, "raibid-common");
+        assert_eq!(common.time_secs, 0.75);
+        assert_eq!(common.cases.len(), 3);
+        assert_eq!(common.cases[0].status, TestCaseStatus::Passed);
+        assert_eq!(common.cases[1].status, TestCaseStatus::Failed);
+        assert_eq!(
+            common.cases[1].message.as_deref(),
+            Some("assertion `left == right` failed")
+        );
+        assert_eq!(common.cases[2].status, TestCaseStatus::Skipped);
+        assert_eq!(report.suites[1].cases[1].status, TestCaseStatus::Error);
+    }
+
+    #[test]
+    fn test_parse_single_testsuite() {
+        let xml = r#"<?xml version="1.0"?>
+<testsuite name="com.example.AppTest">
+  <testcase name="works" classname="com.example.AppTest" time="0.1"/>
+  <testcase name="breaks" classname="com.example.AppTest">
+    <failure>expected 1 but was 2</failure>
+  </testcase>
+</testsuite>"#;
+
+        let report: TestReport = JUnitReport::parse(xml).unwrap().into();
+        let suite = &report.suites[0];
+        assert_eq!(suite.name, "com.example.AppTest");
+        // Counts come from the test cases when the attributes are missing
+        assert_eq!(suite.tests, 2);
+        assert_eq!(suite.failures, 1);
+        assert_eq!(
+            suite.cases[1].message.as_deref(),
+            Some("expected 1 but was 2")
+        );
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        assert!(JUnitReport::parse("<testsuites><testsuite").is_err());
+    }
+
+    #[test]
+    fn test_find_report_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let write = |path: &str| {
+            let path = dir.path().join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, FIXTURE).unwrap();
+        };
+        write("target/nextest/ci/junit.xml");
+        write("java/target/surefire-reports/TEST-AppTest.xml");
+        write("target/debug/TEST-ignored.xml");
+        write("docs/notes.xml");
+
+        let files: Vec<_> = find_report_files(dir.path())
+            .into_iter()
+            .map(|f| f.strip_prefix(dir.path()).unwrap().to_path_buf())
+            .collect();
+        assert_eq!(
+            files,
+            vec![
+                PathBuf::from("java/target/surefire-reports/TEST-AppTest.xml"),
+                PathBuf::from("target/nextest/ci/junit.xml"),
+            ]
+        );
+
+        let report = collect_test_report(dir.path()).unwrap();
+        assert_eq!(report.tests(), 10);
+    }
+
+    #[test]
+    fn test_collect_without_reports() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(collect_test_report(dir.path()), None);
+    }
+}
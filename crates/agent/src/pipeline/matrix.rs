@@ -0,0 +1,140 @@
+//! Matrix build results
+//!
+//! A pipeline with a `matrix:` runs once per toolchain and target combination
+//! (see [`MatrixConfig`](crate::config::MatrixConfig)). The outcome of each
+//! combination is stored as a [`MatrixCellSummary`] in the job's matrix hash,
+//! keyed by the combination's name.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::executor::{MatrixPipelineResult, PipelineResult};
+use crate::MatrixCell;
+use raibid_common::jobs::matrix_results_key;
+use raibid_common::test_report::TestReport;
+
+/// Stored outcome of one matrix combination
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct MatrixCellSummary {
+    /// Toolchain the combination was built with
+    pub toolchain: Option<String>,
+    /// Target the combination was built for
+    pub target: Option<String>,
+    /// Whether every step succeeded or was allowed to fail
+    pub success: bool,
+    /// Steps that ran, in order
+    pub steps: Vec<StepSummary>,
+    /// Test results of the combination's `test` steps
+    pub test_report: Option<TestReport>,
+}
+
+/// Stored outcome of one step of a matrix combination
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct StepSummary {
+    /// Step name
+    pub name: String,
+    /// Whether the step exited successfully
+    pub success: bool,
+    /// Process exit code
+    pub exit_code: Option<i32>,
+    /// Wall-clock duration in milliseconds
+    pub duration_ms: u64,
+}
+
+impl MatrixCellSummary {
+    /// Summarize the result of running `cell`
+    pub fn new(cell: &MatrixCell, result: &PipelineResult) -> Self {
+        Self {
+            toolchain: cell.toolchain.clone(),
+            target: cell.target.clone(),
+            success: result.success(),
+            steps: result
+                .steps
+                .iter()
+                .map(|step| StepSummary {
+                    name: step.name.clone(),
+                    success: step.success,
+                    exit_code: step.exit_code,
+                    duration_ms: step.duration.as_millis() as u64,
+                })
+                .collect(),
+            test_report: result.test_report(),
+        }
+    }
+}
+
+/// Store the outcome of every combination of a matrix run in Redis
+pub async fn store_matrix_results<C>(
+    conn: &mut C,
+    job_id: &str,
+    result: &MatrixPipelineResult,
+) -> Result<()>
+where
+    C: redis::aio::ConnectionLike + Send,
+{
+    let fields = result
+        .cells
+        .iter()
+        .map(|(cell, result)| {
+            serde_json::to_string(&MatrixCellSummary::new(cell, result))
+                .map(|json| (cell.name(), json))
+        })
+        .collect::<serde_json::Result<Vec<_>>>()
+        .context("Failed to encode matrix results")?;
+
+    redis::cmd("HSET")
+        .arg(matrix_results_key(job_id))
+        .arg(&fields)
+        .query_async::<_, ()>(conn)
+        .await
+        .with_context(|| format!("Failed to store matrix results of job {}", job_id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executor::StepResult;
+    use std::time::Duration;
+
+    #[test]
+    fn test_cell_summary() {
+        let cell = MatrixCell {
+            toolchain: Some("1.75".to_string()),
+            target: None,
+        };
+        let result = PipelineResult {
+            steps: vec![StepResult {
+                name: "build".to_string(),
+                success: false,
+                exit_code: Some(101),
+                duration: Duration::from_millis(1500),
+                output: "error[E0658]".to_string(),
+                continue_on_failure: false,
+                test_report: None,
+                benchmarks: None,
+                coverage: None,
+                outdated: None,
+                unused_deps: None,
+                scan: None,
+                cross: None,
+                log_url: None,
+                cancelled: false,
+            }],
+            cache_stats: None,
+            notifications: None,
+        };
+
+        let summary = MatrixCellSummary::new(&cell, &result);
+        assert!(!summary.success);
+        assert_eq!(summary.toolchain.as_deref(), Some("1.75"));
+        assert_eq!(
+            summary.steps,
+            vec![StepSummary {
+                name: "build".to_string(),
+                success: false,
+                exit_code: Some(101),
+                duration_ms: 1500,
+            }]
+        );
+    }
+}
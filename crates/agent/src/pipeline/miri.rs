@@ -0,0 +1,123 @@
+//! Undefined behavior checks with Miri
+//!
+//! A `miri` step runs the test suite under the Miri interpreter with
+//! `cargo miri test`, which reports undefined behavior in unsafe code. Miri
+//! is much slower than a normal test run, so the step only runs when listed
+//! explicitly or when `enable_miri` is set in `.raibid.yaml`.
+//!
+//! Miri ships as a rustup component of nightly toolchains. If `cargo miri`
+//! is missing the executor tries `rustup component add miri`; when that fails
+//! too the step is skipped, or fails with `miri_strict` set. The undefined
+//! behavior errors found are summarized at the end of the step output.
+
+use std::path::Path;
+use tokio::process::Command;
+use tracing::{info, warn};
+
+/// Whether `cargo miri` runs in `workspace` with `env`
+pub async fn miri_available(workspace: &Path, env: &[(String, String)]) -> bool {
+    Command::new("cargo")
+        .arg("miri")
+        .arg("--version")
+        .current_dir(workspace)
+        .envs(env.iter().map(|(k, v)| (k, v)))
+        .output()
+        .await
+        .map(|output| output.status.success())
+        .unwrap_or(false)
+}
+
+/// Make sure `cargo miri` is available, installing the rustup component if
+/// needed
+///
+/// Returns whether Miri can run.
+pub async fn ensure_miri(workspace: &Path, env: &[(String, String)]) -> bool {
+    if miri_available(workspace, env).await {
+        return true;
+    }
+
+    info!("Miri is not installed, running `rustup component add miri`");
+    let installed = Command::new("rustup")
+        .arg("component")
+        .arg("add")
+        .arg("miri")
+        .current_dir(workspace)
+        .envs(env.iter().map(|(k, v)| (k, v)))
+        .output()
+        .await;
+    match installed {
+        Ok(output) if output.status.success() => miri_available(workspace, env).await,
+        Ok(output) => {
+            warn!(
+                "Failed to install Miri: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+            false
+        }
+        Err(e) => {
+            warn!("Failed to run rustup: {}", e);
+            false
+        }
+    }
+}
+
+/// Undefined behavior errors in Miri output
+///
+/// Matches `error[UB]:` diagnostics as well as the
+/// `error: Undefined Behavior:` form printed by current Miri versions.
+pub fn ub_errors(output: &str) -> Vec<&str> {
+    output
+        .lines()
+        .map(str::trim)
+        .filter(|line| {
+            line.starts_with("error[UB]:") || line.starts_with("error: Undefined Behavior")
+        })
+        .collect()
+}
+
+/// Summary of the undefined behavior found, appended to the step output
+pub fn ub_summary(errors: &[&str]) -> String {
+    let mut summary = format!("Miri found {} undefined behavior errors:\n", errors.len());
+    for error in errors {
+        summary.push_str("  ");
+        summary.push_str(error);
+        summary.push('\n');
+    }
+    summary
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OUTPUT: &str = r#"running 2 tests
+test tests::reads_in_bounds ... ok
+test tests::reads_past_end ...
+error: Undefined Behavior: memory access failed: alloc1 has size 4, so pointer at offset 4 is out-of-bounds
+ --> src/lib.rs:9:26
+  |
+9 |         let v = unsafe { *p.add(4) };
+  |                          ^^^^^^^^^ memory access failed
+error[UB]: using uninitialized data, but this operation requires initialized memory
+error: aborting due to 1 previous error
+"#;
+
+    #[test]
+    fn test_ub_errors() {
+        let errors = ub_errors(OUTPUT);
+        assert_eq!(errors.len(), 2);
+        assert!(errors[0].starts_with("error: Undefined Behavior: memory access failed"));
+        assert!(errors[1].starts_with("error[UB]: using uninitialized data"));
+
+        assert!(ub_errors("test result: ok. 2 passed; 0 failed").is_empty());
+    }
+
+    #[test]
+    fn test_ub_summary() {
+        let summary = ub_summary(&["error[UB]: out-of-bounds read"]);
+        assert_eq!(
+            summary,
+            "Miri found 1 undefined behavior errors:\n  error[UB]: out-of-bounds read\n"
+        );
+    }
+}
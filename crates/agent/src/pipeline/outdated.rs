@@ -0,0 +1,119 @@
+//! Outdated dependency report
+//!
+//! An `outdated` step runs `cargo outdated --exit-code 0 --format json`,
+//! which prints one JSON object per workspace member listing its outdated
+//! dependencies. Transitive dependencies are named by their path from a
+//! direct one, such as `clap->atty`. The executor counts them into an
+//! [`OutdatedReport`].
+//!
+//! The step is informational: it passes with a warning when dependencies are
+//! outdated, unless the pipeline sets `fail_on_outdated`. Workspaces without
+//! cargo-outdated installed skip the step.
+
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use std::collections::BTreeSet;
+use tokio::process::Command;
+
+use raibid_common::dependencies::OutdatedReport;
+use raibid_common::jobs::dependencies_key;
+
+/// Separator between the crates in the name of a transitive dependency
+const TRANSITIVE_SEPARATOR: &str = "->";
+
+/// Report of one workspace member in `cargo outdated --format json` output
+#[derive(Debug, Deserialize)]
+struct CrateMetadata {
+    dependencies: Vec<Dependency>,
+}
+
+/// An outdated dependency in `cargo outdated --format json` output
+#[derive(Debug, Deserialize)]
+struct Dependency {
+    name: String,
+}
+
+/// Whether `cargo outdated` is installed
+pub async fn cargo_outdated_available() -> bool {
+    Command::new("which")
+        .arg("cargo-outdated")
+        .output()
+        .await
+        .map(|output| output.status.success())
+        .unwrap_or(false)
+}
+
+/// Count the outdated dependencies in `cargo outdated --format json` output
+///
+/// A dependency outdated in several workspace members is counted once.
+pub fn parse_outdated(output: &str) -> Result<OutdatedReport> {
+    let mut direct = BTreeSet::new();
+    let mut transitive = BTreeSet::new();
+    let mut members = 0;
+
+    for line in output.lines().filter(|line| line.starts_with('{')) {
+        let metadata: CrateMetadata =
+            serde_json::from_str(line).context("Invalid cargo outdated report")?;
+        members += 1;
+        for dependency in metadata.dependencies {
+            if dependency.name.contains(TRANSITIVE_SEPARATOR) {
+                transitive.insert(dependency.name);
+            } else {
+                direct.insert(dependency.name);
+            }
+        }
+    }
+
+    if members == 0 {
+        bail!("No cargo outdated report in the step output");
+    }
+    Ok(OutdatedReport {
+        direct: direct.len() as u64,
+        transitive: transitive.len() as u64,
+    })
+}
+
+/// Store a job's outdated dependency counts in Redis as JSON
+pub async fn store_outdated<C>(conn: &mut C, job_id: &str, report: &OutdatedReport) -> Result<()>
+where
+    C: redis::aio::ConnectionLike + Send,
+{
+    let json = serde_json::to_string(report).context("Failed to encode dependency report")?;
+    redis::cmd("SET")
+        .arg(dependencies_key(job_id))
+        .arg(json)
+        .query_async::<_, ()>(conn)
+        .await
+        .with_context(|| format!("Failed to store dependency report of job {}", job_id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OUTPUT: &str = r#"{"crate_name":"raibid-agent","dependencies":[{"name":"reqwest","project":"0.11.27","compat":"---","latest":"0.12.9","kind":"Normal","platform":null},{"name":"reqwest->hyper","project":"0.14.31","compat":"---","latest":"1.5.1","kind":"Normal","platform":null},{"name":"reqwest->hyper->h2","project":"0.3.26","compat":"---","latest":"0.4.7","kind":"Normal","platform":null}]}
+{"crate_name":"raibid-common","dependencies":[{"name":"reqwest","project":"0.11.27","compat":"---","latest":"0.12.9","kind":"Normal","platform":null},{"name":"redis","project":"0.24.0","compat":"---","latest":"0.27.5","kind":"Normal","platform":null}]}
+"#;
+
+    #[test]
+    fn test_parse_outdated() {
+        let report = parse_outdated(OUTPUT).unwrap();
+        assert_eq!(
+            report,
+            OutdatedReport {
+                direct: 2,
+                transitive: 2
+            }
+        );
+
+        let up_to_date = r#"{"crate_name":"raibid-cli","dependencies":[]}"#;
+        assert_eq!(parse_outdated(up_to_date).unwrap().total(), 0);
+    }
+
+    #[test]
+    fn test_parse_outdated_invalid() {
+        assert!(parse_outdated("").is_err());
+        assert!(parse_outdated("error: no Cargo.toml found\n").is_err());
+        assert!(parse_outdated("{\"crate_name\":\"app\"}\n").is_err());
+    }
+}
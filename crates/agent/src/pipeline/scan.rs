@@ -0,0 +1,209 @@
+//! Container image scanning
+//!
+//! A `security-scan` step follows each `docker build` of a pipeline with a
+//! [`ScanPolicy`] and runs `trivy image` on the built image. trivy exits
+//! non-zero when it finds vulnerabilities at or above the policy's
+//! `min_severity_to_fail`, failing the step unless the policy allows it to
+//! fail. The executor counts the vulnerabilities of each severity in trivy's
+//! JSON output into a [`ScanReport`]. Workspaces without trivy installed skip
+//! the step.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use tokio::process::Command;
+
+use raibid_common::jobs::scan_results_key;
+use raibid_common::scan::{ScanReport, Severity};
+
+/// When a `security-scan` step fails the build
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ScanPolicy {
+    /// Lowest severity that fails the step
+    #[serde(default = "default_min_severity")]
+    pub min_severity_to_fail: Severity,
+    /// Leave out vulnerabilities without a fixed version
+    #[serde(default)]
+    pub ignore_unfixed: bool,
+    /// Record a failed scan without failing the build
+    #[serde(default)]
+    pub continue_on_failure: bool,
+}
+
+impl Default for ScanPolicy {
+    fn default() -> Self {
+        Self {
+            min_severity_to_fail: default_min_severity(),
+            ignore_unfixed: false,
+            continue_on_failure: false,
+        }
+    }
+}
+
+fn default_min_severity() -> Severity {
+    Severity::High
+}
+
+/// Root of `trivy --format json` output
+#[derive(Debug, Deserialize)]
+struct TrivyOutput {
+    #[serde(rename = "Results", default)]
+    results: Vec<TrivyResult>,
+}
+
+/// Scan result for one target (OS packages, a lock file, ...) of the image
+#[derive(Debug, Deserialize)]
+struct TrivyResult {
+    #[serde(rename = "Vulnerabilities", default)]
+    vulnerabilities: Option<Vec<TrivyVulnerability>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TrivyVulnerability {
+    #[serde(rename = "Severity")]
+    severity: String,
+}
+
+/// Whether `trivy` can be found on the `PATH` of `env`, or the agent's
+pub async fn trivy_available(env: &[(String, String)]) -> bool {
+    Command::new("trivy")
+        .arg("--version")
+        .envs(env.iter().map(|(k, v)| (k, v)))
+        .output()
+        .await
+        .map(|output| output.status.success())
+        .unwrap_or(false)
+}
+
+/// Arguments of `trivy` scanning `image` under `policy`
+pub fn trivy_args(image: &str, policy: &ScanPolicy) -> Vec<String> {
+    let severities: Vec<String> = policy
+        .min_severity_to_fail
+        .and_above()
+        .iter()
+        .map(Severity::to_string)
+        .collect();
+    let mut args = vec![
+        "image".to_string(),
+        "--exit-code".to_string(),
+        "1".to_string(),
+        "--severity".to_string(),
+        severities.join(","),
+    ];
+    if policy.ignore_unfixed {
+        args.push("--ignore-unfixed".to_string());
+    }
+    args.extend([
+        image.to_string(),
+        "--format".to_string(),
+        "json".to_string(),
+    ]);
+    args
+}
+
+/// Count the vulnerabilities of each severity in trivy's JSON output
+///
+/// Severities trivy does not know are counted as [`Severity::Unknown`].
+pub fn parse_trivy_report(image: &str, json: &str) -> Result<ScanReport> {
+    let output: TrivyOutput = serde_json::from_str(json).context("Invalid trivy JSON output")?;
+
+    let mut vulnerabilities = BTreeMap::new();
+    for vulnerability in output
+        .results
+        .into_iter()
+        .flat_map(|result| result.vulnerabilities.unwrap_or_default())
+    {
+        let severity = vulnerability.severity.parse().unwrap_or(Severity::Unknown);
+        *vulnerabilities.entry(severity).or_insert(0) += 1;
+    }
+
+    Ok(ScanReport {
+        image: image.to_string(),
+        vulnerabilities,
+    })
+}
+
+/// Store a job's scan result in Redis as JSON
+pub async fn store_scan_report<C>(conn: &mut C, job_id: &str, report: &ScanReport) -> Result<()>
+where
+    C: redis::aio::ConnectionLike + Send,
+{
+    let json = serde_json::to_string(report).context("Failed to encode scan result")?;
+    redis::cmd("SET")
+        .arg(scan_results_key(job_id))
+        .arg(json)
+        .query_async::<_, ()>(conn)
+        .await
+        .with_context(|| format!("Failed to store scan result of job {}", job_id))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Output of `trivy image --format json` with two targets
+    pub(crate) const TRIVY_OUTPUT: &str = r#"{
+  "SchemaVersion": 2,
+  "ArtifactName": "registry.local/app:1.0",
+  "ArtifactType": "container_image",
+  "Results": [
+    {
+      "Target": "registry.local/app:1.0 (debian 12.4)",
+      "Class": "os-pkgs",
+      "Type": "debian",
+      "Vulnerabilities": [
+        {"VulnerabilityID": "CVE-2023-0001", "PkgName": "openssl", "Severity": "CRITICAL", "FixedVersion": "3.0.13"},
+        {"VulnerabilityID": "CVE-2023-0002", "PkgName": "zlib", "Severity": "HIGH"},
+        {"VulnerabilityID": "CVE-2023-0003", "PkgName": "libc6", "Severity": "HIGH", "FixedVersion": "2.36-9"}
+      ]
+    },
+    {
+      "Target": "Cargo.lock",
+      "Class": "lang-pkgs",
+      "Type": "cargo"
+    },
+    {
+      "Target": "app",
+      "Class": "lang-pkgs",
+      "Type": "rustbinary",
+      "Vulnerabilities": [
+        {"VulnerabilityID": "RUSTSEC-2024-0001", "PkgName": "h2", "Severity": "HIGH", "FixedVersion": "0.4.4"}
+      ]
+    }
+  ]
+}"#;
+
+    #[test]
+    fn test_parse_trivy_report() {
+        let report = parse_trivy_report("registry.local/app:1.0", TRIVY_OUTPUT).unwrap();
+        assert_eq!(report.image, "registry.local/app:1.0");
+        assert_eq!(report.count(Severity::Critical), 1);
+        assert_eq!(report.count(Severity::High), 3);
+        assert_eq!(report.total(), 4);
+
+        // Clean images have no `Results` or no `Vulnerabilities`
+        let clean = parse_trivy_report("app", r#"{"SchemaVersion": 2}"#).unwrap();
+        assert_eq!(clean.total(), 0);
+        assert!(parse_trivy_report("app", "not json").is_err());
+    }
+
+    #[test]
+    fn test_trivy_args() {
+        let policy = ScanPolicy::default();
+        assert_eq!(
+            trivy_args("app:1.0", &policy).join(" "),
+            "image --exit-code 1 --severity HIGH,CRITICAL app:1.0 --format json"
+        );
+
+        let policy = ScanPolicy {
+            min_severity_to_fail: Severity::Medium,
+            ignore_unfixed: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            trivy_args("app:1.0", &policy).join(" "),
+            "image --exit-code 1 --severity MEDIUM,HIGH,CRITICAL --ignore-unfixed app:1.0 \
+             --format json"
+        );
+    }
+}
@@ -0,0 +1,116 @@
+//! Unused dependency report
+//!
+//! A `udeps` step runs `cargo +nightly udeps --all-targets --output json`,
+//! which prints one JSON object listing, for each workspace member with
+//! unused dependencies, the unused crates by kind (normal, development and
+//! build). The executor collects their names into an [`UnusedDepsReport`].
+//!
+//! cargo-udeps needs a nightly toolchain. Like an `outdated` step, the step
+//! passes with a warning when dependencies are unused, unless the pipeline
+//! sets `fail_on_unused_deps`. Workspaces without nightly or cargo-udeps
+//! installed skip the step.
+
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use std::collections::{BTreeSet, HashMap};
+use std::path::Path;
+use tokio::process::Command;
+
+use raibid_common::dependencies::UnusedDepsReport;
+use raibid_common::jobs::unused_deps_key;
+
+/// `cargo udeps --output json` output
+#[derive(Debug, Deserialize)]
+struct UdepsOutput {
+    /// Unused dependencies of each workspace member, keyed by package ID
+    unused_deps: HashMap<String, MemberDeps>,
+}
+
+/// Unused dependencies of one workspace member, by kind
+#[derive(Debug, Deserialize)]
+struct MemberDeps {
+    #[serde(default)]
+    normal: Vec<String>,
+    #[serde(default)]
+    development: Vec<String>,
+    #[serde(default)]
+    build: Vec<String>,
+}
+
+/// Whether cargo-udeps can run on the nightly toolchain in `workspace`
+pub async fn udeps_available(workspace: &Path, env: &[(String, String)]) -> bool {
+    Command::new("cargo")
+        .args(["+nightly", "udeps", "--version"])
+        .current_dir(workspace)
+        .envs(env.iter().map(|(k, v)| (k, v)))
+        .output()
+        .await
+        .map(|output| output.status.success())
+        .unwrap_or(false)
+}
+
+/// List the unused dependencies in `cargo udeps --output json` output
+///
+/// A crate unused by several workspace members, or as several kinds of
+/// dependency, is listed once.
+pub fn parse_udeps(output: &str) -> Result<UnusedDepsReport> {
+    let Some(json) = output.lines().find(|line| line.starts_with('{')) else {
+        bail!("No cargo udeps report in the step output");
+    };
+    let output: UdepsOutput = serde_json::from_str(json).context("Invalid cargo udeps report")?;
+
+    let crates: BTreeSet<String> = output
+        .unused_deps
+        .into_values()
+        .flat_map(|deps| {
+            deps.normal
+                .into_iter()
+                .chain(deps.development)
+                .chain(deps.build)
+        })
+        .collect();
+    Ok(UnusedDepsReport {
+        crates: crates.into_iter().collect(),
+    })
+}
+
+/// Store a job's unused dependencies in Redis as JSON
+pub async fn store_unused_deps<C>(
+    conn: &mut C,
+    job_id: &str,
+    report: &UnusedDepsReport,
+) -> Result<()>
+where
+    C: redis::aio::ConnectionLike + Send,
+{
+    let json = serde_json::to_string(report).context("Failed to encode unused dependencies")?;
+    redis::cmd("SET")
+        .arg(unused_deps_key(job_id))
+        .arg(json)
+        .query_async::<_, ()>(conn)
+        .await
+        .with_context(|| format!("Failed to store unused dependencies of job {}", job_id))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    pub(crate) const UDEPS_OUTPUT: &str = r#"{"success":false,"unused_deps":{"raibid-agent 0.1.0 (path+file:///src/crates/agent)":{"manifest_path":"/src/crates/agent/Cargo.toml","normal":["rand","regex"],"development":["mockito"],"build":[]},"raibid-common 0.1.0 (path+file:///src/crates/common)":{"manifest_path":"/src/crates/common/Cargo.toml","normal":["rand"],"development":[],"build":[]}},"note":"Note: They might be false-positive."}"#;
+
+    #[test]
+    fn test_parse_udeps() {
+        let report = parse_udeps(UDEPS_OUTPUT).unwrap();
+        assert_eq!(report.crates, vec!["mockito", "rand", "regex"]);
+
+        let all_used = r#"{"success":true,"unused_deps":{},"note":null}"#;
+        assert!(parse_udeps(all_used).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_parse_udeps_invalid() {
+        assert!(parse_udeps("").is_err());
+        assert!(parse_udeps("error: could not find `Cargo.toml`\n").is_err());
+        assert!(parse_udeps("{\"success\":true}\n").is_err());
+    }
+}
@@ -0,0 +1,576 @@
+//! Automatic retries of failed jobs
+//!
+//! [`RetryingHandler`] wraps a [`JobHandler`] and tracks each job's status in
+//! its Redis hash. When a build fails and the job has retries left, the job is
+//! marked `retrying`, the handler waits with exponential backoff, and then a
+//! copy of the job is queued under a new ID with `retry_count` incremented and
+//! `parent_job_id` pointing at the failed attempt.
+//!
+//! The wait happens inside the job's task, so it keeps its concurrency slot
+//! and its stream message stays pending until the retry has been queued. If
+//! the agent dies during the wait, orphan recovery runs the job again.
+//!
+//! A job that fails with no retries left is added to the dead letter stream
+//! ([`dead_letter_stream`]), from which it can be requeued by hand. So is a
+//! job that still could not run ([`JobOutcome::Retry`]) on its
+//! [`MAX_DELIVERIES`]th delivery, rather than being redelivered forever.
+//!
+//! A job that succeeds releases the jobs held until it did (see
+//! [`raibid_common::job_deps`]).
+//!
+//! A job cancelled while it was still pending is not run at all: no agent
+//! was watching for its cancellation when it was published, so its status is
+//! checked before it starts.
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use chrono::Utc;
+use redis::aio::MultiplexedConnection;
+use redis::AsyncCommands;
+use std::time::Duration;
+use tracing::{error, info, warn};
+
+use crate::consumer::{ClaimedJob, JobHandler, JobOutcome};
+use crate::AgentConfig;
+use raibid_common::job_deps;
+use raibid_common::jobs::{
+    dead_letter_stream, job_key, DeadJob, JobStatus, QueuedJob, JOB_FIELD_STATUS,
+};
+
+/// Longest wait before re-queueing a failed job
+pub const MAX_RETRY_BACKOFF: Duration = Duration::from_secs(300);
+
+/// Deliveries of a job that could not run before it is dead-lettered
+pub const MAX_DELIVERIES: u32 = 5;
+
+/// Approximate number of entries kept in the dead letter stream by default
+pub const DEFAULT_DEAD_LETTER_MAXLEN: usize = 10_000;
+
+/// How many times failed jobs are retried, and how long to wait first
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Retries after the first attempt; 0 disables retries
+    pub max_retries: u32,
+    /// Wait before the first retry, doubled for each later one
+    pub backoff: Duration,
+}
+
+impl RetryPolicy {
+    /// Policy from the agent's `max_retries` and `retry_backoff_ms`
+    pub fn from_config(config: &AgentConfig) -> Self {
+        Self {
+            max_retries: config.max_retries,
+            backoff: Duration::from_millis(config.retry_backoff_ms),
+        }
+    }
+
+    /// Whether a job that failed after `retry_count` retries runs again
+    pub fn should_retry(&self, retry_count: u32) -> bool {
+        retry_count < self.max_retries
+    }
+
+    /// Wait before queueing retry number `retry_count + 1`:
+    /// `backoff * 2^retry_count`, capped at [`MAX_RETRY_BACKOFF`]
+    pub fn delay(&self, retry_count: u32) -> Duration {
+        2u32.checked_pow(retry_count)
+            .and_then(|factor| self.backoff.checked_mul(factor))
+            .map_or(MAX_RETRY_BACKOFF, |delay| delay.min(MAX_RETRY_BACKOFF))
+    }
+}
+
+/// Where job statuses are recorded and retries are queued
+#[async_trait]
+pub trait JobStore: Send + Sync + 'static {
+    /// A job's recorded status, if it has one
+    async fn status(&self, job_id: &str) -> Result<Option<JobStatus>>;
+
+    /// Record a job's status
+    async fn set_status(&self, job_id: &str, status: JobStatus) -> Result<()>;
+
+    /// Queue a job on the stream for its priority
+    async fn enqueue(&self, job: &QueuedJob) -> Result<()>;
+
+    /// Add a job that failed every retry to the dead letter stream
+    async fn dead_letter(&self, job: &DeadJob) -> Result<()>;
+
+    /// Count off a job that succeeded for the jobs waiting for it, returning
+    /// the IDs of those that were queued
+    async fn release_dependents(&self, job_id: &str) -> Result<Vec<String>>;
+}
+
+/// [`JobStore`] backed by the job hashes and job streams in Redis
+#[derive(Clone)]
+pub struct RedisJobStore {
+    conn: MultiplexedConnection,
+    base_stream: String,
+    dead_letter_maxlen: usize,
+}
+
+impl RedisJobStore {
+    /// Use `conn`, queueing jobs on the priority streams of `base_stream`
+    pub fn new(conn: MultiplexedConnection, base_stream: impl Into<String>) -> Self {
+        Self {
+            conn,
+            base_stream: base_stream.into(),
+            dead_letter_maxlen: DEFAULT_DEAD_LETTER_MAXLEN,
+        }
+    }
+
+    /// Trim the dead letter stream to about `maxlen` entries
+    pub fn with_dead_letter_maxlen(mut self, maxlen: usize) -> Self {
+        self.dead_letter_maxlen = maxlen;
+        self
+    }
+}
+
+#[async_trait]
+impl JobStore for RedisJobStore {
+    async fn status(&self, job_id: &str) -> Result<Option<JobStatus>> {
+        let mut conn = self.conn.clone();
+        let status: Option<String> = conn
+            .hget(job_key(job_id), JOB_FIELD_STATUS)
+            .await
+            .with_context(|| format!("Failed to read status of job {}", job_id))?;
+        status.map(|status| status.parse()).transpose()
+    }
+
+    async fn set_status(&self, job_id: &str, status: JobStatus) -> Result<()> {
+        let mut conn = self.conn.clone();
+        let _: () = conn
+            .hset(job_key(job_id), JOB_FIELD_STATUS, status.as_str())
+            .await
+            .with_context(|| format!("Failed to set status of job {}", job_id))?;
+        Ok(())
+    }
+
+    async fn enqueue(&self, job: &QueuedJob) -> Result<()> {
+        let fields = job.to_stream_fields()?;
+        let stream = job.trigger.priority.stream_key(&self.base_stream);
+
+        let mut conn = self.conn.clone();
+        let _: String = conn
+            .xadd(&stream, "*", fields.as_slice())
+            .await
+            .with_context(|| format!("Failed to queue job {} on '{}'", job.id, stream))?;
+        Ok(())
+    }
+
+    async fn dead_letter(&self, job: &DeadJob) -> Result<()> {
+        let fields = job.to_stream_fields()?;
+        let stream = dead_letter_stream(&self.base_stream);
+
+        let mut conn = self.conn.clone();
+        let _: String = redis::cmd("XADD")
+            .arg(&stream)
+            .arg("MAXLEN")
+            .arg("~")
+            .arg(self.dead_letter_maxlen)
+            .arg("*")
+            .arg(&fields)
+            .query_async(&mut conn)
+            .await
+            .with_context(|| format!("Failed to add job {} to '{}'", job.id, stream))?;
+        Ok(())
+    }
+
+    async fn release_dependents(&self, job_id: &str) -> Result<Vec<String>> {
+        let mut conn = self.conn.clone();
+        job_deps::release_dependents(&mut conn, &self.base_stream, job_id)
+            .await
+            .with_context(|| format!("Failed to release the jobs waiting for job {}", job_id))
+    }
+}
+
+/// Runs jobs with an inner handler, recording their status and retrying
+/// failed builds according to a [`RetryPolicy`]
+pub struct RetryingHandler<H, S> {
+    inner: H,
+    store: S,
+    policy: RetryPolicy,
+}
+
+impl<H: JobHandler, S: JobStore> RetryingHandler<H, S> {
+    /// Wrap `inner`, recording statuses and queueing retries in `store`
+    pub fn new(inner: H, store: S, policy: RetryPolicy) -> Self {
+        Self {
+            inner,
+            store,
+            policy,
+        }
+    }
+
+    /// Whether a job was cancelled before it started
+    async fn cancelled_while_pending(&self, job_id: &str) -> bool {
+        match self.store.status(job_id).await {
+            Ok(status) => status == Some(JobStatus::Cancelled),
+            Err(e) => {
+                warn!("{:#}", e);
+                false
+            }
+        }
+    }
+
+    async fn set_status(&self, job_id: &str, status: JobStatus) {
+        // A missing status update must not fail the build itself
+        if let Err(e) = self.store.set_status(job_id, status).await {
+            warn!("{:#}", e);
+        }
+    }
+
+    /// Queue the jobs that were waiting for a job that succeeded
+    async fn release_dependents(&self, job_id: &str) {
+        match self.store.release_dependents(job_id).await {
+            Ok(released) => {
+                for dependent in released {
+                    info!("Queued job {}: job {} succeeded", dependent, job_id);
+                }
+            }
+            Err(e) => error!("{:#}", e),
+        }
+    }
+
+    /// Wait out the backoff and queue the next attempt of a failed job
+    async fn retry(&self, job: &QueuedJob) -> Result<QueuedJob> {
+        let delay = self.policy.delay(job.retry_count);
+        info!(
+            "Retrying job {} in {:?} (retry {} of {})",
+            job.id,
+            delay,
+            job.retry_count + 1,
+            self.policy.max_retries
+        );
+        self.set_status(&job.id, JobStatus::Retrying).await;
+        tokio::time::sleep(delay).await;
+
+        let retry = job.retry(uuid::Uuid::new_v4().to_string());
+        // Before queueing, so it can't overwrite the status of a running retry
+        self.set_status(&retry.id, JobStatus::Pending).await;
+        self.store.enqueue(&retry).await?;
+        Ok(retry)
+    }
+
+    /// Add a job with no retries left to the dead letter stream
+    async fn dead_letter(&self, job: &QueuedJob, reason: String) {
+        match self
+            .store
+            .dead_letter(&DeadJob::new(job, reason, Utc::now()))
+            .await
+        {
+            Ok(()) => warn!("Job {} failed every attempt, moved to dead letters", job.id),
+            Err(e) => error!("{:#}", e),
+        }
+    }
+}
+
+#[async_trait]
+impl<H: JobHandler, S: JobStore> JobHandler for RetryingHandler<H, S> {
+    async fn run(&self, claimed: &ClaimedJob) -> JobOutcome {
+        let job = &claimed.job;
+        // Its cancellation was published before anything watched for it
+        if self.cancelled_while_pending(&job.id).await {
+            info!("Job {} was cancelled before it started", job.id);
+            return JobOutcome::Cancelled;
+        }
+        self.set_status(&job.id, JobStatus::Running).await;
+
+        let mut outcome = self.inner.run(claimed).await;
+        match outcome {
+            JobOutcome::Succeeded => {
+                self.set_status(&job.id, JobStatus::Success).await;
+                self.release_dependents(&job.id).await;
+            }
+            JobOutcome::Failed if self.policy.should_retry(job.retry_count) => {
+                match self.retry(job).await {
+                    Ok(retry) => info!("Queued job {} to retry job {}", retry.id, job.id),
+                    Err(e) => error!("Failed to queue retry of job {}: {:#}", job.id, e),
+                }
+                self.set_status(&job.id, JobStatus::Failed).await;
+            }
+            JobOutcome::Failed => {
+                let reason = match job.retry_count {
+                    0 => "Build failed".to_string(),
+                    retries => format!("Build failed after {} retries", retries),
+                };
+                self.set_status(&job.id, JobStatus::Failed).await;
+                self.dead_letter(job, reason).await;
+            }
+            // Never retried; the status the server set is restored over
+            // `Running`
+            JobOutcome::Cancelled => self.set_status(&job.id, JobStatus::Cancelled).await,
+            // Acknowledged, so it is not redelivered again
+            JobOutcome::Retry if job.retry_count + 1 >= MAX_DELIVERIES => {
+                let reason = format!("Job could not run in {} deliveries", job.retry_count + 1);
+                self.set_status(&job.id, JobStatus::Failed).await;
+                self.dead_letter(job, reason).await;
+                outcome = JobOutcome::Failed;
+            }
+            // Left pending for orphan recovery to pick up
+            JobOutcome::Retry => self.set_status(&job.id, JobStatus::Pending).await,
+        }
+
+        outcome
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use raibid_common::jobs::JobBuilder;
+    use std::collections::HashMap;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Default)]
+    struct MockStore {
+        statuses: Mutex<HashMap<String, Vec<JobStatus>>>,
+        queued: Mutex<Vec<QueuedJob>>,
+        dead: Mutex<Vec<DeadJob>>,
+        succeeded: Mutex<Vec<String>>,
+    }
+
+    #[async_trait]
+    impl JobStore for Arc<MockStore> {
+        async fn status(&self, job_id: &str) -> Result<Option<JobStatus>> {
+            let statuses = self.statuses.lock().unwrap();
+            Ok(statuses.get(job_id).and_then(|s| s.last()).copied())
+        }
+
+        async fn set_status(&self, job_id: &str, status: JobStatus) -> Result<()> {
+            self.statuses
+                .lock()
+                .unwrap()
+                .entry(job_id.to_string())
+                .or_default()
+                .push(status);
+            Ok(())
+        }
+
+        async fn enqueue(&self, job: &QueuedJob) -> Result<()> {
+            self.queued.lock().unwrap().push(job.clone());
+            Ok(())
+        }
+
+        async fn dead_letter(&self, job: &DeadJob) -> Result<()> {
+            self.dead.lock().unwrap().push(job.clone());
+            Ok(())
+        }
+
+        async fn release_dependents(&self, job_id: &str) -> Result<Vec<String>> {
+            self.succeeded.lock().unwrap().push(job_id.to_string());
+            Ok(Vec::new())
+        }
+    }
+
+    /// Pipeline whose builds always fail
+    #[derive(Default)]
+    struct FailingPipeline {
+        runs: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl JobHandler for Arc<FailingPipeline> {
+        async fn run(&self, _job: &ClaimedJob) -> JobOutcome {
+            self.runs.fetch_add(1, Ordering::SeqCst);
+            JobOutcome::Failed
+        }
+    }
+
+    fn claimed(job: QueuedJob) -> ClaimedJob {
+        ClaimedJob {
+            stream: "raibid:jobs:normal".to_string(),
+            message_id: "1700000000000-0".to_string(),
+            job,
+        }
+    }
+
+    #[test]
+    fn test_retry_policy_delay() {
+        let policy = RetryPolicy {
+            max_retries: 3,
+            backoff: Duration::from_millis(5000),
+        };
+        assert_eq!(policy.delay(0), Duration::from_secs(5));
+        assert_eq!(policy.delay(1), Duration::from_secs(10));
+        assert_eq!(policy.delay(2), Duration::from_secs(20));
+        assert_eq!(policy.delay(6), MAX_RETRY_BACKOFF);
+        assert_eq!(policy.delay(40), MAX_RETRY_BACKOFF);
+
+        assert!(policy.should_retry(2));
+        assert!(!policy.should_retry(3));
+    }
+
+    #[tokio::test]
+    async fn test_failing_job_retried_max_retries_times() {
+        let pipeline = Arc::new(FailingPipeline::default());
+        let store = Arc::new(MockStore::default());
+        let handler = RetryingHandler::new(
+            pipeline.clone(),
+            store.clone(),
+            RetryPolicy {
+                max_retries: 2,
+                backoff: Duration::from_millis(1),
+            },
+        );
+
+        // Feed every queued retry back through the handler, like an agent would
+        let first = QueuedJob::new("job-1", JobBuilder::new("a/b").build().unwrap());
+        let mut next = Some(first.clone());
+        let mut last = first.clone();
+        while let Some(job) = next.take() {
+            assert_eq!(handler.run(&claimed(job.clone())).await, JobOutcome::Failed);
+            last = job;
+            next = store.queued.lock().unwrap().pop();
+        }
+
+        assert_eq!(pipeline.runs.load(Ordering::SeqCst), 3);
+        assert_eq!(last.retry_count, 2);
+        assert_ne!(last.id, first.id);
+        assert!(last.parent_job_id.is_some());
+        assert_eq!(last.trigger, first.trigger);
+
+        // Only the last attempt is dead-lettered
+        let dead = store.dead.lock().unwrap();
+        assert_eq!(dead.len(), 1);
+        assert_eq!(dead[0].id, last.id);
+        assert_eq!(dead[0].retry_count, 2);
+        assert_eq!(dead[0].failure_reason, "Build failed after 2 retries");
+
+        // Requeueing starts the retry budget over
+        let requeued = dead[0].requeue();
+        assert_eq!(requeued.retry_count, 0);
+        assert_eq!(requeued.trigger, first.trigger);
+        assert!(handler.policy.should_retry(requeued.retry_count));
+
+        let statuses = store.statuses.lock().unwrap();
+        assert_eq!(
+            statuses[&first.id],
+            vec![JobStatus::Running, JobStatus::Retrying, JobStatus::Failed]
+        );
+        assert_eq!(
+            statuses[&last.id],
+            vec![JobStatus::Pending, JobStatus::Running, JobStatus::Failed]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_no_retries_by_default() {
+        let pipeline = Arc::new(FailingPipeline::default());
+        let store = Arc::new(MockStore::default());
+        let handler = RetryingHandler::new(
+            pipeline.clone(),
+            store.clone(),
+            RetryPolicy::from_config(&AgentConfig::default()),
+        );
+
+        let job = QueuedJob::new("job-1", JobBuilder::new("a/b").build().unwrap());
+        handler.run(&claimed(job)).await;
+
+        assert_eq!(pipeline.runs.load(Ordering::SeqCst), 1);
+        assert!(store.queued.lock().unwrap().is_empty());
+        assert_eq!(store.dead.lock().unwrap()[0].failure_reason, "Build failed");
+        assert_eq!(
+            store.statuses.lock().unwrap()["job-1"],
+            vec![JobStatus::Running, JobStatus::Failed]
+        );
+        assert!(store.succeeded.lock().unwrap().is_empty());
+    }
+
+    /// Pipeline whose jobs can never run
+    struct BrokenPipeline;
+
+    #[async_trait]
+    impl JobHandler for BrokenPipeline {
+        async fn run(&self, _job: &ClaimedJob) -> JobOutcome {
+            JobOutcome::Retry
+        }
+    }
+
+    #[tokio::test]
+    async fn test_undeliverable_job_dead_lettered() {
+        let store = Arc::new(MockStore::default());
+        let handler = RetryingHandler::new(
+            BrokenPipeline,
+            store.clone(),
+            RetryPolicy::from_config(&AgentConfig::default()),
+        );
+
+        let mut job = QueuedJob::new("job-1", JobBuilder::new("a/b").build().unwrap());
+        job.retry_count = MAX_DELIVERIES - 2;
+        assert_eq!(handler.run(&claimed(job.clone())).await, JobOutcome::Retry);
+        assert!(store.dead.lock().unwrap().is_empty());
+
+        // Reclaimed once more by orphan recovery
+        job.retry_count += 1;
+        assert_eq!(handler.run(&claimed(job)).await, JobOutcome::Failed);
+
+        let dead = store.dead.lock().unwrap();
+        assert_eq!(dead.len(), 1);
+        assert_eq!(dead[0].failure_reason, "Job could not run in 5 deliveries");
+        assert!(store.queued.lock().unwrap().is_empty());
+        assert_eq!(
+            store.statuses.lock().unwrap()["job-1"],
+            vec![
+                JobStatus::Running,
+                JobStatus::Pending,
+                JobStatus::Running,
+                JobStatus::Failed
+            ]
+        );
+    }
+
+    /// Pipeline whose builds always succeed
+    struct SucceedingPipeline;
+
+    #[async_trait]
+    impl JobHandler for SucceedingPipeline {
+        async fn run(&self, _job: &ClaimedJob) -> JobOutcome {
+            JobOutcome::Succeeded
+        }
+    }
+
+    #[tokio::test]
+    async fn test_success_releases_dependents() {
+        let store = Arc::new(MockStore::default());
+        let handler = RetryingHandler::new(
+            SucceedingPipeline,
+            store.clone(),
+            RetryPolicy::from_config(&AgentConfig::default()),
+        );
+
+        let job = QueuedJob::new("job-1", JobBuilder::new("a/b").build().unwrap());
+        assert_eq!(handler.run(&claimed(job)).await, JobOutcome::Succeeded);
+
+        assert_eq!(
+            store.statuses.lock().unwrap()["job-1"],
+            vec![JobStatus::Running, JobStatus::Success]
+        );
+        assert_eq!(*store.succeeded.lock().unwrap(), vec!["job-1"]);
+    }
+
+    #[tokio::test]
+    async fn test_job_cancelled_before_pickup_not_run() {
+        let pipeline = Arc::new(FailingPipeline::default());
+        let store = Arc::new(MockStore::default());
+        let handler = RetryingHandler::new(
+            pipeline.clone(),
+            store.clone(),
+            RetryPolicy::from_config(&AgentConfig::default()),
+        );
+
+        // Cancelled through the server while still queued
+        store
+            .set_status("job-1", JobStatus::Cancelled)
+            .await
+            .unwrap();
+
+        let job = QueuedJob::new("job-1", JobBuilder::new("a/b").build().unwrap());
+        assert_eq!(handler.run(&claimed(job)).await, JobOutcome::Cancelled);
+
+        assert_eq!(pipeline.runs.load(Ordering::SeqCst), 0);
+        assert_eq!(
+            store.statuses.lock().unwrap()["job-1"],
+            vec![JobStatus::Cancelled]
+        );
+        assert!(store.dead.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,320 @@
+//! sccache compilation cache
+//!
+//! Pipelines with `use_sccache: true` in `.raibid.yaml` compile through
+//! [sccache](https://github.com/mozilla/sccache). Unless the agent is pointed
+//! at a running sccache server, the executor starts one before the first step,
+//! caching to a GCS or S3 bucket when configured and to a local directory
+//! otherwise. The server outlives the pipeline (it exits after sccache's idle
+//! timeout), so later jobs on the same agent reuse it.
+//!
+//! The cache hits and misses of a pipeline are the difference between the
+//! server's statistics before and after it ran. They are stored in the job's
+//! hash; jobs running at the same time on one server count each other's
+//! compilations.
+
+use anyhow::{anyhow, bail, Context, Result};
+use redis::AsyncCommands;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use tokio::process::Command;
+use tracing::{debug, info};
+
+use raibid_common::jobs::job_key;
+
+/// Field of the job hash holding the number of cache hits
+pub const JOB_FIELD_CACHE_HITS: &str = "cache_hits";
+
+/// Field of the job hash holding the number of cache misses
+pub const JOB_FIELD_CACHE_MISSES: &str = "cache_misses";
+
+/// Field of the job hash holding the cache hit ratio (0.0 to 1.0)
+pub const JOB_FIELD_CACHE_HIT_RATIO: &str = "cache_hit_ratio";
+
+/// Where sccache stores compilation results
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SccacheConfig {
+    /// Address (`host:port`) of an sccache server to use instead of starting
+    /// one
+    ///
+    /// sccache clients connect over loopback, so only the port is used.
+    pub server_address: Option<String>,
+    /// GCS bucket to cache in
+    pub gcs_bucket: Option<String>,
+    /// S3 bucket to cache in, used when no GCS bucket is set
+    pub s3_bucket: Option<String>,
+    /// Directory to cache in when no bucket is set
+    pub local_cache_dir: PathBuf,
+}
+
+impl Default for SccacheConfig {
+    fn default() -> Self {
+        Self {
+            server_address: None,
+            gcs_bucket: None,
+            s3_bucket: None,
+            local_cache_dir: std::env::temp_dir().join("raibid-sccache"),
+        }
+    }
+}
+
+impl SccacheConfig {
+    /// Port of the external server, if one is configured
+    fn server_port(&self) -> Result<Option<u16>> {
+        let Some(ref address) = self.server_address else {
+            return Ok(None);
+        };
+
+        address
+            .rsplit_once(':')
+            .and_then(|(_, port)| port.parse().ok())
+            .map(Some)
+            .ok_or_else(|| anyhow!("Invalid sccache server address '{}'", address))
+    }
+
+    /// Environment of the sccache server, selecting the cache storage
+    fn server_env(&self) -> Vec<(&'static str, String)> {
+        let mut env = vec![(
+            "SCCACHE_DIR",
+            self.local_cache_dir.to_string_lossy().to_string(),
+        )];
+        if let Some(ref bucket) = self.gcs_bucket {
+            env.push(("SCCACHE_GCS_BUCKET", bucket.clone()));
+            env.push(("SCCACHE_GCS_RW_MODE", "READ_WRITE".to_string()));
+        } else if let Some(ref bucket) = self.s3_bucket {
+            env.push(("SCCACHE_BUCKET", bucket.clone()));
+        }
+        env
+    }
+}
+
+/// Cache hits and misses reported by the sccache server
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SccacheStats {
+    pub cache_hits: u64,
+    pub cache_misses: u64,
+}
+
+/// `sccache --show-stats --stats-format json` output
+#[derive(Deserialize)]
+struct StatsOutput {
+    stats: RawStats,
+}
+
+#[derive(Deserialize)]
+struct RawStats {
+    cache_hits: LanguageCounts,
+    cache_misses: LanguageCounts,
+}
+
+/// Counts per language (`Rust`, `C/C++`, ...)
+#[derive(Deserialize)]
+struct LanguageCounts {
+    #[serde(default)]
+    counts: HashMap<String, u64>,
+}
+
+impl SccacheStats {
+    /// Parse the JSON printed by `sccache --show-stats --stats-format json`
+    pub fn from_json(json: &str) -> Result<Self> {
+        let output: StatsOutput =
+            serde_json::from_str(json).context("Invalid sccache statistics")?;
+        Ok(Self {
+            cache_hits: output.stats.cache_hits.counts.values().sum(),
+            cache_misses: output.stats.cache_misses.counts.values().sum(),
+        })
+    }
+
+    /// Hits and misses since `earlier` statistics of the same server
+    pub fn since(&self, earlier: &SccacheStats) -> Self {
+        Self {
+            cache_hits: self.cache_hits.saturating_sub(earlier.cache_hits),
+            cache_misses: self.cache_misses.saturating_sub(earlier.cache_misses),
+        }
+    }
+
+    /// Fraction of cacheable compilations served from the cache
+    ///
+    /// `0.0` when nothing was compiled.
+    pub fn hit_ratio(&self) -> f64 {
+        let total = self.cache_hits + self.cache_misses;
+        if total == 0 {
+            0.0
+        } else {
+            self.cache_hits as f64 / total as f64
+        }
+    }
+}
+
+/// A running sccache server build steps compile through
+#[derive(Debug)]
+pub struct Sccache {
+    config: SccacheConfig,
+    port: Option<u16>,
+}
+
+impl Sccache {
+    /// Connect to the configured server, or start one
+    ///
+    /// A server left running by an earlier pipeline is reused.
+    pub async fn start(config: &SccacheConfig) -> Result<Self> {
+        let sccache = Self {
+            config: config.clone(),
+            port: config.server_port()?,
+        };
+
+        if sccache.port.is_none() {
+            tokio::fs::create_dir_all(&config.local_cache_dir)
+                .await
+                .with_context(|| {
+                    format!(
+                        "Failed to create sccache directory {}",
+                        config.local_cache_dir.display()
+                    )
+                })?;
+
+            let output = sccache
+                .command()
+                .arg("--start-server")
+                .envs(config.server_env())
+                .output()
+                .await
+                .context("Failed to run sccache")?;
+
+            if output.status.success() {
+                info!("Started sccache server");
+            } else if sccache.stats().await.is_ok() {
+                debug!("Using the running sccache server");
+            } else {
+                bail!(
+                    "sccache server failed to start: {}",
+                    String::from_utf8_lossy(&output.stderr).trim()
+                );
+            }
+        }
+
+        Ok(sccache)
+    }
+
+    /// Environment variables that make build steps compile through sccache
+    pub fn env(&self) -> Vec<(String, String)> {
+        let mut env = vec![
+            ("RUSTC_WRAPPER".to_string(), "sccache".to_string()),
+            (
+                "SCCACHE_DIR".to_string(),
+                self.config.local_cache_dir.to_string_lossy().to_string(),
+            ),
+            // sccache cannot cache incremental compilations
+            ("CARGO_INCREMENTAL".to_string(), "0".to_string()),
+        ];
+        if let Some(port) = self.port {
+            env.push(("SCCACHE_SERVER_PORT".to_string(), port.to_string()));
+        }
+        env
+    }
+
+    /// Current statistics of the server
+    pub async fn stats(&self) -> Result<SccacheStats> {
+        let output = self
+            .command()
+            .args(["--show-stats", "--stats-format", "json"])
+            .output()
+            .await
+            .context("Failed to run sccache")?;
+        if !output.status.success() {
+            bail!(
+                "sccache --show-stats failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+
+        SccacheStats::from_json(&String::from_utf8_lossy(&output.stdout))
+    }
+
+    fn command(&self) -> Command {
+        let mut cmd = Command::new("sccache");
+        if let Some(port) = self.port {
+            cmd.env("SCCACHE_SERVER_PORT", port.to_string());
+        }
+        cmd
+    }
+}
+
+/// Store a job's cache statistics in its job hash
+pub async fn store_cache_stats<C>(conn: &mut C, job_id: &str, stats: &SccacheStats) -> Result<()>
+where
+    C: redis::aio::ConnectionLike + Send,
+{
+    let _: () = conn
+        .hset_multiple(
+            job_key(job_id),
+            &[
+                (JOB_FIELD_CACHE_HITS, stats.cache_hits.to_string()),
+                (JOB_FIELD_CACHE_MISSES, stats.cache_misses.to_string()),
+                (JOB_FIELD_CACHE_HIT_RATIO, stats.hit_ratio().to_string()),
+            ],
+        )
+        .await
+        .with_context(|| format!("Failed to store cache statistics of job {}", job_id))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STATS: &str = r#"{
+        "stats": {
+            "compile_requests": 12,
+            "cache_hits": {"counts": {"Rust": 7, "C/C++": 1}, "adv_counts": {}},
+            "cache_misses": {"counts": {"Rust": 2}, "adv_counts": {}},
+            "cache_errors": {"counts": {}, "adv_counts": {}}
+        },
+        "cache_location": "Local disk: \"/tmp/raibid-sccache\""
+    }"#;
+
+    #[test]
+    fn test_stats_from_json() {
+        let stats = SccacheStats::from_json(STATS).unwrap();
+        assert_eq!(stats.cache_hits, 8);
+        assert_eq!(stats.cache_misses, 2);
+        assert_eq!(stats.hit_ratio(), 0.8);
+
+        let earlier = SccacheStats {
+            cache_hits: 5,
+            cache_misses: 2,
+        };
+        assert_eq!(
+            stats.since(&earlier),
+            SccacheStats {
+                cache_hits: 3,
+                cache_misses: 0,
+            }
+        );
+        assert_eq!(SccacheStats::default().hit_ratio(), 0.0);
+
+        assert!(SccacheStats::from_json("not json").is_err());
+    }
+
+    #[test]
+    fn test_server_config() {
+        let mut config = SccacheConfig {
+            gcs_bucket: Some("raibid-cache".to_string()),
+            s3_bucket: Some("ignored".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(config.server_port().unwrap(), None);
+        assert!(config
+            .server_env()
+            .contains(&("SCCACHE_GCS_BUCKET", "raibid-cache".to_string())));
+        assert!(!config
+            .server_env()
+            .iter()
+            .any(|(k, _)| *k == "SCCACHE_BUCKET"));
+
+        config.server_address = Some("127.0.0.1:4226".to_string());
+        assert_eq!(config.server_port().unwrap(), Some(4226));
+        config.server_address = Some("localhost".to_string());
+        assert!(config.server_port().is_err());
+    }
+}
@@ -0,0 +1,183 @@
+//! Agent status events
+//!
+//! [`StatusReportingHandler`] wraps a [`JobHandler`] and publishes an
+//! [`AgentEvent`] on the Redis Pub/Sub channel [`AGENT_EVENTS_CHANNEL`]
+//! whenever the agent goes from idle to busy (its first job starts) or from
+//! busy back to idle (its last running job ends). The server forwards these
+//! events to dashboards, which update without waiting for their next poll.
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use redis::aio::MultiplexedConnection;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use tracing::{debug, warn};
+
+use crate::consumer::{ClaimedJob, JobHandler, JobOutcome};
+use raibid_common::agents::{AgentEvent, AgentStatus, AGENT_EVENTS_CHANNEL};
+
+/// Where agent status events are sent
+#[async_trait]
+pub trait StatusPublisher: Send + Sync + 'static {
+    /// Publish a status change
+    async fn publish(&self, event: &AgentEvent) -> Result<()>;
+}
+
+/// [`StatusPublisher`] sending events to [`AGENT_EVENTS_CHANNEL`]
+#[derive(Clone)]
+pub struct RedisStatusPublisher {
+    conn: MultiplexedConnection,
+}
+
+impl RedisStatusPublisher {
+    /// Publish with `conn`
+    pub fn new(conn: MultiplexedConnection) -> Self {
+        Self { conn }
+    }
+}
+
+#[async_trait]
+impl StatusPublisher for RedisStatusPublisher {
+    async fn publish(&self, event: &AgentEvent) -> Result<()> {
+        let payload = serde_json::to_string(event)?;
+        let mut conn = self.conn.clone();
+        let _: i64 = redis::cmd("PUBLISH")
+            .arg(AGENT_EVENTS_CHANNEL)
+            .arg(payload)
+            .query_async(&mut conn)
+            .await
+            .with_context(|| format!("Failed to publish status of agent {}", event.agent_id))?;
+        Ok(())
+    }
+}
+
+/// Publish that `agent_id` entered `status`, logging rather than returning
+/// failures
+///
+/// A missing event only delays dashboards until their next poll, so it must
+/// not fail the agent.
+pub async fn publish_status<P: StatusPublisher + ?Sized>(
+    publisher: &P,
+    agent_id: &str,
+    status: AgentStatus,
+) {
+    debug!("Agent {} is {}", agent_id, status);
+    if let Err(e) = publisher.publish(&AgentEvent::now(agent_id, status)).await {
+        warn!("{:#}", e);
+    }
+}
+
+/// Runs jobs with an inner handler, publishing the agent's status as it
+/// becomes busy and idle again
+pub struct StatusReportingHandler<H, P> {
+    inner: H,
+    publisher: P,
+    agent_id: String,
+    running: Arc<AtomicUsize>,
+}
+
+impl<H: JobHandler, P: StatusPublisher> StatusReportingHandler<H, P> {
+    /// Wrap `inner`, publishing the status of `agent_id` with `publisher`
+    pub fn new(inner: H, publisher: P, agent_id: impl Into<String>) -> Self {
+        Self {
+            inner,
+            publisher,
+            agent_id: agent_id.into(),
+            running: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    /// Count running jobs in `counter`, e.g. to report them in heartbeats
+    pub fn with_running_jobs(mut self, counter: Arc<AtomicUsize>) -> Self {
+        self.running = counter;
+        self
+    }
+}
+
+#[async_trait]
+impl<H: JobHandler, P: StatusPublisher> JobHandler for StatusReportingHandler<H, P> {
+    async fn run(&self, claimed: &ClaimedJob) -> JobOutcome {
+        if self.running.fetch_add(1, Ordering::AcqRel) == 0 {
+            publish_status(&self.publisher, &self.agent_id, AgentStatus::Busy).await;
+        }
+
+        let outcome = self.inner.run(claimed).await;
+
+        if self.running.fetch_sub(1, Ordering::AcqRel) == 1 {
+            publish_status(&self.publisher, &self.agent_id, AgentStatus::Idle).await;
+        }
+        outcome
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use raibid_common::jobs::{JobBuilder, QueuedJob};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    #[derive(Default)]
+    struct MockPublisher {
+        events: Mutex<Vec<AgentEvent>>,
+    }
+
+    #[async_trait]
+    impl StatusPublisher for Arc<MockPublisher> {
+        async fn publish(&self, event: &AgentEvent) -> Result<()> {
+            self.events.lock().unwrap().push(event.clone());
+            Ok(())
+        }
+    }
+
+    /// Pipeline whose builds take a moment and succeed
+    struct SlowPipeline;
+
+    #[async_trait]
+    impl JobHandler for SlowPipeline {
+        async fn run(&self, _job: &ClaimedJob) -> JobOutcome {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            JobOutcome::Succeeded
+        }
+    }
+
+    fn claimed(id: &str) -> ClaimedJob {
+        ClaimedJob {
+            stream: "raibid:jobs:normal".to_string(),
+            message_id: "1700000000000-0".to_string(),
+            job: QueuedJob::new(id, JobBuilder::new("a/b").build().unwrap()),
+        }
+    }
+
+    fn statuses(publisher: &MockPublisher) -> Vec<AgentStatus> {
+        let events = publisher.events.lock().unwrap();
+        assert!(events.iter().all(|event| event.agent_id == "agent-1"));
+        events.iter().map(|event| event.status).collect()
+    }
+
+    #[tokio::test]
+    async fn test_publishes_busy_then_idle() {
+        let publisher = Arc::new(MockPublisher::default());
+        let handler = StatusReportingHandler::new(SlowPipeline, publisher.clone(), "agent-1");
+
+        let outcome = handler.run(&claimed("job-1")).await;
+        assert_eq!(outcome, JobOutcome::Succeeded);
+        assert_eq!(
+            statuses(&publisher),
+            vec![AgentStatus::Busy, AgentStatus::Idle]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_jobs_publish_once() {
+        let publisher = Arc::new(MockPublisher::default());
+        let handler = StatusReportingHandler::new(SlowPipeline, publisher.clone(), "agent-1");
+
+        let (first, second) = (claimed("job-1"), claimed("job-2"));
+        tokio::join!(handler.run(&first), handler.run(&second));
+        assert_eq!(
+            statuses(&publisher),
+            vec![AgentStatus::Busy, AgentStatus::Idle]
+        );
+    }
+}
@@ -3,7 +3,10 @@
 //! This module handles all command-line argument parsing using clap.
 //! It defines the CLI structure and routes commands to their implementations.
 
-use clap::{Args, Parser, Subcommand};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use clap_complete::Shell;
+use raibid_common::gitea::MIRROR_ORG;
+use raibid_common::jobs::JobPriority;
 use std::path::PathBuf;
 
 /// DGX Spark Personal CI Agent Pool
@@ -18,6 +21,10 @@ pub struct Cli {
     #[arg(short, long, global = true)]
     pub verbose: bool,
 
+    /// Configuration profile to load (overrides RAIBID_PROFILE)
+    #[arg(long, global = true, value_parser = raibid_common::config::PROFILES)]
+    pub profile: Option<String>,
+
     /// Subcommand to execute
     #[command(subcommand)]
     pub command: Option<Commands>,
@@ -29,27 +36,332 @@ pub enum Commands {
     /// Manage configuration
     Config(ConfigCommand),
     /// Launch the TUI dashboard for monitoring and management
-    Tui,
+    Tui {
+        /// Show generated mock data instead of connecting to the API server
+        #[arg(long)]
+        offline: bool,
+
+        /// Width of the jobs panel in percent (requires all three widths)
+        #[arg(long, value_name = "PCT", requires_all = ["agents_pct", "queue_pct"])]
+        jobs_pct: Option<u16>,
+
+        /// Width of the agents panel in percent
+        #[arg(long, value_name = "PCT", requires_all = ["jobs_pct", "queue_pct"])]
+        agents_pct: Option<u16>,
+
+        /// Width of the queue panel in percent
+        #[arg(long, value_name = "PCT", requires_all = ["jobs_pct", "agents_pct"])]
+        queue_pct: Option<u16>,
+    },
     /// Setup infrastructure component
     Setup {
         /// Component to setup (k3s, gitea, redis, keda, flux, all)
         component: String,
+
+        /// Show what would be installed without making any changes
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Upgrade an installed k3s to this version (e.g. v1.29.0+k3s1)
+        #[arg(long, value_name = "VERSION")]
+        upgrade_to: Option<String>,
+
+        /// Overwrite ~/.kube/config with the k3s kubeconfig instead of
+        /// merging the k3s context into it
+        #[arg(long)]
+        no_merge: bool,
     },
     /// Teardown infrastructure component
     Teardown {
         /// Component to teardown (k3s, gitea, redis, keda, flux, all)
         component: String,
+
+        /// Show what would be removed without making any changes
+        #[arg(long)]
+        dry_run: bool,
     },
     /// Show status of infrastructure component
     Status {
         /// Component to show status for (k3s, gitea, redis, keda, flux, all)
         component: Option<String>,
+
+        /// Exit with status 1 if the installed k3s is older than this
+        /// version (e.g. v1.28.0)
+        #[arg(long, value_name = "VERSION")]
+        min_version: Option<String>,
+    },
+    /// Run pre-flight checks for all components and suggest fixes
+    Diagnose,
+    /// Manage CI jobs
+    Jobs(JobsCommand),
+    /// View raibid logs
+    Logs(LogsCommand),
+    /// Inspect CI agents
+    Agent(AgentCommand),
+    /// Manage API server keys
+    ApiKey(ApiKeyCommand),
+    /// Print a shell completion script
+    Completions {
+        /// Shell to generate completions for
+        #[arg(value_enum)]
+        shell: Shell,
+    },
+    /// Mirror repositories into Gitea
+    Mirror(MirrorCommand),
+    /// Manage scheduled builds
+    Schedule(ScheduleCommand),
+}
+
+/// Output format for commands that print API data
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Human-readable table or text
+    #[default]
+    Table,
+    /// The raw API response as JSON
+    Json,
+}
+
+/// Job management commands
+#[derive(Args, Debug)]
+pub struct JobsCommand {
+    #[command(subcommand)]
+    pub command: JobsSubcommand,
+}
+
+/// Job subcommands
+#[derive(Subcommand, Debug)]
+pub enum JobsSubcommand {
+    /// Trigger a new build job
+    Trigger {
+        /// Repository to build (owner/name)
+        #[arg(long, required_unless_present = "from_stdin")]
+        repo: Option<String>,
+
+        /// Branch to build
+        #[arg(long, default_value = "main")]
+        branch: String,
+
+        /// Specific commit SHA to build
+        #[arg(long)]
+        commit: Option<String>,
+
+        /// Queue priority: high, normal, or low [default: normal]
+        ///
+        /// Overrides the priority of a trigger read with --from-stdin.
+        #[arg(long)]
+        priority: Option<JobPriority>,
+
+        /// Read a JSON job trigger from stdin instead of flags
+        #[arg(long, conflicts_with_all = ["repo", "commit"])]
+        from_stdin: bool,
+    },
+    /// List recent jobs, newest first
+    List {
+        /// Only list jobs with this label, e.g. source=gitlab (repeatable)
+        #[arg(long = "label", value_name = "KEY=VALUE")]
+        labels: Vec<String>,
+    },
+    /// Show the test results and coverage a job reported
+    Show {
+        /// Job ID
+        id: String,
+
+        /// Also print the job's line coverage as a bar chart
+        #[arg(long)]
+        coverage: bool,
+    },
+    /// Queue a job that failed every retry again, with its retries reset
+    Requeue {
+        /// ID of the dead job
+        id: String,
+    },
+    /// Run a finished job again under a new ID
+    Retry {
+        /// ID of the finished job
+        id: String,
+    },
+    /// Print a job's build output
+    Logs {
+        /// Job ID
+        id: String,
+
+        /// Keep printing new output until the job finishes
+        #[arg(short, long)]
+        follow: bool,
+
+        /// Don't color the error and warning prefixes
+        #[arg(long)]
+        no_color: bool,
+    },
+}
+
+/// Agent commands
+#[derive(Args, Debug)]
+pub struct AgentCommand {
+    /// Output format
+    #[arg(short, long, value_enum, default_value_t, global = true)]
+    pub output: OutputFormat,
+
+    #[command(subcommand)]
+    pub command: AgentSubcommand,
+}
+
+/// Agent subcommands
+#[derive(Subcommand, Debug)]
+pub enum AgentSubcommand {
+    /// List all agents
+    List,
+    /// Show details and job history for an agent
+    Show {
+        /// Agent ID
+        id: String,
+    },
+    /// Set the range of agent replicas the autoscaler works within
+    Scale {
+        /// Fewest agents to run (0 allows scaling to zero)
+        #[arg(long)]
+        min: u32,
+        /// Most agents to run
+        #[arg(long)]
+        max: u32,
+        /// Number of agents wanted now, within the range
+        #[arg(long)]
+        desired: Option<u32>,
+    },
+    /// Restart an agent once its running jobs finish
+    Restart {
+        /// Agent ID
+        id: String,
+        /// Restart without waiting for running jobs
+        #[arg(long)]
+        force: bool,
+    },
+}
+
+/// API key commands
+#[derive(Args, Debug)]
+pub struct ApiKeyCommand {
+    #[command(subcommand)]
+    pub command: ApiKeySubcommand,
+}
+
+/// API key subcommands
+#[derive(Subcommand, Debug)]
+pub enum ApiKeySubcommand {
+    /// Generate a new random API key and print it with its hash
+    Generate,
+}
+
+/// Log viewing commands
+#[derive(Args, Debug)]
+pub struct LogsCommand {
+    #[command(subcommand)]
+    pub command: LogsSubcommand,
+}
+
+/// Log subcommands
+#[derive(Subcommand, Debug)]
+pub enum LogsSubcommand {
+    /// Show the infrastructure install log (~/.raibid/install.log)
+    Install {
+        /// Only show the last N lines
+        #[arg(long, value_name = "N")]
+        tail: Option<usize>,
     },
-    // Placeholder for future subcommands
-    // These will be added in future issues:
-    // - Job
-    // - Agent
-    // - Mirror
+}
+
+/// Mirror commands
+#[derive(Args, Debug)]
+pub struct MirrorCommand {
+    /// Output format
+    #[arg(short, long, value_enum, default_value_t, global = true)]
+    pub output: OutputFormat,
+
+    /// Gitea organization owning the mirrors, created if missing
+    #[arg(long, default_value = MIRROR_ORG, global = true)]
+    pub org: String,
+
+    #[command(subcommand)]
+    pub command: MirrorSubcommand,
+}
+
+/// Mirror subcommands
+#[derive(Subcommand, Debug)]
+pub enum MirrorSubcommand {
+    /// Create a Gitea pull mirror of a repository
+    Add {
+        /// Repository to mirror (e.g. https://github.com/owner/repo)
+        url: String,
+
+        /// Name of the mirror in Gitea [default: the repository name]
+        #[arg(long)]
+        name: Option<String>,
+
+        /// Minutes between syncs
+        #[arg(long, default_value_t = 60)]
+        sync_interval: u32,
+    },
+    /// List mirrors with their sync status
+    List,
+    /// Delete a mirror from Gitea
+    Remove {
+        /// Name of the mirror in Gitea
+        name: String,
+    },
+}
+
+/// Schedule commands
+#[derive(Args, Debug)]
+pub struct ScheduleCommand {
+    /// Output format
+    #[arg(short, long, value_enum, default_value_t, global = true)]
+    pub output: OutputFormat,
+
+    #[command(subcommand)]
+    pub command: ScheduleSubcommand,
+}
+
+/// Schedule subcommands
+#[derive(Subcommand, Debug)]
+pub enum ScheduleSubcommand {
+    /// Build a branch whenever a cron expression matches
+    Add {
+        /// Repository to build (owner/name)
+        #[arg(long)]
+        repo: String,
+
+        /// Branch to build
+        #[arg(long, default_value = "main")]
+        branch: String,
+
+        /// Cron expression, e.g. "0 2 * * *" for 02:00 every day
+        #[arg(long)]
+        cron: String,
+
+        /// IANA time zone the expression is evaluated in
+        #[arg(long, default_value = "UTC")]
+        timezone: String,
+    },
+    /// List schedules
+    List,
+    /// Delete a schedule
+    Remove {
+        /// Schedule ID
+        id: String,
+    },
+}
+
+/// Format `config show` prints the configuration in
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ConfigFormat {
+    /// YAML, as in configuration files
+    #[default]
+    Yaml,
+    /// Pretty-printed JSON
+    Json,
+    /// TOML
+    Toml,
 }
 
 /// Configuration management commands
@@ -63,25 +375,36 @@ pub struct ConfigCommand {
 #[derive(Subcommand, Debug)]
 pub enum ConfigSubcommand {
     /// Initialize a new configuration file
+    ///
+    /// Prompts for the main settings when run in a terminal. Without a
+    /// terminal, --minimal or --example is required.
     Init {
         /// Output path for the configuration file
         #[arg(short, long, value_name = "FILE")]
         output: Option<PathBuf>,
 
-        /// Generate minimal configuration instead of full example
-        #[arg(short, long)]
+        /// Generate minimal configuration without prompting
+        #[arg(short, long, conflicts_with = "example")]
         minimal: bool,
 
+        /// Generate the full documented example without prompting
+        #[arg(long)]
+        example: bool,
+
         /// Overwrite existing configuration file
         #[arg(short, long)]
         force: bool,
+
+        /// Validate the configuration file after writing it
+        #[arg(long)]
+        validate_after: bool,
     },
 
     /// Show current configuration
     Show {
-        /// Output format (yaml, json, toml)
-        #[arg(short, long, default_value = "yaml")]
-        format: String,
+        /// Output format
+        #[arg(short, long, value_enum, default_value_t)]
+        format: ConfigFormat,
 
         /// Show configuration from specific file instead of merged config
         #[arg(long, value_name = "FILE")]
@@ -97,4 +420,11 @@ pub enum ConfigSubcommand {
 
     /// Show configuration file path
     Path,
+
+    /// Show settings that differ from the defaults
+    Diff {
+        /// Compare a specific file instead of the merged config
+        #[arg(long, value_name = "FILE")]
+        file: Option<PathBuf>,
+    },
 }
@@ -0,0 +1,562 @@
+//! Agent commands
+//!
+//! Provides subcommands for inspecting CI agents registered with the API server:
+//! - list: Table of all agents with their current job and resource usage
+//! - show: Full details and job history for one agent
+//! - scale: Set the replica range the agents are autoscaled within
+//! - restart: Drain an agent, then have the server replace its pod
+//!
+//! All honour `--output json`, which prints the API response as JSON.
+
+use anyhow::{bail, Context, Result};
+use colored::Colorize;
+use comfy_table::{presets::UTF8_FULL, Cell, Color, ContentArrangement, Table};
+use serde::Serialize;
+use std::time::{Duration, Instant};
+
+use crate::cli::{AgentCommand, AgentSubcommand, OutputFormat};
+use raibid_common::agents::{AgentDetails, AgentInfo, AgentScale, AgentStatus};
+use raibid_common::{ApiClient, Config};
+
+/// How often `restart` checks whether a draining agent has finished its jobs
+const DRAIN_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Result of `agent restart`
+#[derive(Debug, Serialize)]
+struct RestartSummary {
+    agent_id: String,
+    /// Whether the agent finished its jobs before the restart
+    drained: bool,
+}
+
+/// Handle agent command and its subcommands
+pub fn handle(cmd: &AgentCommand, config: &Config) -> Result<()> {
+    let client = ApiClient::from_config(config)?;
+
+    let output = match &cmd.command {
+        AgentSubcommand::List => list_agents(&client, cmd.output)?,
+        AgentSubcommand::Show { id } => show_agent(&client, id, cmd.output)?,
+        AgentSubcommand::Scale { min, max, desired } => {
+            let scale = AgentScale {
+                min: *min,
+                max: *max,
+                desired: *desired,
+            };
+            scale_agents(&client, &scale, cmd.output)?
+        }
+        AgentSubcommand::Restart { id, force } => {
+            restart_agent(&client, id, !*force, DRAIN_POLL_INTERVAL, cmd.output)?
+        }
+    };
+
+    println!("{}", output);
+    Ok(())
+}
+
+/// Fetch all agents and render them in the requested format
+fn list_agents(client: &ApiClient, format: OutputFormat) -> Result<String> {
+    let agents = client.list_agents().context("Failed to list agents")?;
+
+    match format {
+        OutputFormat::Json => to_json(&agents),
+        OutputFormat::Table if agents.is_empty() => Ok("No agents registered".to_string()),
+        OutputFormat::Table => Ok(agent_table(&agents).to_string()),
+    }
+}
+
+/// Fetch one agent and render it in the requested format
+fn show_agent(client: &ApiClient, id: &str, format: OutputFormat) -> Result<String> {
+    let agent = client
+        .get_agent(id)
+        .with_context(|| format!("Failed to get agent '{}'", id))?;
+
+    match format {
+        OutputFormat::Json => to_json(&agent),
+        OutputFormat::Table => Ok(agent_details(&agent)),
+    }
+}
+
+/// Apply a replica range and render the range the server applied
+fn scale_agents(client: &ApiClient, scale: &AgentScale, format: OutputFormat) -> Result<String> {
+    // Checked here too so mistakes are reported without a round trip
+    scale.validate().map_err(anyhow::Error::msg)?;
+
+    let applied = client
+        .scale_agents(scale)
+        .context("Failed to scale agents")?;
+
+    match format {
+        OutputFormat::Json => to_json(&applied),
+        OutputFormat::Table => Ok(scale_summary(&applied)),
+    }
+}
+
+/// Restart an agent, first draining it if `drain` is set
+///
+/// The drain is given up after the timeout the agent reported on
+/// registration.
+fn restart_agent(
+    client: &ApiClient,
+    id: &str,
+    drain: bool,
+    poll_interval: Duration,
+    format: OutputFormat,
+) -> Result<String> {
+    if drain {
+        let agent = client
+            .drain_agent(id)
+            .with_context(|| format!("Failed to drain agent '{}'", id))?;
+        let timeout = Duration::from_secs(agent.drain_timeout_secs);
+        wait_for_drain(client, id, timeout, poll_interval)?;
+    }
+
+    client
+        .restart_agent(id)
+        .with_context(|| format!("Failed to restart agent '{}'", id))?;
+
+    let summary = RestartSummary {
+        agent_id: id.to_string(),
+        drained: drain,
+    };
+    match format {
+        OutputFormat::Json => to_json(&summary),
+        OutputFormat::Table => Ok(format!("{} Restarted agent {}", "✓".green(), id)),
+    }
+}
+
+/// Poll a draining agent until it runs no jobs, showing how many are left
+///
+/// An offline agent counts as drained, since it cannot report its jobs
+/// finishing.
+fn wait_for_drain(
+    client: &ApiClient,
+    id: &str,
+    timeout: Duration,
+    poll_interval: Duration,
+) -> Result<()> {
+    let deadline = Instant::now() + timeout;
+    let mut shown = None;
+    loop {
+        let agent = client
+            .get_agent(id)
+            .with_context(|| format!("Failed to get agent '{}'", id))?
+            .info;
+        if agent.active_jobs == 0 || agent.status == AgentStatus::Offline {
+            return Ok(());
+        }
+
+        if shown != Some(agent.active_jobs) {
+            // stderr, so `--output json` stays parseable
+            eprintln!(
+                "Waiting for agent to drain... ({})",
+                active_jobs(agent.active_jobs)
+            );
+            shown = Some(agent.active_jobs);
+        }
+        if Instant::now() >= deadline {
+            bail!(
+                "Agent '{}' still has {} after {}s; use --force to restart it anyway",
+                id,
+                active_jobs(agent.active_jobs),
+                timeout.as_secs()
+            );
+        }
+        std::thread::sleep(poll_interval);
+    }
+}
+
+fn active_jobs(count: u32) -> String {
+    match count {
+        1 => "1 active job".to_string(),
+        n => format!("{} active jobs", n),
+    }
+}
+
+/// Describe the effective replica range
+fn scale_summary(scale: &AgentScale) -> String {
+    let mut summary = format!(
+        "Agents scale between {} and {} replicas",
+        scale.min, scale.max
+    );
+    if let Some(desired) = scale.desired {
+        summary.push_str(&format!(" (desired {})", desired));
+    }
+    summary
+}
+
+fn to_json<T: Serialize>(value: &T) -> Result<String> {
+    serde_json::to_string_pretty(value).context("Failed to serialize API response")
+}
+
+/// Build the agent list table
+fn agent_table(agents: &[AgentInfo]) -> Table {
+    let mut table = Table::new();
+    table
+        .load_preset(UTF8_FULL)
+        .set_content_arrangement(ContentArrangement::Dynamic)
+        .set_header(vec![
+            "Agent ID",
+            "Status",
+            "Current Job",
+            "CPU%",
+            "MEM%",
+            "Uptime",
+        ]);
+
+    for agent in agents {
+        table.add_row(vec![
+            Cell::new(&agent.id),
+            Cell::new(agent.status).fg(status_color(agent.status)),
+            Cell::new(agent.current_job.as_deref().unwrap_or("-")),
+            Cell::new(format!("{:.1}", agent.cpu_percent)),
+            Cell::new(format!("{:.1}", agent.memory_percent)),
+            Cell::new(format_uptime(agent.uptime_secs)),
+        ]);
+    }
+
+    table
+}
+
+/// Render all agent fields followed by its job history
+fn agent_details(agent: &AgentDetails) -> String {
+    let info = &agent.info;
+    let mut lines = vec![
+        format!("{} {}", "Agent".bold().cyan(), info.id.bold()),
+        String::new(),
+        format!("  Status:       {}", info.status),
+        format!(
+            "  Type:         {}",
+            agent.agent_type.as_deref().unwrap_or("-")
+        ),
+        format!(
+            "  Current Job:  {}",
+            info.current_job.as_deref().unwrap_or("-")
+        ),
+        format!("  CPU:          {:.1}%", info.cpu_percent),
+        format!("  Memory:       {:.1}%", info.memory_percent),
+        format!("  Uptime:       {}", format_uptime(info.uptime_secs)),
+        format!(
+            "  Last Seen:    {}",
+            agent
+                .last_heartbeat
+                .map(|t| t.format("%Y-%m-%d %H:%M:%S UTC").to_string())
+                .unwrap_or_else(|| "-".to_string())
+        ),
+        String::new(),
+        format!("{}", "Job History".bold().cyan()),
+    ];
+
+    if agent.job_history.is_empty() {
+        lines.push("  No jobs run yet".to_string());
+    } else {
+        let mut table = Table::new();
+        table
+            .load_preset(UTF8_FULL)
+            .set_content_arrangement(ContentArrangement::Dynamic)
+            .set_header(vec![
+                "Job ID",
+                "Repository",
+                "Status",
+                "Started",
+                "Duration",
+            ]);
+
+        for job in &agent.job_history {
+            table.add_row(vec![
+                Cell::new(&job.job_id),
+                Cell::new(&job.repo),
+                Cell::new(job.status),
+                Cell::new(job.started_at.format("%Y-%m-%d %H:%M:%S")),
+                Cell::new(job.duration_secs.map(format_uptime).unwrap_or_default()),
+            ]);
+        }
+        lines.push(table.to_string());
+    }
+
+    lines.join("\n")
+}
+
+fn status_color(status: AgentStatus) -> Color {
+    match status {
+        AgentStatus::Idle => Color::Green,
+        AgentStatus::Busy => Color::Cyan,
+        AgentStatus::Starting | AgentStatus::Stopping => Color::Yellow,
+        AgentStatus::Offline => Color::Red,
+    }
+}
+
+/// Format a number of seconds as e.g. `2d 3h`, `1h 05m` or `42s`
+fn format_uptime(seconds: u64) -> String {
+    let days = seconds / 86400;
+    let hours = (seconds % 86400) / 3600;
+    let minutes = (seconds % 3600) / 60;
+
+    if days > 0 {
+        format!("{}d {}h", days, hours)
+    } else if hours > 0 {
+        format!("{}h {:02}m", hours, minutes)
+    } else if minutes > 0 {
+        format!("{}m {:02}s", minutes, seconds % 60)
+    } else {
+        format!("{}s", seconds)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const AGENTS_JSON: &str = r#"[
+        {"id": "agent-001", "status": "busy", "current_job": "job-42",
+         "cpu_percent": 71.5, "memory_percent": 40.0, "uptime_secs": 7500},
+        {"id": "agent-002", "status": "idle", "current_job": null,
+         "cpu_percent": 3.0, "memory_percent": 12.0, "uptime_secs": 42}
+    ]"#;
+
+    const AGENT_JSON: &str = r#"{
+        "id": "agent-001", "status": "busy", "current_job": "job-42",
+        "cpu_percent": 71.5, "memory_percent": 40.0, "uptime_secs": 7500,
+        "agent_type": "rust-builder",
+        "job_history": [{
+            "job_id": "job-41", "repo": "raibid-labs/raibid-cli",
+            "status": "failed", "started_at": "2024-01-01T12:00:00Z",
+            "duration_secs": 95
+        }]
+    }"#;
+
+    fn mock_json(server: &mut mockito::Server, path: &str, body: &str) -> mockito::Mock {
+        server
+            .mock("GET", path)
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(body)
+            .create()
+    }
+
+    #[test]
+    fn test_list_agents_table() {
+        let mut server = mockito::Server::new();
+        let mock = mock_json(&mut server, "/api/agents", AGENTS_JSON);
+        let client = ApiClient::new(server.url()).unwrap();
+
+        let output = list_agents(&client, OutputFormat::Table).unwrap();
+        mock.assert();
+        for expected in [
+            "Agent ID",
+            "Current Job",
+            "agent-001",
+            "job-42",
+            "71.5",
+            "40.0",
+            "2h 05m",
+        ] {
+            assert!(
+                output.contains(expected),
+                "missing {:?} in\n{}",
+                expected,
+                output
+            );
+        }
+        assert!(output.contains("agent-002"));
+    }
+
+    #[test]
+    fn test_list_agents_json() {
+        let mut server = mockito::Server::new();
+        mock_json(&mut server, "/api/agents", AGENTS_JSON);
+        let client = ApiClient::new(server.url()).unwrap();
+
+        let output = list_agents(&client, OutputFormat::Json).unwrap();
+        let agents: Vec<AgentInfo> = serde_json::from_str(&output).unwrap();
+        assert_eq!(agents.len(), 2);
+        assert_eq!(agents[1].status, AgentStatus::Idle);
+    }
+
+    #[test]
+    fn test_show_agent() {
+        let mut server = mockito::Server::new();
+        let mock = mock_json(&mut server, "/api/agents/agent-001", AGENT_JSON);
+        let client = ApiClient::new(server.url()).unwrap();
+
+        let output = show_agent(&client, "agent-001", OutputFormat::Table).unwrap();
+        mock.assert();
+        for expected in [
+            "rust-builder",
+            "job-42",
+            "job-41",
+            "raibid-labs/raibid-cli",
+            "failed",
+            "1m 35s",
+        ] {
+            assert!(
+                output.contains(expected),
+                "missing {:?} in\n{}",
+                expected,
+                output
+            );
+        }
+
+        let output = show_agent(&client, "agent-001", OutputFormat::Json).unwrap();
+        let agent: AgentDetails = serde_json::from_str(&output).unwrap();
+        assert_eq!(agent.job_history[0].job_id, "job-41");
+    }
+
+    #[test]
+    fn test_show_agent_not_found() {
+        let mut server = mockito::Server::new();
+        server
+            .mock("GET", "/api/agents/missing")
+            .with_status(404)
+            .with_body("agent not found")
+            .create();
+        let client = ApiClient::new(server.url()).unwrap();
+
+        let err = show_agent(&client, "missing", OutputFormat::Table).unwrap_err();
+        assert!(format!("{:#}", err).contains("404"));
+    }
+
+    #[test]
+    fn test_scale_agents() {
+        let mut server = mockito::Server::new();
+        let mock = server
+            .mock("POST", "/api/agents/scale")
+            .match_body(mockito::Matcher::Json(serde_json::json!({
+                "min": 1,
+                "max": 5,
+                "desired": 3
+            })))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"min": 1, "max": 5, "desired": 3}"#)
+            .expect(2)
+            .create();
+        let client = ApiClient::new(server.url()).unwrap();
+
+        let scale = AgentScale {
+            min: 1,
+            max: 5,
+            desired: Some(3),
+        };
+        let output = scale_agents(&client, &scale, OutputFormat::Table).unwrap();
+        assert_eq!(output, "Agents scale between 1 and 5 replicas (desired 3)");
+
+        let output = scale_agents(&client, &scale, OutputFormat::Json).unwrap();
+        let applied: AgentScale = serde_json::from_str(&output).unwrap();
+        assert_eq!(applied, scale);
+
+        // Invalid ranges never reach the server
+        let inverted = AgentScale {
+            min: 5,
+            max: 1,
+            desired: None,
+        };
+        assert!(scale_agents(&client, &inverted, OutputFormat::Table).is_err());
+        mock.assert();
+    }
+
+    fn agent_json(active_jobs: u32, drain_timeout_secs: u64) -> String {
+        format!(
+            r#"{{"id": "agent-001", "status": "busy", "cpu_percent": 0.0,
+               "memory_percent": 0.0, "uptime_secs": 60,
+               "active_jobs": {}, "draining": true,
+               "drain_timeout_secs": {}}}"#,
+            active_jobs, drain_timeout_secs
+        )
+    }
+
+    #[test]
+    fn test_restart_agent_drains_first() {
+        let mut server = mockito::Server::new();
+        let drain = server
+            .mock("POST", "/api/agents/agent-001/drain")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(agent_json(2, 5))
+            .create();
+        let poll = mock_json(&mut server, "/api/agents/agent-001", &agent_json(0, 5));
+        let restart = server
+            .mock("POST", "/api/agents/agent-001/restart")
+            .with_status(202)
+            .create();
+        let client = ApiClient::new(server.url()).unwrap();
+
+        let output = restart_agent(
+            &client,
+            "agent-001",
+            true,
+            Duration::from_millis(10),
+            OutputFormat::Json,
+        )
+        .unwrap();
+        drain.assert();
+        poll.assert();
+        restart.assert();
+        let summary: serde_json::Value = serde_json::from_str(&output).unwrap();
+        assert_eq!(summary["agent_id"], "agent-001");
+        assert_eq!(summary["drained"], true);
+    }
+
+    #[test]
+    fn test_restart_agent_drain_timeout() {
+        let mut server = mockito::Server::new();
+        server
+            .mock("POST", "/api/agents/agent-001/drain")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(agent_json(2, 0))
+            .create();
+        mock_json(&mut server, "/api/agents/agent-001", &agent_json(2, 0));
+        let restart = server
+            .mock("POST", "/api/agents/agent-001/restart")
+            .expect(0)
+            .create();
+        let client = ApiClient::new(server.url()).unwrap();
+
+        // The agent's own timeout of 0s gives up after the first poll
+        let err = restart_agent(
+            &client,
+            "agent-001",
+            true,
+            Duration::from_millis(10),
+            OutputFormat::Table,
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("2 active jobs"), "{}", err);
+        assert!(err.to_string().contains("--force"));
+        restart.assert();
+    }
+
+    #[test]
+    fn test_restart_agent_force() {
+        let mut server = mockito::Server::new();
+        let drain = server
+            .mock("POST", "/api/agents/agent-001/drain")
+            .expect(0)
+            .create();
+        let restart = server
+            .mock("POST", "/api/agents/agent-001/restart")
+            .with_status(202)
+            .create();
+        let client = ApiClient::new(server.url()).unwrap();
+
+        let output = restart_agent(
+            &client,
+            "agent-001",
+            false,
+            Duration::from_millis(10),
+            OutputFormat::Table,
+        )
+        .unwrap();
+        assert!(output.contains("Restarted agent agent-001"));
+        drain.assert();
+        restart.assert();
+    }
+
+    #[test]
+    fn test_format_uptime() {
+        assert_eq!(format_uptime(42), "42s");
+        assert_eq!(format_uptime(95), "1m 35s");
+        assert_eq!(format_uptime(7500), "2h 05m");
+        assert_eq!(format_uptime(190_000), "2d 4h");
+    }
+}
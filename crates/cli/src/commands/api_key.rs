@@ -0,0 +1,32 @@
+//! API key commands
+//!
+//! Provides subcommands for managing raibid-server API keys:
+//! - generate: Create a new random key and print it with its hash
+
+use anyhow::Result;
+use colored::Colorize;
+
+use crate::cli::{ApiKeyCommand, ApiKeySubcommand};
+use raibid_common::auth::{generate_api_key, hash_api_key};
+
+/// Handle api-key command and its subcommands
+pub fn handle(cmd: &ApiKeyCommand) -> Result<()> {
+    match cmd.command {
+        ApiKeySubcommand::Generate => {
+            generate();
+            Ok(())
+        }
+    }
+}
+
+/// Print a new API key and its hash
+fn generate() {
+    let key = generate_api_key();
+    let hash = hash_api_key(&key);
+
+    println!("{} {}", "API key:".bold(), key.cyan());
+    println!("{} {}", "Hash:   ".bold(), hash);
+    println!();
+    println!("Add the hash to the server's api_keys and set the key as api.api_key");
+    println!("(or RAIBID_API_KEY) for clients. The key is not shown again.");
+}
@@ -0,0 +1,58 @@
+//! Shell completion command
+//!
+//! Prints a completion script for the given shell to stdout, e.g.
+//! `raibid-cli completions bash > ~/.local/share/bash-completion/completions/raibid-cli`.
+
+use anyhow::Result;
+use clap::CommandFactory;
+use clap_complete::{generate, Shell};
+use std::io::{stdout, Write};
+
+use crate::cli::Cli;
+
+/// Command name the completions are registered for
+const BIN_NAME: &str = "raibid-cli";
+
+/// Print the completion script for `shell`
+pub fn execute(shell: Shell) -> Result<()> {
+    write_completions(shell, &mut stdout())
+}
+
+/// Write the completion script for `shell` to `out`
+fn write_completions(shell: Shell, out: &mut impl Write) -> Result<()> {
+    generate(shell, &mut Cli::command(), BIN_NAME, out);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn completions(shell: Shell) -> String {
+        let mut out = Vec::new();
+        write_completions(shell, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_bash_completions_include_jobs_options() {
+        let script = completions(Shell::Bash);
+        assert!(script.contains("trigger"));
+        for option in ["--repo", "--branch", "--priority", "--from-stdin"] {
+            assert!(script.contains(option), "missing {}", option);
+        }
+    }
+
+    #[test]
+    fn test_all_shells() {
+        for shell in [
+            Shell::Bash,
+            Shell::Zsh,
+            Shell::Fish,
+            Shell::PowerShell,
+            Shell::Elvish,
+        ] {
+            assert!(completions(shell).contains(BIN_NAME), "{}", shell);
+        }
+    }
+}
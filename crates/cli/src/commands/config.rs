@@ -1,34 +1,106 @@
 //! Configuration management commands
 //!
 //! Provides subcommands for managing raibid-cli configuration:
-//! - init: Create an example configuration file
+//! - init: Create a configuration file, prompting for the main settings
 //! - show: Display current configuration
 //! - validate: Validate a configuration file
 //! - path: Show configuration file location
+//! - diff: Show settings that differ from the defaults
 
-use crate::cli::ConfigCommand;
-use raibid_common::config::{discover_config_files, load_config, load_config_file, validate_config};
+use crate::cli::{ConfigCommand, ConfigFormat};
+use raibid_common::config::{
+    discover_config_files, expand_paths, load_config, load_config_file, validate_config,
+};
+use raibid_common::Config;
 use anyhow::{Context, Result};
 use colored::Colorize;
+use dialoguer::{Confirm, Input, theme::ColorfulTheme};
+use serde_yaml::Value;
 use std::fs;
+use std::io::IsTerminal;
 use std::path::PathBuf;
 
+/// Parts of setting names whose values are masked in diffs
+const SENSITIVE_KEY_PARTS: &[&str] = &["password", "secret", "api_key", "token"];
+
 /// Handle config command and its subcommands
 pub fn handle(cmd: &ConfigCommand) -> Result<()> {
     match &cmd.command {
         crate::cli::ConfigSubcommand::Init {
             output,
             minimal,
+            example,
             force,
-        } => init_config(output.as_ref(), *minimal, *force),
-        crate::cli::ConfigSubcommand::Show { format, file } => show_config(format, file.as_ref()),
+            validate_after,
+        } => {
+            let template = if *minimal {
+                Some(Template::Minimal)
+            } else if *example {
+                Some(Template::Example)
+            } else {
+                None
+            };
+            let path = init_config(output.as_ref(), template, *force)?;
+            if *validate_after {
+                validate_config_file(Some(&path))?;
+            }
+            Ok(())
+        }
+        crate::cli::ConfigSubcommand::Show { format, file } => show_config(*format, file.as_ref()),
         crate::cli::ConfigSubcommand::Validate { file } => validate_config_file(file.as_ref()),
         crate::cli::ConfigSubcommand::Path => show_config_path(),
+        crate::cli::ConfigSubcommand::Diff { file } => show_config_diff(file.as_ref()),
+    }
+}
+
+/// Configuration file written without prompting
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Template {
+    /// Only the settings most likely to change
+    Minimal,
+    /// Every setting with its default and documentation
+    Example,
+}
+
+/// Source of answers to the `config init` prompts
+trait Prompter {
+    /// Ask for a value, returning `default` if the user accepts it
+    fn input(&mut self, prompt: &str, default: &str) -> Result<String>;
+
+    /// Ask a yes/no question
+    fn confirm(&mut self, prompt: &str, default: bool) -> Result<bool>;
+}
+
+/// Prompts on the terminal
+struct TerminalPrompter {
+    theme: ColorfulTheme,
+}
+
+impl Prompter for TerminalPrompter {
+    fn input(&mut self, prompt: &str, default: &str) -> Result<String> {
+        Ok(Input::<String>::with_theme(&self.theme)
+            .with_prompt(prompt)
+            .default(default.to_string())
+            .interact_text()?)
+    }
+
+    fn confirm(&mut self, prompt: &str, default: bool) -> Result<bool> {
+        Ok(Confirm::with_theme(&self.theme)
+            .with_prompt(prompt)
+            .default(default)
+            .interact()?)
     }
 }
 
-/// Initialize a new configuration file
-fn init_config(output: Option<&PathBuf>, minimal: bool, force: bool) -> Result<()> {
+/// Initialize a new configuration file, returning its path
+///
+/// Without a template the settings are asked for interactively, which
+/// requires stdin to be a terminal.
+fn init_config(
+    output: Option<&PathBuf>,
+    template: Option<Template>,
+    force: bool,
+) -> Result<PathBuf> {
     // Determine output path
     let output_path = if let Some(path) = output {
         path.clone()
@@ -44,11 +116,21 @@ fn init_config(output: Option<&PathBuf>, minimal: bool, force: bool) -> Result<(
         );
     }
 
-    // Get example content
-    let content = if minimal {
-        get_minimal_config()
-    } else {
-        get_example_config()
+    let (content, kind) = match template {
+        Some(Template::Minimal) => (get_minimal_config(), "minimal"),
+        Some(Template::Example) => (get_example_config(), "example"),
+        None => {
+            if !std::io::stdin().is_terminal() {
+                anyhow::bail!(
+                    "stdin is not a terminal. Use --minimal or --example to write a configuration without prompts."
+                );
+            }
+            let mut prompter = TerminalPrompter {
+                theme: ColorfulTheme::default(),
+            };
+            let config = prompt_config(&mut prompter)?;
+            (render_config(&config)?, "new")
+        }
     };
 
     // Write file
@@ -58,43 +140,77 @@ fn init_config(output: Option<&PathBuf>, minimal: bool, force: bool) -> Result<(
     println!(
         "{} Created {} configuration file at: {}",
         "✓".green().bold(),
-        if minimal { "minimal" } else { "example" },
+        kind,
         output_path.display().to_string().cyan()
     );
 
-    Ok(())
+    Ok(output_path)
+}
+
+/// Ask for the main settings, pre-filled with their defaults
+fn prompt_config(prompter: &mut dyn Prompter) -> Result<Config> {
+    let mut config = Config::default();
+
+    config.api.host = prompter.input("API server host", &config.api.host)?;
+    config.api.port = prompt_port(prompter, "API server port", config.api.port)?;
+    config.gitea.url = prompter.input("Gitea URL", &config.gitea.url)?;
+    config.redis.host = prompter.input("Redis host", &config.redis.host)?;
+    config.redis.port = prompt_port(prompter, "Redis port", config.redis.port)?;
+
+    config.api.tls_enabled = prompter.confirm("Enable TLS?", config.api.tls_enabled)?;
+    if config.api.tls_enabled {
+        config.api.tls_cert_path = Some(prompter.input("TLS certificate path", "")?.into());
+        config.api.tls_key_path = Some(prompter.input("TLS private key path", "")?.into());
+    }
+
+    Ok(config)
+}
+
+fn prompt_port(prompter: &mut dyn Prompter, prompt: &str, default: u16) -> Result<u16> {
+    let answer = prompter.input(prompt, &default.to_string())?;
+    answer
+        .trim()
+        .parse()
+        .with_context(|| format!("Invalid {}: '{}'", prompt.to_lowercase(), answer))
+}
+
+/// Render a configuration as YAML with a header comment
+fn render_config(config: &Config) -> Result<String> {
+    let yaml = serde_yaml::to_string(config).context("Failed to serialize config to YAML")?;
+    Ok(format!(
+        "# raibid-cli configuration file\n# Generated by `raibid-cli config init`\n\n{}",
+        yaml
+    ))
 }
 
 /// Show current configuration
-fn show_config(format: &str, file: Option<&PathBuf>) -> Result<()> {
+///
+/// With `file`, only that file is loaded rather than the merged configuration
+/// of every source.
+fn show_config(format: ConfigFormat, file: Option<&PathBuf>) -> Result<()> {
     let config = if let Some(path) = file {
         load_config_file(path)?
     } else {
         load_config()?
     };
 
+    println!("{}", format_config(&config, format)?);
+    Ok(())
+}
+
+/// Serialize a configuration in `format`
+fn format_config(config: &Config, format: ConfigFormat) -> Result<String> {
     match format {
-        "yaml" => {
-            let yaml =
-                serde_yaml::to_string(&config).context("Failed to serialize config to YAML")?;
-            println!("{}", yaml);
-        }
-        "json" => {
-            let json = serde_json::to_string_pretty(&config)
-                .context("Failed to serialize config to JSON")?;
-            println!("{}", json);
+        ConfigFormat::Yaml => {
+            serde_yaml::to_string(config).context("Failed to serialize config to YAML")
         }
-        "toml" => {
-            let toml_str =
-                toml::to_string_pretty(&config).context("Failed to serialize config to TOML")?;
-            println!("{}", toml_str);
+        ConfigFormat::Json => {
+            serde_json::to_string_pretty(config).context("Failed to serialize config to JSON")
         }
-        _ => {
-            anyhow::bail!("Unsupported format: {}. Use yaml, json, or toml", format);
+        ConfigFormat::Toml => {
+            toml::to_string_pretty(config).context("Failed to serialize config to TOML")
         }
     }
-
-    Ok(())
 }
 
 /// Validate a configuration file
@@ -147,6 +263,149 @@ fn show_config_path() -> Result<()> {
     Ok(())
 }
 
+/// A setting that differs from the defaults
+#[derive(Debug, Clone, PartialEq)]
+enum ConfigChange {
+    /// Setting with no default
+    Added { key: String, value: Value },
+    /// Default setting missing from the configuration
+    Removed { key: String, value: Value },
+    /// Setting with a value other than the default
+    Changed { key: String, old: Value, new: Value },
+}
+
+/// Show settings that differ from the defaults
+fn show_config_diff(file: Option<&PathBuf>) -> Result<()> {
+    let (config, source) = if let Some(path) = file {
+        (load_config_file(path)?, path.display().to_string())
+    } else {
+        (load_config()?, "merged configuration".to_string())
+    };
+
+    // The merged config has `~` expanded, so expand the defaults' paths too
+    let defaults = expand_paths(Config::default())?;
+    let config = expand_paths(config)?;
+    let changes = diff_config(&defaults, &config)?;
+    if changes.is_empty() {
+        println!("{} Configuration matches the defaults", "✓".green().bold());
+        return Ok(());
+    }
+
+    print!("{}", format_diff(&changes, &source));
+    Ok(())
+}
+
+/// Compare `config` against `defaults` setting by setting
+fn diff_config(defaults: &Config, config: &Config) -> Result<Vec<ConfigChange>> {
+    let defaults = serde_yaml::to_value(defaults).context("Failed to serialize default config")?;
+    let config = serde_yaml::to_value(config).context("Failed to serialize config")?;
+
+    let mut changes = Vec::new();
+    diff_values("", &defaults, &config, &mut changes);
+    Ok(changes)
+}
+
+/// Recursively collect the differences between two YAML values
+///
+/// Mappings are compared key by key; any other value, including lists, is
+/// compared as a whole.
+fn diff_values(prefix: &str, old: &Value, new: &Value, changes: &mut Vec<ConfigChange>) {
+    let (Value::Mapping(old_map), Value::Mapping(new_map)) = (old, new) else {
+        if old != new {
+            changes.push(ConfigChange::Changed {
+                key: prefix.to_string(),
+                old: old.clone(),
+                new: new.clone(),
+            });
+        }
+        return;
+    };
+
+    for (key, old_value) in old_map {
+        let path = key_path(prefix, key);
+        match new_map.get(key) {
+            Some(new_value) => diff_values(&path, old_value, new_value, changes),
+            None => changes.push(ConfigChange::Removed {
+                key: path,
+                value: old_value.clone(),
+            }),
+        }
+    }
+
+    for (key, new_value) in new_map {
+        if !old_map.contains_key(key) {
+            changes.push(ConfigChange::Added {
+                key: key_path(prefix, key),
+                value: new_value.clone(),
+            });
+        }
+    }
+}
+
+/// Dotted path of `key` below `prefix`
+fn key_path(prefix: &str, key: &Value) -> String {
+    let key = match key {
+        Value::String(s) => s.clone(),
+        other => format_value(other),
+    };
+
+    if prefix.is_empty() {
+        key
+    } else {
+        format!("{}.{}", prefix, key)
+    }
+}
+
+/// Format changes as a unified diff, additions in green and removals in red
+fn format_diff(changes: &[ConfigChange], source: &str) -> String {
+    let mut out = format!(
+        "{}\n{}\n",
+        "--- defaults".red(),
+        format!("+++ {}", source).green()
+    );
+
+    for change in changes {
+        match change {
+            ConfigChange::Added { key, value } => {
+                out.push_str(&format!("{}\n", diff_line('+', key, value).green()));
+            }
+            ConfigChange::Removed { key, value } => {
+                out.push_str(&format!("{}\n", diff_line('-', key, value).red()));
+            }
+            ConfigChange::Changed { key, old, new } => {
+                out.push_str(&format!("{}\n", diff_line('-', key, old).red()));
+                out.push_str(&format!("{}\n", diff_line('+', key, new).green()));
+            }
+        }
+    }
+
+    out
+}
+
+/// A single diff line, with sensitive values masked
+fn diff_line(sign: char, key: &str, value: &Value) -> String {
+    let lower = key.to_lowercase();
+    let value = if SENSITIVE_KEY_PARTS.iter().any(|part| lower.contains(part)) {
+        "***".to_string()
+    } else {
+        format_value(value)
+    };
+
+    format!("{} {}: {}", sign, key, value)
+}
+
+/// Format a value on one line
+fn format_value(value: &Value) -> String {
+    match value {
+        Value::Null => "null".to_string(),
+        Value::Bool(b) => b.to_string(),
+        Value::Number(n) => n.to_string(),
+        Value::String(s) => s.clone(),
+        // Lists and mappings render inline as JSON flow style
+        other => serde_json::to_string(other).unwrap_or_else(|_| format!("{:?}", other)),
+    }
+}
+
 /// Get minimal configuration example
 fn get_minimal_config() -> String {
     r#"# Minimal raibid-cli configuration
@@ -206,6 +465,9 @@ api:
   # Path to TLS private key (only if tls_enabled: true)
   # tls_key_path: /path/to/key.pem
 
+  # API key sent to the server (generate one with: raibid api-key generate)
+  # api_key: rbd_...
+
 # Agent configuration
 agents:
   # Agent types to enable (currently only 'rust' is supported in MVP)
@@ -277,3 +539,181 @@ ui:
 "#
     .to_string()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Answers prompts from a fixed sequence, with `None` accepting the default
+    struct ScriptedPrompter {
+        answers: std::collections::VecDeque<Option<&'static str>>,
+    }
+
+    impl ScriptedPrompter {
+        fn new(answers: &[Option<&'static str>]) -> Self {
+            Self {
+                answers: answers.iter().copied().collect(),
+            }
+        }
+
+        fn next(&mut self) -> Result<Option<&'static str>> {
+            self.answers
+                .pop_front()
+                .ok_or_else(|| anyhow::anyhow!("Unexpected prompt"))
+        }
+    }
+
+    impl Prompter for ScriptedPrompter {
+        fn input(&mut self, _prompt: &str, default: &str) -> Result<String> {
+            Ok(self.next()?.unwrap_or(default).to_string())
+        }
+
+        fn confirm(&mut self, _prompt: &str, default: bool) -> Result<bool> {
+            Ok(self.next()?.map(|a| a == "y").unwrap_or(default))
+        }
+    }
+
+    #[test]
+    fn test_prompt_config_defaults() {
+        let mut prompter = ScriptedPrompter::new(&[None; 6]);
+        let config = prompt_config(&mut prompter).unwrap();
+        assert_eq!(config, Config::default());
+        assert!(prompter.answers.is_empty());
+    }
+
+    #[test]
+    fn test_prompt_config_answers() {
+        let mut prompter = ScriptedPrompter::new(&[
+            Some("0.0.0.0"),
+            Some("9443"),
+            None,
+            Some("localhost"),
+            Some("6380"),
+            Some("y"),
+            Some("/etc/raibid/tls.crt"),
+            Some("/etc/raibid/tls.key"),
+        ]);
+        let config = prompt_config(&mut prompter).unwrap();
+        assert_eq!(config.api.host, "0.0.0.0");
+        assert_eq!(config.api.port, 9443);
+        assert_eq!(config.gitea.url, Config::default().gitea.url);
+        assert_eq!(config.redis.host, "localhost");
+        assert_eq!(config.redis.port, 6380);
+        assert!(config.api.tls_enabled);
+        assert_eq!(
+            config.api.tls_key_path,
+            Some(PathBuf::from("/etc/raibid/tls.key"))
+        );
+
+        // The written file loads back and passes validation
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("raibid.yaml");
+        fs::write(&path, render_config(&config).unwrap()).unwrap();
+        let loaded = load_config_file(&path).unwrap();
+        assert_eq!(loaded, config);
+        assert!(validate_config(&loaded).is_ok());
+    }
+
+    #[test]
+    fn test_prompt_config_invalid_port() {
+        let mut prompter = ScriptedPrompter::new(&[None, Some("http")]);
+        let err = prompt_config(&mut prompter).unwrap_err();
+        assert!(err.to_string().contains("api server port"));
+    }
+
+    /// Configuration with every optional setting set
+    fn full_config() -> Config {
+        let mut config = Config::default();
+        config.api.port = 9443;
+        config.api.tls_enabled = true;
+        config.api.tls_cert_path = Some(PathBuf::from("/etc/raibid/tls.crt"));
+        config.api.tls_key_path = Some(PathBuf::from("/etc/raibid/tls.key"));
+        config.redis.password = Some("hunter2".to_string());
+        config.agents.types = vec!["rust".to_string(), "docker".to_string()];
+        config
+    }
+
+    #[test]
+    fn test_format_config_round_trip() {
+        for config in [Config::default(), full_config()] {
+            let yaml = format_config(&config, ConfigFormat::Yaml).unwrap();
+            assert_eq!(serde_yaml::from_str::<Config>(&yaml).unwrap(), config);
+
+            let json = format_config(&config, ConfigFormat::Json).unwrap();
+            assert_eq!(serde_json::from_str::<Config>(&json).unwrap(), config);
+
+            let toml_str = format_config(&config, ConfigFormat::Toml).unwrap();
+            assert_eq!(toml::from_str::<Config>(&toml_str).unwrap(), config);
+        }
+    }
+
+    #[test]
+    fn test_show_rejects_unknown_format() {
+        use clap::Parser;
+
+        let cli = crate::cli::Cli::try_parse_from(["raibid-cli", "config", "show", "-f", "toml"]);
+        assert!(cli.is_ok());
+
+        let err = crate::cli::Cli::try_parse_from(["raibid-cli", "config", "show", "-f", "xml"])
+            .unwrap_err();
+        assert_eq!(err.kind(), clap::error::ErrorKind::InvalidValue);
+        assert_eq!(err.exit_code(), 2);
+    }
+
+    #[test]
+    fn test_config_diff_shows_changed_key() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("raibid.yaml");
+        let mut config = Config::default();
+        config.api.port = 9090;
+        fs::write(&path, serde_yaml::to_string(&config).unwrap()).unwrap();
+
+        let loaded = load_config_file(&path).unwrap();
+        let changes = diff_config(&Config::default(), &loaded).unwrap();
+        assert_eq!(
+            changes,
+            vec![ConfigChange::Changed {
+                key: "api.port".to_string(),
+                old: Value::from(8080),
+                new: Value::from(9090),
+            }]
+        );
+
+        let output = format_diff(&changes, "raibid.yaml");
+        assert!(output.contains("- api.port: 8080"));
+        assert!(output.contains("+ api.port: 9090"));
+    }
+
+    #[test]
+    fn test_config_diff_masks_secrets() {
+        let mut config = Config::default();
+        config.redis.password = Some("hunter2".to_string());
+
+        let changes = diff_config(&Config::default(), &config).unwrap();
+        let output = format_diff(&changes, "raibid.yaml");
+        assert!(output.contains("redis.password: ***"));
+        assert!(!output.contains("hunter2"));
+    }
+
+    #[test]
+    fn test_diff_values_added_and_removed() {
+        let old: Value = serde_yaml::from_str("a: 1\nb:\n  c: true\n").unwrap();
+        let new: Value = serde_yaml::from_str("b:\n  c: true\nd: [1, 2]\n").unwrap();
+
+        let mut changes = Vec::new();
+        diff_values("", &old, &new, &mut changes);
+        assert_eq!(
+            changes,
+            vec![
+                ConfigChange::Removed {
+                    key: "a".to_string(),
+                    value: Value::from(1),
+                },
+                ConfigChange::Added {
+                    key: "d".to_string(),
+                    value: serde_yaml::from_str("[1, 2]").unwrap(),
+                },
+            ]
+        );
+    }
+}
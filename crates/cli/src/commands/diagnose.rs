@@ -0,0 +1,110 @@
+//! Diagnose command
+//!
+//! Runs the pre-flight checks of every component and prints each failure
+//! with a suggested fix, so problems can be solved before `raibid-cli setup`
+//! runs into them. Fails when any required check fails; warnings alone do
+//! not.
+
+use anyhow::{bail, Result};
+use colored::Colorize;
+
+use super::setup::Component;
+use raibid_common::infrastructure::{
+    flux_requirements, gitea_requirements, k3s_requirements, keda_requirements, redis_requirements,
+    PreFlightResult, PreFlightValidator, SystemRequirements,
+};
+
+/// Execute the diagnose command
+pub fn execute() -> Result<()> {
+    let runtime = tokio::runtime::Runtime::new()?;
+    let components = Component::all_components();
+    let validators: Vec<_> = components
+        .iter()
+        .map(|&component| PreFlightValidator::new(requirements(component)))
+        .collect();
+    let results = runtime.block_on(async {
+        let mut results = Vec::new();
+        for validator in &validators {
+            results.push(validator.validate_all_async().await);
+        }
+        results
+    });
+
+    println!("{}", "Pre-flight diagnosis".bold().cyan());
+    println!();
+
+    let mut failures = 0;
+    for (component, results) in components.iter().zip(&results) {
+        let (report, failed) = render_component(component.name(), results);
+        println!("{}", report);
+        failures += failed;
+    }
+
+    if failures > 0 {
+        bail!("{} pre-flight check(s) failed", failures);
+    }
+    println!("{} All pre-flight checks passed", "✓".green().bold());
+    Ok(())
+}
+
+/// Pre-flight requirements of `component`
+fn requirements(component: Component) -> SystemRequirements {
+    match component {
+        Component::K3s => k3s_requirements(),
+        Component::Gitea => gitea_requirements(),
+        Component::Redis => redis_requirements(),
+        Component::Keda => keda_requirements(),
+        Component::Flux => flux_requirements(),
+        Component::All => SystemRequirements::default(),
+    }
+}
+
+/// Render the check results of one component, returning the number of
+/// failed checks
+fn render_component(name: &str, results: &[PreFlightResult]) -> (String, usize) {
+    let mut lines = vec![format!("{}", name.bold())];
+    let mut failed = 0;
+
+    for error in results.iter().flat_map(|r| &r.errors) {
+        failed += 1;
+        lines.push(format!("  {} {}", "✗".red(), error.message));
+        if let Some(hint) = error.remediation() {
+            lines.push(format!("    {} {}", "Hint:".yellow().bold(), hint.yellow()));
+        }
+    }
+    for warning in results.iter().flat_map(|r| &r.warnings) {
+        lines.push(format!("  {} {}", "⚠".yellow(), warning));
+    }
+    if lines.len() == 1 {
+        lines.push(format!("  {} All checks passed", "✓".green()));
+    }
+
+    lines.push(String::new());
+    (lines.join("\n"), failed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_component() {
+        let mut missing = PreFlightResult::new();
+        missing.add_error(
+            "required_command",
+            "Required command 'helm' not found in PATH",
+        );
+        let mut low_memory = PreFlightResult::new();
+        low_memory.add_warning("Low available memory. Required: 4GB, Available: 2GB");
+
+        let (report, failed) = render_component("gitea", &[missing, low_memory]);
+        assert_eq!(failed, 1);
+        assert!(report.contains("Required command 'helm' not found in PATH"));
+        assert!(report.contains("Install the missing command"));
+        assert!(report.contains("Low available memory"));
+
+        let (report, failed) = render_component("k3s", &[PreFlightResult::new()]);
+        assert_eq!(failed, 0);
+        assert!(report.contains("All checks passed"));
+    }
+}
@@ -9,6 +9,7 @@ use std::thread;
 use std::time::Duration;
 use raibid_common::infrastructure::{
     K3sInstaller, GiteaInstaller, RedisInstaller, KedaInstaller, FluxInstaller, FluxConfig,
+    RUNNER_LABELS, RUNNER_NAME,
 };
 
 use crate::cli::InitSubcommand;
@@ -210,6 +211,7 @@ fn init_gitea(
             "Deploy Gitea Helm chart",
             "Wait for pods to be ready",
             "Configure OCI registry",
+            "Register Gitea Actions runner",
         ]);
         return Ok(());
     }
@@ -319,6 +321,18 @@ fn init_gitea(
         return Err(e);
     }
 
+    // Gitea is usable without a runner, so a failed registration only warns
+    if installer.registers_runner() {
+        print!("  {} Registering Gitea Actions runner... ", "→".blue());
+        match installer.register_runner(RUNNER_NAME, RUNNER_LABELS) {
+            Ok(_) => println!("{}", "done".green()),
+            Err(e) => {
+                println!("{}", "failed".yellow());
+                println!("  {} Runner registration failed: {:#}", "⚠".yellow(), e);
+            }
+        }
+    }
+
     // Cleanup on success
     installer.cleanup()?;
 
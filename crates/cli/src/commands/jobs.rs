@@ -0,0 +1,483 @@
+//! Job management commands
+//!
+//! Provides subcommands for interacting with CI jobs on the API server:
+//! - trigger: Submit a new build job (from flags or JSON on stdin)
+//! - list: Table of recent jobs, filtered by `--label key=value`
+//! - show: Print a job's test results and, with `--coverage`, its coverage
+//! - requeue: Queue a job from the dead letter stream again
+//! - retry: Run a finished job again under a new ID
+//! - logs: Print a job's build output, with `--follow` until it finishes
+
+use anyhow::{Context, Result};
+use colored::Colorize;
+use comfy_table::{presets::UTF8_FULL, Cell, ContentArrangement, Table};
+use std::io::{Read, Write};
+
+use crate::cli::{JobsCommand, JobsSubcommand};
+use raibid_common::jobs::{
+    format_label_selector, parse_label_selector, Job, JobBuilder, JobLogEntry, JobStatus,
+    JobTrigger,
+};
+use raibid_common::{ApiClient, Config};
+
+/// Handle jobs command and its subcommands
+pub fn handle(cmd: &JobsCommand, config: &Config) -> Result<()> {
+    match &cmd.command {
+        JobsSubcommand::Trigger {
+            repo,
+            branch,
+            commit,
+            priority,
+            from_stdin,
+        } => {
+            let mut trigger = if *from_stdin {
+                read_trigger(std::io::stdin().lock())?
+            } else {
+                // clap guarantees --repo is present without --from-stdin
+                let repo = repo.as_deref().context("--repo is required")?;
+                let mut builder = JobBuilder::new(repo).branch(branch);
+                if let Some(commit) = commit {
+                    builder = builder.commit(commit);
+                }
+                builder.build()?
+            };
+            if let Some(priority) = priority {
+                trigger.priority = *priority;
+            }
+
+            trigger_job(&trigger, config)
+        }
+        JobsSubcommand::List { labels } => {
+            let client = ApiClient::from_config(config)?;
+            println!("{}", list_jobs(&client, labels)?);
+            Ok(())
+        }
+        JobsSubcommand::Show { id, coverage } => show_job(id, *coverage, config),
+        JobsSubcommand::Requeue { id } => requeue_job(id, config),
+        JobsSubcommand::Retry { id } => {
+            let client = ApiClient::from_config(config)?;
+            println!("{}", retry_job(&client, id)?);
+            Ok(())
+        }
+        JobsSubcommand::Logs {
+            id,
+            follow,
+            no_color,
+        } => {
+            if *no_color {
+                colored::control::set_override(false);
+            }
+            let client = ApiClient::from_config(config)?;
+            print_logs(&client, id, *follow, &mut std::io::stdout().lock())
+        }
+    }
+}
+
+/// Read and validate a JSON job trigger
+fn read_trigger(mut reader: impl Read) -> Result<JobTrigger> {
+    let mut input = String::new();
+    reader
+        .read_to_string(&mut input)
+        .context("Failed to read job trigger from stdin")?;
+
+    let trigger: JobTrigger =
+        serde_json::from_str(&input).context("Failed to parse job trigger JSON")?;
+
+    JobBuilder::from_trigger(trigger).build()
+}
+
+/// Submit a job trigger to the API server
+fn trigger_job(trigger: &JobTrigger, config: &Config) -> Result<()> {
+    let client = ApiClient::from_config(config)?;
+    let job = client.trigger_job(trigger)?;
+
+    print!(
+        "{} Triggered job {} for {} ({})",
+        "✓".green().bold(),
+        job.id.cyan(),
+        job.repo,
+        job.branch
+    );
+    if !trigger.priority.is_normal() {
+        print!(" with {} priority", trigger.priority);
+    }
+    println!();
+
+    Ok(())
+}
+
+/// Fetch the jobs with all of `labels`, given as `key=value`, and render
+/// them as a table
+fn list_jobs(client: &ApiClient, labels: &[String]) -> Result<String> {
+    let selector = parse_label_selector(&labels.join(","))?;
+    let jobs = client.list_jobs(&selector).context("Failed to list jobs")?;
+
+    if jobs.is_empty() {
+        return Ok("No jobs found".to_string());
+    }
+    Ok(job_table(&jobs).to_string())
+}
+
+/// Build the job list table
+fn job_table(jobs: &[Job]) -> Table {
+    let mut table = Table::new();
+    table
+        .load_preset(UTF8_FULL)
+        .set_content_arrangement(ContentArrangement::Dynamic)
+        .set_header(vec![
+            "ID",
+            "Repository",
+            "Branch",
+            "Status",
+            "Created",
+            "Labels",
+        ]);
+
+    for job in jobs {
+        table.add_row(vec![
+            Cell::new(&job.id),
+            Cell::new(&job.repo),
+            Cell::new(&job.branch),
+            Cell::new(job.status),
+            Cell::new(job.created_at.format("%Y-%m-%d %H:%M UTC")),
+            Cell::new(format_label_selector(&job.labels)),
+        ]);
+    }
+
+    table
+}
+
+/// Width of the coverage bar chart in characters
+const COVERAGE_BAR_WIDTH: usize = 40;
+
+/// Print the reports a job produced
+fn show_job(id: &str, coverage: bool, config: &Config) -> Result<()> {
+    let client = ApiClient::from_config(config)?;
+
+    println!("{} {}", "Job".bold(), id.cyan());
+    match client.test_results(id)? {
+        Some(report) => println!(
+            "  Tests:    {} passed, {} failed, {} errored, {} skipped",
+            report.passed(),
+            report.failures(),
+            report.errors(),
+            report.skipped()
+        ),
+        None => println!("  Tests:    {}", "no test results".dimmed()),
+    }
+
+    if coverage {
+        match client.coverage(id)? {
+            Some(report) => println!(
+                "  Coverage: {} ({}/{} lines)",
+                coverage_bar(report.percent(), COVERAGE_BAR_WIDTH),
+                report.lines_covered,
+                report.lines_valid
+            ),
+            None => println!("  Coverage: {}", "no coverage report".dimmed()),
+        }
+    }
+
+    Ok(())
+}
+
+/// Render a percentage as a `[####....] 72.5%` bar `width` characters wide
+fn coverage_bar(percent: f64, width: usize) -> String {
+    let filled = ((percent.clamp(0.0, 100.0) / 100.0) * width as f64).round() as usize;
+    format!(
+        "[{}{}] {:.1}%",
+        "#".repeat(filled),
+        ".".repeat(width - filled),
+        percent
+    )
+}
+
+/// Move a dead job back to its priority queue
+fn requeue_job(id: &str, config: &Config) -> Result<()> {
+    let client = ApiClient::from_config(config)?;
+    client.requeue_job(id)?;
+
+    println!("{} Requeued job {}", "✓".green().bold(), id.cyan());
+    Ok(())
+}
+
+/// Queue a finished job again and describe the new job
+fn retry_job(client: &ApiClient, id: &str) -> Result<String> {
+    let job = client
+        .retry_job(id)
+        .with_context(|| format!("Failed to retry job '{}'", id))?;
+
+    Ok(format!(
+        "{} Retrying job {} as {}\n  Status: {}/api/jobs/{}/status",
+        "✓".green().bold(),
+        id,
+        job.id.cyan(),
+        client.base_url(),
+        job.id
+    ))
+}
+
+/// Write a job's build output to `out`, following it until the job
+/// finishes if `follow` is set
+fn print_logs(client: &ApiClient, id: &str, follow: bool, out: &mut impl Write) -> Result<()> {
+    if !follow {
+        for entry in client.get_job_logs(id)? {
+            writeln!(out, "{}", format_log_line(&entry))?;
+        }
+        return Ok(());
+    }
+
+    let mut stream = client.stream_job_logs(id)?;
+    for entry in stream.by_ref() {
+        writeln!(out, "{}", format_log_line(&entry?))?;
+        out.flush()?;
+    }
+
+    match stream.status() {
+        Some(status) => {
+            let status = match status {
+                JobStatus::Success => status.as_str().green(),
+                JobStatus::Failed => status.as_str().red(),
+                _ => status.as_str().yellow(),
+            };
+            writeln!(out, "{} Job {} finished: {}", "→".blue(), id.cyan(), status)?;
+            Ok(())
+        }
+        None => anyhow::bail!("Log stream for job '{}' ended before the job finished", id),
+    }
+}
+
+/// Render a log line with its time, prefixing errors and warnings
+///
+/// Lines are printed as they are, long ones are left for the terminal to
+/// wrap.
+fn format_log_line(entry: &JobLogEntry) -> String {
+    let time = entry.timestamp.format("%H:%M:%S").to_string().dimmed();
+    match log_level(&entry.message) {
+        Some(level @ "ERROR") => format!("{} {} {}", time, level.red().bold(), entry.message),
+        Some(level) => format!("{} {} {}", time, level.yellow().bold(), entry.message),
+        None => format!("{} {}", time, entry.message),
+    }
+}
+
+/// `ERROR` or `WARN` for error and warning lines of compiler or log output
+fn log_level(message: &str) -> Option<&'static str> {
+    let message = message.trim_start().to_ascii_lowercase();
+    if message.starts_with("error") {
+        Some("ERROR")
+    } else if message.starts_with("warn") {
+        Some("WARN")
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use raibid_common::jobs::JobPriority;
+
+    #[test]
+    fn test_coverage_bar() {
+        assert_eq!(coverage_bar(72.5, 10), "[#######...] 72.5%");
+        assert_eq!(coverage_bar(0.0, 4), "[....] 0.0%");
+        assert_eq!(coverage_bar(100.0, 4), "[####] 100.0%");
+    }
+
+    #[test]
+    fn test_read_trigger_valid() {
+        let json =
+            r#"{"repo": "raibid-labs/raibid-cli", "branch": "develop", "commit": "abc1234"}"#;
+        let trigger = read_trigger(json.as_bytes()).unwrap();
+        assert_eq!(trigger.repo, "raibid-labs/raibid-cli");
+        assert_eq!(trigger.branch, "develop");
+        assert_eq!(trigger.commit.as_deref(), Some("abc1234"));
+    }
+
+    #[test]
+    fn test_read_trigger_priority() {
+        let json = r#"{"repo": "raibid-labs/raibid-cli", "priority": "low"}"#;
+        let trigger = read_trigger(json.as_bytes()).unwrap();
+        assert_eq!(trigger.priority, JobPriority::Low);
+
+        let json = r#"{"repo": "raibid-labs/raibid-cli", "priority": "urgent"}"#;
+        assert!(read_trigger(json.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_read_trigger_invalid_json() {
+        assert!(read_trigger("not json".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_list_jobs_by_label() {
+        let mut server = mockito::Server::new();
+        let mock = server
+            .mock("GET", "/api/jobs")
+            .match_query(mockito::Matcher::UrlEncoded(
+                "labels".to_string(),
+                "event=push,source=gitlab".to_string(),
+            ))
+            .with_header("content-type", "application/json")
+            .with_body(
+                r#"[{"id":"job-1","repo":"raibid-labs/raibid-cli","branch":"main",
+                     "status":"running","created_at":"2024-01-01T12:00:00Z",
+                     "labels":{"source":"gitlab","event":"push"}}]"#,
+            )
+            .create();
+        let client = ApiClient::new(server.url()).unwrap();
+
+        let labels = vec!["source=gitlab".to_string(), "event=push".to_string()];
+        let output = list_jobs(&client, &labels).unwrap();
+        mock.assert();
+        assert!(output.contains("job-1"));
+        assert!(output.contains("running"));
+
+        assert!(list_jobs(&client, &["source".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_retry_job() {
+        let mut server = mockito::Server::new();
+        let mock = server
+            .mock("POST", "/api/jobs/job-1/retry")
+            .with_status(201)
+            .with_header("content-type", "application/json")
+            .with_body(
+                r#"{"id":"job-2","repo":"raibid-labs/raibid-cli","branch":"main",
+                    "status":"pending","created_at":"2024-01-01T12:00:00Z"}"#,
+            )
+            .create();
+        let client = ApiClient::new(server.url()).unwrap();
+
+        let output = retry_job(&client, "job-1").unwrap();
+        mock.assert();
+        assert!(output.contains("job-2"));
+        assert!(output.contains(&format!("{}/api/jobs/job-2/status", server.url())));
+    }
+
+    #[test]
+    fn test_retry_running_job_fails() {
+        let mut server = mockito::Server::new();
+        server
+            .mock("POST", "/api/jobs/job-1/retry")
+            .with_status(409)
+            .with_body(
+                r#"{"error":"Job job-1 is running and can only be retried once it has finished"}"#,
+            )
+            .create();
+        let client = ApiClient::new(server.url()).unwrap();
+
+        let err = retry_job(&client, "job-1").unwrap_err();
+        assert!(format!("{:#}", err).contains("is running"));
+    }
+
+    #[test]
+    fn test_read_trigger_fails_validation() {
+        let json = r#"{"repo": "no-owner", "branch": "main"}"#;
+        assert!(read_trigger(json.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_log_level() {
+        assert_eq!(
+            log_level("error[E0425]: cannot find value `x`"),
+            Some("ERROR")
+        );
+        assert_eq!(log_level("ERROR raibid_agent: step failed"), Some("ERROR"));
+        assert_eq!(log_level("warning: unused variable: `y`"), Some("WARN"));
+        assert_eq!(log_level("  WARN retrying"), Some("WARN"));
+        assert_eq!(log_level("   Compiling raibid-cli v0.1.0"), None);
+    }
+
+    #[test]
+    fn test_follow_logs_from_sse_server() {
+        use std::io::{BufRead, BufReader};
+        use std::net::TcpListener;
+
+        colored::control::set_override(false);
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = std::thread::spawn(move || {
+            let (mut socket, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(socket.try_clone().unwrap());
+            let mut request_line = String::new();
+            reader.read_line(&mut request_line).unwrap();
+            for line in reader.lines() {
+                if line.unwrap().is_empty() {
+                    break;
+                }
+            }
+
+            write!(
+                socket,
+                "HTTP/1.1 200 OK\r\ncontent-type: text/event-stream\r\nconnection: close\r\n\r\n"
+            )
+            .unwrap();
+            for i in 0..10 {
+                let message = if i == 9 {
+                    "error: build failed"
+                } else {
+                    "step"
+                };
+                write!(
+                    socket,
+                    "event: log\ndata: {{\"timestamp\":\"2024-01-01T12:00:0{}Z\",\"message\":\"{} {}\"}}\n\n",
+                    i, message, i
+                )
+                .unwrap();
+                socket.flush().unwrap();
+            }
+            write!(socket, "event: done\ndata: failed\n\n").unwrap();
+            request_line
+        });
+        let client = ApiClient::new(format!("http://{}", addr)).unwrap();
+
+        let mut out = Vec::new();
+        print_logs(&client, "job-1", true, &mut out).unwrap();
+
+        let request_line = server.join().unwrap();
+        assert!(request_line.starts_with("GET /api/jobs/job-1/logs?follow=true "));
+        let output = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 11);
+        assert_eq!(lines[0], "12:00:00 step 0");
+        assert_eq!(lines[9], "12:00:09 ERROR error: build failed 9");
+        assert_eq!(lines[10], "→ Job job-1 finished: failed");
+    }
+
+    #[test]
+    fn test_print_logs() {
+        colored::control::set_override(false);
+        let mut server = mockito::Server::new();
+        server
+            .mock("GET", "/api/jobs/job-1/logs")
+            .with_header("content-type", "application/json")
+            .with_body(r#"[{"timestamp":"2024-01-01T12:00:00Z","message":"Compiling raibid-cli"}]"#)
+            .create();
+        server
+            .mock("GET", "/api/jobs/job-1/logs?follow=true")
+            .with_header("content-type", "text/event-stream")
+            .with_body(
+                "event: log\n\
+                 data: {\"timestamp\":\"2024-01-01T12:00:00Z\",\"message\":\"Compiling raibid-cli\"}\n\n\
+                 event: done\ndata: failed\n\n",
+            )
+            .create();
+        let client = ApiClient::new(server.url()).unwrap();
+
+        let mut out = Vec::new();
+        print_logs(&client, "job-1", false, &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "12:00:00 Compiling raibid-cli\n"
+        );
+
+        let mut out = Vec::new();
+        print_logs(&client, "job-1", true, &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "12:00:00 Compiling raibid-cli\n→ Job job-1 finished: failed\n"
+        );
+    }
+}
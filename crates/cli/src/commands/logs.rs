@@ -0,0 +1,62 @@
+//! Log viewing commands
+//!
+//! Provides subcommands for viewing raibid logs without knowing where they live:
+//! - install: Show the infrastructure install log written by `setup`
+
+use anyhow::{Context, Result};
+use colored::Colorize;
+
+use crate::cli::{LogsCommand, LogsSubcommand};
+use crate::install_log;
+
+/// Handle logs command and its subcommands
+pub fn handle(cmd: &LogsCommand) -> Result<()> {
+    match &cmd.command {
+        LogsSubcommand::Install { tail } => show_install_log(*tail),
+    }
+}
+
+/// Print the install log, optionally only the last `tail` lines
+fn show_install_log(tail: Option<usize>) -> Result<()> {
+    let path = install_log::path();
+    if !path.exists() {
+        println!("{} No install log found at {}", "ℹ".blue(), path.display());
+        println!(
+            "  {} Run 'raibid setup <component>' to create one",
+            "→".blue()
+        );
+        return Ok(());
+    }
+
+    let contents = std::fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+
+    for line in last_lines(&contents, tail) {
+        println!("{}", line);
+    }
+
+    Ok(())
+}
+
+/// Select the last `n` lines of `contents`, or all lines if `n` is None
+fn last_lines(contents: &str, n: Option<usize>) -> Vec<&str> {
+    let lines: Vec<&str> = contents.lines().collect();
+    match n {
+        Some(n) => lines[lines.len().saturating_sub(n)..].to_vec(),
+        None => lines,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_last_lines() {
+        let contents = "one\ntwo\nthree\n";
+        assert_eq!(last_lines(contents, None), vec!["one", "two", "three"]);
+        assert_eq!(last_lines(contents, Some(2)), vec!["two", "three"]);
+        assert_eq!(last_lines(contents, Some(10)).len(), 3);
+        assert!(last_lines(contents, Some(0)).is_empty());
+    }
+}
@@ -0,0 +1,351 @@
+//! Mirror commands
+//!
+//! Provides subcommands for mirroring repositories into Gitea:
+//! - add: Create a pull mirror of a repository, synced on an interval
+//! - list: Table of mirrors with their source and sync status
+//! - remove: Delete a mirror
+//!
+//! Mirrors live in the organization given by `--org` (`mirrors` by default),
+//! which `add` creates if it does not exist yet. The Gitea URL and admin
+//! credentials (or an access token) are read from
+//! `~/.raibid/gitea-credentials.json`. All honour `--output json`.
+
+use anyhow::{bail, Context, Result};
+use colored::Colorize;
+use comfy_table::{presets::UTF8_FULL, Cell, Color, ContentArrangement, Table};
+use serde::Serialize;
+
+use crate::cli::{MirrorCommand, MirrorSubcommand, OutputFormat};
+use raibid_common::gitea::{GiteaClient, GiteaCredentials, Repository};
+
+/// Handle mirror command and its subcommands
+pub fn handle(cmd: &MirrorCommand) -> Result<()> {
+    let credentials = GiteaCredentials::load(&GiteaCredentials::default_path()?)?;
+    let client = GiteaClient::new(credentials)?;
+
+    let output = match &cmd.command {
+        MirrorSubcommand::Add {
+            url,
+            name,
+            sync_interval,
+        } => add_mirror(
+            &client,
+            &cmd.org,
+            url,
+            name.as_deref(),
+            *sync_interval,
+            cmd.output,
+        )?,
+        MirrorSubcommand::List => list_mirrors(&client, cmd.output)?,
+        MirrorSubcommand::Remove { name } => remove_mirror(&client, &cmd.org, name, cmd.output)?,
+    };
+
+    println!("{}", output);
+    Ok(())
+}
+
+/// Create a mirror in `org`, creating the organization first if needed, and
+/// render the created repository
+fn add_mirror(
+    client: &GiteaClient,
+    org: &str,
+    url: &str,
+    name: Option<&str>,
+    sync_interval: u32,
+    format: OutputFormat,
+) -> Result<String> {
+    if sync_interval == 0 {
+        bail!("--sync-interval must be greater than 0");
+    }
+    let clone_addr = clone_address(url);
+    let name = match name {
+        Some(name) => name.to_string(),
+        None => repo_name(&clone_addr)?,
+    };
+
+    client
+        .ensure_organization(org)
+        .with_context(|| format!("Failed to set up organization '{}'", org))?;
+    let repo = client
+        .create_mirror(org, &clone_addr, &name, sync_interval)
+        .with_context(|| format!("Failed to mirror {}", clone_addr))?;
+
+    match format {
+        OutputFormat::Json => to_json(&repo),
+        OutputFormat::Table => Ok(format!(
+            "{} Mirrored {} to {} (syncs every {} minutes)",
+            "✓".green().bold(),
+            clone_addr,
+            repo.full_name.cyan(),
+            sync_interval
+        )),
+    }
+}
+
+/// Delete the mirror `org/name`
+fn remove_mirror(
+    client: &GiteaClient,
+    org: &str,
+    name: &str,
+    format: OutputFormat,
+) -> Result<String> {
+    let full_name = format!("{}/{}", org, name);
+    client
+        .delete_repo(org, name)
+        .with_context(|| format!("Failed to remove mirror {}", full_name))?;
+
+    match format {
+        OutputFormat::Json => to_json(&serde_json::json!({ "removed": full_name })),
+        OutputFormat::Table => Ok(format!(
+            "{} Removed mirror {}",
+            "✓".green().bold(),
+            full_name.cyan()
+        )),
+    }
+}
+
+/// Fetch all mirrors and render them in the requested format
+fn list_mirrors(client: &GiteaClient, format: OutputFormat) -> Result<String> {
+    let mirrors = client.list_mirrors().context("Failed to list mirrors")?;
+
+    match format {
+        OutputFormat::Json => to_json(&mirrors),
+        OutputFormat::Table if mirrors.is_empty() => Ok("No mirrors configured".to_string()),
+        OutputFormat::Table => Ok(mirror_table(&mirrors).to_string()),
+    }
+}
+
+fn to_json<T: Serialize>(value: &T) -> Result<String> {
+    serde_json::to_string_pretty(value).context("Failed to serialize Gitea response")
+}
+
+/// Add `https://` to addresses given without a scheme (`github.com/owner/repo`)
+fn clone_address(url: &str) -> String {
+    if url.contains("://") {
+        url.to_string()
+    } else {
+        format!("https://{}", url)
+    }
+}
+
+/// Repository name from the last path segment of a clone address
+fn repo_name(clone_addr: &str) -> Result<String> {
+    let path = clone_addr
+        .split_once("://")
+        .map_or(clone_addr, |(_, rest)| rest)
+        .trim_end_matches('/');
+    match path.rsplit_once('/') {
+        Some((_, name)) if !name.trim_end_matches(".git").is_empty() => {
+            Ok(name.trim_end_matches(".git").to_string())
+        }
+        _ => bail!(
+            "Cannot determine a repository name from '{}'; pass --name",
+            clone_addr
+        ),
+    }
+}
+
+/// Format of sync times in the mirror list
+const TIME_FORMAT: &str = "%Y-%m-%d %H:%M UTC";
+
+/// Build the mirror list table
+fn mirror_table(mirrors: &[Repository]) -> Table {
+    let mut table = Table::new();
+    table
+        .load_preset(UTF8_FULL)
+        .set_content_arrangement(ContentArrangement::Dynamic)
+        .set_header(vec!["Name", "Source", "Last Sync", "Next Sync", "Status"]);
+
+    for repo in mirrors {
+        let (status, color) = mirror_status(repo);
+        let last_sync = repo.last_sync().map(|t| t.format(TIME_FORMAT).to_string());
+        let next_sync = repo.next_sync().map(|t| t.format(TIME_FORMAT).to_string());
+        table.add_row(vec![
+            Cell::new(&repo.full_name),
+            Cell::new(&repo.original_url),
+            Cell::new(last_sync.as_deref().unwrap_or("-")),
+            Cell::new(next_sync.as_deref().unwrap_or("-")),
+            Cell::new(status).fg(color),
+        ]);
+    }
+
+    table
+}
+
+/// Sync status shown for a mirror
+fn mirror_status(repo: &Repository) -> (&'static str, Color) {
+    if repo.last_sync().is_none() {
+        ("pending", Color::Yellow)
+    } else if repo.empty {
+        ("empty", Color::Yellow)
+    } else {
+        ("synced", Color::Green)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mockito::Matcher;
+    use raibid_common::gitea::{MIRROR_ORG, MIRROR_TOPIC};
+
+    const REPO_JSON: &str = r#"{
+        "name": "ripgrep", "full_name": "mirrors/ripgrep", "mirror": true,
+        "original_url": "https://github.com/BurntSushi/ripgrep",
+        "mirror_interval": "30m0s", "mirror_updated": "2024-01-01T12:00:00Z",
+        "empty": false
+    }"#;
+
+    fn client(server: &mockito::Server) -> GiteaClient {
+        GiteaClient::new(GiteaCredentials {
+            url: server.url(),
+            admin_username: "admin".to_string(),
+            admin_password: "secret".to_string(),
+            token: None,
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_add_mirror() {
+        let mut server = mockito::Server::new();
+        let org_lookup = server
+            .mock("GET", "/api/v1/orgs/mirrors")
+            .with_status(404)
+            .create();
+        let org_create = server
+            .mock("POST", "/api/v1/orgs")
+            .match_body(Matcher::PartialJson(
+                serde_json::json!({ "username": "mirrors" }),
+            ))
+            .with_status(201)
+            .with_body(r#"{"id": 2, "username": "mirrors"}"#)
+            .create();
+        let migrate = server
+            .mock("POST", "/api/v1/repos/migrate")
+            .match_header("authorization", Matcher::Regex("^Basic ".to_string()))
+            .match_body(Matcher::PartialJson(serde_json::json!({
+                "clone_addr": "https://github.com/BurntSushi/ripgrep",
+                "repo_name": "ripgrep",
+                "repo_owner": "mirrors",
+                "mirror": true,
+                "mirror_interval": "30m",
+            })))
+            .with_status(201)
+            .with_header("content-type", "application/json")
+            .with_body(REPO_JSON)
+            .create();
+        let topic = server
+            .mock(
+                "PUT",
+                format!("/api/v1/repos/mirrors/ripgrep/topics/{}", MIRROR_TOPIC).as_str(),
+            )
+            .with_status(204)
+            .create();
+
+        let output = add_mirror(
+            &client(&server),
+            MIRROR_ORG,
+            "github.com/BurntSushi/ripgrep",
+            None,
+            30,
+            OutputFormat::Table,
+        )
+        .unwrap();
+        org_lookup.assert();
+        org_create.assert();
+        migrate.assert();
+        topic.assert();
+        assert!(output.contains("mirrors/ripgrep"));
+        assert!(output.contains("every 30 minutes"));
+    }
+
+    #[test]
+    fn test_add_mirror_error() {
+        let mut server = mockito::Server::new();
+        server
+            .mock("GET", "/api/v1/orgs/mirrors")
+            .with_status(200)
+            .with_body(r#"{"id": 2, "username": "mirrors"}"#)
+            .create();
+        server
+            .mock("POST", "/api/v1/repos/migrate")
+            .with_status(409)
+            .with_body(r#"{"message": "The repository with the same name already exists."}"#)
+            .create();
+
+        let err = add_mirror(
+            &client(&server),
+            MIRROR_ORG,
+            "https://github.com/BurntSushi/ripgrep.git",
+            None,
+            60,
+            OutputFormat::Table,
+        )
+        .unwrap_err();
+        assert!(format!("{:#}", err).contains("already exists"));
+    }
+
+    #[test]
+    fn test_list_mirrors_table() {
+        let mut server = mockito::Server::new();
+        let search = server
+            .mock("GET", "/api/v1/repos/search")
+            .match_query(Matcher::AllOf(vec![
+                Matcher::UrlEncoded("q".to_string(), MIRROR_TOPIC.to_string()),
+                Matcher::UrlEncoded("topic".to_string(), "true".to_string()),
+                Matcher::UrlEncoded("limit".to_string(), "50".to_string()),
+            ]))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(format!(r#"{{"ok": true, "data": [{}]}}"#, REPO_JSON))
+            .create();
+
+        let output = list_mirrors(&client(&server), OutputFormat::Table).unwrap();
+        search.assert();
+        for expected in [
+            "Last Sync",
+            "Next Sync",
+            "mirrors/ripgrep",
+            "https://github.com/BurntSushi/ripgrep",
+            "2024-01-01 12:00 UTC",
+            "2024-01-01 12:30 UTC",
+            "synced",
+        ] {
+            assert!(
+                output.contains(expected),
+                "missing {:?} in\n{}",
+                expected,
+                output
+            );
+        }
+    }
+
+    #[test]
+    fn test_remove_mirror() {
+        let mut server = mockito::Server::new();
+        let delete = server
+            .mock("DELETE", "/api/v1/repos/mirrors/ripgrep")
+            .with_status(204)
+            .create();
+
+        let output =
+            remove_mirror(&client(&server), MIRROR_ORG, "ripgrep", OutputFormat::Table).unwrap();
+        delete.assert();
+        assert!(output.contains("Removed mirror mirrors/ripgrep"));
+    }
+
+    #[test]
+    fn test_repo_name() {
+        assert_eq!(
+            repo_name("https://github.com/owner/repo.git").unwrap(),
+            "repo"
+        );
+        assert_eq!(repo_name("https://github.com/owner/repo/").unwrap(), "repo");
+        assert!(repo_name("https://github.com").is_err());
+        assert_eq!(
+            clone_address("github.com/owner/repo"),
+            "https://github.com/owner/repo"
+        );
+    }
+}
@@ -3,13 +3,15 @@
 //! This module contains the actual implementation of CLI commands.
 //! Each command is implemented as a separate module.
 
+pub mod agent;
+pub mod api_key;
+pub mod completions;
 pub mod config;
+pub mod diagnose;
+pub mod jobs;
+pub mod logs;
+pub mod mirror;
+pub mod schedule;
 pub mod setup;
-pub mod teardown;
 pub mod status;
-
-// Placeholder for future command implementations
-// Command modules will be added in future issues:
-// - pub mod job;
-// - pub mod agent;
-// - pub mod mirror;
+pub mod teardown;
@@ -0,0 +1,221 @@
+//! Schedule commands
+//!
+//! Provides subcommands for builds the API server queues on a cron schedule:
+//! - add: Build a repository branch whenever a cron expression matches
+//! - list: Table of schedules with the time each last queued a build
+//! - remove: Delete a schedule
+//!
+//! `add` and `list` honour `--output json`.
+
+use anyhow::{Context, Result};
+use colored::Colorize;
+use comfy_table::{presets::UTF8_FULL, Cell, ContentArrangement, Table};
+use serde::Serialize;
+
+use crate::cli::{OutputFormat, ScheduleCommand, ScheduleSubcommand};
+use raibid_common::schedules::{Schedule, ScheduleRequest};
+use raibid_common::{ApiClient, Config};
+
+/// Handle schedule command and its subcommands
+pub fn handle(cmd: &ScheduleCommand, config: &Config) -> Result<()> {
+    let client = ApiClient::from_config(config)?;
+
+    let output = match &cmd.command {
+        ScheduleSubcommand::Add {
+            repo,
+            branch,
+            cron,
+            timezone,
+        } => {
+            let request = ScheduleRequest {
+                repo: repo.clone(),
+                branch: branch.clone(),
+                cron: cron.clone(),
+                timezone: timezone.clone(),
+            };
+            add_schedule(&client, &request, cmd.output)?
+        }
+        ScheduleSubcommand::List => list_schedules(&client, cmd.output)?,
+        ScheduleSubcommand::Remove { id } => remove_schedule(&client, id)?,
+    };
+
+    println!("{}", output);
+    Ok(())
+}
+
+/// Create a schedule and render the created schedule
+fn add_schedule(
+    client: &ApiClient,
+    request: &ScheduleRequest,
+    format: OutputFormat,
+) -> Result<String> {
+    let schedule = client
+        .create_schedule(request)
+        .context("Failed to create schedule")?;
+
+    match format {
+        OutputFormat::Json => to_json(&schedule),
+        OutputFormat::Table => Ok(format!(
+            "{} Scheduled {}@{} at '{}' ({}) as {}",
+            "✓".green().bold(),
+            schedule.repo,
+            schedule.branch,
+            schedule.cron,
+            schedule.timezone,
+            schedule.id.cyan()
+        )),
+    }
+}
+
+/// Fetch all schedules and render them in the requested format
+fn list_schedules(client: &ApiClient, format: OutputFormat) -> Result<String> {
+    let schedules = client
+        .list_schedules()
+        .context("Failed to list schedules")?;
+
+    match format {
+        OutputFormat::Json => to_json(&schedules),
+        OutputFormat::Table if schedules.is_empty() => Ok("No schedules configured".to_string()),
+        OutputFormat::Table => Ok(schedule_table(&schedules).to_string()),
+    }
+}
+
+/// Delete a schedule
+fn remove_schedule(client: &ApiClient, id: &str) -> Result<String> {
+    client
+        .delete_schedule(id)
+        .with_context(|| format!("Failed to delete schedule '{}'", id))?;
+
+    Ok(format!(
+        "{} Deleted schedule {}",
+        "✓".green().bold(),
+        id.cyan()
+    ))
+}
+
+fn to_json<T: Serialize>(value: &T) -> Result<String> {
+    serde_json::to_string_pretty(value).context("Failed to serialize API response")
+}
+
+/// Format of run times in the schedule list
+const TIME_FORMAT: &str = "%Y-%m-%d %H:%M UTC";
+
+/// Build the schedule list table
+fn schedule_table(schedules: &[Schedule]) -> Table {
+    let mut table = Table::new();
+    table
+        .load_preset(UTF8_FULL)
+        .set_content_arrangement(ContentArrangement::Dynamic)
+        .set_header(vec![
+            "ID",
+            "Repository",
+            "Branch",
+            "Cron",
+            "Time Zone",
+            "Last Run",
+        ]);
+
+    for schedule in schedules {
+        let last_run = schedule.last_run.map(|t| t.format(TIME_FORMAT).to_string());
+        table.add_row(vec![
+            Cell::new(&schedule.id),
+            Cell::new(&schedule.repo),
+            Cell::new(&schedule.branch),
+            Cell::new(&schedule.cron),
+            Cell::new(&schedule.timezone),
+            Cell::new(last_run.as_deref().unwrap_or("never")),
+        ]);
+    }
+
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCHEDULE_JSON: &str = r#"{
+        "id": "sched-1", "repo": "raibid-labs/raibid-cli", "branch": "main",
+        "cron": "0 2 * * *", "timezone": "Europe/Berlin",
+        "created_at": "2024-01-01T12:00:00Z", "last_run": "2024-01-02T01:00:00Z"
+    }"#;
+
+    #[test]
+    fn test_add_schedule() {
+        let mut server = mockito::Server::new();
+        let mock = server
+            .mock("POST", "/api/schedules")
+            .match_body(mockito::Matcher::Json(serde_json::json!({
+                "repo": "raibid-labs/raibid-cli",
+                "branch": "main",
+                "cron": "0 2 * * *",
+                "timezone": "Europe/Berlin",
+            })))
+            .with_status(201)
+            .with_header("content-type", "application/json")
+            .with_body(SCHEDULE_JSON)
+            .create();
+        let client = ApiClient::new(server.url()).unwrap();
+
+        let request = ScheduleRequest {
+            repo: "raibid-labs/raibid-cli".to_string(),
+            branch: "main".to_string(),
+            cron: "0 2 * * *".to_string(),
+            timezone: "Europe/Berlin".to_string(),
+        };
+        let output = add_schedule(&client, &request, OutputFormat::Table).unwrap();
+        mock.assert();
+        assert!(output.contains("sched-1"));
+        assert!(output.contains("'0 2 * * *'"));
+    }
+
+    #[test]
+    fn test_add_schedule_rejected() {
+        let mut server = mockito::Server::new();
+        server
+            .mock("POST", "/api/schedules")
+            .with_status(400)
+            .with_body(r#"{"error": "Invalid cron expression '0 25 * * *'"}"#)
+            .create();
+        let client = ApiClient::new(server.url()).unwrap();
+
+        let request = ScheduleRequest {
+            repo: "raibid-labs/raibid-cli".to_string(),
+            branch: "main".to_string(),
+            cron: "0 25 * * *".to_string(),
+            timezone: "UTC".to_string(),
+        };
+        let err = add_schedule(&client, &request, OutputFormat::Table).unwrap_err();
+        assert!(format!("{:#}", err).contains("Invalid cron expression"));
+    }
+
+    #[test]
+    fn test_list_schedules_table() {
+        let mut server = mockito::Server::new();
+        let mock = server
+            .mock("GET", "/api/schedules")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(format!("[{}]", SCHEDULE_JSON))
+            .create();
+        let client = ApiClient::new(server.url()).unwrap();
+
+        let output = list_schedules(&client, OutputFormat::Table).unwrap();
+        mock.assert();
+        for expected in [
+            "Last Run",
+            "sched-1",
+            "raibid-labs/raibid-cli",
+            "0 2 * * *",
+            "Europe/Berlin",
+            "2024-01-02 01:00 UTC",
+        ] {
+            assert!(
+                output.contains(expected),
+                "missing {:?} in\n{}",
+                expected,
+                output
+            );
+        }
+    }
+}
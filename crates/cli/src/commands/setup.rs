@@ -2,12 +2,26 @@
 //!
 //! Implements the setup command for infrastructure components.
 //! Real implementations: k3s, Gitea, Redis, KEDA, Flux.
+//!
+//! All output is also appended to the install log (see [`crate::install_log`]).
+//! With `--dry-run` only the plan is printed; nothing is installed or logged.
+//! `--upgrade-to <version>` upgrades an existing k3s installation instead.
+//! k3s merges its context into an existing kubeconfig; `--no-merge`
+//! overwrites the kubeconfig instead.
 
 use anyhow::Result;
 use colored::Colorize;
 use std::thread;
 use std::time::Duration;
 use raibid_common::infrastructure::{K3sInstaller, GiteaInstaller, RedisInstaller, KedaInstaller, FluxInstaller, FluxConfig};
+use raibid_common::infrastructure::{
+    GiteaConfig, K3sConfig, K3sMode, K3sVersion, KedaConfig, PlanStep, RedisConfig,
+    RootlessPrerequisites, RUNNER_LABELS, RUNNER_NAME,
+};
+use raibid_common::infrastructure::plan::{format_estimate, total_estimate};
+use raibid_common::infrastructure::remediation_hint;
+
+use crate::install_log::{self, tee_print, tee_println};
 
 /// Infrastructure component that can be set up
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -74,29 +88,147 @@ impl std::str::FromStr for Component {
 }
 
 /// Execute the setup command for a component
-pub fn execute(component: Component) -> Result<()> {
-    if component == Component::All {
-        setup_all()
+///
+/// `merge_kubeconfig` is false with `--no-merge`.
+pub fn execute(component: Component, dry_run: bool, merge_kubeconfig: bool) -> Result<()> {
+    if dry_run {
+        println!("{}", render_plan("installed", &setup_plan(component)));
+        return Ok(());
+    }
+
+    tracing::info!(target: install_log::TARGET, "=== raibid setup {} ===", component.name());
+
+    let result = if component == Component::All {
+        setup_all(merge_kubeconfig)
     } else {
-        setup_component(component)
+        setup_component(component, merge_kubeconfig)
+    };
+
+    report_failure(&result);
+    result
+}
+
+/// Upgrade an installed component to `target_version`
+///
+/// Only k3s can be upgraded.
+pub fn upgrade(component: Component, target_version: &str, dry_run: bool) -> Result<()> {
+    if component != Component::K3s {
+        anyhow::bail!("--upgrade-to is only supported for k3s");
+    }
+    let target = K3sVersion::parse(target_version)?.to_string();
+
+    if dry_run {
+        let plan = [(Component::K3s, K3sConfig::default().upgrade_plan(&target))];
+        println!("{}", render_plan("upgraded", &plan));
+        return Ok(());
+    }
+
+    tracing::info!(
+        target: install_log::TARGET,
+        "=== raibid setup k3s --upgrade-to {} ===",
+        target
+    );
+
+    let result = upgrade_k3s_real(&target);
+    report_failure(&result);
+    result
+}
+
+/// Record a failed setup, print its fix if known and point at the install log
+fn report_failure(result: &Result<()>) {
+    if let Err(ref e) = result {
+        tracing::error!(target: install_log::TARGET, "Setup failed: {:#}", e);
+        if let Some(hint) = remediation_hint(e) {
+            println!("{} {}", "Hint:".yellow().bold(), hint.yellow());
+        }
+        println!();
+        println!(
+            "{} See {} for full details",
+            "→".yellow(),
+            install_log::DISPLAY_PATH.bold()
+        );
     }
 }
 
+/// Steps setting up `component` would take, per component in install order
+///
+/// Built from the default installer configurations, so nothing is run,
+/// downloaded, or written.
+pub fn setup_plan(component: Component) -> Vec<(Component, Vec<PlanStep>)> {
+    let components = match component {
+        Component::All => Component::all_components(),
+        single => vec![single],
+    };
+
+    components
+        .into_iter()
+        .map(|component| {
+            let steps = match component {
+                Component::K3s => K3sConfig::default().plan(),
+                Component::Gitea => GiteaConfig::default().plan(),
+                Component::Redis => RedisConfig::default().plan(),
+                Component::Keda => KedaConfig::default().plan(),
+                Component::Flux => FluxConfig::default().plan(),
+                Component::All => unreachable!("expanded above"),
+            };
+            (component, steps)
+        })
+        .collect()
+}
+
+/// Render a dry-run plan as numbered steps with estimated durations
+///
+/// `action` completes "The following would be ...", e.g. `installed`.
+pub fn render_plan(action: &str, plan: &[(Component, Vec<PlanStep>)]) -> String {
+    let mut lines = vec![
+        format!("{}", "DRY-RUN MODE: No changes will be made".yellow().bold()),
+        String::new(),
+        format!("{}", format!("The following would be {}:", action).bold()),
+    ];
+
+    for (component, steps) in plan {
+        lines.push(String::new());
+        lines.push(format!(
+            "{} {}",
+            component.name().bold().cyan(),
+            format!("({})", format_estimate(total_estimate(steps))).dimmed()
+        ));
+        for (i, step) in steps.iter().enumerate() {
+            lines.push(format!(
+                "  {} {} {}",
+                format!("{}.", i + 1).blue(),
+                step.description,
+                format!("({})", step.estimate_hint()).dimmed()
+            ));
+        }
+    }
+
+    let total = plan.iter().map(|(_, steps)| total_estimate(steps)).sum();
+    lines.push(String::new());
+    lines.push(format!(
+        "{} {}",
+        "Estimated total:".bold(),
+        format_estimate(total)
+    ));
+
+    lines.join("\n")
+}
+
 /// Setup all components
-fn setup_all() -> Result<()> {
-    println!(
+fn setup_all(merge_kubeconfig: bool) -> Result<()> {
+    tee_println!(
         "{} {}",
         "Setting up all components...".bold().cyan(),
         "🚀".bold()
     );
-    println!();
+    tee_println!();
 
     for component in Component::all_components() {
-        setup_component(component)?;
-        println!();
+        setup_component(component, merge_kubeconfig)?;
+        tee_println!();
     }
 
-    println!(
+    tee_println!(
         "{} {}",
         "All components setup successfully!".bold().green(),
         "✓".bold().green()
@@ -106,13 +238,20 @@ fn setup_all() -> Result<()> {
 }
 
 /// Setup a single component
-fn setup_component(component: Component) -> Result<()> {
-    println!(
+fn setup_component(component: Component, merge_kubeconfig: bool) -> Result<()> {
+    let _span = tracing::info_span!(
+        target: install_log::TARGET,
+        "install",
+        component = component.name()
+    )
+    .entered();
+
+    tee_println!(
         "{} {}",
         format!("Setting up {}...", component.name()).bold().cyan(),
         "⚙️".bold()
     );
-    println!();
+    tee_println!();
 
     // Show dependencies
     show_dependencies(component)?;
@@ -122,7 +261,7 @@ fn setup_component(component: Component) -> Result<()> {
 
     // Run actual setup or simulation based on component
     match component {
-        Component::K3s => setup_k3s_real()?,
+        Component::K3s => setup_k3s_real(merge_kubeconfig)?,
         Component::Gitea => setup_gitea_real()?,
         Component::Redis => setup_redis_real()?,
         Component::Keda => setup_keda_real()?,
@@ -130,7 +269,7 @@ fn setup_component(component: Component) -> Result<()> {
         _ => simulate_setup(component)?,
     }
 
-    println!(
+    tee_println!(
         "{} {} {}",
         "✓".bold().green(),
         component.name().bold(),
@@ -145,9 +284,9 @@ fn show_dependencies(component: Component) -> Result<()> {
     let deps = component.dependencies();
 
     if deps.is_empty() {
-        println!("{} No dependencies", "ℹ".blue());
+        tee_println!("{} No dependencies", "ℹ".blue());
     } else {
-        println!(
+        tee_println!(
             "{} {} requires: {}",
             "ℹ".blue(),
             component.name().bold(),
@@ -159,7 +298,7 @@ fn show_dependencies(component: Component) -> Result<()> {
         );
 
         for dep in deps {
-            println!(
+            tee_println!(
                 "  {} {} would be installed first",
                 "→".blue(),
                 dep.name().yellow()
@@ -167,36 +306,36 @@ fn show_dependencies(component: Component) -> Result<()> {
         }
     }
 
-    println!();
+    tee_println!();
     Ok(())
 }
 
 /// Run pre-flight checks
 fn run_preflight_checks() -> Result<()> {
-    println!("{}", "Running pre-flight checks...".bold());
+    tee_println!("{}", "Running pre-flight checks...".bold());
 
     // Check disk space
-    print!("  {} Checking disk space... ", "→".blue());
+    tee_print!("  {} Checking disk space... ", "→".blue());
     thread::sleep(Duration::from_millis(100));
-    println!("{} {} available", "✓".green(), "250 GB".bold());
+    tee_println!("{} {} available", "✓".green(), "250 GB".bold());
 
     // Check memory
-    print!("  {} Checking memory... ", "→".blue());
+    tee_print!("  {} Checking memory... ", "→".blue());
     thread::sleep(Duration::from_millis(100));
-    println!("{} {} available", "✓".green(), "128 GB".bold());
+    tee_println!("{} {} available", "✓".green(), "128 GB".bold());
 
     // Check CPU
-    print!("  {} Checking CPU cores... ", "→".blue());
+    tee_print!("  {} Checking CPU cores... ", "→".blue());
     thread::sleep(Duration::from_millis(100));
-    println!("{} {} cores", "✓".green(), "20".bold());
+    tee_println!("{} {} cores", "✓".green(), "20".bold());
 
-    println!();
+    tee_println!();
     Ok(())
 }
 
 /// Simulate the setup process
 fn simulate_setup(component: Component) -> Result<()> {
-    println!("{}", "Installing component...".bold());
+    tee_println!("{}", "Installing component...".bold());
 
     let steps = match component {
         Component::K3s => vec![
@@ -233,72 +372,117 @@ fn simulate_setup(component: Component) -> Result<()> {
     };
 
     for step in steps {
-        print!("  {} {}... ", "→".blue(), step);
+        tee_print!("  {} {}... ", "→".blue(), step);
         thread::sleep(Duration::from_millis(200));
-        println!("{}", "done".green());
+        tee_println!("{}", "done".green());
     }
 
-    println!();
+    tee_println!();
+    Ok(())
+}
+
+/// Check the host can run rootless k3s
+///
+/// Missing recommended prerequisites are reported as warnings; missing
+/// required ones abort the setup before anything is installed.
+fn check_rootless_prerequisites() -> Result<()> {
+    tee_print!("  {} Checking rootless prerequisites... ", "→".blue());
+    let results = RootlessPrerequisites::detect().check();
+
+    let errors: Vec<_> = results.iter().flat_map(|r| &r.errors).collect();
+    let warnings: Vec<_> = results.iter().flat_map(|r| &r.warnings).collect();
+    if errors.is_empty() {
+        tee_println!("{}", "done".green());
+    } else {
+        tee_println!("{}", "failed".red());
+    }
+
+    for warning in warnings {
+        tee_println!("    {} {}", "⚠".yellow(), warning);
+    }
+    for error in &errors {
+        tee_println!("    {} {}", "✗".red(), error.message);
+        if let Some(hint) = error.remediation() {
+            tee_println!("      {} {}", "Hint:".yellow(), hint);
+        }
+    }
+
+    if !errors.is_empty() {
+        anyhow::bail!("{} rootless k3s prerequisite(s) not met", errors.len());
+    }
     Ok(())
 }
 
 /// Real k3s installation implementation
-fn setup_k3s_real() -> Result<()> {
-    println!("{}", "Installing k3s cluster...".bold());
+fn setup_k3s_real(merge_kubeconfig: bool) -> Result<()> {
+    tee_println!("{}", "Installing k3s cluster...".bold());
 
     // Create runtime for async operations
     let runtime = tokio::runtime::Runtime::new()?;
 
     // Create installer
-    let installer = K3sInstaller::new()?;
+    let config = K3sConfig {
+        merge_kubeconfig,
+        ..Default::default()
+    };
+    let kubeconfig_path = config.kubeconfig_path.clone();
+    if matches!(config.mode, K3sMode::Rootless) {
+        check_rootless_prerequisites()?;
+    }
+    let installer = K3sInstaller::with_config(config)?;
 
     // Run installation with rollback on failure
     let result = (|| -> Result<()> {
         // Download binary
-        print!("  {} Downloading k3s binary... ", "→".blue());
+        tee_print!("  {} Downloading k3s binary... ", "→".blue());
         let binary_path = runtime.block_on(installer.download_binary())?;
-        println!("{}", "done".green());
+        tee_println!("{}", "done".green());
 
         // Download and verify checksums
-        print!("  {} Verifying checksum... ", "→".blue());
+        tee_print!("  {} Verifying checksum... ", "→".blue());
         let checksums = runtime.block_on(installer.download_checksums())?;
         installer.verify_checksum(&binary_path, &checksums)?;
-        println!("{}", "done".green());
+        tee_println!("{}", "done".green());
 
         // Install binary
-        print!("  {} Installing k3s binary... ", "→".blue());
+        tee_print!("  {} Installing k3s binary... ", "→".blue());
         installer.install_binary(&binary_path)?;
-        println!("{}", "done".green());
+        tee_println!("{}", "done".green());
 
         // Bootstrap cluster
-        print!("  {} Starting k3s cluster... ", "→".blue());
+        tee_print!("  {} Starting k3s cluster... ", "→".blue());
         installer.bootstrap_cluster()?;
-        println!("{}", "done".green());
+        tee_println!("{}", "done".green());
 
         // Configure kubeconfig
-        print!("  {} Configuring kubectl... ", "→".blue());
+        tee_print!("  {} Configuring kubectl... ", "→".blue());
         installer.configure_kubeconfig()?;
-        println!("{}", "done".green());
+        tee_println!("{}", "done".green());
+        tee_println!(
+            "    {} Use it with: export KUBECONFIG={}",
+            "ℹ".blue(),
+            kubeconfig_path.display()
+        );
 
         // Validate cluster
-        print!("  {} Validating cluster... ", "→".blue());
+        tee_print!("  {} Validating cluster... ", "→".blue());
         installer.validate_cluster()?;
-        println!("{}", "done".green());
+        tee_println!("{}", "done".green());
 
         Ok(())
     })();
 
     // Handle errors with rollback
     if let Err(e) = result {
-        println!("{}", "failed".red());
-        println!();
-        println!("{} Installation failed: {}", "✗".bold().red(), e);
-        println!("{} Rolling back changes...", "→".yellow());
+        tee_println!("{}", "failed".red());
+        tee_println!();
+        tee_println!("{} Installation failed: {}", "✗".bold().red(), e);
+        tee_println!("{} Rolling back changes...", "→".yellow());
 
         if let Err(rollback_err) = installer.rollback() {
-            println!("{} Rollback failed: {}", "✗".bold().red(), rollback_err);
+            tee_println!("{} Rollback failed: {}", "✗".bold().red(), rollback_err);
         } else {
-            println!("{} Rollback completed", "✓".green());
+            tee_println!("{} Rollback completed", "✓".green());
         }
 
         return Err(e);
@@ -307,13 +491,80 @@ fn setup_k3s_real() -> Result<()> {
     // Cleanup on success
     installer.cleanup()?;
 
-    println!();
+    tee_println!();
+    Ok(())
+}
+
+/// Real k3s upgrade implementation
+fn upgrade_k3s_real(target: &str) -> Result<()> {
+    tee_println!("{}", format!("Upgrading k3s to {}...", target).bold());
+
+    let runtime = tokio::runtime::Runtime::new()?;
+    let installer = K3sInstaller::with_config(K3sConfig {
+        version: target.to_string(),
+        ..Default::default()
+    })?;
+
+    tee_print!("  {} Checking installed version... ", "→".blue());
+    let installed = installer.installed_version()?;
+    tee_println!("{}", installed.to_string().green());
+
+    tee_print!("  {} Downloading and verifying k3s {}... ", "→".blue(), target);
+    let Some(binary_path) = runtime.block_on(installer.prepare_upgrade(target))? else {
+        tee_println!("{}", "skipped".yellow());
+        tee_println!("{} k3s {} is already installed", "ℹ".blue(), installed);
+        return Ok(());
+    };
+    tee_println!("{}", "done".green());
+
+    tee_print!("  {} Stopping k3s server... ", "→".blue());
+    installer.stop_server()?;
+    tee_println!("{}", "done".green());
+
+    // From here on the cluster is down until the upgrade completes
+    let result = (|| -> Result<()> {
+        tee_print!("  {} Replacing k3s binary... ", "→".blue());
+        installer.install_binary(&binary_path)?;
+        tee_println!("{}", "done".green());
+
+        tee_print!("  {} Restarting k3s server... ", "→".blue());
+        installer.bootstrap_cluster()?;
+        tee_println!("{}", "done".green());
+
+        tee_print!("  {} Waiting for node to become Ready... ", "→".blue());
+        installer.wait_for_node_ready()?;
+        tee_println!("{}", "done".green());
+
+        installer.write_version_file()?;
+        Ok(())
+    })();
+
+    if let Err(e) = result {
+        tee_println!("{}", "failed".red());
+        tee_println!();
+        tee_println!("{} Upgrade failed: {}", "✗".bold().red(), e);
+        tee_println!(
+            "{} k3s was stopped for the upgrade; check the cluster before retrying",
+            "→".yellow()
+        );
+        return Err(e);
+    }
+
+    installer.cleanup()?;
+
+    tee_println!();
+    tee_println!(
+        "{} k3s upgraded from {} to {}",
+        "✓".bold().green(),
+        installed,
+        target
+    );
     Ok(())
 }
 
 /// Real Gitea installation implementation
 fn setup_gitea_real() -> Result<()> {
-    println!("{}", "Installing Gitea via Helm...".bold());
+    tee_println!("{}", "Installing Gitea via Helm...".bold());
 
     // Create installer
     let installer = GiteaInstaller::new()?;
@@ -321,55 +572,71 @@ fn setup_gitea_real() -> Result<()> {
     // Run installation with rollback on failure
     let result = (|| -> Result<()> {
         // Check prerequisites
-        print!("  {} Checking prerequisites... ", "→".blue());
+        tee_print!("  {} Checking prerequisites... ", "→".blue());
         installer.check_kubectl()?;
-        println!("{}", "done".green());
+        tee_println!("{}", "done".green());
 
         // Install Helm if needed
-        print!("  {} Installing Helm if needed... ", "→".blue());
+        tee_print!("  {} Installing Helm if needed... ", "→".blue());
         if installer.check_helm().is_err() {
             installer.install_helm()?;
         }
-        println!("{}", "done".green());
+        tee_println!("{}", "done".green());
 
         // Create namespace
-        print!("  {} Creating Gitea namespace... ", "→".blue());
+        tee_print!("  {} Creating Gitea namespace... ", "→".blue());
         installer.create_namespace()?;
-        println!("{}", "done".green());
+        tee_println!("{}", "done".green());
 
         // Add Helm repository
-        print!("  {} Adding Gitea Helm repository... ", "→".blue());
+        tee_print!("  {} Adding Gitea Helm repository... ", "→".blue());
         installer.add_helm_repo()?;
-        println!("{}", "done".green());
+        tee_println!("{}", "done".green());
 
         // Deploy Helm chart
-        print!("  {} Deploying Gitea Helm chart (this may take several minutes)... ", "→".blue());
+        tee_print!(
+            "  {} Deploying Gitea Helm chart (this may take several minutes)... ",
+            "→".blue()
+        );
         installer.deploy_helm_chart()?;
-        println!("{}", "done".green());
+        tee_println!("{}", "done".green());
 
         // Wait for pods to be ready
-        print!("  {} Waiting for Gitea pods to be ready... ", "→".blue());
+        tee_print!("  {} Waiting for Gitea pods to be ready... ", "→".blue());
         installer.wait_for_ready()?;
-        println!("{}", "done".green());
+        tee_println!("{}", "done".green());
 
         // Validate installation
-        print!("  {} Validating installation... ", "→".blue());
+        tee_print!("  {} Validating installation... ", "→".blue());
         installer.validate_installation()?;
-        println!("{}", "done".green());
+        tee_println!("{}", "done".green());
 
         // Get service info
-        print!("  {} Getting service information... ", "→".blue());
+        tee_print!("  {} Getting service information... ", "→".blue());
         let service_info = installer.get_service_info()?;
-        println!("{}", "done".green());
+        tee_println!("{}", "done".green());
 
         // Print access information
-        println!();
-        println!("{}", "Gitea Access Information:".bold().cyan());
-        println!("  {} URL: {}", "→".blue(), service_info.access_url().bold().green());
+        tee_println!();
+        tee_println!("{}", "Gitea Access Information:".bold().cyan());
+        tee_println!(
+            "  {} URL: {}",
+            "→".blue(),
+            service_info.access_url().bold().green()
+        );
 
+        // Printed only to the terminal, never to the install log
         let (admin_user, admin_password) = installer.get_credentials();
-        println!("  {} Admin username: {}", "→".blue(), admin_user.bold().yellow());
-        println!("  {} Admin password: {}", "→".blue(), admin_password.bold().yellow());
+        println!(
+            "  {} Admin username: {}",
+            "→".blue(),
+            admin_user.bold().yellow()
+        );
+        println!(
+            "  {} Admin password: {}",
+            "→".blue(),
+            admin_password.bold().yellow()
+        );
 
         // Save credentials for Flux to use later
         let home = dirs::home_dir().unwrap_or_else(|| std::path::PathBuf::from("/root"));
@@ -391,39 +658,56 @@ fn setup_gitea_real() -> Result<()> {
             std::fs::set_permissions(&creds_path, std::fs::Permissions::from_mode(0o600))?;
         }
 
-        println!();
-        println!("{}", "⚠ Credentials saved securely for Flux integration".yellow().bold());
-        println!("  {} {}", "→".blue(), creds_path.display());
+        tee_println!();
+        tee_println!(
+            "{}",
+            "⚠ Credentials saved securely for Flux integration"
+                .yellow()
+                .bold()
+        );
+        tee_println!("  {} {}", "→".blue(), creds_path.display());
 
         Ok(())
     })();
 
     // Handle errors with rollback
     if let Err(e) = result {
-        println!("{}", "failed".red());
-        println!();
-        println!("{} Installation failed: {}", "✗".bold().red(), e);
-        println!("{} Rolling back changes...", "→".yellow());
+        tee_println!("{}", "failed".red());
+        tee_println!();
+        tee_println!("{} Installation failed: {}", "✗".bold().red(), e);
+        tee_println!("{} Rolling back changes...", "→".yellow());
 
         if let Err(rollback_err) = installer.rollback() {
-            println!("{} Rollback failed: {}", "✗".bold().red(), rollback_err);
+            tee_println!("{} Rollback failed: {}", "✗".bold().red(), rollback_err);
         } else {
-            println!("{} Rollback completed", "✓".green());
+            tee_println!("{} Rollback completed", "✓".green());
         }
 
         return Err(e);
     }
 
+    // Gitea is usable without a runner, so a failed registration only warns
+    if installer.registers_runner() {
+        tee_print!("  {} Registering Gitea Actions runner... ", "→".blue());
+        match installer.register_runner(RUNNER_NAME, RUNNER_LABELS) {
+            Ok(_) => tee_println!("{}", "done".green()),
+            Err(e) => {
+                tee_println!("{}", "failed".yellow());
+                tee_println!("  {} Runner registration failed: {:#}", "⚠".yellow(), e);
+            }
+        }
+    }
+
     // Cleanup on success
     installer.cleanup()?;
 
-    println!();
+    tee_println!();
     Ok(())
 }
 
 /// Real Redis installation implementation
 fn setup_redis_real() -> Result<()> {
-    println!("{}", "Installing Redis with Helm...".bold());
+    tee_println!("{}", "Installing Redis with Helm...".bold());
 
     // Create installer
     let mut installer = RedisInstaller::new()?;
@@ -431,34 +715,39 @@ fn setup_redis_real() -> Result<()> {
     // Run installation with rollback on failure
     let result = (|| -> Result<()> {
         // Add Helm repository
-        print!("  {} Adding Bitnami Helm repository... ", "→".blue());
+        tee_print!("  {} Adding Bitnami Helm repository... ", "→".blue());
         installer.add_helm_repo()?;
-        println!("{}", "done".green());
+        tee_println!("{}", "done".green());
 
         // Create namespace
-        print!("  {} Creating Redis namespace... ", "→".blue());
+        tee_print!("  {} Creating Redis namespace... ", "→".blue());
         installer.create_namespace()?;
-        println!("{}", "done".green());
+        tee_println!("{}", "done".green());
 
         // Deploy Redis
-        print!("  {} Deploying Redis Helm chart... ", "→".blue());
+        tee_print!("  {} Deploying Redis Helm chart... ", "→".blue());
         installer.deploy_redis()?;
-        println!("{}", "done".green());
+        tee_println!("{}", "done".green());
+
+        // Share the password with the KEDA scaler
+        tee_print!("  {} Creating Redis password Secret for KEDA... ", "→".blue());
+        installer.create_auth_secret()?;
+        tee_println!("{}", "done".green());
 
         // Wait for Redis to be ready
-        print!("  {} Waiting for Redis to be ready... ", "→".blue());
+        tee_print!("  {} Waiting for Redis to be ready... ", "→".blue());
         installer.wait_for_ready()?;
-        println!("{}", "done".green());
+        tee_println!("{}", "done".green());
 
         // Initialize Redis Streams
-        print!("  {} Initializing Redis Streams... ", "→".blue());
+        tee_print!("  {} Initializing Redis Streams... ", "→".blue());
         installer.initialize_streams()?;
-        println!("{}", "done".green());
+        tee_println!("{}", "done".green());
 
         // Validate installation
-        print!("  {} Validating Redis installation... ", "→".blue());
+        tee_print!("  {} Validating Redis installation... ", "→".blue());
         installer.validate()?;
-        println!("{}", "done".green());
+        tee_println!("{}", "done".green());
 
         // Save connection credentials
         let home = dirs::home_dir().unwrap_or_else(|| std::path::PathBuf::from("/root"));
@@ -467,97 +756,125 @@ fn setup_redis_real() -> Result<()> {
             std::fs::create_dir_all(parent)?;
         }
 
-        print!("  {} Saving connection credentials... ", "→".blue());
+        tee_print!("  {} Saving connection credentials... ", "→".blue());
         installer.save_credentials(&creds_path)?;
-        println!("{}", "done".green());
+        tee_println!("{}", "done".green());
 
         // Display connection info
         let conn_info = installer.get_connection_info()?;
-        println!();
-        println!("{}", "Redis connection details:".bold().green());
-        println!("  {} Host: {}", "→".blue(), conn_info.host.bold());
-        println!("  {} Port: {}", "→".blue(), conn_info.port.to_string().bold());
-        println!("  {} Namespace: {}", "→".blue(), conn_info.namespace.bold());
-        println!("  {} Credentials saved to: {}", "→".blue(), creds_path.display().to_string().bold());
+        tee_println!();
+        tee_println!("{}", "Redis connection details:".bold().green());
+        tee_println!("  {} Host: {}", "→".blue(), conn_info.host.bold());
+        tee_println!(
+            "  {} Port: {}",
+            "→".blue(),
+            conn_info.port.to_string().bold()
+        );
+        tee_println!("  {} Namespace: {}", "→".blue(), conn_info.namespace.bold());
+        tee_println!(
+            "  {} Credentials saved to: {}",
+            "→".blue(),
+            creds_path.display().to_string().bold()
+        );
 
         Ok(())
     })();
 
     // Handle errors with rollback
     if let Err(e) = result {
-        println!("{}", "failed".red());
-        println!();
-        println!("{} Installation failed: {}", "✗".bold().red(), e);
-        println!("{} Rolling back changes...", "→".yellow());
+        tee_println!("{}", "failed".red());
+        tee_println!();
+        tee_println!("{} Installation failed: {}", "✗".bold().red(), e);
+        tee_println!("{} Rolling back changes...", "→".yellow());
 
         if let Err(rollback_err) = installer.uninstall() {
-            println!("{} Rollback failed: {}", "✗".bold().red(), rollback_err);
+            tee_println!("{} Rollback failed: {}", "✗".bold().red(), rollback_err);
         } else {
-            println!("{} Rollback completed", "✓".green());
+            tee_println!("{} Rollback completed", "✓".green());
         }
 
         return Err(e);
     }
 
-    println!();
+    tee_println!();
     Ok(())
 }
 
+/// Redis password saved by the Redis setup in ~/.raibid/redis-credentials.json
+fn saved_redis_password() -> Option<String> {
+    let home = dirs::home_dir()?;
+    let contents = std::fs::read_to_string(home.join(".raibid").join("redis-credentials.json")).ok()?;
+    let credentials: serde_json::Value = serde_json::from_str(&contents).ok()?;
+    credentials["password"].as_str().map(str::to_string)
+}
+
 /// Real KEDA installation implementation
 fn setup_keda_real() -> Result<()> {
-    println!("{}", "Installing KEDA autoscaler...".bold());
+    tee_println!("{}", "Installing KEDA autoscaler...".bold());
 
-    // Create installer
-    let installer = KedaInstaller::new()?;
+    // Create installer, authenticating the scaler when Redis has a password
+    let redis_config = RedisConfig {
+        password: saved_redis_password(),
+        ..RedisConfig::default()
+    };
+    let installer = KedaInstaller::with_config(KedaConfig::default().with_redis_auth(&redis_config))?;
 
     // Run installation with rollback on failure
     let result = (|| -> Result<()> {
         // Check Helm
-        print!("  {} Checking Helm... ", "→".blue());
+        tee_print!("  {} Checking Helm... ", "→".blue());
         installer.check_helm()?;
-        println!("{}", "done".green());
+        tee_println!("{}", "done".green());
 
         // Add Helm repository
-        print!("  {} Adding KEDA Helm repository... ", "→".blue());
+        tee_print!("  {} Adding KEDA Helm repository... ", "→".blue());
         installer.add_helm_repo()?;
-        println!("{}", "done".green());
+        tee_println!("{}", "done".green());
 
         // Create namespace
-        print!("  {} Creating KEDA namespace... ", "→".blue());
+        tee_print!("  {} Creating KEDA namespace... ", "→".blue());
         installer.create_namespace()?;
-        println!("{}", "done".green());
+        tee_println!("{}", "done".green());
 
         // Deploy KEDA
-        print!("  {} Deploying KEDA operators... ", "→".blue());
+        tee_print!("  {} Deploying KEDA operators... ", "→".blue());
         installer.deploy_keda()?;
-        println!("{}", "done".green());
+        tee_println!("{}", "done".green());
 
         // Wait for KEDA to be ready
-        print!("  {} Waiting for KEDA to be ready... ", "→".blue());
+        tee_print!("  {} Waiting for KEDA to be ready... ", "→".blue());
         installer.wait_for_ready()?;
-        println!("{}", "done".green());
+        tee_println!("{}", "done".green());
 
         // Validate installation
-        print!("  {} Validating KEDA installation... ", "→".blue());
+        tee_print!("  {} Validating KEDA installation... ", "→".blue());
         installer.validate()?;
-        println!("{}", "done".green());
+        tee_println!("{}", "done".green());
+
+        // Create TriggerAuthentication for the Redis password
+        tee_print!("  {} Creating TriggerAuthentication for Redis... ", "→".blue());
+        installer.create_trigger_auth()?;
+        tee_println!("{}", "done".green());
 
         // Create ScaledObject for Redis Streams
-        print!("  {} Creating ScaledObject for Redis Streams... ", "→".blue());
+        tee_print!(
+            "  {} Creating ScaledObject for Redis Streams... ",
+            "→".blue()
+        );
         installer.create_scaled_object()?;
-        println!("{}", "done".green());
+        tee_println!("{}", "done".green());
 
         // Display KEDA status
-        println!();
-        println!("{}", "KEDA Status:".bold().cyan());
+        tee_println!();
+        tee_println!("{}", "KEDA Status:".bold().cyan());
         match installer.get_scaled_object_status() {
             Ok(status) => {
                 for line in status.lines() {
-                    println!("  {}", line);
+                    tee_println!("  {}", line);
                 }
             }
             Err(e) => {
-                println!("  {} Failed to get status: {}", "⚠".yellow(), e);
+                tee_println!("  {} Failed to get status: {}", "⚠".yellow(), e);
             }
         }
 
@@ -566,27 +883,27 @@ fn setup_keda_real() -> Result<()> {
 
     // Handle errors with rollback
     if let Err(e) = result {
-        println!("{}", "failed".red());
-        println!();
-        println!("{} Installation failed: {}", "✗".bold().red(), e);
-        println!("{} Rolling back changes...", "→".yellow());
+        tee_println!("{}", "failed".red());
+        tee_println!();
+        tee_println!("{} Installation failed: {}", "✗".bold().red(), e);
+        tee_println!("{} Rolling back changes...", "→".yellow());
 
         if let Err(rollback_err) = installer.uninstall() {
-            println!("{} Rollback failed: {}", "✗".bold().red(), rollback_err);
+            tee_println!("{} Rollback failed: {}", "✗".bold().red(), rollback_err);
         } else {
-            println!("{} Rollback completed", "✓".green());
+            tee_println!("{} Rollback completed", "✓".green());
         }
 
         return Err(e);
     }
 
-    println!();
+    tee_println!();
     Ok(())
 }
 
 /// Real Flux installation implementation
 fn setup_flux_real() -> Result<()> {
-    println!("{}", "Installing Flux GitOps...".bold());
+    tee_println!("{}", "Installing Flux GitOps...".bold());
 
     // Create runtime for async operations
     let runtime = tokio::runtime::Runtime::new()?;
@@ -611,12 +928,12 @@ fn setup_flux_real() -> Result<()> {
 
         (username, password)
     } else {
-        println!(
+        tee_println!(
             "{} Gitea credentials not found at {}",
             "⚠".yellow(),
             gitea_creds_path.display()
         );
-        println!("  {} Please run 'raibid-cli setup gitea' first", "→".blue());
+        tee_println!("  {} Please run 'raibid-cli setup gitea' first", "→".blue());
         return Err(anyhow::anyhow!("Gitea must be installed before Flux"));
     };
 
@@ -633,62 +950,62 @@ fn setup_flux_real() -> Result<()> {
     // Run installation with rollback on failure
     let result = (|| -> Result<()> {
         // Check if Flux CLI is installed
-        print!("  {} Checking for Flux CLI... ", "→".blue());
+        tee_print!("  {} Checking for Flux CLI... ", "→".blue());
         let flux_installed = installer.check_flux_cli()?;
 
         if flux_installed {
-            println!("{}", "already installed".green());
+            tee_println!("{}", "already installed".green());
         } else {
-            println!("{}", "not found".yellow());
+            tee_println!("{}", "not found".yellow());
 
             // Download Flux CLI
-            print!("  {} Downloading Flux CLI... ", "→".blue());
+            tee_print!("  {} Downloading Flux CLI... ", "→".blue());
             let archive_path = runtime.block_on(installer.download_flux())?;
-            println!("{}", "done".green());
+            tee_println!("{}", "done".green());
 
             // Download and verify checksums
-            print!("  {} Verifying checksum... ", "→".blue());
+            tee_print!("  {} Verifying checksum... ", "→".blue());
             let checksums = runtime.block_on(installer.download_checksums())?;
             installer.verify_checksum(&archive_path, &checksums)?;
-            println!("{}", "done".green());
+            tee_println!("{}", "done".green());
 
             // Install Flux CLI
-            print!("  {} Installing Flux CLI... ", "→".blue());
+            tee_print!("  {} Installing Flux CLI... ", "→".blue());
             installer.install_flux_cli(&archive_path)?;
-            println!("{}", "done".green());
+            tee_println!("{}", "done".green());
         }
 
         // Bootstrap Flux with Gitea
-        print!("  {} Bootstrapping Flux with Gitea... ", "→".blue());
+        tee_print!("  {} Bootstrapping Flux with Gitea... ", "→".blue());
         installer.bootstrap_flux()?;
-        println!("{}", "done".green());
+        tee_println!("{}", "done".green());
 
         // Configure image automation
-        print!("  {} Configuring image automation... ", "→".blue());
+        tee_print!("  {} Configuring image automation... ", "→".blue());
         installer.configure_image_automation()?;
-        println!("{}", "done".green());
+        tee_println!("{}", "done".green());
 
         // Configure notifications
-        print!("  {} Configuring notification controller... ", "→".blue());
+        tee_print!("  {} Configuring notification controller... ", "→".blue());
         installer.configure_notifications()?;
-        println!("{}", "done".green());
+        tee_println!("{}", "done".green());
 
         // Validate installation
-        print!("  {} Validating Flux installation... ", "→".blue());
+        tee_print!("  {} Validating Flux installation... ", "→".blue());
         installer.validate_installation()?;
-        println!("{}", "done".green());
+        tee_println!("{}", "done".green());
 
         // Get and display status
-        println!();
-        println!("{}", "Flux Status:".bold().cyan());
+        tee_println!();
+        tee_println!("{}", "Flux Status:".bold().cyan());
         match installer.get_status() {
             Ok(status) => {
                 for line in status.lines() {
-                    println!("  {}", line);
+                    tee_println!("  {}", line);
                 }
             }
             Err(e) => {
-                println!("  {} Failed to get status: {}", "⚠".yellow(), e);
+                tee_println!("  {} Failed to get status: {}", "⚠".yellow(), e);
             }
         }
 
@@ -697,15 +1014,15 @@ fn setup_flux_real() -> Result<()> {
 
     // Handle errors with rollback
     if let Err(e) = result {
-        println!("{}", "failed".red());
-        println!();
-        println!("{} Installation failed: {}", "✗".bold().red(), e);
-        println!("{} Rolling back changes...", "→".yellow());
+        tee_println!("{}", "failed".red());
+        tee_println!();
+        tee_println!("{} Installation failed: {}", "✗".bold().red(), e);
+        tee_println!("{} Rolling back changes...", "→".yellow());
 
         if let Err(rollback_err) = installer.rollback() {
-            println!("{} Rollback failed: {}", "✗".bold().red(), rollback_err);
+            tee_println!("{} Rollback failed: {}", "✗".bold().red(), rollback_err);
         } else {
-            println!("{} Rollback completed", "✓".green());
+            tee_println!("{} Rollback completed", "✓".green());
         }
 
         return Err(e);
@@ -714,6 +1031,51 @@ fn setup_flux_real() -> Result<()> {
     // Cleanup on success
     installer.cleanup()?;
 
-    println!();
+    tee_println!();
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_setup_all_dry_run_output() {
+        colored::control::set_override(false);
+
+        let output = render_plan("installed", &setup_plan(Component::All));
+
+        assert!(output.starts_with("DRY-RUN MODE: No changes will be made\n"));
+        assert!(output.contains("The following would be installed:"));
+        assert!(output.contains("\n  1. Download k3s "));
+        assert!(output.contains("Estimated total: ~"));
+
+        // Every component has a section, in install order
+        let positions: Vec<usize> = Component::all_components()
+            .iter()
+            .map(|c| {
+                let header = format!("\n{} (~", c.name());
+                output
+                    .find(&header)
+                    .unwrap_or_else(|| panic!("missing {} in\n{}", c.name(), output))
+            })
+            .collect();
+        assert_eq!(positions.len(), 5);
+        assert!(positions.windows(2).all(|w| w[0] < w[1]), "{}", output);
+    }
+
+    #[test]
+    fn test_setup_plan_single_component() {
+        let plan = setup_plan(Component::Redis);
+        assert_eq!(plan.len(), 1);
+        assert_eq!(plan[0].0, Component::Redis);
+        assert!(!plan[0].1.is_empty());
+    }
+
+    #[test]
+    fn test_upgrade_only_k3s() {
+        assert!(upgrade(Component::Redis, "v1.29.0+k3s1", true).is_err());
+        assert!(upgrade(Component::K3s, "latest", true).is_err());
+        assert!(upgrade(Component::K3s, "v1.29.0+k3s1", true).is_ok());
+    }
+}
@@ -11,20 +11,53 @@ use super::setup::Component;
 use raibid_common::infrastructure::{
     ComponentStatusChecker, K3sStatusChecker, GiteaStatusChecker,
     RedisStatusChecker, KedaStatusChecker, FluxStatusChecker,
-    ComponentStatus, ComponentHealth,
+    ComponentStatus, ComponentHealth, K3sInstaller,
 };
 
 /// Execute the status command for a component
-pub fn execute(component: Option<Component>) -> Result<()> {
+///
+/// With `min_version`, exits with status 1 if the installed k3s is older.
+pub fn execute(component: Option<Component>, min_version: Option<&str>) -> Result<()> {
     let component = component.unwrap_or(Component::All);
+    if min_version.is_some() && component != Component::K3s {
+        anyhow::bail!("--min-version is only supported for k3s");
+    }
 
     // Create tokio runtime for async operations
     let runtime = tokio::runtime::Runtime::new()?;
 
     if component == Component::All {
-        runtime.block_on(show_all_status())
+        runtime.block_on(show_all_status())?;
     } else {
-        runtime.block_on(show_component_status(component))
+        runtime.block_on(show_component_status(component))?;
+    }
+
+    if let Some(min_version) = min_version {
+        check_k3s_version(min_version);
+    }
+    Ok(())
+}
+
+/// Exit with status 1 unless the installed k3s is at least `min_version`
+fn check_k3s_version(min_version: &str) {
+    let checked =
+        K3sInstaller::new().and_then(|installer| installer.check_minimum_version(min_version));
+    match checked {
+        Ok(()) => println!(
+            "{} k3s meets the minimum version {}",
+            "✓".green(),
+            min_version
+        ),
+        Err(e) => {
+            println!("{} {}", "Error:".red().bold(), e);
+            println!();
+            println!("{}", "Try:".green().bold());
+            println!(
+                "  {} raibid-cli setup k3s --upgrade-to <version>",
+                "→".blue()
+            );
+            std::process::exit(1);
+        }
     }
 }
 
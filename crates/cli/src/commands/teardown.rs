@@ -2,16 +2,30 @@
 //!
 //! Mock implementation of the teardown command for infrastructure components.
 //! This is a placeholder that simulates the teardown process with colorful output.
+//! With `--dry-run` only the plan is printed.
+//!
+//! `teardown all` removes components through a [`RollbackManager`], in
+//! reverse dependency order: Flux, KEDA, Redis, Gitea, then k3s.
 
 use anyhow::Result;
 use colored::Colorize;
+use std::cmp::Reverse;
 use std::thread;
 use std::time::Duration;
 
-use super::setup::Component;
+use super::setup::{render_plan, Component};
+use raibid_common::infrastructure::{
+    InfraError, PlanStep, RollbackManager, FLUX_PRIORITY, GITEA_PRIORITY, K3S_PRIORITY,
+    KEDA_PRIORITY, REDIS_PRIORITY,
+};
 
 /// Execute the teardown command for a component
-pub fn execute(component: Component) -> Result<()> {
+pub fn execute(component: Component, dry_run: bool) -> Result<()> {
+    if dry_run {
+        println!("{}", render_plan("removed", &teardown_plan(component)));
+        return Ok(());
+    }
+
     if component == Component::All {
         teardown_all()
     } else {
@@ -19,7 +33,7 @@ pub fn execute(component: Component) -> Result<()> {
     }
 }
 
-/// Teardown all components (in reverse order)
+/// Teardown all components (in reverse dependency order)
 fn teardown_all() -> Result<()> {
     println!(
         "{} {}",
@@ -28,14 +42,7 @@ fn teardown_all() -> Result<()> {
     );
     println!();
 
-    // Teardown in reverse order to respect dependencies
-    let mut components = Component::all_components();
-    components.reverse();
-
-    for component in components {
-        teardown_component(component)?;
-        println!();
-    }
+    teardown_manager().rollback()?;
 
     println!(
         "{} {}",
@@ -46,6 +53,38 @@ fn teardown_all() -> Result<()> {
     Ok(())
 }
 
+/// Rollback priority of a component; higher priorities are removed first
+fn rollback_priority(component: Component) -> u8 {
+    match component {
+        Component::Flux => FLUX_PRIORITY,
+        Component::Keda => KEDA_PRIORITY,
+        Component::Redis => REDIS_PRIORITY,
+        Component::Gitea => GITEA_PRIORITY,
+        Component::K3s | Component::All => K3S_PRIORITY,
+    }
+}
+
+/// Rollback manager tearing down every component
+fn teardown_manager() -> RollbackManager {
+    let mut manager = RollbackManager::new("raibid-ci");
+    for component in Component::all_components() {
+        manager.push_with_priority(
+            component.name(),
+            Box::new(move || {
+                teardown_component(component).map_err(|e| InfraError::Rollback {
+                    component: component.name().to_string(),
+                    reason: format!("{:#}", e),
+                    partial_cleanup: Vec::new(),
+                })?;
+                println!();
+                Ok(())
+            }),
+            rollback_priority(component),
+        );
+    }
+    manager
+}
+
 /// Teardown a single component
 fn teardown_component(component: Component) -> Result<()> {
     println!(
@@ -123,46 +162,73 @@ fn show_removal_info(component: Component) -> Result<()> {
     Ok(())
 }
 
-/// Simulate the teardown process
-fn simulate_teardown(component: Component) -> Result<()> {
-    println!("{}", "Cleaning up resources...".bold());
+/// Steps tearing down `component` would take, per component in removal order
+pub fn teardown_plan(component: Component) -> Vec<(Component, Vec<PlanStep>)> {
+    let components = match component {
+        Component::All => {
+            // Same order as the rollback manager removes them in
+            let mut components = Component::all_components();
+            components.sort_by_key(|component| Reverse(rollback_priority(*component)));
+            components
+        }
+        single => vec![single],
+    };
 
+    components
+        .into_iter()
+        .map(|component| (component, teardown_steps(component)))
+        .collect()
+}
+
+/// Steps removing a single component
+fn teardown_steps(component: Component) -> Vec<PlanStep> {
+    let secs = Duration::from_secs;
     let steps = match component {
         Component::K3s => vec![
-            "Stopping k3s service",
-            "Removing k3s binaries",
-            "Cleaning up container data",
-            "Removing configuration files",
+            ("Stopping k3s service", secs(10)),
+            ("Removing k3s binaries", secs(1)),
+            ("Cleaning up container data", secs(30)),
+            ("Removing configuration files", secs(1)),
         ],
         Component::Gitea => vec![
-            "Scaling down Gitea pods",
-            "Removing Helm release",
-            "Cleaning up persistent volumes",
-            "Deleting namespace",
+            ("Scaling down Gitea pods", secs(30)),
+            ("Removing Helm release", secs(20)),
+            ("Cleaning up persistent volumes", secs(10)),
+            ("Deleting namespace", secs(30)),
         ],
         Component::Redis => vec![
-            "Stopping Redis pods",
-            "Removing Helm release",
-            "Cleaning up data volumes",
-            "Deleting namespace",
+            ("Stopping Redis pods", secs(20)),
+            ("Removing Helm release", secs(20)),
+            ("Cleaning up data volumes", secs(10)),
+            ("Deleting namespace", secs(30)),
         ],
         Component::Keda => vec![
-            "Removing KEDA operator",
-            "Deleting custom resources",
-            "Removing Helm release",
-            "Cleaning up configurations",
+            ("Removing KEDA operator", secs(20)),
+            ("Deleting custom resources", secs(5)),
+            ("Removing Helm release", secs(20)),
+            ("Cleaning up configurations", secs(5)),
         ],
         Component::Flux => vec![
-            "Stopping Flux controllers",
-            "Removing GitOps configs",
-            "Deleting Flux namespace",
-            "Cleaning up custom resources",
+            ("Stopping Flux controllers", secs(20)),
+            ("Removing GitOps configs", secs(5)),
+            ("Deleting Flux namespace", secs(30)),
+            ("Cleaning up custom resources", secs(5)),
         ],
         Component::All => vec![],
     };
 
-    for step in steps {
-        print!("  {} {}... ", "→".yellow(), step);
+    steps
+        .into_iter()
+        .map(|(description, estimate)| PlanStep::new(description, estimate))
+        .collect()
+}
+
+/// Simulate the teardown process
+fn simulate_teardown(component: Component) -> Result<()> {
+    println!("{}", "Cleaning up resources...".bold());
+
+    for step in teardown_steps(component) {
+        print!("  {} {}... ", "→".yellow(), step.description);
         thread::sleep(Duration::from_millis(200));
         println!("{}", "done".green());
     }
@@ -170,3 +236,36 @@ fn simulate_teardown(component: Component) -> Result<()> {
     println!();
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_teardown_all_plan_reverse_order() {
+        let plan = teardown_plan(Component::All);
+        let order: Vec<Component> = plan.iter().map(|(c, _)| *c).collect();
+        assert_eq!(
+            order,
+            vec![
+                Component::Flux,
+                Component::Keda,
+                Component::Redis,
+                Component::Gitea,
+                Component::K3s,
+            ]
+        );
+        assert!(plan.iter().all(|(_, steps)| !steps.is_empty()));
+    }
+
+    #[test]
+    fn test_teardown_all_follows_rollback_order() {
+        let mut manager = teardown_manager();
+        let planned: Vec<String> = teardown_plan(Component::All)
+            .iter()
+            .map(|(component, _)| component.name().to_string())
+            .collect();
+        assert_eq!(manager.dry_run(), planned);
+        manager.disable_auto_rollback();
+    }
+}
@@ -0,0 +1,196 @@
+//! Persistent install log
+//!
+//! `raibid setup` prints progress to stdout, which is gone once the terminal
+//! scrolls. Everything it prints is also emitted as a tracing event on
+//! [`TARGET`] and appended to `~/.raibid/install.log` by the layer from
+//! [`layer`], with timestamps and the component being installed. The console
+//! layer filters these events out since they were already printed.
+//!
+//! Use [`tee_println!`] and [`tee_print!`] in place of `println!`/`print!`,
+//! except for secrets such as passwords, which must not end up in the log.
+//! The log is only readable by its owner.
+
+use anyhow::{Context, Result};
+use std::cell::RefCell;
+use std::fs::{self, File, OpenOptions};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tracing::Subscriber;
+use tracing_subscriber::filter::filter_fn;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::Layer;
+
+/// Tracing target for install log events
+pub const TARGET: &str = "raibid::install";
+
+/// Install log location as shown to users
+pub const DISPLAY_PATH: &str = "~/.raibid/install.log";
+
+thread_local! {
+    /// Output printed without a trailing newline, waiting for the rest of the line
+    static PARTIAL_LINE: RefCell<String> = const { RefCell::new(String::new()) };
+}
+
+/// Print a line to stdout and record it in the install log
+macro_rules! tee_println {
+    () => {
+        $crate::install_log::record_line("")
+    };
+    ($($arg:tt)*) => {{
+        let line = format!($($arg)*);
+        println!("{}", line);
+        $crate::install_log::record_line(&line);
+    }};
+}
+
+/// Print to stdout without a newline and record it in the install log
+///
+/// The text is held back until the line is completed by [`tee_println!`].
+macro_rules! tee_print {
+    ($($arg:tt)*) => {{
+        let text = format!($($arg)*);
+        print!("{}", text);
+        $crate::install_log::record_partial(&text);
+    }};
+}
+
+pub(crate) use tee_print;
+pub(crate) use tee_println;
+
+/// Path of the install log
+pub fn path() -> PathBuf {
+    dirs::home_dir()
+        .unwrap_or_else(|| PathBuf::from("/root"))
+        .join(".raibid")
+        .join("install.log")
+}
+
+/// Create the layer that appends install log events to [`path`]
+pub fn layer<S>() -> Result<impl Layer<S>>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    let file = open(&path())?;
+
+    Ok(tracing_subscriber::fmt::layer()
+        .with_writer(Mutex::new(file))
+        .with_ansi(false)
+        .with_target(false)
+        .with_filter(filter_fn(|metadata| metadata.target() == TARGET)))
+}
+
+/// Open the install log at `path` for appending, readable only by its owner
+fn open(path: &Path) -> Result<File> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+
+    let mut options = OpenOptions::new();
+    options.create(true).append(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let file = options
+        .open(path)
+        .with_context(|| format!("Failed to open install log {}", path.display()))?;
+
+    // Logs created by earlier versions were readable by everyone
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(fs::Permissions::from_mode(0o600))
+            .with_context(|| format!("Failed to restrict access to {}", path.display()))?;
+    }
+
+    Ok(file)
+}
+
+/// Record a complete line, including any partial output before it
+pub fn record_line(line: &str) {
+    let line = PARTIAL_LINE.with(|partial| {
+        let mut partial = partial.borrow_mut();
+        partial.push_str(line);
+        std::mem::take(&mut *partial)
+    });
+
+    let line = strip_ansi(&line);
+    if !line.trim().is_empty() {
+        tracing::info!(target: TARGET, "{}", line.trim_end());
+    }
+}
+
+/// Record output that does not end a line yet
+pub fn record_partial(text: &str) {
+    PARTIAL_LINE.with(|partial| partial.borrow_mut().push_str(text));
+}
+
+/// Remove ANSI escape sequences (terminal colors) from a string
+fn strip_ansi(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            // CSI sequence: ESC [ parameters final-byte
+            if chars.next() == Some('[') {
+                for c in chars.by_ref() {
+                    if ('@'..='~').contains(&c) {
+                        break;
+                    }
+                }
+            }
+        } else {
+            out.push(c);
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_ansi() {
+        assert_eq!(strip_ansi("\x1b[1;32mdone\x1b[0m"), "done");
+        assert_eq!(strip_ansi("  → plain"), "  → plain");
+    }
+
+    #[test]
+    fn test_partial_output_joined() {
+        record_partial("  → Installing k3s binary... ");
+        let joined = PARTIAL_LINE.with(|p| p.borrow().clone());
+        assert_eq!(joined, "  → Installing k3s binary... ");
+
+        record_line("done");
+        assert!(PARTIAL_LINE.with(|p| p.borrow().is_empty()));
+    }
+
+    #[test]
+    fn test_path() {
+        assert!(path().ends_with(".raibid/install.log"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_log_only_readable_by_owner() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let created = dir.path().join(".raibid/install.log");
+        open(&created).unwrap();
+        let mode = fs::metadata(&created).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+
+        let existing = dir.path().join("old-install.log");
+        fs::write(&existing, "").unwrap();
+        fs::set_permissions(&existing, fs::Permissions::from_mode(0o644)).unwrap();
+        open(&existing).unwrap();
+        let mode = fs::metadata(&existing).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+}
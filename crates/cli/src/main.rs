@@ -1,21 +1,32 @@
 mod cli;
 mod commands;
+mod install_log;
 
 use anyhow::Result;
 use clap::Parser;
+use tracing_subscriber::filter::filter_fn;
+use tracing_subscriber::prelude::*;
 use tracing_subscriber::EnvFilter;
 
 use cli::Cli;
 
 fn main() -> Result<()> {
-    // Initialize logging
-    setup_logging()?;
-
     // Parse CLI arguments
     let cli = Cli::parse();
 
+    // Initialize logging, recording setup output to the install log
+    setup_logging(matches!(
+        cli.command,
+        Some(cli::Commands::Setup { dry_run: false, .. })
+    ))?;
+
+    // Select the profile for this and any later configuration load
+    if let Some(ref profile) = cli.profile {
+        std::env::set_var(raibid_common::config::PROFILE_ENV, profile);
+    }
+
     // Load configuration
-    let _config = raibid_common::Config::load()?;
+    let config = raibid_common::Config::load()?;
 
     // Handle commands
     match cli.command {
@@ -28,37 +39,134 @@ fn main() -> Result<()> {
             // Handle config subcommands
             commands::config::handle(&cmd)
         }
-        Some(cli::Commands::Tui) => {
-            // Launch TUI dashboard
-            raibid_tui::launch()
+        Some(cli::Commands::Tui {
+            offline,
+            jobs_pct,
+            agents_pct,
+            queue_pct,
+        }) => {
+            // Launch TUI dashboard, polling the API server unless offline
+            let api_url = if offline {
+                None
+            } else {
+                Some(
+                    raibid_common::ApiClient::from_config(&config)?
+                        .base_url()
+                        .to_string(),
+                )
+            };
+            // Panel widths from the flags, else as last resized
+            let layout_file = raibid_tui::PanelWidths::default_path();
+            let panel_widths = match (jobs_pct, agents_pct, queue_pct) {
+                (Some(jobs), Some(agents), Some(queue)) => {
+                    raibid_tui::PanelWidths::new(jobs, agents, queue)?
+                }
+                _ => layout_file
+                    .as_deref()
+                    .and_then(raibid_tui::PanelWidths::load)
+                    .unwrap_or_default(),
+            };
+            let tui_config = raibid_tui::AppConfig {
+                refresh_interval: std::time::Duration::from_millis(
+                    config.ui.refresh_rate_ms.into(),
+                ),
+                panel_widths,
+                layout_file,
+                api_url,
+                api_key: config.api.api_key.clone(),
+            };
+            raibid_tui::launch_with_config(tui_config)
         }
-        Some(cli::Commands::Setup { component }) => {
+        Some(cli::Commands::Setup {
+            component,
+            dry_run,
+            upgrade_to,
+            no_merge,
+        }) => {
             // Handle setup command
             let comp = component.parse()?;
-            commands::setup::execute(comp)
+            match upgrade_to {
+                Some(version) => commands::setup::upgrade(comp, &version, dry_run),
+                None => commands::setup::execute(comp, dry_run, !no_merge),
+            }
         }
-        Some(cli::Commands::Teardown { component }) => {
+        Some(cli::Commands::Teardown { component, dry_run }) => {
             // Handle teardown command
             let comp = component.parse()?;
-            commands::teardown::execute(comp)
+            commands::teardown::execute(comp, dry_run)
         }
-        Some(cli::Commands::Status { component }) => {
+        Some(cli::Commands::Status {
+            component,
+            min_version,
+        }) => {
             // Handle status command
             let comp = match component {
                 Some(c) => Some(c.parse()?),
                 None => None,
             };
-            commands::status::execute(comp)
+            commands::status::execute(comp, min_version.as_deref())
+        }
+        Some(cli::Commands::Diagnose) => {
+            // Run pre-flight checks and suggest fixes
+            commands::diagnose::execute()
+        }
+        Some(cli::Commands::Jobs(cmd)) => {
+            // Handle jobs subcommands
+            commands::jobs::handle(&cmd, &config)
+        }
+        Some(cli::Commands::Logs(cmd)) => {
+            // Handle logs subcommands
+            commands::logs::handle(&cmd)
+        }
+        Some(cli::Commands::Agent(cmd)) => {
+            // Handle agent subcommands
+            commands::agent::handle(&cmd, &config)
+        }
+        Some(cli::Commands::ApiKey(cmd)) => {
+            // Handle api-key subcommands
+            commands::api_key::handle(&cmd)
+        }
+        Some(cli::Commands::Completions { shell }) => {
+            // Print completion script to stdout
+            commands::completions::execute(shell)
+        }
+        Some(cli::Commands::Mirror(cmd)) => {
+            // Handle mirror subcommands
+            commands::mirror::handle(&cmd)
+        }
+        Some(cli::Commands::Schedule(cmd)) => {
+            // Handle schedule subcommands
+            commands::schedule::handle(&cmd, &config)
         }
     }
 }
 
-fn setup_logging() -> Result<()> {
+fn setup_logging(record_install: bool) -> Result<()> {
     let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
 
-    tracing_subscriber::fmt()
-        .with_env_filter(filter)
+    // Install log events duplicate what was already printed to stdout
+    let console = tracing_subscriber::fmt::layer()
         .with_target(false)
+        .with_filter(filter)
+        .with_filter(filter_fn(|metadata| {
+            metadata.target() != install_log::TARGET
+        }));
+
+    let install_layer = if record_install {
+        match install_log::layer() {
+            Ok(layer) => Some(layer),
+            Err(e) => {
+                eprintln!("Warning: install log disabled: {:#}", e);
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    tracing_subscriber::registry()
+        .with(console)
+        .with(install_layer)
         .init();
 
     Ok(())
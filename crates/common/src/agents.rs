@@ -0,0 +1,277 @@
+//! Agent types
+//!
+//! Types describing CI agents as reported by the raibid-server API.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::jobs::JobStatus;
+
+/// Agent execution status
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AgentStatus {
+    /// Agent is idle and ready for work
+    Idle,
+    /// Agent is executing a job
+    Busy,
+    /// Agent is starting up
+    Starting,
+    /// Agent is shutting down
+    Stopping,
+    /// Agent stopped sending heartbeats
+    Offline,
+}
+
+impl AgentStatus {
+    /// Get status as string
+    pub fn as_str(&self) -> &str {
+        match self {
+            AgentStatus::Idle => "idle",
+            AgentStatus::Busy => "busy",
+            AgentStatus::Starting => "starting",
+            AgentStatus::Stopping => "stopping",
+            AgentStatus::Offline => "offline",
+        }
+    }
+}
+
+impl std::fmt::Display for AgentStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// Agent summary reported by `GET /api/agents`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AgentInfo {
+    /// Unique agent identifier
+    pub id: String,
+    /// Current status
+    pub status: AgentStatus,
+    /// Job the agent is executing, if any
+    #[serde(default)]
+    pub current_job: Option<String>,
+    /// CPU usage percentage (0-100)
+    pub cpu_percent: f64,
+    /// Memory usage percentage (0-100)
+    pub memory_percent: f64,
+    /// Seconds since the agent started
+    pub uptime_secs: u64,
+    /// Jobs the agent reported running in its last heartbeat
+    #[serde(default)]
+    pub active_jobs: u32,
+    /// Whether the agent was asked to stop taking jobs
+    #[serde(default)]
+    pub draining: bool,
+    /// How long a restart waits for the agent to drain, from its
+    /// registration
+    #[serde(default = "default_drain_timeout_secs")]
+    pub drain_timeout_secs: u64,
+}
+
+/// Body of `POST /api/agents/:id/heartbeat`
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct AgentHeartbeat {
+    /// Jobs the agent is running
+    pub active_jobs: u32,
+}
+
+/// Response to `POST /api/agents/:id/heartbeat`
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct HeartbeatResponse {
+    /// The agent should finish its running jobs and claim no more, set by
+    /// `POST /api/agents/:id/drain`
+    #[serde(default)]
+    pub draining: bool,
+}
+
+/// Body of `POST /api/agents/register`, sent by agents on startup
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AgentRegistration {
+    /// Unique agent identifier
+    pub agent_id: String,
+    /// Agent version
+    pub version: String,
+    /// Toolchains the agent can build with (e.g. `rust`, `docker`)
+    #[serde(default)]
+    pub capabilities: Vec<String>,
+    /// Kubernetes pod running the agent, deleted to restart it
+    #[serde(default)]
+    pub pod_name: Option<String>,
+    /// How long a restart waits for the agent's running jobs to finish
+    #[serde(default = "default_drain_timeout_secs")]
+    pub drain_timeout_secs: u64,
+}
+
+/// Drain timeout of agents that do not report one
+pub const DEFAULT_DRAIN_TIMEOUT_SECS: u64 = 300;
+
+fn default_drain_timeout_secs() -> u64 {
+    DEFAULT_DRAIN_TIMEOUT_SECS
+}
+
+/// Agent replica range, the body and response of `POST /api/agents/scale`
+///
+/// The server applies `min` and `max` to the KEDA ScaledObject scaling the
+/// agents; KEDA picks the replica count within that range from the queue
+/// depth.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct AgentScale {
+    /// Fewest agents to run (0 allows scaling to zero)
+    pub min: u32,
+    /// Most agents to run
+    pub max: u32,
+    /// Number of agents wanted now, within `min..=max`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub desired: Option<u32>,
+}
+
+impl AgentScale {
+    /// Check that the range is non-empty and contains `desired`
+    pub fn validate(&self) -> Result<(), String> {
+        if self.max == 0 {
+            return Err("max must be greater than 0".to_string());
+        }
+        if self.min > self.max {
+            return Err(format!(
+                "min ({}) must not be greater than max ({})",
+                self.min, self.max
+            ));
+        }
+        if let Some(desired) = self.desired {
+            if !(self.min..=self.max).contains(&desired) {
+                return Err(format!(
+                    "desired ({}) must be between min ({}) and max ({})",
+                    desired, self.min, self.max
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A job previously executed by an agent
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AgentJobRecord {
+    /// Job identifier
+    pub job_id: String,
+    /// Repository in `owner/name` form
+    pub repo: String,
+    /// Final (or current) job status
+    pub status: JobStatus,
+    /// When the agent started the job
+    pub started_at: DateTime<Utc>,
+    /// How long the job ran, if it has finished
+    #[serde(default)]
+    pub duration_secs: Option<u64>,
+}
+
+/// Redis Pub/Sub channel agent status changes are published on
+pub const AGENT_EVENTS_CHANNEL: &str = "raibid:agent-events";
+
+/// An agent's status changed, published as JSON on [`AGENT_EVENTS_CHANNEL`]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AgentEvent {
+    /// Agent whose status changed
+    pub agent_id: String,
+    /// New status
+    pub status: AgentStatus,
+    /// When the status changed
+    pub timestamp: DateTime<Utc>,
+}
+
+impl AgentEvent {
+    /// Event for `agent_id` entering `status` now
+    pub fn now(agent_id: impl Into<String>, status: AgentStatus) -> Self {
+        Self {
+            agent_id: agent_id.into(),
+            status,
+            timestamp: Utc::now(),
+        }
+    }
+}
+
+/// Full agent details reported by `GET /api/agents/:id`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AgentDetails {
+    /// Agent summary
+    #[serde(flatten)]
+    pub info: AgentInfo,
+    /// Agent type (e.g. `rust-builder`)
+    #[serde(default)]
+    pub agent_type: Option<String>,
+    /// When the agent last reported in
+    #[serde(default)]
+    pub last_heartbeat: Option<DateTime<Utc>>,
+    /// Jobs executed by the agent, most recent first
+    #[serde(default)]
+    pub job_history: Vec<AgentJobRecord>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_agent_details_flattened() {
+        let json = r#"{
+            "id": "agent-001",
+            "status": "busy",
+            "current_job": "job-42",
+            "cpu_percent": 71.5,
+            "memory_percent": 40.0,
+            "uptime_secs": 3600,
+            "job_history": [{
+                "job_id": "job-41",
+                "repo": "raibid-labs/raibid-cli",
+                "status": "success",
+                "started_at": "2024-01-01T00:00:00Z",
+                "duration_secs": 95
+            }]
+        }"#;
+
+        let details: AgentDetails = serde_json::from_str(json).unwrap();
+        assert_eq!(details.info.id, "agent-001");
+        assert_eq!(details.info.status, AgentStatus::Busy);
+        assert_eq!(details.info.active_jobs, 0);
+        assert!(!details.info.draining);
+        assert_eq!(details.info.drain_timeout_secs, DEFAULT_DRAIN_TIMEOUT_SECS);
+        assert_eq!(details.agent_type, None);
+        assert_eq!(details.job_history.len(), 1);
+        assert_eq!(details.job_history[0].status, JobStatus::Success);
+    }
+
+    #[test]
+    fn test_agent_event_payload() {
+        let json = r#"{"agent_id":"agent-001","status":"busy","timestamp":"2024-01-01T00:00:00Z"}"#;
+        let event: AgentEvent = serde_json::from_str(json).unwrap();
+        assert_eq!(event.agent_id, "agent-001");
+        assert_eq!(event.status, AgentStatus::Busy);
+        assert_eq!(serde_json::to_string(&event).unwrap(), json);
+    }
+
+    #[test]
+    fn test_agent_scale() {
+        let scale = AgentScale {
+            min: 1,
+            max: 5,
+            desired: Some(3),
+        };
+        assert!(scale.validate().is_ok());
+        assert_eq!(
+            serde_json::to_string(&scale).unwrap(),
+            r#"{"min":1,"max":5,"desired":3}"#
+        );
+
+        let scale: AgentScale = serde_json::from_str(r#"{"min":0,"max":2}"#).unwrap();
+        assert_eq!(scale.desired, None);
+        assert!(scale.validate().is_ok());
+
+        let invalid = |min, max, desired| AgentScale { min, max, desired }.validate().is_err();
+        assert!(invalid(3, 2, None));
+        assert!(invalid(0, 0, None));
+        assert!(invalid(1, 5, Some(6)));
+        assert!(invalid(1, 5, Some(0)));
+    }
+}
@@ -0,0 +1,594 @@
+//! raibid-server API client
+//!
+//! Blocking HTTP client for the raibid-server REST API, used by the CLI and TUI,
+//! and its async counterpart [`AsyncApiClient`].
+
+use anyhow::{anyhow, Context, Result};
+use reqwest::blocking::{Client, RequestBuilder, Response};
+use serde::de::DeserializeOwned;
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read};
+use std::time::Duration;
+
+use crate::agents::{AgentDetails, AgentInfo, AgentScale};
+use crate::auth::API_KEY_HEADER;
+use crate::config::Config;
+use crate::coverage::CoverageReport;
+use crate::dependencies::OutdatedReport;
+use crate::jobs::{
+    format_label_selector, DeadJob, ErrorLogEntry, Job, JobLogEntry, JobStatus, JobTrigger,
+    QueueStats, LOG_EVENT_DONE, LOG_EVENT_ENTRY,
+};
+use crate::schedules::{Schedule, ScheduleRequest};
+use crate::test_report::TestReport;
+
+mod async_client;
+
+pub use async_client::AsyncApiClient;
+
+/// Default request timeout
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Client for the raibid-server REST API
+#[derive(Debug, Clone)]
+pub struct ApiClient {
+    base_url: String,
+    client: Client,
+    api_key: Option<String>,
+}
+
+impl ApiClient {
+    /// Create a client for the given server base URL (e.g. `http://127.0.0.1:8080`)
+    pub fn new(base_url: impl Into<String>) -> Result<Self> {
+        let client = Client::builder()
+            .timeout(DEFAULT_TIMEOUT)
+            .build()
+            .context("Failed to build HTTP client")?;
+
+        Ok(Self {
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            client,
+            api_key: None,
+        })
+    }
+
+    /// Send `key` in the API key header with every request
+    pub fn with_api_key(mut self, key: impl Into<String>) -> Self {
+        self.api_key = Some(key.into());
+        self
+    }
+
+    /// Create a client pointing at the API server from configuration
+    pub fn from_config(config: &Config) -> Result<Self> {
+        let scheme = if config.api.tls_enabled { "https" } else { "http" };
+        let client = Self::new(format!("{}://{}:{}", scheme, config.api.host, config.api.port))?;
+
+        Ok(match &config.api.api_key {
+            Some(key) => client.with_api_key(key),
+            None => client,
+        })
+    }
+
+    /// Get the server base URL
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// Get the API key sent with every request, if any
+    pub fn api_key(&self) -> Option<&str> {
+        self.api_key.as_deref()
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}{}", self.base_url, path)
+    }
+
+    fn get(&self, path: &str) -> RequestBuilder {
+        self.authorize(self.client.get(self.url(path)))
+    }
+
+    fn post(&self, path: &str) -> RequestBuilder {
+        self.authorize(self.client.post(self.url(path)))
+    }
+
+    fn delete(&self, path: &str) -> RequestBuilder {
+        self.authorize(self.client.delete(self.url(path)))
+    }
+
+    fn authorize(&self, request: RequestBuilder) -> RequestBuilder {
+        match &self.api_key {
+            Some(key) => request.header(API_KEY_HEADER, key),
+            None => request,
+        }
+    }
+
+    /// Submit a job trigger
+    pub fn trigger_job(&self, trigger: &JobTrigger) -> Result<Job> {
+        let response = self
+            .post("/api/jobs")
+            .json(trigger)
+            .send()
+            .with_context(|| format!("Failed to connect to API server at {}", self.base_url))?;
+
+        parse_response(response)
+    }
+
+    /// List jobs known to the server with all of `labels`
+    pub fn list_jobs(&self, labels: &HashMap<String, String>) -> Result<Vec<Job>> {
+        let mut request = self.get("/api/jobs");
+        if !labels.is_empty() {
+            request = request.query(&[("labels", format_label_selector(labels))]);
+        }
+        let response = request
+            .send()
+            .with_context(|| format!("Failed to connect to API server at {}", self.base_url))?;
+
+        parse_response(response)
+    }
+
+    /// Get the test results of a job, or `None` if it has not reported any
+    pub fn test_results(&self, job_id: &str) -> Result<Option<TestReport>> {
+        let response = self
+            .get(&format!("/api/jobs/{}/test-results", job_id))
+            .send()
+            .with_context(|| format!("Failed to connect to API server at {}", self.base_url))?;
+
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        parse_response(response).map(Some)
+    }
+
+    /// Get the line coverage of a job, or `None` if it has not reported any
+    pub fn coverage(&self, job_id: &str) -> Result<Option<CoverageReport>> {
+        let response = self
+            .get(&format!("/api/jobs/{}/coverage", job_id))
+            .send()
+            .with_context(|| format!("Failed to connect to API server at {}", self.base_url))?;
+
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        parse_response(response).map(Some)
+    }
+
+    /// Get the outdated dependencies of a job, or `None` if it has not
+    /// reported any
+    pub fn dependencies(&self, job_id: &str) -> Result<Option<OutdatedReport>> {
+        let response = self
+            .get(&format!("/api/jobs/{}/dependencies", job_id))
+            .send()
+            .with_context(|| format!("Failed to connect to API server at {}", self.base_url))?;
+
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        parse_response(response).map(Some)
+    }
+
+    /// Get the build output a job has written so far
+    pub fn get_job_logs(&self, job_id: &str) -> Result<Vec<JobLogEntry>> {
+        let response = self
+            .get(&format!("/api/jobs/{}/logs", job_id))
+            .send()
+            .with_context(|| format!("Failed to connect to API server at {}", self.base_url))?;
+
+        parse_response(response)
+    }
+
+    /// Follow a job's build output until the job finishes
+    ///
+    /// Yields the output written so far, then new lines as they are written.
+    /// The request has no timeout, since a build may run for hours.
+    pub fn stream_job_logs(&self, job_id: &str) -> Result<JobLogStream<Response>> {
+        let client = Client::builder()
+            .timeout(None::<Duration>)
+            .build()
+            .context("Failed to build HTTP client")?;
+        let request = client
+            .get(self.url(&format!("/api/jobs/{}/logs", job_id)))
+            .query(&[("follow", "true")]);
+
+        let response = self
+            .authorize(request)
+            .send()
+            .with_context(|| format!("Failed to connect to API server at {}", self.base_url))?;
+
+        check_response(response).map(JobLogStream::new)
+    }
+
+    /// List jobs that failed every retry, most recent first
+    pub fn dead_jobs(&self, limit: usize) -> Result<Vec<DeadJob>> {
+        let response = self
+            .get("/api/jobs/dead")
+            .query(&[("limit", limit)])
+            .send()
+            .with_context(|| format!("Failed to connect to API server at {}", self.base_url))?;
+
+        parse_response(response)
+    }
+
+    /// Move a dead job back to the queue with its retry count reset
+    pub fn requeue_job(&self, job_id: &str) -> Result<()> {
+        let response = self
+            .post(&format!("/api/jobs/dead/{}/requeue", job_id))
+            .send()
+            .with_context(|| format!("Failed to connect to API server at {}", self.base_url))?;
+
+        check_response(response).map(|_| ())
+    }
+
+    /// Queue a finished job again under a new ID, returning the new job
+    pub fn retry_job(&self, job_id: &str) -> Result<Job> {
+        let response = self
+            .post(&format!("/api/jobs/{}/retry", job_id))
+            .send()
+            .with_context(|| format!("Failed to connect to API server at {}", self.base_url))?;
+
+        parse_response(response)
+    }
+
+    /// Get job queue statistics
+    pub fn queue_stats(&self) -> Result<QueueStats> {
+        let response = self
+            .get("/api/queue/stats")
+            .send()
+            .with_context(|| format!("Failed to connect to API server at {}", self.base_url))?;
+
+        parse_response(response)
+    }
+
+    /// Get the most recent error-level log entries across all jobs
+    pub fn recent_errors(&self, limit: usize) -> Result<Vec<ErrorLogEntry>> {
+        let response = self
+            .get("/api/logs/errors")
+            .query(&[("limit", limit)])
+            .send()
+            .with_context(|| format!("Failed to connect to API server at {}", self.base_url))?;
+
+        parse_response(response)
+    }
+
+    /// List all registered agents
+    pub fn list_agents(&self) -> Result<Vec<AgentInfo>> {
+        let response = self
+            .get("/api/agents")
+            .send()
+            .with_context(|| format!("Failed to connect to API server at {}", self.base_url))?;
+
+        parse_response(response)
+    }
+
+    /// Set the range the agents are scaled within, returning the range applied
+    pub fn scale_agents(&self, scale: &AgentScale) -> Result<AgentScale> {
+        let response = self
+            .post("/api/agents/scale")
+            .json(scale)
+            .send()
+            .with_context(|| format!("Failed to connect to API server at {}", self.base_url))?;
+
+        parse_response(response)
+    }
+
+    /// Get details and job history for a single agent
+    pub fn get_agent(&self, id: &str) -> Result<AgentDetails> {
+        let response = self
+            .get(&format!("/api/agents/{}", id))
+            .send()
+            .with_context(|| format!("Failed to connect to API server at {}", self.base_url))?;
+
+        parse_response(response)
+    }
+
+    /// Stop dispatching jobs to an agent, returning its state
+    pub fn drain_agent(&self, id: &str) -> Result<AgentInfo> {
+        let response = self
+            .post(&format!("/api/agents/{}/drain", id))
+            .send()
+            .with_context(|| format!("Failed to connect to API server at {}", self.base_url))?;
+
+        parse_response(response)
+    }
+
+    /// Replace the Kubernetes pod running an agent
+    pub fn restart_agent(&self, id: &str) -> Result<()> {
+        let response = self
+            .post(&format!("/api/agents/{}/restart", id))
+            .send()
+            .with_context(|| format!("Failed to connect to API server at {}", self.base_url))?;
+
+        check_response(response).map(|_| ())
+    }
+
+    /// Create a build schedule
+    pub fn create_schedule(&self, request: &ScheduleRequest) -> Result<Schedule> {
+        let response = self
+            .post("/api/schedules")
+            .json(request)
+            .send()
+            .with_context(|| format!("Failed to connect to API server at {}", self.base_url))?;
+
+        parse_response(response)
+    }
+
+    /// List build schedules, oldest first
+    pub fn list_schedules(&self) -> Result<Vec<Schedule>> {
+        let response = self
+            .get("/api/schedules")
+            .send()
+            .with_context(|| format!("Failed to connect to API server at {}", self.base_url))?;
+
+        parse_response(response)
+    }
+
+    /// Delete a build schedule
+    pub fn delete_schedule(&self, id: &str) -> Result<()> {
+        let response = self
+            .delete(&format!("/api/schedules/{}", id))
+            .send()
+            .with_context(|| format!("Failed to connect to API server at {}", self.base_url))?;
+
+        check_response(response).map(|_| ())
+    }
+}
+
+/// Log lines of a job read from the server-sent events of
+/// `GET /api/jobs/:id/logs?follow=true`
+///
+/// Ends once the server reports that the job finished, or if the connection
+/// closes first.
+pub struct JobLogStream<R> {
+    reader: BufReader<R>,
+    finished: bool,
+    status: Option<JobStatus>,
+}
+
+impl<R: Read> JobLogStream<R> {
+    /// Read events from `reader`
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader: BufReader::new(reader),
+            finished: false,
+            status: None,
+        }
+    }
+
+    /// Final status of the job, once the stream has ended with it
+    pub fn status(&self) -> Option<JobStatus> {
+        self.status
+    }
+
+    /// Read the next event as `(event, data)`, `None` at the end of the
+    /// stream
+    fn next_event(&mut self) -> Result<Option<(String, String)>> {
+        let (mut event, mut data) = (String::new(), Vec::new());
+        loop {
+            let mut line = String::new();
+            if self
+                .reader
+                .read_line(&mut line)
+                .context("Failed to read job logs")?
+                == 0
+            {
+                return Ok(None);
+            }
+
+            let line = line.trim_end_matches(['\r', '\n']);
+            if line.is_empty() {
+                if !event.is_empty() || !data.is_empty() {
+                    return Ok(Some((event, data.join("\n"))));
+                }
+                continue;
+            }
+
+            // Lines starting with a colon are comments, e.g. keep-alives
+            let (field, value) = line.split_once(':').unwrap_or((line, ""));
+            let value = value.strip_prefix(' ').unwrap_or(value);
+            match field {
+                "event" => event = value.to_string(),
+                "data" => data.push(value.to_string()),
+                _ => {}
+            }
+        }
+    }
+}
+
+impl<R: Read> Iterator for JobLogStream<R> {
+    type Item = Result<JobLogEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.finished {
+            let (event, data) = match self.next_event() {
+                Ok(Some(event)) => event,
+                Ok(None) => break,
+                Err(e) => {
+                    self.finished = true;
+                    return Some(Err(e));
+                }
+            };
+
+            match event.as_str() {
+                LOG_EVENT_ENTRY => {
+                    return Some(serde_json::from_str(&data).context("Failed to parse log line"))
+                }
+                LOG_EVENT_DONE => {
+                    self.finished = true;
+                    self.status = data.parse().ok();
+                }
+                _ => {}
+            }
+        }
+        None
+    }
+}
+
+/// Deserialize a successful response or turn an error status into an error
+fn parse_response<T: DeserializeOwned>(response: Response) -> Result<T> {
+    check_response(response)?
+        .json()
+        .context("Failed to parse API response")
+}
+
+/// Turn an error status into an error
+fn check_response(response: Response) -> Result<Response> {
+    let status = response.status();
+    if !status.is_success() {
+        let body = response.text().unwrap_or_default();
+        return Err(anyhow!("API request failed ({}): {}", status, body.trim()));
+    }
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_base_url_trailing_slash() {
+        let client = ApiClient::new("http://localhost:8080/").unwrap();
+        assert_eq!(client.base_url(), "http://localhost:8080");
+        assert_eq!(client.url("/api/jobs"), "http://localhost:8080/api/jobs");
+    }
+
+    #[test]
+    fn test_from_config() {
+        let mut config = Config::default();
+        let client = ApiClient::from_config(&config).unwrap();
+        assert_eq!(client.base_url(), "http://127.0.0.1:8080");
+
+        config.api.tls_enabled = true;
+        let client = ApiClient::from_config(&config).unwrap();
+        assert_eq!(client.base_url(), "https://127.0.0.1:8080");
+        assert_eq!(client.api_key, None);
+
+        config.api.api_key = Some("rbd_secret".to_string());
+        let client = ApiClient::from_config(&config).unwrap();
+        assert_eq!(client.api_key.as_deref(), Some("rbd_secret"));
+    }
+
+    #[test]
+    fn test_test_results() {
+        let mut server = mockito::Server::new();
+        server
+            .mock("GET", "/api/jobs/job-1/test-results")
+            .with_body(
+                r#"{"suites":[{"name":"raibid-common","tests":3,"failures":1,"errors":0,
+                    "cases":[]}]}"#,
+            )
+            .create();
+        server
+            .mock("GET", "/api/jobs/job-2/test-results")
+            .with_status(404)
+            .with_body(r#"{"error":"No test results for job job-2"}"#)
+            .create();
+
+        let client = ApiClient::new(server.url()).unwrap();
+        let report = client.test_results("job-1").unwrap().unwrap();
+        assert_eq!(report.tests(), 3);
+        assert_eq!(report.passed(), 2);
+        assert_eq!(client.test_results("job-2").unwrap(), None);
+    }
+
+    #[test]
+    fn test_requeue_job() {
+        let mut server = mockito::Server::new();
+        server
+            .mock("POST", "/api/jobs/dead/job-1/requeue")
+            .with_status(204)
+            .create();
+        server
+            .mock("POST", "/api/jobs/dead/job-2/requeue")
+            .with_status(404)
+            .with_body(r#"{"error":"Job job-2 is not a dead job"}"#)
+            .create();
+
+        let client = ApiClient::new(server.url()).unwrap();
+        client.requeue_job("job-1").unwrap();
+        let err = client.requeue_job("job-2").unwrap_err().to_string();
+        assert!(err.contains("not a dead job"));
+    }
+
+    const LOG_EVENTS: &str = "event: log\n\
+        data: {\"timestamp\":\"2024-01-01T12:00:00Z\",\"message\":\"Compiling raibid-cli\"}\n\
+        \n\
+        :\n\
+        \n\
+        event: log\r\n\
+        data: {\"timestamp\":\"2024-01-01T12:00:01Z\",\"message\":\"test result: ok\"}\r\n\
+        \r\n\
+        event: done\n\
+        data: success\n\
+        \n\
+        event: log\n\
+        data: {\"timestamp\":\"2024-01-01T12:00:02Z\",\"message\":\"after done\"}\n\
+        \n";
+
+    #[test]
+    fn test_job_log_stream() {
+        let mut stream = JobLogStream::new(LOG_EVENTS.as_bytes());
+        let messages: Vec<String> = stream
+            .by_ref()
+            .map(|entry| entry.unwrap().message)
+            .collect();
+        assert_eq!(messages, vec!["Compiling raibid-cli", "test result: ok"]);
+        assert_eq!(stream.status(), Some(JobStatus::Success));
+
+        // A connection closed before the job finished ends without a status
+        let truncated = &LOG_EVENTS[..LOG_EVENTS.find("event: done").unwrap()];
+        let mut stream = JobLogStream::new(truncated.as_bytes());
+        assert_eq!(stream.by_ref().count(), 2);
+        assert_eq!(stream.status(), None);
+
+        let mut stream = JobLogStream::new("event: log\ndata: not json\n\n".as_bytes());
+        assert!(stream.next().unwrap().is_err());
+    }
+
+    #[test]
+    fn test_stream_job_logs() {
+        let mut server = mockito::Server::new();
+        server
+            .mock("GET", "/api/jobs/job-1/logs")
+            .match_query(mockito::Matcher::UrlEncoded(
+                "follow".to_string(),
+                "true".to_string(),
+            ))
+            .match_header(API_KEY_HEADER, "rbd_secret")
+            .with_header("content-type", "text/event-stream")
+            .with_body(LOG_EVENTS)
+            .create();
+
+        let client = ApiClient::new(server.url())
+            .unwrap()
+            .with_api_key("rbd_secret");
+        let mut stream = client.stream_job_logs("job-1").unwrap();
+        assert_eq!(stream.by_ref().count(), 2);
+        assert_eq!(stream.status(), Some(JobStatus::Success));
+    }
+
+    #[test]
+    fn test_retry_job() {
+        let mut server = mockito::Server::new();
+        server
+            .mock("POST", "/api/jobs/job-1/retry")
+            .with_status(201)
+            .with_body(
+                r#"{"id":"job-2","repo":"raibid-labs/raibid-cli","branch":"main",
+                    "status":"pending","created_at":"2024-01-01T12:00:00Z"}"#,
+            )
+            .create();
+        server
+            .mock("POST", "/api/jobs/job-3/retry")
+            .with_status(409)
+            .with_body(
+                r#"{"error":"Job job-3 is running and can only be retried once it has finished"}"#,
+            )
+            .create();
+
+        let client = ApiClient::new(server.url()).unwrap();
+        let job = client.retry_job("job-1").unwrap();
+        assert_eq!(job.id, "job-2");
+        let err = client.retry_job("job-3").unwrap_err().to_string();
+        assert!(err.contains("409"));
+        assert!(err.contains("is running"));
+    }
+}
@@ -0,0 +1,276 @@
+//! Async raibid-server API client
+//!
+//! [`AsyncApiClient`] covers the requests async callers make, such as the TUI
+//! poller, without blocking an executor thread. The synchronous CLI commands
+//! keep using the blocking [`ApiClient`].
+
+use anyhow::{anyhow, Context, Result};
+use reqwest::{Client, RequestBuilder, Response};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use std::collections::HashMap;
+
+use super::{ApiClient, DEFAULT_TIMEOUT};
+use crate::agents::AgentInfo;
+use crate::auth::API_KEY_HEADER;
+use crate::config::Config;
+use crate::jobs::{format_label_selector, Job, JobLogEntry, JobStatus, JobTrigger, QueueStats};
+
+/// Async client for the raibid-server REST API
+#[derive(Debug, Clone)]
+pub struct AsyncApiClient {
+    base_url: String,
+    client: Client,
+    api_key: Option<String>,
+}
+
+/// Body of `GET /api/jobs/:id/status`
+#[derive(Debug, Deserialize)]
+struct JobStatusBody {
+    status: JobStatus,
+}
+
+impl AsyncApiClient {
+    /// Create a client for the given server base URL (e.g. `http://127.0.0.1:8080`)
+    pub fn new(base_url: impl Into<String>) -> Result<Self> {
+        let client = Client::builder()
+            .timeout(DEFAULT_TIMEOUT)
+            .build()
+            .context("Failed to build HTTP client")?;
+
+        Ok(Self {
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            client,
+            api_key: None,
+        })
+    }
+
+    /// Send `key` in the API key header with every request
+    pub fn with_api_key(mut self, key: impl Into<String>) -> Self {
+        self.api_key = Some(key.into());
+        self
+    }
+
+    /// Create a client pointing at the API server from configuration
+    pub fn from_config(config: &Config) -> Result<Self> {
+        Self::try_from(&ApiClient::from_config(config)?)
+    }
+
+    /// Get the server base URL
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}{}", self.base_url, path)
+    }
+
+    fn get(&self, path: &str) -> RequestBuilder {
+        self.authorize(self.client.get(self.url(path)))
+    }
+
+    fn post(&self, path: &str) -> RequestBuilder {
+        self.authorize(self.client.post(self.url(path)))
+    }
+
+    fn authorize(&self, request: RequestBuilder) -> RequestBuilder {
+        match &self.api_key {
+            Some(key) => request.header(API_KEY_HEADER, key),
+            None => request,
+        }
+    }
+
+    /// Send `request`, reporting connection failures against the server URL
+    async fn send(&self, request: RequestBuilder) -> Result<Response> {
+        request
+            .send()
+            .await
+            .with_context(|| format!("Failed to connect to API server at {}", self.base_url))
+    }
+
+    /// Submit a job trigger
+    pub async fn trigger_job(&self, trigger: &JobTrigger) -> Result<Job> {
+        let response = self.send(self.post("/api/jobs").json(trigger)).await?;
+        parse_response(response).await
+    }
+
+    /// List jobs known to the server with all of `labels`
+    pub async fn list_jobs(&self, labels: &HashMap<String, String>) -> Result<Vec<Job>> {
+        let mut request = self.get("/api/jobs");
+        if !labels.is_empty() {
+            request = request.query(&[("labels", format_label_selector(labels))]);
+        }
+        let response = self.send(request).await?;
+        parse_response(response).await
+    }
+
+    /// Get the current status of a job
+    pub async fn job_status(&self, job_id: &str) -> Result<JobStatus> {
+        let response = self
+            .send(self.get(&format!("/api/jobs/{}/status", job_id)))
+            .await?;
+        parse_response::<JobStatusBody>(response)
+            .await
+            .map(|body| body.status)
+    }
+
+    /// Get the build output a job has written so far
+    pub async fn get_job_logs(&self, job_id: &str) -> Result<Vec<JobLogEntry>> {
+        let response = self
+            .send(self.get(&format!("/api/jobs/{}/logs", job_id)))
+            .await?;
+        parse_response(response).await
+    }
+
+    /// Get job queue statistics
+    pub async fn queue_stats(&self) -> Result<QueueStats> {
+        let response = self.send(self.get("/api/queue/stats")).await?;
+        parse_response(response).await
+    }
+
+    /// List all registered agents
+    pub async fn list_agents(&self) -> Result<Vec<AgentInfo>> {
+        let response = self.send(self.get("/api/agents")).await?;
+        parse_response(response).await
+    }
+}
+
+impl TryFrom<&ApiClient> for AsyncApiClient {
+    type Error = anyhow::Error;
+
+    /// Async client for the same server and API key
+    fn try_from(client: &ApiClient) -> Result<Self> {
+        let async_client = Self::new(client.base_url.clone())?;
+        Ok(match &client.api_key {
+            Some(key) => async_client.with_api_key(key),
+            None => async_client,
+        })
+    }
+}
+
+impl From<AsyncApiClient> for ApiClient {
+    /// Blocking client for the same server and API key
+    ///
+    /// Building a blocking client starts a thread and waits for it, so on a
+    /// multi-threaded runtime it is built in
+    /// [`block_in_place`](tokio::task::block_in_place) to keep other tasks
+    /// running. Its requests must still be made outside async code.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the HTTP client cannot be built, like `reqwest`'s
+    /// `Client::new`.
+    fn from(client: AsyncApiClient) -> Self {
+        let AsyncApiClient {
+            base_url, api_key, ..
+        } = client;
+        let build = move || {
+            let client = ApiClient::new(base_url).expect("Failed to build HTTP client");
+            match api_key {
+                Some(key) => client.with_api_key(key),
+                None => client,
+            }
+        };
+
+        match tokio::runtime::Handle::try_current() {
+            Ok(handle) if handle.runtime_flavor() == tokio::runtime::RuntimeFlavor::MultiThread => {
+                tokio::task::block_in_place(build)
+            }
+            _ => build(),
+        }
+    }
+}
+
+/// Deserialize a successful response or turn an error status into an error
+async fn parse_response<T: DeserializeOwned>(response: Response) -> Result<T> {
+    check_response(response)
+        .await?
+        .json()
+        .await
+        .context("Failed to parse API response")
+}
+
+/// Turn an error status into an error
+async fn check_response(response: Response) -> Result<Response> {
+    let status = response.status();
+    if !status.is_success() {
+        let body = response.text().await.unwrap_or_default();
+        return Err(anyhow!("API request failed ({}): {}", status, body.trim()));
+    }
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_list_jobs_and_status() {
+        let mut server = mockito::Server::new_async().await;
+        let jobs = server
+            .mock("GET", "/api/jobs")
+            .match_query(mockito::Matcher::UrlEncoded(
+                "labels".into(),
+                "team=infra".into(),
+            ))
+            .match_header(API_KEY_HEADER, "rbd_secret")
+            .with_body(
+                r#"[{"id":"job-1","repo":"raibid/core","branch":"main","commit":"abc123",
+                     "status":"running",
+                     "created_at":"2024-01-01T00:00:00Z"}]"#,
+            )
+            .create_async()
+            .await;
+        server
+            .mock("GET", "/api/jobs/job-1/status")
+            .with_body(r#"{"id":"job-1","status":"running"}"#)
+            .create_async()
+            .await;
+        server
+            .mock("GET", "/api/jobs/job-2/status")
+            .with_status(404)
+            .with_body(r#"{"error":"Job job-2 not found"}"#)
+            .create_async()
+            .await;
+
+        let client = AsyncApiClient::new(format!("{}/", server.url()))
+            .unwrap()
+            .with_api_key("rbd_secret");
+        assert_eq!(client.base_url(), server.url());
+
+        let labels = HashMap::from([("team".to_string(), "infra".to_string())]);
+        let listed = client.list_jobs(&labels).await.unwrap();
+        assert_eq!(listed[0].id, "job-1");
+        jobs.assert_async().await;
+
+        assert_eq!(
+            client.job_status("job-1").await.unwrap(),
+            JobStatus::Running
+        );
+        let err = client.job_status("job-2").await.unwrap_err().to_string();
+        assert!(err.contains("404"));
+        assert!(err.contains("not found"));
+    }
+
+    #[tokio::test]
+    async fn test_connection_error() {
+        // Nothing listens on the discard port
+        let client = AsyncApiClient::new("http://127.0.0.1:9").unwrap();
+        let err = client.queue_stats().await.unwrap_err().to_string();
+        assert!(err.contains("Failed to connect to API server at http://127.0.0.1:9"));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_into_blocking_client() {
+        let client = AsyncApiClient::new("http://localhost:8080")
+            .unwrap()
+            .with_api_key("rbd_secret");
+        let blocking = ApiClient::from(client);
+        assert_eq!(blocking.base_url(), "http://localhost:8080");
+        assert_eq!(blocking.api_key.as_deref(), Some("rbd_secret"));
+
+        let client = AsyncApiClient::try_from(&blocking).unwrap();
+        assert_eq!(client.base_url(), "http://localhost:8080");
+        assert_eq!(client.api_key.as_deref(), Some("rbd_secret"));
+    }
+}
@@ -0,0 +1,61 @@
+//! Build artifact types
+//!
+//! Agents record the binaries a job built in an [`ArtifactMetadata`] and
+//! store it in Redis under [`artifacts_key`](crate::jobs::artifacts_key).
+
+use serde::{Deserialize, Serialize};
+
+/// Artifacts produced by a job
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ArtifactMetadata {
+    /// Built binaries as `<target triple>/<file name>`, e.g.
+    /// `aarch64-unknown-linux-gnu/raibid-agent`
+    #[serde(default)]
+    pub binaries: Vec<String>,
+}
+
+impl ArtifactMetadata {
+    /// File names of the binaries built for `target`
+    pub fn binaries_for<'a>(&'a self, target: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        self.binaries.iter().filter_map(move |binary| {
+            binary
+                .strip_prefix(target)
+                .and_then(|rest| rest.strip_prefix('/'))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_binaries_for_target() {
+        let artifacts = ArtifactMetadata {
+            binaries: vec![
+                "x86_64-unknown-linux-gnu/app".to_string(),
+                "x86_64-unknown-linux-gnu/app-cli".to_string(),
+                "x86_64-unknown-linux-gnux32/app".to_string(),
+                "x86_64-pc-windows-gnu/app.exe".to_string(),
+            ],
+        };
+        assert_eq!(
+            artifacts
+                .binaries_for("x86_64-unknown-linux-gnu")
+                .collect::<Vec<_>>(),
+            vec!["app", "app-cli"]
+        );
+        assert_eq!(
+            artifacts
+                .binaries_for("x86_64-pc-windows-gnu")
+                .collect::<Vec<_>>(),
+            vec!["app.exe"]
+        );
+
+        let json = serde_json::to_string(&artifacts).unwrap();
+        assert_eq!(
+            serde_json::from_str::<ArtifactMetadata>(&json).unwrap(),
+            artifacts
+        );
+    }
+}
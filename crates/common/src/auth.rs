@@ -0,0 +1,80 @@
+//! API key helpers
+//!
+//! Clients authenticate to raibid-server by sending an API key in the
+//! [`API_KEY_HEADER`] header. The server only keeps SHA-256 hashes of its
+//! keys; configured keys may be given either in plain text or already hashed
+//! as `sha256:<hex>` (the form printed by `raibid api-key generate`).
+
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+
+/// Request header carrying the API key
+pub const API_KEY_HEADER: &str = "X-Raibid-Api-Key";
+
+/// Request header carrying the admin key on `/admin/` routes
+pub const ADMIN_KEY_HEADER: &str = "X-Admin-Key";
+
+/// Prefix marking a configured key as an already-hashed value
+pub const HASH_PREFIX: &str = "sha256:";
+
+/// Prefix of generated API keys, making them easy to spot in configs and logs
+const KEY_PREFIX: &str = "rbd_";
+
+/// Number of random characters in a generated key
+const KEY_LENGTH: usize = 40;
+
+/// Generate a new random API key
+pub fn generate_api_key() -> String {
+    let random: String = rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(KEY_LENGTH)
+        .map(char::from)
+        .collect();
+    format!("{}{}", KEY_PREFIX, random)
+}
+
+/// Hash an API key, returning `sha256:<hex>`
+pub fn hash_api_key(key: &str) -> String {
+    format!("{}{}", HASH_PREFIX, sha256::digest(key))
+}
+
+/// Hash of a configured key, which may be plain text or already hashed
+pub fn configured_key_hash(entry: &str) -> String {
+    let entry = entry.trim();
+    match entry.strip_prefix(HASH_PREFIX) {
+        Some(hex) => format!("{}{}", HASH_PREFIX, hex.to_ascii_lowercase()),
+        None => hash_api_key(entry),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generate_api_key() {
+        let key = generate_api_key();
+        assert!(key.starts_with(KEY_PREFIX));
+        assert_eq!(key.len(), KEY_PREFIX.len() + KEY_LENGTH);
+        assert_ne!(key, generate_api_key());
+    }
+
+    #[test]
+    fn test_hash_api_key() {
+        assert_eq!(
+            hash_api_key("abc"),
+            "sha256:ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+
+    #[test]
+    fn test_configured_key_hash() {
+        let hash = hash_api_key("rbd_secret");
+        assert_eq!(configured_key_hash("rbd_secret"), hash);
+        assert_eq!(configured_key_hash(&hash), hash);
+        assert_eq!(
+            configured_key_hash(&hash.to_uppercase().replace("SHA256:", "sha256:")),
+            hash
+        );
+    }
+}
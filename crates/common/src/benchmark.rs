@@ -0,0 +1,171 @@
+//! Benchmark result types
+//!
+//! Agents parse the output of a `bench` step into [`BenchResult`]s and store
+//! them in Redis under [`benchmarks_key`](crate::jobs::benchmarks_key) and,
+//! for comparisons between branches,
+//! [`latest_benchmarks_key`](crate::jobs::latest_benchmarks_key).
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Regression threshold used when none is configured, in percent
+pub const DEFAULT_REGRESSION_THRESHOLD_PCT: f64 = 10.0;
+
+/// A single benchmark measurement
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct BenchResult {
+    /// Benchmark name
+    pub name: String,
+    /// Time per iteration in nanoseconds
+    pub nanoseconds_per_iter: u64,
+    /// Deviation between runs in nanoseconds (the `+/-` value)
+    pub variance: u64,
+}
+
+impl BenchResult {
+    /// Parse one line of Bencher format output
+    ///
+    /// Lines look like `test parse::large ... bench:   1,234 ns/iter (+/- 56)`;
+    /// anything else yields `None`.
+    pub fn parse_bencher_line(line: &str) -> Option<Self> {
+        let rest = line.trim().strip_prefix("test ")?;
+        let (name, rest) = rest.split_once(" ... bench:")?;
+        let (time, rest) = rest.trim().split_once(" ns/iter")?;
+        let variance = rest.trim().strip_prefix("(+/-")?.trim().strip_suffix(')')?;
+
+        Some(Self {
+            name: name.trim().to_string(),
+            nanoseconds_per_iter: parse_number(time)?,
+            variance: parse_number(variance)?,
+        })
+    }
+
+    /// Parse every benchmark line in Bencher format output, ignoring the rest
+    pub fn parse_bencher_output(output: &str) -> Vec<Self> {
+        output
+            .lines()
+            .filter_map(Self::parse_bencher_line)
+            .collect()
+    }
+}
+
+/// Parse a number with optional thousands separators (`1,234`)
+fn parse_number(s: &str) -> Option<u64> {
+    s.trim().replace(',', "").parse().ok()
+}
+
+/// Change of one benchmark between a base and a head branch
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct BenchComparison {
+    /// Benchmark name
+    pub name: String,
+    /// Nanoseconds per iteration on the base branch
+    pub base_ns: u64,
+    /// Nanoseconds per iteration on the head branch
+    pub head_ns: u64,
+    /// Change from base to head in percent; positive means slower
+    pub percent_change: f64,
+    /// Whether the head branch is slower by more than the threshold
+    pub regression: bool,
+}
+
+/// Compare the benchmarks two branches have in common
+///
+/// Benchmarks present on only one side are left out. The result is sorted by
+/// name.
+pub fn compare_benchmarks(
+    base: &[BenchResult],
+    head: &[BenchResult],
+    threshold_pct: f64,
+) -> Vec<BenchComparison> {
+    let base: BTreeMap<&str, u64> = base
+        .iter()
+        .map(|b| (b.name.as_str(), b.nanoseconds_per_iter))
+        .collect();
+    let head: BTreeMap<&str, u64> = head
+        .iter()
+        .map(|b| (b.name.as_str(), b.nanoseconds_per_iter))
+        .collect();
+
+    base.iter()
+        .filter_map(|(name, &base_ns)| {
+            let head_ns = *head.get(name)?;
+            let percent_change = if base_ns == 0 {
+                0.0
+            } else {
+                (head_ns as f64 - base_ns as f64) / base_ns as f64 * 100.0
+            };
+
+            Some(BenchComparison {
+                name: name.to_string(),
+                base_ns,
+                head_ns,
+                percent_change,
+                regression: percent_change > threshold_pct,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bench(name: &str, ns: u64) -> BenchResult {
+        BenchResult {
+            name: name.to_string(),
+            nanoseconds_per_iter: ns,
+            variance: 0,
+        }
+    }
+
+    #[test]
+    fn test_parse_bencher_output() {
+        let output = "\
+   Compiling raibid v0.1.0
+running 3 tests
+test parse::small ... bench:         512 ns/iter (+/- 12)
+test parse::large ... bench:   1,234,567 ns/iter (+/- 8,910)
+test parse::ignored ... ignored
+
+test result: ok. 0 passed; 0 failed; 1 ignored; 2 measured
+";
+
+        let results = BenchResult::parse_bencher_output(output);
+        assert_eq!(
+            results,
+            vec![
+                BenchResult {
+                    name: "parse::small".to_string(),
+                    nanoseconds_per_iter: 512,
+                    variance: 12,
+                },
+                BenchResult {
+                    name: "parse::large".to_string(),
+                    nanoseconds_per_iter: 1_234_567,
+                    variance: 8_910,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_compare_benchmarks() {
+        let base = vec![bench("a", 100), bench("b", 100), bench("removed", 50)];
+        let head = vec![bench("a", 115), bench("b", 95), bench("added", 50)];
+
+        let comparisons = compare_benchmarks(&base, &head, DEFAULT_REGRESSION_THRESHOLD_PCT);
+        assert_eq!(comparisons.len(), 2);
+
+        assert_eq!(comparisons[0].name, "a");
+        assert!((comparisons[0].percent_change - 15.0).abs() < 1e-9);
+        assert!(comparisons[0].regression);
+
+        assert_eq!(comparisons[1].name, "b");
+        assert!((comparisons[1].percent_change + 5.0).abs() < 1e-9);
+        assert!(!comparisons[1].regression);
+
+        // A higher threshold tolerates the slowdown
+        assert!(!compare_benchmarks(&base, &head, 20.0)[0].regression);
+    }
+}
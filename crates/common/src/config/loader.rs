@@ -6,10 +6,16 @@
 //! - User file (~/.config/raibid/config.yaml)
 //! - System file (/etc/raibid/config.yaml)
 //! - Defaults (lowest priority)
+//!
+//! `RAIBID_PROFILE` selects a profile (`dev` unless set). Its overlay,
+//! `~/.config/raibid/config.<profile>.yaml`, is applied on top of the
+//! merged files when it exists, and `RAIBID_<PROFILE>_<SECTION>_<KEY>`
+//! variables take precedence over `RAIBID_<SECTION>_<KEY>`.
 
-use super::schema::Config;
+use super::schema::{Config, DEFAULT_PROFILE};
 use anyhow::{Context, Result};
 use regex::Regex;
+use serde_yaml::Value;
 use std::env;
 use std::fs;
 use std::path::{Path, PathBuf};
@@ -46,15 +52,49 @@ pub fn discover_config_files() -> Vec<PathBuf> {
     paths
 }
 
+/// Environment variable selecting the configuration profile
+pub const PROFILE_ENV: &str = "RAIBID_PROFILE";
+
+/// Profiles a configuration can be loaded for
+pub const PROFILES: [&str; 4] = ["dev", "staging", "prod", "test"];
+
+/// Profile selected by `RAIBID_PROFILE`, or the default profile
+pub fn selected_profile() -> Result<String> {
+    match env::var(PROFILE_ENV) {
+        Ok(profile) => validate_profile(&profile).map(|_| profile),
+        Err(_) => Ok(DEFAULT_PROFILE.to_string()),
+    }
+}
+
+/// Check that `profile` is one of [`PROFILES`]
+pub fn validate_profile(profile: &str) -> Result<()> {
+    if !PROFILES.contains(&profile) {
+        anyhow::bail!(
+            "Unknown profile '{}', expected one of: {}",
+            profile,
+            PROFILES.join(", ")
+        );
+    }
+    Ok(())
+}
+
+/// Overlay file of a profile: ~/.config/raibid/config.<profile>.yaml
+pub fn profile_config_file(profile: &str) -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("raibid").join(format!("config.{}.yaml", profile)))
+}
+
 /// Load and merge configuration from all sources
 ///
 /// Precedence (highest to lowest):
-/// 1. Environment variables (RAIBID_*)
-/// 2. Local file (./raibid.yaml)
-/// 3. User file (~/.config/raibid/config.yaml)
-/// 4. System file (/etc/raibid/config.yaml)
-/// 5. Default values
+/// 1. Environment variables (RAIBID_<PROFILE>_*, then RAIBID_*)
+/// 2. Profile overlay (~/.config/raibid/config.<profile>.yaml)
+/// 3. Local file (./raibid.yaml)
+/// 4. User file (~/.config/raibid/config.yaml)
+/// 5. System file (/etc/raibid/config.yaml)
+/// 6. Default values
 pub fn load_config() -> Result<Config> {
+    let profile = selected_profile()?;
+
     // Start with defaults
     let mut config = Config::default();
 
@@ -65,6 +105,12 @@ pub fn load_config() -> Result<Config> {
         config = merge_configs(config, file_config);
     }
 
+    // Apply the profile overlay on top
+    if let Some(path) = profile_config_file(&profile).filter(|path| path.exists()) {
+        config = merge_overlay(config, &path)?;
+    }
+    config.profile = Some(profile);
+
     // Expand paths (~ -> home directory)
     config = expand_paths(config)?;
 
@@ -93,6 +139,45 @@ pub fn load_config_file(path: &Path) -> Result<Config> {
     Ok(config)
 }
 
+/// Apply the overlay file at `path` on top of `config`
+///
+/// Unlike [`merge_configs`], settings missing from the overlay keep their
+/// value from `config`, so an overlay only needs the settings that differ.
+pub fn merge_overlay(config: Config, path: &Path) -> Result<Config> {
+    let contents = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read config file: {}", path.display()))?;
+    let overlay: Value = serde_yaml::from_str(&contents)
+        .with_context(|| format!("Failed to parse config file: {}", path.display()))?;
+    if overlay.is_null() {
+        return Ok(config);
+    }
+
+    let profile = config.profile.clone();
+    let mut merged = serde_yaml::to_value(&config).context("Failed to serialize config")?;
+    merge_values(&mut merged, overlay);
+    let mut config: Config = serde_path_to_error::deserialize(merged)
+        .with_context(|| format!("Failed to parse config file: {}", path.display()))?;
+    config.profile = profile;
+    Ok(config)
+}
+
+/// Merge `overlay` into `base`, recursing into mappings
+fn merge_values(base: &mut Value, overlay: Value) {
+    match (base, overlay) {
+        (Value::Mapping(base), Value::Mapping(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(existing) => merge_values(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
 /// Merge two configurations (base + override)
 ///
 /// For scalars and options: override wins
@@ -105,6 +190,7 @@ fn merge_configs(_base: Config, override_cfg: Config) -> Config {
         gitea: override_cfg.gitea,
         redis: override_cfg.redis,
         ui: override_cfg.ui,
+        profile: override_cfg.profile,
     }
 }
 
@@ -189,85 +275,99 @@ pub fn substitute_env_vars(mut config: Config) -> Result<Config> {
 /// - RAIBID_CLUSTER_NAME
 /// - RAIBID_API_PORT
 /// - RAIBID_AGENTS_MAX_AGENTS
+///
+/// A variable for the active profile, such as RAIBID_PROD_REDIS_PASSWORD,
+/// wins over the same variable without the profile.
 pub fn apply_env_overrides(mut config: Config) -> Result<Config> {
+    let profile_prefix = format!("RAIBID_{}_", config.active_profile().to_uppercase());
+    let var = |name: &str| {
+        let key = name.trim_start_matches("RAIBID_");
+        env::var(format!("{}{}", profile_prefix, key))
+            .or_else(|_| env::var(name))
+            .ok()
+    };
+
     // Cluster overrides
-    if let Ok(val) = env::var("RAIBID_CLUSTER_NAME") {
+    if let Some(val) = var("RAIBID_CLUSTER_NAME") {
         config.cluster.name = val;
     }
-    if let Ok(val) = env::var("RAIBID_CLUSTER_API_PORT") {
+    if let Some(val) = var("RAIBID_CLUSTER_API_PORT") {
         config.cluster.api_port = val.parse().context("Invalid RAIBID_CLUSTER_API_PORT")?;
     }
-    if let Ok(val) = env::var("RAIBID_CLUSTER_NAMESPACE") {
+    if let Some(val) = var("RAIBID_CLUSTER_NAMESPACE") {
         config.cluster.namespace = val;
     }
-    if let Ok(val) = env::var("RAIBID_CLUSTER_RESERVED_CORES") {
+    if let Some(val) = var("RAIBID_CLUSTER_RESERVED_CORES") {
         config.cluster.reserved_cores = val
             .parse()
             .context("Invalid RAIBID_CLUSTER_RESERVED_CORES")?;
     }
-    if let Ok(val) = env::var("RAIBID_CLUSTER_RESERVED_MEMORY_GB") {
+    if let Some(val) = var("RAIBID_CLUSTER_RESERVED_MEMORY_GB") {
         config.cluster.reserved_memory_gb = val
             .parse()
             .context("Invalid RAIBID_CLUSTER_RESERVED_MEMORY_GB")?;
     }
 
     // API overrides
-    if let Ok(val) = env::var("RAIBID_API_HOST") {
+    if let Some(val) = var("RAIBID_API_HOST") {
         config.api.host = val;
     }
-    if let Ok(val) = env::var("RAIBID_API_PORT") {
+    if let Some(val) = var("RAIBID_API_PORT") {
         config.api.port = val.parse().context("Invalid RAIBID_API_PORT")?;
     }
-    if let Ok(val) = env::var("RAIBID_API_TLS_ENABLED") {
+    if let Some(val) = var("RAIBID_API_TLS_ENABLED") {
         config.api.tls_enabled = val.parse().context("Invalid RAIBID_API_TLS_ENABLED")?;
     }
+    if let Some(val) = var("RAIBID_API_KEY") {
+        config.api.api_key = Some(val);
+    }
 
     // Agent overrides
-    if let Ok(val) = env::var("RAIBID_AGENTS_MIN_AGENTS") {
+    if let Some(val) = var("RAIBID_AGENTS_MIN_AGENTS") {
         config.agents.min_agents = val.parse().context("Invalid RAIBID_AGENTS_MIN_AGENTS")?;
     }
-    if let Ok(val) = env::var("RAIBID_AGENTS_MAX_AGENTS") {
+    if let Some(val) = var("RAIBID_AGENTS_MAX_AGENTS") {
         config.agents.max_agents = val.parse().context("Invalid RAIBID_AGENTS_MAX_AGENTS")?;
     }
-    if let Ok(val) = env::var("RAIBID_AGENTS_IDLE_TIMEOUT_SECONDS") {
+    if let Some(val) = var("RAIBID_AGENTS_IDLE_TIMEOUT_SECONDS") {
         config.agents.idle_timeout_seconds = val
             .parse()
             .context("Invalid RAIBID_AGENTS_IDLE_TIMEOUT_SECONDS")?;
     }
 
     // Gitea overrides
-    if let Ok(val) = env::var("RAIBID_GITEA_URL") {
+    if let Some(val) = var("RAIBID_GITEA_URL") {
         config.gitea.url = val;
     }
-    if let Ok(val) = env::var("RAIBID_GITEA_ADMIN_USER") {
+    if let Some(val) = var("RAIBID_GITEA_ADMIN_USER") {
         config.gitea.admin_user = val;
     }
-    if let Ok(val) = env::var("RAIBID_GITEA_ADMIN_PASSWORD") {
+    if let Some(val) = var("RAIBID_GITEA_ADMIN_PASSWORD") {
         config.gitea.admin_password = Some(val);
     }
 
     // Redis overrides
-    if let Ok(val) = env::var("RAIBID_REDIS_HOST") {
+    if let Some(val) = var("RAIBID_REDIS_HOST") {
         config.redis.host = val;
     }
-    if let Ok(val) = env::var("RAIBID_REDIS_PORT") {
+    if let Some(val) = var("RAIBID_REDIS_PORT") {
         config.redis.port = val.parse().context("Invalid RAIBID_REDIS_PORT")?;
     }
-    if let Ok(val) = env::var("RAIBID_REDIS_PASSWORD") {
+    if let Some(val) = var("RAIBID_REDIS_PASSWORD") {
         config.redis.password = Some(val);
     }
-    if let Ok(val) = env::var("RAIBID_REDIS_DATABASE") {
+    if let Some(val) = var("RAIBID_REDIS_DATABASE") {
         config.redis.database = val.parse().context("Invalid RAIBID_REDIS_DATABASE")?;
     }
 
     // UI overrides
-    if let Ok(val) = env::var("RAIBID_UI_TUI_ENABLED") {
+    if let Some(val) = var("RAIBID_UI_TUI_ENABLED") {
         config.ui.tui_enabled = val.parse().context("Invalid RAIBID_UI_TUI_ENABLED")?;
     }
-    if let Ok(val) = env::var("RAIBID_UI_REFRESH_RATE_MS") {
+    if let Some(val) = var("RAIBID_UI_REFRESH_RATE_MS") {
         config.ui.refresh_rate_ms = val.parse().context("Invalid RAIBID_UI_REFRESH_RATE_MS")?;
     }
-    if let Ok(val) = env::var("RAIBID_UI_COLOR_SCHEME") {
+    if let Some(val) = var("RAIBID_UI_COLOR_SCHEME") {
         config.ui.color_scheme = val;
     }
 
@@ -388,4 +488,59 @@ mod tests {
 
         env::remove_var("TEST_VAR");
     }
+
+    #[test]
+    fn test_validate_profile() {
+        for profile in PROFILES {
+            assert!(validate_profile(profile).is_ok());
+        }
+        let err = validate_profile("production").unwrap_err();
+        assert!(
+            err.to_string().contains("dev, staging, prod, test"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_profile_overlay_wins() {
+        let dir = tempfile::tempdir().unwrap();
+        let base = dir.path().join("config.yaml");
+        fs::write(
+            &base,
+            "redis:\n  host: redis.local\n  password: base-secret\napi:\n  port: 9090\n",
+        )
+        .unwrap();
+        let overlay = dir.path().join("config.prod.yaml");
+        fs::write(&overlay, "redis:\n  password: prod-secret\n").unwrap();
+
+        let mut config = load_config_file(&base).unwrap();
+        config.profile = Some("prod".to_string());
+        let config = merge_overlay(config, &overlay).unwrap();
+
+        assert_eq!(config.redis.password.as_deref(), Some("prod-secret"));
+        // Settings the overlay leaves out keep their base value
+        assert_eq!(config.redis.host, "redis.local");
+        assert_eq!(config.api.port, 9090);
+        assert_eq!(config.active_profile(), "prod");
+    }
+
+    #[test]
+    fn test_profile_env_overrides() {
+        env::set_var("RAIBID_STAGING_GITEA_ADMIN_USER", "staging-admin");
+        env::set_var("RAIBID_GITEA_ADMIN_USER", "admin");
+
+        let config = Config {
+            profile: Some("staging".to_string()),
+            ..Config::default()
+        };
+        let staging = apply_env_overrides(config).unwrap();
+        let dev = apply_env_overrides(Config::default()).unwrap();
+
+        assert_eq!(staging.gitea.admin_user, "staging-admin");
+        assert_eq!(dev.gitea.admin_user, "admin");
+
+        env::remove_var("RAIBID_STAGING_GITEA_ADMIN_USER");
+        env::remove_var("RAIBID_GITEA_ADMIN_USER");
+    }
 }
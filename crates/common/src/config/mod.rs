@@ -7,17 +7,23 @@
 //! - Defaults
 //!
 //! Configuration sources are merged with the following precedence (highest to lowest):
-//! 1. Environment variables (RAIBID_*)
-//! 2. Local file (./raibid.yaml)
-//! 3. User file (~/.config/raibid/config.yaml)
-//! 4. System file (/etc/raibid/config.yaml)
-//! 5. Defaults
+//! 1. Environment variables (RAIBID_<PROFILE>_*, then RAIBID_*)
+//! 2. Profile overlay (~/.config/raibid/config.<profile>.yaml)
+//! 3. Local file (./raibid.yaml)
+//! 4. User file (~/.config/raibid/config.yaml)
+//! 5. System file (/etc/raibid/config.yaml)
+//! 6. Defaults
+//!
+//! The profile is chosen with `RAIBID_PROFILE` (or `--profile` on the CLI)
+//! from `dev`, `staging`, `prod` and `test`, and defaults to `dev`.
 
 mod loader;
 mod schema;
 
 // Re-export public API
 pub use loader::{
-    discover_config_files, load_config, load_config_file, validate_config,
+    discover_config_files, expand_paths, load_config, load_config_file, merge_overlay,
+    profile_config_file, selected_profile, validate_config, validate_profile, PROFILES,
+    PROFILE_ENV,
 };
-pub use schema::Config;
+pub use schema::{Config, DEFAULT_PROFILE};
@@ -6,6 +6,9 @@ use anyhow::Result;
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 
+/// Profile used when `RAIBID_PROFILE` is not set
+pub const DEFAULT_PROFILE: &str = "dev";
+
 /// Main configuration structure
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
 #[serde(deny_unknown_fields)]
@@ -33,20 +36,31 @@ pub struct Config {
     /// UI configuration
     #[serde(default)]
     pub ui: UiConfig,
+
+    /// Profile the configuration was loaded for, set by the loader
+    #[serde(skip)]
+    pub profile: Option<String>,
 }
 
 impl Config {
     /// Load configuration from all sources
     ///
     /// This loads configuration from multiple sources in order of precedence:
-    /// 1. Environment variables (RAIBID_*)
-    /// 2. Local file (./raibid.yaml)
-    /// 3. User file (~/.config/raibid/config.yaml)
-    /// 4. System file (/etc/raibid/config.yaml)
-    /// 5. Default values
+    /// 1. Environment variables (RAIBID_<PROFILE>_*, then RAIBID_*)
+    /// 2. Profile overlay (~/.config/raibid/config.<profile>.yaml)
+    /// 3. Local file (./raibid.yaml)
+    /// 4. User file (~/.config/raibid/config.yaml)
+    /// 5. System file (/etc/raibid/config.yaml)
+    /// 6. Default values
     pub fn load() -> Result<Self> {
         crate::config::loader::load_config()
     }
+
+    /// Profile the configuration was loaded for (`dev`, `staging`, `prod` or
+    /// `test`)
+    pub fn active_profile(&self) -> &str {
+        self.profile.as_deref().unwrap_or(DEFAULT_PROFILE)
+    }
 }
 
 /// Cluster (k3s) configuration
@@ -101,6 +115,10 @@ pub struct ApiConfig {
     /// Path to TLS private key
     #[serde(default)]
     pub tls_key_path: Option<PathBuf>,
+
+    /// API key sent to the server with every request
+    #[serde(default)]
+    pub api_key: Option<String>,
 }
 
 /// Agent configuration
@@ -314,6 +332,7 @@ impl Default for ApiConfig {
             tls_enabled: false,
             tls_cert_path: None,
             tls_key_path: None,
+            api_key: None,
         }
     }
 }
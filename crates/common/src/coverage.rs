@@ -0,0 +1,53 @@
+//! Code coverage types
+//!
+//! Agents parse the Cobertura XML report of a `coverage` step into a
+//! [`CoverageReport`] and store it in Redis under
+//! [`coverage_key`](crate::jobs::coverage_key).
+
+use serde::{Deserialize, Serialize};
+
+/// Line coverage of a job
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct CoverageReport {
+    /// Lines executed by the tests
+    pub lines_covered: u64,
+    /// Lines that could be executed
+    pub lines_valid: u64,
+}
+
+impl CoverageReport {
+    /// Percentage of lines covered, 0 when there are no lines
+    pub fn percent(&self) -> f64 {
+        if self.lines_valid == 0 {
+            return 0.0;
+        }
+        self.lines_covered as f64 * 100.0 / self.lines_valid as f64
+    }
+
+    /// Whether coverage is at least `min_percent`
+    pub fn meets(&self, min_percent: u8) -> bool {
+        self.percent() >= f64::from(min_percent)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_percent_and_threshold() {
+        let report = CoverageReport {
+            lines_covered: 725,
+            lines_valid: 1000,
+        };
+        assert!((report.percent() - 72.5).abs() < f64::EPSILON);
+        assert!(report.meets(70));
+        assert!(!report.meets(80));
+
+        let empty = CoverageReport {
+            lines_covered: 0,
+            lines_valid: 0,
+        };
+        assert_eq!(empty.percent(), 0.0);
+    }
+}
@@ -0,0 +1,96 @@
+//! Outdated and unused dependency types
+//!
+//! Agents count the dependencies `cargo outdated` reports in an `outdated`
+//! step into an [`OutdatedReport`] and store it in Redis under
+//! [`dependencies_key`](crate::jobs::dependencies_key). The dependencies
+//! `cargo udeps` finds unused in a `udeps` step are listed in an
+//! [`UnusedDepsReport`] under
+//! [`unused_deps_key`](crate::jobs::unused_deps_key).
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Outdated dependencies of a job's workspace
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct OutdatedReport {
+    /// Outdated dependencies listed in a manifest
+    pub direct: u64,
+    /// Outdated dependencies of dependencies
+    pub transitive: u64,
+}
+
+impl OutdatedReport {
+    /// Number of outdated dependencies
+    pub fn total(&self) -> u64 {
+        self.direct + self.transitive
+    }
+}
+
+impl fmt::Display for OutdatedReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} outdated ({} direct, {} transitive)",
+            self.total(),
+            self.direct,
+            self.transitive
+        )
+    }
+}
+
+/// Dependencies declared in a job's workspace but never used
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct UnusedDepsReport {
+    /// Names of the unused crates, sorted, each listed once
+    pub crates: Vec<String>,
+}
+
+impl UnusedDepsReport {
+    /// Whether every dependency is used
+    pub fn is_empty(&self) -> bool {
+        self.crates.is_empty()
+    }
+}
+
+impl fmt::Display for UnusedDepsReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return write!(f, "no unused dependencies");
+        }
+        write!(
+            f,
+            "{} unused ({})",
+            self.crates.len(),
+            self.crates.join(", ")
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_total_and_display() {
+        let report = OutdatedReport {
+            direct: 2,
+            transitive: 5,
+        };
+        assert_eq!(report.total(), 7);
+        assert_eq!(report.to_string(), "7 outdated (2 direct, 5 transitive)");
+        assert_eq!(OutdatedReport::default().total(), 0);
+    }
+
+    #[test]
+    fn test_unused_display() {
+        let report = UnusedDepsReport {
+            crates: vec!["rand".to_string(), "regex".to_string()],
+        };
+        assert_eq!(report.to_string(), "2 unused (rand, regex)");
+        assert!(UnusedDepsReport::default().is_empty());
+        assert_eq!(
+            UnusedDepsReport::default().to_string(),
+            "no unused dependencies"
+        );
+    }
+}
@@ -0,0 +1,433 @@
+//! Gitea API client
+//!
+//! Blocking client for the parts of the Gitea REST API used to manage
+//! repository mirrors. The Gitea URL and admin credentials come from
+//! `~/.raibid/gitea-credentials.json`, written by `raibid-cli setup gitea`.
+//!
+//! Mirrors are owned by an organization ([`MIRROR_ORG`] unless chosen
+//! otherwise), created on first use by [`GiteaClient::ensure_organization`].
+
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Datelike, Utc};
+use reqwest::blocking::{Client, RequestBuilder, Response};
+use reqwest::StatusCode;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// File name of the Gitea credentials in `~/.raibid`
+pub const CREDENTIALS_FILE: &str = "gitea-credentials.json";
+
+/// Topic added to mirrored repositories
+pub const MIRROR_TOPIC: &str = "mirror";
+
+/// Organization owning mirrored repositories by default
+pub const MIRROR_ORG: &str = "mirrors";
+
+/// Most repositories returned by [`GiteaClient::list_mirrors`]
+pub const MIRROR_LIST_LIMIT: usize = 50;
+
+/// Default request timeout
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Gitea URL and admin credentials
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct GiteaCredentials {
+    /// Gitea base URL
+    pub url: String,
+    /// Admin user
+    pub admin_username: String,
+    /// Admin password, used when no token is set
+    pub admin_password: String,
+    /// Access token sent as a bearer token instead of the admin password
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+}
+
+impl GiteaCredentials {
+    /// Path of the credentials file, `~/.raibid/gitea-credentials.json`
+    pub fn default_path() -> Result<PathBuf> {
+        let home = dirs::home_dir().context("Could not determine home directory")?;
+        Ok(home.join(".raibid").join(CREDENTIALS_FILE))
+    }
+
+    /// Read credentials from `path`
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path).with_context(|| {
+            format!(
+                "Failed to read Gitea credentials from {} (run `raibid-cli setup gitea` first)",
+                path.display()
+            )
+        })?;
+        serde_json::from_str(&contents)
+            .with_context(|| format!("Invalid Gitea credentials in {}", path.display()))
+    }
+}
+
+/// A Gitea repository, limited to the fields used for mirrors
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Repository {
+    /// Repository name
+    pub name: String,
+    /// `owner/name`
+    pub full_name: String,
+    /// Whether this repository is a pull mirror
+    #[serde(default)]
+    pub mirror: bool,
+    /// URL the mirror pulls from
+    #[serde(default)]
+    pub original_url: String,
+    /// Sync interval as a Go duration (`8h0m0s`)
+    #[serde(default)]
+    pub mirror_interval: String,
+    /// Time of the last sync (Gitea reports year 1 when it never synced)
+    #[serde(default)]
+    pub mirror_updated: Option<DateTime<Utc>>,
+    /// Whether the repository has no content yet
+    #[serde(default)]
+    pub empty: bool,
+}
+
+impl Repository {
+    /// Time of the last sync, if the mirror has synced
+    pub fn last_sync(&self) -> Option<DateTime<Utc>> {
+        self.mirror_updated.filter(|time| time.year() > 1)
+    }
+
+    /// Time the next sync is due, if the mirror has synced
+    pub fn next_sync(&self) -> Option<DateTime<Utc>> {
+        let interval = parse_go_duration(&self.mirror_interval)?;
+        let interval = chrono::Duration::from_std(interval).ok()?;
+        Some(self.last_sync()? + interval)
+    }
+}
+
+/// Body of `POST /api/v1/repos/migrate`
+#[derive(Debug, Serialize)]
+struct MigrateRepo<'a> {
+    clone_addr: &'a str,
+    repo_name: &'a str,
+    repo_owner: &'a str,
+    mirror: bool,
+    mirror_interval: String,
+    service: &'a str,
+}
+
+/// Body of `POST /api/v1/orgs`
+#[derive(Debug, Serialize)]
+struct CreateOrg<'a> {
+    username: &'a str,
+    visibility: &'a str,
+}
+
+/// Response of `GET /api/v1/repos/search`
+#[derive(Debug, Deserialize)]
+struct SearchResults {
+    #[serde(default)]
+    data: Vec<Repository>,
+}
+
+/// Client for the Gitea REST API
+#[derive(Debug, Clone)]
+pub struct GiteaClient {
+    credentials: GiteaCredentials,
+    client: Client,
+}
+
+impl GiteaClient {
+    /// Create a client authenticating with `credentials`
+    pub fn new(mut credentials: GiteaCredentials) -> Result<Self> {
+        let client = Client::builder()
+            .timeout(DEFAULT_TIMEOUT)
+            .build()
+            .context("Failed to build HTTP client")?;
+
+        credentials.url = credentials.url.trim_end_matches('/').to_string();
+        Ok(Self {
+            credentials,
+            client,
+        })
+    }
+
+    /// Get the Gitea base URL
+    pub fn base_url(&self) -> &str {
+        &self.credentials.url
+    }
+
+    fn authorize(&self, request: RequestBuilder) -> RequestBuilder {
+        match &self.credentials.token {
+            Some(token) => request.bearer_auth(token),
+            None => request.basic_auth(
+                &self.credentials.admin_username,
+                Some(&self.credentials.admin_password),
+            ),
+        }
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}{}", self.credentials.url, path)
+    }
+
+    /// Create the organization `name` unless it already exists
+    pub fn ensure_organization(&self, name: &str) -> Result<()> {
+        let org_path = format!("/api/v1/orgs/{}", name);
+        let response = self.send(self.client.get(self.url(&org_path)))?;
+        if response.status() != StatusCode::NOT_FOUND {
+            check_response(response)?;
+            return Ok(());
+        }
+
+        let body = CreateOrg {
+            username: name,
+            visibility: "public",
+        };
+        check_response(self.send(self.client.post(self.url("/api/v1/orgs")).json(&body))?)
+            .with_context(|| format!("Failed to create organization '{}'", name))?;
+        Ok(())
+    }
+
+    /// Create a pull mirror of `clone_addr` owned by `owner`, a user or an
+    /// existing organization
+    ///
+    /// Gitea clones the repository before answering, so this can take a while
+    /// for large repositories.
+    pub fn create_mirror(
+        &self,
+        owner: &str,
+        clone_addr: &str,
+        repo_name: &str,
+        interval_minutes: u32,
+    ) -> Result<Repository> {
+        let body = MigrateRepo {
+            clone_addr,
+            repo_name,
+            repo_owner: owner,
+            mirror: true,
+            mirror_interval: format!("{}m", interval_minutes),
+            service: if clone_addr.contains("github.com") {
+                "github"
+            } else {
+                "git"
+            },
+        };
+        let repo: Repository = parse_response(
+            self.send(
+                self.client
+                    .post(self.url("/api/v1/repos/migrate"))
+                    .json(&body),
+            )?,
+        )?;
+
+        // The topic lets mirrors be found without listing every repository
+        let topic_path = format!("/api/v1/repos/{}/topics/{}", repo.full_name, MIRROR_TOPIC);
+        check_response(self.send(self.client.put(self.url(&topic_path)))?)?;
+
+        Ok(repo)
+    }
+
+    /// Delete the repository `owner/name`
+    pub fn delete_repo(&self, owner: &str, name: &str) -> Result<()> {
+        let repo_path = format!("/api/v1/repos/{}/{}", owner, name);
+        check_response(self.send(self.client.delete(self.url(&repo_path)))?)?;
+        Ok(())
+    }
+
+    /// List mirrors, i.e. repositories with the [`MIRROR_TOPIC`] topic
+    pub fn list_mirrors(&self) -> Result<Vec<Repository>> {
+        let limit = MIRROR_LIST_LIMIT.to_string();
+        let request = self.client.get(self.url("/api/v1/repos/search")).query(&[
+            ("q", MIRROR_TOPIC),
+            ("topic", "true"),
+            ("limit", &limit),
+        ]);
+        let results: SearchResults = parse_response(self.send(request)?)?;
+
+        Ok(results
+            .data
+            .into_iter()
+            .filter(|repo| repo.mirror)
+            .collect())
+    }
+
+    fn send(&self, request: RequestBuilder) -> Result<Response> {
+        self.authorize(request)
+            .send()
+            .with_context(|| format!("Failed to connect to Gitea at {}", self.credentials.url))
+    }
+}
+
+/// Parse a Go duration made of hours, minutes and seconds (`8h0m0s`, `10m`)
+pub fn parse_go_duration(s: &str) -> Option<Duration> {
+    let mut total = 0;
+    let mut digits = String::new();
+    for c in s.trim().chars() {
+        if c.is_ascii_digit() {
+            digits.push(c);
+            continue;
+        }
+        let value: u64 = digits.parse().ok()?;
+        digits.clear();
+        total += match c {
+            'h' => value * 3600,
+            'm' => value * 60,
+            's' => value,
+            _ => return None,
+        };
+    }
+
+    if !digits.is_empty() || s.trim().is_empty() {
+        return None;
+    }
+    Some(Duration::from_secs(total))
+}
+
+/// Deserialize a successful response or turn an error status into an error
+fn parse_response<T: DeserializeOwned>(response: Response) -> Result<T> {
+    check_response(response)?
+        .json()
+        .context("Failed to parse Gitea response")
+}
+
+/// Turn an error status into an error
+fn check_response(response: Response) -> Result<Response> {
+    let status = response.status();
+    if !status.is_success() {
+        let body = response.text().unwrap_or_default();
+        return Err(anyhow!(
+            "Gitea request failed ({}): {}",
+            status,
+            body.trim()
+        ));
+    }
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_go_duration() {
+        assert_eq!(
+            parse_go_duration("8h0m0s"),
+            Some(Duration::from_secs(8 * 3600))
+        );
+        assert_eq!(parse_go_duration("10m"), Some(Duration::from_secs(600)));
+        assert_eq!(
+            parse_go_duration("1h30m15s"),
+            Some(Duration::from_secs(5415))
+        );
+        assert_eq!(parse_go_duration(""), None);
+        assert_eq!(parse_go_duration("10"), None);
+        assert_eq!(parse_go_duration("5d"), None);
+    }
+
+    #[test]
+    fn test_repository_sync_times() {
+        let repo: Repository = serde_json::from_str(
+            r#"{"name": "repo", "full_name": "admin/repo", "mirror": true,
+                "mirror_interval": "1h0m0s", "mirror_updated": "2024-01-01T12:00:00Z"}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            repo.next_sync().unwrap().to_rfc3339(),
+            "2024-01-01T13:00:00+00:00"
+        );
+
+        let never: Repository = serde_json::from_str(
+            r#"{"name": "repo", "full_name": "admin/repo", "mirror": true,
+                "mirror_interval": "1h0m0s", "mirror_updated": "0001-01-01T00:00:00Z"}"#,
+        )
+        .unwrap();
+        assert_eq!(never.last_sync(), None);
+        assert_eq!(never.next_sync(), None);
+    }
+
+    fn client(server: &mockito::Server, token: Option<&str>) -> GiteaClient {
+        GiteaClient::new(GiteaCredentials {
+            url: server.url(),
+            admin_username: "admin".to_string(),
+            admin_password: "secret".to_string(),
+            token: token.map(str::to_string),
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_ensure_organization() {
+        let mut server = mockito::Server::new();
+        let lookup = server
+            .mock("GET", "/api/v1/orgs/mirrors")
+            .match_header("authorization", "Bearer gitea-token")
+            .with_status(404)
+            .create();
+        let create = server
+            .mock("POST", "/api/v1/orgs")
+            .match_header("authorization", "Bearer gitea-token")
+            .match_body(mockito::Matcher::PartialJson(serde_json::json!({
+                "username": "mirrors",
+            })))
+            .with_status(201)
+            .with_body(r#"{"id": 2, "username": "mirrors"}"#)
+            .create();
+
+        client(&server, Some("gitea-token"))
+            .ensure_organization(MIRROR_ORG)
+            .unwrap();
+        lookup.assert();
+        create.assert();
+
+        // An existing organization is left alone
+        let mut server = mockito::Server::new();
+        server
+            .mock("GET", "/api/v1/orgs/mirrors")
+            .with_status(200)
+            .with_body(r#"{"id": 2, "username": "mirrors"}"#)
+            .create();
+        let create = server.mock("POST", "/api/v1/orgs").expect(0).create();
+        client(&server, None)
+            .ensure_organization(MIRROR_ORG)
+            .unwrap();
+        create.assert();
+    }
+
+    #[test]
+    fn test_delete_repo() {
+        let mut server = mockito::Server::new();
+        let delete = server
+            .mock("DELETE", "/api/v1/repos/mirrors/ripgrep")
+            .with_status(204)
+            .create();
+        client(&server, None)
+            .delete_repo(MIRROR_ORG, "ripgrep")
+            .unwrap();
+        delete.assert();
+
+        server
+            .mock("DELETE", "/api/v1/repos/mirrors/missing")
+            .with_status(404)
+            .with_body(r#"{"message": "repository does not exist"}"#)
+            .create();
+        let err = client(&server, None)
+            .delete_repo(MIRROR_ORG, "missing")
+            .unwrap_err();
+        assert!(err.to_string().contains("404"));
+    }
+
+    #[test]
+    fn test_load_credentials() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(CREDENTIALS_FILE);
+        assert!(GiteaCredentials::load(&path).is_err());
+
+        std::fs::write(
+            &path,
+            r#"{"admin_username": "admin", "admin_password": "secret", "url": "http://gitea:3000"}"#,
+        )
+        .unwrap();
+        let credentials = GiteaCredentials::load(&path).unwrap();
+        assert_eq!(credentials.url, "http://gitea:3000");
+        assert_eq!(credentials.token, None);
+    }
+}
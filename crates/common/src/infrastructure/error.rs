@@ -2,10 +2,27 @@
 //!
 //! This module provides comprehensive error handling for all infrastructure operations
 //! with detailed context, retry capabilities, and recovery suggestions.
+//!
+//! Installers return `anyhow` errors. Error sites attach a fix the user can
+//! apply with [`RemediationContext::remediation`]; [`remediation_hint`] finds
+//! it (or the suggestion of an [`InfraError`]) anywhere in an error's chain
+//! so the CLI can print it below the error.
 
 use std::fmt;
 use std::time::Duration;
 
+/// Fix for a missing `kubectl`
+pub const KUBECTL_INSTALL_HINT: &str =
+    "Install kubectl (https://kubernetes.io/docs/tasks/tools/) and make sure it is on your PATH";
+
+/// Fix for a missing `helm`
+pub const HELM_INSTALL_HINT: &str =
+    "Install Helm (https://helm.sh/docs/intro/install/) and make sure it is on your PATH";
+
+/// Suggestion shown for [`InfraError::ChecksumMismatch`]
+const CHECKSUM_MISMATCH_SUGGESTION: &str =
+    "The downloaded file may be corrupted. Please retry the installation.";
+
 /// Result type alias for infrastructure operations
 #[allow(dead_code)]
 pub type InfraResult<T> = Result<T, InfraError>;
@@ -199,6 +216,65 @@ pub struct ValidationError {
     pub message: String,
 }
 
+impl ValidationError {
+    /// Fix for a failed pre-flight check, by the check's field
+    pub fn remediation(&self) -> Option<&'static str> {
+        match self.field.as_str() {
+            "disk_space" => Some("Free up disk space (e.g. `docker system prune` or removing old build caches) and retry."),
+            "required_command" => Some("Install the missing command with your package manager and make sure it is on your PATH."),
+            "required_directory" => Some("Create the directory with `mkdir -p <path>` or fix the configured path."),
+            "rootless_uidmap" => Some("Install newuidmap and newgidmap (the `uidmap` package on Debian/Ubuntu, `shadow-utils` on Fedora)."),
+            "rootless_userns" => Some("Enable unprivileged user namespaces with `sudo sysctl -w kernel.unprivileged_userns_clone=1`."),
+            "rootless_subuid" => Some("Add subordinate IDs with `sudo usermod --add-subuids 100000-165535 --add-subgids 100000-165535 $USER`."),
+            _ => None,
+        }
+    }
+}
+
+/// An error message with a suggested fix, attached with
+/// [`RemediationContext::remediation`]
+///
+/// Displays as the message alone; the hint is read with [`remediation_hint`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Remediation {
+    pub message: String,
+    pub hint: String,
+}
+
+impl fmt::Display for Remediation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+/// Attach a [`Remediation`] to a failed result
+pub trait RemediationContext<T> {
+    /// Like `anyhow::Context::context`, adding a fix for the user
+    fn remediation(self, message: impl Into<String>, hint: impl Into<String>) -> anyhow::Result<T>;
+}
+
+impl<T, E> RemediationContext<T> for Result<T, E>
+where
+    E: std::error::Error + Send + Sync + 'static,
+{
+    fn remediation(self, message: impl Into<String>, hint: impl Into<String>) -> anyhow::Result<T> {
+        self.map_err(|e| {
+            anyhow::Error::new(e).context(Remediation {
+                message: message.into(),
+                hint: hint.into(),
+            })
+        })
+    }
+}
+
+/// The fix suggested anywhere in `err`'s chain, outermost first
+pub fn remediation_hint(err: &anyhow::Error) -> Option<&str> {
+    if let Some(remediation) = err.downcast_ref::<Remediation>() {
+        return Some(&remediation.hint);
+    }
+    err.downcast_ref::<InfraError>().and_then(InfraError::remediation)
+}
+
 impl fmt::Display for InfraError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -212,8 +288,8 @@ impl fmt::Display for InfraError {
             InfraError::ChecksumMismatch { component, expected, actual, file_path } => {
                 write!(
                     f,
-                    "Checksum verification failed for {}\nFile: {}\nExpected: {}\nActual: {}\nSuggestion: The downloaded file may be corrupted. Please retry the installation.",
-                    component, file_path, expected, actual
+                    "Checksum verification failed for {}\nFile: {}\nExpected: {}\nActual: {}\nSuggestion: {}",
+                    component, file_path, expected, actual, CHECKSUM_MISMATCH_SUGGESTION
                 )
             }
             InfraError::Network { operation, reason, suggestion } => {
@@ -407,6 +483,29 @@ impl InfraError {
         }
     }
 
+    /// Suggested fix for the user, if the error carries one
+    #[allow(dead_code)]
+    pub fn remediation(&self) -> Option<&str> {
+        match self {
+            InfraError::Download { suggestion, .. }
+            | InfraError::Network { suggestion, .. }
+            | InfraError::Installation { suggestion, .. }
+            | InfraError::Configuration { suggestion, .. }
+            | InfraError::PrerequisiteMissing { suggestion, .. }
+            | InfraError::CommandFailed { suggestion, .. }
+            | InfraError::Kubernetes { suggestion, .. }
+            | InfraError::Helm { suggestion, .. }
+            | InfraError::Timeout { suggestion, .. }
+            | InfraError::HealthCheck { suggestion, .. } => Some(suggestion),
+            InfraError::ChecksumMismatch { .. } => Some(CHECKSUM_MISMATCH_SUGGESTION),
+            InfraError::Rollback { .. }
+            | InfraError::FileSystem { .. }
+            | InfraError::Validation { .. }
+            | InfraError::Transient { .. }
+            | InfraError::Fatal { .. } => None,
+        }
+    }
+
     /// Check if error is transient and can be retried
     #[allow(dead_code)]
     pub fn is_transient(&self) -> bool {
@@ -521,6 +620,55 @@ mod tests {
         assert!(msg.contains("namespace"));
     }
 
+    #[test]
+    fn test_remediation_hints() {
+        let err = InfraError::installation("k3s", InstallPhase::Installation, "permission denied");
+        assert_eq!(err.remediation(), Some("Check for sufficient permissions and disk space."));
+
+        let err = InfraError::ChecksumMismatch {
+            component: "k3s".to_string(),
+            expected: "abc".to_string(),
+            actual: "def".to_string(),
+            file_path: "/tmp/k3s".to_string(),
+        };
+        assert_eq!(err.remediation(), Some(CHECKSUM_MISMATCH_SUGGESTION));
+        assert!(err.to_string().contains(CHECKSUM_MISMATCH_SUGGESTION));
+
+        let err = InfraError::Fatal {
+            component: "k3s".to_string(),
+            reason: "unsupported platform".to_string(),
+            context: vec![],
+        };
+        assert_eq!(err.remediation(), None);
+
+        let missing = ValidationError {
+            field: "required_command".to_string(),
+            message: "Required command 'helm' not found in PATH".to_string(),
+        };
+        assert!(missing.remediation().unwrap().contains("package manager"));
+    }
+
+    #[test]
+    fn test_remediation_context() {
+        let result: Result<(), std::io::Error> =
+            Err(std::io::Error::new(std::io::ErrorKind::PermissionDenied, "denied"));
+        let err = result
+            .remediation("Failed to copy binary", "Run `chmod +x ~/.local/bin`")
+            .unwrap_err()
+            .context("k3s installation failed");
+
+        assert_eq!(remediation_hint(&err), Some("Run `chmod +x ~/.local/bin`"));
+        assert_eq!(format!("{:#}", err), "k3s installation failed: Failed to copy binary: denied");
+
+        let err = anyhow::Error::new(InfraError::network("download", "connection refused"))
+            .context("setup failed");
+        assert_eq!(
+            remediation_hint(&err),
+            Some("Check if the target service is running and accessible.")
+        );
+        assert_eq!(remediation_hint(&anyhow::anyhow!("plain")), None);
+    }
+
     #[test]
     fn test_helm_error_display() {
         let err = InfraError::Helm {
@@ -7,8 +7,12 @@ use anyhow::{Context, Result, anyhow};
 use std::fs;
 use std::path::PathBuf;
 use std::process::Command;
+use std::time::Duration;
 use tracing::{debug, info, warn};
 
+use super::plan::PlanStep;
+use super::redis::{RedisConfig, REDIS_AUTH_SECRET, REDIS_AUTH_SECRET_KEY};
+
 /// KEDA Helm chart information
 const KEDA_HELM_REPO: &str = "https://kedacore.github.io/charts";
 const KEDA_HELM_REPO_NAME: &str = "kedacore";
@@ -16,6 +20,9 @@ const KEDA_CHART_NAME: &str = "kedacore/keda";
 const KEDA_RELEASE_NAME: &str = "raibid-keda";
 const KEDA_NAMESPACE: &str = "keda";
 
+/// Name of the TriggerAuthentication giving the scaler the Redis password
+pub const TRIGGER_AUTH_NAME: &str = "raibid-redis-trigger-auth";
+
 /// ScaledObject configuration for Redis Streams
 #[derive(Debug, Clone)]
 pub struct ScaledObjectConfig {
@@ -41,6 +48,10 @@ pub struct ScaledObjectConfig {
     pub target_name: String,
     /// Target resource type (Deployment or Job)
     pub target_kind: TargetKind,
+    /// Secret in `namespace` holding the Redis password under
+    /// `redisPassword`; when set, the trigger authenticates through a
+    /// TriggerAuthentication
+    pub redis_auth_secret: Option<String>,
 }
 
 /// Target resource kind for scaling
@@ -65,6 +76,7 @@ impl Default for ScaledObjectConfig {
             polling_interval: 10, // 10 seconds
             target_name: "raibid-ci-agent".to_string(),
             target_kind: TargetKind::Deployment,
+            redis_auth_secret: None,
         }
     }
 }
@@ -103,6 +115,64 @@ impl Default for KedaConfig {
     }
 }
 
+impl KedaConfig {
+    /// Authenticate the scaler with the password Secret created by
+    /// `RedisInstaller` when `redis` has a password
+    pub fn with_redis_auth(mut self, redis: &RedisConfig) -> Self {
+        if let Some(ref mut scaled_object) = self.scaled_object {
+            scaled_object.redis_auth_secret =
+                redis.password.as_ref().map(|_| REDIS_AUTH_SECRET.to_string());
+        }
+        self
+    }
+
+    /// Actions installing KEDA with this configuration would take
+    pub fn plan(&self) -> Vec<PlanStep> {
+        let mut steps = vec![
+            PlanStep::new("Check that Helm is installed", Duration::from_secs(1)),
+            PlanStep::new(
+                format!("Add Helm repository {}", KEDA_HELM_REPO),
+                Duration::from_secs(5),
+            ),
+            PlanStep::new(
+                format!("Create namespace '{}'", self.namespace),
+                Duration::from_secs(2),
+            ),
+            PlanStep::new(
+                format!("Deploy Helm release '{}' ({})", self.release_name, KEDA_CHART_NAME),
+                Duration::from_secs(90),
+            ),
+            PlanStep::new("Wait for the KEDA operator to become ready", Duration::from_secs(60)),
+            PlanStep::new("Validate the installation", Duration::from_secs(5)),
+        ];
+
+        if let Some(ref scaled_object) = self.scaled_object {
+            if let Some(ref secret) = scaled_object.redis_auth_secret {
+                steps.push(PlanStep::new(
+                    format!(
+                        "Create TriggerAuthentication '{}' for Secret '{}'",
+                        TRIGGER_AUTH_NAME, secret
+                    ),
+                    Duration::from_secs(2),
+                ));
+            }
+            steps.push(PlanStep::new(
+                format!(
+                    "Create ScaledObject '{}' scaling {} on stream '{}' ({}-{} replicas)",
+                    scaled_object.name,
+                    scaled_object.target_name,
+                    scaled_object.stream_name,
+                    scaled_object.min_replica_count,
+                    scaled_object.max_replica_count
+                ),
+                Duration::from_secs(5),
+            ));
+        }
+
+        steps
+    }
+}
+
 /// KEDA installer
 pub struct KedaInstaller {
     config: KedaConfig,
@@ -119,6 +189,13 @@ impl KedaInstaller {
         Ok(Self { config })
     }
 
+    /// Actions an installation would take, for dry runs
+    ///
+    /// See [`KedaConfig::plan`].
+    pub fn plan(&self) -> Vec<PlanStep> {
+        self.config.plan()
+    }
+
     /// Check if kubectl is available
     #[allow(dead_code)]
     fn check_kubectl(&self) -> Result<()> {
@@ -438,6 +515,17 @@ rbac:
             ),
         };
 
+        let authentication_ref = if config.redis_auth_secret.is_some() {
+            format!(
+                r#"    authenticationRef:
+      name: {}
+"#,
+                TRIGGER_AUTH_NAME
+            )
+        } else {
+            String::new()
+        };
+
         let yaml = format!(
             r#"apiVersion: keda.sh/v1alpha1
 kind: ScaledObject
@@ -457,7 +545,7 @@ spec:
       consumerGroup: {consumer_group}
       pendingEntriesCount: "{pending_entries_count}"
       lagCount: "5"
-"#,
+{authentication_ref}"#,
             name = config.name,
             namespace = config.namespace,
             target_ref = target_ref,
@@ -468,11 +556,78 @@ spec:
             stream_name = config.stream_name,
             consumer_group = config.consumer_group,
             pending_entries_count = config.pending_entries_count,
+            authentication_ref = authentication_ref,
         );
 
         Ok(yaml)
     }
 
+    /// Generate the TriggerAuthentication YAML manifest, if the scaler
+    /// authenticates with Redis
+    fn generate_trigger_auth_yaml(&self, config: &ScaledObjectConfig) -> Option<String> {
+        let secret = config.redis_auth_secret.as_ref()?;
+
+        Some(format!(
+            r#"apiVersion: keda.sh/v1alpha1
+kind: TriggerAuthentication
+metadata:
+  name: {name}
+  namespace: {namespace}
+spec:
+  secretTargetRef:
+  - parameter: password
+    name: {secret}
+    key: {key}
+"#,
+            name = TRIGGER_AUTH_NAME,
+            namespace = config.namespace,
+            secret = secret,
+            key = REDIS_AUTH_SECRET_KEY,
+        ))
+    }
+
+    /// Create the TriggerAuthentication referenced by the ScaledObject
+    ///
+    /// Does nothing unless the ScaledObject authenticates with Redis.
+    pub fn create_trigger_auth(&self) -> Result<()> {
+        let config = match &self.config.scaled_object {
+            Some(cfg) => cfg,
+            None => return Ok(()),
+        };
+        let yaml = match self.generate_trigger_auth_yaml(config) {
+            Some(yaml) => yaml,
+            None => {
+                debug!("Redis authentication not configured, skipping TriggerAuthentication");
+                return Ok(());
+            }
+        };
+
+        info!("Creating TriggerAuthentication: {}", TRIGGER_AUTH_NAME);
+
+        let yaml_file = std::env::temp_dir().join("trigger-auth.yaml");
+        fs::write(&yaml_file, &yaml)
+            .context("Failed to write TriggerAuthentication YAML file")?;
+
+        let output = Command::new("kubectl")
+            .arg("apply")
+            .arg("-f")
+            .arg(&yaml_file)
+            .env("KUBECONFIG", &self.config.kubeconfig_path)
+            .output()
+            .context("Failed to apply TriggerAuthentication")?;
+
+        // Clean up YAML file
+        let _ = fs::remove_file(&yaml_file);
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(anyhow!("Failed to create TriggerAuthentication: {}", stderr));
+        }
+
+        info!("TriggerAuthentication created successfully");
+        Ok(())
+    }
+
     /// Create ScaledObject for Redis Streams autoscaling
     pub fn create_scaled_object(&self) -> Result<()> {
         let config = match &self.config.scaled_object {
@@ -573,6 +728,7 @@ spec:
 
         // Create ScaledObject if configured
         if self.config.scaled_object.is_some() {
+            self.create_trigger_auth()?;
             self.create_scaled_object()?;
         }
 
@@ -595,6 +751,17 @@ spec:
                 .arg(&config.namespace)
                 .env("KUBECONFIG", &self.config.kubeconfig_path)
                 .output();
+
+            if config.redis_auth_secret.is_some() {
+                let _ = Command::new("kubectl")
+                    .arg("delete")
+                    .arg("triggerauthentication")
+                    .arg(TRIGGER_AUTH_NAME)
+                    .arg("--namespace")
+                    .arg(&config.namespace)
+                    .env("KUBECONFIG", &self.config.kubeconfig_path)
+                    .output();
+            }
         }
 
         // Uninstall Helm release
@@ -702,8 +869,10 @@ mod tests {
     #[test]
     fn test_scaled_object_yaml_with_job_target() {
         let installer = KedaInstaller::new().unwrap();
-        let mut config = ScaledObjectConfig::default();
-        config.target_kind = TargetKind::Job;
+        let config = ScaledObjectConfig {
+            target_kind: TargetKind::Job,
+            ..Default::default()
+        };
 
         let yaml = installer.generate_scaled_object_yaml(&config);
 
@@ -713,11 +882,40 @@ mod tests {
         assert!(yaml_str.contains("restartPolicy: Never"));
     }
 
+    #[test]
+    fn test_scaled_object_yaml_authentication_ref() {
+        let installer = KedaInstaller::new().unwrap();
+        let config = ScaledObjectConfig::default();
+        let yaml = installer.generate_scaled_object_yaml(&config).unwrap();
+        assert!(!yaml.contains("authenticationRef:"));
+        assert!(installer.generate_trigger_auth_yaml(&config).is_none());
+
+        let redis = RedisConfig {
+            password: Some("s3cret".to_string()),
+            ..Default::default()
+        };
+        let installer =
+            KedaInstaller::with_config(KedaConfig::default().with_redis_auth(&redis)).unwrap();
+        let config = installer.config.scaled_object.clone().unwrap();
+
+        let yaml = installer.generate_scaled_object_yaml(&config).unwrap();
+        assert!(yaml.contains("    authenticationRef:\n      name: raibid-redis-trigger-auth\n"));
+
+        let trigger_auth = installer.generate_trigger_auth_yaml(&config).unwrap();
+        assert!(trigger_auth.contains("kind: TriggerAuthentication"));
+        assert!(trigger_auth.contains("namespace: raibid-ci"));
+        assert!(trigger_auth.contains("name: raibid-redis-auth"));
+        assert!(trigger_auth.contains("key: redisPassword"));
+        assert!(!trigger_auth.contains("s3cret"));
+    }
+
     #[test]
     fn test_custom_config() {
-        let mut config = KedaConfig::default();
-        config.log_level = "debug".to_string();
-        config.metrics_server_enabled = false;
+        let config = KedaConfig {
+            log_level: "debug".to_string(),
+            metrics_server_enabled: false,
+            ..Default::default()
+        };
 
         let installer = KedaInstaller::with_config(config.clone());
         assert!(installer.is_ok());
@@ -729,10 +927,12 @@ mod tests {
 
     #[test]
     fn test_scaled_object_config_customization() {
-        let mut config = ScaledObjectConfig::default();
-        config.min_replica_count = 2;
-        config.max_replica_count = 20;
-        config.pending_entries_count = "5".to_string();
+        let config = ScaledObjectConfig {
+            min_replica_count: 2,
+            max_replica_count: 20,
+            pending_entries_count: "5".to_string(),
+            ..Default::default()
+        };
 
         assert_eq!(config.min_replica_count, 2);
         assert_eq!(config.max_replica_count, 20);
@@ -12,6 +12,7 @@ pub mod redis;
 pub mod keda;
 pub mod flux;
 pub mod status;
+pub mod plan;
 
 // Error handling and utilities
 pub mod error;
@@ -25,7 +26,8 @@ pub use k3s::K3sInstaller;
 pub use gitea::GiteaInstaller;
 pub use redis::RedisInstaller;
 pub use keda::KedaInstaller;
-pub use flux::{FluxInstaller, FluxConfig};
+pub use flux::{FluxInstaller, FluxConfig, BootstrapMode, GiteaRemote};
+pub use plan::PlanStep;
 pub use status::{
     ComponentStatusChecker, K3sStatusChecker, GiteaStatusChecker,
     RedisStatusChecker, KedaStatusChecker, FluxStatusChecker,
@@ -34,17 +36,23 @@ pub use status::{
 
 // Error handling exports (for tests and external use)
 #[allow(unused_imports)]
-pub use error::{InfraError, InfraResult, InstallPhase, HelmOperation, ValidationError, ErrorContext};
+pub use error::{
+    InfraError, InfraResult, InstallPhase, HelmOperation, ValidationError, ErrorContext,
+    Remediation, RemediationContext, remediation_hint, HELM_INSTALL_HINT, KUBECTL_INSTALL_HINT,
+};
 #[allow(unused_imports)]
 pub use retry::{RetryConfig, retry_with_backoff, retry_with_backoff_async, poll_until, poll_until_async};
 #[allow(unused_imports)]
 pub use preflight::{
-    SystemRequirements, PreFlightValidator, PreFlightResult,
+    SystemRequirements, PreFlightValidator, PreFlightResult, Severity, RootlessPrerequisites,
     k3s_requirements, gitea_requirements, redis_requirements,
     keda_requirements, flux_requirements,
 };
 #[allow(unused_imports)]
-pub use rollback::{RollbackManager, RollbackContext, RollbackAction};
+pub use rollback::{
+    RollbackManager, RollbackContext, RollbackAction,
+    FLUX_PRIORITY, KEDA_PRIORITY, REDIS_PRIORITY, GITEA_PRIORITY, K3S_PRIORITY,
+};
 #[allow(unused_imports)]
 pub use healthcheck::{
     HealthStatus, HealthCheckResult, CheckResult,
@@ -53,9 +61,9 @@ pub use healthcheck::{
 
 // Config exports (for tests and commands)
 #[allow(unused_imports)]
-pub use k3s::K3sConfig;
+pub use k3s::{InstallManifest, K3sConfig, K3sMode, K3sVersion};
 #[allow(unused_imports)]
-pub use gitea::{GiteaConfig, ServiceType};
+pub use gitea::{GiteaConfig, ServiceType, RUNNER_LABELS, RUNNER_NAME};
 #[allow(unused_imports)]
 pub use redis::{RedisConfig, RedisConnectionInfo, RedisStreamsConfig};
 #[allow(unused_imports)]
@@ -0,0 +1,74 @@
+//! Dry-run plans
+//!
+//! Each installer can describe the actions an installation would take,
+//! without running commands, touching the network, or writing files. This
+//! backs `raibid setup --dry-run`.
+//!
+//! Plans are built from the installer configuration alone (e.g.
+//! [`K3sConfig::plan`](super::K3sConfig::plan)), so they can be shown on
+//! machines where the installer itself could not be created.
+
+use std::time::Duration;
+
+/// One action an installer would take
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlanStep {
+    /// Human-readable description of the action
+    pub description: String,
+    /// Rough time the action usually takes
+    pub estimate: Duration,
+}
+
+impl PlanStep {
+    /// Create a plan step
+    pub fn new(description: impl Into<String>, estimate: Duration) -> Self {
+        Self {
+            description: description.into(),
+            estimate,
+        }
+    }
+
+    /// Estimated duration as a short hint, e.g. `~30s` or `~3m`
+    pub fn estimate_hint(&self) -> String {
+        format_estimate(self.estimate)
+    }
+}
+
+/// Sum of the estimated durations of `steps`
+pub fn total_estimate(steps: &[PlanStep]) -> Duration {
+    steps.iter().map(|step| step.estimate).sum()
+}
+
+/// Format an estimated duration as `~45s`, `~2m` or `~1m 30s`
+pub fn format_estimate(estimate: Duration) -> String {
+    let secs = estimate.as_secs();
+    match (secs / 60, secs % 60) {
+        (0, 0) => "<1s".to_string(),
+        (0, s) => format!("~{}s", s),
+        (m, 0) => format!("~{}m", m),
+        (m, s) => format!("~{}m {}s", m, s),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_estimate() {
+        assert_eq!(format_estimate(Duration::from_millis(500)), "<1s");
+        assert_eq!(format_estimate(Duration::from_secs(45)), "~45s");
+        assert_eq!(format_estimate(Duration::from_secs(120)), "~2m");
+        assert_eq!(format_estimate(Duration::from_secs(90)), "~1m 30s");
+    }
+
+    #[test]
+    fn test_total_estimate() {
+        let steps = vec![
+            PlanStep::new("Download", Duration::from_secs(30)),
+            PlanStep::new("Install", Duration::from_secs(60)),
+        ];
+        assert_eq!(total_estimate(&steps), Duration::from_secs(90));
+        assert_eq!(steps[1].estimate_hint(), "~1m");
+    }
+}
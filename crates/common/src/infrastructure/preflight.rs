@@ -5,11 +5,25 @@
 
 #![allow(dead_code)]
 
-use std::path::Path;
+use futures::future::join_all;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
 use std::process::Command;
+use std::time::Duration;
 use tracing::{debug, info, warn};
 
 use crate::infrastructure::error::{InfraError, InfraResult, ValidationError};
+use crate::infrastructure::k3s::K3sVersion;
+
+/// Timeout applied to each check run by [`PreFlightValidator::validate_all_async`]
+pub const ASYNC_CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Oldest k3s version raibid supports
+pub const MIN_K3S_VERSION: &str = "v1.26.0";
+
+/// A boxed pre-flight check future
+type CheckFuture = Pin<Box<dyn Future<Output = PreFlightResult> + Send>>;
 
 /// System requirements
 #[derive(Debug, Clone)]
@@ -26,6 +40,11 @@ pub struct SystemRequirements {
     pub required_directories: Vec<String>,
     /// Required network endpoints that must be reachable
     pub required_endpoints: Vec<String>,
+    /// Check the prerequisites of rootless k3s (see [`RootlessPrerequisites`])
+    pub rootless: bool,
+    /// Oldest supported k3s, e.g. `v1.26.0`, checked when k3s is already
+    /// installed
+    pub min_k3s_version: Option<String>,
 }
 
 impl Default for SystemRequirements {
@@ -37,24 +56,52 @@ impl Default for SystemRequirements {
             optional_commands: vec![],
             required_directories: vec![],
             required_endpoints: vec![],
+            rootless: false,
+            min_k3s_version: None,
         }
     }
 }
 
+/// How a failed pre-flight check affects installation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Severity {
+    /// Installation cannot proceed; failures are errors
+    #[default]
+    Required,
+    /// Installation can proceed with reduced functionality; failures are
+    /// warnings
+    Recommended,
+}
+
 /// Pre-flight check result
 #[derive(Debug)]
 pub struct PreFlightResult {
     pub passed: bool,
     pub errors: Vec<ValidationError>,
     pub warnings: Vec<String>,
+    /// Whether [`PreFlightResult::add_failure`] records errors or warnings
+    pub severity: Severity,
 }
 
 impl PreFlightResult {
     pub fn new() -> Self {
+        Self::with_severity(Severity::Required)
+    }
+
+    pub fn with_severity(severity: Severity) -> Self {
         Self {
             passed: true,
             errors: Vec::new(),
             warnings: Vec::new(),
+            severity,
+        }
+    }
+
+    /// Record a failed check as an error or a warning, by severity
+    pub fn add_failure(&mut self, field: impl Into<String>, message: impl Into<String>) {
+        match self.severity {
+            Severity::Required => self.add_error(field, message),
+            Severity::Recommended => self.add_warning(message),
         }
     }
 
@@ -70,6 +117,13 @@ impl PreFlightResult {
         self.warnings.push(message.into());
     }
 
+    /// Add the errors and warnings of another check
+    pub fn merge(&mut self, other: PreFlightResult) {
+        self.passed &= other.passed;
+        self.errors.extend(other.errors);
+        self.warnings.extend(other.warnings);
+    }
+
     pub fn to_result(&self, component: &str) -> InfraResult<()> {
         if self.passed {
             Ok(())
@@ -83,6 +137,7 @@ impl PreFlightResult {
 }
 
 /// Pre-flight validator
+#[derive(Debug, Clone)]
 pub struct PreFlightValidator {
     requirements: SystemRequirements,
 }
@@ -116,6 +171,16 @@ impl PreFlightValidator {
         // Check network connectivity
         self.check_network_connectivity(&mut result);
 
+        // Check the version of an existing k3s
+        self.check_k3s_version(&mut result);
+
+        // Check rootless prerequisites
+        if self.requirements.rootless {
+            for check in RootlessPrerequisites::detect().check() {
+                result.merge(check);
+            }
+        }
+
         // Log warnings
         for warning in &result.warnings {
             warn!("Pre-flight warning: {}", warning);
@@ -124,6 +189,85 @@ impl PreFlightValidator {
         result.to_result(component)
     }
 
+    /// Run all pre-flight checks concurrently, returning one result per check
+    ///
+    /// Each check is bounded by [`ASYNC_CHECK_TIMEOUT`]. A check that times out
+    /// is reported as a warning rather than an error, since the service it
+    /// probes may not be installed yet.
+    pub async fn validate_all_async(&self) -> Vec<PreFlightResult> {
+        let mut checks: Vec<(String, CheckFuture)> = vec![
+            (
+                "disk_space".to_string(),
+                self.blocking_check(Self::check_disk_space),
+            ),
+            (
+                "memory".to_string(),
+                self.blocking_check(Self::check_memory),
+            ),
+            (
+                "required_commands".to_string(),
+                self.blocking_check(Self::check_required_commands),
+            ),
+            (
+                "optional_commands".to_string(),
+                self.blocking_check(Self::check_optional_commands),
+            ),
+            (
+                "required_directories".to_string(),
+                self.blocking_check(Self::check_required_directories),
+            ),
+        ];
+
+        if self.requirements.min_k3s_version.is_some() {
+            checks.push((
+                "k3s_version".to_string(),
+                self.blocking_check(Self::check_k3s_version),
+            ));
+        }
+
+        for endpoint in &self.requirements.required_endpoints {
+            checks.push((
+                format!("endpoint {}", endpoint),
+                Box::pin(check_endpoint_async(endpoint.clone())),
+            ));
+        }
+
+        let mut results = join_all(
+            checks
+                .into_iter()
+                .map(|(name, check)| run_with_timeout(name, check, ASYNC_CHECK_TIMEOUT)),
+        )
+        .await;
+
+        // One result per rootless prerequisite; they only read local files
+        if self.requirements.rootless {
+            results.extend(
+                tokio::task::spawn_blocking(|| RootlessPrerequisites::detect().check())
+                    .await
+                    .unwrap_or_default(),
+            );
+        }
+        results
+    }
+
+    /// Run a synchronous check on the blocking thread pool
+    fn blocking_check(&self, check: fn(&Self, &mut PreFlightResult)) -> CheckFuture {
+        let validator = self.clone();
+        Box::pin(async move {
+            tokio::task::spawn_blocking(move || {
+                let mut result = PreFlightResult::new();
+                check(&validator, &mut result);
+                result
+            })
+            .await
+            .unwrap_or_else(|e| {
+                let mut result = PreFlightResult::new();
+                result.add_warning(format!("Pre-flight check did not complete: {}", e));
+                result
+            })
+        })
+    }
+
     /// Check available disk space
     fn check_disk_space(&self, result: &mut PreFlightResult) {
         debug!("Checking disk space (minimum: {} GB)", self.requirements.min_disk_space_gb);
@@ -259,6 +403,36 @@ impl PreFlightValidator {
         }
     }
 
+    /// Check an installed k3s is not older than the minimum version
+    ///
+    /// Passes if k3s is not installed yet.
+    fn check_k3s_version(&self, result: &mut PreFlightResult) {
+        let Some(minimum) = &self.requirements.min_k3s_version else {
+            return;
+        };
+        debug!("Checking k3s version (minimum: {})", minimum);
+
+        let output = match Command::new("k3s").arg("--version").output() {
+            Ok(output) if output.status.success() => output,
+            _ => {
+                debug!("k3s is not installed, skipping version check");
+                return;
+            }
+        };
+
+        let checked = K3sVersion::from_version_output(&String::from_utf8_lossy(&output.stdout))
+            .and_then(|version| version.ensure_at_least(minimum));
+        if let Err(e) = checked {
+            result.add_error(
+                "k3s_version",
+                format!(
+                    "{}. Upgrade with: raibid-cli setup k3s --upgrade-to <version>",
+                    e
+                ),
+            );
+        }
+    }
+
     /// Check network connectivity to required endpoints
     fn check_network_connectivity(&self, result: &mut PreFlightResult) {
         if self.requirements.required_endpoints.is_empty() {
@@ -303,7 +477,174 @@ impl PreFlightValidator {
     }
 }
 
+/// Run a check, reporting a warning if it does not finish within `timeout`
+async fn run_with_timeout(name: String, check: CheckFuture, timeout: Duration) -> PreFlightResult {
+    match tokio::time::timeout(timeout, check).await {
+        Ok(result) => result,
+        Err(_) => {
+            warn!("Pre-flight check '{}' timed out", name);
+            let mut result = PreFlightResult::new();
+            result.add_warning(format!("{}: check timed out after {:?}", name, timeout));
+            result
+        }
+    }
+}
+
+/// Check that an HTTP endpoint responds with a 2xx or 3xx status
+async fn check_endpoint_async(endpoint: String) -> PreFlightResult {
+    let mut result = PreFlightResult::new();
+
+    match reqwest::get(&endpoint).await {
+        Ok(response) if response.status().is_success() || response.status().is_redirection() => {
+            debug!("Network endpoint '{}' is reachable", endpoint);
+        }
+        Ok(response) => {
+            result.add_warning(format!(
+                "Network endpoint '{}' returned HTTP {}",
+                endpoint,
+                response.status().as_u16()
+            ));
+        }
+        Err(_) => {
+            result.add_warning(format!(
+                "Could not reach network endpoint '{}'. Check network connectivity.",
+                endpoint
+            ));
+        }
+    }
+
+    result
+}
+
+/// Host state rootless k3s depends on
+///
+/// The checks read files below `root` and look for binaries in `path`, so
+/// they can run against a fake filesystem.
+#[derive(Debug, Clone)]
+pub struct RootlessPrerequisites {
+    /// Filesystem root, `/` outside of tests
+    pub root: PathBuf,
+    /// Directories searched for binaries
+    pub path: Vec<PathBuf>,
+    /// Name of the user k3s runs as
+    pub user: String,
+    /// UID of the user, if known
+    pub uid: Option<u32>,
+}
+
+impl RootlessPrerequisites {
+    /// Prerequisites of the current user on this host
+    pub fn detect() -> Self {
+        let id = |flag: &str| {
+            Command::new("id")
+                .arg(flag)
+                .output()
+                .ok()
+                .filter(|output| output.status.success())
+                .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+        };
+
+        Self {
+            root: PathBuf::from("/"),
+            path: std::env::var_os("PATH")
+                .map(|path| std::env::split_paths(&path).collect())
+                .unwrap_or_default(),
+            user: std::env::var("USER")
+                .ok()
+                .or_else(|| id("-un"))
+                .unwrap_or_default(),
+            uid: id("-u").and_then(|uid| uid.parse().ok()),
+        }
+    }
+
+    /// Run every check, one result each
+    pub fn check(&self) -> Vec<PreFlightResult> {
+        vec![
+            self.check_uidmap(),
+            self.check_userns_clone(),
+            self.check_subuid(),
+            self.check_fuse_overlayfs(),
+        ]
+    }
+
+    /// `newuidmap` and `newgidmap` map the subordinate IDs into the user
+    /// namespace
+    pub fn check_uidmap(&self) -> PreFlightResult {
+        let mut result = PreFlightResult::with_severity(Severity::Required);
+        for binary in ["newuidmap", "newgidmap"] {
+            if !self.has_binary(binary) {
+                result.add_failure(
+                    "rootless_uidmap",
+                    format!("'{}' not found; rootless k3s needs it to map user IDs", binary),
+                );
+            }
+        }
+        result
+    }
+
+    /// Debian and Ubuntu kernels can disable unprivileged user namespaces
+    ///
+    /// Other kernels have no such switch and pass.
+    pub fn check_userns_clone(&self) -> PreFlightResult {
+        let mut result = PreFlightResult::with_severity(Severity::Required);
+        let path = self.root.join("proc/sys/kernel/unprivileged_userns_clone");
+        if let Ok(value) = std::fs::read_to_string(&path) {
+            if value.trim() != "1" {
+                result.add_failure(
+                    "rootless_userns",
+                    "Unprivileged user namespaces are disabled (kernel.unprivileged_userns_clone is not 1)",
+                );
+            }
+        }
+        result
+    }
+
+    /// The user needs a subordinate UID range in `/etc/subuid`
+    pub fn check_subuid(&self) -> PreFlightResult {
+        let mut result = PreFlightResult::with_severity(Severity::Required);
+        let contents = std::fs::read_to_string(self.root.join("etc/subuid")).unwrap_or_default();
+        let uid = self.uid.map(|uid| uid.to_string());
+
+        let has_range = contents.lines().any(|line| {
+            let fields: Vec<&str> = line.trim().split(':').collect();
+            match fields.as_slice() {
+                [owner, _start, count] => {
+                    (*owner == self.user || Some(*owner) == uid.as_deref())
+                        && count.parse::<u64>().is_ok_and(|count| count > 0)
+                }
+                _ => false,
+            }
+        });
+        if !has_range {
+            result.add_failure(
+                "rootless_subuid",
+                format!("No subordinate UID range for user '{}' in /etc/subuid", self.user),
+            );
+        }
+        result
+    }
+
+    /// `fuse-overlayfs` provides overlay mounts on kernels without
+    /// unprivileged overlayfs (before 5.11)
+    pub fn check_fuse_overlayfs(&self) -> PreFlightResult {
+        let mut result = PreFlightResult::with_severity(Severity::Recommended);
+        if !self.has_binary("fuse-overlayfs") {
+            result.add_failure(
+                "rootless_fuse_overlayfs",
+                "'fuse-overlayfs' not found; rootless k3s falls back to native overlayfs, which needs kernel 5.11 or later",
+            );
+        }
+        result
+    }
+
+    fn has_binary(&self, name: &str) -> bool {
+        self.path.iter().any(|dir| dir.join(name).is_file())
+    }
+}
+
 /// Create system requirements for k3s installation
+///
+/// k3s is installed rootless by default, so its prerequisites are checked.
 pub fn k3s_requirements() -> SystemRequirements {
     SystemRequirements {
         min_disk_space_gb: 10,
@@ -312,6 +653,8 @@ pub fn k3s_requirements() -> SystemRequirements {
         optional_commands: vec!["sudo".to_string()],
         required_directories: vec![],
         required_endpoints: vec!["https://github.com".to_string()],
+        rootless: true,
+        min_k3s_version: Some(MIN_K3S_VERSION.to_string()),
     }
 }
 
@@ -327,6 +670,8 @@ pub fn gitea_requirements() -> SystemRequirements {
         optional_commands: vec![],
         required_directories: vec![],
         required_endpoints: vec![],
+        rootless: false,
+        min_k3s_version: None,
     }
 }
 
@@ -342,6 +687,8 @@ pub fn redis_requirements() -> SystemRequirements {
         optional_commands: vec![],
         required_directories: vec![],
         required_endpoints: vec![],
+        rootless: false,
+        min_k3s_version: None,
     }
 }
 
@@ -357,6 +704,8 @@ pub fn keda_requirements() -> SystemRequirements {
         optional_commands: vec![],
         required_directories: vec![],
         required_endpoints: vec![],
+        rootless: false,
+        min_k3s_version: None,
     }
 }
 
@@ -373,6 +722,8 @@ pub fn flux_requirements() -> SystemRequirements {
         optional_commands: vec!["sudo".to_string()],
         required_directories: vec![],
         required_endpoints: vec!["https://github.com".to_string()],
+        rootless: false,
+        min_k3s_version: None,
     }
 }
 
@@ -436,6 +787,8 @@ mod tests {
         assert!(req.required_commands.contains(&"tar".to_string()));
         assert!(req.required_commands.contains(&"curl".to_string()));
         assert_eq!(req.min_disk_space_gb, 10);
+        assert_eq!(req.min_k3s_version.as_deref(), Some("v1.26.0"));
+        assert!(gitea_requirements().min_k3s_version.is_none());
     }
 
     #[test]
@@ -492,4 +845,126 @@ mod tests {
         assert!(result.passed);
         assert!(!result.warnings.is_empty());
     }
+
+    #[tokio::test]
+    async fn test_validate_all_async_one_result_per_check() {
+        let req = SystemRequirements {
+            required_commands: vec!["nonexistent_command_12345".to_string()],
+            ..Default::default()
+        };
+        let validator = PreFlightValidator::new(req);
+
+        let results = validator.validate_all_async().await;
+
+        assert_eq!(results.len(), 5);
+        assert!(results.iter().any(|r| !r.passed));
+    }
+
+    #[tokio::test]
+    async fn test_run_with_timeout_reports_warning() {
+        let check: CheckFuture = Box::pin(async {
+            tokio::time::sleep(Duration::from_secs(60)).await;
+            PreFlightResult::new()
+        });
+
+        let result = run_with_timeout("gitea".to_string(), check, Duration::from_millis(10)).await;
+
+        assert!(result.passed);
+        assert_eq!(result.warnings, vec!["gitea: check timed out after 10ms"]);
+    }
+
+    /// Fake host with the given files below its root and binaries on its
+    /// path
+    fn host(
+        files: &[(&str, &str)],
+        binaries: &[&str],
+    ) -> (tempfile::TempDir, RootlessPrerequisites) {
+        let dir = tempfile::tempdir().unwrap();
+        for (path, contents) in files {
+            let path = dir.path().join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, contents).unwrap();
+        }
+        let bin = dir.path().join("usr/bin");
+        std::fs::create_dir_all(&bin).unwrap();
+        for binary in binaries {
+            std::fs::write(bin.join(binary), "").unwrap();
+        }
+
+        let prerequisites = RootlessPrerequisites {
+            root: dir.path().to_path_buf(),
+            path: vec![bin],
+            user: "raibid".to_string(),
+            uid: Some(1000),
+        };
+        (dir, prerequisites)
+    }
+
+    #[test]
+    fn test_k3s_requirements_check_rootless() {
+        assert!(k3s_requirements().rootless);
+        assert!(!gitea_requirements().rootless);
+    }
+
+    #[test]
+    fn test_rootless_all_met() {
+        let (_dir, host) = host(
+            &[
+                ("proc/sys/kernel/unprivileged_userns_clone", "1\n"),
+                ("etc/subuid", "raibid:100000:65536\n"),
+            ],
+            &["newuidmap", "newgidmap", "fuse-overlayfs"],
+        );
+        let results = host.check();
+        assert_eq!(results.len(), 4);
+        assert!(results.iter().all(|r| r.passed && r.warnings.is_empty()));
+    }
+
+    #[test]
+    fn test_rootless_uidmap() {
+        let (_dir, missing) = host(&[], &["newuidmap"]);
+        let result = missing.check_uidmap();
+        assert_eq!(result.severity, Severity::Required);
+        assert!(!result.passed);
+        assert_eq!(result.errors.len(), 1);
+        assert!(result.errors[0].message.contains("newgidmap"));
+    }
+
+    #[test]
+    fn test_rootless_userns_clone() {
+        let (_dir, disabled) = host(&[("proc/sys/kernel/unprivileged_userns_clone", "0\n")], &[]);
+        let result = disabled.check_userns_clone();
+        assert!(!result.passed);
+        assert_eq!(result.errors[0].field, "rootless_userns");
+
+        // Kernels without the switch allow user namespaces
+        let (_dir, absent) = host(&[], &[]);
+        assert!(absent.check_userns_clone().passed);
+    }
+
+    #[test]
+    fn test_rootless_subuid() {
+        let (_dir, missing) = host(&[("etc/subuid", "other:100000:65536\n")], &[]);
+        assert!(!missing.check_subuid().passed);
+
+        let (_dir, empty_range) = host(&[("etc/subuid", "raibid:100000:0\n")], &[]);
+        assert!(!empty_range.check_subuid().passed);
+
+        // Entries may name the user by UID
+        let (_dir, by_uid) = host(&[("etc/subuid", "1000:100000:65536\n")], &[]);
+        assert!(by_uid.check_subuid().passed);
+
+        let (_dir, no_file) = host(&[], &[]);
+        assert!(!no_file.check_subuid().passed);
+    }
+
+    #[test]
+    fn test_rootless_fuse_overlayfs_recommended() {
+        let (_dir, missing) = host(&[], &[]);
+        let result = missing.check_fuse_overlayfs();
+        assert_eq!(result.severity, Severity::Recommended);
+        assert!(result.passed);
+        assert!(result.errors.is_empty());
+        assert_eq!(result.warnings.len(), 1);
+    }
 }
@@ -7,8 +7,12 @@ use anyhow::{Context, Result, anyhow};
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::time::Duration;
 use tracing::{debug, info, warn};
 
+use super::error::{RemediationContext, HELM_INSTALL_HINT};
+use super::plan::PlanStep;
+
 /// Redis Helm chart information
 const REDIS_HELM_REPO: &str = "https://charts.bitnami.com/bitnami";
 const REDIS_HELM_REPO_NAME: &str = "bitnami";
@@ -16,6 +20,12 @@ const REDIS_CHART_NAME: &str = "bitnami/redis";
 const REDIS_RELEASE_NAME: &str = "raibid-redis";
 const REDIS_NAMESPACE: &str = "raibid-redis";
 
+/// Secret holding the Redis password for the KEDA scaler
+pub const REDIS_AUTH_SECRET: &str = "raibid-redis-auth";
+
+/// Key of the password in [`REDIS_AUTH_SECRET`]
+pub const REDIS_AUTH_SECRET_KEY: &str = "redisPassword";
+
 /// Redis Streams configuration for job queue
 #[derive(Debug, Clone)]
 pub struct RedisStreamsConfig {
@@ -55,6 +65,9 @@ pub struct RedisConfig {
     pub auth_enabled: bool,
     /// Password for Redis (generated if not provided)
     pub password: Option<String>,
+    /// Namespace of the agents, where [`REDIS_AUTH_SECRET`] is created for
+    /// the KEDA scaler
+    pub auth_secret_namespace: String,
     /// Enable Redis Sentinel for HA
     pub sentinel_enabled: bool,
     /// Number of Redis replicas
@@ -76,6 +89,7 @@ impl Default for RedisConfig {
             persistence_size: "8Gi".to_string(),
             auth_enabled: true,
             password: None, // Auto-generate
+            auth_secret_namespace: "raibid-ci".to_string(),
             sentinel_enabled: false, // MVP: single instance
             replica_count: 0, // MVP: no replicas
             streams_config: RedisStreamsConfig::default(),
@@ -84,6 +98,63 @@ impl Default for RedisConfig {
     }
 }
 
+impl RedisConfig {
+    /// Actions installing Redis with this configuration would take
+    pub fn plan(&self) -> Vec<PlanStep> {
+        let persistence = if self.persistence_enabled {
+            format!("{} persistent volume", self.persistence_size)
+        } else {
+            "no persistence".to_string()
+        };
+
+        let mut steps = vec![
+            PlanStep::new(
+                format!("Add Helm repository {}", REDIS_HELM_REPO),
+                Duration::from_secs(5),
+            ),
+            PlanStep::new(
+                format!("Create namespace '{}'", self.namespace),
+                Duration::from_secs(2),
+            ),
+            PlanStep::new(
+                format!(
+                    "Deploy Helm release '{}' ({}, {})",
+                    self.release_name, REDIS_CHART_NAME, persistence
+                ),
+                Duration::from_secs(90),
+            ),
+        ];
+
+        if self.auth_enabled {
+            steps.push(PlanStep::new(
+                format!(
+                    "Create Secret '{}' in namespace '{}' for the KEDA scaler",
+                    REDIS_AUTH_SECRET, self.auth_secret_namespace
+                ),
+                Duration::from_secs(2),
+            ));
+        }
+
+        steps.extend([
+            PlanStep::new("Wait for Redis to become ready", Duration::from_secs(60)),
+            PlanStep::new(
+                format!(
+                    "Create stream '{}' with consumer group '{}'",
+                    self.streams_config.queue_stream, self.streams_config.consumer_group
+                ),
+                Duration::from_secs(2),
+            ),
+            PlanStep::new("Validate the installation", Duration::from_secs(5)),
+            PlanStep::new(
+                "Save connection credentials to ~/.raibid/redis-credentials.json",
+                Duration::from_secs(1),
+            ),
+        ]);
+
+        steps
+    }
+}
+
 /// Redis installer
 pub struct RedisInstaller {
     config: RedisConfig,
@@ -100,6 +171,13 @@ impl RedisInstaller {
         Ok(Self { config })
     }
 
+    /// Actions an installation would take, for dry runs
+    ///
+    /// See [`RedisConfig::plan`].
+    pub fn plan(&self) -> Vec<PlanStep> {
+        self.config.plan()
+    }
+
     /// Check if kubectl is available
     #[allow(dead_code)]
     fn check_kubectl(&self) -> Result<()> {
@@ -148,7 +226,7 @@ impl RedisInstaller {
             .arg(REDIS_HELM_REPO)
             .env("KUBECONFIG", &self.config.kubeconfig_path)
             .output()
-            .context("Failed to add Helm repository")?;
+            .remediation("Failed to add Helm repository", HELM_INSTALL_HINT)?;
 
         if !output.status.success() {
             let stderr = String::from_utf8_lossy(&output.stderr);
@@ -280,6 +358,70 @@ commonConfiguration: |-
         Ok(values)
     }
 
+    /// Generate the YAML manifest of the password Secret for the KEDA scaler
+    fn generate_auth_secret_yaml(&mut self) -> String {
+        let password = self.get_or_generate_password();
+
+        format!(
+            r#"apiVersion: v1
+kind: Secret
+metadata:
+  name: {name}
+  namespace: {namespace}
+type: Opaque
+stringData:
+  {key}: "{password}"
+"#,
+            name = REDIS_AUTH_SECRET,
+            namespace = self.config.auth_secret_namespace,
+            key = REDIS_AUTH_SECRET_KEY,
+            password = password,
+        )
+    }
+
+    /// Create the Secret the KEDA scaler reads the Redis password from
+    ///
+    /// KEDA can only reference Secrets in the ScaledObject's namespace, so the
+    /// Secret is created in `auth_secret_namespace` rather than next to Redis.
+    pub fn create_auth_secret(&mut self) -> Result<()> {
+        info!(
+            "Creating Secret {} in namespace {}",
+            REDIS_AUTH_SECRET, self.config.auth_secret_namespace
+        );
+
+        // Create target namespace if it doesn't exist
+        let _ = Command::new("kubectl")
+            .arg("create")
+            .arg("namespace")
+            .arg(&self.config.auth_secret_namespace)
+            .env("KUBECONFIG", &self.config.kubeconfig_path)
+            .output();
+
+        let yaml = self.generate_auth_secret_yaml();
+        let yaml_file = std::env::temp_dir().join("redis-auth-secret.yaml");
+        fs::write(&yaml_file, yaml)
+            .context("Failed to write Secret YAML file")?;
+
+        let output = Command::new("kubectl")
+            .arg("apply")
+            .arg("-f")
+            .arg(&yaml_file)
+            .env("KUBECONFIG", &self.config.kubeconfig_path)
+            .output()
+            .context("Failed to apply Redis password Secret")?;
+
+        // Clean up YAML file
+        let _ = fs::remove_file(&yaml_file);
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(anyhow!("Failed to create Redis password Secret: {}", stderr));
+        }
+
+        info!("Secret created successfully");
+        Ok(())
+    }
+
     /// Deploy Redis using Helm
     pub fn deploy_redis(&mut self) -> Result<()> {
         info!("Deploying Redis via Helm");
@@ -540,6 +682,11 @@ commonConfiguration: |-
         // Deploy Redis
         self.deploy_redis()?;
 
+        // Share the password with the KEDA scaler
+        if self.config.auth_enabled {
+            self.create_auth_secret()?;
+        }
+
         // Wait for ready
         self.wait_for_ready()?;
 
@@ -561,6 +708,17 @@ commonConfiguration: |-
     pub fn uninstall(&self) -> Result<()> {
         info!("Uninstalling Redis");
 
+        if self.config.auth_enabled {
+            let _ = Command::new("kubectl")
+                .arg("delete")
+                .arg("secret")
+                .arg(REDIS_AUTH_SECRET)
+                .arg("--namespace")
+                .arg(&self.config.auth_secret_namespace)
+                .env("KUBECONFIG", &self.config.kubeconfig_path)
+                .output();
+        }
+
         let output = Command::new("helm")
             .arg("uninstall")
             .arg(&self.config.release_name)
@@ -671,6 +829,20 @@ mod tests {
         assert!(values_str.contains("appendonly yes"));
     }
 
+    #[test]
+    fn test_auth_secret_yaml() {
+        let mut installer = RedisInstaller::with_config(RedisConfig {
+            password: Some("testpass".to_string()),
+            ..RedisConfig::default()
+        })
+        .unwrap();
+        let yaml = installer.generate_auth_secret_yaml();
+
+        assert!(yaml.contains("name: raibid-redis-auth"));
+        assert!(yaml.contains("namespace: raibid-ci"));
+        assert!(yaml.contains("redisPassword: \"testpass\""));
+    }
+
     #[test]
     fn test_connection_info_url() {
         let conn_info = RedisConnectionInfo {
@@ -8,16 +8,41 @@
 use tracing::{debug, info, warn};
 
 use crate::infrastructure::error::{InfraError, InfraResult};
+use crate::infrastructure::k3s::K3sInstaller;
 
 /// Rollback action that can be executed
 pub type RollbackAction = Box<dyn FnOnce() -> InfraResult<()> + Send>;
 
+/// Priority of removing Flux, which reconciles everything else into the cluster
+pub const FLUX_PRIORITY: u8 = 50;
+/// Priority of removing KEDA, which scales agents based on Redis
+pub const KEDA_PRIORITY: u8 = 40;
+/// Priority of removing Redis
+pub const REDIS_PRIORITY: u8 = 30;
+/// Priority of removing Gitea
+pub const GITEA_PRIORITY: u8 = 20;
+/// Priority of removing k3s, which every other component runs on
+pub const K3S_PRIORITY: u8 = 10;
+/// Priority of actions added with [`RollbackManager::add_action`]
+pub const DEFAULT_PRIORITY: u8 = 0;
+
+/// Rollback action waiting to be executed
+struct PendingAction {
+    description: String,
+    priority: u8,
+    action: RollbackAction,
+}
+
 /// Rollback manager for tracking and executing cleanup actions
+///
+/// Actions run in descending priority, and actions of the same priority in
+/// reverse order of being added. Components are torn down in reverse
+/// dependency order by adding them with the `*_PRIORITY` constants.
 pub struct RollbackManager {
     /// Component name
     component: String,
-    /// Stack of rollback actions
-    actions: Vec<(String, RollbackAction)>,
+    /// Rollback actions in the order they were added
+    actions: Vec<PendingAction>,
     /// Whether to automatically rollback on drop
     auto_rollback: bool,
 }
@@ -32,11 +57,54 @@ impl RollbackManager {
         }
     }
 
-    /// Add a rollback action with a description
+    /// Add a rollback action with a description and [`DEFAULT_PRIORITY`]
     pub fn add_action(&mut self, description: impl Into<String>, action: RollbackAction) {
-        let desc = description.into();
-        debug!("Adding rollback action: {}", desc);
-        self.actions.push((desc, action));
+        self.push_with_priority(description, action, DEFAULT_PRIORITY);
+    }
+
+    /// Add a rollback action that runs before every action of lower priority
+    pub fn push_with_priority(
+        &mut self,
+        description: impl Into<String>,
+        action: RollbackAction,
+        priority: u8,
+    ) {
+        let description = description.into();
+        debug!("Adding rollback action: {} (priority {})", description, priority);
+        self.actions.push(PendingAction {
+            description,
+            priority,
+            action,
+        });
+    }
+
+    /// Add rolling back a k3s installation with [`K3sInstaller::rollback`]
+    ///
+    /// The action has [`K3S_PRIORITY`], so the cluster is only removed once
+    /// every component added with a higher priority was uninstalled.
+    pub fn add_k3s_rollback(&mut self, installer: K3sInstaller) {
+        self.push_with_priority(
+            "Roll back k3s installation",
+            Box::new(move || {
+                installer.rollback().map_err(|e| InfraError::Rollback {
+                    component: "k3s".to_string(),
+                    reason: format!("{:#}", e),
+                    partial_cleanup: Vec::new(),
+                })
+            }),
+            K3S_PRIORITY,
+        );
+    }
+
+    /// Descriptions of the actions a rollback would execute, in order
+    pub fn dry_run(&self) -> Vec<String> {
+        let mut ordered: Vec<&PendingAction> = self.actions.iter().collect();
+        ordered.sort_by_key(|pending| pending.priority);
+        ordered
+            .iter()
+            .rev()
+            .map(|pending| pending.description.clone())
+            .collect()
     }
 
     /// Disable automatic rollback on drop
@@ -51,7 +119,7 @@ impl RollbackManager {
         self.actions.clear();
     }
 
-    /// Execute all rollback actions in reverse order
+    /// Execute all rollback actions in the order shown by [`dry_run`](Self::dry_run)
     pub fn rollback(mut self) -> InfraResult<()> {
         self.execute_rollback()
     }
@@ -68,8 +136,16 @@ impl RollbackManager {
         let mut failed_actions = Vec::new();
         let mut successful_cleanups = Vec::new();
 
-        // Execute actions in reverse order (LIFO)
-        while let Some((description, action)) = self.actions.pop() {
+        // Highest priority first, then in reverse order (LIFO); the sort is
+        // stable, so actions of the same priority keep the order they were
+        // added in
+        self.actions.sort_by_key(|pending| pending.priority);
+        while let Some(PendingAction {
+            description,
+            action,
+            ..
+        }) = self.actions.pop()
+        {
             debug!("Executing rollback action: {}", description);
 
             match action() {
@@ -482,4 +558,101 @@ mod tests {
         let result = manager.rollback();
         assert!(result.is_ok());
     }
+
+    #[test]
+    fn test_k3s_rolled_back_after_components() {
+        use crate::infrastructure::k3s::{InstallManifest, K3sConfig};
+        use std::fs;
+        use std::sync::{Arc, Mutex};
+
+        let dir = tempfile::tempdir().unwrap();
+        let binary = dir.path().join("k3s");
+        fs::write(&binary, "").unwrap();
+        let config = K3sConfig {
+            install_dir: dir.path().to_path_buf(),
+            data_dir: dir.path().join("data"),
+            kubeconfig_path: dir.path().join("config"),
+            install_manifest: dir.path().join("k3s-install.json"),
+            uninstall_script: dir.path().join("k3s-uninstall.sh"),
+            ..Default::default()
+        };
+        let manifest = InstallManifest {
+            binary: Some(binary.clone()),
+            ..Default::default()
+        };
+        manifest.save(&config.install_manifest).unwrap();
+        let Ok(installer) = K3sInstaller::with_config(config) else {
+            return; // Unsupported platform
+        };
+
+        let mut manager = RollbackManager::new("raibid");
+        manager.add_k3s_rollback(installer);
+        let cluster_present = Arc::new(Mutex::new(None));
+        manager.push_with_priority(
+            "Uninstall Gitea",
+            {
+                let cluster_present = cluster_present.clone();
+                let binary = binary.clone();
+                Box::new(move || {
+                    *cluster_present.lock().unwrap() = Some(binary.exists());
+                    Ok(())
+                })
+            },
+            GITEA_PRIORITY,
+        );
+
+        manager.disable_auto_rollback();
+        manager.rollback().unwrap();
+        assert_eq!(*cluster_present.lock().unwrap(), Some(true));
+        assert!(!binary.exists());
+    }
+
+    #[test]
+    fn test_components_rolled_back_in_dependency_order() {
+        use std::sync::{Arc, Mutex};
+
+        let executed = Arc::new(Mutex::new(Vec::new()));
+        let mut manager = RollbackManager::new("raibid");
+        // Installation order, with an unprioritized action in between
+        for (name, priority) in [
+            ("k3s", K3S_PRIORITY),
+            ("gitea", GITEA_PRIORITY),
+            ("cleanup", DEFAULT_PRIORITY),
+            ("redis", REDIS_PRIORITY),
+            ("keda", KEDA_PRIORITY),
+            ("flux", FLUX_PRIORITY),
+        ] {
+            let executed = executed.clone();
+            manager.push_with_priority(
+                name,
+                Box::new(move || {
+                    executed.lock().unwrap().push(name.to_string());
+                    Ok(())
+                }),
+                priority,
+            );
+        }
+
+        let planned = manager.dry_run();
+        assert_eq!(
+            planned,
+            vec!["flux", "keda", "redis", "gitea", "k3s", "cleanup"]
+        );
+
+        manager.rollback().unwrap();
+        assert_eq!(*executed.lock().unwrap(), planned);
+    }
+
+    #[test]
+    fn test_same_priority_rolled_back_in_reverse() {
+        let mut manager = RollbackManager::new("gitea");
+        manager.push_with_priority("create namespace", Box::new(|| Ok(())), GITEA_PRIORITY);
+        manager.push_with_priority("install chart", Box::new(|| Ok(())), GITEA_PRIORITY);
+        manager.add_action("remove download", Box::new(|| Ok(())));
+        assert_eq!(
+            manager.dry_run(),
+            vec!["install chart", "create namespace", "remove download"]
+        );
+        manager.disable_auto_rollback();
+    }
 }
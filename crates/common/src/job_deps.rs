@@ -0,0 +1,475 @@
+//! Job dependency chains
+//!
+//! A job whose trigger lists [`depends_on`](crate::jobs::JobTrigger::depends_on)
+//! is not added to a job stream when it is queued. [`hold_job`] keeps its
+//! stream entry in [`PENDING_DEPS_JOBS_KEY`], scores it in the
+//! [`PENDING_DEPS_KEY`] sorted set by the number of dependencies that have
+//! not succeeded yet, and adds it to the [`dependents_key`] set of each
+//! dependency. When a job succeeds, [`release_dependents`] decrements the
+//! count of every job waiting for it and queues those with nothing left to
+//! wait for.
+//!
+//! A dependency is counted off once per waiting job, by whoever removes the
+//! job from the dependency's set, so a dependency succeeding while a job is
+//! being held is not counted twice. Jobs waiting for a dependency that fails
+//! or is cancelled keep waiting.
+
+use redis::aio::ConnectionLike;
+use redis::{ErrorKind, RedisError, RedisResult};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+use tracing::info;
+
+use crate::jobs::{job_key, JobStatus, QueuedJob, JOB_FIELD_STATUS};
+
+/// Sorted set of jobs waiting for their dependencies, scored by the number
+/// of dependencies that have not succeeded yet
+pub const PENDING_DEPS_KEY: &str = "raibid:pending-deps";
+
+/// Hash from the ID of a job waiting for its dependencies to its stream
+/// entry fields as JSON
+pub const PENDING_DEPS_JOBS_KEY: &str = "raibid:pending-deps:jobs";
+
+/// Key of the set of jobs waiting for `job_id` to succeed
+pub fn dependents_key(job_id: &str) -> String {
+    format!("raibid:dependents:{}", job_id)
+}
+
+/// A job waiting, directly or through other jobs, for another job
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DependentJob {
+    /// Waiting job
+    pub id: String,
+    /// Dependency it is waiting for in this chain
+    pub waiting_for: String,
+    /// Dependencies of the job that have not succeeded yet
+    pub remaining_dependencies: u64,
+}
+
+/// Hold `job`, with stream entry `fields`, until its dependencies succeed
+///
+/// Dependencies that already succeeded are counted off right away. Returns
+/// the stream message ID if that leaves nothing to wait for and the job was
+/// queued on the stream for its priority of `base_stream`.
+pub async fn hold_job<C>(
+    conn: &mut C,
+    base_stream: &str,
+    job: &QueuedJob,
+    fields: &[(&'static str, String)],
+) -> RedisResult<Option<String>>
+where
+    C: ConnectionLike + Send,
+{
+    let dependencies: BTreeSet<&str> = job.trigger.depends_on.iter().map(String::as_str).collect();
+    let entry: HashMap<&str, &str> = fields
+        .iter()
+        .map(|(field, value)| (*field, value.as_str()))
+        .collect();
+    let entry = serde_json::to_string(&entry).map_err(|e| encoding_error(&job.id, e))?;
+
+    redis::cmd("HSET")
+        .arg(PENDING_DEPS_JOBS_KEY)
+        .arg(&job.id)
+        .arg(entry)
+        .query_async::<_, ()>(conn)
+        .await?;
+    redis::cmd("ZADD")
+        .arg(PENDING_DEPS_KEY)
+        .arg(dependencies.len())
+        .arg(&job.id)
+        .query_async::<_, ()>(conn)
+        .await?;
+    for dependency in &dependencies {
+        redis::cmd("SADD")
+            .arg(dependents_key(dependency))
+            .arg(&job.id)
+            .query_async::<_, ()>(conn)
+            .await?;
+    }
+
+    // After registering, so a dependency finishing in between is seen by
+    // either its agent or this check
+    let mut entry_id = None;
+    for dependency in &dependencies {
+        let status: Option<String> = redis::cmd("HGET")
+            .arg(job_key(dependency))
+            .arg(JOB_FIELD_STATUS)
+            .query_async(conn)
+            .await?;
+        if status.as_deref() == Some(JobStatus::Success.as_str()) {
+            if let Some(id) = satisfy_dependency(conn, base_stream, dependency, &job.id).await? {
+                entry_id = Some(id);
+            }
+        }
+    }
+    Ok(entry_id)
+}
+
+/// Count off `job_id` for every job waiting for it, returning the IDs of
+/// the jobs that were queued because nothing is left to wait for
+///
+/// Called once the job has succeeded.
+pub async fn release_dependents<C>(
+    conn: &mut C,
+    base_stream: &str,
+    job_id: &str,
+) -> RedisResult<Vec<String>>
+where
+    C: ConnectionLike + Send,
+{
+    let mut dependents: Vec<String> = redis::cmd("SMEMBERS")
+        .arg(dependents_key(job_id))
+        .query_async(conn)
+        .await?;
+    dependents.sort();
+
+    let mut released = Vec::new();
+    for dependent in dependents {
+        if satisfy_dependency(conn, base_stream, job_id, &dependent)
+            .await?
+            .is_some()
+        {
+            released.push(dependent);
+        }
+    }
+    Ok(released)
+}
+
+/// Jobs waiting for `job_id`, then the jobs waiting for those, and so on
+pub async fn dependent_chain<C>(conn: &mut C, job_id: &str) -> RedisResult<Vec<DependentJob>>
+where
+    C: ConnectionLike + Send,
+{
+    let mut chain = Vec::new();
+    let mut seen = HashSet::from([job_id.to_string()]);
+    let mut queue = VecDeque::from([job_id.to_string()]);
+
+    while let Some(dependency) = queue.pop_front() {
+        let mut dependents: Vec<String> = redis::cmd("SMEMBERS")
+            .arg(dependents_key(&dependency))
+            .query_async(conn)
+            .await?;
+        dependents.sort();
+
+        for dependent in dependents {
+            if !seen.insert(dependent.clone()) {
+                continue;
+            }
+            let remaining: Option<f64> = redis::cmd("ZSCORE")
+                .arg(PENDING_DEPS_KEY)
+                .arg(&dependent)
+                .query_async(conn)
+                .await?;
+            chain.push(DependentJob {
+                id: dependent.clone(),
+                waiting_for: dependency.clone(),
+                remaining_dependencies: remaining.unwrap_or(0.0).max(0.0) as u64,
+            });
+            queue.push_back(dependent);
+        }
+    }
+    Ok(chain)
+}
+
+/// Count off `dependency` for the waiting job `job_id`, queueing the job if
+/// that was its last one
+///
+/// Returns the stream message ID if the job was queued.
+async fn satisfy_dependency<C>(
+    conn: &mut C,
+    base_stream: &str,
+    dependency: &str,
+    job_id: &str,
+) -> RedisResult<Option<String>>
+where
+    C: ConnectionLike + Send,
+{
+    let removed: bool = redis::cmd("SREM")
+        .arg(dependents_key(dependency))
+        .arg(job_id)
+        .query_async(conn)
+        .await?;
+    if !removed {
+        return Ok(None);
+    }
+
+    let remaining: f64 = redis::cmd("ZINCRBY")
+        .arg(PENDING_DEPS_KEY)
+        .arg(-1)
+        .arg(job_id)
+        .query_async(conn)
+        .await?;
+    if remaining > 0.0 {
+        return Ok(None);
+    }
+
+    redis::cmd("ZREM")
+        .arg(PENDING_DEPS_KEY)
+        .arg(job_id)
+        .query_async::<_, ()>(conn)
+        .await?;
+    let entry: Option<String> = redis::cmd("HGET")
+        .arg(PENDING_DEPS_JOBS_KEY)
+        .arg(job_id)
+        .query_async(conn)
+        .await?;
+    redis::cmd("HDEL")
+        .arg(PENDING_DEPS_JOBS_KEY)
+        .arg(job_id)
+        .query_async::<_, ()>(conn)
+        .await?;
+    let Some(entry) = entry else {
+        return Ok(None);
+    };
+
+    let fields: HashMap<String, String> =
+        serde_json::from_str(&entry).map_err(|e| encoding_error(job_id, e))?;
+    let job = QueuedJob::from_stream_fields(&fields).map_err(|e| encoding_error(job_id, e))?;
+    let stream = job.trigger.priority.stream_key(base_stream);
+
+    let mut fields: Vec<(String, String)> = fields.into_iter().collect();
+    fields.sort();
+    let entry_id: String = redis::cmd("XADD")
+        .arg(&stream)
+        .arg("*")
+        .arg(&fields)
+        .query_async(conn)
+        .await?;
+    info!(
+        "Queued job {} on '{}': its dependencies succeeded",
+        job_id, stream
+    );
+    Ok(Some(entry_id))
+}
+
+fn encoding_error(job_id: &str, e: impl std::fmt::Display) -> RedisError {
+    RedisError::from((
+        ErrorKind::TypeError,
+        "Invalid held job",
+        format!("job {}: {:#}", job_id, e),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jobs::JobBuilder;
+    use redis::Value;
+
+    /// In-memory Redis supporting the commands used for dependency chains
+    #[derive(Default)]
+    struct FakeRedis {
+        sets: HashMap<String, BTreeSet<String>>,
+        hashes: HashMap<String, HashMap<String, String>>,
+        scores: HashMap<String, f64>,
+        streams: HashMap<String, Vec<HashMap<String, String>>>,
+    }
+
+    impl FakeRedis {
+        fn succeed(&mut self, job_id: &str) {
+            self.hashes
+                .entry(job_key(job_id))
+                .or_default()
+                .insert(JOB_FIELD_STATUS.to_string(), "success".to_string());
+        }
+
+        /// IDs of the jobs on the normal priority stream, oldest first
+        fn queued(&self) -> Vec<String> {
+            self.streams
+                .get("raibid:jobs:normal")
+                .into_iter()
+                .flatten()
+                .map(|fields| fields["job_id"].clone())
+                .collect()
+        }
+
+        fn execute(&mut self, args: &[String]) -> Value {
+            let int = |n: bool| Value::Int(i64::from(n));
+            let data = |s: &str| Value::Data(s.as_bytes().to_vec());
+            match args[0].as_str() {
+                "SADD" => int(self
+                    .sets
+                    .entry(args[1].clone())
+                    .or_default()
+                    .insert(args[2].clone())),
+                "SREM" => int(self
+                    .sets
+                    .entry(args[1].clone())
+                    .or_default()
+                    .remove(&args[2])),
+                "SMEMBERS" => Value::Bulk(
+                    self.sets
+                        .get(&args[1])
+                        .into_iter()
+                        .flatten()
+                        .map(|member| data(member))
+                        .collect(),
+                ),
+                "HSET" => int(self
+                    .hashes
+                    .entry(args[1].clone())
+                    .or_default()
+                    .insert(args[2].clone(), args[3].clone())
+                    .is_none()),
+                "HGET" => match self.hashes.get(&args[1]).and_then(|h| h.get(&args[2])) {
+                    Some(value) => data(value),
+                    None => Value::Nil,
+                },
+                "HDEL" => int(self
+                    .hashes
+                    .entry(args[1].clone())
+                    .or_default()
+                    .remove(&args[2])
+                    .is_some()),
+                "ZADD" => int(self
+                    .scores
+                    .insert(args[3].clone(), args[2].parse().unwrap())
+                    .is_none()),
+                "ZINCRBY" => {
+                    let score = self.scores.entry(args[3].clone()).or_default();
+                    *score += args[2].parse::<f64>().unwrap();
+                    data(&score.to_string())
+                }
+                "ZREM" => int(self.scores.remove(&args[2]).is_some()),
+                "ZSCORE" => match self.scores.get(&args[2]) {
+                    Some(score) => data(&score.to_string()),
+                    None => Value::Nil,
+                },
+                "XADD" => {
+                    let fields = args[3..]
+                        .chunks(2)
+                        .map(|pair| (pair[0].clone(), pair[1].clone()))
+                        .collect();
+                    let stream = self.streams.entry(args[1].clone()).or_default();
+                    stream.push(fields);
+                    data(&format!("{}-0", stream.len()))
+                }
+                other => panic!("unexpected command {}", other),
+            }
+        }
+    }
+
+    impl ConnectionLike for FakeRedis {
+        fn req_packed_command<'a>(
+            &'a mut self,
+            cmd: &'a redis::Cmd,
+        ) -> redis::RedisFuture<'a, Value> {
+            let args: Vec<String> = cmd
+                .args_iter()
+                .map(|arg| match arg {
+                    redis::Arg::Simple(bytes) => String::from_utf8_lossy(bytes).to_string(),
+                    redis::Arg::Cursor => "0".to_string(),
+                })
+                .collect();
+            let result = self.execute(&args);
+            Box::pin(async move { Ok(result) })
+        }
+
+        fn req_packed_commands<'a>(
+            &'a mut self,
+            _pipeline: &'a redis::Pipeline,
+            _offset: usize,
+            _count: usize,
+        ) -> redis::RedisFuture<'a, Vec<Value>> {
+            Box::pin(async { Ok(Vec::new()) })
+        }
+
+        fn get_db(&self) -> i64 {
+            0
+        }
+    }
+
+    async fn hold(conn: &mut FakeRedis, id: &str, depends_on: &[&str]) -> Option<String> {
+        let trigger = depends_on
+            .iter()
+            .fold(JobBuilder::new("raibid-labs/raibid-cli"), |builder, id| {
+                builder.depends_on(*id)
+            })
+            .build()
+            .unwrap();
+        let job = QueuedJob::new(id, trigger);
+        let fields = job.to_stream_fields().unwrap();
+        hold_job(conn, "raibid:jobs", &job, &fields).await.unwrap()
+    }
+
+    async fn succeed(conn: &mut FakeRedis, id: &str) -> Vec<String> {
+        conn.succeed(id);
+        release_dependents(conn, "raibid:jobs", id).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_linear_chain() {
+        let mut conn = FakeRedis::default();
+        assert_eq!(hold(&mut conn, "job-b", &["job-a"]).await, None);
+        assert_eq!(hold(&mut conn, "job-c", &["job-b"]).await, None);
+        assert!(conn.queued().is_empty());
+
+        let chain = dependent_chain(&mut conn, "job-a").await.unwrap();
+        assert_eq!(
+            chain,
+            vec![
+                DependentJob {
+                    id: "job-b".to_string(),
+                    waiting_for: "job-a".to_string(),
+                    remaining_dependencies: 1,
+                },
+                DependentJob {
+                    id: "job-c".to_string(),
+                    waiting_for: "job-b".to_string(),
+                    remaining_dependencies: 1,
+                },
+            ]
+        );
+
+        assert_eq!(succeed(&mut conn, "job-a").await, vec!["job-b"]);
+        assert_eq!(conn.queued(), vec!["job-b"]);
+        assert!(!conn.hashes[PENDING_DEPS_JOBS_KEY].contains_key("job-b"));
+
+        // Released jobs keep their trigger, dependencies included
+        let fields = &conn.streams["raibid:jobs:normal"][0];
+        let job = QueuedJob::from_stream_fields(fields).unwrap();
+        assert_eq!(job.trigger.depends_on, vec!["job-a"]);
+
+        assert_eq!(succeed(&mut conn, "job-b").await, vec!["job-c"]);
+        assert_eq!(conn.queued(), vec!["job-b", "job-c"]);
+        assert!(conn.scores.is_empty());
+        assert!(dependent_chain(&mut conn, "job-a")
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_fan_in() {
+        let mut conn = FakeRedis::default();
+        assert_eq!(hold(&mut conn, "job-c", &["job-a", "job-b"]).await, None);
+        assert_eq!(conn.scores["job-c"], 2.0);
+
+        assert!(succeed(&mut conn, "job-b").await.is_empty());
+        assert_eq!(conn.scores["job-c"], 1.0);
+        assert!(conn.queued().is_empty());
+
+        // A second success of the same dependency is not counted again
+        assert!(succeed(&mut conn, "job-b").await.is_empty());
+        assert_eq!(conn.scores["job-c"], 1.0);
+
+        assert_eq!(succeed(&mut conn, "job-a").await, vec!["job-c"]);
+        assert_eq!(conn.queued(), vec!["job-c"]);
+    }
+
+    #[tokio::test]
+    async fn test_succeeded_dependencies_counted_when_held() {
+        let mut conn = FakeRedis::default();
+        conn.succeed("job-a");
+
+        assert_eq!(hold(&mut conn, "job-c", &["job-a", "job-b"]).await, None);
+        assert_eq!(conn.scores["job-c"], 1.0);
+
+        conn.succeed("job-b");
+        assert_eq!(
+            hold(&mut conn, "job-d", &["job-a", "job-b"]).await,
+            Some("1-0".to_string())
+        );
+        assert_eq!(conn.queued(), vec!["job-d"]);
+    }
+}
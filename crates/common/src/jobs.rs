@@ -0,0 +1,1150 @@
+//! Job types shared between the server, agents, and clients
+//!
+//! A [`JobTrigger`] is the request to build a repository at a given ref. It is
+//! what clients submit to `POST /api/jobs` and what gets queued on the job
+//! stream. Use [`JobBuilder`] to construct or validate one.
+
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Request to run a build
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct JobTrigger {
+    /// Repository in `owner/name` form
+    pub repo: String,
+    /// Branch to build
+    #[serde(default = "default_branch")]
+    pub branch: String,
+    /// Specific commit to build (defaults to the branch head)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub commit: Option<String>,
+    /// Extra environment variables for the build
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub env: HashMap<String, String>,
+    /// Queue priority
+    #[serde(default, skip_serializing_if = "JobPriority::is_normal")]
+    pub priority: JobPriority,
+    /// Labels to filter jobs by, e.g. `source=gitlab`
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub labels: HashMap<String, String>,
+    /// Capabilities an agent needs to run the job, e.g. `docker` or `gpu`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub required_capabilities: Vec<String>,
+    /// Jobs that must succeed before this one is queued, see
+    /// [`crate::job_deps`]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub depends_on: Vec<String>,
+}
+
+impl JobTrigger {
+    /// Required capabilities missing from `capabilities`
+    pub fn missing_capabilities<'a>(&'a self, capabilities: &[String]) -> Vec<&'a str> {
+        self.required_capabilities
+            .iter()
+            .filter(|required| !capabilities.contains(required))
+            .map(String::as_str)
+            .collect()
+    }
+}
+
+impl Default for JobTrigger {
+    fn default() -> Self {
+        Self {
+            repo: String::new(),
+            branch: default_branch(),
+            commit: None,
+            env: HashMap::new(),
+            priority: JobPriority::Normal,
+            labels: HashMap::new(),
+            required_capabilities: Vec::new(),
+            depends_on: Vec::new(),
+        }
+    }
+}
+
+fn default_branch() -> String {
+    "main".to_string()
+}
+
+/// Job queue priority
+///
+/// Each priority has its own Redis stream (see [`JobPriority::stream_key`]).
+/// Agents drain higher-priority streams first.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JobPriority {
+    High,
+    #[default]
+    Normal,
+    Low,
+}
+
+impl JobPriority {
+    /// All priorities, highest first
+    pub const ALL: [JobPriority; 3] = [JobPriority::High, JobPriority::Normal, JobPriority::Low];
+
+    /// Get priority as string
+    pub fn as_str(&self) -> &str {
+        match self {
+            JobPriority::High => "high",
+            JobPriority::Normal => "normal",
+            JobPriority::Low => "low",
+        }
+    }
+
+    /// Whether this is the default priority
+    pub fn is_normal(&self) -> bool {
+        *self == JobPriority::Normal
+    }
+
+    /// Stream holding jobs of this priority, e.g. `raibid:jobs:high` for the
+    /// base job stream `raibid:jobs`
+    pub fn stream_key(&self, base_stream: &str) -> String {
+        format!("{}:{}", base_stream, self.as_str())
+    }
+}
+
+impl std::fmt::Display for JobPriority {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl std::str::FromStr for JobPriority {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "high" => Ok(JobPriority::High),
+            "normal" => Ok(JobPriority::Normal),
+            "low" => Ok(JobPriority::Low),
+            _ => Err(anyhow::anyhow!(
+                "Unknown job priority: {} (expected high, normal, or low)",
+                s
+            )),
+        }
+    }
+}
+
+/// Job execution status
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JobStatus {
+    Pending,
+    Running,
+    /// Failed and waiting to be queued again
+    Retrying,
+    Success,
+    Failed,
+    Cancelled,
+}
+
+impl JobStatus {
+    /// Get status as string
+    pub fn as_str(&self) -> &str {
+        match self {
+            JobStatus::Pending => "pending",
+            JobStatus::Running => "running",
+            JobStatus::Retrying => "retrying",
+            JobStatus::Success => "success",
+            JobStatus::Failed => "failed",
+            JobStatus::Cancelled => "cancelled",
+        }
+    }
+
+    /// Whether the job has finished and will not change status again
+    pub fn is_terminal(&self) -> bool {
+        matches!(
+            self,
+            JobStatus::Success | JobStatus::Failed | JobStatus::Cancelled
+        )
+    }
+}
+
+impl std::fmt::Display for JobStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl std::str::FromStr for JobStatus {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "pending" => Ok(JobStatus::Pending),
+            "running" => Ok(JobStatus::Running),
+            "retrying" => Ok(JobStatus::Retrying),
+            "success" => Ok(JobStatus::Success),
+            "failed" => Ok(JobStatus::Failed),
+            "cancelled" => Ok(JobStatus::Cancelled),
+            _ => Err(anyhow::anyhow!("Unknown job status: {}", s)),
+        }
+    }
+}
+
+/// A queued or executed job
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Job {
+    /// Unique job identifier
+    pub id: String,
+    /// Repository in `owner/name` form
+    pub repo: String,
+    /// Branch being built
+    pub branch: String,
+    /// Commit being built
+    #[serde(default)]
+    pub commit: Option<String>,
+    /// Current status
+    pub status: JobStatus,
+    /// When the job was created
+    pub created_at: DateTime<Utc>,
+    /// Labels of the job's trigger
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub labels: HashMap<String, String>,
+}
+
+/// Field holding the job ID in job stream entries
+pub const STREAM_FIELD_JOB_ID: &str = "job_id";
+
+/// Field holding the JSON-encoded [`JobTrigger`] in job stream entries
+pub const STREAM_FIELD_TRIGGER: &str = "trigger";
+
+/// Field holding the number of earlier attempts in job stream entries
+pub const STREAM_FIELD_RETRY_COUNT: &str = "retry_count";
+
+/// Field holding the ID of the failed job a retry was queued for
+pub const STREAM_FIELD_PARENT_JOB_ID: &str = "parent_job_id";
+
+/// Field holding the W3C `traceparent` of the request that queued the job
+pub const STREAM_FIELD_TRACEPARENT: &str = "traceparent";
+
+/// Field holding the `X-Request-Id` of the request that queued the job
+pub const STREAM_FIELD_REQUEST_ID: &str = "request_id";
+
+/// Field holding how often agents lacking the job's required capabilities
+/// handed it back to the stream
+pub const STREAM_FIELD_HAND_BACKS: &str = "hand_backs";
+
+/// A job as stored in the Redis job stream
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueuedJob {
+    /// Job identifier
+    pub id: String,
+    /// What to build
+    pub trigger: JobTrigger,
+    /// Number of automatic retries before this attempt (0 for the first run)
+    pub retry_count: u32,
+    /// Failed job this one retries
+    pub parent_job_id: Option<String>,
+    /// ID of the request that queued the first attempt, if any
+    pub request_id: Option<String>,
+}
+
+impl QueuedJob {
+    /// A first attempt at running `trigger`
+    pub fn new(id: impl Into<String>, trigger: JobTrigger) -> Self {
+        Self {
+            id: id.into(),
+            trigger,
+            retry_count: 0,
+            parent_job_id: None,
+            request_id: None,
+        }
+    }
+
+    /// The next attempt at this job, queued under `id`
+    pub fn retry(&self, id: impl Into<String>) -> Self {
+        Self {
+            id: id.into(),
+            trigger: self.trigger.clone(),
+            retry_count: self.retry_count + 1,
+            parent_job_id: Some(self.id.clone()),
+            request_id: self.request_id.clone(),
+        }
+    }
+
+    /// Encode as stream entry fields for `XADD`
+    pub fn to_stream_fields(&self) -> Result<Vec<(&'static str, String)>> {
+        let mut fields = vec![
+            (STREAM_FIELD_JOB_ID, self.id.clone()),
+            (
+                STREAM_FIELD_TRIGGER,
+                serde_json::to_string(&self.trigger).context("Failed to encode job trigger")?,
+            ),
+        ];
+        if self.retry_count > 0 {
+            fields.push((STREAM_FIELD_RETRY_COUNT, self.retry_count.to_string()));
+        }
+        if let Some(ref parent) = self.parent_job_id {
+            fields.push((STREAM_FIELD_PARENT_JOB_ID, parent.clone()));
+        }
+        if let Some(ref request_id) = self.request_id {
+            fields.push((STREAM_FIELD_REQUEST_ID, request_id.clone()));
+        }
+        Ok(fields)
+    }
+
+    /// Decode from stream entry fields
+    pub fn from_stream_fields(fields: &HashMap<String, String>) -> Result<Self> {
+        let id = fields
+            .get(STREAM_FIELD_JOB_ID)
+            .with_context(|| format!("Stream entry missing '{}' field", STREAM_FIELD_JOB_ID))?;
+        let trigger = fields
+            .get(STREAM_FIELD_TRIGGER)
+            .with_context(|| format!("Stream entry missing '{}' field", STREAM_FIELD_TRIGGER))?;
+
+        let retry_count = match fields.get(STREAM_FIELD_RETRY_COUNT) {
+            Some(count) => count
+                .parse()
+                .with_context(|| format!("Invalid retry count '{}' for job {}", count, id))?,
+            None => 0,
+        };
+
+        Ok(Self {
+            id: id.clone(),
+            trigger: serde_json::from_str(trigger)
+                .with_context(|| format!("Invalid job trigger for job {}", id))?,
+            retry_count,
+            parent_job_id: fields.get(STREAM_FIELD_PARENT_JOB_ID).cloned(),
+            request_id: fields.get(STREAM_FIELD_REQUEST_ID).cloned(),
+        })
+    }
+}
+
+/// Field holding when a dead-lettered job failed for the last time (RFC 3339)
+pub const STREAM_FIELD_FAILED_AT: &str = "failed_at";
+
+/// Field holding why a dead-lettered job failed
+pub const STREAM_FIELD_FAILURE_REASON: &str = "failure_reason";
+
+/// Stream of jobs that failed every retry, next to the priority streams of
+/// `base_stream`
+pub fn dead_letter_stream(base_stream: &str) -> String {
+    format!("{}:dead", base_stream)
+}
+
+/// A job that failed every retry, as kept in the dead letter stream
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DeadJob {
+    /// ID of the last failed attempt
+    pub id: String,
+    /// What was built
+    pub trigger: JobTrigger,
+    /// Number of retries before the last attempt
+    pub retry_count: u32,
+    /// Failed job the last attempt retried
+    #[serde(default)]
+    pub parent_job_id: Option<String>,
+    /// When the last attempt failed
+    pub failed_at: DateTime<Utc>,
+    /// Why the last attempt failed
+    pub failure_reason: String,
+}
+
+impl DeadJob {
+    /// Dead letter entry for the last attempt of a job
+    pub fn new(
+        job: &QueuedJob,
+        failure_reason: impl Into<String>,
+        failed_at: DateTime<Utc>,
+    ) -> Self {
+        Self {
+            id: job.id.clone(),
+            trigger: job.trigger.clone(),
+            retry_count: job.retry_count,
+            parent_job_id: job.parent_job_id.clone(),
+            failed_at,
+            failure_reason: failure_reason.into(),
+        }
+    }
+
+    /// The job to queue again: same ID and trigger, with `retry_count` reset
+    pub fn requeue(&self) -> QueuedJob {
+        QueuedJob::new(self.id.clone(), self.trigger.clone())
+    }
+
+    /// Encode as stream entry fields for `XADD`
+    pub fn to_stream_fields(&self) -> Result<Vec<(&'static str, String)>> {
+        let job = QueuedJob {
+            id: self.id.clone(),
+            trigger: self.trigger.clone(),
+            retry_count: self.retry_count,
+            parent_job_id: self.parent_job_id.clone(),
+            request_id: None,
+        };
+
+        let mut fields = job.to_stream_fields()?;
+        fields.push((STREAM_FIELD_FAILED_AT, self.failed_at.to_rfc3339()));
+        fields.push((STREAM_FIELD_FAILURE_REASON, self.failure_reason.clone()));
+        Ok(fields)
+    }
+
+    /// Decode from stream entry fields
+    pub fn from_stream_fields(fields: &HashMap<String, String>) -> Result<Self> {
+        let job = QueuedJob::from_stream_fields(fields)?;
+        let failed_at = fields
+            .get(STREAM_FIELD_FAILED_AT)
+            .with_context(|| format!("Stream entry missing '{}' field", STREAM_FIELD_FAILED_AT))?;
+
+        Ok(Self {
+            failed_at: failed_at
+                .parse()
+                .with_context(|| format!("Invalid failure time for job {}", job.id))?,
+            failure_reason: fields
+                .get(STREAM_FIELD_FAILURE_REASON)
+                .cloned()
+                .unwrap_or_default(),
+            id: job.id,
+            trigger: job.trigger,
+            retry_count: job.retry_count,
+            parent_job_id: job.parent_job_id,
+        })
+    }
+}
+
+/// Prefix of the Redis streams holding build output
+pub const LOG_STREAM_PREFIX: &str = "raibid:logs:";
+
+/// Redis stream holding a job's build output
+pub fn log_stream_key(job_id: &str) -> String {
+    format!("{}{}", LOG_STREAM_PREFIX, job_id)
+}
+
+/// ID of the job whose build output a Redis stream holds
+pub fn log_stream_job_id(key: &str) -> Option<&str> {
+    key.strip_prefix(LOG_STREAM_PREFIX)
+        .filter(|job_id| !job_id.is_empty())
+}
+
+/// Redis hash holding a job's metadata, including its `status` field
+pub fn job_key(job_id: &str) -> String {
+    format!("raibid:job:{}", job_id)
+}
+
+/// Redis key holding a job's [`TestReport`](crate::test_report::TestReport) as JSON
+pub fn test_results_key(job_id: &str) -> String {
+    format!("raibid:test-results:{}", job_id)
+}
+
+/// Redis key holding a job's [`BenchResult`](crate::benchmark::BenchResult)s as JSON
+pub fn benchmarks_key(job_id: &str) -> String {
+    format!("raibid:benchmarks:{}", job_id)
+}
+
+/// Redis key holding the most recent benchmarks of a repository branch as JSON
+pub fn latest_benchmarks_key(repo: &str, branch: &str) -> String {
+    format!("raibid:benchmarks:latest:{}:{}", repo, branch)
+}
+
+/// Redis key holding a job's [`CoverageReport`](crate::coverage::CoverageReport) as JSON
+pub fn coverage_key(job_id: &str) -> String {
+    format!("raibid:coverage:{}", job_id)
+}
+
+/// Redis key holding a job's
+/// [`OutdatedReport`](crate::dependencies::OutdatedReport) as JSON
+pub fn dependencies_key(job_id: &str) -> String {
+    format!("raibid:dependencies:{}", job_id)
+}
+
+/// Redis key holding a job's
+/// [`UnusedDepsReport`](crate::dependencies::UnusedDepsReport) as JSON
+pub fn unused_deps_key(job_id: &str) -> String {
+    format!("raibid:unused-deps:{}", job_id)
+}
+
+/// Redis key holding a job's [`ScanReport`](crate::scan::ScanReport) as JSON
+pub fn scan_results_key(job_id: &str) -> String {
+    format!("raibid:scan:{}", job_id)
+}
+
+/// Redis hash holding the result of each matrix combination of a job as
+/// JSON, keyed by combination name
+pub fn matrix_results_key(job_id: &str) -> String {
+    format!("raibid:matrix:{}", job_id)
+}
+
+/// Redis hash holding the result of each cross-compilation target of a job
+/// as JSON, keyed by target triple
+pub fn cross_results_key(job_id: &str) -> String {
+    format!("raibid:cross:{}", job_id)
+}
+
+/// Redis hash holding the URL of the full output of each step of a job whose
+/// output was uploaded to external storage, keyed by step name
+pub fn step_logs_key(job_id: &str) -> String {
+    format!("raibid:step-logs:{}", job_id)
+}
+
+/// Redis key holding the gzipped full output of a job's step, when kept in
+/// Redis
+pub fn full_log_key(job_id: &str, step: &str) -> String {
+    format!("raibid:full-log:{}:{}", job_id, step)
+}
+
+/// Redis key holding a job's
+/// [`ArtifactMetadata`](crate::artifacts::ArtifactMetadata) as JSON
+pub fn artifacts_key(job_id: &str) -> String {
+    format!("raibid:artifacts:{}", job_id)
+}
+
+/// Field of the job hash holding the [`JobStatus`]
+pub const JOB_FIELD_STATUS: &str = "status";
+
+/// Redis Pub/Sub channel the ID of each cancelled job is published on, so
+/// the agent running it can stop the build
+pub const JOB_CANCEL_CHANNEL: &str = "raibid:job-cancel";
+
+/// Field of the job hash holding when the job was queued, as RFC 3339
+pub const JOB_FIELD_CREATED_AT: &str = "created_at";
+
+/// Field of the job hash holding the `X-Request-Id` of the request that
+/// queued the job
+pub const JOB_FIELD_REQUEST_ID: &str = "request_id";
+
+/// Field of the job hash holding the trace ID of the request that queued the
+/// job
+pub const JOB_FIELD_TRACE_ID: &str = "trace_id";
+
+/// Prefix of the job hash fields holding the job's labels, one
+/// `label.<key>` field per label
+pub const JOB_FIELD_LABEL_PREFIX: &str = "label.";
+
+/// Job hash fields storing `labels`
+pub fn label_fields(labels: &HashMap<String, String>) -> Vec<(String, String)> {
+    let mut fields: Vec<(String, String)> = labels
+        .iter()
+        .map(|(key, value)| (format!("{}{}", JOB_FIELD_LABEL_PREFIX, key), value.clone()))
+        .collect();
+    fields.sort();
+    fields
+}
+
+/// Labels stored in the fields of a job hash
+pub fn labels_from_fields(fields: &HashMap<String, String>) -> HashMap<String, String> {
+    fields
+        .iter()
+        .filter_map(|(field, value)| {
+            let key = field.strip_prefix(JOB_FIELD_LABEL_PREFIX)?;
+            Some((key.to_string(), value.clone()))
+        })
+        .collect()
+}
+
+/// Parse a label selector of comma-separated `key=value` pairs, e.g.
+/// `source=gitlab,event=push`
+pub fn parse_label_selector(selector: &str) -> Result<HashMap<String, String>> {
+    let mut labels = HashMap::new();
+    for pair in selector.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+        let Some((key, value)) = pair.split_once('=') else {
+            bail!("Invalid label selector '{}': expected key=value", pair);
+        };
+        let (key, value) = (key.trim(), value.trim());
+        if !is_valid_label_key(key) {
+            bail!("Invalid label key '{}'", key);
+        }
+        labels.insert(key.to_string(), value.to_string());
+    }
+    Ok(labels)
+}
+
+/// Render `labels` as a selector for [`parse_label_selector`], sorted by key
+pub fn format_label_selector(labels: &HashMap<String, String>) -> String {
+    let mut pairs: Vec<String> = labels
+        .iter()
+        .map(|(key, value)| format!("{}={}", key, value))
+        .collect();
+    pairs.sort();
+    pairs.join(",")
+}
+
+/// Whether `labels` has every label of `selector`
+pub fn matches_labels(
+    labels: &HashMap<String, String>,
+    selector: &HashMap<String, String>,
+) -> bool {
+    selector
+        .iter()
+        .all(|(key, value)| labels.get(key) == Some(value))
+}
+
+/// Server-sent event carrying a [`JobLogEntry`] as JSON, sent by
+/// `GET /api/jobs/:id/logs?follow=true`
+pub const LOG_EVENT_ENTRY: &str = "log";
+
+/// Server-sent event ending a followed log, carrying the job's final
+/// [`JobStatus`]
+pub const LOG_EVENT_DONE: &str = "done";
+
+/// Log stream entry field naming the step that wrote the line, when known
+pub const LOG_FIELD_STEP: &str = "step";
+
+/// One line of build output
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct JobLogEntry {
+    /// When the line was written
+    pub timestamp: DateTime<Utc>,
+    /// Output line
+    pub message: String,
+}
+
+impl JobLogEntry {
+    /// Encode as log stream entry fields for `XADD`
+    pub fn to_stream_fields(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("timestamp", self.timestamp.to_rfc3339()),
+            ("message", self.message.clone()),
+        ]
+    }
+
+    /// Decode from log stream entry fields
+    pub fn from_stream_fields(fields: &HashMap<String, String>) -> Result<Self> {
+        let timestamp = fields
+            .get("timestamp")
+            .context("Log entry missing 'timestamp' field")?;
+        let message = fields
+            .get("message")
+            .context("Log entry missing 'message' field")?;
+
+        Ok(Self {
+            timestamp: DateTime::parse_from_rfc3339(timestamp)
+                .with_context(|| format!("Invalid log timestamp '{}'", timestamp))?
+                .with_timezone(&Utc),
+            message: message.clone(),
+        })
+    }
+}
+
+/// Job queue statistics reported by `GET /api/queue/stats`
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct QueueStats {
+    /// Number of entries in the job streams
+    pub total_messages: u64,
+    /// Messages delivered to a consumer but not yet acknowledged
+    pub pending_count: u64,
+    /// Consumers registered in the worker group
+    pub consumer_count: u64,
+    /// Entries not yet delivered to the group (Redis 7+ only)
+    pub lag: Option<u64>,
+    /// Idle time of the oldest pending message in milliseconds
+    pub oldest_pending_ms: Option<u64>,
+}
+
+impl QueueStats {
+    /// Jobs waiting for an agent or currently being processed
+    pub fn depth(&self) -> u64 {
+        self.lag.unwrap_or(0) + self.pending_count
+    }
+
+    /// Fold in the statistics of another stream read by the same group
+    pub fn merge(&mut self, other: &QueueStats) {
+        self.total_messages += other.total_messages;
+        self.pending_count += other.pending_count;
+        // The same agents consume every stream
+        self.consumer_count = self.consumer_count.max(other.consumer_count);
+        self.lag = match (self.lag, other.lag) {
+            (None, None) => None,
+            (a, b) => Some(a.unwrap_or(0) + b.unwrap_or(0)),
+        };
+        self.oldest_pending_ms = self.oldest_pending_ms.max(other.oldest_pending_ms);
+    }
+}
+
+/// Error-level log line reported by `GET /api/logs/errors`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ErrorLogEntry {
+    /// When the error was logged
+    pub timestamp: DateTime<Utc>,
+    /// Job that produced the error
+    pub job_id: String,
+    /// Pipeline step that was running
+    pub step: String,
+    /// Full error message (may span multiple lines)
+    pub message: String,
+}
+
+impl ErrorLogEntry {
+    /// First line of the error message
+    pub fn summary(&self) -> &str {
+        self.message.lines().next().unwrap_or("")
+    }
+}
+
+/// Builder for validated [`JobTrigger`]s
+#[derive(Debug, Clone, Default)]
+pub struct JobBuilder {
+    trigger: JobTrigger,
+}
+
+impl JobBuilder {
+    /// Start building a trigger for a repository
+    pub fn new(repo: impl Into<String>) -> Self {
+        Self {
+            trigger: JobTrigger {
+                repo: repo.into(),
+                ..Default::default()
+            },
+        }
+    }
+
+    /// Start from an existing trigger (e.g. one deserialized from JSON)
+    pub fn from_trigger(trigger: JobTrigger) -> Self {
+        Self { trigger }
+    }
+
+    /// Set the branch to build
+    pub fn branch(mut self, branch: impl Into<String>) -> Self {
+        self.trigger.branch = branch.into();
+        self
+    }
+
+    /// Pin the build to a specific commit
+    pub fn commit(mut self, commit: impl Into<String>) -> Self {
+        self.trigger.commit = Some(commit.into());
+        self
+    }
+
+    /// Add a build environment variable
+    pub fn env(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.trigger.env.insert(key.into(), value.into());
+        self
+    }
+
+    /// Set the queue priority
+    pub fn priority(mut self, priority: JobPriority) -> Self {
+        self.trigger.priority = priority;
+        self
+    }
+
+    /// Add a label
+    pub fn label(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.trigger.labels.insert(key.into(), value.into());
+        self
+    }
+
+    /// Only run the job on agents with `capability`
+    pub fn require_capability(mut self, capability: impl Into<String>) -> Self {
+        let capability = capability.into();
+        if !self.trigger.required_capabilities.contains(&capability) {
+            self.trigger.required_capabilities.push(capability);
+        }
+        self
+    }
+
+    /// Only queue the job once the job `job_id` has succeeded
+    pub fn depends_on(mut self, job_id: impl Into<String>) -> Self {
+        let job_id = job_id.into();
+        if !self.trigger.depends_on.contains(&job_id) {
+            self.trigger.depends_on.push(job_id);
+        }
+        self
+    }
+
+    /// Validate and return the trigger
+    pub fn build(self) -> Result<JobTrigger> {
+        let trigger = self.trigger;
+
+        let mut parts = trigger.repo.split('/');
+        let valid_repo = matches!(
+            (parts.next(), parts.next(), parts.next()),
+            (Some(owner), Some(name), None) if !owner.is_empty() && !name.is_empty()
+        );
+        if !valid_repo {
+            bail!(
+                "Invalid repository '{}': expected 'owner/name'",
+                trigger.repo
+            );
+        }
+
+        if trigger.branch.is_empty() || trigger.branch.chars().any(char::is_whitespace) {
+            bail!("Invalid branch name '{}'", trigger.branch);
+        }
+
+        if let Some(ref commit) = trigger.commit {
+            let valid_sha = (7..=40).contains(&commit.len())
+                && commit.chars().all(|c| c.is_ascii_hexdigit());
+            if !valid_sha {
+                bail!(
+                    "Invalid commit '{}': expected a 7-40 character hex SHA",
+                    commit
+                );
+            }
+        }
+
+        if let Some(key) = trigger.env.keys().find(|k| !is_valid_env_key(k)) {
+            bail!("Invalid environment variable name '{}'", key);
+        }
+
+        if let Some(key) = trigger.labels.keys().find(|k| !is_valid_label_key(k)) {
+            bail!("Invalid label key '{}'", key);
+        }
+        if let Some(value) = trigger
+            .labels
+            .values()
+            .find(|v| v.contains(',') || v.chars().any(char::is_whitespace))
+        {
+            bail!("Invalid label value '{}'", value);
+        }
+
+        if let Some(capability) = trigger
+            .required_capabilities
+            .iter()
+            .find(|c| c.is_empty() || c.chars().any(char::is_whitespace))
+        {
+            bail!("Invalid capability '{}'", capability);
+        }
+
+        if let Some(job_id) = trigger
+            .depends_on
+            .iter()
+            .find(|id| id.is_empty() || id.chars().any(char::is_whitespace))
+        {
+            bail!("Invalid dependency job ID '{}'", job_id);
+        }
+
+        Ok(trigger)
+    }
+}
+
+fn is_valid_env_key(key: &str) -> bool {
+    let mut chars = key.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Label keys are letters, digits, `-`, `_`, `.`, and `/`, so they survive
+/// selectors and hash field names
+fn is_valid_label_key(key: &str) -> bool {
+    !key.is_empty()
+        && key.len() <= 63
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | '/'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builder_valid() {
+        let trigger = JobBuilder::new("raibid-labs/raibid-cli")
+            .branch("develop")
+            .commit("a1b2c3d4")
+            .env("RUST_LOG", "debug")
+            .build()
+            .unwrap();
+
+        assert_eq!(trigger.repo, "raibid-labs/raibid-cli");
+        assert_eq!(trigger.branch, "develop");
+        assert_eq!(trigger.commit.as_deref(), Some("a1b2c3d4"));
+        assert_eq!(trigger.env.get("RUST_LOG").unwrap(), "debug");
+    }
+
+    #[test]
+    fn test_builder_defaults_to_main() {
+        let trigger = JobBuilder::new("raibid-labs/raibid-cli").build().unwrap();
+        assert_eq!(trigger.branch, "main");
+        assert!(trigger.commit.is_none());
+    }
+
+    #[test]
+    fn test_builder_rejects_invalid_fields() {
+        assert!(JobBuilder::new("raibid-cli").build().is_err());
+        assert!(JobBuilder::new("a/b/c").build().is_err());
+        assert!(JobBuilder::new("a/b").branch("has space").build().is_err());
+        assert!(JobBuilder::new("a/b").commit("xyz").build().is_err());
+        assert!(JobBuilder::new("a/b").env("1BAD", "x").build().is_err());
+        assert!(JobBuilder::new("a/b").label("a=b", "x").build().is_err());
+        assert!(JobBuilder::new("a/b")
+            .label("source", "a,b")
+            .build()
+            .is_err());
+    }
+
+    #[test]
+    fn test_parse_label_selector() {
+        let selector = parse_label_selector("source=gitlab, event=push").unwrap();
+        assert_eq!(selector.len(), 2);
+        assert_eq!(selector["source"], "gitlab");
+        assert_eq!(selector["event"], "push");
+        assert_eq!(format_label_selector(&selector), "event=push,source=gitlab");
+
+        assert!(parse_label_selector("").unwrap().is_empty());
+        assert!(parse_label_selector("source").is_err());
+        assert!(parse_label_selector("=gitlab").is_err());
+        assert!(parse_label_selector("so urce=gitlab").is_err());
+    }
+
+    #[test]
+    fn test_matches_labels() {
+        let trigger = JobBuilder::new("a/b")
+            .label("source", "gitlab")
+            .label("event", "push")
+            .build()
+            .unwrap();
+
+        assert!(matches_labels(&trigger.labels, &HashMap::new()));
+        assert!(matches_labels(
+            &trigger.labels,
+            &parse_label_selector("source=gitlab").unwrap()
+        ));
+        assert!(!matches_labels(
+            &trigger.labels,
+            &parse_label_selector("source=gitlab,event=tag").unwrap()
+        ));
+        assert!(!matches_labels(
+            &trigger.labels,
+            &parse_label_selector("team=infra").unwrap()
+        ));
+    }
+
+    #[test]
+    fn test_label_fields_round_trip() {
+        let labels = parse_label_selector("source=gitlab,event=push").unwrap();
+        let fields = label_fields(&labels);
+        assert_eq!(
+            fields,
+            vec![
+                ("label.event".to_string(), "push".to_string()),
+                ("label.source".to_string(), "gitlab".to_string()),
+            ]
+        );
+
+        // Other fields of the job hash are not labels
+        let mut hash: HashMap<String, String> = fields.into_iter().collect();
+        hash.insert(JOB_FIELD_STATUS.to_string(), "running".to_string());
+        assert_eq!(labels_from_fields(&hash), labels);
+    }
+
+    #[test]
+    fn test_missing_capabilities() {
+        let trigger = JobBuilder::new("a/b")
+            .require_capability("docker")
+            .require_capability("gpu")
+            .require_capability("gpu")
+            .build()
+            .unwrap();
+        assert_eq!(trigger.required_capabilities, vec!["docker", "gpu"]);
+
+        let rust = vec!["rust".to_string(), "docker".to_string()];
+        assert_eq!(trigger.missing_capabilities(&rust), vec!["gpu"]);
+        let gpu = vec!["docker".to_string(), "gpu".to_string()];
+        assert!(trigger.missing_capabilities(&gpu).is_empty());
+
+        assert!(JobBuilder::new("a/b")
+            .require_capability("has space")
+            .build()
+            .is_err());
+    }
+
+    #[test]
+    fn test_builder_depends_on() {
+        let trigger = JobBuilder::new("a/b")
+            .depends_on("job-1")
+            .depends_on("job-2")
+            .depends_on("job-1")
+            .build()
+            .unwrap();
+        assert_eq!(trigger.depends_on, vec!["job-1", "job-2"]);
+
+        let json = serde_json::to_string(&trigger).unwrap();
+        assert!(json.contains(r#""depends_on":["job-1","job-2"]"#));
+        let plain = serde_json::to_string(&JobBuilder::new("a/b").build().unwrap()).unwrap();
+        assert!(!plain.contains("depends_on"));
+
+        assert!(JobBuilder::new("a/b").depends_on("").build().is_err());
+    }
+
+    #[test]
+    fn test_trigger_from_json() {
+        let json = r#"{"repo": "raibid-labs/raibid-cli", "branch": "main", "env": {"CI": "1"}}"#;
+        let trigger: JobTrigger = serde_json::from_str(json).unwrap();
+        let trigger = JobBuilder::from_trigger(trigger).build().unwrap();
+        assert_eq!(trigger.env.get("CI").unwrap(), "1");
+    }
+
+    #[test]
+    fn test_trigger_json_rejects_unknown_fields() {
+        let json = r#"{"repo": "a/b", "branch": "main", "brnach": "dev"}"#;
+        assert!(serde_json::from_str::<JobTrigger>(json).is_err());
+    }
+
+    #[test]
+    fn test_trigger_priority() {
+        let trigger = JobBuilder::new("a/b").build().unwrap();
+        assert_eq!(trigger.priority, JobPriority::Normal);
+        // Normal priority is left out so older consumers can still decode it
+        assert!(!serde_json::to_string(&trigger)
+            .unwrap()
+            .contains("priority"));
+
+        let trigger = JobBuilder::new("a/b")
+            .priority(JobPriority::High)
+            .build()
+            .unwrap();
+        let json = serde_json::to_string(&trigger).unwrap();
+        assert!(json.contains(r#""priority":"high""#));
+        assert_eq!(serde_json::from_str::<JobTrigger>(&json).unwrap(), trigger);
+    }
+
+    #[test]
+    fn test_job_priority() {
+        assert_eq!("LOW".parse::<JobPriority>().unwrap(), JobPriority::Low);
+        assert!("urgent".parse::<JobPriority>().is_err());
+        assert_eq!(
+            JobPriority::ALL.map(|p| p.stream_key("raibid:jobs")),
+            ["raibid:jobs:high", "raibid:jobs:normal", "raibid:jobs:low"]
+        );
+    }
+
+    #[test]
+    fn test_queue_stats_merge() {
+        let mut stats = QueueStats {
+            total_messages: 4,
+            pending_count: 1,
+            consumer_count: 2,
+            lag: None,
+            oldest_pending_ms: Some(500),
+        };
+        stats.merge(&QueueStats {
+            total_messages: 6,
+            pending_count: 2,
+            consumer_count: 3,
+            lag: Some(4),
+            oldest_pending_ms: None,
+        });
+
+        assert_eq!(stats.total_messages, 10);
+        assert_eq!(stats.pending_count, 3);
+        assert_eq!(stats.consumer_count, 3);
+        assert_eq!(stats.lag, Some(4));
+        assert_eq!(stats.oldest_pending_ms, Some(500));
+    }
+
+    #[test]
+    fn test_job_status_terminal() {
+        assert!(JobStatus::Success.is_terminal());
+        assert!(JobStatus::Cancelled.is_terminal());
+        assert!(!JobStatus::Running.is_terminal());
+        assert!(!JobStatus::Retrying.is_terminal());
+        assert_eq!("failed".parse::<JobStatus>().unwrap(), JobStatus::Failed);
+    }
+
+    #[test]
+    fn test_error_log_entry_summary() {
+        let entry = ErrorLogEntry {
+            timestamp: Utc::now(),
+            job_id: "job-1".to_string(),
+            step: "clippy".to_string(),
+            message: "error[E0308]: mismatched types\n --> src/main.rs:4:5".to_string(),
+        };
+        assert_eq!(entry.summary(), "error[E0308]: mismatched types");
+    }
+
+    #[test]
+    fn test_queued_job_stream_fields_roundtrip() {
+        let job = QueuedJob::new(
+            "job-1",
+            JobBuilder::new("raibid-labs/raibid-cli").build().unwrap(),
+        );
+
+        let fields: HashMap<String, String> = job
+            .to_stream_fields()
+            .unwrap()
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v))
+            .collect();
+        assert_eq!(QueuedJob::from_stream_fields(&fields).unwrap(), job);
+
+        let mut missing = fields.clone();
+        missing.remove(STREAM_FIELD_TRIGGER);
+        assert!(QueuedJob::from_stream_fields(&missing).is_err());
+    }
+
+    #[test]
+    fn test_queued_job_request_id() {
+        let mut job = QueuedJob::new("job-1", JobBuilder::new("a/b").build().unwrap());
+        job.request_id = Some("req-1".to_string());
+
+        let fields: HashMap<String, String> = job
+            .to_stream_fields()
+            .unwrap()
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v))
+            .collect();
+        assert_eq!(fields[STREAM_FIELD_REQUEST_ID], "req-1");
+        assert_eq!(QueuedJob::from_stream_fields(&fields).unwrap(), job);
+
+        // Retries stay linked to the request that queued the first attempt
+        assert_eq!(job.retry("job-2").request_id.as_deref(), Some("req-1"));
+    }
+
+    #[test]
+    fn test_queued_job_retry() {
+        let job = QueuedJob::new("job-1", JobBuilder::new("a/b").build().unwrap());
+        let retry = job.retry("job-2").retry("job-3");
+        assert_eq!(retry.retry_count, 2);
+        assert_eq!(retry.parent_job_id.as_deref(), Some("job-2"));
+        assert_eq!(retry.trigger, job.trigger);
+
+        let fields: HashMap<String, String> = retry
+            .to_stream_fields()
+            .unwrap()
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v))
+            .collect();
+        assert_eq!(fields[STREAM_FIELD_RETRY_COUNT], "2");
+        assert_eq!(QueuedJob::from_stream_fields(&fields).unwrap(), retry);
+    }
+
+    #[test]
+    fn test_job_log_entry_stream_fields_roundtrip() {
+        let entry = JobLogEntry {
+            timestamp: "2024-01-01T12:00:00Z".parse().unwrap(),
+            message: "Compiling raibid-cli v0.1.0".to_string(),
+        };
+
+        let fields: HashMap<String, String> = entry
+            .to_stream_fields()
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v))
+            .collect();
+        assert_eq!(JobLogEntry::from_stream_fields(&fields).unwrap(), entry);
+        assert_eq!(log_stream_key("job-1"), "raibid:logs:job-1");
+        assert_eq!(log_stream_job_id("raibid:logs:job-1"), Some("job-1"));
+        assert_eq!(log_stream_job_id("raibid:logs:"), None);
+        assert_eq!(log_stream_job_id("raibid:job:job-1"), None);
+    }
+
+    #[test]
+    fn test_dead_job_stream_fields_roundtrip() {
+        let job = QueuedJob::new("job-1", JobBuilder::new("a/b").build().unwrap())
+            .retry("job-2")
+            .retry("job-3");
+        let dead = DeadJob::new(
+            &job,
+            "Build failed after 2 retries",
+            "2024-01-01T12:00:00Z".parse().unwrap(),
+        );
+        assert_eq!(dead.retry_count, 2);
+
+        let fields: HashMap<String, String> = dead
+            .to_stream_fields()
+            .unwrap()
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v))
+            .collect();
+        assert_eq!(DeadJob::from_stream_fields(&fields).unwrap(), dead);
+
+        let requeued = dead.requeue();
+        assert_eq!(requeued.id, "job-3");
+        assert_eq!(requeued.retry_count, 0);
+        assert_eq!(requeued.parent_job_id, None);
+        assert_eq!(dead_letter_stream("raibid:jobs"), "raibid:jobs:dead");
+    }
+}
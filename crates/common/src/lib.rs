@@ -3,13 +3,30 @@
 //! Common types, utilities, and infrastructure components shared across the raibid-ci workspace.
 //! This crate provides:
 //! - Configuration management
+//! - Job, agent and schedule types, the API client, and API key helpers
+//! - Job dependency chains held in Redis
+//! - A Gitea API client for repository mirrors
 //! - Infrastructure deployment and management (k3s, Gitea, Flux, Redis, KEDA)
 //! - Shared error types
 //! - Utility functions
 
+pub mod agents;
+pub mod api;
+pub mod artifacts;
+pub mod auth;
+pub mod benchmark;
 pub mod config;
+pub mod coverage;
+pub mod dependencies;
+pub mod gitea;
 pub mod infrastructure;
+pub mod job_deps;
+pub mod jobs;
+pub mod scan;
+pub mod schedules;
+pub mod test_report;
 
 // Re-export commonly used types
+pub use api::{ApiClient, AsyncApiClient};
 pub use config::Config;
 pub use infrastructure::error::InfraError;
@@ -0,0 +1,137 @@
+//! Container image scan types
+//!
+//! Agents parse the trivy JSON output of a `security-scan` step into a
+//! [`ScanReport`] and store it in Redis under
+//! [`scan_results_key`](crate::jobs::scan_results_key).
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+/// Severity of a vulnerability, lowest first
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Unknown,
+    Low,
+    Medium,
+    High,
+    Critical,
+}
+
+impl Severity {
+    /// Every severity, lowest first
+    pub const ALL: [Severity; 5] = [
+        Severity::Unknown,
+        Severity::Low,
+        Severity::Medium,
+        Severity::High,
+        Severity::Critical,
+    ];
+
+    /// Name used by trivy, e.g. `HIGH`
+    pub fn as_str(&self) -> &str {
+        match self {
+            Severity::Unknown => "UNKNOWN",
+            Severity::Low => "LOW",
+            Severity::Medium => "MEDIUM",
+            Severity::High => "HIGH",
+            Severity::Critical => "CRITICAL",
+        }
+    }
+
+    /// This severity and every higher one
+    pub fn and_above(&self) -> Vec<Severity> {
+        Self::ALL.into_iter().filter(|s| s >= self).collect()
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl FromStr for Severity {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|severity| severity.as_str().eq_ignore_ascii_case(s))
+            .ok_or_else(|| anyhow::anyhow!("Unknown severity: {}", s))
+    }
+}
+
+/// Vulnerabilities found in a container image
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ScanReport {
+    /// Image that was scanned
+    pub image: String,
+    /// Number of vulnerabilities of each severity found
+    pub vulnerabilities: BTreeMap<Severity, u64>,
+}
+
+impl ScanReport {
+    /// Number of vulnerabilities of `severity`
+    pub fn count(&self, severity: Severity) -> u64 {
+        self.vulnerabilities.get(&severity).copied().unwrap_or(0)
+    }
+
+    /// Number of vulnerabilities of any severity
+    pub fn total(&self) -> u64 {
+        self.vulnerabilities.values().sum()
+    }
+}
+
+impl fmt::Display for ScanReport {
+    /// Counts from most to least severe, e.g. `1 CRITICAL, 3 HIGH`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.total() == 0 {
+            return write!(f, "no vulnerabilities");
+        }
+        let counts: Vec<String> = self
+            .vulnerabilities
+            .iter()
+            .rev()
+            .filter(|(_, count)| **count > 0)
+            .map(|(severity, count)| format!("{} {}", count, severity))
+            .collect();
+        write!(f, "{}", counts.join(", "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_severity_order_and_names() {
+        assert_eq!(
+            Severity::High.and_above(),
+            vec![Severity::High, Severity::Critical]
+        );
+        assert_eq!("critical".parse::<Severity>().unwrap(), Severity::Critical);
+        assert_eq!("MEDIUM".parse::<Severity>().unwrap(), Severity::Medium);
+        assert!("severe".parse::<Severity>().is_err());
+    }
+
+    #[test]
+    fn test_report_counts_and_json() {
+        let report = ScanReport {
+            image: "registry.local/app:1.0".to_string(),
+            vulnerabilities: BTreeMap::from([(Severity::High, 3), (Severity::Critical, 1)]),
+        };
+        assert_eq!(report.count(Severity::High), 3);
+        assert_eq!(report.count(Severity::Low), 0);
+        assert_eq!(report.total(), 4);
+        assert_eq!(report.to_string(), "1 CRITICAL, 3 HIGH");
+        assert_eq!(ScanReport::default().to_string(), "no vulnerabilities");
+
+        let json = serde_json::to_string(&report).unwrap();
+        assert!(json.contains(r#""high":3"#));
+        assert_eq!(serde_json::from_str::<ScanReport>(&json).unwrap(), report);
+    }
+}
@@ -0,0 +1,86 @@
+//! Scheduled builds
+//!
+//! A [`Schedule`] queues a build of a repository branch whenever its cron
+//! expression matches. The server stores schedules as JSON in the
+//! [`SCHEDULES_KEY`] hash, keyed by schedule ID, and checks them once a
+//! minute.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Redis hash holding every [`Schedule`] as JSON, keyed by schedule ID
+pub const SCHEDULES_KEY: &str = "raibid:schedules";
+
+/// Time zone used when a schedule does not name one
+pub const DEFAULT_TIMEZONE: &str = "UTC";
+
+fn default_timezone() -> String {
+    DEFAULT_TIMEZONE.to_string()
+}
+
+/// Body of `POST /api/schedules`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ScheduleRequest {
+    /// Repository in `owner/name` form
+    pub repo: String,
+    /// Branch to build
+    pub branch: String,
+    /// Cron expression, with five (`0 2 * * *`) or six fields (seconds first)
+    pub cron: String,
+    /// IANA time zone the expression is evaluated in, e.g. `Europe/Berlin`
+    #[serde(default = "default_timezone")]
+    pub timezone: String,
+}
+
+/// A stored build schedule
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Schedule {
+    /// Schedule ID
+    pub id: String,
+    /// Repository in `owner/name` form
+    pub repo: String,
+    /// Branch to build
+    pub branch: String,
+    /// Cron expression
+    pub cron: String,
+    /// IANA time zone the expression is evaluated in
+    pub timezone: String,
+    /// When the schedule was created
+    pub created_at: DateTime<Utc>,
+    /// When the schedule last queued a build
+    #[serde(default)]
+    pub last_run: Option<DateTime<Utc>>,
+}
+
+impl Schedule {
+    /// New schedule for `request`, created at `now`
+    pub fn new(id: impl Into<String>, request: ScheduleRequest, now: DateTime<Utc>) -> Self {
+        Self {
+            id: id.into(),
+            repo: request.repo,
+            branch: request.branch,
+            cron: request.cron,
+            timezone: request.timezone,
+            created_at: now,
+            last_run: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_timezone_defaults_to_utc() {
+        let request: ScheduleRequest = serde_json::from_str(
+            r#"{"repo": "raibid-labs/raibid-cli", "branch": "main", "cron": "0 2 * * *"}"#,
+        )
+        .unwrap();
+        assert_eq!(request.timezone, DEFAULT_TIMEZONE);
+
+        let schedule = Schedule::new("sched-1", request, Utc::now());
+        assert_eq!(schedule.branch, "main");
+        assert_eq!(schedule.last_run, None);
+    }
+}
@@ -0,0 +1,138 @@
+//! Test result types
+//!
+//! Agents parse the JUnit XML written by test runners into a [`TestReport`]
+//! and store it in Redis under [`test_results_key`](crate::jobs::test_results_key);
+//! the server serves it from `GET /api/jobs/:id/test-results`.
+
+use serde::{Deserialize, Serialize};
+
+/// Outcome of a single test case
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TestCaseStatus {
+    Passed,
+    /// Assertion failure
+    Failed,
+    /// Unexpected error (panic outside an assertion, setup failure, ...)
+    Error,
+    Skipped,
+}
+
+/// A single test case
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TestCaseResult {
+    /// Test name
+    pub name: String,
+    /// Test class or module, if reported
+    #[serde(default)]
+    pub classname: Option<String>,
+    /// Run time in seconds, if reported
+    #[serde(default)]
+    pub time_secs: Option<f64>,
+    pub status: TestCaseStatus,
+    /// Failure or error message
+    #[serde(default)]
+    pub message: Option<String>,
+}
+
+/// A test suite and its test cases
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TestSuiteResult {
+    /// Suite name
+    pub name: String,
+    /// Number of tests, as reported by the suite
+    pub tests: u32,
+    /// Number of failed tests
+    pub failures: u32,
+    /// Number of tests that errored
+    pub errors: u32,
+    /// Number of skipped tests
+    #[serde(default)]
+    pub skipped: u32,
+    /// Run time in seconds
+    #[serde(default)]
+    pub time_secs: f64,
+    /// Individual test cases
+    #[serde(default)]
+    pub cases: Vec<TestCaseResult>,
+}
+
+/// Test results of a job, merged from every report file it produced
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct TestReport {
+    pub suites: Vec<TestSuiteResult>,
+}
+
+impl TestReport {
+    /// Total number of tests
+    pub fn tests(&self) -> u32 {
+        self.suites.iter().map(|s| s.tests).sum()
+    }
+
+    /// Total number of failed tests
+    pub fn failures(&self) -> u32 {
+        self.suites.iter().map(|s| s.failures).sum()
+    }
+
+    /// Total number of tests that errored
+    pub fn errors(&self) -> u32 {
+        self.suites.iter().map(|s| s.errors).sum()
+    }
+
+    /// Total number of skipped tests
+    pub fn skipped(&self) -> u32 {
+        self.suites.iter().map(|s| s.skipped).sum()
+    }
+
+    /// Total number of passed tests
+    pub fn passed(&self) -> u32 {
+        self.tests()
+            .saturating_sub(self.failures() + self.errors() + self.skipped())
+    }
+
+    /// Whether no test failed or errored
+    pub fn success(&self) -> bool {
+        self.failures() == 0 && self.errors() == 0
+    }
+
+    /// Add the suites of another report
+    pub fn merge(&mut self, other: TestReport) {
+        self.suites.extend(other.suites);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn suite(tests: u32, failures: u32, errors: u32, skipped: u32) -> TestSuiteResult {
+        TestSuiteResult {
+            name: "suite".to_string(),
+            tests,
+            failures,
+            errors,
+            skipped,
+            time_secs: 0.5,
+            cases: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_report_totals() {
+        let mut report = TestReport {
+            suites: vec![suite(10, 1, 0, 2)],
+        };
+        assert!(!report.success());
+
+        report.merge(TestReport {
+            suites: vec![suite(5, 0, 1, 0)],
+        });
+        assert_eq!(report.tests(), 15);
+        assert_eq!(report.failures(), 1);
+        assert_eq!(report.errors(), 1);
+        assert_eq!(report.skipped(), 2);
+        assert_eq!(report.passed(), 11);
+
+        assert!(TestReport::default().success());
+    }
+}
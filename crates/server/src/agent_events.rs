@@ -0,0 +1,205 @@
+//! Live agent status events
+//!
+//! Agents publish an [`AgentEvent`] on the Redis Pub/Sub channel
+//! [`AGENT_EVENTS_CHANNEL`] when they become busy or idle, and the server
+//! publishes one when it marks an agent dead. Like the
+//! [`LogMultiplexer`](crate::log_stream::LogMultiplexer), [`AgentEventHub`]
+//! holds a single subscription to the channel and broadcasts its events to
+//! every connected client, so dashboards see status changes without polling
+//! `GET /api/agents`.
+
+use anyhow::{bail, Context, Result};
+use futures::StreamExt;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+
+use raibid_common::agents::{AgentEvent, AGENT_EVENTS_CHANNEL};
+
+/// Capacity of the broadcast channel
+const CHANNEL_CAPACITY: usize = 256;
+
+/// Delay before resubscribing after the subscription fails
+const RETRY_DELAY: Duration = Duration::from_secs(2);
+
+/// Fans agent events out to any number of subscribers
+#[derive(Debug)]
+pub struct AgentEventHub {
+    sender: broadcast::Sender<AgentEvent>,
+}
+
+impl Default for AgentEventHub {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AgentEventHub {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(CHANNEL_CAPACITY);
+        Self { sender }
+    }
+
+    /// Receive every event broadcast from now on
+    pub fn subscribe(&self) -> broadcast::Receiver<AgentEvent> {
+        self.sender.subscribe()
+    }
+
+    /// Send an event to the current subscribers
+    pub fn broadcast(&self, event: AgentEvent) {
+        // No subscribers is fine; the event is only of interest to live clients
+        let _ = self.sender.send(event);
+    }
+
+    /// Broadcast the event in a message received on [`AGENT_EVENTS_CHANNEL`]
+    pub fn handle_message(&self, payload: &str) {
+        match serde_json::from_str::<AgentEvent>(payload) {
+            Ok(event) => {
+                debug!("Agent {} is {}", event.agent_id, event.status);
+                self.broadcast(event);
+            }
+            Err(e) => warn!("Skipping malformed agent event {:?}: {}", payload, e),
+        }
+    }
+
+    /// Spawn the task subscribing to [`AGENT_EVENTS_CHANNEL`] on the Redis
+    /// at `redis_url`, resubscribing whenever the connection drops
+    pub fn spawn_subscriber(self: &Arc<Self>, redis_url: &str) -> JoinHandle<()> {
+        let hub = Arc::clone(self);
+        let redis_url = redis_url.to_string();
+        tokio::spawn(async move {
+            loop {
+                if let Err(e) = hub.listen(&redis_url).await {
+                    warn!("Agent event subscription failed: {:#}", e);
+                }
+                tokio::time::sleep(RETRY_DELAY).await;
+            }
+        })
+    }
+
+    /// Subscribe and broadcast messages until the connection drops
+    async fn listen(&self, redis_url: &str) -> Result<()> {
+        let client = redis::Client::open(redis_url).context("Invalid Redis URL")?;
+        let mut pubsub = client
+            .get_async_connection()
+            .await
+            .context("Failed to connect to Redis")?
+            .into_pubsub();
+        pubsub
+            .subscribe(AGENT_EVENTS_CHANNEL)
+            .await
+            .with_context(|| format!("Failed to subscribe to '{}'", AGENT_EVENTS_CHANNEL))?;
+        info!("Subscribed to agent events on '{}'", AGENT_EVENTS_CHANNEL);
+
+        let mut messages = pubsub.on_message();
+        while let Some(message) = messages.next().await {
+            match message.get_payload::<String>() {
+                Ok(payload) => self.handle_message(&payload),
+                Err(e) => warn!("Skipping unreadable agent event: {}", e),
+            }
+        }
+        bail!("Redis closed the connection")
+    }
+}
+
+/// Publish `event` on [`AGENT_EVENTS_CHANNEL`]
+pub async fn publish_agent_event<C>(conn: &mut C, event: &AgentEvent) -> redis::RedisResult<()>
+where
+    C: redis::aio::ConnectionLike + Send,
+{
+    let payload = serde_json::to_string(event).map_err(|e| {
+        redis::RedisError::from((
+            redis::ErrorKind::TypeError,
+            "Failed to serialize agent event",
+            e.to_string(),
+        ))
+    })?;
+    redis::cmd("PUBLISH")
+        .arg(AGENT_EVENTS_CHANNEL)
+        .arg(payload)
+        .query_async::<_, i64>(conn)
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use raibid_common::agents::AgentStatus;
+    use redis::Value;
+
+    /// Connection delivering `PUBLISH`ed messages to a hub, like Redis
+    /// delivers them to the subscriber task
+    struct PubSubConnection {
+        hub: Arc<AgentEventHub>,
+    }
+
+    impl redis::aio::ConnectionLike for PubSubConnection {
+        fn req_packed_command<'a>(
+            &'a mut self,
+            cmd: &'a redis::Cmd,
+        ) -> redis::RedisFuture<'a, Value> {
+            let args: Vec<String> = cmd
+                .args_iter()
+                .map(|arg| match arg {
+                    redis::Arg::Simple(bytes) => String::from_utf8_lossy(bytes).to_string(),
+                    redis::Arg::Cursor => "0".to_string(),
+                })
+                .collect();
+            assert_eq!(args[..2], ["PUBLISH", AGENT_EVENTS_CHANNEL]);
+            self.hub.handle_message(&args[2]);
+            Box::pin(async { Ok(Value::Int(1)) })
+        }
+
+        fn req_packed_commands<'a>(
+            &'a mut self,
+            _pipeline: &'a redis::Pipeline,
+            _offset: usize,
+            _count: usize,
+        ) -> redis::RedisFuture<'a, Vec<Value>> {
+            Box::pin(async { Ok(Vec::new()) })
+        }
+
+        fn get_db(&self) -> i64 {
+            0
+        }
+    }
+
+    #[tokio::test]
+    async fn test_published_event_reaches_subscribers() {
+        let hub = Arc::new(AgentEventHub::new());
+        let mut first = hub.subscribe();
+        let mut second = hub.subscribe();
+
+        let event = AgentEvent::now("agent-1", AgentStatus::Busy);
+        let mut conn = PubSubConnection { hub: hub.clone() };
+        publish_agent_event(&mut conn, &event).await.unwrap();
+
+        for subscriber in [&mut first, &mut second] {
+            let received = tokio::time::timeout(Duration::from_millis(500), subscriber.recv())
+                .await
+                .expect("event not received within 500ms")
+                .unwrap();
+            assert_eq!(received, event);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_malformed_message_skipped() {
+        let hub = AgentEventHub::new();
+        let mut events = hub.subscribe();
+
+        hub.handle_message("not json");
+        hub.handle_message(
+            r#"{"agent_id":"agent-1","status":"asleep","timestamp":"2024-01-01T00:00:00Z"}"#,
+        );
+        assert!(events.try_recv().is_err());
+
+        hub.handle_message(
+            r#"{"agent_id":"agent-1","status":"idle","timestamp":"2024-01-01T00:00:00Z"}"#,
+        );
+        assert_eq!(events.try_recv().unwrap().status, AgentStatus::Idle);
+    }
+}
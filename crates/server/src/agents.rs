@@ -0,0 +1,360 @@
+//! Agent registry
+//!
+//! Agents register with `POST /api/agents/register` on startup and then send a
+//! heartbeat every [`HEARTBEAT_INTERVAL`]. A background task sweeps the
+//! registry and moves agents that stop reporting through
+//! [`AgentHealth::Unhealthy`] to [`AgentHealth::Dead`]; a heartbeat makes an
+//! agent healthy again. Agents found dead are announced as offline on the agent
+//! events channel (see [`crate::agent_events`]).
+//!
+//! Heartbeats carry the number of jobs the agent is running. An agent marked
+//! draining with [`AgentRegistry::drain`] is told so in the response to its
+//! next heartbeat, and stops claiming jobs.
+
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+use std::time::Duration;
+use tracing::{info, warn};
+
+use raibid_common::agents::{AgentInfo, AgentRegistration, AgentStatus};
+
+/// How often agents are expected to send a heartbeat
+pub const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Time without a heartbeat after which an agent is unhealthy
+pub const UNHEALTHY_AFTER: Duration = Duration::from_secs(30);
+
+/// Time without a heartbeat after which an agent is considered dead
+pub const DEAD_AFTER: Duration = Duration::from_secs(90);
+
+/// Interval between registry sweeps
+pub const HEALTH_SWEEP_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Liveness of a registered agent, derived from its last heartbeat
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AgentHealth {
+    /// Heartbeat received within [`UNHEALTHY_AFTER`]
+    Healthy,
+    /// No heartbeat for [`UNHEALTHY_AFTER`]
+    Unhealthy,
+    /// No heartbeat for [`DEAD_AFTER`]
+    Dead,
+}
+
+impl AgentHealth {
+    /// Health of an agent whose last heartbeat was `silence` ago
+    pub fn after(silence: Duration) -> Self {
+        if silence >= DEAD_AFTER {
+            AgentHealth::Dead
+        } else if silence >= UNHEALTHY_AFTER {
+            AgentHealth::Unhealthy
+        } else {
+            AgentHealth::Healthy
+        }
+    }
+}
+
+/// A registered agent
+#[derive(Debug, Clone, PartialEq)]
+pub struct RegisteredAgent {
+    pub agent_id: String,
+    pub version: String,
+    pub capabilities: Vec<String>,
+    pub registered_at: DateTime<Utc>,
+    pub last_seen: DateTime<Utc>,
+    pub health: AgentHealth,
+    /// Jobs running as of the last heartbeat
+    pub active_jobs: u32,
+    pub draining: bool,
+    /// Kubernetes pod running the agent, if it reported one
+    pub pod_name: Option<String>,
+    pub drain_timeout_secs: u64,
+}
+
+impl RegisteredAgent {
+    /// Summary reported by `GET /api/agents`
+    ///
+    /// Agents that miss heartbeats are reported offline.
+    pub fn info(&self, now: DateTime<Utc>) -> AgentInfo {
+        let status = match self.health {
+            AgentHealth::Healthy if self.active_jobs > 0 => AgentStatus::Busy,
+            AgentHealth::Healthy => AgentStatus::Idle,
+            AgentHealth::Unhealthy | AgentHealth::Dead => AgentStatus::Offline,
+        };
+
+        AgentInfo {
+            id: self.agent_id.clone(),
+            status,
+            current_job: None,
+            cpu_percent: 0.0,
+            memory_percent: 0.0,
+            uptime_secs: (now - self.registered_at).num_seconds().max(0) as u64,
+            active_jobs: self.active_jobs,
+            draining: self.draining,
+            drain_timeout_secs: self.drain_timeout_secs,
+        }
+    }
+}
+
+/// Agents known to the server, keyed by agent ID
+#[derive(Debug, Default)]
+pub struct AgentRegistry {
+    agents: DashMap<String, RegisteredAgent>,
+}
+
+impl AgentRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add an agent, replacing any earlier registration with the same ID
+    pub fn register(&self, registration: AgentRegistration, now: DateTime<Utc>) -> RegisteredAgent {
+        let agent = RegisteredAgent {
+            agent_id: registration.agent_id,
+            version: registration.version,
+            capabilities: registration.capabilities,
+            registered_at: now,
+            last_seen: now,
+            health: AgentHealth::Healthy,
+            active_jobs: 0,
+            draining: false,
+            pod_name: registration.pod_name,
+            drain_timeout_secs: registration.drain_timeout_secs,
+        };
+
+        if self
+            .agents
+            .insert(agent.agent_id.clone(), agent.clone())
+            .is_some()
+        {
+            info!("Agent {} re-registered", agent.agent_id);
+        } else {
+            info!(
+                "Agent {} registered (version {}, capabilities: {})",
+                agent.agent_id,
+                agent.version,
+                agent.capabilities.join(", ")
+            );
+        }
+        agent
+    }
+
+    /// Record a heartbeat, returning `false` if the agent is not registered
+    pub fn heartbeat(&self, agent_id: &str, now: DateTime<Utc>) -> bool {
+        let Some(mut agent) = self.agents.get_mut(agent_id) else {
+            return false;
+        };
+
+        if agent.health != AgentHealth::Healthy {
+            info!("Agent {} is healthy again", agent_id);
+        }
+        agent.last_seen = now;
+        agent.health = AgentHealth::Healthy;
+        true
+    }
+
+    /// Record the number of jobs an agent reported in its heartbeat
+    pub fn set_active_jobs(&self, agent_id: &str, active_jobs: u32) {
+        if let Some(mut agent) = self.agents.get_mut(agent_id) {
+            agent.active_jobs = active_jobs;
+        }
+    }
+
+    /// Mark an agent as draining, returning it or `None` if it is not
+    /// registered
+    pub fn drain(&self, agent_id: &str) -> Option<RegisteredAgent> {
+        let mut agent = self.agents.get_mut(agent_id)?;
+        if !agent.draining {
+            info!("Draining agent {}", agent_id);
+            agent.draining = true;
+        }
+        Some(agent.clone())
+    }
+
+    /// Forget an agent, returning `false` if it was not registered
+    pub fn remove(&self, agent_id: &str) -> bool {
+        self.agents.remove(agent_id).is_some()
+    }
+
+    /// Get a registered agent
+    pub fn get(&self, agent_id: &str) -> Option<RegisteredAgent> {
+        self.agents.get(agent_id).map(|agent| agent.clone())
+    }
+
+    /// All registered agents, ordered by ID
+    pub fn list(&self) -> Vec<RegisteredAgent> {
+        let mut agents: Vec<_> = self.agents.iter().map(|agent| agent.clone()).collect();
+        agents.sort_by(|a, b| a.agent_id.cmp(&b.agent_id));
+        agents
+    }
+
+    /// Update every agent's health from the time since its last heartbeat,
+    /// returning the IDs of agents that died since the previous sweep
+    pub fn sweep(&self, now: DateTime<Utc>) -> Vec<String> {
+        let mut died = Vec::new();
+        for mut agent in self.agents.iter_mut() {
+            let silence = (now - agent.last_seen).to_std().unwrap_or_default();
+            let health = AgentHealth::after(silence);
+            if health == agent.health {
+                continue;
+            }
+
+            match health {
+                AgentHealth::Unhealthy => warn!(
+                    "Agent {} missed heartbeats for {:?}, marking unhealthy",
+                    agent.agent_id, silence
+                ),
+                AgentHealth::Dead => {
+                    warn!(
+                        "Agent {} missed heartbeats for {:?}, marking dead",
+                        agent.agent_id, silence
+                    );
+                    died.push(agent.agent_id.clone());
+                }
+                AgentHealth::Healthy => {}
+            }
+            agent.health = health;
+        }
+        died
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use raibid_common::agents::DEFAULT_DRAIN_TIMEOUT_SECS;
+
+    fn registration(id: &str) -> AgentRegistration {
+        AgentRegistration {
+            agent_id: id.to_string(),
+            version: "0.1.0".to_string(),
+            capabilities: vec!["rust".to_string(), "docker".to_string()],
+            pod_name: None,
+            drain_timeout_secs: DEFAULT_DRAIN_TIMEOUT_SECS,
+        }
+    }
+
+    fn at(secs: i64) -> DateTime<Utc> {
+        Utc.timestamp_opt(1_700_000_000 + secs, 0).unwrap()
+    }
+
+    fn health(registry: &AgentRegistry, id: &str) -> AgentHealth {
+        registry.get(id).unwrap().health
+    }
+
+    #[test]
+    fn test_health_after_silence() {
+        assert_eq!(AgentHealth::after(Duration::ZERO), AgentHealth::Healthy);
+        assert_eq!(
+            AgentHealth::after(Duration::from_secs(29)),
+            AgentHealth::Healthy
+        );
+        assert_eq!(
+            AgentHealth::after(Duration::from_secs(30)),
+            AgentHealth::Unhealthy
+        );
+        assert_eq!(
+            AgentHealth::after(Duration::from_secs(89)),
+            AgentHealth::Unhealthy
+        );
+        assert_eq!(
+            AgentHealth::after(Duration::from_secs(90)),
+            AgentHealth::Dead
+        );
+    }
+
+    #[test]
+    fn test_missed_heartbeats_mark_unhealthy_then_dead() {
+        let registry = AgentRegistry::new();
+        registry.register(registration("agent-1"), at(0));
+
+        registry.sweep(at(15));
+        assert_eq!(health(&registry, "agent-1"), AgentHealth::Healthy);
+
+        registry.sweep(at(30));
+        assert_eq!(health(&registry, "agent-1"), AgentHealth::Unhealthy);
+
+        assert_eq!(registry.sweep(at(90)), vec!["agent-1".to_string()]);
+        assert_eq!(health(&registry, "agent-1"), AgentHealth::Dead);
+
+        // Reported once
+        assert!(registry.sweep(at(95)).is_empty());
+    }
+
+    #[test]
+    fn test_heartbeat_revives_agent() {
+        let registry = AgentRegistry::new();
+        registry.register(registration("agent-1"), at(0));
+
+        registry.sweep(at(120));
+        assert_eq!(health(&registry, "agent-1"), AgentHealth::Dead);
+
+        assert!(registry.heartbeat("agent-1", at(125)));
+        assert_eq!(health(&registry, "agent-1"), AgentHealth::Healthy);
+        assert_eq!(registry.get("agent-1").unwrap().last_seen, at(125));
+
+        // The heartbeat resets the clock
+        registry.sweep(at(150));
+        assert_eq!(health(&registry, "agent-1"), AgentHealth::Healthy);
+    }
+
+    #[test]
+    fn test_heartbeat_unknown_agent() {
+        let registry = AgentRegistry::new();
+        assert!(!registry.heartbeat("agent-1", at(0)));
+        assert!(registry.list().is_empty());
+    }
+
+    #[test]
+    fn test_reregister_resets_agent() {
+        let registry = AgentRegistry::new();
+        registry.register(registration("agent-1"), at(0));
+        registry.sweep(at(60));
+
+        let mut update = registration("agent-1");
+        update.version = "0.2.0".to_string();
+        registry.register(update, at(61));
+
+        let agent = registry.get("agent-1").unwrap();
+        assert_eq!(agent.version, "0.2.0");
+        assert_eq!(agent.health, AgentHealth::Healthy);
+        assert_eq!(registry.list().len(), 1);
+    }
+
+    #[test]
+    fn test_agent_info() {
+        let registry = AgentRegistry::new();
+        let agent = registry.register(registration("agent-1"), at(0));
+
+        let info = agent.info(at(45));
+        assert_eq!(info.id, "agent-1");
+        assert_eq!(info.status, AgentStatus::Idle);
+        assert_eq!(info.uptime_secs, 45);
+
+        registry.sweep(at(45));
+        let info = registry.get("agent-1").unwrap().info(at(45));
+        assert_eq!(info.status, AgentStatus::Offline);
+    }
+
+    #[test]
+    fn test_drain() {
+        let registry = AgentRegistry::new();
+        registry.register(registration("agent-1"), at(0));
+        assert!(registry.drain("agent-2").is_none());
+
+        registry.set_active_jobs("agent-1", 2);
+        let info = registry.drain("agent-1").unwrap().info(at(10));
+        assert!(info.draining);
+        assert_eq!(info.active_jobs, 2);
+        assert_eq!(info.status, AgentStatus::Busy);
+
+        registry.set_active_jobs("agent-1", 0);
+        let info = registry.get("agent-1").unwrap().info(at(20));
+        assert_eq!(info.status, AgentStatus::Idle);
+        assert!(info.draining);
+
+        assert!(registry.remove("agent-1"));
+        assert!(!registry.remove("agent-1"));
+    }
+}
@@ -0,0 +1,234 @@
+//! Response cache
+//!
+//! The TUI polls `GET /api/jobs` on every refresh tick, so job list and job
+//! status responses are cached in memory for a short TTL instead of being
+//! rebuilt from Redis for every client. Cached responses carry
+//! `Cache-Control: max-age` and an `ETag`; a request whose `If-None-Match`
+//! matches is answered with `304 Not Modified`.
+//!
+//! Routes that queue jobs invalidate the cached entries they affect. Status
+//! changes written by agents go straight to Redis and show up once the entry
+//! expires.
+
+use axum::body::Bytes;
+use axum::http::header::{CACHE_CONTROL, CONTENT_TYPE, ETAG, IF_NONE_MATCH};
+use axum::http::{HeaderMap, HeaderValue, StatusCode};
+use axum::response::{IntoResponse, Response};
+use moka::sync::Cache;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::future::Future;
+use std::time::Duration;
+
+use crate::error::ApiResult;
+
+/// Default TTL of cached job lists, in milliseconds
+pub const DEFAULT_CACHE_TTL_MS: u64 = 2_000;
+
+/// Most responses kept per cache
+const MAX_ENTRIES: u64 = 1_000;
+
+/// A serialized JSON response with its validators
+#[derive(Debug, Clone)]
+pub struct CachedJson {
+    body: Bytes,
+    etag: String,
+    max_age: Duration,
+}
+
+impl CachedJson {
+    /// Serialize `value`, to be cached by clients for `max_age`
+    pub fn new<T: Serialize>(value: &T, max_age: Duration) -> ApiResult<Self> {
+        let body = serde_json::to_vec(value).map_err(anyhow::Error::from)?;
+        let digest = Sha256::digest(&body);
+        let etag = format!(
+            "\"{}\"",
+            digest[..16]
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect::<String>()
+        );
+
+        Ok(Self {
+            body: Bytes::from(body),
+            etag,
+            max_age,
+        })
+    }
+
+    /// Entity tag of the body, quoted
+    pub fn etag(&self) -> &str {
+        &self.etag
+    }
+
+    /// Response for a request with `headers`, `304 Not Modified` if the
+    /// client already has this body
+    pub fn respond(&self, headers: &HeaderMap) -> Response {
+        let cache_control = format!("max-age={}", self.max_age.as_secs());
+        let validators = [
+            (ETAG, HeaderValue::from_str(&self.etag).expect("hex ETag")),
+            (
+                CACHE_CONTROL,
+                HeaderValue::from_str(&cache_control).expect("numeric max-age"),
+            ),
+        ];
+
+        if self.matches(headers.get(IF_NONE_MATCH)) {
+            return (StatusCode::NOT_MODIFIED, validators).into_response();
+        }
+
+        (
+            validators,
+            [(CONTENT_TYPE, HeaderValue::from_static("application/json"))],
+            self.body.clone(),
+        )
+            .into_response()
+    }
+
+    fn matches(&self, if_none_match: Option<&HeaderValue>) -> bool {
+        let Some(value) = if_none_match.and_then(|v| v.to_str().ok()) else {
+            return false;
+        };
+        value
+            .split(',')
+            .map(str::trim)
+            .any(|tag| tag == "*" || tag.trim_start_matches("W/") == self.etag)
+    }
+}
+
+/// JSON responses cached by key for a fixed TTL
+pub struct JsonCache {
+    cache: Cache<String, CachedJson>,
+    ttl: Duration,
+}
+
+impl JsonCache {
+    /// Cache entries for `ttl`; a zero TTL disables caching
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            cache: Cache::builder()
+                .max_capacity(MAX_ENTRIES)
+                .time_to_live(ttl.max(Duration::from_millis(1)))
+                .build(),
+            ttl,
+        }
+    }
+
+    /// Cached response for `key`, or serialize what `fetch` returns and cache
+    /// it
+    ///
+    /// Errors from `fetch` are returned and not cached.
+    pub async fn get_or_fetch<T, F, Fut>(&self, key: &str, fetch: F) -> ApiResult<CachedJson>
+    where
+        T: Serialize,
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = ApiResult<T>>,
+    {
+        if let Some(cached) = self.cache.get(key) {
+            return Ok(cached);
+        }
+
+        let response = CachedJson::new(&fetch().await?, self.ttl)?;
+        if !self.ttl.is_zero() {
+            self.cache.insert(key.to_string(), response.clone());
+        }
+        Ok(response)
+    }
+
+    /// Drop the cached response for `key`
+    pub fn invalidate(&self, key: &str) {
+        self.cache.invalidate(key);
+    }
+
+    /// Drop every cached response
+    pub fn invalidate_all(&self) {
+        self.cache.invalidate_all();
+    }
+
+    /// Approximate number of cached responses
+    pub fn entry_count(&self) -> u64 {
+        self.cache.entry_count()
+    }
+}
+
+/// Caches of the job routes
+pub struct ResponseCache {
+    /// `GET /api/jobs`, keyed by query
+    pub job_lists: JsonCache,
+    /// `GET /api/jobs/:id/status`, keyed by job ID
+    pub job_statuses: JsonCache,
+}
+
+impl ResponseCache {
+    /// Cache job lists for `ttl` and job statuses for half as long
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            job_lists: JsonCache::new(ttl),
+            job_statuses: JsonCache::new(ttl / 2),
+        }
+    }
+
+    /// Forget cached responses a change to `job_id` makes stale
+    pub fn invalidate_job(&self, job_id: &str) {
+        self.job_lists.invalidate_all();
+        self.job_statuses.invalidate(job_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    async fn fetch_counted(cache: &JsonCache, calls: &AtomicUsize) -> CachedJson {
+        cache
+            .get_or_fetch("jobs", || async {
+                calls.fetch_add(1, Ordering::SeqCst);
+                Ok(vec!["job-1", "job-2"])
+            })
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_identical_requests_within_ttl_fetch_once() {
+        let cache = JsonCache::new(Duration::from_secs(60));
+        let calls = AtomicUsize::new(0);
+
+        let first = fetch_counted(&cache, &calls).await;
+        let second = fetch_counted(&cache, &calls).await;
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert_eq!(first.etag(), second.etag());
+
+        cache.invalidate("jobs");
+        fetch_counted(&cache, &calls).await;
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_zero_ttl_disables_cache() {
+        let cache = JsonCache::new(Duration::ZERO);
+        let calls = AtomicUsize::new(0);
+
+        fetch_counted(&cache, &calls).await;
+        fetch_counted(&cache, &calls).await;
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_respond_with_validators() {
+        let cached = CachedJson::new(&vec!["job-1"], Duration::from_secs(2)).unwrap();
+
+        let response = cached.respond(&HeaderMap::new());
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[CACHE_CONTROL], "max-age=2");
+        assert_eq!(response.headers()[ETAG], cached.etag());
+
+        let mut headers = HeaderMap::new();
+        headers.insert(IF_NONE_MATCH, cached.etag().parse().unwrap());
+        assert_eq!(cached.respond(&headers).status(), StatusCode::NOT_MODIFIED);
+
+        headers.insert(IF_NONE_MATCH, HeaderValue::from_static("\"other\""));
+        assert_eq!(cached.respond(&headers).status(), StatusCode::OK);
+    }
+}
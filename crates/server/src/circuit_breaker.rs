@@ -0,0 +1,245 @@
+//! Redis circuit breaker
+//!
+//! While Redis is down every request touching it would wait for a connection
+//! that never comes. [`CircuitBreaker`] counts consecutive Redis failures in
+//! [`AppState::with_redis`](crate::AppState::with_redis) and, after
+//! [`FAILURE_THRESHOLD`] of them, opens: Redis operations fail immediately
+//! with [`CircuitOpen`], which handlers report as `503 Service Unavailable`.
+//!
+//! After [`OPEN_DURATION`] the circuit is half-open and lets a single probe
+//! through. A successful probe closes the circuit again; a failed one
+//! re-opens it for another [`OPEN_DURATION`].
+
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::{info, warn};
+
+/// Consecutive Redis failures that open the circuit
+pub const FAILURE_THRESHOLD: u64 = 5;
+
+/// How long the circuit stays open before a probe is allowed
+pub const OPEN_DURATION: Duration = Duration::from_secs(30);
+
+/// State of a [`CircuitBreaker`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum CircuitState {
+    /// Requests reach Redis
+    Closed = 0,
+    /// Requests fail without reaching Redis
+    Open = 1,
+    /// One probe request is reaching Redis, the others fail
+    HalfOpen = 2,
+}
+
+impl CircuitState {
+    fn from_u8(value: u8) -> Self {
+        match value {
+            1 => CircuitState::Open,
+            2 => CircuitState::HalfOpen,
+            _ => CircuitState::Closed,
+        }
+    }
+
+    /// Lowercase name, as shown by `GET /health`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CircuitState::Closed => "closed",
+            CircuitState::Open => "open",
+            CircuitState::HalfOpen => "half_open",
+        }
+    }
+}
+
+/// Error returned instead of running a Redis operation while the circuit is
+/// open
+#[derive(Debug, thiserror::Error)]
+#[error("Redis is not reachable (circuit breaker open)")]
+pub struct CircuitOpen;
+
+/// Circuit breaker guarding Redis access, shared by all clones of
+/// [`AppState`](crate::AppState)
+#[derive(Debug, Clone, Default)]
+pub struct CircuitBreaker {
+    state: Arc<AtomicU8>,
+    failures: Arc<AtomicU64>,
+    /// Milliseconds since the Unix epoch of the last failure, or of the
+    /// start of the current probe
+    last_failure_ms: Arc<AtomicU64>,
+}
+
+impl CircuitBreaker {
+    /// Create a closed circuit breaker
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Current state; an open circuit past [`OPEN_DURATION`] is reported as
+    /// half-open
+    pub fn state(&self) -> CircuitState {
+        self.state_at(now_ms())
+    }
+
+    /// Whether a Redis operation may run now
+    ///
+    /// Once [`OPEN_DURATION`] has passed since the last failure the first
+    /// caller is let through as the probe.
+    pub fn allow_request(&self) -> bool {
+        self.allow_request_at(now_ms())
+    }
+
+    /// Record a Redis operation that reached Redis, closing the circuit
+    pub fn record_success(&self) {
+        self.failures.store(0, Ordering::Relaxed);
+        let previous = self
+            .state
+            .swap(CircuitState::Closed as u8, Ordering::AcqRel);
+        if previous != CircuitState::Closed as u8 {
+            info!("Redis is reachable again, closing the circuit breaker");
+        }
+    }
+
+    /// Record a Redis operation that could not reach Redis
+    pub fn record_failure(&self) {
+        self.record_failure_at(now_ms());
+    }
+
+    fn state_at(&self, now: u64) -> CircuitState {
+        match CircuitState::from_u8(self.state.load(Ordering::Acquire)) {
+            CircuitState::Open if self.open_elapsed(now) => CircuitState::HalfOpen,
+            state => state,
+        }
+    }
+
+    fn allow_request_at(&self, now: u64) -> bool {
+        match CircuitState::from_u8(self.state.load(Ordering::Acquire)) {
+            CircuitState::Closed => true,
+            // A probe that never reported back (its request was cancelled) is
+            // replaced after another OPEN_DURATION
+            CircuitState::Open | CircuitState::HalfOpen => {
+                // Only the caller moving the timestamp becomes the probe
+                let since = self.last_failure_ms.load(Ordering::Acquire);
+                let probe = now.saturating_sub(since) >= OPEN_DURATION.as_millis() as u64
+                    && self
+                        .last_failure_ms
+                        .compare_exchange(since, now, Ordering::AcqRel, Ordering::Acquire)
+                        .is_ok();
+                if probe {
+                    self.state
+                        .store(CircuitState::HalfOpen as u8, Ordering::Release);
+                }
+                probe
+            }
+        }
+    }
+
+    fn record_failure_at(&self, now: u64) {
+        self.last_failure_ms.store(now, Ordering::Release);
+        let failures = self.failures.fetch_add(1, Ordering::AcqRel) + 1;
+
+        let state = CircuitState::from_u8(self.state.load(Ordering::Acquire));
+        let open = match state {
+            CircuitState::HalfOpen => true,
+            CircuitState::Closed => failures >= FAILURE_THRESHOLD,
+            CircuitState::Open => false,
+        };
+        if open {
+            self.state
+                .store(CircuitState::Open as u8, Ordering::Release);
+            warn!(
+                "Opening the Redis circuit breaker after {} consecutive failures, \
+                 retrying in {:?}",
+                failures, OPEN_DURATION
+            );
+        }
+    }
+
+    fn open_elapsed(&self, now: u64) -> bool {
+        let last_failure = self.last_failure_ms.load(Ordering::Acquire);
+        now.saturating_sub(last_failure) >= OPEN_DURATION.as_millis() as u64
+    }
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const START: u64 = 1_000_000;
+    const REOPEN: u64 = START + 30_000;
+
+    fn open_breaker() -> CircuitBreaker {
+        let breaker = CircuitBreaker::new();
+        for _ in 0..FAILURE_THRESHOLD {
+            breaker.record_failure_at(START);
+        }
+        breaker
+    }
+
+    #[test]
+    fn test_opens_after_consecutive_failures() {
+        let breaker = CircuitBreaker::new();
+        for _ in 0..FAILURE_THRESHOLD - 1 {
+            breaker.record_failure_at(START);
+        }
+        assert_eq!(breaker.state_at(START), CircuitState::Closed);
+        assert!(breaker.allow_request_at(START));
+
+        // A success in between resets the count
+        breaker.record_success();
+        breaker.record_failure_at(START);
+        assert_eq!(breaker.state_at(START), CircuitState::Closed);
+
+        let breaker = open_breaker();
+        assert_eq!(breaker.state_at(START), CircuitState::Open);
+        assert!(!breaker.allow_request_at(START + 1_000));
+    }
+
+    #[test]
+    fn test_half_open_allows_one_probe() {
+        let breaker = open_breaker();
+        assert!(!breaker.allow_request_at(REOPEN - 1));
+        assert_eq!(breaker.state_at(REOPEN), CircuitState::HalfOpen);
+
+        assert!(breaker.allow_request_at(REOPEN));
+        assert!(!breaker.allow_request_at(REOPEN));
+        assert_eq!(breaker.state_at(REOPEN), CircuitState::HalfOpen);
+
+        // A probe that never reports back is eventually replaced
+        assert!(!breaker.allow_request_at(REOPEN + 29_999));
+        assert!(breaker.allow_request_at(REOPEN + 30_000));
+        assert!(!breaker.allow_request_at(REOPEN + 30_000));
+    }
+
+    #[test]
+    fn test_successful_probe_closes() {
+        let breaker = open_breaker();
+        assert!(breaker.allow_request_at(REOPEN));
+
+        breaker.record_success();
+        assert_eq!(breaker.state_at(REOPEN), CircuitState::Closed);
+        assert!(breaker.allow_request_at(REOPEN));
+
+        // The failure count starts over
+        breaker.record_failure_at(REOPEN);
+        assert_eq!(breaker.state_at(REOPEN), CircuitState::Closed);
+    }
+
+    #[test]
+    fn test_failed_probe_reopens() {
+        let breaker = open_breaker();
+        assert!(breaker.allow_request_at(REOPEN));
+
+        breaker.record_failure_at(REOPEN);
+        assert_eq!(breaker.state_at(REOPEN), CircuitState::Open);
+        assert!(!breaker.allow_request_at(REOPEN + 29_999));
+        assert!(breaker.allow_request_at(REOPEN + 30_000));
+    }
+}
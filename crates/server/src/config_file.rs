@@ -0,0 +1,142 @@
+//! Reloadable configuration file
+//!
+//! Settings are read from `RAIBID_*` environment variables at startup.
+//! Credentials and the benchmark threshold can also be set in a YAML file
+//! named by `RAIBID_SERVER_CONFIG`, which `POST /admin/reload-config`
+//! re-reads while the server runs, e.g. after a mounted Kubernetes Secret
+//! changed:
+//!
+//! ```yaml
+//! api_keys:
+//!   - sha256:9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08
+//! admin_keys:
+//!   - sha256:60303ae22b998861bce3b28f33eec1be758a213c86c93c076dbe9f558c11c752
+//! gitlab_webhook_secret: gitlab-secret
+//! bitbucket_webhook_secret: bitbucket-secret
+//! bitbucket_allowed_ips: [104.192.136.0/21]
+//! gitea_webhook_secret: gitea-secret
+//! benchmark_regression_threshold_pct: 15
+//! ```
+//!
+//! Settings missing from the file keep their environment value.
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::path::Path;
+
+use crate::ServerConfig;
+
+/// Settings read from the configuration file
+#[derive(Debug, Clone, Default, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigFile {
+    /// Replaces [`ServerConfig::api_keys`]
+    pub api_keys: Option<Vec<String>>,
+    /// Replaces [`ServerConfig::admin_keys`]
+    pub admin_keys: Option<Vec<String>>,
+    /// Replaces [`ServerConfig::gitlab_webhook_secret`]
+    pub gitlab_webhook_secret: Option<String>,
+    /// Replaces [`ServerConfig::bitbucket_webhook_secret`]
+    pub bitbucket_webhook_secret: Option<String>,
+    /// Replaces [`ServerConfig::bitbucket_allowed_ips`], as CIDRs
+    pub bitbucket_allowed_ips: Option<Vec<String>>,
+    /// Replaces [`ServerConfig::gitea_webhook_secret`]
+    pub gitea_webhook_secret: Option<String>,
+    /// Replaces [`ServerConfig::benchmark_regression_threshold_pct`]
+    pub benchmark_regression_threshold_pct: Option<f64>,
+}
+
+impl ConfigFile {
+    /// Parse a configuration file
+    pub fn from_yaml(contents: &str) -> Result<Self> {
+        serde_yaml::from_str(contents).context("Failed to parse server configuration")
+    }
+
+    /// Read and parse the configuration file at `path`
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        Self::from_yaml(&contents).with_context(|| format!("Invalid {}", path.display()))
+    }
+
+    /// Apply the settings in the file to `config`, returning the names of
+    /// those it set
+    pub fn apply(&self, config: &mut ServerConfig) -> Result<Vec<&'static str>> {
+        let mut applied = Vec::new();
+
+        if let Some(ref nets) = self.bitbucket_allowed_ips {
+            config.bitbucket_allowed_ips = nets
+                .iter()
+                .map(|net| {
+                    net.trim()
+                        .parse()
+                        .with_context(|| format!("Invalid bitbucket_allowed_ips entry '{}'", net))
+                })
+                .collect::<Result<_>>()?;
+            applied.push("bitbucket_allowed_ips");
+        }
+        if let Some(ref keys) = self.api_keys {
+            config.api_keys = keys.clone();
+            applied.push("api_keys");
+        }
+        if let Some(ref keys) = self.admin_keys {
+            config.admin_keys = keys.clone();
+            applied.push("admin_keys");
+        }
+        if let Some(ref secret) = self.gitlab_webhook_secret {
+            config.gitlab_webhook_secret = Some(secret.clone());
+            applied.push("gitlab_webhook_secret");
+        }
+        if let Some(ref secret) = self.bitbucket_webhook_secret {
+            config.bitbucket_webhook_secret = Some(secret.clone());
+            applied.push("bitbucket_webhook_secret");
+        }
+        if let Some(ref secret) = self.gitea_webhook_secret {
+            config.gitea_webhook_secret = Some(secret.clone());
+            applied.push("gitea_webhook_secret");
+        }
+        if let Some(threshold) = self.benchmark_regression_threshold_pct {
+            config.benchmark_regression_threshold_pct = threshold;
+            applied.push("benchmark_regression_threshold_pct");
+        }
+
+        Ok(applied)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply_keeps_unset_settings() {
+        let file = ConfigFile::from_yaml(
+            "api_keys: [rbd_new]\nbitbucket_allowed_ips: [104.192.136.0/21]\n",
+        )
+        .unwrap();
+        let mut config = ServerConfig {
+            api_keys: vec!["rbd_old".to_string()],
+            gitlab_webhook_secret: Some("gitlab-secret".to_string()),
+            ..Default::default()
+        };
+
+        let applied = file.apply(&mut config).unwrap();
+        assert_eq!(applied, vec!["bitbucket_allowed_ips", "api_keys"]);
+        assert_eq!(config.api_keys, vec!["rbd_new"]);
+        assert_eq!(config.bitbucket_allowed_ips.len(), 1);
+        assert_eq!(
+            config.gitlab_webhook_secret.as_deref(),
+            Some("gitlab-secret")
+        );
+    }
+
+    #[test]
+    fn test_invalid_file_rejected() {
+        assert!(ConfigFile::from_yaml("redis_url: redis://elsewhere\n").is_err());
+
+        let file = ConfigFile::from_yaml("bitbucket_allowed_ips: [not-a-network]\n").unwrap();
+        let mut config = ServerConfig::default();
+        assert!(file.apply(&mut config).is_err());
+        assert!(config.bitbucket_allowed_ips.is_empty());
+    }
+}
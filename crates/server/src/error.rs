@@ -0,0 +1,130 @@
+//! API error responses
+//!
+//! Handlers return `Result<_, ApiError>`; errors are rendered as a JSON body
+//! `{"error": "..."}` with a matching HTTP status code.
+
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use serde_json::json;
+
+use crate::circuit_breaker::CircuitOpen;
+
+/// Error returned from API handlers
+#[derive(Debug, thiserror::Error)]
+pub enum ApiError {
+    /// Requested resource does not exist
+    #[error("{0}")]
+    NotFound(String),
+
+    /// Request was malformed or failed validation
+    #[error("{0}")]
+    BadRequest(String),
+
+    /// Request conflicts with the current state of the resource
+    #[error("{0}")]
+    Conflict(String),
+
+    /// Request lacked valid credentials
+    #[error("{0}")]
+    Unauthorized(String),
+
+    /// Client exceeded its request rate limit
+    #[error("{0}")]
+    TooManyRequests(String),
+
+    /// A backing service (e.g. Redis) is unavailable
+    #[error("Service unavailable: {0}")]
+    Unavailable(String),
+
+    /// Unexpected internal failure
+    #[error(transparent)]
+    Internal(anyhow::Error),
+}
+
+impl From<anyhow::Error> for ApiError {
+    /// Redis operations refused by the open circuit breaker become
+    /// `503 Service Unavailable`; anything else is an internal error
+    fn from(err: anyhow::Error) -> Self {
+        match err.downcast_ref::<CircuitOpen>() {
+            Some(open) => ApiError::Unavailable(open.to_string()),
+            None => ApiError::Internal(err),
+        }
+    }
+}
+
+impl ApiError {
+    /// HTTP status code for this error
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            ApiError::NotFound(_) => StatusCode::NOT_FOUND,
+            ApiError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            ApiError::Conflict(_) => StatusCode::CONFLICT,
+            ApiError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            ApiError::TooManyRequests(_) => StatusCode::TOO_MANY_REQUESTS,
+            ApiError::Unavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+            ApiError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let status = self.status_code();
+        if status.is_server_error() {
+            tracing::error!("API error: {:#}", self);
+        }
+
+        (status, Json(json!({ "error": self.to_string() }))).into_response()
+    }
+}
+
+/// Result type for API handlers
+pub type ApiResult<T> = Result<T, ApiError>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status_codes() {
+        assert_eq!(
+            ApiError::NotFound("job".into()).status_code(),
+            StatusCode::NOT_FOUND
+        );
+        assert_eq!(
+            ApiError::BadRequest("bad".into()).status_code(),
+            StatusCode::BAD_REQUEST
+        );
+        assert_eq!(
+            ApiError::Conflict("running".into()).status_code(),
+            StatusCode::CONFLICT
+        );
+        assert_eq!(
+            ApiError::Unauthorized("key".into()).status_code(),
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(
+            ApiError::TooManyRequests("slow down".into()).status_code(),
+            StatusCode::TOO_MANY_REQUESTS
+        );
+        assert_eq!(
+            ApiError::Unavailable("redis".into()).status_code(),
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+        assert_eq!(
+            ApiError::from(anyhow::anyhow!("boom")).status_code(),
+            StatusCode::INTERNAL_SERVER_ERROR
+        );
+        assert_eq!(
+            ApiError::from(anyhow::Error::new(CircuitOpen)).status_code(),
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+    }
+
+    #[test]
+    fn test_into_response() {
+        let response = ApiError::NotFound("Job job-1 not found".into()).into_response();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+}
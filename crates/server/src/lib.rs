@@ -1,39 +1,554 @@
 //! raibid-server
 //!
 //! API server for job dispatching and TUI communication.
-//! This crate will handle:
+//! This crate handles:
 //! - Job queue management
 //! - Agent registration and health checks
-//! - Real-time status updates for TUI
+//! - Real-time status updates for TUI, including agent status changes
+//!   relayed from Redis Pub/Sub
 //! - WebSocket connections for live monitoring
-//!
-//! ## Status: Placeholder
-//! This crate is a placeholder for future implementation.
+//! - Cron-scheduled builds
+//! - Admin routes for shutdown, draining and configuration reloads
+//! - OpenTelemetry tracing of requests
 
 #![allow(dead_code)]
 
-use anyhow::Result;
+pub mod agent_events;
+pub mod agents;
+pub mod cache;
+pub mod circuit_breaker;
+pub mod config_file;
+pub mod error;
+pub mod log_stream;
+pub mod middleware;
+pub mod routes;
+pub mod scaling;
+pub mod scheduler;
+pub mod state;
+pub mod telemetry;
+
+pub use error::{ApiError, ApiResult};
+pub use middleware::CorsConfig;
+pub use state::AppState;
+
+use anyhow::{bail, Context, Result};
+use axum_server::tls_rustls::RustlsConfig;
+use cache::DEFAULT_CACHE_TTL_MS;
+use config_file::ConfigFile;
+use ipnet::IpNet;
+use middleware::DEFAULT_MAX_BODY_SIZE_BYTES;
+use raibid_common::benchmark::DEFAULT_REGRESSION_THRESHOLD_PCT;
+use raibid_common::infrastructure::keda::ScaledObjectConfig;
+use std::env;
+use std::net::{SocketAddr, TcpListener};
+use std::path::PathBuf;
+use std::time::Duration;
+use telemetry::DEFAULT_SERVICE_NAME;
+use tracing::{info, warn};
+
+/// Default [`ServerConfig::redis_pool_size`]
+pub const DEFAULT_REDIS_POOL_SIZE: usize = 10;
+
+/// Default [`ServerConfig::redis_connect_timeout_ms`]
+pub const DEFAULT_REDIS_CONNECT_TIMEOUT_MS: u64 = 5_000;
+
+/// Default [`ServerConfig::redis_wait_timeout_ms`]
+pub const DEFAULT_REDIS_WAIT_TIMEOUT_MS: u64 = 2_000;
+
+/// Default [`ServerConfig::dedup_window_secs`]
+pub const DEFAULT_DEDUP_WINDOW_SECS: u64 = 3600;
+
+/// Time in-flight requests get to finish after a shutdown is requested
+pub const SHUTDOWN_GRACE_PERIOD: Duration = Duration::from_secs(30);
 
 /// Server configuration
+#[derive(Debug, Clone)]
 pub struct ServerConfig {
     pub host: String,
     pub port: u16,
+    /// Redis connection URL (`redis://[:password@]host:port[/db]`)
+    pub redis_url: String,
+    /// Most Redis connections kept open at once
+    pub redis_pool_size: usize,
+    /// How long connecting to Redis may take, in milliseconds
+    pub redis_connect_timeout_ms: u64,
+    /// How long a request waits for a free Redis connection when all are in
+    /// use, in milliseconds
+    pub redis_wait_timeout_ms: u64,
+    /// Base name of the job streams, one per priority (`<queue_stream>:high`,
+    /// `<queue_stream>:normal`, `<queue_stream>:low`)
+    pub queue_stream: String,
+    /// Consumer group agents read the job stream with
+    pub consumer_group: String,
+    /// How long a queued commit is remembered, in seconds; builds of the
+    /// same repository and commit queued within the window are not queued
+    /// again
+    ///
+    /// `0` disables deduplication.
+    pub dedup_window_secs: u64,
+    /// Whether to record metrics and serve them for Prometheus
+    pub metrics_enabled: bool,
+    /// Path the Prometheus metrics are served on
+    pub metrics_path: String,
+    /// API keys required on `/api/` routes, plain text or `sha256:<hex>`
+    ///
+    /// Authentication is disabled when empty.
+    pub api_keys: Vec<String>,
+    /// Keys required on `/admin/` routes in `X-Admin-Key`, plain text or
+    /// `sha256:<hex>`
+    ///
+    /// The admin routes reject every request when empty.
+    pub admin_keys: Vec<String>,
+    /// Secret GitLab sends in `X-Gitlab-Token` with webhook requests
+    ///
+    /// GitLab webhooks are rejected when unset.
+    pub gitlab_webhook_secret: Option<String>,
+    /// Secret Bitbucket signs webhook requests with in `X-Hub-Signature`
+    pub bitbucket_webhook_secret: Option<String>,
+    /// Networks Bitbucket webhook requests may come from
+    ///
+    /// Bitbucket webhooks are rejected when neither this nor
+    /// `bitbucket_webhook_secret` is set.
+    pub bitbucket_allowed_ips: Vec<IpNet>,
+    /// Secret Gitea signs webhook requests with in `X-Gitea-Signature`
+    ///
+    /// Gitea webhooks are rejected when unset.
+    pub gitea_webhook_secret: Option<String>,
+    /// Whether Gitea pull request events queue and cancel builds
+    ///
+    /// When disabled, only pushes are built.
+    pub handle_pr_events: bool,
+    /// Sustained webhook requests per second allowed from one client IP
+    ///
+    /// Rate limiting is disabled when unset.
+    pub rate_limit_rps: Option<u32>,
+    /// Webhook requests one client IP may burst above the sustained rate
+    /// (defaults to `rate_limit_rps`)
+    pub rate_limit_burst: Option<u32>,
+    /// Proxies (e.g. the ingress controller) trusted to name the client in
+    /// `X-Forwarded-For`
+    ///
+    /// Clients are rate limited by their connection address when empty.
+    pub trusted_proxies: Vec<IpNet>,
+    /// Largest request body accepted, in bytes
+    ///
+    /// Larger requests are answered with `413 Payload Too Large`.
+    pub max_body_size_bytes: usize,
+    /// Whether to gzip JSON responses for clients accepting it
+    pub compression_enabled: bool,
+    /// How long job lists are cached, in milliseconds; job statuses are
+    /// cached for half as long
+    ///
+    /// Caching is disabled when 0.
+    pub cache_ttl_ms: u64,
+    /// PEM certificate chain to serve HTTPS with
+    ///
+    /// TLS is enabled when both this and `tls_key_path` are set. Production
+    /// deployments should use a certificate issued by cert-manager.
+    pub tls_cert_path: Option<PathBuf>,
+    /// PEM private key of `tls_cert_path`
+    pub tls_key_path: Option<PathBuf>,
+    /// Slowdown in percent above which `GET /api/benchmarks/compare` flags a
+    /// benchmark as a regression
+    pub benchmark_regression_threshold_pct: f64,
+    /// KEDA ScaledObject scaling the agents
+    pub scaled_object_name: String,
+    /// Namespace of `scaled_object_name`
+    pub scaled_object_namespace: String,
+    /// Deployment running the agents, in `scaled_object_namespace`
+    pub agent_deployment_name: String,
+    /// CORS settings for browser clients on other origins
+    ///
+    /// Cross-origin requests are not answered with CORS headers when unset.
+    pub cors: Option<CorsConfig>,
+    /// YAML file with settings that can be reloaded while the server runs,
+    /// see [`config_file`]
+    pub config_file: Option<PathBuf>,
+    /// OTLP gRPC endpoint of an OpenTelemetry collector
+    /// (`http://otel-collector:4317`)
+    ///
+    /// Spans are only logged when unset.
+    pub otel_endpoint: Option<String>,
+    /// `service.name` spans are exported with
+    pub service_name: String,
+    /// Whether `GET /health/ready` also checks that the k3s API answers
+    pub health_check_k3s: bool,
 }
 
 impl Default for ServerConfig {
     fn default() -> Self {
+        let scaled_object = ScaledObjectConfig::default();
         Self {
             host: "127.0.0.1".to_string(),
             port: 8080,
+            redis_url: "redis://127.0.0.1:6379".to_string(),
+            redis_pool_size: DEFAULT_REDIS_POOL_SIZE,
+            redis_connect_timeout_ms: DEFAULT_REDIS_CONNECT_TIMEOUT_MS,
+            redis_wait_timeout_ms: DEFAULT_REDIS_WAIT_TIMEOUT_MS,
+            queue_stream: "raibid:jobs".to_string(),
+            consumer_group: "raibid-workers".to_string(),
+            dedup_window_secs: DEFAULT_DEDUP_WINDOW_SECS,
+            metrics_enabled: true,
+            metrics_path: "/metrics".to_string(),
+            api_keys: Vec::new(),
+            admin_keys: Vec::new(),
+            gitlab_webhook_secret: None,
+            bitbucket_webhook_secret: None,
+            bitbucket_allowed_ips: Vec::new(),
+            gitea_webhook_secret: None,
+            handle_pr_events: false,
+            rate_limit_rps: None,
+            rate_limit_burst: None,
+            trusted_proxies: Vec::new(),
+            max_body_size_bytes: DEFAULT_MAX_BODY_SIZE_BYTES,
+            compression_enabled: true,
+            cache_ttl_ms: DEFAULT_CACHE_TTL_MS,
+            tls_cert_path: None,
+            tls_key_path: None,
+            benchmark_regression_threshold_pct: DEFAULT_REGRESSION_THRESHOLD_PCT,
+            scaled_object_name: scaled_object.name,
+            scaled_object_namespace: scaled_object.namespace,
+            agent_deployment_name: scaled_object.target_name,
+            cors: None,
+            config_file: None,
+            otel_endpoint: None,
+            service_name: DEFAULT_SERVICE_NAME.to_string(),
+            health_check_k3s: false,
+        }
+    }
+}
+
+impl ServerConfig {
+    /// Default configuration with environment variable overrides
+    ///
+    /// Reads `RAIBID_SERVER_HOST`, `RAIBID_SERVER_PORT`, `RAIBID_REDIS_URL`,
+    /// `RAIBID_REDIS_POOL_SIZE`, `RAIBID_REDIS_CONNECT_TIMEOUT_MS`,
+    /// `RAIBID_REDIS_WAIT_TIMEOUT_MS`, `RAIBID_DEDUP_WINDOW_SECS`,
+    /// `RAIBID_METRICS_ENABLED` (`true` or `false`), `RAIBID_METRICS_PATH`,
+    /// `RAIBID_API_KEYS` and `RAIBID_ADMIN_KEYS` (comma-separated),
+    /// `RAIBID_GITLAB_WEBHOOK_SECRET`, `RAIBID_BITBUCKET_WEBHOOK_SECRET`,
+    /// `RAIBID_BITBUCKET_ALLOWED_IPS` (comma-separated CIDRs),
+    /// `RAIBID_RATE_LIMIT_RPS`, `RAIBID_RATE_LIMIT_BURST`,
+    /// `RAIBID_TRUSTED_PROXIES` (comma-separated CIDRs), `RAIBID_MAX_BODY_SIZE`
+    /// (bytes), `RAIBID_COMPRESSION` (`true` or `false`), `RAIBID_CACHE_TTL_MS`,
+    /// `RAIBID_TLS_CERT`, `RAIBID_TLS_KEY`,
+    /// `RAIBID_BENCHMARK_REGRESSION_THRESHOLD` (percent),
+    /// `RAIBID_SCALED_OBJECT`, `RAIBID_SCALED_OBJECT_NAMESPACE`,
+    /// `RAIBID_CORS_ORIGINS` (comma-separated, `*` for any origin),
+    /// `RAIBID_OTEL_ENDPOINT`, `RAIBID_SERVICE_NAME` and
+    /// `RAIBID_HEALTH_CHECK_K3S` (`true` or `false`), then applies the file
+    /// named by `RAIBID_SERVER_CONFIG`, if set.
+    pub fn from_env() -> Result<Self> {
+        let mut config = Self::default();
+
+        if let Ok(val) = env::var("RAIBID_SERVER_HOST") {
+            config.host = val;
+        }
+        if let Ok(val) = env::var("RAIBID_SERVER_PORT") {
+            config.port = val.parse().context("Invalid RAIBID_SERVER_PORT")?;
+        }
+        if let Ok(val) = env::var("RAIBID_REDIS_URL") {
+            config.redis_url = val;
+        }
+        if let Ok(val) = env::var("RAIBID_REDIS_POOL_SIZE") {
+            config.redis_pool_size = val.parse().context("Invalid RAIBID_REDIS_POOL_SIZE")?;
+        }
+        if let Ok(val) = env::var("RAIBID_REDIS_CONNECT_TIMEOUT_MS") {
+            config.redis_connect_timeout_ms = val
+                .parse()
+                .context("Invalid RAIBID_REDIS_CONNECT_TIMEOUT_MS")?;
+        }
+        if let Ok(val) = env::var("RAIBID_REDIS_WAIT_TIMEOUT_MS") {
+            config.redis_wait_timeout_ms = val
+                .parse()
+                .context("Invalid RAIBID_REDIS_WAIT_TIMEOUT_MS")?;
+        }
+        if let Ok(val) = env::var("RAIBID_DEDUP_WINDOW_SECS") {
+            config.dedup_window_secs = val.parse().context("Invalid RAIBID_DEDUP_WINDOW_SECS")?;
+        }
+        if let Ok(val) = env::var("RAIBID_METRICS_ENABLED") {
+            config.metrics_enabled = val.parse().context("Invalid RAIBID_METRICS_ENABLED")?;
+        }
+        if let Ok(val) = env::var("RAIBID_METRICS_PATH") {
+            config.metrics_path = val;
+        }
+        if let Ok(val) = env::var("RAIBID_API_KEYS") {
+            config.api_keys = val
+                .split(',')
+                .map(str::trim)
+                .filter(|key| !key.is_empty())
+                .map(String::from)
+                .collect();
+        }
+        if let Ok(val) = env::var("RAIBID_ADMIN_KEYS") {
+            config.admin_keys = val
+                .split(',')
+                .map(str::trim)
+                .filter(|key| !key.is_empty())
+                .map(String::from)
+                .collect();
+        }
+        if let Ok(val) = env::var("RAIBID_GITLAB_WEBHOOK_SECRET") {
+            config.gitlab_webhook_secret = Some(val);
+        }
+        if let Ok(val) = env::var("RAIBID_BITBUCKET_WEBHOOK_SECRET") {
+            config.bitbucket_webhook_secret = Some(val);
+        }
+        if let Ok(val) = env::var("RAIBID_GITEA_WEBHOOK_SECRET") {
+            config.gitea_webhook_secret = Some(val);
+        }
+        if let Ok(val) = env::var("RAIBID_HANDLE_PR_EVENTS") {
+            config.handle_pr_events = val.parse().context("Invalid RAIBID_HANDLE_PR_EVENTS")?;
+        }
+        if let Ok(val) = env::var("RAIBID_BITBUCKET_ALLOWED_IPS") {
+            config.bitbucket_allowed_ips = val
+                .split(',')
+                .map(str::trim)
+                .filter(|net| !net.is_empty())
+                .map(|net| {
+                    net.parse().with_context(|| {
+                        format!("Invalid RAIBID_BITBUCKET_ALLOWED_IPS entry '{}'", net)
+                    })
+                })
+                .collect::<Result<_>>()?;
+        }
+        if let Ok(val) = env::var("RAIBID_RATE_LIMIT_RPS") {
+            config.rate_limit_rps = Some(val.parse().context("Invalid RAIBID_RATE_LIMIT_RPS")?);
+        }
+        if let Ok(val) = env::var("RAIBID_RATE_LIMIT_BURST") {
+            config.rate_limit_burst = Some(val.parse().context("Invalid RAIBID_RATE_LIMIT_BURST")?);
+        }
+        if let Ok(val) = env::var("RAIBID_TRUSTED_PROXIES") {
+            config.trusted_proxies = val
+                .split(',')
+                .map(str::trim)
+                .filter(|net| !net.is_empty())
+                .map(|net| {
+                    net.parse()
+                        .with_context(|| format!("Invalid RAIBID_TRUSTED_PROXIES entry '{}'", net))
+                })
+                .collect::<Result<_>>()?;
+        }
+        if let Ok(val) = env::var("RAIBID_MAX_BODY_SIZE") {
+            config.max_body_size_bytes = val.parse().context("Invalid RAIBID_MAX_BODY_SIZE")?;
+        }
+        if let Ok(val) = env::var("RAIBID_COMPRESSION") {
+            config.compression_enabled = val.parse().context("Invalid RAIBID_COMPRESSION")?;
+        }
+        if let Ok(val) = env::var("RAIBID_CACHE_TTL_MS") {
+            config.cache_ttl_ms = val.parse().context("Invalid RAIBID_CACHE_TTL_MS")?;
+        }
+        if let Ok(val) = env::var("RAIBID_TLS_CERT") {
+            config.tls_cert_path = Some(PathBuf::from(val));
+        }
+        if let Ok(val) = env::var("RAIBID_TLS_KEY") {
+            config.tls_key_path = Some(PathBuf::from(val));
+        }
+        if let Ok(val) = env::var("RAIBID_BENCHMARK_REGRESSION_THRESHOLD") {
+            config.benchmark_regression_threshold_pct = val
+                .parse()
+                .context("Invalid RAIBID_BENCHMARK_REGRESSION_THRESHOLD")?;
+        }
+        if let Ok(val) = env::var("RAIBID_SCALED_OBJECT") {
+            config.scaled_object_name = val;
+        }
+        if let Ok(val) = env::var("RAIBID_SCALED_OBJECT_NAMESPACE") {
+            config.scaled_object_namespace = val;
+        }
+        if let Ok(val) = env::var("RAIBID_AGENT_DEPLOYMENT") {
+            config.agent_deployment_name = val;
+        }
+        if let Ok(val) = env::var("RAIBID_CORS_ORIGINS") {
+            let origins: Vec<String> = val
+                .split(',')
+                .map(str::trim)
+                .filter(|origin| !origin.is_empty())
+                .map(String::from)
+                .collect();
+            if !origins.is_empty() {
+                config.cors = Some(CorsConfig::with_origins(origins));
+            }
+        }
+        if let Ok(val) = env::var("RAIBID_OTEL_ENDPOINT") {
+            config.otel_endpoint = Some(val).filter(|endpoint| !endpoint.is_empty());
+        }
+        if let Ok(val) = env::var("RAIBID_SERVICE_NAME") {
+            config.service_name = val;
+        }
+        if let Ok(val) = env::var("RAIBID_HEALTH_CHECK_K3S") {
+            config.health_check_k3s = val.parse().context("Invalid RAIBID_HEALTH_CHECK_K3S")?;
+        }
+        if let Ok(val) = env::var("RAIBID_SERVER_CONFIG") {
+            let path = PathBuf::from(val);
+            ConfigFile::load(&path)?.apply(&mut config)?;
+            config.config_file = Some(path);
+        }
+
+        Ok(config)
+    }
+
+    /// Socket address to bind
+    pub fn bind_address(&self) -> String {
+        format!("{}:{}", self.host, self.port)
+    }
+
+    /// Certificate and key paths, if TLS is enabled
+    ///
+    /// Fails if only one of them is set, rather than silently serving plain
+    /// HTTP.
+    pub fn tls_paths(&self) -> Result<Option<(&PathBuf, &PathBuf)>> {
+        match (&self.tls_cert_path, &self.tls_key_path) {
+            (Some(cert), Some(key)) => Ok(Some((cert, key))),
+            (None, None) => Ok(None),
+            _ => bail!("TLS needs both a certificate (RAIBID_TLS_CERT) and a key (RAIBID_TLS_KEY)"),
         }
     }
 }
 
+/// API server
+pub struct Server {
+    state: AppState,
+}
+
+impl Server {
+    /// Create a server with the given configuration
+    pub fn new(config: ServerConfig) -> Result<Self> {
+        Ok(Self {
+            state: AppState::new(config)?,
+        })
+    }
+
+    /// Get shared application state
+    pub fn state(&self) -> &AppState {
+        &self.state
+    }
+
+    /// Install the log subscriber, exporting spans to
+    /// [`ServerConfig::otel_endpoint`] if set
+    ///
+    /// Call once, from within the Tokio runtime, before [`Server::run`].
+    pub fn init_tracing(config: &ServerConfig) -> Result<()> {
+        telemetry::init_tracing(config)
+    }
+
+    /// Check Redis, bind the listener and serve requests until shut down
+    /// through `POST /admin/shutdown`
+    pub async fn run(self) -> Result<()> {
+        let address = self.state.config().bind_address();
+
+        if self.state.api_keys().is_empty() {
+            warn!("No API keys configured, /api/ routes are unauthenticated");
+        }
+        if self.state.admin_keys().is_empty() {
+            warn!("No admin keys configured, /admin/ routes are disabled");
+        }
+        if self.state.gitlab_webhook_secret().is_none() {
+            warn!("No GitLab webhook secret configured, GitLab webhooks are rejected");
+        }
+        if self.state.config().bitbucket_webhook_secret.is_none()
+            && self.state.config().bitbucket_allowed_ips.is_empty()
+        {
+            warn!("No Bitbucket webhook secret or IP allowlist configured, Bitbucket webhooks are rejected");
+        }
+        if self.state.config().gitea_webhook_secret.is_none() {
+            warn!("No Gitea webhook secret configured, Gitea webhooks are rejected");
+        }
+
+        self.state.validate_redis_version().await?;
+        self.state.spawn_redis_health_check();
+        self.state.spawn_agent_health_check();
+        self.state
+            .agent_events()
+            .spawn_subscriber(&self.state.config().redis_url);
+        self.state.replay_guard().spawn_cleanup();
+        self.state.webhook_dedup().spawn_cleanup();
+        scheduler::spawn_scheduler(self.state.clone());
+        self.state.mark_started();
+
+        let listener =
+            TcpListener::bind(&address).with_context(|| format!("Failed to bind {}", address))?;
+        let result = self.serve(listener).await;
+        telemetry::shutdown_tracing();
+        result
+    }
+
+    /// Serve requests on `listener` until shut down
+    ///
+    /// Serves HTTPS when a certificate and key are configured, plain HTTP
+    /// otherwise; the routes are the same either way. On shutdown no new
+    /// connections are accepted and in-flight requests are given
+    /// [`SHUTDOWN_GRACE_PERIOD`] to finish.
+    pub async fn serve(self, listener: TcpListener) -> Result<()> {
+        let tls = load_tls_config(&self.state.config()).await?;
+        let shutdown = self.state.shutdown_signal();
+
+        // Tokio needs the listener in non-blocking mode
+        listener
+            .set_nonblocking(true)
+            .context("Failed to configure listener")?;
+        let address = listener
+            .local_addr()
+            .context("Failed to get listener address")?;
+        // Connection info gives the rate limiter the client address
+        let app = routes::router(self.state).into_make_service_with_connect_info::<SocketAddr>();
+
+        let result = match tls {
+            Some(tls) => {
+                info!("raibid-server listening on https://{}", address);
+                let handle = axum_server::Handle::new();
+                let shutdown_handle = handle.clone();
+                tokio::spawn(async move {
+                    if shutdown.await.is_ok() {
+                        shutdown_handle.graceful_shutdown(Some(SHUTDOWN_GRACE_PERIOD));
+                    }
+                });
+                axum_server::from_tcp_rustls(listener, tls)
+                    .handle(handle)
+                    .serve(app)
+                    .await
+                    .context("Server error")
+            }
+            None => {
+                info!("raibid-server listening on http://{}", address);
+                let listener = tokio::net::TcpListener::from_std(listener)
+                    .context("Failed to configure listener")?;
+                axum::serve(listener, app)
+                    .with_graceful_shutdown(async move {
+                        if shutdown.await.is_err() {
+                            std::future::pending::<()>().await;
+                        }
+                    })
+                    .await
+                    .context("Server error")
+            }
+        };
+
+        info!("raibid-server stopped");
+        result
+    }
+}
+
+/// Load the TLS certificate and key, if configured
+async fn load_tls_config(config: &ServerConfig) -> Result<Option<RustlsConfig>> {
+    let Some((cert, key)) = config.tls_paths()? else {
+        return Ok(None);
+    };
+
+    let tls = RustlsConfig::from_pem_file(cert, key)
+        .await
+        .with_context(|| {
+            format!(
+                "Failed to load TLS certificate {} and key {}",
+                cert.display(),
+                key.display()
+            )
+        })?;
+    Ok(Some(tls))
+}
+
 /// Start the API server
-pub async fn start_server(_config: ServerConfig) -> Result<()> {
-    // Placeholder implementation
-    // Will be implemented in a future issue
-    anyhow::bail!("Server implementation pending")
+pub async fn start_server(config: ServerConfig) -> Result<()> {
+    Server::new(config)?.run().await
 }
 
 #[cfg(test)]
@@ -45,5 +560,135 @@ mod tests {
         let config = ServerConfig::default();
         assert_eq!(config.host, "127.0.0.1");
         assert_eq!(config.port, 8080);
+        assert_eq!(config.redis_url, "redis://127.0.0.1:6379");
+        assert_eq!(config.redis_pool_size, 10);
+        assert_eq!(config.redis_connect_timeout_ms, 5_000);
+        assert_eq!(config.redis_wait_timeout_ms, 2_000);
+        assert_eq!(config.queue_stream, "raibid:jobs");
+        assert_eq!(config.dedup_window_secs, 3600);
+        assert!(config.metrics_enabled);
+        assert_eq!(config.metrics_path, "/metrics");
+        assert!(config.api_keys.is_empty());
+        assert!(config.admin_keys.is_empty());
+        assert_eq!(config.config_file, None);
+        assert_eq!(config.gitlab_webhook_secret, None);
+        assert_eq!(config.gitea_webhook_secret, None);
+        assert!(!config.handle_pr_events);
+        assert_eq!(config.rate_limit_rps, None);
+        assert!(config.trusted_proxies.is_empty());
+        assert_eq!(config.max_body_size_bytes, 1_048_576);
+        assert!(config.compression_enabled);
+        assert_eq!(config.cache_ttl_ms, 2_000);
+        assert_eq!(config.tls_cert_path, None);
+        assert_eq!(config.benchmark_regression_threshold_pct, 10.0);
+        assert_eq!(config.scaled_object_name, "raibid-ci-agent-scaler");
+        assert_eq!(config.scaled_object_namespace, "raibid-ci");
+        assert_eq!(config.agent_deployment_name, "raibid-ci-agent");
+        assert_eq!(config.otel_endpoint, None);
+        assert_eq!(config.service_name, "raibid-server");
+        assert_eq!(config.bind_address(), "127.0.0.1:8080");
+    }
+
+    #[test]
+    fn test_server_config_from_env() {
+        env::set_var("RAIBID_TLS_CERT", "/etc/raibid/tls/tls.crt");
+        env::set_var("RAIBID_TLS_KEY", "/etc/raibid/tls/tls.key");
+        env::set_var(
+            "RAIBID_BITBUCKET_ALLOWED_IPS",
+            "104.192.136.0/21, 2401:1d80:1010::/64",
+        );
+        env::set_var(
+            "RAIBID_CORS_ORIGINS",
+            "https://ci.example.com, http://localhost:3000",
+        );
+        env::set_var("RAIBID_RATE_LIMIT_RPS", "20");
+        env::set_var("RAIBID_RATE_LIMIT_BURST", "50");
+        env::set_var("RAIBID_TRUSTED_PROXIES", "10.42.0.0/16");
+        env::set_var("RAIBID_METRICS_ENABLED", "false");
+        env::set_var("RAIBID_METRICS_PATH", "/internal/metrics");
+        let config = ServerConfig::from_env().unwrap();
+        env::remove_var("RAIBID_TLS_CERT");
+        env::remove_var("RAIBID_TLS_KEY");
+        env::remove_var("RAIBID_CORS_ORIGINS");
+        env::remove_var("RAIBID_BITBUCKET_ALLOWED_IPS");
+        env::remove_var("RAIBID_RATE_LIMIT_RPS");
+        env::remove_var("RAIBID_RATE_LIMIT_BURST");
+        env::remove_var("RAIBID_TRUSTED_PROXIES");
+        env::remove_var("RAIBID_METRICS_ENABLED");
+        env::remove_var("RAIBID_METRICS_PATH");
+
+        assert_eq!(config.rate_limit_rps, Some(20));
+        assert_eq!(config.rate_limit_burst, Some(50));
+        assert_eq!(
+            config.trusted_proxies,
+            vec!["10.42.0.0/16".parse().unwrap()]
+        );
+        assert!(!config.metrics_enabled);
+        assert_eq!(config.metrics_path, "/internal/metrics");
+
+        assert_eq!(config.bitbucket_allowed_ips.len(), 2);
+        assert!(config.bitbucket_allowed_ips[0]
+            .contains(&"104.192.137.1".parse::<std::net::IpAddr>().unwrap()));
+
+        assert_eq!(
+            config.cors.as_ref().unwrap().allowed_origins,
+            vec!["https://ci.example.com", "http://localhost:3000"]
+        );
+
+        assert_eq!(
+            config.tls_cert_path,
+            Some(PathBuf::from("/etc/raibid/tls/tls.crt"))
+        );
+        assert_eq!(
+            config.tls_key_path,
+            Some(PathBuf::from("/etc/raibid/tls/tls.key"))
+        );
+        assert!(config.tls_paths().unwrap().is_some());
+    }
+
+    #[test]
+    fn test_tls_paths_need_cert_and_key() {
+        assert!(ServerConfig::default().tls_paths().unwrap().is_none());
+
+        let config = ServerConfig {
+            tls_cert_path: Some(PathBuf::from("tls.crt")),
+            ..Default::default()
+        };
+        assert!(config.tls_paths().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_serve_https() {
+        let dir = tempfile::tempdir().unwrap();
+        let cert = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+        let cert_pem = cert.serialize_pem().unwrap();
+        let cert_path = dir.path().join("tls.crt");
+        let key_path = dir.path().join("tls.key");
+        std::fs::write(&cert_path, &cert_pem).unwrap();
+        std::fs::write(&key_path, cert.serialize_private_key_pem()).unwrap();
+
+        let server = Server::new(ServerConfig {
+            metrics_enabled: false,
+            tls_cert_path: Some(cert_path),
+            tls_key_path: Some(key_path),
+            ..Default::default()
+        })
+        .unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let task = tokio::spawn(server.serve(listener));
+
+        let client = reqwest::Client::builder()
+            .add_root_certificate(reqwest::Certificate::from_pem(cert_pem.as_bytes()).unwrap())
+            .build()
+            .unwrap();
+        let response = client
+            .get(format!("https://localhost:{}/health", port))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+        task.abort();
     }
 }
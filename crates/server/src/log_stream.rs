@@ -0,0 +1,493 @@
+//! Live job log fan-out
+//!
+//! Build output is appended by agents to the Redis stream
+//! `raibid:logs:<job_id>`. Rather than have every WebSocket client poll Redis,
+//! [`LogMultiplexer`] runs a single reader task per watched job and broadcasts
+//! entries to all of that job's subscribers. The reader stops once the job
+//! reaches a terminal status or its last subscriber disconnects.
+//!
+//! Each job channel keeps a bounded backlog of the entries read so far, so a
+//! client joining mid-build still sees earlier output.
+//!
+//! [`prune_logs`] deletes the streams of old jobs, for
+//! `POST /admin/prune-logs`.
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use deadpool_redis::Pool;
+use redis::streams::{StreamReadOptions, StreamReadReply};
+use redis::AsyncCommands;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tracing::{debug, warn};
+
+use raibid_common::jobs::{
+    job_key, log_stream_job_id, log_stream_key, JobLogEntry, JobStatus, JOB_FIELD_CREATED_AT,
+    JOB_FIELD_STATUS, LOG_STREAM_PREFIX,
+};
+
+/// Maximum entries kept per job for late subscribers
+const MAX_BACKLOG: usize = 1000;
+
+/// Capacity of each job's broadcast channel
+const CHANNEL_CAPACITY: usize = 256;
+
+/// How long a reader blocks waiting for new entries before re-checking status
+const READ_BLOCK: Duration = Duration::from_secs(1);
+
+/// Delay before retrying after a failed read
+const RETRY_DELAY: Duration = Duration::from_secs(2);
+
+/// Stream ID to start reading a log stream from
+const STREAM_START: &str = "0-0";
+
+/// Keys requested per `SCAN` while pruning log streams
+const PRUNE_SCAN_COUNT: usize = 100;
+
+/// Where job log entries and status come from
+#[async_trait]
+pub trait LogSource: Send + Sync + 'static {
+    /// Read entries after `last_id`, waiting up to `block` if there are none
+    ///
+    /// Returns `(stream_id, entry)` pairs in stream order.
+    async fn read_logs(
+        &self,
+        job_id: &str,
+        last_id: &str,
+        block: Option<Duration>,
+    ) -> Result<Vec<(String, JobLogEntry)>>;
+
+    /// Current job status, `None` if the job is unknown
+    async fn job_status(&self, job_id: &str) -> Result<Option<JobStatus>>;
+}
+
+/// Reads job logs from Redis streams and job status from the job hash
+pub struct RedisLogSource {
+    pool: Pool,
+}
+
+impl RedisLogSource {
+    /// Create a log source using connections from `pool`
+    pub fn new(pool: Pool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl LogSource for RedisLogSource {
+    async fn read_logs(
+        &self,
+        job_id: &str,
+        last_id: &str,
+        block: Option<Duration>,
+    ) -> Result<Vec<(String, JobLogEntry)>> {
+        let mut conn = self.pool.get().await.context("Redis unavailable")?;
+
+        let mut options = StreamReadOptions::default().count(CHANNEL_CAPACITY);
+        if let Some(block) = block {
+            options = options.block(block.as_millis() as usize);
+        }
+
+        let reply: Option<StreamReadReply> = conn
+            .xread_options(&[log_stream_key(job_id)], &[last_id], &options)
+            .await
+            .context("Failed to read job log stream")?;
+
+        let mut entries = Vec::new();
+        for stream_id in reply.into_iter().flat_map(|r| r.keys).flat_map(|k| k.ids) {
+            let fields = stream_id
+                .map
+                .iter()
+                .map(|(k, v)| Ok((k.clone(), redis::from_redis_value(v)?)))
+                .collect::<redis::RedisResult<HashMap<String, String>>>();
+
+            match fields
+                .map_err(anyhow::Error::from)
+                .and_then(|f| JobLogEntry::from_stream_fields(&f))
+            {
+                Ok(entry) => entries.push((stream_id.id, entry)),
+                Err(e) => warn!(
+                    "Skipping malformed log entry {} for job {}: {:#}",
+                    stream_id.id, job_id, e
+                ),
+            }
+        }
+
+        Ok(entries)
+    }
+
+    async fn job_status(&self, job_id: &str) -> Result<Option<JobStatus>> {
+        let mut conn = self.pool.get().await.context("Redis unavailable")?;
+
+        let status: Option<String> = conn
+            .hget(job_key(job_id), JOB_FIELD_STATUS)
+            .await
+            .context("Failed to read job status")?;
+
+        status.map(|s| s.parse()).transpose()
+    }
+}
+
+/// Event delivered to log subscribers
+#[derive(Debug, Clone, PartialEq)]
+pub enum LogEvent {
+    /// A new line of build output
+    Entry(JobLogEntry),
+    /// The job reached a terminal status; no more entries will follow
+    Finished(JobStatus),
+}
+
+/// A subscription to one job's logs
+pub struct LogSubscription {
+    /// Entries read before the subscription started
+    pub backlog: Vec<JobLogEntry>,
+    /// Live events
+    pub events: broadcast::Receiver<LogEvent>,
+}
+
+struct JobChannel {
+    sender: broadcast::Sender<LogEvent>,
+    backlog: VecDeque<JobLogEntry>,
+}
+
+/// Shares one log reader per job between any number of subscribers
+pub struct LogMultiplexer {
+    source: Arc<dyn LogSource>,
+    channels: Mutex<HashMap<String, JobChannel>>,
+}
+
+impl LogMultiplexer {
+    /// Create a multiplexer reading from `source`
+    pub fn new(source: Arc<dyn LogSource>) -> Self {
+        Self {
+            source,
+            channels: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Subscribe to a job's logs, starting its reader if it isn't running
+    pub fn subscribe(self: &Arc<Self>, job_id: &str) -> LogSubscription {
+        let mut channels = self.channels.lock().unwrap();
+
+        if let Some(channel) = channels.get(job_id) {
+            return LogSubscription {
+                backlog: channel.backlog.iter().cloned().collect(),
+                events: channel.sender.subscribe(),
+            };
+        }
+
+        let (sender, events) = broadcast::channel(CHANNEL_CAPACITY);
+        channels.insert(
+            job_id.to_string(),
+            JobChannel {
+                sender,
+                backlog: VecDeque::new(),
+            },
+        );
+
+        let multiplexer = Arc::clone(self);
+        let job_id = job_id.to_string();
+        tokio::spawn(async move { multiplexer.pump(job_id).await });
+
+        LogSubscription {
+            backlog: Vec::new(),
+            events,
+        }
+    }
+
+    /// Every entry of a job's log written so far
+    pub async fn snapshot(&self, job_id: &str) -> Result<Vec<JobLogEntry>> {
+        let mut last_id = STREAM_START.to_string();
+        let mut entries = Vec::new();
+        loop {
+            let read = self.source.read_logs(job_id, &last_id, None).await?;
+            let Some((id, _)) = read.last() else {
+                return Ok(entries);
+            };
+            last_id = id.clone();
+            entries.extend(read.into_iter().map(|(_, entry)| entry));
+        }
+    }
+
+    /// Number of jobs with an active reader
+    pub fn active_jobs(&self) -> usize {
+        self.channels.lock().unwrap().len()
+    }
+
+    /// Read a job's log stream and broadcast entries until it finishes
+    async fn pump(&self, job_id: String) {
+        debug!("Starting log reader for job {}", job_id);
+        let mut last_id = STREAM_START.to_string();
+
+        loop {
+            let read = self
+                .source
+                .read_logs(&job_id, &last_id, Some(READ_BLOCK))
+                .await;
+            match read {
+                Ok(entries) => self.publish(&job_id, entries, &mut last_id),
+                Err(e) => {
+                    warn!("Failed to read logs for job {}: {:#}", job_id, e);
+                    tokio::time::sleep(RETRY_DELAY).await;
+                }
+            }
+
+            match self.source.job_status(&job_id).await {
+                Ok(Some(status)) if status.is_terminal() => {
+                    // Pick up anything written between the last read and the
+                    // status change
+                    let remaining = self.source.read_logs(&job_id, &last_id, None).await;
+                    if let Ok(entries) = remaining {
+                        self.publish(&job_id, entries, &mut last_id);
+                    }
+                    self.finish(&job_id, LogEvent::Finished(status));
+                    break;
+                }
+                Ok(_) => {}
+                Err(e) => warn!("Failed to read status for job {}: {:#}", job_id, e),
+            }
+
+            if self.close_if_unwatched(&job_id) {
+                break;
+            }
+        }
+
+        debug!("Stopped log reader for job {}", job_id);
+    }
+
+    /// Record entries in the backlog and send them to subscribers
+    fn publish(&self, job_id: &str, entries: Vec<(String, JobLogEntry)>, last_id: &mut String) {
+        let mut channels = self.channels.lock().unwrap();
+        let Some(channel) = channels.get_mut(job_id) else {
+            return;
+        };
+
+        for (id, entry) in entries {
+            *last_id = id;
+            if channel.backlog.len() == MAX_BACKLOG {
+                channel.backlog.pop_front();
+            }
+            channel.backlog.push_back(entry.clone());
+            // No receivers is fine; close_if_unwatched handles it
+            let _ = channel.sender.send(LogEvent::Entry(entry));
+        }
+    }
+
+    /// Send a final event and drop the job's channel
+    fn finish(&self, job_id: &str, event: LogEvent) {
+        if let Some(channel) = self.channels.lock().unwrap().remove(job_id) {
+            let _ = channel.sender.send(event);
+        }
+    }
+
+    /// Drop the job's channel if nobody is subscribed; returns whether it was dropped
+    fn close_if_unwatched(&self, job_id: &str) -> bool {
+        let mut channels = self.channels.lock().unwrap();
+        match channels.get(job_id) {
+            Some(channel) if channel.sender.receiver_count() > 0 => false,
+            _ => {
+                channels.remove(job_id);
+                true
+            }
+        }
+    }
+}
+
+/// Delete the log streams of jobs queued before `older_than`, returning how
+/// many were deleted
+///
+/// A job's age is the `created_at` field of its job hash; streams of jobs
+/// without one are kept.
+pub async fn prune_logs<C>(conn: &mut C, older_than: DateTime<Utc>) -> redis::RedisResult<u64>
+where
+    C: redis::aio::ConnectionLike + Send,
+{
+    let pattern = format!("{}*", LOG_STREAM_PREFIX);
+    let mut cursor = 0u64;
+    let mut pruned = 0;
+
+    loop {
+        let (next, keys): (u64, Vec<String>) = redis::cmd("SCAN")
+            .arg(cursor)
+            .arg("MATCH")
+            .arg(&pattern)
+            .arg("COUNT")
+            .arg(PRUNE_SCAN_COUNT)
+            .query_async(conn)
+            .await?;
+
+        for key in keys {
+            let Some(job_id) = log_stream_job_id(&key) else {
+                continue;
+            };
+            let created_at: Option<String> = redis::cmd("HGET")
+                .arg(job_key(job_id))
+                .arg(JOB_FIELD_CREATED_AT)
+                .query_async(conn)
+                .await?;
+            let expired = created_at
+                .and_then(|t| DateTime::parse_from_rfc3339(&t).ok())
+                .is_some_and(|t| t < older_than);
+
+            if expired {
+                redis::cmd("DEL")
+                    .arg(&key)
+                    .query_async::<_, ()>(conn)
+                    .await?;
+                debug!("Pruned log stream of job {}", job_id);
+                pruned += 1;
+            }
+        }
+
+        if next == 0 {
+            return Ok(pruned);
+        }
+        cursor = next;
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    /// In-memory log source standing in for Redis
+    #[derive(Default)]
+    pub(crate) struct MockLogSource {
+        logs: Mutex<HashMap<String, Vec<JobLogEntry>>>,
+        statuses: Mutex<HashMap<String, JobStatus>>,
+    }
+
+    impl MockLogSource {
+        pub(crate) fn push(&self, job_id: &str, message: &str) {
+            self.logs
+                .lock()
+                .unwrap()
+                .entry(job_id.to_string())
+                .or_default()
+                .push(JobLogEntry {
+                    timestamp: Utc::now(),
+                    message: message.to_string(),
+                });
+        }
+
+        pub(crate) fn set_status(&self, job_id: &str, status: JobStatus) {
+            self.statuses
+                .lock()
+                .unwrap()
+                .insert(job_id.to_string(), status);
+        }
+    }
+
+    #[async_trait]
+    impl LogSource for MockLogSource {
+        async fn read_logs(
+            &self,
+            job_id: &str,
+            last_id: &str,
+            block: Option<Duration>,
+        ) -> Result<Vec<(String, JobLogEntry)>> {
+            // IDs are "0-<index + 1>"; "0-0" means from the start
+            let start: usize = last_id.trim_start_matches("0-").parse()?;
+            let entries: Vec<(String, JobLogEntry)> = self
+                .logs
+                .lock()
+                .unwrap()
+                .get(job_id)
+                .map(|logs| {
+                    logs.iter()
+                        .enumerate()
+                        .skip(start)
+                        .map(|(i, e)| (format!("0-{}", i + 1), e.clone()))
+                        .collect()
+                })
+                .unwrap_or_default();
+
+            if entries.is_empty() && block.is_some() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+            Ok(entries)
+        }
+
+        async fn job_status(&self, job_id: &str) -> Result<Option<JobStatus>> {
+            Ok(self.statuses.lock().unwrap().get(job_id).copied())
+        }
+    }
+
+    async fn next_event(events: &mut broadcast::Receiver<LogEvent>) -> LogEvent {
+        tokio::time::timeout(Duration::from_secs(5), events.recv())
+            .await
+            .expect("timed out waiting for log event")
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_subscribers_share_one_reader() {
+        let source = Arc::new(MockLogSource::default());
+        source.push("job-1", "Compiling");
+        let multiplexer = Arc::new(LogMultiplexer::new(source.clone()));
+
+        let mut first = multiplexer.subscribe("job-1");
+        assert!(first.backlog.is_empty());
+        assert!(
+            matches!(next_event(&mut first.events).await, LogEvent::Entry(e) if e.message == "Compiling")
+        );
+
+        // A late subscriber gets earlier output from the backlog
+        let mut second = multiplexer.subscribe("job-1");
+        assert_eq!(second.backlog.len(), 1);
+        assert_eq!(multiplexer.active_jobs(), 1);
+
+        source.push("job-1", "Finished");
+        source.set_status("job-1", JobStatus::Success);
+
+        for subscription in [&mut first, &mut second] {
+            assert!(
+                matches!(next_event(&mut subscription.events).await, LogEvent::Entry(e) if e.message == "Finished")
+            );
+            assert_eq!(
+                next_event(&mut subscription.events).await,
+                LogEvent::Finished(JobStatus::Success)
+            );
+        }
+        assert_eq!(multiplexer.active_jobs(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_snapshot() {
+        let source = Arc::new(MockLogSource::default());
+        source.push("job-3", "Compiling");
+        source.push("job-3", "Finished");
+        let multiplexer = Arc::new(LogMultiplexer::new(source));
+
+        let messages: Vec<String> = multiplexer
+            .snapshot("job-3")
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.message)
+            .collect();
+        assert_eq!(messages, vec!["Compiling", "Finished"]);
+        assert!(multiplexer.snapshot("job-4").await.unwrap().is_empty());
+        assert_eq!(multiplexer.active_jobs(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_reader_stops_without_subscribers() {
+        let source = Arc::new(MockLogSource::default());
+        let multiplexer = Arc::new(LogMultiplexer::new(source));
+
+        drop(multiplexer.subscribe("job-2"));
+
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while multiplexer.active_jobs() > 0 {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("reader did not stop");
+    }
+}
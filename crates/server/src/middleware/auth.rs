@@ -0,0 +1,355 @@
+//! API key authentication
+//!
+//! [`RequireApiKey`] rejects requests whose [`API_KEY_HEADER`] does not match
+//! one of the configured keys with `401 Unauthorized`. Keys are compared by
+//! SHA-256 hash; see [`raibid_common::auth`]. With no keys configured the
+//! layer lets every request through. [`RequireApiKey::or_query_param`] also
+//! accepts the key in a query parameter, for WebSocket clients that cannot
+//! set headers on the upgrade request.
+//!
+//! [`RequireApiKey::admin`] guards the admin routes the same way with the
+//! [`ADMIN_KEY_HEADER`], but rejects every request while no admin keys are
+//! configured.
+
+use axum::extract::{Query, Request};
+use axum::response::{IntoResponse, Response};
+use futures::future::{ready, Either, Ready};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, OnceLock, RwLock};
+use std::task::{Context, Poll};
+use tower::{Layer, Service};
+
+use crate::error::ApiError;
+use raibid_common::auth::{configured_key_hash, hash_api_key, ADMIN_KEY_HEADER, API_KEY_HEADER};
+
+/// Query parameter accepted in place of the [`API_KEY_HEADER`] on WebSocket
+/// routes
+pub const API_KEY_QUERY_PARAM: &str = "api_key";
+
+/// API keys accepted by the server
+///
+/// The keys can be replaced while the server runs, see
+/// [`AppState::reload_config`](crate::AppState::reload_config).
+pub struct ApiKeys {
+    keys: RwLock<Arc<KeySet>>,
+}
+
+struct KeySet {
+    configured: Vec<String>,
+    /// Hashes of `configured`, computed on first use
+    hashes: OnceLock<HashSet<String>>,
+}
+
+impl KeySet {
+    fn new(configured: Vec<String>) -> Arc<Self> {
+        Arc::new(Self {
+            configured,
+            hashes: OnceLock::new(),
+        })
+    }
+}
+
+impl ApiKeys {
+    /// Accept the given keys, each plain text or `sha256:<hex>`
+    pub fn new(configured: Vec<String>) -> Self {
+        Self {
+            keys: RwLock::new(KeySet::new(configured)),
+        }
+    }
+
+    /// Accept `configured` instead of the current keys
+    pub fn replace(&self, configured: Vec<String>) {
+        *self.keys.write().unwrap() = KeySet::new(configured);
+    }
+
+    /// Number of configured keys
+    pub fn len(&self) -> usize {
+        self.keys.read().unwrap().configured.len()
+    }
+
+    /// Whether no keys are configured, disabling authentication
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Check a key presented by a client
+    pub fn verify(&self, key: &str) -> bool {
+        let keys = self.keys.read().unwrap().clone();
+        let hashes = keys.hashes.get_or_init(|| {
+            keys.configured
+                .iter()
+                .map(|entry| configured_key_hash(entry))
+                .collect()
+        });
+        hashes.contains(&hash_api_key(key))
+    }
+}
+
+impl std::fmt::Debug for ApiKeys {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ApiKeys")
+            .field("count", &self.len())
+            .finish()
+    }
+}
+
+/// Layer requiring a valid API key on every request
+#[derive(Debug, Clone)]
+pub struct RequireApiKey {
+    keys: Arc<ApiKeys>,
+    header: &'static str,
+    /// Query parameter also accepted in place of the header
+    query_param: Option<&'static str>,
+    /// Whether requests are let through while no keys are configured
+    open_when_empty: bool,
+}
+
+impl RequireApiKey {
+    /// Require one of `keys`
+    pub fn new(keys: Arc<ApiKeys>) -> Self {
+        Self {
+            keys,
+            header: API_KEY_HEADER,
+            query_param: None,
+            open_when_empty: true,
+        }
+    }
+
+    /// Require one of the admin `keys` in the [`ADMIN_KEY_HEADER`], rejecting
+    /// every request while none are configured
+    pub fn admin(keys: Arc<ApiKeys>) -> Self {
+        Self {
+            keys,
+            header: ADMIN_KEY_HEADER,
+            query_param: None,
+            open_when_empty: false,
+        }
+    }
+
+    /// Also accept the key in query parameter `name`
+    pub fn or_query_param(mut self, name: &'static str) -> Self {
+        self.query_param = Some(name);
+        self
+    }
+}
+
+impl<S> Layer<S> for RequireApiKey {
+    type Service = RequireApiKeyService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RequireApiKeyService {
+            inner,
+            keys: self.clone(),
+        }
+    }
+}
+
+/// Service produced by [`RequireApiKey`]
+#[derive(Debug, Clone)]
+pub struct RequireApiKeyService<S> {
+    inner: S,
+    keys: RequireApiKey,
+}
+
+impl<S> RequireApiKeyService<S> {
+    fn authorized(&self, request: &Request) -> bool {
+        if self.keys.keys.is_empty() {
+            return self.keys.open_when_empty;
+        }
+
+        let header = request
+            .headers()
+            .get(self.keys.header)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|key| self.keys.keys.verify(key));
+        header
+            || self
+                .keys
+                .query_param
+                .is_some_and(|name| self.query_key_valid(request, name))
+    }
+
+    /// Whether query parameter `name` holds a valid key
+    fn query_key_valid(&self, request: &Request, name: &str) -> bool {
+        Query::<HashMap<String, String>>::try_from_uri(request.uri())
+            .ok()
+            .and_then(|Query(params)| params.get(name).cloned())
+            .is_some_and(|key| self.keys.keys.verify(&key))
+    }
+}
+
+impl<S> Service<Request> for RequireApiKeyService<S>
+where
+    S: Service<Request, Response = Response>,
+{
+    type Response = Response;
+    type Error = S::Error;
+    type Future = Either<S::Future, Ready<Result<Response, S::Error>>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request) -> Self::Future {
+        if self.authorized(&request) {
+            Either::Left(self.inner.call(request))
+        } else {
+            let message = if self.keys.header == ADMIN_KEY_HEADER {
+                "Invalid or missing admin key"
+            } else {
+                "Invalid or missing API key"
+            };
+            let response = ApiError::Unauthorized(message.to_string()).into_response();
+            Either::Right(ready(Ok(response)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::http::StatusCode;
+    use axum::routing::get;
+    use axum::Router;
+    use tower::ServiceExt;
+
+    fn app(keys: &[&str]) -> Router {
+        let keys = Arc::new(ApiKeys::new(keys.iter().map(|k| k.to_string()).collect()));
+        Router::new()
+            .route("/api/ping", get(|| async { "pong" }))
+            .route_layer(RequireApiKey::new(keys))
+    }
+
+    async fn call(app: Router, key: Option<&str>) -> (StatusCode, String) {
+        let mut request = axum::http::Request::get("/api/ping");
+        if let Some(key) = key {
+            request = request.header(API_KEY_HEADER, key);
+        }
+
+        let response = app
+            .oneshot(request.body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_missing_key_rejected() {
+        let (status, body) = call(app(&["rbd_secret"]), None).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert_eq!(body, r#"{"error":"Invalid or missing API key"}"#);
+    }
+
+    #[tokio::test]
+    async fn test_wrong_key_rejected() {
+        let (status, _) = call(app(&["rbd_secret"]), Some("rbd_guess")).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_valid_key_accepted() {
+        let hashed = hash_api_key("rbd_other");
+        let app = app(&["rbd_secret", &hashed]);
+
+        assert_eq!(
+            call(app.clone(), Some("rbd_secret")).await.0,
+            StatusCode::OK
+        );
+        assert_eq!(call(app, Some("rbd_other")).await.0, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_no_keys_configured() {
+        assert_eq!(call(app(&[]), None).await.0, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_replaced_keys() {
+        let keys = Arc::new(ApiKeys::new(vec!["rbd_old".to_string()]));
+        let app = Router::new()
+            .route("/api/ping", get(|| async { "pong" }))
+            .route_layer(RequireApiKey::new(keys.clone()));
+
+        keys.replace(vec!["rbd_new".to_string()]);
+        assert_eq!(
+            call(app.clone(), Some("rbd_old")).await.0,
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(call(app, Some("rbd_new")).await.0, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_query_param_key() {
+        let keys = Arc::new(ApiKeys::new(vec!["rbd_secret".to_string()]));
+        let app = |layer: RequireApiKey| {
+            Router::new()
+                .route("/ws/agents", get(|| async { "events" }))
+                .route_layer(layer)
+        };
+        let status = |app: Router, uri: &str| {
+            let request = axum::http::Request::get(uri).body(Body::empty()).unwrap();
+            async move { app.oneshot(request).await.unwrap().status() }
+        };
+
+        let ws = app(RequireApiKey::new(keys.clone()).or_query_param(API_KEY_QUERY_PARAM));
+        assert_eq!(
+            status(ws.clone(), "/ws/agents?api_key=rbd_secret").await,
+            StatusCode::OK
+        );
+        assert_eq!(
+            status(ws.clone(), "/ws/agents?api_key=rbd_guess").await,
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(status(ws, "/ws/agents").await, StatusCode::UNAUTHORIZED);
+
+        // Only where enabled
+        let api = app(RequireApiKey::new(keys));
+        assert_eq!(
+            status(api, "/ws/agents?api_key=rbd_secret").await,
+            StatusCode::UNAUTHORIZED
+        );
+    }
+
+    #[tokio::test]
+    async fn test_admin_keys_closed_when_empty() {
+        let admin = |keys: Vec<String>| {
+            Router::new()
+                .route("/admin/state", get(|| async { "ok" }))
+                .route_layer(RequireApiKey::admin(Arc::new(ApiKeys::new(keys))))
+        };
+        let request = |key: Option<&str>| {
+            let mut request = axum::http::Request::get("/admin/state");
+            if let Some(key) = key {
+                request = request.header(ADMIN_KEY_HEADER, key);
+            }
+            request.body(Body::empty()).unwrap()
+        };
+
+        let response = admin(Vec::new()).oneshot(request(None)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let app = admin(vec!["rbd_admin".to_string()]);
+        let response = app
+            .clone()
+            .oneshot(request(Some("rbd_admin")))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        // The API key header is not accepted in its place
+        let response = app
+            .oneshot(
+                axum::http::Request::get("/admin/state")
+                    .header(API_KEY_HEADER, "rbd_admin")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+}
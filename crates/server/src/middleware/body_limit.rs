@@ -0,0 +1,29 @@
+//! Request body size limit
+//!
+//! [`body_limit`] rejects requests whose body is larger than
+//! `ServerConfig::max_body_size_bytes` with `413 Payload Too Large`, so large
+//! or malformed webhook payloads cannot exhaust memory. Bodies announcing
+//! their size in `Content-Length` are rejected before they are read; others
+//! are cut off once they pass the limit.
+//!
+//! axum's own 2 MB extractor limit is disabled, so the configured size is the
+//! only limit.
+
+use axum::extract::DefaultBodyLimit;
+use tower::layer::util::{Identity, Stack};
+use tower::ServiceBuilder;
+use tower_http::limit::RequestBodyLimitLayer;
+
+/// Body size allowed when none is configured (1 MB)
+pub const DEFAULT_MAX_BODY_SIZE_BYTES: usize = 1_048_576;
+
+/// Layer returned by [`body_limit`]
+pub type BodyLimit =
+    ServiceBuilder<Stack<DefaultBodyLimit, Stack<RequestBodyLimitLayer, Identity>>>;
+
+/// Limit request bodies to `max_bytes`
+pub fn body_limit(max_bytes: usize) -> BodyLimit {
+    ServiceBuilder::new()
+        .layer(RequestBodyLimitLayer::new(max_bytes))
+        .layer(DefaultBodyLimit::disable())
+}
@@ -0,0 +1,41 @@
+//! Response compression
+//!
+//! [`compression`] gzips JSON responses for clients sending
+//! `Accept-Encoding: gzip`, which shrinks job and agent lists with hundreds of
+//! entries considerably. Other content types and bodies too small to benefit
+//! are sent as is.
+
+use axum::body::HttpBody;
+use axum::http::header::CONTENT_TYPE;
+use axum::http::Response;
+use tower_http::compression::predicate::{And, Predicate, SizeAbove};
+use tower_http::compression::CompressionLayer;
+
+/// Responses smaller than this are not compressed, in bytes
+pub const MIN_COMPRESSED_SIZE_BYTES: u16 = 32;
+
+/// Layer returned by [`compression`]
+pub type Compression = CompressionLayer<And<SizeAbove, JsonContent>>;
+
+/// Compress responses with an `application/json` body
+#[derive(Debug, Clone, Copy, Default)]
+pub struct JsonContent;
+
+impl Predicate for JsonContent {
+    fn should_compress<B>(&self, response: &Response<B>) -> bool
+    where
+        B: HttpBody,
+    {
+        response
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.starts_with("application/json"))
+    }
+}
+
+/// Gzip JSON responses larger than [`MIN_COMPRESSED_SIZE_BYTES`]
+pub fn compression() -> Compression {
+    CompressionLayer::new()
+        .compress_when(SizeAbove::new(MIN_COMPRESSED_SIZE_BYTES).and(JsonContent))
+}
@@ -0,0 +1,130 @@
+//! Cross-origin requests
+//!
+//! Browser-based UIs served from another origin can only call the API if it
+//! answers with CORS headers. [`CorsConfig::layer`] builds the
+//! [`CorsLayer`] installed around every route when `ServerConfig::cors` is
+//! set; it also answers pre-flight `OPTIONS` requests before authentication
+//! runs.
+
+use anyhow::{bail, Context, Result};
+use axum::http::header::CONTENT_TYPE;
+use axum::http::{HeaderName, HeaderValue, Method};
+use std::time::Duration;
+use tower_http::cors::{AllowOrigin, Any, CorsLayer};
+
+use raibid_common::auth::API_KEY_HEADER;
+
+/// Origin entry allowing requests from any origin
+pub const ANY_ORIGIN: &str = "*";
+
+/// CORS settings
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CorsConfig {
+    /// Origins allowed to call the API (`https://ci.example.com`), or `*`
+    pub allowed_origins: Vec<String>,
+    /// HTTP methods allowed in cross-origin requests
+    pub allowed_methods: Vec<String>,
+    /// Whether browsers may send cookies and credentials
+    ///
+    /// Not allowed together with the `*` origin.
+    pub allow_credentials: bool,
+    /// How long browsers may cache pre-flight responses
+    pub max_age_secs: u64,
+}
+
+impl Default for CorsConfig {
+    fn default() -> Self {
+        Self {
+            allowed_origins: Vec::new(),
+            allowed_methods: vec!["GET".to_string(), "POST".to_string()],
+            allow_credentials: false,
+            max_age_secs: 3600,
+        }
+    }
+}
+
+impl CorsConfig {
+    /// Default settings allowing `origins`
+    pub fn with_origins(origins: Vec<String>) -> Self {
+        Self {
+            allowed_origins: origins,
+            ..Default::default()
+        }
+    }
+
+    /// Build the layer, failing on invalid origins or methods
+    pub fn layer(&self) -> Result<CorsLayer> {
+        let any_origin = self.allowed_origins.iter().any(|o| o == ANY_ORIGIN);
+        if any_origin && self.allow_credentials {
+            bail!("CORS credentials cannot be allowed for any origin ('*')");
+        }
+
+        let origin = if any_origin {
+            AllowOrigin::from(Any)
+        } else {
+            let origins = self
+                .allowed_origins
+                .iter()
+                .map(|origin| {
+                    HeaderValue::from_str(origin)
+                        .with_context(|| format!("Invalid CORS origin '{}'", origin))
+                })
+                .collect::<Result<Vec<_>>>()?;
+            AllowOrigin::list(origins)
+        };
+
+        let methods = self
+            .allowed_methods
+            .iter()
+            .map(|method| {
+                Method::from_bytes(method.trim().to_uppercase().as_bytes())
+                    .with_context(|| format!("Invalid CORS method '{}'", method))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let api_key_header =
+            HeaderName::from_bytes(API_KEY_HEADER.as_bytes()).context("Invalid API key header")?;
+
+        Ok(CorsLayer::new()
+            .allow_origin(origin)
+            .allow_methods(methods)
+            .allow_headers([CONTENT_TYPE, api_key_header])
+            .allow_credentials(self.allow_credentials)
+            .max_age(Duration::from_secs(self.max_age_secs)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_layer_validation() {
+        assert!(
+            CorsConfig::with_origins(vec!["https://ci.example.com".to_string()])
+                .layer()
+                .is_ok()
+        );
+        assert!(CorsConfig::with_origins(vec![ANY_ORIGIN.to_string()])
+            .layer()
+            .is_ok());
+
+        let config = CorsConfig {
+            allow_credentials: true,
+            ..CorsConfig::with_origins(vec![ANY_ORIGIN.to_string()])
+        };
+        assert!(config.layer().is_err());
+
+        let config = CorsConfig {
+            allowed_methods: vec!["GET".to_string(), "NOT A METHOD".to_string()],
+            ..Default::default()
+        };
+        assert!(config.layer().is_err());
+
+        assert!(
+            CorsConfig::with_origins(vec!["https://bad\norigin".to_string()])
+                .layer()
+                .is_err()
+        );
+    }
+}
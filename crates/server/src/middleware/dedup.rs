@@ -0,0 +1,395 @@
+//! Webhook delivery deduplication
+//!
+//! GitHub and Gitea deliver a webhook again when the server does not answer
+//! within their 10 second timeout, so slow Redis writes could queue the same
+//! build twice. [`dedup`] answers a delivery whose ID (`X-GitHub-Delivery` or
+//! `X-Gitea-Delivery`) was handled successfully less than [`DUPLICATE_WINDOW`]
+//! after its first receipt with `200 OK` without running the handler, which
+//! stops the retries. Unlike the replay check of the webhook handlers, a
+//! redelivery is not an error.
+//!
+//! A retry that arrives while the first attempt is still being handled is
+//! answered with `503 Service Unavailable`, so the provider delivers it again
+//! if that attempt fails. A delivery whose handler fails, or never finishes,
+//! is forgotten so the provider's retry is processed. Delivery IDs are
+//! evicted [`DELIVERY_TTL`] after first receipt.
+
+use axum::extract::{Request, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use dashmap::mapref::entry::Entry;
+use dashmap::DashMap;
+use serde_json::json;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::task::JoinHandle;
+use tracing::debug;
+
+use crate::error::ApiError;
+
+/// Header with the ID of a GitHub delivery
+pub const GITHUB_DELIVERY_HEADER: &str = "X-GitHub-Delivery";
+
+/// Header with the ID of a Gitea delivery
+pub const GITEA_DELIVERY_HEADER: &str = "X-Gitea-Delivery";
+
+/// How long after first receipt a delivery is answered as a duplicate
+pub const DUPLICATE_WINDOW: Duration = Duration::from_secs(60);
+
+/// How long delivery IDs are remembered
+pub const DELIVERY_TTL: Duration = Duration::from_secs(10 * 60);
+
+/// Interval between evictions of expired delivery IDs
+pub const DEDUP_CLEANUP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// What to do with a webhook delivery
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeliveryStatus {
+    /// Not seen before, or no longer remembered: handle it
+    New,
+    /// An earlier attempt is still being handled
+    InFlight,
+    /// Already handled successfully
+    Duplicate,
+}
+
+/// A remembered webhook delivery
+#[derive(Debug, Clone, Copy)]
+struct Delivery {
+    /// When the delivery was first received
+    received: Instant,
+    /// Whether its handler succeeded
+    handled: bool,
+}
+
+/// Webhook delivery IDs received within [`DELIVERY_TTL`]
+#[derive(Debug, Default)]
+pub struct WebhookDedup {
+    deliveries: DashMap<String, Delivery>,
+}
+
+impl WebhookDedup {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Status of delivery `id` received at `now`
+    ///
+    /// A [`DeliveryStatus::New`] delivery is recorded as in flight until it
+    /// is [completed](Self::complete) or [forgotten](Self::forget).
+    pub fn begin(&self, id: &str, now: Instant) -> DeliveryStatus {
+        match self.deliveries.entry(id.to_string()) {
+            Entry::Occupied(entry) if !entry.get().handled => DeliveryStatus::InFlight,
+            Entry::Occupied(entry)
+                if now.saturating_duration_since(entry.get().received) < DUPLICATE_WINDOW =>
+            {
+                DeliveryStatus::Duplicate
+            }
+            entry => {
+                entry.insert(Delivery {
+                    received: now,
+                    handled: false,
+                });
+                DeliveryStatus::New
+            }
+        }
+    }
+
+    /// Record that delivery `id` was handled successfully
+    pub fn complete(&self, id: &str) {
+        if let Some(mut delivery) = self.deliveries.get_mut(id) {
+            delivery.handled = true;
+        }
+    }
+
+    /// Forget delivery `id` so a retry of it is processed
+    pub fn forget(&self, id: &str) {
+        self.deliveries.remove(id);
+    }
+
+    /// Drop delivery IDs first received more than [`DELIVERY_TTL`] before `now`
+    pub fn evict_expired(&self, now: Instant) {
+        let before = self.deliveries.len();
+        self.deliveries
+            .retain(|_, delivery| now.saturating_duration_since(delivery.received) < DELIVERY_TTL);
+
+        let evicted = before.saturating_sub(self.deliveries.len());
+        if evicted > 0 {
+            debug!("Evicted {} expired webhook delivery IDs", evicted);
+        }
+    }
+
+    /// Number of remembered delivery IDs
+    pub fn len(&self) -> usize {
+        self.deliveries.len()
+    }
+
+    /// Whether no delivery IDs are remembered
+    pub fn is_empty(&self) -> bool {
+        self.deliveries.is_empty()
+    }
+
+    /// Spawn the background task that evicts expired delivery IDs
+    pub fn spawn_cleanup(self: &Arc<Self>) -> JoinHandle<()> {
+        let dedup = Arc::clone(self);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(DEDUP_CLEANUP_INTERVAL);
+            loop {
+                interval.tick().await;
+                dedup.evict_expired(Instant::now());
+            }
+        })
+    }
+}
+
+/// Delivery ID of a GitHub or Gitea webhook request
+fn delivery_id(headers: &HeaderMap) -> Option<String> {
+    [GITHUB_DELIVERY_HEADER, GITEA_DELIVERY_HEADER]
+        .into_iter()
+        .filter_map(|header| headers.get(header))
+        .filter_map(|value| value.to_str().ok())
+        .map(str::trim)
+        .find(|id| !id.is_empty())
+        .map(String::from)
+}
+
+/// Answer repeated webhook deliveries without running the handler
+///
+/// Requests without a delivery ID are passed through.
+pub async fn dedup(
+    State(dedup): State<Arc<WebhookDedup>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Some(id) = delivery_id(request.headers()) else {
+        return next.run(request).await;
+    };
+
+    match dedup.begin(&id, Instant::now()) {
+        DeliveryStatus::New => {}
+        DeliveryStatus::InFlight => {
+            debug!("Webhook delivery {} is already being handled", id);
+            return ApiError::Unavailable("delivery is already being processed".to_string())
+                .into_response();
+        }
+        DeliveryStatus::Duplicate => {
+            debug!("Ignoring duplicate webhook delivery {}", id);
+            return (
+                StatusCode::OK,
+                Json(json!({ "message": "duplicate request ignored" })),
+            )
+                .into_response();
+        }
+    }
+
+    // Forgets the delivery if the handler fails or is cancelled
+    let attempt = InFlight {
+        dedup: &dedup,
+        id: &id,
+        handled: false,
+    };
+    let response = next.run(request).await;
+    if response.status().is_success() {
+        attempt.complete();
+    }
+    response
+}
+
+/// A delivery being handled, forgotten when dropped before completion
+struct InFlight<'a> {
+    dedup: &'a WebhookDedup,
+    id: &'a str,
+    handled: bool,
+}
+
+impl InFlight<'_> {
+    fn complete(mut self) {
+        self.dedup.complete(self.id);
+        self.handled = true;
+    }
+}
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        if !self.handled {
+            self.dedup.forget(self.id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::routing::post;
+    use axum::{middleware, Router};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tower::ServiceExt;
+
+    fn app(
+        deliveries: &Arc<WebhookDedup>,
+        handled: &Arc<AtomicUsize>,
+        status: StatusCode,
+    ) -> Router {
+        let handled = Arc::clone(handled);
+        Router::new()
+            .route(
+                "/webhooks/gitea",
+                post(move || async move {
+                    handled.fetch_add(1, Ordering::SeqCst);
+                    (status, "queued")
+                }),
+            )
+            .route_layer(middleware::from_fn_with_state(
+                Arc::clone(deliveries),
+                dedup,
+            ))
+    }
+
+    async fn deliver(app: &Router, header: &str, id: &str) -> (StatusCode, String) {
+        let request = axum::http::Request::post("/webhooks/gitea")
+            .header(header, id)
+            .body(Body::empty())
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_delivery_ignored() {
+        let dedup = Arc::new(WebhookDedup::new());
+        let handled = Arc::new(AtomicUsize::new(0));
+        let app = app(&dedup, &handled, StatusCode::OK);
+
+        let id = "7c9e6679-7425-40de-944b-e07fc1f90ae7";
+        assert_eq!(
+            deliver(&app, GITEA_DELIVERY_HEADER, id).await,
+            (StatusCode::OK, "queued".to_string())
+        );
+        let (status, body) = deliver(&app, GITEA_DELIVERY_HEADER, id).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, r#"{"message":"duplicate request ignored"}"#);
+        assert_eq!(handled.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_distinct_deliveries_processed() {
+        let dedup = Arc::new(WebhookDedup::new());
+        let handled = Arc::new(AtomicUsize::new(0));
+        let app = app(&dedup, &handled, StatusCode::OK);
+
+        deliver(&app, GITHUB_DELIVERY_HEADER, "delivery-1").await;
+        let (_, body) = deliver(&app, GITHUB_DELIVERY_HEADER, "delivery-2").await;
+        assert_eq!(body, "queued");
+        assert_eq!(handled.load(Ordering::SeqCst), 2);
+        assert_eq!(dedup.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_failed_delivery_can_be_retried() {
+        let dedup = Arc::new(WebhookDedup::new());
+        let handled = Arc::new(AtomicUsize::new(0));
+        let app = app(&dedup, &handled, StatusCode::SERVICE_UNAVAILABLE);
+
+        deliver(&app, GITEA_DELIVERY_HEADER, "delivery-1").await;
+        let (status, _) = deliver(&app, GITEA_DELIVERY_HEADER, "delivery-1").await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(handled.load(Ordering::SeqCst), 2);
+        assert!(dedup.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_retry_during_first_attempt_rejected() {
+        let deliveries = Arc::new(WebhookDedup::new());
+        let (entered_tx, entered_rx) = tokio::sync::oneshot::channel();
+        let (release_tx, release_rx) = tokio::sync::oneshot::channel::<()>();
+        let gate = Arc::new(std::sync::Mutex::new(Some((entered_tx, release_rx))));
+        let handled = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&handled);
+        let app = Router::new()
+            .route(
+                "/webhooks/gitea",
+                post(move || async move {
+                    counter.fetch_add(1, Ordering::SeqCst);
+                    let gate = gate.lock().unwrap().take();
+                    if let Some((entered, release)) = gate {
+                        let _ = entered.send(());
+                        let _ = release.await;
+                    }
+                    "queued"
+                }),
+            )
+            .route_layer(middleware::from_fn_with_state(
+                Arc::clone(&deliveries),
+                dedup,
+            ));
+
+        let first = tokio::spawn({
+            let app = app.clone();
+            async move { deliver(&app, GITEA_DELIVERY_HEADER, "delivery-1").await }
+        });
+        entered_rx.await.unwrap();
+
+        let (status, _) = deliver(&app, GITEA_DELIVERY_HEADER, "delivery-1").await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+
+        release_tx.send(()).unwrap();
+        assert_eq!(first.await.unwrap().0, StatusCode::OK);
+        let (status, body) = deliver(&app, GITEA_DELIVERY_HEADER, "delivery-1").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, r#"{"message":"duplicate request ignored"}"#);
+        assert_eq!(handled.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_duplicate_window_and_eviction() {
+        let dedup = WebhookDedup::new();
+        let start = Instant::now();
+
+        assert_eq!(dedup.begin("delivery-1", start), DeliveryStatus::New);
+        assert_eq!(
+            dedup.begin("delivery-1", start + Duration::from_secs(1)),
+            DeliveryStatus::InFlight
+        );
+        dedup.complete("delivery-1");
+        assert_eq!(
+            dedup.begin("delivery-1", start + Duration::from_secs(59)),
+            DeliveryStatus::Duplicate
+        );
+        assert_eq!(
+            dedup.begin("delivery-1", start + DUPLICATE_WINDOW),
+            DeliveryStatus::New
+        );
+
+        dedup.evict_expired(start + DUPLICATE_WINDOW + DELIVERY_TTL);
+        assert!(dedup.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_delivery_forgotten() {
+        let deliveries = Arc::new(WebhookDedup::new());
+        let app = Router::new()
+            .route("/webhooks/gitea", post(std::future::pending::<&str>))
+            .route_layer(middleware::from_fn_with_state(
+                Arc::clone(&deliveries),
+                dedup,
+            ));
+
+        let attempt = tokio::spawn({
+            let app = app.clone();
+            async move { deliver(&app, GITEA_DELIVERY_HEADER, "delivery-1").await }
+        });
+        while deliveries.is_empty() {
+            tokio::task::yield_now().await;
+        }
+        attempt.abort();
+        let _ = attempt.await;
+        assert!(deliveries.is_empty());
+    }
+}
@@ -0,0 +1,21 @@
+//! HTTP middleware
+//!
+//! Tower layers applied to groups of routes in [`crate::routes::router`].
+
+pub mod auth;
+pub mod body_limit;
+pub mod compression;
+pub mod cors;
+pub mod dedup;
+pub mod rate_limit;
+pub mod request_id;
+pub mod trace;
+
+pub use auth::{ApiKeys, RequireApiKey, API_KEY_QUERY_PARAM};
+pub use body_limit::{body_limit, DEFAULT_MAX_BODY_SIZE_BYTES};
+pub use compression::compression;
+pub use cors::CorsConfig;
+pub use dedup::{dedup, DeliveryStatus, WebhookDedup};
+pub use rate_limit::RateLimit;
+pub use request_id::{request_id, RequestId, REQUEST_ID_HEADER};
+pub use trace::{trace_context, trace_layer};
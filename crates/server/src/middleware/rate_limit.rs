@@ -0,0 +1,313 @@
+//! Per-client rate limiting
+//!
+//! [`RateLimit`] keeps a token bucket per client IP and answers requests
+//! beyond it with `429 Too Many Requests` and a `Retry-After` header. It
+//! guards the webhook routes, where many repositories pushing at once could
+//! otherwise exhaust the Redis connection pool.
+//!
+//! The client IP is the connection's peer address. Only when that is one of
+//! the configured trusted proxies is `X-Forwarded-For` read: the client is
+//! the last address in it that is not itself a trusted proxy, since anything
+//! before that could have been sent by the client.
+
+use axum::extract::{ConnectInfo, Request};
+use axum::http::header::RETRY_AFTER;
+use axum::http::HeaderValue;
+use axum::response::{IntoResponse, Response};
+use futures::future::{ready, Either, Ready};
+use governor::clock::{Clock, DefaultClock};
+use governor::{DefaultKeyedRateLimiter, Quota, RateLimiter};
+use ipnet::IpNet;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::num::NonZeroU32;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tower::{Layer, Service};
+
+use crate::error::ApiError;
+use crate::ServerConfig;
+
+/// Header set by reverse proxies with the original client address
+pub const FORWARDED_FOR_HEADER: &str = "X-Forwarded-For";
+
+/// Number of tracked clients above which idle buckets are dropped
+const MAX_TRACKED_CLIENTS: usize = 10_000;
+
+/// Layer limiting each client IP to a steady rate with a burst allowance
+#[derive(Clone)]
+pub struct RateLimit {
+    limiter: Arc<DefaultKeyedRateLimiter<IpAddr>>,
+    trusted_proxies: Arc<[IpNet]>,
+}
+
+impl RateLimit {
+    /// Allow `rps` requests per second per client, with bursts of `burst`
+    pub fn new(rps: NonZeroU32, burst: NonZeroU32) -> Self {
+        let quota = Quota::per_second(rps).allow_burst(burst);
+        Self {
+            limiter: Arc::new(RateLimiter::keyed(quota)),
+            trusted_proxies: Arc::new([]),
+        }
+    }
+
+    /// Read the client address from `X-Forwarded-For` on requests from
+    /// `proxies`
+    pub fn with_trusted_proxies(mut self, proxies: Vec<IpNet>) -> Self {
+        self.trusted_proxies = proxies.into();
+        self
+    }
+
+    /// Rate limit from `rate_limit_rps` and `rate_limit_burst`
+    ///
+    /// Returns `None` when `rate_limit_rps` is unset or 0. The burst defaults
+    /// to one second's worth of requests.
+    pub fn from_config(config: &ServerConfig) -> Option<Self> {
+        let rps = NonZeroU32::new(config.rate_limit_rps?)?;
+        let burst = config
+            .rate_limit_burst
+            .and_then(NonZeroU32::new)
+            .unwrap_or(rps);
+        Some(Self::new(rps, burst).with_trusted_proxies(config.trusted_proxies.clone()))
+    }
+}
+
+impl std::fmt::Debug for RateLimit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RateLimit")
+            .field("clients", &self.limiter.len())
+            .field("trusted_proxies", &self.trusted_proxies)
+            .finish()
+    }
+}
+
+impl<S> Layer<S> for RateLimit {
+    type Service = RateLimitService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RateLimitService {
+            inner,
+            limit: self.clone(),
+        }
+    }
+}
+
+/// Service produced by [`RateLimit`]
+#[derive(Debug, Clone)]
+pub struct RateLimitService<S> {
+    inner: S,
+    limit: RateLimit,
+}
+
+impl<S> RateLimitService<S> {
+    /// Take a token for the client, returning the seconds until one is
+    /// available if none is left
+    fn check(&self, request: &Request) -> Result<(), u64> {
+        let limiter = &self.limit.limiter;
+        if limiter.len() > MAX_TRACKED_CLIENTS {
+            limiter.retain_recent();
+        }
+
+        let ip = client_ip(request, &self.limit.trusted_proxies);
+        limiter.check_key(&ip).map_err(|not_until| {
+            let wait = not_until.wait_time_from(DefaultClock::default().now());
+            // Round up so clients never retry before a token is available
+            let retry_after = wait.as_secs() + u64::from(wait.subsec_nanos() > 0);
+            retry_after.max(1)
+        })
+    }
+}
+
+/// `429 Too Many Requests` telling the client when to retry
+fn too_many_requests(retry_after: u64) -> Response {
+    let mut response = ApiError::TooManyRequests("Rate limit exceeded".to_string()).into_response();
+    response
+        .headers_mut()
+        .insert(RETRY_AFTER, HeaderValue::from(retry_after));
+    response
+}
+
+impl<S> Service<Request> for RateLimitService<S>
+where
+    S: Service<Request, Response = Response>,
+{
+    type Response = Response;
+    type Error = S::Error;
+    type Future = Either<S::Future, Ready<Result<Response, S::Error>>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request) -> Self::Future {
+        match self.check(&request) {
+            Ok(()) => Either::Left(self.inner.call(request)),
+            Err(retry_after) => Either::Right(ready(Ok(too_many_requests(retry_after)))),
+        }
+    }
+}
+
+/// Client address from the connection, or from `X-Forwarded-For` when the
+/// connection comes from one of `trusted_proxies`
+///
+/// Requests without a connection address (e.g. in tests) share the
+/// unspecified address.
+fn client_ip(request: &Request, trusted_proxies: &[IpNet]) -> IpAddr {
+    let Some(ConnectInfo(peer)) = request.extensions().get::<ConnectInfo<SocketAddr>>() else {
+        return IpAddr::V4(Ipv4Addr::UNSPECIFIED);
+    };
+    let is_trusted = |ip: &IpAddr| trusted_proxies.iter().any(|net| net.contains(ip));
+
+    // Each proxy appends the address it received the request from, so walk
+    // back from the nearest hop while it is one of ours
+    let hops = request
+        .headers()
+        .get_all(FORWARDED_FOR_HEADER)
+        .iter()
+        .rev()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.rsplit(','));
+    let mut client = peer.ip();
+    for hop in hops {
+        if !is_trusted(&client) {
+            break;
+        }
+        match hop.trim().parse() {
+            Ok(ip) => client = ip,
+            Err(_) => break,
+        }
+    }
+    client
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::http::StatusCode;
+    use axum::routing::post;
+    use axum::Router;
+    use tower::ServiceExt;
+
+    fn app(rps: u32, burst: u32) -> Router {
+        let limit = RateLimit::new(
+            NonZeroU32::new(rps).unwrap(),
+            NonZeroU32::new(burst).unwrap(),
+        );
+        Router::new()
+            .route("/webhooks/test", post(|| async { "queued" }))
+            .route_layer(limit)
+    }
+
+    async fn call(app: Router, ip: &str) -> Response {
+        let mut request = axum::http::Request::post("/webhooks/test")
+            .body(Body::empty())
+            .unwrap();
+        let peer = SocketAddr::new(ip.parse().unwrap(), 40000);
+        request.extensions_mut().insert(ConnectInfo(peer));
+        app.oneshot(request).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_requests_after_burst_rejected() {
+        let app = app(1, 10);
+
+        let mut statuses = Vec::new();
+        for _ in 0..100 {
+            let response = call(app.clone(), "203.0.113.7").await;
+            if response.status() == StatusCode::TOO_MANY_REQUESTS {
+                assert!(response.headers().contains_key(RETRY_AFTER));
+            }
+            statuses.push(response.status());
+        }
+
+        assert!(statuses[..10].iter().all(|s| *s == StatusCode::OK));
+        assert!(statuses[10..]
+            .iter()
+            .all(|s| *s == StatusCode::TOO_MANY_REQUESTS));
+    }
+
+    #[tokio::test]
+    async fn test_limit_is_per_client() {
+        let app = app(1, 1);
+
+        assert_eq!(
+            call(app.clone(), "203.0.113.7").await.status(),
+            StatusCode::OK
+        );
+        assert_eq!(
+            call(app.clone(), "203.0.113.7").await.status(),
+            StatusCode::TOO_MANY_REQUESTS
+        );
+        assert_eq!(call(app, "198.51.100.1").await.status(), StatusCode::OK);
+    }
+
+    fn forwarded(peer: [u8; 4], forwarded: Option<&str>) -> Request {
+        let mut request = axum::http::Request::post("/");
+        if let Some(forwarded) = forwarded {
+            request = request.header(FORWARDED_FOR_HEADER, forwarded);
+        }
+        let mut request = request.body(Body::empty()).unwrap();
+        request
+            .extensions_mut()
+            .insert(ConnectInfo(SocketAddr::from((peer, 40000))));
+        request
+    }
+
+    #[test]
+    fn test_client_ip_ignores_forwarded_for_by_default() {
+        let request = forwarded([203, 0, 113, 7], Some("198.51.100.1"));
+        assert_eq!(
+            client_ip(&request, &[]),
+            "203.0.113.7".parse::<IpAddr>().unwrap()
+        );
+
+        let request = axum::http::Request::post("/").body(Body::empty()).unwrap();
+        assert_eq!(client_ip(&request, &[]), IpAddr::V4(Ipv4Addr::UNSPECIFIED));
+    }
+
+    #[test]
+    fn test_client_ip_behind_trusted_proxy() {
+        let trusted: Vec<IpNet> = vec!["10.0.0.0/8".parse().unwrap()];
+        let ip = |peer, header| client_ip(&forwarded(peer, header), &trusted);
+
+        assert_eq!(
+            ip([10, 0, 0, 5], Some("203.0.113.7")),
+            "203.0.113.7".parse::<IpAddr>().unwrap()
+        );
+        // Addresses the client prepended itself are skipped
+        assert_eq!(
+            ip([10, 0, 0, 5], Some("192.0.2.1, 203.0.113.7, 10.0.0.9")),
+            "203.0.113.7".parse::<IpAddr>().unwrap()
+        );
+        // Untrusted peers cannot pick their own address
+        assert_eq!(
+            ip([198, 51, 100, 1], Some("203.0.113.7")),
+            "198.51.100.1".parse::<IpAddr>().unwrap()
+        );
+        assert_eq!(
+            ip([10, 0, 0, 5], Some("not an ip")),
+            "10.0.0.5".parse::<IpAddr>().unwrap()
+        );
+        assert_eq!(
+            ip([10, 0, 0, 5], None),
+            "10.0.0.5".parse::<IpAddr>().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_from_config() {
+        assert!(RateLimit::from_config(&ServerConfig::default()).is_none());
+
+        let config = ServerConfig {
+            rate_limit_rps: Some(0),
+            ..Default::default()
+        };
+        assert!(RateLimit::from_config(&config).is_none());
+
+        let config = ServerConfig {
+            rate_limit_rps: Some(5),
+            ..Default::default()
+        };
+        assert!(RateLimit::from_config(&config).is_some());
+    }
+}
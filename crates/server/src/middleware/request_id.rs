@@ -0,0 +1,128 @@
+//! Request IDs
+//!
+//! [`request_id`] gives every request an ID, taken from its `X-Request-Id`
+//! header or generated, and returns it in the response's `X-Request-Id`
+//! header. The ID is recorded on the request span opened by
+//! [`super::trace_layer`] and is available to code handling the request
+//! through [`current_request_id`], so jobs queued by a webhook can be traced
+//! back to the delivery that queued them.
+
+use axum::extract::Request;
+use axum::http::HeaderValue;
+use axum::middleware::Next;
+use axum::response::Response;
+use std::future::Future;
+
+/// Header carrying the request ID
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Longest caller-supplied request ID accepted; longer ones are replaced
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+/// ID of a request, stored in its extensions
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestId(pub String);
+
+impl RequestId {
+    /// A new random request ID
+    pub fn generate() -> Self {
+        Self(uuid::Uuid::new_v4().to_string())
+    }
+
+    /// Accept a caller-supplied ID of printable ASCII without spaces
+    pub fn parse(value: &str) -> Option<Self> {
+        let valid = !value.is_empty()
+            && value.len() <= MAX_REQUEST_ID_LEN
+            && value.bytes().all(|b| b.is_ascii_graphic());
+        valid.then(|| Self(value.to_string()))
+    }
+}
+
+tokio::task_local! {
+    static CURRENT_REQUEST_ID: String;
+}
+
+/// Run `future` as part of the request with ID `request_id`
+pub async fn with_request_id<F: Future>(request_id: String, future: F) -> F::Output {
+    CURRENT_REQUEST_ID.scope(request_id, future).await
+}
+
+/// ID of the request being handled, if any
+pub fn current_request_id() -> Option<String> {
+    CURRENT_REQUEST_ID.try_with(String::clone).ok()
+}
+
+/// Attach the request's ID, generating one if it has none
+///
+/// Must run outside of [`super::trace_layer`], which reads the ID from the
+/// request extensions.
+pub async fn request_id(mut request: Request, next: Next) -> Response {
+    let id = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(RequestId::parse)
+        .unwrap_or_else(RequestId::generate);
+    request.extensions_mut().insert(id.clone());
+
+    let mut response = with_request_id(id.0.clone(), next.run(request)).await;
+    if let Ok(value) = HeaderValue::from_str(&id.0) {
+        response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::routing::get;
+    use axum::{middleware, Router};
+    use tower::ServiceExt;
+
+    /// Echoes the request ID seen by the handler
+    async fn send(header: Option<&str>) -> (String, String) {
+        let app = Router::new()
+            .route("/", get(|| async { current_request_id().unwrap() }))
+            .layer(middleware::from_fn(request_id));
+
+        let mut request = axum::http::Request::get("/");
+        if let Some(header) = header {
+            request = request.header(REQUEST_ID_HEADER, header);
+        }
+        let response = app
+            .oneshot(request.body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let header = response.headers()[REQUEST_ID_HEADER]
+            .to_str()
+            .unwrap()
+            .to_string();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (header, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_request_id_propagated() {
+        let (header, seen) = send(Some("gitea-delivery-42")).await;
+        assert_eq!(header, "gitea-delivery-42");
+        assert_eq!(seen, "gitea-delivery-42");
+    }
+
+    #[tokio::test]
+    async fn test_request_id_generated() {
+        let too_long = "x".repeat(MAX_REQUEST_ID_LEN + 1);
+        for header in [None, Some("has space"), Some(too_long.as_str())] {
+            let (id, seen) = send(header).await;
+            assert_eq!(id, seen);
+            assert!(uuid::Uuid::parse_str(&id).is_ok(), "{}", id);
+        }
+    }
+
+    #[test]
+    fn test_current_request_id_outside_request() {
+        assert_eq!(current_request_id(), None);
+    }
+}
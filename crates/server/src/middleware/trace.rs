@@ -0,0 +1,127 @@
+//! Request tracing
+//!
+//! [`trace_context`] reads the W3C `traceparent` header of each request, or
+//! starts a new trace when it is missing or malformed, and returns it in the
+//! response's `traceparent` header. [`trace_layer`] opens a span per request
+//! carrying the trace ID and the [`RequestId`], parented to the caller's span
+//! so that exported spans join the caller's trace.
+
+use axum::extract::Request;
+use axum::http::HeaderValue;
+use axum::middleware::Next;
+use axum::response::Response;
+use tower_http::classify::{ServerErrorsAsFailures, SharedClassifier};
+use tower_http::trace::{MakeSpan, TraceLayer};
+use tracing::Span;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+use super::RequestId;
+use crate::telemetry::{self, TraceContext, TRACEPARENT_HEADER};
+
+/// Layer returned by [`trace_layer`]
+pub type Trace = TraceLayer<SharedClassifier<ServerErrorsAsFailures>, RequestSpan>;
+
+/// Attach the request's trace context, generating one if it has none
+///
+/// Must run outside of [`trace_layer`], which reads the context from the
+/// request extensions.
+pub async fn trace_context(mut request: Request, next: Next) -> Response {
+    let trace = request
+        .headers()
+        .get(TRACEPARENT_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(TraceContext::parse)
+        .unwrap_or_else(TraceContext::generate);
+    request.extensions_mut().insert(trace.clone());
+
+    let mut response = telemetry::with_trace(trace.clone(), next.run(request)).await;
+    if let Ok(value) = HeaderValue::from_str(&trace.to_string()) {
+        response.headers_mut().insert(TRACEPARENT_HEADER, value);
+    }
+    response
+}
+
+/// Span opened for each request by [`trace_layer`]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RequestSpan;
+
+impl<B> MakeSpan<B> for RequestSpan {
+    fn make_span(&mut self, request: &axum::http::Request<B>) -> Span {
+        let trace = request.extensions().get::<TraceContext>();
+        let request_id = request.extensions().get::<RequestId>();
+        let span = tracing::info_span!(
+            "request",
+            method = %request.method(),
+            uri = %request.uri(),
+            trace_id = trace.map(|t| t.trace_id.as_str()),
+            request_id = request_id.map(|id| id.0.as_str()),
+        );
+        if let Some(trace) = trace {
+            span.set_parent(trace.otel_context());
+        }
+        span
+    }
+}
+
+/// Open a span per request, logging its response
+pub fn trace_layer() -> Trace {
+    TraceLayer::new_for_http().make_span_with(RequestSpan)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::routing::get;
+    use axum::{middleware, Router};
+    use tower::ServiceExt;
+
+    const HEADER: &str = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+
+    /// Echoes the trace seen by the handler
+    fn app() -> Router {
+        Router::new()
+            .route(
+                "/",
+                get(|| async { telemetry::current_trace().unwrap().trace_id }),
+            )
+            .layer(trace_layer())
+            .layer(middleware::from_fn(trace_context))
+    }
+
+    async fn send(traceparent: Option<&str>) -> (String, String) {
+        let mut request = axum::http::Request::get("/");
+        if let Some(traceparent) = traceparent {
+            request = request.header(TRACEPARENT_HEADER, traceparent);
+        }
+        let response = app()
+            .oneshot(request.body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let header = response.headers()[TRACEPARENT_HEADER]
+            .to_str()
+            .unwrap()
+            .to_string();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (header, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_traceparent_propagated() {
+        let (header, trace_id) = send(Some(HEADER)).await;
+        assert_eq!(header, HEADER);
+        assert_eq!(trace_id, "4bf92f3577b34da6a3ce929d0e0e4736");
+    }
+
+    #[tokio::test]
+    async fn test_trace_generated_without_traceparent() {
+        for traceparent in [None, Some("not-a-trace")] {
+            let (header, trace_id) = send(traceparent).await;
+            let trace = TraceContext::parse(&header).unwrap();
+            assert_eq!(trace.trace_id, trace_id);
+            assert_ne!(trace_id, "4bf92f3577b34da6a3ce929d0e0e4736");
+        }
+    }
+}
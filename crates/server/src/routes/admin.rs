@@ -0,0 +1,476 @@
+//! Admin routes
+//!
+//! Runtime management of the server, authenticated with an admin key in the
+//! `X-Admin-Key` header:
+//!
+//! - `GET /admin/state`: connected agents, queue depth and cache sizes
+//! - `POST /admin/drain`: stop accepting new jobs from webhooks and wait for
+//!   running jobs to finish
+//! - `POST /admin/shutdown`: stop the server gracefully, finishing in-flight
+//!   requests
+//! - `POST /admin/reload-config`: re-read the configuration file, see
+//!   [`crate::config_file`]
+//! - `POST /admin/prune-logs`: delete the log streams of old jobs, see
+//!   [`crate::log_stream::prune_logs`]
+
+use axum::extract::{Query, State};
+use axum::http::StatusCode;
+use axum::Json;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::time::Duration;
+use tokio::time::Instant;
+use tracing::{info, warn};
+
+use crate::error::{ApiError, ApiResult};
+use crate::log_stream::prune_logs;
+use crate::routes::queue::fetch_queue_stats;
+use crate::state::AppState;
+
+/// How long `POST /admin/drain` waits for running jobs when no `timeout_secs`
+/// is given
+pub const DEFAULT_DRAIN_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Interval between checks for running jobs while draining
+const DRAIN_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Age in hours past which `POST /admin/prune-logs` deletes job logs when no
+/// `older_than_hours` is given
+pub const DEFAULT_PRUNE_AGE_HOURS: u64 = 24;
+
+/// Body of `GET /admin/state`
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct ServerSummary {
+    /// Registered agents
+    pub agents: usize,
+    /// Jobs waiting in the queue, if Redis is reachable
+    pub queue_depth: Option<u64>,
+    /// Whether Redis is reachable
+    pub redis_available: bool,
+    /// Whether webhooks stopped accepting new jobs
+    pub draining: bool,
+    /// Cached responses
+    pub cache: CacheSummary,
+}
+
+/// Number of cached responses per cache
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct CacheSummary {
+    pub job_lists: u64,
+    pub job_statuses: u64,
+}
+
+/// Query parameters of `POST /admin/drain`
+#[derive(Debug, Deserialize)]
+pub struct DrainQuery {
+    /// Seconds to wait for running jobs to finish
+    pub timeout_secs: Option<u64>,
+}
+
+/// Body of a completed `POST /admin/drain`
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct DrainStatus {
+    pub draining: bool,
+    /// Jobs claimed by agents and not yet acknowledged
+    pub running_jobs: u64,
+}
+
+/// Query parameters of `POST /admin/prune-logs`
+#[derive(Debug, Deserialize)]
+pub struct PruneLogsQuery {
+    /// Delete the logs of jobs queued more than this many hours ago
+    pub older_than_hours: Option<u64>,
+}
+
+/// `GET /admin/state`
+pub async fn state(State(state): State<AppState>) -> Json<ServerSummary> {
+    let redis_available = state.redis_available();
+    let queue_depth = if redis_available {
+        match queue_stats_pending(&state).await {
+            Ok((depth, _)) => Some(depth),
+            Err(e) => {
+                warn!("Failed to read queue depth: {:#}", e);
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    let cache = state.response_cache();
+    Json(ServerSummary {
+        agents: state.agents().list().len(),
+        queue_depth,
+        redis_available,
+        draining: state.is_draining(),
+        cache: CacheSummary {
+            job_lists: cache.job_lists.entry_count(),
+            job_statuses: cache.job_statuses.entry_count(),
+        },
+    })
+}
+
+/// `POST /admin/drain`
+///
+/// Webhooks reject new jobs from the first call on; the response is sent
+/// once no job is running, or `503` when the timeout passes first.
+pub async fn drain(
+    State(state): State<AppState>,
+    Query(query): Query<DrainQuery>,
+) -> ApiResult<Json<DrainStatus>> {
+    if !state.start_draining() {
+        info!("Draining: webhooks no longer accept new jobs");
+    }
+
+    if !state.redis_available() {
+        return Err(ApiError::Unavailable("Redis is not reachable".to_string()));
+    }
+
+    let timeout = query
+        .timeout_secs
+        .map(Duration::from_secs)
+        .unwrap_or(DEFAULT_DRAIN_TIMEOUT);
+    let deadline = Instant::now() + timeout;
+
+    loop {
+        let (_, running_jobs) = queue_stats_pending(&state).await?;
+        if running_jobs == 0 {
+            info!("Drained: no jobs running");
+            return Ok(Json(DrainStatus {
+                draining: true,
+                running_jobs,
+            }));
+        }
+        if Instant::now() >= deadline {
+            return Err(ApiError::Unavailable(format!(
+                "{} jobs still running after {}s",
+                running_jobs,
+                timeout.as_secs()
+            )));
+        }
+        tokio::time::sleep(DRAIN_POLL_INTERVAL.min(deadline - Instant::now())).await;
+    }
+}
+
+/// `POST /admin/shutdown`
+pub async fn shutdown(State(state): State<AppState>) -> ApiResult<(StatusCode, Json<Value>)> {
+    if !state.request_shutdown() {
+        return Err(ApiError::Conflict(
+            "Shutdown already requested or server not running".to_string(),
+        ));
+    }
+
+    info!("Shutdown requested through the admin API");
+    Ok((
+        StatusCode::ACCEPTED,
+        Json(json!({ "status": "shutting down" })),
+    ))
+}
+
+/// `POST /admin/reload-config`
+pub async fn reload_config(State(state): State<AppState>) -> ApiResult<Json<Value>> {
+    let reloaded = state
+        .reload_config()
+        .map_err(|e| ApiError::BadRequest(format!("{:#}", e)))?;
+    Ok(Json(json!({ "reloaded": reloaded })))
+}
+
+/// `POST /admin/prune-logs`
+pub async fn prune_job_logs(
+    State(state): State<AppState>,
+    Query(query): Query<PruneLogsQuery>,
+) -> ApiResult<Json<Value>> {
+    if !state.redis_available() {
+        return Err(ApiError::Unavailable("Redis is not reachable".to_string()));
+    }
+
+    let hours = query.older_than_hours.unwrap_or(DEFAULT_PRUNE_AGE_HOURS);
+    let age = i64::try_from(hours)
+        .ok()
+        .and_then(chrono::Duration::try_hours)
+        .ok_or_else(|| ApiError::BadRequest(format!("older_than_hours too large: {}", hours)))?;
+    let older_than = chrono::Utc::now() - age;
+
+    let pruned = state
+        .with_redis(|mut conn| async move { prune_logs(&mut conn, older_than).await })
+        .await?;
+
+    info!("Pruned {} job logs older than {}h", pruned, hours);
+    Ok(Json(json!({ "pruned": pruned })))
+}
+
+/// Queue depth and pending (claimed, unacknowledged) job count
+async fn queue_stats_pending(state: &AppState) -> ApiResult<(u64, u64)> {
+    let config = state.config();
+    let stream = config.queue_stream.clone();
+    let group = config.consumer_group.clone();
+
+    let stats = state
+        .with_redis(|mut conn| {
+            let stream = stream.clone();
+            let group = group.clone();
+            async move { fetch_queue_stats(&mut conn, &stream, &group).await }
+        })
+        .await?;
+
+    Ok((stats.depth(), stats.pending_count))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::routes::router;
+    use crate::routes::webhooks::gitlab::{EVENT_UUID_HEADER, TOKEN_HEADER};
+    use crate::routes::webhooks::replay::DATE_HEADER;
+    use crate::ServerConfig;
+    use axum::body::Body;
+    use axum::http::Request;
+    use raibid_common::agents::{AgentRegistration, DEFAULT_DRAIN_TIMEOUT_SECS};
+    use raibid_common::auth::{ADMIN_KEY_HEADER, API_KEY_HEADER};
+    use std::io::Write;
+    use tower::ServiceExt;
+
+    const ADMIN_KEY: &str = "rbd_admin";
+
+    fn state() -> AppState {
+        let state = AppState::new(ServerConfig {
+            api_keys: vec!["rbd_secret".to_string()],
+            admin_keys: vec![ADMIN_KEY.to_string()],
+            gitlab_webhook_secret: Some("gitlab-secret".to_string()),
+            ..Default::default()
+        })
+        .unwrap();
+        state.set_redis_available(false);
+        state
+    }
+
+    fn admin(method: &str, uri: &str, key: Option<&str>) -> Request<Body> {
+        let mut request = Request::builder().method(method).uri(uri);
+        if let Some(key) = key {
+            request = request.header(ADMIN_KEY_HEADER, key);
+        }
+        request.body(Body::empty()).unwrap()
+    }
+
+    async fn json<T: serde::de::DeserializeOwned>(response: axum::response::Response) -> T {
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_admin_key_required() {
+        let app = router(state());
+        for (method, uri) in [
+            ("GET", "/admin/state"),
+            ("POST", "/admin/drain"),
+            ("POST", "/admin/shutdown"),
+            ("POST", "/admin/reload-config"),
+            ("POST", "/admin/prune-logs"),
+        ] {
+            let response = app.clone().oneshot(admin(method, uri, None)).await.unwrap();
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED, "{}", uri);
+
+            // An API key is not an admin key
+            let request = Request::builder()
+                .method(method)
+                .uri(uri)
+                .header(API_KEY_HEADER, "rbd_secret")
+                .body(Body::empty())
+                .unwrap();
+            let response = app.clone().oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED, "{}", uri);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_admin_closed_without_admin_keys() {
+        let state = AppState::new(ServerConfig::default()).unwrap();
+        let response = router(state)
+            .oneshot(admin("GET", "/admin/state", Some("anything")))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_state() {
+        let state = state();
+        state.agents().register(
+            AgentRegistration {
+                agent_id: "agent-1".to_string(),
+                version: "0.1.0".to_string(),
+                capabilities: vec!["rust".to_string()],
+                pod_name: None,
+                drain_timeout_secs: DEFAULT_DRAIN_TIMEOUT_SECS,
+            },
+            chrono::Utc::now(),
+        );
+
+        let response = router(state)
+            .oneshot(admin("GET", "/admin/state", Some(ADMIN_KEY)))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let summary: ServerSummary = json(response).await;
+        assert_eq!(
+            summary,
+            ServerSummary {
+                agents: 1,
+                queue_depth: None,
+                redis_available: false,
+                draining: false,
+                cache: CacheSummary {
+                    job_lists: 0,
+                    job_statuses: 0,
+                },
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_shutdown() {
+        let state = state();
+        let app = router(state.clone());
+
+        // No server waiting for a shutdown request
+        let response = app
+            .clone()
+            .oneshot(admin("POST", "/admin/shutdown", Some(ADMIN_KEY)))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+
+        let signal = state.shutdown_signal();
+        let response = app
+            .clone()
+            .oneshot(admin("POST", "/admin/shutdown", Some(ADMIN_KEY)))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        signal.await.unwrap();
+
+        let response = app
+            .oneshot(admin("POST", "/admin/shutdown", Some(ADMIN_KEY)))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+    }
+
+    #[tokio::test]
+    async fn test_drain_rejects_new_jobs() {
+        let state = state();
+        let app = router(state.clone());
+
+        // Running jobs cannot be counted without Redis, but draining starts
+        let response = app
+            .clone()
+            .oneshot(admin("POST", "/admin/drain", Some(ADMIN_KEY)))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert!(state.is_draining());
+
+        let request = Request::post("/webhooks/gitlab")
+            .header(TOKEN_HEADER, "gitlab-secret")
+            .header(EVENT_UUID_HEADER, "drain-1")
+            .header(DATE_HEADER, chrono::Utc::now().to_rfc2822())
+            .header("content-type", "application/json")
+            .body(Body::from(include_str!(
+                "../../tests/fixtures/gitlab_push.json"
+            )))
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let body: Value = json(response).await;
+        assert!(body["error"].as_str().unwrap().contains("draining"));
+
+        let response = app
+            .oneshot(admin("GET", "/admin/state", Some(ADMIN_KEY)))
+            .await
+            .unwrap();
+        let summary: ServerSummary = json(response).await;
+        assert!(summary.draining);
+    }
+
+    #[tokio::test]
+    async fn test_reload_config() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        writeln!(file, "api_keys: [rbd_rotated]").unwrap();
+
+        let state = AppState::new(ServerConfig {
+            api_keys: vec!["rbd_secret".to_string()],
+            admin_keys: vec![ADMIN_KEY.to_string()],
+            config_file: Some(file.path().to_path_buf()),
+            ..Default::default()
+        })
+        .unwrap();
+        state.set_redis_available(false);
+        let app = router(state);
+
+        let stats = |key: &'static str| {
+            Request::get("/api/queue/stats")
+                .header(API_KEY_HEADER, key)
+                .body(Body::empty())
+                .unwrap()
+        };
+        let response = app.clone().oneshot(stats("rbd_secret")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+        let response = app
+            .clone()
+            .oneshot(admin("POST", "/admin/reload-config", Some(ADMIN_KEY)))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: Value = json(response).await;
+        assert_eq!(body, json!({ "reloaded": ["api_keys"] }));
+
+        let response = app.clone().oneshot(stats("rbd_secret")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        let response = app.clone().oneshot(stats("rbd_rotated")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+        // The admin key is untouched by a file without `admin_keys`
+        let response = app
+            .oneshot(admin("GET", "/admin/state", Some(ADMIN_KEY)))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_prune_logs_requires_redis() {
+        let app = router(state());
+        let response = app
+            .clone()
+            .oneshot(admin(
+                "POST",
+                "/admin/prune-logs?older_than_hours=24",
+                Some(ADMIN_KEY),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+        let response = app
+            .oneshot(admin(
+                "POST",
+                "/admin/prune-logs?older_than_hours=soon",
+                Some(ADMIN_KEY),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_reload_without_config_file() {
+        let response = router(state())
+            .oneshot(admin("POST", "/admin/reload-config", Some(ADMIN_KEY)))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+}
@@ -0,0 +1,197 @@
+//! Agent routes
+//!
+//! - `POST /api/agents/register`: add an agent to the registry
+//! - `POST /api/agents/:id/heartbeat`: record that an agent is alive and how
+//!   many jobs it is running
+//! - `GET /api/agents`: list registered agents and their status
+//! - `GET /api/agents/:id`: details of one agent
+//! - `POST /api/agents/:id/drain`: stop an agent from claiming jobs
+//! - `POST /api/agents/:id/restart`: recreate the agents' Deployment (see
+//!   [`crate::scaling`])
+//! - `POST /api/agents/scale`: set the agent replica range (see
+//!   [`crate::scaling`])
+//! - `GET /api/events/agents`: a stream of server-sent `agent` events, one per
+//!   agent status change (see [`crate::agent_events`])
+//!
+//! See [`crate::agents`] for how missed heartbeats affect agent status.
+
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::Json;
+use chrono::Utc;
+use futures::stream::{self, Stream};
+use tokio::sync::broadcast::error::RecvError;
+use tracing::warn;
+
+use crate::error::{ApiError, ApiResult};
+use crate::state::AppState;
+use raibid_common::agents::{
+    AgentDetails, AgentHeartbeat, AgentInfo, AgentRegistration, AgentScale, HeartbeatResponse,
+};
+
+/// Name of the server-sent events of `GET /api/events/agents`
+pub const AGENT_EVENT: &str = "agent";
+
+/// `POST /api/agents/register`
+pub async fn register(
+    State(state): State<AppState>,
+    Json(registration): Json<AgentRegistration>,
+) -> ApiResult<(StatusCode, Json<AgentInfo>)> {
+    if registration.agent_id.trim().is_empty() {
+        return Err(ApiError::BadRequest(
+            "agent_id must not be empty".to_string(),
+        ));
+    }
+
+    let now = Utc::now();
+    let agent = state.agents().register(registration, now);
+    Ok((StatusCode::CREATED, Json(agent.info(now))))
+}
+
+/// `POST /api/agents/:id/heartbeat`
+///
+/// The body, if any, reports the jobs the agent is running. The response
+/// tells a draining agent to stop claiming jobs.
+pub async fn heartbeat(
+    State(state): State<AppState>,
+    Path(agent_id): Path<String>,
+    body: Option<Json<AgentHeartbeat>>,
+) -> ApiResult<Json<HeartbeatResponse>> {
+    let agents = state.agents();
+    if !agents.heartbeat(&agent_id, Utc::now()) {
+        // Agents re-register when the server forgot them (e.g. after a restart)
+        return Err(not_registered(&agent_id));
+    }
+
+    if let Some(Json(heartbeat)) = body {
+        agents.set_active_jobs(&agent_id, heartbeat.active_jobs);
+    }
+    let draining = agents.get(&agent_id).is_some_and(|agent| agent.draining);
+    Ok(Json(HeartbeatResponse { draining }))
+}
+
+/// `GET /api/agents`
+pub async fn list(State(state): State<AppState>) -> Json<Vec<AgentInfo>> {
+    let now = Utc::now();
+    Json(
+        state
+            .agents()
+            .list()
+            .iter()
+            .map(|agent| agent.info(now))
+            .collect(),
+    )
+}
+
+/// `GET /api/agents/:id`
+pub async fn show(
+    State(state): State<AppState>,
+    Path(agent_id): Path<String>,
+) -> ApiResult<Json<AgentDetails>> {
+    let agent = state
+        .agents()
+        .get(&agent_id)
+        .ok_or_else(|| not_registered(&agent_id))?;
+
+    Ok(Json(AgentDetails {
+        info: agent.info(Utc::now()),
+        agent_type: None,
+        last_heartbeat: Some(agent.last_seen),
+        job_history: Vec::new(),
+    }))
+}
+
+/// `POST /api/agents/:id/drain`
+///
+/// The agent learns it is draining from its next heartbeat response, then
+/// finishes its running jobs without claiming new ones.
+pub async fn drain(
+    State(state): State<AppState>,
+    Path(agent_id): Path<String>,
+) -> ApiResult<Json<AgentInfo>> {
+    let agent = state
+        .agents()
+        .drain(&agent_id)
+        .ok_or_else(|| not_registered(&agent_id))?;
+    Ok(Json(agent.info(Utc::now())))
+}
+
+/// `POST /api/agents/:id/restart`
+///
+/// Deletes the agent's pod whether or not the agent has drained, and forgets
+/// the agent; the Deployment starts a replacement, which registers when it
+/// starts. Agents that did not report a pod cannot be restarted.
+pub async fn restart(
+    State(state): State<AppState>,
+    Path(agent_id): Path<String>,
+) -> ApiResult<StatusCode> {
+    let agent = state
+        .agents()
+        .get(&agent_id)
+        .ok_or_else(|| not_registered(&agent_id))?;
+    let pod = agent.pod_name.ok_or_else(|| {
+        ApiError::Conflict(format!(
+            "Agent '{}' is not running in a Kubernetes pod",
+            agent_id
+        ))
+    })?;
+
+    state
+        .agent_deployment()
+        .restart_pod(&pod)
+        .await
+        .map_err(|e| ApiError::Unavailable(format!("Failed to restart agent: {:#}", e)))?;
+
+    state.agents().remove(&agent_id);
+    Ok(StatusCode::ACCEPTED)
+}
+
+/// `POST /api/agents/scale`
+///
+/// Responds with the applied range once the ScaledObject is patched.
+pub async fn scale(
+    State(state): State<AppState>,
+    Json(scale): Json<AgentScale>,
+) -> ApiResult<Json<AgentScale>> {
+    scale.validate().map_err(ApiError::BadRequest)?;
+
+    state
+        .scale_target()
+        .apply(&scale)
+        .await
+        .map_err(|e| ApiError::Unavailable(format!("Failed to scale agents: {:#}", e)))?;
+
+    state.set_agent_scale(scale);
+    Ok(Json(scale))
+}
+
+/// `GET /api/events/agents`
+pub async fn events(
+    State(state): State<AppState>,
+) -> Sse<impl Stream<Item = Result<Event, axum::Error>>> {
+    let events = state.agent_events().subscribe();
+    let stream = stream::unfold(events, |mut events| async move {
+        loop {
+            match events.recv().await {
+                Ok(event) => {
+                    let sse = Event::default().event(AGENT_EVENT).json_data(event);
+                    return Some((sse, events));
+                }
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!(
+                        "Agent event follower fell behind, skipped {} events",
+                        skipped
+                    )
+                }
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    });
+
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
+
+fn not_registered(agent_id: &str) -> ApiError {
+    ApiError::NotFound(format!("Agent {} is not registered", agent_id))
+}
@@ -0,0 +1,97 @@
+//! Benchmark routes
+//!
+//! - `GET /api/benchmarks/compare?repo=…&base=main&head=feature`: percent
+//!   change of each benchmark between the latest results of two branches
+//!
+//! Benchmarks slower on `head` by more than the threshold are flagged as
+//! regressions. The threshold defaults to
+//! [`ServerConfig::benchmark_regression_threshold_pct`](crate::ServerConfig)
+//! and can be overridden per request with `threshold`.
+
+use axum::extract::{Query, State};
+use axum::Json;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{ApiError, ApiResult};
+use crate::routes::jobs::get_json;
+use crate::state::AppState;
+use raibid_common::benchmark::{compare_benchmarks, BenchComparison, BenchResult};
+use raibid_common::jobs::latest_benchmarks_key;
+
+/// Query parameters of `GET /api/benchmarks/compare`
+#[derive(Debug, Deserialize)]
+pub struct CompareQuery {
+    /// Repository (`owner/name`)
+    pub repo: String,
+    /// Branch to compare against
+    #[serde(default = "default_base")]
+    pub base: String,
+    /// Branch being compared
+    pub head: String,
+    /// Regression threshold in percent
+    pub threshold: Option<f64>,
+}
+
+fn default_base() -> String {
+    "main".to_string()
+}
+
+/// Response of `GET /api/benchmarks/compare`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CompareResponse {
+    pub repo: String,
+    pub base: String,
+    pub head: String,
+    /// Threshold the regressions were flagged with, in percent
+    pub threshold_pct: f64,
+    /// Benchmarks present on both branches, sorted by name
+    pub benchmarks: Vec<BenchComparison>,
+    /// Number of benchmarks flagged as regressions
+    pub regressions: usize,
+}
+
+/// `GET /api/benchmarks/compare`
+pub async fn compare(
+    State(state): State<AppState>,
+    Query(query): Query<CompareQuery>,
+) -> ApiResult<Json<CompareResponse>> {
+    let threshold_pct = query
+        .threshold
+        .unwrap_or(state.config().benchmark_regression_threshold_pct);
+    if !threshold_pct.is_finite() || threshold_pct < 0.0 {
+        return Err(ApiError::BadRequest(
+            "threshold must be a non-negative number".to_string(),
+        ));
+    }
+
+    let base = latest(&state, &query.repo, &query.base).await?;
+    let head = latest(&state, &query.repo, &query.head).await?;
+
+    let benchmarks = compare_benchmarks(&base, &head, threshold_pct);
+    let regressions = benchmarks.iter().filter(|b| b.regression).count();
+
+    Ok(Json(CompareResponse {
+        repo: query.repo,
+        base: query.base,
+        head: query.head,
+        threshold_pct,
+        benchmarks,
+        regressions,
+    }))
+}
+
+/// Latest benchmark results of a branch
+async fn latest(state: &AppState, repo: &str, branch: &str) -> ApiResult<Vec<BenchResult>> {
+    get_json(
+        state,
+        latest_benchmarks_key(repo, branch),
+        "benchmark results",
+    )
+    .await?
+    .ok_or_else(|| {
+        ApiError::NotFound(format!(
+            "No benchmark results for {} branch {}",
+            repo, branch
+        ))
+    })
+}
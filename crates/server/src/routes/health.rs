@@ -0,0 +1,174 @@
+//! Health routes
+//!
+//! Every health route is public, even when API keys are configured.
+//!
+//! - `GET /health`: status summary with the last Redis health check result
+//! - `GET /health/live`: liveness probe, answered without external calls
+//! - `GET /health/ready`: readiness probe, checks Redis and, if
+//!   `health_check_k3s` is set, the k3s API
+//! - `GET /health/started`: startup probe, ready once startup checks passed
+
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::Json;
+use kube::Client;
+use serde_json::{json, Map, Value};
+use std::time::Duration;
+use tracing::warn;
+
+use crate::state::AppState;
+
+/// How long each readiness check may take
+const READY_CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// `GET /health`
+pub async fn health(State(state): State<AppState>) -> Json<Value> {
+    Json(json!({
+        "status": "ok",
+        "redis_available": state.redis_available(),
+        "redis_circuit": state.redis_circuit().state().as_str(),
+    }))
+}
+
+/// `GET /health/live`
+pub async fn live() -> Json<Value> {
+    Json(json!({ "status": "alive" }))
+}
+
+/// `GET /health/ready`
+///
+/// Answers `503 Service Unavailable` if any checked component is down,
+/// listing each component as `ok` or `down`.
+pub async fn ready(State(state): State<AppState>) -> (StatusCode, Json<Value>) {
+    let mut components = Map::new();
+    components.insert(
+        "redis".to_string(),
+        component_status("Redis", ping_redis(&state).await),
+    );
+    if state.config().health_check_k3s {
+        components.insert("k3s".to_string(), component_status("k3s", ping_k3s().await));
+    }
+
+    let ready = components.values().all(|status| status == "ok");
+    let (code, status) = if ready {
+        (StatusCode::OK, "ready")
+    } else {
+        (StatusCode::SERVICE_UNAVAILABLE, "not_ready")
+    };
+    (
+        code,
+        Json(json!({ "status": status, "components": components })),
+    )
+}
+
+/// `GET /health/started`
+pub async fn started(State(state): State<AppState>) -> (StatusCode, Json<Value>) {
+    if state.is_started() {
+        (StatusCode::OK, Json(json!({ "status": "started" })))
+    } else {
+        (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(json!({ "status": "starting" })),
+        )
+    }
+}
+
+/// `ok`, or `down` after logging why the check failed
+fn component_status(name: &str, result: anyhow::Result<()>) -> Value {
+    match result {
+        Ok(()) => json!("ok"),
+        Err(e) => {
+            warn!("Readiness check of {} failed: {:#}", name, e);
+            json!("down")
+        }
+    }
+}
+
+async fn ping_redis(state: &AppState) -> anyhow::Result<()> {
+    let ping = state.with_redis(|mut conn| async move {
+        redis::cmd("PING").query_async::<_, String>(&mut conn).await
+    });
+    tokio::time::timeout(READY_CHECK_TIMEOUT, ping)
+        .await
+        .map_err(|_| anyhow::anyhow!("PING timed out after {:?}", READY_CHECK_TIMEOUT))??;
+    Ok(())
+}
+
+async fn ping_k3s() -> anyhow::Result<()> {
+    let check = async {
+        let client = Client::try_default().await?;
+        client.apiserver_version().await?;
+        anyhow::Ok(())
+    };
+    tokio::time::timeout(READY_CHECK_TIMEOUT, check)
+        .await
+        .map_err(|_| {
+            anyhow::anyhow!(
+                "Kubernetes API did not answer within {:?}",
+                READY_CHECK_TIMEOUT
+            )
+        })?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::routes::router;
+    use crate::ServerConfig;
+    use axum::body::Body;
+    use axum::http::Request;
+    use tower::ServiceExt;
+
+    /// State whose Redis refuses connections
+    fn state() -> AppState {
+        AppState::new(ServerConfig {
+            redis_url: "redis://127.0.0.1:1".to_string(),
+            redis_connect_timeout_ms: 200,
+            redis_wait_timeout_ms: 200,
+            api_keys: vec!["rbd_secret".to_string()],
+            ..Default::default()
+        })
+        .unwrap()
+    }
+
+    async fn get(state: &AppState, uri: &str) -> (StatusCode, Value) {
+        let response = router(state.clone())
+            .oneshot(Request::get(uri).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_ready_with_redis_down() {
+        let (status, body) = get(&state(), "/health/ready").await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body["status"], "not_ready");
+        assert_eq!(body["components"]["redis"], "down");
+        // k3s is only checked when enabled
+        assert!(body["components"].get("k3s").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_live_without_dependencies() {
+        let (status, body) = get(&state(), "/health/live").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["status"], "alive");
+    }
+
+    #[tokio::test]
+    async fn test_started_after_startup() {
+        let state = state();
+        let (status, body) = get(&state, "/health/started").await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body["status"], "starting");
+
+        state.mark_started();
+        let (status, _) = get(&state, "/health/started").await;
+        assert_eq!(status, StatusCode::OK);
+    }
+}
@@ -0,0 +1,1156 @@
+//! Job routes
+//!
+//! - `GET /api/jobs`: most recent jobs across the priority streams, newest
+//!   first; `?labels=source=gitlab,event=push` keeps the jobs with all of
+//!   the given labels
+//! - `POST /api/jobs`: queue a build for a [`JobTrigger`] under a new ID
+//! - `GET /api/jobs/:id/status`: current status of a job
+//! - `GET /api/jobs/:id/logs`: build output written so far; with
+//!   `?follow=true`, a stream of server-sent events following the output
+//!   until the job finishes
+//! - `GET /api/jobs/:id/test-results`: test report parsed by the agent from
+//!   the job's JUnit XML output
+//! - `GET /api/jobs/:id/benchmarks`: benchmark results of the job's `bench`
+//!   step
+//! - `GET /api/jobs/:id/coverage`: line coverage of the job's `coverage` step
+//! - `GET /api/jobs/:id/dependencies`: outdated dependencies found by the
+//!   job's `outdated` step
+//! - `GET /api/jobs/:id/unused-deps`: unused dependencies found by the job's
+//!   `udeps` step
+//! - `GET /api/jobs/:id/scan-results`: vulnerabilities found by the job's
+//!   `security-scan` step
+//! - `GET /api/jobs/:id/steps/:step/full-log`: full output of a step too
+//!   long to keep in the step result; served from Redis or redirected to
+//!   where the agent uploaded it
+//! - `POST /api/jobs/:id/retry`: queue a finished job again under a new ID
+//! - `POST /api/jobs/:id/cancel`: mark a job that has not finished as
+//!   cancelled
+//! - `POST /api/jobs/:id/dependents`: jobs held until the job succeeds, and
+//!   the jobs held until those succeed
+//! - `GET /api/jobs/:id/trace`: request and trace that queued a job, and the
+//!   log stream its agent writes to
+//! - `GET /api/jobs/dead`: jobs that failed every retry, most recent first
+//! - `POST /api/jobs/dead/:id/requeue`: move a dead job back to the queue for
+//!   its priority with its retry count reset
+//!
+//! Job list and status responses are cached briefly, see [`crate::cache`].
+
+use axum::extract::{Path, Query, State};
+use axum::http::header::CONTENT_TYPE;
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::{IntoResponse, Redirect, Response};
+use axum::Json;
+use chrono::{DateTime, TimeZone, Utc};
+use futures::stream::{self, Stream, StreamExt};
+use redis::AsyncCommands;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+use std::io::Read;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{info, warn};
+
+use crate::error::{ApiError, ApiResult};
+use crate::log_stream::LogEvent;
+use crate::routes::queue;
+use crate::state::AppState;
+use raibid_common::benchmark::BenchResult;
+use raibid_common::coverage::CoverageReport;
+use raibid_common::dependencies::{OutdatedReport, UnusedDepsReport};
+use raibid_common::job_deps::{self, DependentJob};
+use raibid_common::jobs::{
+    benchmarks_key, coverage_key, dead_letter_stream, dependencies_key, format_label_selector,
+    full_log_key, job_key, log_stream_key, matches_labels, parse_label_selector, scan_results_key,
+    step_logs_key, test_results_key, unused_deps_key, DeadJob, Job, JobBuilder, JobPriority,
+    JobStatus, JobTrigger, QueuedJob, JOB_CANCEL_CHANNEL, JOB_FIELD_REQUEST_ID, JOB_FIELD_STATUS,
+    JOB_FIELD_TRACE_ID, LOG_EVENT_DONE, LOG_EVENT_ENTRY, STREAM_FIELD_JOB_ID,
+};
+use raibid_common::scan::ScanReport;
+use raibid_common::test_report::TestReport;
+
+/// Number of jobs returned by `GET /api/jobs` when no `limit` is given
+const DEFAULT_JOB_LIST_LIMIT: usize = 100;
+
+/// Query parameters of `GET /api/jobs`
+#[derive(Debug, Deserialize)]
+pub struct JobsQuery {
+    /// Maximum number of jobs to return
+    pub limit: Option<usize>,
+    /// Only return jobs with all of these labels, given as a selector like
+    /// `source=gitlab,event=push`
+    #[serde(default, deserialize_with = "deserialize_label_selector")]
+    pub labels: Option<HashMap<String, String>>,
+}
+
+/// Parse the `labels` query parameter with [`parse_label_selector`]
+fn deserialize_label_selector<'de, D>(
+    deserializer: D,
+) -> Result<Option<HashMap<String, String>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Option::<String>::deserialize(deserializer)?
+        .map(|selector| parse_label_selector(&selector))
+        .transpose()
+        .map_err(|e| serde::de::Error::custom(format!("{:#}", e)))
+}
+
+/// Body of `GET /api/jobs/:id/status`
+#[derive(Debug, Serialize)]
+pub struct JobStatusInfo {
+    /// Job identifier
+    pub id: String,
+    /// Current status
+    pub status: JobStatus,
+}
+
+/// Body of `GET /api/jobs/:id/trace`
+#[derive(Debug, PartialEq, Serialize)]
+pub struct JobTrace {
+    /// Job identifier
+    pub job_id: String,
+    /// `X-Request-Id` of the request that queued the job
+    pub request_id: Option<String>,
+    /// Trace ID of the request that queued the job
+    pub trace_id: Option<String>,
+    /// Redis stream holding the job's build output
+    pub log_stream: String,
+    /// Number of entries in the log stream
+    pub log_entries: u64,
+}
+
+/// `GET /api/jobs`
+pub async fn list(
+    State(state): State<AppState>,
+    Query(query): Query<JobsQuery>,
+    headers: HeaderMap,
+) -> ApiResult<Response> {
+    let limit = query.limit.unwrap_or(DEFAULT_JOB_LIST_LIMIT);
+    let labels = query.labels.unwrap_or_default();
+    let cache_key = format!("limit={}&labels={}", limit, format_label_selector(&labels));
+    let response = state
+        .response_cache()
+        .job_lists
+        .get_or_fetch(&cache_key, || recent_jobs(&state, limit, &labels))
+        .await?;
+
+    Ok(response.respond(&headers))
+}
+
+/// `POST /api/jobs`
+///
+/// Validates the trigger and queues it under a new ID. Invalid triggers are
+/// rejected with `400 Bad Request`, and every trigger with
+/// `503 Service Unavailable` while the server is draining.
+pub async fn create(
+    State(state): State<AppState>,
+    Json(trigger): Json<JobTrigger>,
+) -> ApiResult<(StatusCode, Json<Job>)> {
+    if state.is_draining() {
+        return Err(ApiError::Unavailable(
+            "Server is draining and not accepting new jobs".to_string(),
+        ));
+    }
+    let trigger = JobBuilder::from_trigger(trigger)
+        .build()
+        .map_err(|e| ApiError::BadRequest(format!("{:#}", e)))?;
+    if !state.redis_available() {
+        return Err(ApiError::Unavailable("Redis is not reachable".to_string()));
+    }
+
+    let job = QueuedJob::new(uuid::Uuid::new_v4().to_string(), trigger);
+    let base_stream = state.config().queue_stream.clone();
+    let queued = state
+        .with_redis(|mut conn| {
+            let (base_stream, job) = (base_stream.clone(), job.clone());
+            async move {
+                conn.hset::<_, _, _, ()>(
+                    job_key(&job.id),
+                    JOB_FIELD_STATUS,
+                    JobStatus::Pending.as_str(),
+                )
+                .await?;
+                queue::queue_job(&mut conn, &base_stream, &job).await
+            }
+        })
+        .await?;
+
+    state.response_cache().invalidate_job(&job.id);
+    info!("Queued job {} for {}", job.id, job.trigger.repo);
+    Ok((
+        StatusCode::CREATED,
+        Json(Job {
+            id: job.id,
+            repo: job.trigger.repo,
+            branch: job.trigger.branch,
+            commit: job.trigger.commit,
+            status: JobStatus::Pending,
+            created_at: match queued {
+                queue::Enqueued::Added(entry_id) => entry_time(&entry_id),
+                _ => None,
+            }
+            .unwrap_or_else(Utc::now),
+            labels: job.trigger.labels,
+        }),
+    ))
+}
+
+/// `GET /api/jobs/:id/status`
+pub async fn status(
+    State(state): State<AppState>,
+    Path(job_id): Path<String>,
+    headers: HeaderMap,
+) -> ApiResult<Response> {
+    let response = state
+        .response_cache()
+        .job_statuses
+        .get_or_fetch(&job_id, || async {
+            let status = job_status(&state, &job_id)
+                .await?
+                .ok_or_else(|| ApiError::NotFound(format!("Job {} not found", job_id)))?;
+            Ok(JobStatusInfo {
+                id: job_id.clone(),
+                status,
+            })
+        })
+        .await?;
+
+    Ok(response.respond(&headers))
+}
+
+/// Read the `limit` most recent jobs with all of `labels` from the priority
+/// streams
+///
+/// Jobs requeued under the same ID are listed once, with their latest entry.
+/// Labels are matched among the `limit` most recent entries of each stream.
+pub(crate) async fn recent_jobs(
+    state: &AppState,
+    limit: usize,
+    labels: &HashMap<String, String>,
+) -> ApiResult<Vec<Job>> {
+    if !state.redis_available() {
+        return Err(ApiError::Unavailable("Redis is not reachable".to_string()));
+    }
+
+    let base_stream = state.config().queue_stream.clone();
+    let streams: Vec<String> = JobPriority::ALL
+        .iter()
+        .map(|priority| priority.stream_key(&base_stream))
+        .collect();
+    let entries: Vec<Vec<(String, HashMap<String, String>)>> = state
+        .with_redis(|mut conn| {
+            let mut pipe = redis::pipe();
+            for stream in &streams {
+                pipe.cmd("XREVRANGE")
+                    .arg(stream)
+                    .arg("+")
+                    .arg("-")
+                    .arg("COUNT")
+                    .arg(limit);
+            }
+            async move { pipe.query_async(&mut conn).await }
+        })
+        .await?;
+
+    let mut queued: Vec<(DateTime<Utc>, QueuedJob)> = entries
+        .iter()
+        .flatten()
+        .filter_map(|(entry_id, fields)| {
+            match (entry_time(entry_id), QueuedJob::from_stream_fields(fields)) {
+                (Some(created_at), Ok(job)) => Some((created_at, job)),
+                (_, Err(e)) => {
+                    warn!("Skipping job stream entry {}: {:#}", entry_id, e);
+                    None
+                }
+                (None, _) => None,
+            }
+        })
+        .filter(|(_, job)| matches_labels(&job.trigger.labels, labels))
+        .collect();
+    queued.sort_by_key(|(created_at, _)| Reverse(*created_at));
+    let mut seen = HashSet::new();
+    queued.retain(|(_, job)| seen.insert(job.id.clone()));
+    queued.truncate(limit);
+
+    if queued.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let keys: Vec<String> = queued.iter().map(|(_, job)| job_key(&job.id)).collect();
+    let statuses: Vec<Option<String>> = state
+        .with_redis(|mut conn| {
+            let mut pipe = redis::pipe();
+            for key in &keys {
+                pipe.hget(key, JOB_FIELD_STATUS);
+            }
+            async move { pipe.query_async(&mut conn).await }
+        })
+        .await?;
+
+    Ok(queued
+        .into_iter()
+        .zip(statuses)
+        .map(|((created_at, job), status)| Job {
+            id: job.id,
+            repo: job.trigger.repo,
+            branch: job.trigger.branch,
+            commit: job.trigger.commit,
+            // Agents set the status when they pick a job up
+            status: status
+                .and_then(|status| status.parse().ok())
+                .unwrap_or(JobStatus::Pending),
+            created_at,
+            labels: job.trigger.labels,
+        })
+        .collect())
+}
+
+/// Status recorded in a job's hash, if the job exists
+async fn job_status(state: &AppState, job_id: &str) -> ApiResult<Option<JobStatus>> {
+    if !state.redis_available() {
+        return Err(ApiError::Unavailable("Redis is not reachable".to_string()));
+    }
+
+    let key = job_key(job_id);
+    let status: Option<String> = state
+        .with_redis(|mut conn| {
+            let key = key.clone();
+            async move { conn.hget(&key, JOB_FIELD_STATUS).await }
+        })
+        .await?;
+
+    status
+        .map(|status| status.parse().map_err(ApiError::Internal))
+        .transpose()
+}
+
+/// Time a stream entry was added, from its `<milliseconds>-<sequence>` ID
+fn entry_time(entry_id: &str) -> Option<DateTime<Utc>> {
+    let millis = entry_id.split('-').next()?.parse().ok()?;
+    Utc.timestamp_millis_opt(millis).single()
+}
+
+/// Query parameters of `GET /api/jobs/:id/logs`
+#[derive(Debug, Default, Deserialize)]
+pub struct LogsQuery {
+    /// Stream new output as server-sent events until the job finishes
+    #[serde(default)]
+    pub follow: bool,
+}
+
+/// `GET /api/jobs/:id/logs`
+///
+/// Without `follow`, the output written so far as a JSON array. With it, a
+/// `log` event per line, starting with earlier output, and a final `done`
+/// event with the job's status once it finishes.
+pub async fn logs(
+    State(state): State<AppState>,
+    Path(job_id): Path<String>,
+    Query(query): Query<LogsQuery>,
+) -> ApiResult<Response> {
+    if query.follow {
+        return Ok(Sse::new(follow_logs(&state, &job_id))
+            .keep_alive(KeepAlive::default())
+            .into_response());
+    }
+
+    let entries = state.logs().snapshot(&job_id).await?;
+    Ok(Json(entries).into_response())
+}
+
+/// Server-sent events for a job's earlier and new log output
+fn follow_logs(state: &AppState, job_id: &str) -> impl Stream<Item = Result<Event, axum::Error>> {
+    let subscription = state.logs().subscribe(job_id);
+    let job_id = job_id.to_string();
+
+    let backlog = stream::iter(subscription.backlog.into_iter().map(LogEvent::Entry));
+    let live = stream::unfold(Some(subscription.events), move |events| {
+        let job_id = job_id.clone();
+        async move {
+            let mut events = events?;
+            loop {
+                match events.recv().await {
+                    Ok(event @ LogEvent::Entry(_)) => return Some((event, Some(events))),
+                    // End the stream after the final event
+                    Ok(event @ LogEvent::Finished(_)) => return Some((event, None)),
+                    Err(RecvError::Lagged(skipped)) => warn!(
+                        "Log follower for job {} fell behind, skipped {} entries",
+                        job_id, skipped
+                    ),
+                    Err(RecvError::Closed) => return None,
+                }
+            }
+        }
+    });
+
+    backlog.chain(live).map(|event| match event {
+        LogEvent::Entry(entry) => Event::default().event(LOG_EVENT_ENTRY).json_data(entry),
+        LogEvent::Finished(status) => {
+            Ok(Event::default().event(LOG_EVENT_DONE).data(status.as_str()))
+        }
+    })
+}
+
+/// `GET /api/jobs/:id/test-results`
+pub async fn test_results(
+    State(state): State<AppState>,
+    Path(job_id): Path<String>,
+) -> ApiResult<Json<TestReport>> {
+    let report = get_json(&state, test_results_key(&job_id), "test results")
+        .await?
+        .ok_or_else(|| ApiError::NotFound(format!("No test results for job {}", job_id)))?;
+
+    Ok(Json(report))
+}
+
+/// `GET /api/jobs/:id/benchmarks`
+pub async fn benchmarks(
+    State(state): State<AppState>,
+    Path(job_id): Path<String>,
+) -> ApiResult<Json<Vec<BenchResult>>> {
+    let results = get_json(&state, benchmarks_key(&job_id), "benchmark results")
+        .await?
+        .ok_or_else(|| ApiError::NotFound(format!("No benchmark results for job {}", job_id)))?;
+
+    Ok(Json(results))
+}
+
+/// `GET /api/jobs/:id/coverage`
+pub async fn coverage(
+    State(state): State<AppState>,
+    Path(job_id): Path<String>,
+) -> ApiResult<Json<CoverageReport>> {
+    let report = get_json(&state, coverage_key(&job_id), "coverage")
+        .await?
+        .ok_or_else(|| ApiError::NotFound(format!("No coverage for job {}", job_id)))?;
+
+    Ok(Json(report))
+}
+
+/// `GET /api/jobs/:id/dependencies`
+pub async fn dependencies(
+    State(state): State<AppState>,
+    Path(job_id): Path<String>,
+) -> ApiResult<Json<OutdatedReport>> {
+    let report = get_json(&state, dependencies_key(&job_id), "dependency report")
+        .await?
+        .ok_or_else(|| ApiError::NotFound(format!("No dependency report for job {}", job_id)))?;
+
+    Ok(Json(report))
+}
+
+/// `GET /api/jobs/:id/unused-deps`
+pub async fn unused_deps(
+    State(state): State<AppState>,
+    Path(job_id): Path<String>,
+) -> ApiResult<Json<UnusedDepsReport>> {
+    let report = get_json(&state, unused_deps_key(&job_id), "unused dependencies")
+        .await?
+        .ok_or_else(|| ApiError::NotFound(format!("No unused dependencies for job {}", job_id)))?;
+
+    Ok(Json(report))
+}
+
+/// `GET /api/jobs/:id/scan-results`
+pub async fn scan_results(
+    State(state): State<AppState>,
+    Path(job_id): Path<String>,
+) -> ApiResult<Json<ScanReport>> {
+    let report = get_json(&state, scan_results_key(&job_id), "scan results")
+        .await?
+        .ok_or_else(|| ApiError::NotFound(format!("No scan results for job {}", job_id)))?;
+
+    Ok(Json(report))
+}
+
+/// `GET /api/jobs/:id/steps/:step/full-log`
+pub async fn full_log(
+    State(state): State<AppState>,
+    Path((job_id, step)): Path<(String, String)>,
+) -> ApiResult<Response> {
+    if !state.redis_available() {
+        return Err(ApiError::Unavailable("Redis is not reachable".to_string()));
+    }
+
+    let (log_key, urls_key) = (full_log_key(&job_id, &step), step_logs_key(&job_id));
+    let (log, url): (Option<Vec<u8>>, Option<String>) = state
+        .with_redis(|mut conn| {
+            let mut pipe = redis::pipe();
+            pipe.cmd("GET")
+                .arg(&log_key)
+                .cmd("HGET")
+                .arg(&urls_key)
+                .arg(&step);
+            async move { pipe.query_async(&mut conn).await }
+        })
+        .await?;
+
+    full_log_response(&job_id, &step, log, url)
+}
+
+/// The gzipped output kept in Redis, or a redirect to where it was uploaded
+fn full_log_response(
+    job_id: &str,
+    step: &str,
+    log: Option<Vec<u8>>,
+    url: Option<String>,
+) -> ApiResult<Response> {
+    if let Some(log) = log {
+        let mut output = String::new();
+        flate2::read::GzDecoder::new(log.as_slice())
+            .read_to_string(&mut output)
+            .map_err(|e| {
+                ApiError::Internal(anyhow::anyhow!(
+                    "Invalid log of step {} of job {}: {}",
+                    step,
+                    job_id,
+                    e
+                ))
+            })?;
+        return Ok(([(CONTENT_TYPE, "text/plain; charset=utf-8")], output).into_response());
+    }
+
+    match url {
+        Some(url) => Ok(Redirect::temporary(&url).into_response()),
+        None => Err(ApiError::NotFound(format!(
+            "No stored log for step {} of job {}",
+            step, job_id
+        ))),
+    }
+}
+
+/// Number of dead jobs returned when no `limit` is given
+const DEFAULT_DEAD_JOB_LIMIT: usize = 100;
+
+/// Query parameters of `GET /api/jobs/dead`
+#[derive(Debug, Deserialize)]
+pub struct DeadJobsQuery {
+    /// Maximum number of jobs to return
+    pub limit: Option<usize>,
+}
+
+/// `GET /api/jobs/dead`
+pub async fn dead(
+    State(state): State<AppState>,
+    Query(query): Query<DeadJobsQuery>,
+) -> ApiResult<Json<Vec<DeadJob>>> {
+    if !state.redis_available() {
+        return Err(ApiError::Unavailable("Redis is not reachable".to_string()));
+    }
+
+    let stream = dead_letter_stream(&state.config().queue_stream);
+    let limit = query.limit.unwrap_or(DEFAULT_DEAD_JOB_LIMIT);
+    let entries: Vec<(String, HashMap<String, String>)> = state
+        .with_redis(|mut conn| {
+            let stream = stream.clone();
+            async move {
+                redis::cmd("XREVRANGE")
+                    .arg(&stream)
+                    .arg("+")
+                    .arg("-")
+                    .arg("COUNT")
+                    .arg(limit)
+                    .query_async(&mut conn)
+                    .await
+            }
+        })
+        .await?;
+
+    let jobs = entries
+        .iter()
+        .filter_map(
+            |(entry_id, fields)| match DeadJob::from_stream_fields(fields) {
+                Ok(job) => Some(job),
+                Err(e) => {
+                    warn!("Skipping dead letter entry {}: {:#}", entry_id, e);
+                    None
+                }
+            },
+        )
+        .collect();
+
+    Ok(Json(jobs))
+}
+
+/// `POST /api/jobs/dead/:id/requeue`
+///
+/// The job keeps its ID, so its logs and status stay in one place.
+pub async fn requeue(
+    State(state): State<AppState>,
+    Path(job_id): Path<String>,
+) -> ApiResult<StatusCode> {
+    if !state.redis_available() {
+        return Err(ApiError::Unavailable("Redis is not reachable".to_string()));
+    }
+
+    let base_stream = state.config().queue_stream.clone();
+    let stream = dead_letter_stream(&base_stream);
+    // The stream is trimmed by the agents, so a full scan stays bounded
+    let entries: Vec<(String, HashMap<String, String>)> = state
+        .with_redis(|mut conn| {
+            let stream = stream.clone();
+            async move {
+                redis::cmd("XRANGE")
+                    .arg(&stream)
+                    .arg("-")
+                    .arg("+")
+                    .query_async(&mut conn)
+                    .await
+            }
+        })
+        .await?;
+
+    let (entry_id, dead) = entries
+        .iter()
+        .filter(|(_, fields)| fields.get(STREAM_FIELD_JOB_ID) == Some(&job_id))
+        .find_map(|(entry_id, fields)| {
+            DeadJob::from_stream_fields(fields)
+                .ok()
+                .map(|dead| (entry_id.clone(), dead))
+        })
+        .ok_or_else(|| ApiError::NotFound(format!("Job {} is not a dead job", job_id)))?;
+
+    let job = dead.requeue();
+    let fields = job.to_stream_fields()?;
+    let queue = job.trigger.priority.stream_key(&base_stream);
+
+    state
+        .with_redis(|mut conn| {
+            let mut pipe = redis::pipe();
+            pipe.atomic()
+                .cmd("HSET")
+                .arg(job_key(&job.id))
+                .arg(JOB_FIELD_STATUS)
+                .arg(JobStatus::Pending.as_str())
+                .ignore()
+                .cmd("XADD")
+                .arg(&queue)
+                .arg("*")
+                .arg(&fields)
+                .ignore()
+                .cmd("XDEL")
+                .arg(&stream)
+                .arg(&entry_id)
+                .ignore();
+            async move { pipe.query_async::<_, ()>(&mut conn).await }
+        })
+        .await?;
+
+    state.response_cache().invalidate_job(&job.id);
+    info!("Requeued dead job {} on '{}'", job.id, queue);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// `POST /api/jobs/:id/retry`
+///
+/// Queues a copy of a finished job under a new ID with `retry_count`
+/// incremented and `parent_job_id` set to the original, the same way agents
+/// retry failed jobs. Jobs that have not finished are rejected with
+/// `409 Conflict`.
+pub async fn retry(
+    State(state): State<AppState>,
+    Path(job_id): Path<String>,
+) -> ApiResult<(StatusCode, Json<Job>)> {
+    let status = job_status(&state, &job_id)
+        .await?
+        .ok_or_else(|| ApiError::NotFound(format!("Job {} not found", job_id)))?;
+    check_retryable(&job_id, status)?;
+
+    let original = find_queued_job(&state, &job_id)
+        .await?
+        .ok_or_else(|| ApiError::NotFound(format!("Job {} is no longer queued", job_id)))?;
+    let job = original.retry(uuid::Uuid::new_v4().to_string());
+
+    let base_stream = state.config().queue_stream.clone();
+    let queued = state
+        .with_redis(|mut conn| {
+            let (base_stream, job) = (base_stream.clone(), job.clone());
+            async move {
+                conn.hset::<_, _, _, ()>(
+                    job_key(&job.id),
+                    JOB_FIELD_STATUS,
+                    JobStatus::Pending.as_str(),
+                )
+                .await?;
+                queue::queue_job(&mut conn, &base_stream, &job).await
+            }
+        })
+        .await?;
+
+    state.response_cache().invalidate_job(&job.id);
+    info!(
+        "Queued job {} as retry {} of job {}",
+        job.id, job.retry_count, job_id
+    );
+    Ok((
+        StatusCode::CREATED,
+        Json(Job {
+            id: job.id,
+            repo: job.trigger.repo,
+            branch: job.trigger.branch,
+            commit: job.trigger.commit,
+            status: JobStatus::Pending,
+            created_at: match queued {
+                queue::Enqueued::Added(entry_id) => entry_time(&entry_id),
+                _ => None,
+            }
+            .unwrap_or_else(Utc::now),
+            labels: job.trigger.labels,
+        }),
+    ))
+}
+
+/// `POST /api/jobs/:id/cancel`
+///
+/// Marks a job that has not finished as cancelled. Finished jobs are
+/// rejected with `409 Conflict`.
+pub async fn cancel(
+    State(state): State<AppState>,
+    Path(job_id): Path<String>,
+) -> ApiResult<StatusCode> {
+    cancel_job(&state, &job_id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Mark a job that has not finished as cancelled
+///
+/// Jobs no agent has picked up yet have no status and count as pending.
+pub(crate) async fn cancel_job(state: &AppState, job_id: &str) -> ApiResult<()> {
+    let status = match job_status(state, job_id).await? {
+        Some(status) => status,
+        None if find_queued_job(state, job_id).await?.is_some() => JobStatus::Pending,
+        None => return Err(ApiError::NotFound(format!("Job {} not found", job_id))),
+    };
+    check_cancellable(job_id, status)?;
+
+    state
+        .with_redis(|mut conn| {
+            let job_id = job_id.to_string();
+            async move { mark_cancelled(&mut conn, &job_id).await }
+        })
+        .await?;
+
+    state.response_cache().invalidate_job(job_id);
+    info!("Cancelled job {}", job_id);
+    Ok(())
+}
+
+/// Set a job's status to cancelled and publish its ID on
+/// [`JOB_CANCEL_CHANNEL`], so the agent running it stops the build
+async fn mark_cancelled<C>(conn: &mut C, job_id: &str) -> redis::RedisResult<()>
+where
+    C: redis::aio::ConnectionLike + Send,
+{
+    redis::pipe()
+        .atomic()
+        .hset(
+            job_key(job_id),
+            JOB_FIELD_STATUS,
+            JobStatus::Cancelled.as_str(),
+        )
+        .ignore()
+        .publish(JOB_CANCEL_CHANNEL, job_id)
+        .ignore()
+        .query_async(conn)
+        .await
+}
+
+/// Cancel the jobs with all of `labels` building `commit` that have not
+/// finished, returning their IDs
+///
+/// Only the [`DEFAULT_JOB_LIST_LIMIT`] most recent jobs are considered.
+pub(crate) async fn cancel_jobs_for_commit(
+    state: &AppState,
+    labels: &HashMap<String, String>,
+    commit: &str,
+) -> ApiResult<Vec<String>> {
+    let jobs = recent_jobs(state, DEFAULT_JOB_LIST_LIMIT, labels).await?;
+    let mut cancelled = Vec::new();
+    for job in jobs {
+        if job.commit.as_deref() == Some(commit) && !job.status.is_terminal() {
+            cancel_job(state, &job.id).await?;
+            cancelled.push(job.id);
+        }
+    }
+    Ok(cancelled)
+}
+
+/// `POST /api/jobs/:id/dependents`
+///
+/// Lists the jobs waiting for a job, then the jobs waiting for those, with
+/// the number of dependencies each is still waiting for. Jobs leave the
+/// chain once they are queued.
+pub async fn dependents(
+    State(state): State<AppState>,
+    Path(job_id): Path<String>,
+) -> ApiResult<Json<Vec<DependentJob>>> {
+    if !state.redis_available() {
+        return Err(ApiError::Unavailable("Redis is not reachable".to_string()));
+    }
+
+    let chain = state
+        .with_redis(|mut conn| {
+            let job_id = job_id.clone();
+            async move { job_deps::dependent_chain(&mut conn, &job_id).await }
+        })
+        .await?;
+    Ok(Json(chain))
+}
+
+/// `GET /api/jobs/:id/trace`
+///
+/// Links a job to the webhook or API request that queued it and to the log
+/// stream its agent writes to; agents log under a span carrying the same
+/// request ID.
+pub async fn trace(
+    State(state): State<AppState>,
+    Path(job_id): Path<String>,
+) -> ApiResult<Json<JobTrace>> {
+    if !state.redis_available() {
+        return Err(ApiError::Unavailable("Redis is not reachable".to_string()));
+    }
+
+    let (key, log_stream) = (job_key(&job_id), log_stream_key(&job_id));
+    let (fields, log_entries): (HashMap<String, String>, u64) = state
+        .with_redis(|mut conn| {
+            let mut pipe = redis::pipe();
+            pipe.cmd("HGETALL").arg(&key).cmd("XLEN").arg(&log_stream);
+            async move { pipe.query_async(&mut conn).await }
+        })
+        .await?;
+
+    let trace = job_trace(&job_id, fields, log_entries)
+        .ok_or_else(|| ApiError::NotFound(format!("Job {} not found", job_id)))?;
+    Ok(Json(trace))
+}
+
+/// Trace of a job from its hash fields; `None` if the job does not exist
+fn job_trace(
+    job_id: &str,
+    mut fields: HashMap<String, String>,
+    log_entries: u64,
+) -> Option<JobTrace> {
+    if fields.is_empty() {
+        return None;
+    }
+    Some(JobTrace {
+        job_id: job_id.to_string(),
+        request_id: fields.remove(JOB_FIELD_REQUEST_ID),
+        trace_id: fields.remove(JOB_FIELD_TRACE_ID),
+        log_stream: log_stream_key(job_id),
+        log_entries,
+    })
+}
+
+/// Reject retries of jobs that are still queued or running
+fn check_retryable(job_id: &str, status: JobStatus) -> ApiResult<()> {
+    if status.is_terminal() {
+        return Ok(());
+    }
+    Err(ApiError::Conflict(format!(
+        "Job {} is {} and can only be retried once it has finished",
+        job_id, status
+    )))
+}
+
+/// Reject cancellation of jobs that have finished
+fn check_cancellable(job_id: &str, status: JobStatus) -> ApiResult<()> {
+    if !status.is_terminal() {
+        return Ok(());
+    }
+    Err(ApiError::Conflict(format!(
+        "Job {} is {} and can no longer be cancelled",
+        job_id, status
+    )))
+}
+
+/// Latest entry for `job_id` on the priority streams
+///
+/// The streams are trimmed by the agents, so a full scan stays bounded.
+async fn find_queued_job(state: &AppState, job_id: &str) -> ApiResult<Option<QueuedJob>> {
+    let base_stream = state.config().queue_stream.clone();
+    let streams: Vec<String> = JobPriority::ALL
+        .iter()
+        .map(|priority| priority.stream_key(&base_stream))
+        .collect();
+    let entries: Vec<Vec<(String, HashMap<String, String>)>> = state
+        .with_redis(|mut conn| {
+            let mut pipe = redis::pipe();
+            for stream in &streams {
+                pipe.cmd("XREVRANGE").arg(stream).arg("+").arg("-");
+            }
+            async move { pipe.query_async(&mut conn).await }
+        })
+        .await?;
+
+    Ok(entries
+        .iter()
+        .flatten()
+        .filter(|(_, fields)| fields.get(STREAM_FIELD_JOB_ID).map(String::as_str) == Some(job_id))
+        .max_by(|a, b| entry_time(&a.0).cmp(&entry_time(&b.0)))
+        .map(|(_, fields)| QueuedJob::from_stream_fields(fields))
+        .transpose()?)
+}
+
+/// Read and decode a JSON value stored by an agent
+///
+/// Returns `None` if the key does not exist; `what` names the value in errors.
+pub(crate) async fn get_json<T: DeserializeOwned>(
+    state: &AppState,
+    key: String,
+    what: &str,
+) -> ApiResult<Option<T>> {
+    if !state.redis_available() {
+        return Err(ApiError::Unavailable("Redis is not reachable".to_string()));
+    }
+
+    let json: Option<String> = state
+        .with_redis(|mut conn| {
+            let key = key.clone();
+            async move { conn.get(&key).await }
+        })
+        .await?;
+
+    json.map(|json| {
+        serde_json::from_str(&json)
+            .map_err(|e| ApiError::Internal(anyhow::anyhow!("Invalid {} at {}: {}", what, key, e)))
+    })
+    .transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::log_stream::tests::MockLogSource;
+    use crate::routes;
+    use crate::ServerConfig;
+    use axum::body::Body;
+    use axum::http::header::CONTENT_TYPE;
+    use axum::http::Request;
+    use raibid_common::jobs::JobLogEntry;
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tower::ServiceExt;
+
+    #[test]
+    fn test_entry_time() {
+        assert_eq!(
+            entry_time("1700000000123-0"),
+            Utc.timestamp_millis_opt(1_700_000_000_123).single()
+        );
+        assert_eq!(entry_time("not-an-id"), None);
+    }
+
+    #[test]
+    fn test_jobs_query_labels() {
+        let uri = "/api/jobs?limit=5&labels=source%3Dgitlab%2Cevent%3Dpush"
+            .parse()
+            .unwrap();
+        let Query(query) = Query::<JobsQuery>::try_from_uri(&uri).unwrap();
+        assert_eq!(query.limit, Some(5));
+        let labels = query.labels.unwrap();
+        assert_eq!(labels["source"], "gitlab");
+        assert_eq!(labels["event"], "push");
+
+        let uri = "/api/jobs".parse().unwrap();
+        assert_eq!(
+            Query::<JobsQuery>::try_from_uri(&uri).unwrap().0.labels,
+            None
+        );
+
+        let uri = "/api/jobs?labels=source".parse().unwrap();
+        assert!(Query::<JobsQuery>::try_from_uri(&uri).is_err());
+    }
+
+    async fn get_logs(source: Arc<MockLogSource>, uri: &str) -> (Option<String>, String) {
+        let state = AppState::new(ServerConfig::default())
+            .unwrap()
+            .with_log_source(source);
+        let response = routes::router(state)
+            .oneshot(Request::get(uri).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let content_type = response
+            .headers()
+            .get(CONTENT_TYPE)
+            .map(|value| value.to_str().unwrap().to_string());
+        let body = tokio::time::timeout(
+            Duration::from_secs(5),
+            axum::body::to_bytes(response.into_body(), usize::MAX),
+        )
+        .await
+        .expect("log stream did not end")
+        .unwrap();
+        (content_type, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_logs_snapshot() {
+        let source = Arc::new(MockLogSource::default());
+        source.push("job-1", "Compiling raibid-cli");
+        source.push("job-1", "Finished dev profile");
+
+        let (_, body) = get_logs(source, "/api/jobs/job-1/logs").await;
+        let entries: Vec<JobLogEntry> = serde_json::from_str(&body).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[1].message, "Finished dev profile");
+    }
+
+    #[tokio::test]
+    async fn test_logs_follow_until_done() {
+        let source = Arc::new(MockLogSource::default());
+        source.push("job-1", "Compiling raibid-cli");
+        source.push("job-1", "test result: ok");
+        source.set_status("job-1", JobStatus::Success);
+
+        let (content_type, body) = get_logs(source, "/api/jobs/job-1/logs?follow=true").await;
+        assert_eq!(content_type.as_deref(), Some("text/event-stream"));
+
+        let events: Vec<&str> = body.split("\n\n").filter(|e| !e.is_empty()).collect();
+        assert_eq!(events.len(), 3, "{}", body);
+        assert!(events[0].starts_with("event: log\ndata: {"));
+        assert!(events[0].contains(r#""message":"Compiling raibid-cli""#));
+        assert!(events[1].contains(r#""message":"test result: ok""#));
+        assert_eq!(events[2], "event: done\ndata: success");
+    }
+
+    #[test]
+    fn test_job_trace() {
+        assert_eq!(job_trace("job-1", HashMap::new(), 0), None);
+
+        let fields = HashMap::from([
+            (JOB_FIELD_STATUS.to_string(), "running".to_string()),
+            (JOB_FIELD_REQUEST_ID.to_string(), "req-42".to_string()),
+            (
+                JOB_FIELD_TRACE_ID.to_string(),
+                "4bf92f3577b34da6a3ce929d0e0e4736".to_string(),
+            ),
+        ]);
+        assert_eq!(
+            job_trace("job-1", fields, 12),
+            Some(JobTrace {
+                job_id: "job-1".to_string(),
+                request_id: Some("req-42".to_string()),
+                trace_id: Some("4bf92f3577b34da6a3ce929d0e0e4736".to_string()),
+                log_stream: "raibid:logs:job-1".to_string(),
+                log_entries: 12,
+            })
+        );
+
+        // Jobs queued outside of a request, such as by the scheduler
+        let fields = HashMap::from([(JOB_FIELD_STATUS.to_string(), "pending".to_string())]);
+        let trace = job_trace("job-2", fields, 0).unwrap();
+        assert_eq!(trace.request_id, None);
+        assert_eq!(trace.trace_id, None);
+    }
+
+    #[tokio::test]
+    async fn test_full_log_response() {
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        std::io::Write::write_all(&mut encoder, b"test case_1 ... ok\n").unwrap();
+        let log = encoder.finish().unwrap();
+
+        let response = full_log_response("job-1", "test", Some(log), None).unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(&body[..], b"test case_1 ... ok\n");
+
+        let url = "https://logs.s3.amazonaws.com/job-1/test.log.gz?X-Amz-Signature=abc";
+        let response = full_log_response("job-1", "test", None, Some(url.to_string())).unwrap();
+        assert_eq!(response.status(), StatusCode::TEMPORARY_REDIRECT);
+        assert_eq!(response.headers()["location"], url);
+
+        assert!(matches!(
+            full_log_response("job-1", "test", None, None),
+            Err(ApiError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_only_finished_jobs_are_retryable() {
+        for status in [JobStatus::Failed, JobStatus::Success, JobStatus::Cancelled] {
+            assert!(check_retryable("job-1", status).is_ok());
+        }
+        for status in [JobStatus::Pending, JobStatus::Running, JobStatus::Retrying] {
+            let err = check_retryable("job-1", status).unwrap_err();
+            assert!(matches!(err, ApiError::Conflict(_)));
+            assert!(err.to_string().contains(status.as_str()));
+        }
+    }
+
+    /// Connection recording the commands of each pipeline sent to it
+    #[derive(Default)]
+    struct RecordingConnection {
+        commands: Vec<Vec<String>>,
+    }
+
+    impl redis::aio::ConnectionLike for RecordingConnection {
+        fn req_packed_command<'a>(
+            &'a mut self,
+            _cmd: &'a redis::Cmd,
+        ) -> redis::RedisFuture<'a, redis::Value> {
+            Box::pin(async { Ok(redis::Value::Okay) })
+        }
+
+        fn req_packed_commands<'a>(
+            &'a mut self,
+            pipeline: &'a redis::Pipeline,
+            _offset: usize,
+            _count: usize,
+        ) -> redis::RedisFuture<'a, Vec<redis::Value>> {
+            for cmd in pipeline.cmd_iter() {
+                self.commands.push(
+                    cmd.args_iter()
+                        .map(|arg| match arg {
+                            redis::Arg::Simple(bytes) => String::from_utf8_lossy(bytes).to_string(),
+                            redis::Arg::Cursor => "0".to_string(),
+                        })
+                        .collect(),
+                );
+            }
+            // The reply to EXEC, one per command of the transaction
+            Box::pin(async {
+                Ok(vec![redis::Value::Bulk(vec![
+                    redis::Value::Int(1),
+                    redis::Value::Int(1),
+                ])])
+            })
+        }
+
+        fn get_db(&self) -> i64 {
+            0
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cancel_publishes_job_id() {
+        let mut conn = RecordingConnection::default();
+        mark_cancelled(&mut conn, "job-1").await.unwrap();
+
+        assert_eq!(
+            conn.commands,
+            vec![
+                vec!["HSET", "raibid:job:job-1", "status", "cancelled"],
+                vec!["PUBLISH", JOB_CANCEL_CHANNEL, "job-1"],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_only_unfinished_jobs_are_cancellable() {
+        for status in [JobStatus::Pending, JobStatus::Running, JobStatus::Retrying] {
+            assert!(check_cancellable("job-1", status).is_ok());
+        }
+        for status in [JobStatus::Failed, JobStatus::Success, JobStatus::Cancelled] {
+            let err = check_cancellable("job-1", status).unwrap_err();
+            assert!(matches!(err, ApiError::Conflict(_)));
+            assert!(err.to_string().contains(status.as_str()));
+        }
+    }
+}
@@ -0,0 +1,168 @@
+//! Log routes
+//!
+//! - `GET /api/logs/errors`: error lines from the build output of the most
+//!   recent jobs, newest first; `?limit=N` caps the number returned
+//!
+//! Lines are matched by their leading marker (`error`, `fatal:`,
+//! `thread '…' panicked`, or a failed test summary) among the last
+//! [`ERROR_SCAN_ENTRIES`] lines of each job's log stream.
+
+use axum::extract::{Query, State};
+use axum::Json;
+use serde::Deserialize;
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use tracing::warn;
+
+use crate::error::ApiResult;
+use crate::routes::jobs;
+use crate::state::AppState;
+use raibid_common::jobs::{log_stream_key, ErrorLogEntry, JobLogEntry, LOG_FIELD_STEP};
+
+/// Number of errors returned when no `limit` is given
+const DEFAULT_ERROR_LIMIT: usize = 20;
+
+/// Number of most recent jobs whose logs are searched
+const ERROR_SCAN_JOBS: usize = 50;
+
+/// Number of lines read from the end of each job's log
+pub const ERROR_SCAN_ENTRIES: usize = 200;
+
+/// Query parameters of `GET /api/logs/errors`
+#[derive(Debug, Deserialize)]
+pub struct ErrorsQuery {
+    /// Maximum number of errors to return
+    pub limit: Option<usize>,
+}
+
+/// `GET /api/logs/errors`
+pub async fn errors(
+    State(state): State<AppState>,
+    Query(query): Query<ErrorsQuery>,
+) -> ApiResult<Json<Vec<ErrorLogEntry>>> {
+    let limit = query.limit.unwrap_or(DEFAULT_ERROR_LIMIT);
+    let recent = jobs::recent_jobs(&state, ERROR_SCAN_JOBS, &HashMap::new()).await?;
+    if recent.is_empty() || limit == 0 {
+        return Ok(Json(Vec::new()));
+    }
+
+    let keys: Vec<String> = recent.iter().map(|job| log_stream_key(&job.id)).collect();
+    let logs: Vec<Vec<(String, HashMap<String, String>)>> = state
+        .with_redis(|mut conn| {
+            let mut pipe = redis::pipe();
+            for key in &keys {
+                pipe.cmd("XREVRANGE")
+                    .arg(key)
+                    .arg("+")
+                    .arg("-")
+                    .arg("COUNT")
+                    .arg(ERROR_SCAN_ENTRIES);
+            }
+            async move { pipe.query_async(&mut conn).await }
+        })
+        .await?;
+
+    let mut errors: Vec<ErrorLogEntry> = recent
+        .iter()
+        .zip(&logs)
+        .flat_map(|(job, entries)| error_entries(&job.id, entries))
+        .collect();
+    errors.sort_by_key(|error| Reverse(error.timestamp));
+    errors.truncate(limit);
+    Ok(Json(errors))
+}
+
+/// Error lines among a job's log stream entries
+fn error_entries(
+    job_id: &str,
+    entries: &[(String, HashMap<String, String>)],
+) -> Vec<ErrorLogEntry> {
+    entries
+        .iter()
+        .filter_map(
+            |(entry_id, fields)| match JobLogEntry::from_stream_fields(fields) {
+                Ok(entry) => Some((entry, fields.get(LOG_FIELD_STEP))),
+                Err(e) => {
+                    warn!("Skipping log entry {} of job {}: {:#}", entry_id, job_id, e);
+                    None
+                }
+            },
+        )
+        .filter(|(entry, _)| is_error(&entry.message))
+        .map(|(entry, step)| ErrorLogEntry {
+            timestamp: entry.timestamp,
+            job_id: job_id.to_string(),
+            step: step.cloned().unwrap_or_default(),
+            message: entry.message,
+        })
+        .collect()
+}
+
+/// Whether a line of build output reports an error
+fn is_error(message: &str) -> bool {
+    let line = message.trim_start();
+    line.starts_with("error")
+        || line.starts_with("fatal:")
+        || line.starts_with("test result: FAILED")
+        || (line.starts_with("thread '") && line.contains("' panicked at"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(timestamp: &str, message: &str, step: Option<&str>) -> HashMap<String, String> {
+        let mut fields = HashMap::from([
+            ("timestamp".to_string(), timestamp.to_string()),
+            ("message".to_string(), message.to_string()),
+        ]);
+        if let Some(step) = step {
+            fields.insert(LOG_FIELD_STEP.to_string(), step.to_string());
+        }
+        fields
+    }
+
+    #[test]
+    fn test_error_entries() {
+        let entries = vec![
+            (
+                "3-0".to_string(),
+                entry(
+                    "2026-10-16T10:02:00Z",
+                    "error[E0308]: mismatched types",
+                    Some("build"),
+                ),
+            ),
+            (
+                "2-0".to_string(),
+                entry(
+                    "2026-10-16T10:01:00Z",
+                    "   Compiling app v0.1.0",
+                    Some("build"),
+                ),
+            ),
+            (
+                "1-0".to_string(),
+                entry("2026-10-16T10:00:00Z", "fatal: repository not found", None),
+            ),
+            ("0-0".to_string(), HashMap::new()),
+        ];
+
+        let errors = error_entries("job-1", &entries);
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0].job_id, "job-1");
+        assert_eq!(errors[0].step, "build");
+        assert_eq!(errors[0].summary(), "error[E0308]: mismatched types");
+        assert_eq!(errors[1].step, "");
+        assert_eq!(errors[1].message, "fatal: repository not found");
+    }
+
+    #[test]
+    fn test_is_error() {
+        assert!(is_error("error: could not compile `app`"));
+        assert!(is_error("thread 'main' panicked at src/main.rs:4:5:"));
+        assert!(is_error("test result: FAILED. 3 passed; 1 failed"));
+        assert!(!is_error("test result: ok. 4 passed; 0 failed"));
+        assert!(!is_error("warning: unused variable: `errors`"));
+    }
+}
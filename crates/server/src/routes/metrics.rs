@@ -0,0 +1,268 @@
+//! Prometheus metrics
+//!
+//! - `GET /metrics` (path configurable via [`ServerConfig::metrics_path`]):
+//!   all metrics in the Prometheus text exposition format
+//!
+//! Counters are recorded through the `metrics` facade as events happen, using
+//! the `record_*` helpers below. Queue gauges are refreshed from Redis on each
+//! scrape. [`track_requests`] records the latency of every request.
+//!
+//! [`ServerConfig::metrics_path`]: crate::ServerConfig::metrics_path
+
+use axum::extract::{MatchedPath, Request, State};
+use axum::http::{header, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use metrics::{counter, describe_counter, describe_gauge, describe_histogram, gauge, histogram};
+use metrics_exporter_prometheus::{Matcher, PrometheusBuilder, PrometheusHandle};
+use std::sync::OnceLock;
+use std::time::Instant;
+use tracing::{debug, warn};
+
+use crate::state::AppState;
+use raibid_common::agents::AgentStatus;
+use raibid_common::jobs::JobStatus;
+
+/// Jobs that reached each status
+pub const JOBS_TOTAL: &str = "raibid_jobs_total";
+/// Jobs delivered to an agent and not yet acknowledged
+pub const JOBS_ACTIVE: &str = "raibid_jobs_active";
+/// Entries in the job streams
+pub const QUEUE_DEPTH: &str = "raibid_queue_depth";
+/// Registered agents by status
+pub const AGENTS_TOTAL: &str = "raibid_agents_total";
+/// Webhook requests by source and response status
+pub const WEBHOOK_REQUESTS_TOTAL: &str = "raibid_webhook_requests_total";
+/// HTTP request latency by route, method, and response status
+pub const HTTP_REQUEST_DURATION: &str = "raibid_http_request_duration_seconds";
+
+/// Histogram buckets for request latency, in seconds
+const LATENCY_BUCKETS: &[f64] = &[
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// Content type of the Prometheus text exposition format
+const PROMETHEUS_CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+
+/// Route label for requests that matched no route
+const UNMATCHED_ROUTE: &str = "unmatched";
+
+static PROMETHEUS: OnceLock<PrometheusHandle> = OnceLock::new();
+
+/// Get the handle of the process-wide Prometheus recorder, installing it first
+///
+/// The `metrics` facade allows a single global recorder, so every
+/// [`AppState`] shares it.
+pub fn prometheus_handle() -> PrometheusHandle {
+    PROMETHEUS
+        .get_or_init(|| {
+            let recorder = PrometheusBuilder::new()
+                .set_buckets_for_metric(
+                    Matcher::Full(HTTP_REQUEST_DURATION.to_string()),
+                    LATENCY_BUCKETS,
+                )
+                .expect("latency buckets are not empty")
+                .build_recorder();
+            let handle = recorder.handle();
+
+            if let Err(e) = metrics::set_global_recorder(recorder) {
+                warn!(
+                    "Metrics recorder already installed, /metrics will be empty: {}",
+                    e
+                );
+            }
+            describe_metrics();
+
+            handle
+        })
+        .clone()
+}
+
+fn describe_metrics() {
+    describe_counter!(JOBS_TOTAL, "Jobs that reached each status");
+    describe_gauge!(
+        JOBS_ACTIVE,
+        "Jobs delivered to an agent and not yet acknowledged"
+    );
+    describe_gauge!(QUEUE_DEPTH, "Entries in the job streams");
+    describe_gauge!(AGENTS_TOTAL, "Registered agents by status");
+    describe_counter!(
+        WEBHOOK_REQUESTS_TOTAL,
+        "Webhook requests by source and response status"
+    );
+    describe_histogram!(HTTP_REQUEST_DURATION, "HTTP request latency in seconds");
+}
+
+/// Count a job reaching `status`
+pub fn record_job_status(status: JobStatus) {
+    counter!(JOBS_TOTAL, "status" => status.as_str().to_string()).increment(1);
+}
+
+/// Set the number of agents currently in `status`
+pub fn record_agent_count(status: AgentStatus, count: u64) {
+    gauge!(AGENTS_TOTAL, "status" => status.as_str().to_string()).set(count as f64);
+}
+
+/// Count a webhook request from `source` (e.g. `gitea`) answered with `status`
+pub fn record_webhook_request(source: &str, status: StatusCode) {
+    counter!(
+        WEBHOOK_REQUESTS_TOTAL,
+        "source" => source.to_string(),
+        "status" => status.as_u16().to_string()
+    )
+    .increment(1);
+}
+
+/// Middleware recording request latency per route and method
+///
+/// Requests are labelled with the route pattern (`/ws/jobs/:job_id/logs`),
+/// not the raw path, to keep label cardinality bounded.
+pub async fn track_requests(request: Request, next: Next) -> Response {
+    let start = Instant::now();
+    let method = request.method().to_string();
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or_else(|| UNMATCHED_ROUTE.to_string());
+
+    let response = next.run(request).await;
+
+    histogram!(
+        HTTP_REQUEST_DURATION,
+        "method" => method,
+        "route" => route,
+        "status" => response.status().as_u16().to_string()
+    )
+    .record(start.elapsed().as_secs_f64());
+
+    response
+}
+
+/// `GET /metrics`
+pub async fn render(State(state): State<AppState>) -> Response {
+    let Some(handle) = state.metrics() else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    if state.redis_available() {
+        if let Err(e) = refresh_queue_gauges(&state).await {
+            // Serve the last known values rather than failing the scrape
+            debug!("Failed to refresh queue metrics: {:#}", e);
+        }
+    }
+
+    (
+        [(header::CONTENT_TYPE, PROMETHEUS_CONTENT_TYPE)],
+        handle.render(),
+    )
+        .into_response()
+}
+
+/// Update queue depth and active job gauges from Redis
+async fn refresh_queue_gauges(state: &AppState) -> anyhow::Result<()> {
+    let stream = state.config().queue_stream.clone();
+    let group = state.config().consumer_group.clone();
+
+    let stats = state
+        .with_redis(|mut conn| {
+            let stream = stream.clone();
+            let group = group.clone();
+            async move { super::queue::fetch_queue_stats(&mut conn, &stream, &group).await }
+        })
+        .await?;
+
+    gauge!(QUEUE_DEPTH).set(stats.total_messages as f64);
+    gauge!(JOBS_ACTIVE).set(stats.pending_count as f64);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::routes;
+    use crate::ServerConfig;
+    use axum::body::Body;
+    use axum::http::Request;
+    use tower::ServiceExt;
+
+    async fn get(state: AppState, path: &str) -> (StatusCode, String) {
+        let response = routes::router(state)
+            .oneshot(Request::get(path).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    fn offline_state(config: ServerConfig) -> AppState {
+        let state = AppState::new(config).unwrap();
+        // Skip the Redis refresh; there is no Redis in unit tests
+        state.set_redis_available(false);
+        state
+    }
+
+    #[tokio::test]
+    async fn test_metrics_endpoint() {
+        let state = offline_state(ServerConfig::default());
+        record_job_status(JobStatus::Success);
+        record_agent_count(AgentStatus::Idle, 3);
+        record_webhook_request("gitea", StatusCode::ACCEPTED);
+
+        // Hit another route first so its latency shows up
+        get(state.clone(), "/api/queue/stats").await;
+        let (status, body) = get(state, "/metrics").await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert!(
+            body.contains(r#"raibid_jobs_total{status="success"}"#),
+            "{}",
+            body
+        );
+        assert!(
+            body.contains(r#"raibid_agents_total{status="idle"} 3"#),
+            "{}",
+            body
+        );
+        assert!(
+            body.contains(r#"raibid_webhook_requests_total{source="gitea",status="202"}"#),
+            "{}",
+            body
+        );
+        assert!(body.contains(r#"route="/api/queue/stats""#), "{}", body);
+        assert!(
+            body.contains("raibid_http_request_duration_seconds_bucket"),
+            "{}",
+            body
+        );
+    }
+
+    #[tokio::test]
+    async fn test_metrics_custom_path() {
+        let state = offline_state(ServerConfig {
+            metrics_path: "/internal/metrics".to_string(),
+            ..Default::default()
+        });
+
+        assert_eq!(
+            get(state.clone(), "/metrics").await.0,
+            StatusCode::NOT_FOUND
+        );
+        assert_eq!(get(state, "/internal/metrics").await.0, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_metrics_disabled() {
+        let state = offline_state(ServerConfig {
+            metrics_enabled: false,
+            ..Default::default()
+        });
+
+        assert!(state.metrics().is_none());
+        assert_eq!(get(state, "/metrics").await.0, StatusCode::NOT_FOUND);
+    }
+}
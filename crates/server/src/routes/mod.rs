@@ -0,0 +1,680 @@
+//! HTTP routes
+//!
+//! Each submodule owns the handlers for one area of the API. [`router`] wires
+//! them together with the shared [`AppState`].
+
+pub mod admin;
+pub mod agents;
+pub mod benchmarks;
+pub mod health;
+pub mod jobs;
+pub mod logs;
+pub mod metrics;
+pub mod queue;
+pub mod schedules;
+pub mod webhooks;
+pub mod ws;
+
+use axum::routing::{delete, get, post};
+use axum::{middleware, Router};
+
+use crate::middleware::{
+    body_limit, compression, dedup, request_id, trace_context, trace_layer, RateLimit,
+    RequireApiKey, API_KEY_QUERY_PARAM,
+};
+use crate::state::AppState;
+
+/// Build the API router
+///
+/// `/api/` routes require an API key and `/admin/` routes an admin key;
+/// `/ws/` routes require an API key too, which WebSocket clients may pass
+/// as `?api_key=` instead. The `/health` routes are public and webhooks
+/// authenticate with their provider's secret and are rate limited per client
+/// when a limit is configured. Repeated webhook deliveries are answered
+/// without queueing another job. Request bodies of every route are limited to
+/// `max_body_size_bytes`. The metrics route and request latency layer are
+/// only added when metrics are enabled. JSON responses are gzipped for
+/// clients accepting it unless compression is disabled. CORS headers are
+/// added around every route when CORS is configured, so pre-flight requests
+/// are answered before authentication. Every request is traced, joining the
+/// trace of its `traceparent` header if it has one, and tagged with the ID of
+/// its `X-Request-Id` header or a generated one.
+pub fn router(state: AppState) -> Router {
+    let api = Router::new()
+        .route("/api/queue/stats", get(queue::stats))
+        .route("/api/jobs", get(jobs::list).post(jobs::create))
+        .route("/api/jobs/dead", get(jobs::dead))
+        .route("/api/jobs/dead/:id/requeue", post(jobs::requeue))
+        .route("/api/jobs/:id/test-results", get(jobs::test_results))
+        .route("/api/jobs/:id/benchmarks", get(jobs::benchmarks))
+        .route("/api/jobs/:id/coverage", get(jobs::coverage))
+        .route("/api/jobs/:id/dependencies", get(jobs::dependencies))
+        .route("/api/jobs/:id/unused-deps", get(jobs::unused_deps))
+        .route("/api/jobs/:id/scan-results", get(jobs::scan_results))
+        .route("/api/jobs/:id/steps/:step/full-log", get(jobs::full_log))
+        .route("/api/jobs/:id/status", get(jobs::status))
+        .route("/api/jobs/:id/logs", get(jobs::logs))
+        .route("/api/jobs/:id/retry", post(jobs::retry))
+        .route("/api/jobs/:id/cancel", post(jobs::cancel))
+        .route("/api/jobs/:id/dependents", post(jobs::dependents))
+        .route("/api/jobs/:id/trace", get(jobs::trace))
+        .route("/api/logs/errors", get(logs::errors))
+        .route("/api/benchmarks/compare", get(benchmarks::compare))
+        .route("/api/agents", get(agents::list))
+        .route("/api/agents/register", post(agents::register))
+        .route("/api/agents/:id", get(agents::show))
+        .route("/api/agents/:id/heartbeat", post(agents::heartbeat))
+        .route("/api/agents/:id/drain", post(agents::drain))
+        .route("/api/agents/:id/restart", post(agents::restart))
+        .route("/api/agents/scale", post(agents::scale))
+        .route("/api/events/agents", get(agents::events))
+        .route(
+            "/api/schedules",
+            get(schedules::list).post(schedules::create),
+        )
+        .route("/api/schedules/:id", delete(schedules::delete))
+        .route_layer(RequireApiKey::new(state.api_keys().clone()));
+
+    let admin = Router::new()
+        .route("/admin/state", get(admin::state))
+        .route("/admin/drain", post(admin::drain))
+        .route("/admin/shutdown", post(admin::shutdown))
+        .route("/admin/reload-config", post(admin::reload_config))
+        .route("/admin/prune-logs", post(admin::prune_job_logs))
+        .route_layer(RequireApiKey::admin(state.admin_keys().clone()));
+
+    let ws = Router::new()
+        .route("/ws/jobs/:job_id/logs", get(ws::job_logs))
+        .route("/ws/agents", get(ws::agent_events))
+        .route_layer(
+            RequireApiKey::new(state.api_keys().clone()).or_query_param(API_KEY_QUERY_PARAM),
+        );
+
+    let mut webhooks = webhooks::routes().route_layer(middleware::from_fn_with_state(
+        state.webhook_dedup().clone(),
+        dedup,
+    ));
+    if let Some(limit) = RateLimit::from_config(&state.config()) {
+        webhooks = webhooks.route_layer(limit);
+    }
+
+    let mut router = Router::new()
+        .merge(api)
+        .merge(admin)
+        .merge(ws)
+        .merge(webhooks)
+        .route("/health", get(health::health))
+        .route("/health/live", get(health::live))
+        .route("/health/ready", get(health::ready))
+        .route("/health/started", get(health::started))
+        .layer(body_limit(state.config().max_body_size_bytes));
+
+    if state.metrics().is_some() {
+        router = router
+            .route(&state.config().metrics_path, get(metrics::render))
+            .layer(middleware::from_fn(metrics::track_requests));
+    }
+
+    if state.config().compression_enabled {
+        router = router.layer(compression());
+    }
+
+    if let Some(cors) = state.cors() {
+        router = router.layer(cors.clone());
+    }
+
+    router
+        .layer(trace_layer())
+        .layer(middleware::from_fn(trace_context))
+        .layer(middleware::from_fn(request_id))
+        .with_state(state)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scaling::{AgentDeployment, ScaleTarget};
+    use crate::{CorsConfig, ServerConfig};
+    use axum::body::Body;
+    use axum::http::header::{
+        ACCEPT_ENCODING, ACCESS_CONTROL_ALLOW_ORIGIN, ACCESS_CONTROL_REQUEST_HEADERS,
+        ACCESS_CONTROL_REQUEST_METHOD, CONTENT_ENCODING, CONTENT_LENGTH, CONTENT_TYPE, ORIGIN,
+    };
+    use axum::http::{Request, StatusCode};
+    use raibid_common::agents::{
+        AgentDetails, AgentInfo, AgentScale, AgentStatus, HeartbeatResponse,
+    };
+    use raibid_common::auth::API_KEY_HEADER;
+    use std::io::Read;
+    use std::sync::{Arc, Mutex};
+    use tower::ServiceExt;
+
+    fn state() -> AppState {
+        let state = AppState::new(ServerConfig {
+            api_keys: vec!["rbd_secret".to_string()],
+            ..Default::default()
+        })
+        .unwrap();
+        state.set_redis_available(false);
+        state
+    }
+
+    async fn status(request: Request<Body>) -> StatusCode {
+        router(state()).oneshot(request).await.unwrap().status()
+    }
+
+    #[tokio::test]
+    async fn test_api_routes_require_key() {
+        let request = Request::get("/api/queue/stats")
+            .body(Body::empty())
+            .unwrap();
+        assert_eq!(status(request).await, StatusCode::UNAUTHORIZED);
+
+        // Authenticated, then fails on the unavailable Redis
+        let request = Request::get("/api/queue/stats")
+            .header(API_KEY_HEADER, "rbd_secret")
+            .body(Body::empty())
+            .unwrap();
+        assert_eq!(status(request).await, StatusCode::SERVICE_UNAVAILABLE);
+    }
+
+    #[tokio::test]
+    async fn test_websocket_routes_require_key() {
+        for path in ["/ws/agents", "/ws/jobs/job-1/logs"] {
+            let request = Request::get(path).body(Body::empty()).unwrap();
+            assert_eq!(status(request).await, StatusCode::UNAUTHORIZED, "{}", path);
+
+            // Authenticated, then rejected as not a WebSocket upgrade
+            let request = Request::get(format!("{}?api_key=rbd_secret", path))
+                .body(Body::empty())
+                .unwrap();
+            assert_ne!(status(request).await, StatusCode::UNAUTHORIZED, "{}", path);
+            let request = Request::get(path)
+                .header(API_KEY_HEADER, "rbd_secret")
+                .body(Body::empty())
+                .unwrap();
+            assert_ne!(status(request).await, StatusCode::UNAUTHORIZED, "{}", path);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cors_preflight() {
+        let state = AppState::new(ServerConfig {
+            api_keys: vec!["rbd_secret".to_string()],
+            cors: Some(CorsConfig::with_origins(vec![
+                "https://ci.example.com".to_string()
+            ])),
+            ..Default::default()
+        })
+        .unwrap();
+        let app = router(state);
+
+        let preflight = |origin: &'static str| {
+            Request::options("/api/queue/stats")
+                .header(ORIGIN, origin)
+                .header(ACCESS_CONTROL_REQUEST_METHOD, "GET")
+                .header(ACCESS_CONTROL_REQUEST_HEADERS, API_KEY_HEADER)
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        // Answered without an API key
+        let response = app
+            .clone()
+            .oneshot(preflight("https://ci.example.com"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()[ACCESS_CONTROL_ALLOW_ORIGIN],
+            "https://ci.example.com"
+        );
+
+        let response = app
+            .clone()
+            .oneshot(preflight("https://evil.example.com"))
+            .await
+            .unwrap();
+        assert!(!response.headers().contains_key(ACCESS_CONTROL_ALLOW_ORIGIN));
+    }
+
+    #[tokio::test]
+    async fn test_cors_disabled_by_default() {
+        let request = Request::options("/api/queue/stats")
+            .header(ORIGIN, "https://ci.example.com")
+            .header(ACCESS_CONTROL_REQUEST_METHOD, "GET")
+            .body(Body::empty())
+            .unwrap();
+        let response = router(state()).oneshot(request).await.unwrap();
+        assert!(!response.headers().contains_key(ACCESS_CONTROL_ALLOW_ORIGIN));
+    }
+
+    #[tokio::test]
+    async fn test_dead_job_routes() {
+        let request = Request::get("/api/jobs/dead?limit=10")
+            .header(API_KEY_HEADER, "rbd_secret")
+            .body(Body::empty())
+            .unwrap();
+        assert_eq!(status(request).await, StatusCode::SERVICE_UNAVAILABLE);
+
+        let request = Request::post("/api/jobs/dead/job-1/requeue")
+            .body(Body::empty())
+            .unwrap();
+        assert_eq!(status(request).await, StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_create_job() {
+        let create = |body: &'static str| {
+            Request::post("/api/jobs")
+                .header(API_KEY_HEADER, "rbd_secret")
+                .header(CONTENT_TYPE, "application/json")
+                .body(Body::from(body))
+                .unwrap()
+        };
+
+        assert_eq!(
+            status(create(r#"{"repo":"not-a-repo"}"#)).await,
+            StatusCode::BAD_REQUEST
+        );
+        // Valid, then fails on the unavailable Redis
+        assert_eq!(
+            status(create(r#"{"repo":"acme/app","branch":"main"}"#)).await,
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+
+        let request = Request::post("/api/jobs")
+            .header(CONTENT_TYPE, "application/json")
+            .body(Body::from(r#"{"repo":"acme/app"}"#))
+            .unwrap();
+        assert_eq!(status(request).await, StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_recent_errors_route() {
+        let request = Request::get("/api/logs/errors?limit=5")
+            .body(Body::empty())
+            .unwrap();
+        assert_eq!(status(request).await, StatusCode::UNAUTHORIZED);
+
+        let request = Request::get("/api/logs/errors?limit=5")
+            .header(API_KEY_HEADER, "rbd_secret")
+            .body(Body::empty())
+            .unwrap();
+        assert_eq!(status(request).await, StatusCode::SERVICE_UNAVAILABLE);
+    }
+
+    #[tokio::test]
+    async fn test_benchmark_compare_validates_query() {
+        let compare = |query: &str| {
+            Request::get(format!("/api/benchmarks/compare?{}", query))
+                .header(API_KEY_HEADER, "rbd_secret")
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        // `head` is required
+        assert_eq!(
+            status(compare("repo=acme/app")).await,
+            StatusCode::BAD_REQUEST
+        );
+        assert_eq!(
+            status(compare("repo=acme/app&head=feature&threshold=-5")).await,
+            StatusCode::BAD_REQUEST
+        );
+        assert_eq!(
+            status(compare("repo=acme/app&head=feature")).await,
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+    }
+
+    #[tokio::test]
+    async fn test_agent_registration() {
+        let app = router(state());
+        let send = |request: Request<Body>| app.clone().oneshot(request);
+
+        let request = Request::post("/api/agents/agent-1/heartbeat")
+            .header(API_KEY_HEADER, "rbd_secret")
+            .body(Body::empty())
+            .unwrap();
+        assert_eq!(send(request).await.unwrap().status(), StatusCode::NOT_FOUND);
+
+        let request = Request::post("/api/agents/register")
+            .header(API_KEY_HEADER, "rbd_secret")
+            .header("content-type", "application/json")
+            .body(Body::from(
+                r#"{"agent_id":"agent-1","version":"0.1.0","capabilities":["rust","docker"]}"#,
+            ))
+            .unwrap();
+        assert_eq!(send(request).await.unwrap().status(), StatusCode::CREATED);
+
+        let request = Request::post("/api/agents/agent-1/heartbeat")
+            .header(API_KEY_HEADER, "rbd_secret")
+            .body(Body::empty())
+            .unwrap();
+        assert_eq!(send(request).await.unwrap().status(), StatusCode::OK);
+
+        let request = Request::get("/api/agents")
+            .header(API_KEY_HEADER, "rbd_secret")
+            .body(Body::empty())
+            .unwrap();
+        let response = send(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let agents: Vec<AgentInfo> = serde_json::from_slice(&body).unwrap();
+        assert_eq!(agents.len(), 1);
+        assert_eq!(agents[0].id, "agent-1");
+        assert_eq!(agents[0].status, AgentStatus::Idle);
+    }
+
+    #[tokio::test]
+    async fn test_agent_events_stream() {
+        use futures::StreamExt;
+        use raibid_common::agents::AgentEvent;
+
+        let state = state();
+        let request = Request::get("/api/events/agents")
+            .header(API_KEY_HEADER, "rbd_secret")
+            .body(Body::empty())
+            .unwrap();
+        let response = router(state.clone()).oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[CONTENT_TYPE], "text/event-stream");
+
+        let event = AgentEvent::now("agent-1", AgentStatus::Offline);
+        state.agent_events().broadcast(event.clone());
+
+        let mut body = response.into_body().into_data_stream();
+        let chunk = tokio::time::timeout(std::time::Duration::from_millis(500), body.next())
+            .await
+            .expect("event not received within 500ms")
+            .unwrap()
+            .unwrap();
+        let chunk = String::from_utf8(chunk.to_vec()).unwrap();
+        assert!(chunk.starts_with("event: agent\n"));
+        let data = chunk
+            .lines()
+            .find_map(|line| line.strip_prefix("data: "))
+            .unwrap();
+        assert_eq!(serde_json::from_str::<AgentEvent>(data).unwrap(), event);
+    }
+
+    /// Records restarted pods instead of deleting them
+    #[derive(Default)]
+    struct RecordingDeployment {
+        restarted: Mutex<Vec<String>>,
+    }
+
+    #[async_trait::async_trait]
+    impl AgentDeployment for RecordingDeployment {
+        async fn restart_pod(&self, pod: &str) -> anyhow::Result<()> {
+            self.restarted.lock().unwrap().push(pod.to_string());
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_drain_and_restart_agent() {
+        let deployment = Arc::new(RecordingDeployment::default());
+        let state = state().with_agent_deployment(deployment.clone());
+        let app = router(state.clone());
+        let send = |method: &str, path: &str, body: &'static str| {
+            let request = Request::builder()
+                .method(method)
+                .uri(path)
+                .header(API_KEY_HEADER, "rbd_secret")
+                .header(CONTENT_TYPE, "application/json")
+                .body(Body::from(body))
+                .unwrap();
+            app.clone().oneshot(request)
+        };
+        let json = |response: axum::response::Response| async move {
+            axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap()
+        };
+
+        let response = send("POST", "/api/agents/agent-1/drain", "").await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let registration = r#"{
+            "agent_id": "agent-1",
+            "version": "0.1.0",
+            "pod_name": "raibid-ci-agent-7d9f8b6c5-x2k4p",
+            "drain_timeout_secs": 600
+        }"#;
+        send("POST", "/api/agents/register", registration)
+            .await
+            .unwrap();
+        let heartbeat = send(
+            "POST",
+            "/api/agents/agent-1/heartbeat",
+            r#"{"active_jobs":2}"#,
+        )
+        .await
+        .unwrap();
+        let body = json(heartbeat).await;
+        let response: HeartbeatResponse = serde_json::from_slice(&body).unwrap();
+        assert!(!response.draining);
+
+        let response = send("POST", "/api/agents/agent-1/drain", "").await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let info: AgentInfo = serde_json::from_slice(&json(response).await).unwrap();
+        assert!(info.draining);
+        assert_eq!(info.active_jobs, 2);
+        assert_eq!(info.drain_timeout_secs, 600);
+
+        // The agent hears about the drain and reports its jobs finishing
+        let heartbeat = send(
+            "POST",
+            "/api/agents/agent-1/heartbeat",
+            r#"{"active_jobs":0}"#,
+        )
+        .await
+        .unwrap();
+        let body = json(heartbeat).await;
+        let response: HeartbeatResponse = serde_json::from_slice(&body).unwrap();
+        assert!(response.draining);
+
+        let response = send("GET", "/api/agents/agent-1", "").await.unwrap();
+        let details: AgentDetails = serde_json::from_slice(&json(response).await).unwrap();
+        assert_eq!(details.info.active_jobs, 0);
+        assert_eq!(details.info.status, AgentStatus::Idle);
+
+        let response = send("POST", "/api/agents/agent-1/restart", "")
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        assert_eq!(
+            *deployment.restarted.lock().unwrap(),
+            ["raibid-ci-agent-7d9f8b6c5-x2k4p"]
+        );
+        assert!(state.agents().get("agent-1").is_none());
+
+        let response = send("POST", "/api/agents/agent-1/restart", "")
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        // Agents outside Kubernetes have no pod to delete
+        let registration = r#"{"agent_id":"agent-2","version":"0.1.0"}"#;
+        send("POST", "/api/agents/register", registration)
+            .await
+            .unwrap();
+        let response = send("POST", "/api/agents/agent-2/restart", "")
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+        assert_eq!(deployment.restarted.lock().unwrap().len(), 1);
+    }
+
+    /// Records the applied scale instead of patching a cluster
+    #[derive(Default)]
+    struct RecordingTarget {
+        applied: Mutex<Vec<AgentScale>>,
+    }
+
+    #[async_trait::async_trait]
+    impl ScaleTarget for RecordingTarget {
+        async fn apply(&self, scale: &AgentScale) -> anyhow::Result<()> {
+            self.applied.lock().unwrap().push(*scale);
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_scale_agents() {
+        let target = Arc::new(RecordingTarget::default());
+        let state = state().with_scale_target(target.clone());
+        let app = router(state.clone());
+
+        let scale = |body: &'static str| {
+            Request::post("/api/agents/scale")
+                .header(API_KEY_HEADER, "rbd_secret")
+                .header("content-type", "application/json")
+                .body(Body::from(body))
+                .unwrap()
+        };
+
+        let response = app
+            .clone()
+            .oneshot(scale(r#"{"min":1,"max":5,"desired":3}"#))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let applied: AgentScale = serde_json::from_slice(&body).unwrap();
+        let expected = AgentScale {
+            min: 1,
+            max: 5,
+            desired: Some(3),
+        };
+        assert_eq!(applied, expected);
+        assert_eq!(*target.applied.lock().unwrap(), vec![expected]);
+        assert_eq!(state.agent_scale(), Some(expected));
+
+        // An inverted range is rejected before reaching the cluster
+        let response = app.oneshot(scale(r#"{"min":5,"max":1}"#)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(target.applied.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_oversized_body_rejected() {
+        let body = vec![b'x'; 2 * 1024 * 1024];
+        let request = Request::post("/webhooks/gitlab")
+            .header(CONTENT_LENGTH, body.len())
+            .body(Body::from(body))
+            .unwrap();
+        assert_eq!(status(request).await, StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[tokio::test]
+    async fn test_body_within_limit_accepted() {
+        // 512 KB of capabilities
+        let registration = serde_json::json!({
+            "agent_id": "agent-1",
+            "version": "0.1.0",
+            "capabilities": vec!["x".repeat(1024); 512],
+        })
+        .to_string();
+        assert!(registration.len() > 512 * 1024);
+        assert!(registration.len() < 1_048_576);
+
+        let request = Request::post("/api/agents/register")
+            .header(API_KEY_HEADER, "rbd_secret")
+            .header(CONTENT_TYPE, "application/json")
+            .header(CONTENT_LENGTH, registration.len())
+            .body(Body::from(registration))
+            .unwrap();
+        assert_eq!(status(request).await, StatusCode::CREATED);
+    }
+
+    #[tokio::test]
+    async fn test_health_is_public() {
+        let request = Request::get("/health").body(Body::empty()).unwrap();
+        assert_eq!(status(request).await, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit_only_on_webhooks() {
+        let state = AppState::new(ServerConfig {
+            rate_limit_rps: Some(1),
+            rate_limit_burst: Some(1),
+            ..Default::default()
+        })
+        .unwrap();
+        state.set_redis_available(false);
+        let app = router(state);
+
+        let webhook = || {
+            Request::post("/webhooks/gitlab")
+                .body(Body::empty())
+                .unwrap()
+        };
+        let first = app.clone().oneshot(webhook()).await.unwrap();
+        assert_ne!(first.status(), StatusCode::TOO_MANY_REQUESTS);
+        let second = app.clone().oneshot(webhook()).await.unwrap();
+        assert_eq!(second.status(), StatusCode::TOO_MANY_REQUESTS);
+
+        for _ in 0..3 {
+            let request = Request::get("/health").body(Body::empty()).unwrap();
+            let response = app.clone().oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+    }
+
+    async fn health_response(
+        app: Router,
+        accept_encoding: Option<&str>,
+    ) -> (Option<String>, Vec<u8>) {
+        let mut request = Request::get("/health");
+        if let Some(encoding) = accept_encoding {
+            request = request.header(ACCEPT_ENCODING, encoding);
+        }
+        let response = app
+            .oneshot(request.body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let encoding = response
+            .headers()
+            .get(CONTENT_ENCODING)
+            .map(|v| v.to_str().unwrap().to_string());
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (encoding, body.to_vec())
+    }
+
+    #[tokio::test]
+    async fn test_json_responses_gzipped() {
+        let (encoding, plain) = health_response(router(state()), None).await;
+        assert_eq!(encoding, None);
+
+        let (encoding, compressed) = health_response(router(state()), Some("gzip")).await;
+        assert_eq!(encoding.as_deref(), Some("gzip"));
+
+        let mut decompressed = Vec::new();
+        flate2::read::GzDecoder::new(compressed.as_slice())
+            .read_to_end(&mut decompressed)
+            .unwrap();
+        assert_eq!(decompressed, plain);
+    }
+
+    #[tokio::test]
+    async fn test_compression_disabled() {
+        let state = AppState::new(ServerConfig {
+            compression_enabled: false,
+            ..Default::default()
+        })
+        .unwrap();
+        state.set_redis_available(false);
+
+        let (encoding, _) = health_response(router(state), Some("gzip")).await;
+        assert_eq!(encoding, None);
+    }
+}
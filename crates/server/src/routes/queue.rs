@@ -0,0 +1,835 @@
+//! Job queue routes
+//!
+//! - `GET /api/queue/stats`: depth, pending, and consumer statistics for the
+//!   job streams, read directly from Redis
+//!
+//! Jobs are queued with [`queue_job`] on one stream per [`JobPriority`],
+//! named after [`ServerConfig::queue_stream`]; statistics are summed over all
+//! of them. Webhook builds go through [`queue_job_deduplicated`], which
+//! queues each repository and commit once per
+//! [`ServerConfig::dedup_window_secs`], so redelivered webhooks don't build a
+//! commit twice. Jobs that depend on other jobs are held back until those
+//! succeed, see [`raibid_common::job_deps`].
+//!
+//! [`ServerConfig::queue_stream`]: crate::ServerConfig::queue_stream
+//! [`ServerConfig::dedup_window_secs`]: crate::ServerConfig::dedup_window_secs
+
+use axum::extract::State;
+use axum::Json;
+use chrono::Utc;
+use redis::Value;
+use std::collections::HashMap;
+use tracing::Instrument;
+
+use crate::error::{ApiError, ApiResult};
+use crate::middleware::request_id;
+use crate::state::AppState;
+use crate::telemetry;
+use raibid_common::job_deps;
+use raibid_common::jobs::{
+    job_key, label_fields, JobPriority, QueueStats, QueuedJob, JOB_FIELD_CREATED_AT,
+    JOB_FIELD_REQUEST_ID, JOB_FIELD_TRACE_ID, STREAM_FIELD_TRACEPARENT,
+};
+
+/// Number of pending entries sampled to find the oldest pending message
+const PENDING_SAMPLE_SIZE: usize = 10;
+
+/// Prefix of the keys holding the ID of the job that queued a commit,
+/// `raibid:seen-commit:<repo>:<commit>`, which expire with the dedup window
+pub const SEEN_COMMIT_KEY_PREFIX: &str = "raibid:seen-commit";
+
+/// Outcome of [`queue_job_deduplicated`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Enqueued {
+    /// Added to the job stream under this message ID
+    Added(String),
+    /// Held until the jobs it depends on succeed
+    Waiting,
+    /// The commit was already queued within the window by the job with this
+    /// ID
+    Duplicate(String),
+}
+
+/// `GET /api/queue/stats`
+pub async fn stats(State(state): State<AppState>) -> ApiResult<Json<QueueStats>> {
+    if !state.redis_available() {
+        return Err(ApiError::Unavailable("Redis is not reachable".to_string()));
+    }
+
+    let stream = state.config().queue_stream.clone();
+    let group = state.config().consumer_group.clone();
+
+    let stats = state
+        .with_redis(|mut conn| {
+            let stream = stream.clone();
+            let group = group.clone();
+            async move { fetch_queue_stats(&mut conn, &stream, &group).await }
+        })
+        .await?;
+
+    Ok(Json(stats))
+}
+
+/// Add a job to the stream for its priority
+///
+/// A job with dependencies that have not all succeeded is held back instead
+/// (see [`job_deps::hold_job`]) and added by the agent that finishes the
+/// last of them.
+///
+/// Jobs queued while handling a request carry its `traceparent`, so agents
+/// can continue the trace, and its request ID unless they already have one.
+/// The time the job was queued is recorded in its job hash, which decides
+/// when its log stream is pruned, along with the request and trace IDs read
+/// by `GET /api/jobs/:id/trace` and a `label.<key>` field per label.
+pub async fn queue_job<C>(
+    conn: &mut C,
+    base_stream: &str,
+    job: &QueuedJob,
+) -> redis::RedisResult<Enqueued>
+where
+    C: redis::aio::ConnectionLike + Send,
+{
+    let trace = telemetry::current_trace();
+    let mut job = job.clone();
+    if job.request_id.is_none() {
+        job.request_id = request_id::current_request_id();
+    }
+    let stream = job.trigger.priority.stream_key(base_stream);
+    let span = tracing::info_span!(
+        "queue_job",
+        job_id = %job.id,
+        stream = %stream,
+        trace_id = trace.as_ref().map(|t| t.trace_id.as_str()),
+        request_id = job.request_id.as_deref(),
+    );
+
+    async move {
+        let mut fields = job.to_stream_fields().map_err(|e| {
+            redis::RedisError::from((
+                redis::ErrorKind::TypeError,
+                "Failed to encode job",
+                format!("{:#}", e),
+            ))
+        })?;
+        if let Some(ref trace) = trace {
+            fields.push((STREAM_FIELD_TRACEPARENT, trace.to_string()));
+        }
+
+        let enqueued = if job.trigger.depends_on.is_empty() {
+            let entry_id: String = redis::cmd("XADD")
+                .arg(&stream)
+                .arg("*")
+                .arg(&fields)
+                .query_async(conn)
+                .await?;
+            Enqueued::Added(entry_id)
+        } else {
+            match job_deps::hold_job(conn, base_stream, &job, &fields).await? {
+                Some(entry_id) => Enqueued::Added(entry_id),
+                None => {
+                    tracing::info!(
+                        "Holding job {} until {} succeed",
+                        job.id,
+                        job.trigger.depends_on.join(", ")
+                    );
+                    Enqueued::Waiting
+                }
+            }
+        };
+
+        let mut hset = redis::cmd("HSET");
+        hset.arg(job_key(&job.id))
+            .arg(JOB_FIELD_CREATED_AT)
+            .arg(Utc::now().to_rfc3339());
+        if let Some(ref request_id) = job.request_id {
+            hset.arg(JOB_FIELD_REQUEST_ID).arg(request_id);
+        }
+        if let Some(ref trace) = trace {
+            hset.arg(JOB_FIELD_TRACE_ID).arg(&trace.trace_id);
+        }
+        hset.arg(label_fields(&job.trigger.labels));
+        hset.query_async::<_, ()>(conn).await?;
+        Ok(enqueued)
+    }
+    .instrument(span)
+    .await
+}
+
+/// Add a job to the stream for its priority unless its commit was queued in
+/// the last `window_secs` seconds
+///
+/// Only first attempts at a specific commit are deduplicated; retries and
+/// branch builds are always queued, as is everything when `window_secs` is
+/// 0. Each commit's dedup key expires `window_secs` after it was queued.
+pub async fn queue_job_deduplicated<C>(
+    conn: &mut C,
+    base_stream: &str,
+    job: &QueuedJob,
+    window_secs: u64,
+) -> redis::RedisResult<Enqueued>
+where
+    C: redis::aio::ConnectionLike + Send,
+{
+    let member = match dedup_member(job) {
+        Some(member) if window_secs > 0 => member,
+        _ => return queue_job(conn, base_stream, job).await,
+    };
+
+    if let Some(existing) = claim_commit(conn, &member, &job.id, window_secs).await? {
+        tracing::info!(
+            "Not queueing job {}: {} already queued as job {}",
+            job.id,
+            member,
+            existing
+        );
+        return Ok(Enqueued::Duplicate(existing));
+    }
+
+    match queue_job(conn, base_stream, job).await {
+        Ok(enqueued) => Ok(enqueued),
+        Err(e) => {
+            // Let the next delivery queue the commit instead; the claim
+            // expires with the window if this fails too
+            let _ = release_commit(conn, &member).await;
+            Err(e)
+        }
+    }
+}
+
+/// `<repo>:<commit>` of a first attempt at a specific commit
+fn dedup_member(job: &QueuedJob) -> Option<String> {
+    if job.retry_count > 0 {
+        return None;
+    }
+    job.trigger
+        .commit
+        .as_ref()
+        .map(|commit| format!("{}:{}", job.trigger.repo, commit))
+}
+
+/// Dedup key of `<repo>:<commit>` pair `member`
+fn seen_commit_key(member: &str) -> String {
+    format!("{}:{}", SEEN_COMMIT_KEY_PREFIX, member)
+}
+
+/// Record `member` as queued by `job_id` for `window_secs`, or return the
+/// job that already queued it
+///
+/// `SET NX` decides which of two concurrent deliveries queues the commit, and
+/// records its job in the same step.
+async fn claim_commit<C>(
+    conn: &mut C,
+    member: &str,
+    job_id: &str,
+    window_secs: u64,
+) -> redis::RedisResult<Option<String>>
+where
+    C: redis::aio::ConnectionLike + Send,
+{
+    let key = seen_commit_key(member);
+    loop {
+        let claimed: Option<String> = redis::cmd("SET")
+            .arg(&key)
+            .arg(job_id)
+            .arg("NX")
+            .arg("EX")
+            .arg(window_secs)
+            .query_async(conn)
+            .await?;
+        if claimed.is_some() {
+            return Ok(None);
+        }
+
+        let existing: Option<String> = redis::cmd("GET").arg(&key).query_async(conn).await?;
+        // Missing if the claim expired or was released in between
+        if existing.is_some() {
+            return Ok(existing);
+        }
+    }
+}
+
+/// Forget that `member` was queued
+async fn release_commit<C>(conn: &mut C, member: &str) -> redis::RedisResult<()>
+where
+    C: redis::aio::ConnectionLike + Send,
+{
+    redis::cmd("DEL")
+        .arg(seen_commit_key(member))
+        .query_async(conn)
+        .await
+}
+
+/// Collect statistics for every priority stream of `base_stream`
+pub async fn fetch_queue_stats<C>(
+    conn: &mut C,
+    base_stream: &str,
+    group: &str,
+) -> redis::RedisResult<QueueStats>
+where
+    C: redis::aio::ConnectionLike + Send,
+{
+    let mut stats = QueueStats::default();
+    for priority in JobPriority::ALL {
+        let stream = priority.stream_key(base_stream);
+        stats.merge(&fetch_stream_stats(conn, &stream, group).await?);
+    }
+    Ok(stats)
+}
+
+/// Query `XLEN`, `XPENDING`, and `XINFO GROUPS` for one job stream
+async fn fetch_stream_stats<C>(
+    conn: &mut C,
+    stream: &str,
+    group: &str,
+) -> redis::RedisResult<QueueStats>
+where
+    C: redis::aio::ConnectionLike + Send,
+{
+    let total_messages: u64 = redis::cmd("XLEN").arg(stream).query_async(conn).await?;
+
+    // XINFO GROUPS errors if the stream does not exist yet
+    if total_messages == 0 {
+        let exists: bool = redis::cmd("EXISTS").arg(stream).query_async(conn).await?;
+        if !exists {
+            return Ok(QueueStats::default());
+        }
+    }
+
+    let pending: Vec<(String, String, u64, u64)> = redis::cmd("XPENDING")
+        .arg(stream)
+        .arg(group)
+        .arg("-")
+        .arg("+")
+        .arg(PENDING_SAMPLE_SIZE)
+        .query_async(conn)
+        .await?;
+
+    let groups: Vec<HashMap<String, Value>> = redis::cmd("XINFO")
+        .arg("GROUPS")
+        .arg(stream)
+        .query_async(conn)
+        .await?;
+
+    let mut stats = group_stats(&groups, group);
+    stats.total_messages = total_messages;
+    stats.oldest_pending_ms = pending.iter().map(|(_, _, idle_ms, _)| *idle_ms).max();
+
+    Ok(stats)
+}
+
+/// Extract consumer, pending, and lag counts for `group` from `XINFO GROUPS`
+fn group_stats(groups: &[HashMap<String, Value>], group: &str) -> QueueStats {
+    let info = groups
+        .iter()
+        .find(|g| g.get("name").and_then(value_string).as_deref() == Some(group));
+
+    match info {
+        Some(info) => QueueStats {
+            pending_count: info.get("pending").and_then(value_u64).unwrap_or(0),
+            consumer_count: info.get("consumers").and_then(value_u64).unwrap_or(0),
+            // `lag` is only reported by Redis 7+ and may be nil
+            lag: info.get("lag").and_then(value_u64),
+            ..Default::default()
+        },
+        None => QueueStats::default(),
+    }
+}
+
+fn value_u64(value: &Value) -> Option<u64> {
+    match value {
+        Value::Int(n) => u64::try_from(*n).ok(),
+        Value::Data(bytes) => std::str::from_utf8(bytes).ok()?.parse().ok(),
+        _ => None,
+    }
+}
+
+fn value_string(value: &Value) -> Option<String> {
+    match value {
+        Value::Data(bytes) => String::from_utf8(bytes.clone()).ok(),
+        Value::Status(s) => Some(s.clone()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::telemetry::TraceContext;
+    use raibid_common::jobs::{JobBuilder, STREAM_FIELD_REQUEST_ID};
+    use std::sync::{Arc, Mutex};
+    use tracing::field::{Field, Visit};
+    use tracing_subscriber::layer::{Context, SubscriberExt};
+    use tracing_subscriber::Layer;
+
+    fn group(name: &str, consumers: i64, pending: i64, lag: Value) -> HashMap<String, Value> {
+        HashMap::from([
+            ("name".to_string(), Value::Data(name.as_bytes().to_vec())),
+            ("consumers".to_string(), Value::Int(consumers)),
+            ("pending".to_string(), Value::Int(pending)),
+            ("lag".to_string(), lag),
+        ])
+    }
+
+    #[test]
+    fn test_group_stats_selects_group() {
+        let groups = vec![
+            group("other", 9, 9, Value::Int(9)),
+            group("raibid-workers", 3, 2, Value::Int(5)),
+        ];
+
+        let stats = group_stats(&groups, "raibid-workers");
+        assert_eq!(stats.consumer_count, 3);
+        assert_eq!(stats.pending_count, 2);
+        assert_eq!(stats.lag, Some(5));
+    }
+
+    #[test]
+    fn test_group_stats_nil_lag() {
+        let groups = vec![group("raibid-workers", 1, 0, Value::Nil)];
+        let stats = group_stats(&groups, "raibid-workers");
+        assert_eq!(stats.lag, None);
+    }
+
+    #[test]
+    fn test_group_stats_missing_group() {
+        let stats = group_stats(&[], "raibid-workers");
+        assert_eq!(stats, QueueStats::default());
+    }
+
+    /// Records each command sent, answering with a stream message ID
+    #[derive(Default)]
+    struct RecordingConnection {
+        commands: Vec<String>,
+    }
+
+    impl redis::aio::ConnectionLike for RecordingConnection {
+        fn req_packed_command<'a>(
+            &'a mut self,
+            cmd: &'a redis::Cmd,
+        ) -> redis::RedisFuture<'a, Value> {
+            self.commands
+                .push(String::from_utf8_lossy(&cmd.get_packed_command()).to_string());
+            Box::pin(async { Ok(Value::Data(b"1-0".to_vec())) })
+        }
+
+        fn req_packed_commands<'a>(
+            &'a mut self,
+            _pipeline: &'a redis::Pipeline,
+            _offset: usize,
+            _count: usize,
+        ) -> redis::RedisFuture<'a, Vec<Value>> {
+            Box::pin(async { Ok(Vec::new()) })
+        }
+
+        fn get_db(&self) -> i64 {
+            0
+        }
+    }
+
+    /// Name and fields of a span
+    type RecordedSpan = (String, HashMap<String, String>);
+
+    /// Records the name and fields of every span opened
+    #[derive(Clone, Default)]
+    struct SpanRecorder(Arc<Mutex<Vec<RecordedSpan>>>);
+
+    #[derive(Default)]
+    struct Fields(HashMap<String, String>);
+
+    impl Visit for Fields {
+        fn record_str(&mut self, field: &Field, value: &str) {
+            self.0.insert(field.name().to_string(), value.to_string());
+        }
+
+        fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+            self.0
+                .insert(field.name().to_string(), format!("{:?}", value));
+        }
+    }
+
+    impl<S: tracing::Subscriber> Layer<S> for SpanRecorder {
+        fn on_new_span(
+            &self,
+            attrs: &tracing::span::Attributes<'_>,
+            _id: &tracing::span::Id,
+            _ctx: Context<'_, S>,
+        ) {
+            let mut fields = Fields::default();
+            attrs.record(&mut fields);
+            self.0
+                .lock()
+                .unwrap()
+                .push((attrs.metadata().name().to_string(), fields.0));
+        }
+    }
+
+    fn job() -> QueuedJob {
+        QueuedJob::new(
+            "job-1",
+            JobBuilder::new("raibid-labs/raibid-cli").build().unwrap(),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_queue_job_propagates_traceparent() {
+        let recorder = SpanRecorder::default();
+        let _guard =
+            tracing::subscriber::set_default(tracing_subscriber::registry().with(recorder.clone()));
+
+        let trace =
+            TraceContext::parse("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01").unwrap();
+        let mut conn = RecordingConnection::default();
+        let id = telemetry::with_trace(trace.clone(), queue_job(&mut conn, "raibid:jobs", &job()))
+            .await
+            .unwrap();
+        assert_eq!(id, Enqueued::Added("1-0".to_string()));
+
+        let xadd = &conn.commands[0];
+        assert!(xadd.contains("XADD"));
+        assert!(xadd.contains(STREAM_FIELD_TRACEPARENT));
+        assert!(xadd.contains(&trace.to_string()));
+
+        let spans = recorder.0.lock().unwrap();
+        let (_, fields) = spans
+            .iter()
+            .find(|(name, _)| name == "queue_job")
+            .expect("queue_job span");
+        assert_eq!(fields["trace_id"], trace.trace_id);
+        assert_eq!(fields["job_id"], "job-1");
+        assert_eq!(fields["stream"], "raibid:jobs:normal");
+    }
+
+    #[tokio::test]
+    async fn test_queue_job_records_request_id() {
+        let recorder = SpanRecorder::default();
+        let _guard =
+            tracing::subscriber::set_default(tracing_subscriber::registry().with(recorder.clone()));
+
+        let mut conn = RecordingConnection::default();
+        request_id::with_request_id(
+            "req-42".to_string(),
+            queue_job(&mut conn, "raibid:jobs", &job()),
+        )
+        .await
+        .unwrap();
+
+        let xadd = &conn.commands[0];
+        assert!(xadd.contains(STREAM_FIELD_REQUEST_ID));
+        assert!(xadd.contains("req-42"));
+        let hset = &conn.commands[1];
+        assert!(hset.contains(JOB_FIELD_REQUEST_ID));
+        assert!(hset.contains("req-42"));
+
+        let spans = recorder.0.lock().unwrap();
+        let (_, fields) = spans
+            .iter()
+            .find(|(name, _)| name == "queue_job")
+            .expect("queue_job span");
+        assert_eq!(fields["request_id"], "req-42");
+    }
+
+    #[tokio::test]
+    async fn test_queue_job_stores_labels() {
+        let job = QueuedJob::new(
+            "job-1",
+            JobBuilder::new("raibid-labs/raibid-cli")
+                .label("source", "gitlab")
+                .label("event", "push")
+                .build()
+                .unwrap(),
+        );
+        let mut conn = RecordingConnection::default();
+        queue_job(&mut conn, "raibid:jobs", &job).await.unwrap();
+
+        let hset = &conn.commands[1];
+        assert!(hset.contains("HSET"));
+        assert!(hset.contains("label.source\r\n$6\r\ngitlab"));
+        assert!(hset.contains("label.event\r\n$4\r\npush"));
+    }
+
+    #[tokio::test]
+    async fn test_queue_job_keeps_request_id_of_retries() {
+        let mut original = job();
+        original.request_id = Some("req-1".to_string());
+
+        let mut conn = RecordingConnection::default();
+        request_id::with_request_id(
+            "req-2".to_string(),
+            queue_job(&mut conn, "raibid:jobs", &original.retry("job-2")),
+        )
+        .await
+        .unwrap();
+        assert!(conn.commands[0].contains("req-1"));
+        assert!(!conn.commands[0].contains("req-2"));
+    }
+
+    #[tokio::test]
+    async fn test_queue_job_holds_jobs_with_dependencies() {
+        let job = QueuedJob::new(
+            "job-2",
+            JobBuilder::new("raibid-labs/raibid-cli")
+                .depends_on("job-1")
+                .build()
+                .unwrap(),
+        );
+        let mut conn = RecordingConnection::default();
+        let queued = queue_job(&mut conn, "raibid:jobs", &job).await.unwrap();
+        assert_eq!(queued, Enqueued::Waiting);
+
+        assert!(!conn.commands.iter().any(|c| c.contains("XADD")));
+        assert!(conn.commands[0].contains(job_deps::PENDING_DEPS_JOBS_KEY));
+        assert!(conn.commands[1].contains(job_deps::PENDING_DEPS_KEY));
+        assert!(conn.commands[2].contains("raibid:dependents:job-1"));
+        let hset = conn.commands.last().unwrap();
+        assert!(hset.contains("raibid:job:job-2"));
+        assert!(hset.contains(JOB_FIELD_CREATED_AT));
+    }
+
+    #[tokio::test]
+    async fn test_queue_job_without_trace() {
+        let mut conn = RecordingConnection::default();
+        queue_job(&mut conn, "raibid:jobs", &job()).await.unwrap();
+        assert!(!conn.commands[0].contains(STREAM_FIELD_TRACEPARENT));
+    }
+
+    #[tokio::test]
+    async fn test_queue_job_records_created_at() {
+        let mut conn = RecordingConnection::default();
+        queue_job(&mut conn, "raibid:jobs", &job()).await.unwrap();
+
+        let hset = &conn.commands[1];
+        assert!(hset.contains("HSET"));
+        assert!(hset.contains("raibid:job:job-1"));
+        assert!(hset.contains(JOB_FIELD_CREATED_AT));
+    }
+
+    /// In-memory Redis supporting the commands used for deduplication, with
+    /// a clock advanced by the test
+    #[derive(Default)]
+    struct FakeRedis {
+        strings: HashMap<String, String>,
+        hashes: HashMap<String, HashMap<String, String>>,
+        expires_at: HashMap<String, u64>,
+        now: u64,
+        entries: u64,
+        fail_xadd: bool,
+    }
+
+    impl FakeRedis {
+        fn advance(&mut self, secs: u64) {
+            self.now += secs;
+            let now = self.now;
+            let expired: Vec<String> = self
+                .expires_at
+                .iter()
+                .filter(|(_, &at)| at <= now)
+                .map(|(key, _)| key.clone())
+                .collect();
+            for key in expired {
+                self.expires_at.remove(&key);
+                self.strings.remove(&key);
+            }
+        }
+
+        fn execute(&mut self, args: &[String]) -> redis::RedisResult<Value> {
+            let int = |n: bool| Value::Int(i64::from(n));
+            Ok(match args[0].as_str() {
+                // SET key value NX EX seconds
+                "SET" if self.strings.contains_key(&args[1]) => Value::Nil,
+                "SET" => {
+                    assert_eq!(args[3..5], ["NX", "EX"]);
+                    let secs: u64 = args[5].parse().unwrap();
+                    self.strings.insert(args[1].clone(), args[2].clone());
+                    self.expires_at.insert(args[1].clone(), self.now + secs);
+                    Value::Okay
+                }
+                "GET" => match self.strings.get(&args[1]) {
+                    Some(value) => Value::Data(value.as_bytes().to_vec()),
+                    None => Value::Nil,
+                },
+                "DEL" => {
+                    self.expires_at.remove(&args[1]);
+                    int(self.strings.remove(&args[1]).is_some())
+                }
+                "HSET" => int(self
+                    .hashes
+                    .entry(args[1].clone())
+                    .or_default()
+                    .insert(args[2].clone(), args[3].clone())
+                    .is_none()),
+                "XADD" if self.fail_xadd => {
+                    return Err(redis::RedisError::from((redis::ErrorKind::IoError, "down")))
+                }
+                "XADD" => {
+                    self.entries += 1;
+                    Value::Data(format!("{}-0", self.entries).into_bytes())
+                }
+                other => panic!("unexpected command {}", other),
+            })
+        }
+    }
+
+    impl redis::aio::ConnectionLike for FakeRedis {
+        fn req_packed_command<'a>(
+            &'a mut self,
+            cmd: &'a redis::Cmd,
+        ) -> redis::RedisFuture<'a, Value> {
+            let args: Vec<String> = cmd
+                .args_iter()
+                .map(|arg| match arg {
+                    redis::Arg::Simple(bytes) => String::from_utf8_lossy(bytes).to_string(),
+                    redis::Arg::Cursor => "0".to_string(),
+                })
+                .collect();
+            let result = self.execute(&args);
+            Box::pin(async move { result })
+        }
+
+        fn req_packed_commands<'a>(
+            &'a mut self,
+            _pipeline: &'a redis::Pipeline,
+            _offset: usize,
+            _count: usize,
+        ) -> redis::RedisFuture<'a, Vec<Value>> {
+            Box::pin(async { Ok(Vec::new()) })
+        }
+
+        fn get_db(&self) -> i64 {
+            0
+        }
+    }
+
+    fn commit_job(id: &str, commit: &str) -> QueuedJob {
+        QueuedJob::new(
+            id,
+            JobBuilder::new("raibid-labs/raibid-cli")
+                .commit(commit)
+                .build()
+                .unwrap(),
+        )
+    }
+
+    const COMMIT: &str = "da1560886d4f094c3e6c9ef40349f7d38b5d27d7";
+
+    #[tokio::test]
+    async fn test_dedup_returns_existing_job() {
+        let mut conn = FakeRedis::default();
+        let first =
+            queue_job_deduplicated(&mut conn, "raibid:jobs", &commit_job("job-1", COMMIT), 3600)
+                .await
+                .unwrap();
+        assert_eq!(first, Enqueued::Added("1-0".to_string()));
+
+        let second =
+            queue_job_deduplicated(&mut conn, "raibid:jobs", &commit_job("job-2", COMMIT), 3600)
+                .await
+                .unwrap();
+        assert_eq!(second, Enqueued::Duplicate("job-1".to_string()));
+        assert_eq!(conn.entries, 1);
+        let key = format!("raibid:seen-commit:raibid-labs/raibid-cli:{}", COMMIT);
+        assert_eq!(conn.strings[&key], "job-1");
+    }
+
+    #[tokio::test]
+    async fn test_dedup_miss_queues() {
+        let mut conn = FakeRedis::default();
+        queue_job_deduplicated(&mut conn, "raibid:jobs", &commit_job("job-1", COMMIT), 3600)
+            .await
+            .unwrap();
+
+        // Another commit, a retry, a branch build, and deduplication disabled
+        let other = commit_job("job-2", "4bf92f3577b34da6a3ce929d0e0e4736a0ba902b");
+        let retry = commit_job("job-1", COMMIT).retry("job-3");
+        let branch = QueuedJob::new("job-4", job().trigger);
+        for (job, window_secs) in [
+            (other, 3600),
+            (retry, 3600),
+            (branch, 3600),
+            (commit_job("job-5", COMMIT), 0),
+        ] {
+            let queued = queue_job_deduplicated(&mut conn, "raibid:jobs", &job, window_secs)
+                .await
+                .unwrap();
+            assert!(matches!(queued, Enqueued::Added(_)), "{}", job.id);
+        }
+        assert_eq!(conn.entries, 5);
+    }
+
+    #[tokio::test]
+    async fn test_dedup_window_expiry() {
+        let mut conn = FakeRedis::default();
+        queue_job_deduplicated(&mut conn, "raibid:jobs", &commit_job("job-1", COMMIT), 3600)
+            .await
+            .unwrap();
+
+        conn.advance(3599);
+        let within =
+            queue_job_deduplicated(&mut conn, "raibid:jobs", &commit_job("job-2", COMMIT), 3600)
+                .await
+                .unwrap();
+        assert_eq!(within, Enqueued::Duplicate("job-1".to_string()));
+
+        conn.advance(1);
+        let after =
+            queue_job_deduplicated(&mut conn, "raibid:jobs", &commit_job("job-3", COMMIT), 3600)
+                .await
+                .unwrap();
+        assert_eq!(after, Enqueued::Added("2-0".to_string()));
+
+        let again =
+            queue_job_deduplicated(&mut conn, "raibid:jobs", &commit_job("job-4", COMMIT), 3600)
+                .await
+                .unwrap();
+        assert_eq!(again, Enqueued::Duplicate("job-3".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_dedup_keys_expire_per_commit() {
+        const OTHER: &str = "4bf92f3577b34da6a3ce929d0e0e4736a0ba902b";
+        let mut conn = FakeRedis::default();
+        queue_job_deduplicated(&mut conn, "raibid:jobs", &commit_job("job-1", COMMIT), 3600)
+            .await
+            .unwrap();
+
+        // Queueing another commit does not extend the first one's window
+        conn.advance(1800);
+        queue_job_deduplicated(&mut conn, "raibid:jobs", &commit_job("job-2", OTHER), 3600)
+            .await
+            .unwrap();
+        conn.advance(1800);
+
+        let first =
+            queue_job_deduplicated(&mut conn, "raibid:jobs", &commit_job("job-3", COMMIT), 3600)
+                .await
+                .unwrap();
+        assert_eq!(first, Enqueued::Added("3-0".to_string()));
+        let other =
+            queue_job_deduplicated(&mut conn, "raibid:jobs", &commit_job("job-4", OTHER), 3600)
+                .await
+                .unwrap();
+        assert_eq!(other, Enqueued::Duplicate("job-2".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_dedup_released_when_queueing_fails() {
+        let mut conn = FakeRedis {
+            fail_xadd: true,
+            ..Default::default()
+        };
+        assert!(queue_job_deduplicated(
+            &mut conn,
+            "raibid:jobs",
+            &commit_job("job-1", COMMIT),
+            3600
+        )
+        .await
+        .is_err());
+
+        conn.fail_xadd = false;
+        let retried =
+            queue_job_deduplicated(&mut conn, "raibid:jobs", &commit_job("job-2", COMMIT), 3600)
+                .await
+                .unwrap();
+        assert_eq!(retried, Enqueued::Added("1-0".to_string()));
+    }
+}
@@ -0,0 +1,148 @@
+//! Schedule routes
+//!
+//! - `POST /api/schedules`: create a schedule from a [`ScheduleRequest`]
+//! - `GET /api/schedules`: list schedules, oldest first
+//! - `DELETE /api/schedules/:id`: remove a schedule
+//!
+//! See [`crate::scheduler`] for how schedules queue builds.
+
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::Json;
+use chrono::Utc;
+use redis::AsyncCommands;
+use std::collections::HashMap;
+use tracing::info;
+
+use crate::error::{ApiError, ApiResult};
+use crate::scheduler;
+use crate::state::AppState;
+use raibid_common::schedules::{Schedule, ScheduleRequest, SCHEDULES_KEY};
+
+/// `POST /api/schedules`
+pub async fn create(
+    State(state): State<AppState>,
+    Json(request): Json<ScheduleRequest>,
+) -> ApiResult<(StatusCode, Json<Schedule>)> {
+    scheduler::validate(&request).map_err(ApiError::BadRequest)?;
+
+    if !state.redis_available() {
+        return Err(ApiError::Unavailable("Redis is not reachable".to_string()));
+    }
+
+    let schedule = Schedule::new(uuid::Uuid::new_v4().to_string(), request, Utc::now());
+    let json = serde_json::to_string(&schedule).map_err(anyhow::Error::from)?;
+    state
+        .with_redis(|mut conn| {
+            let (id, json) = (schedule.id.clone(), json.clone());
+            async move { conn.hset::<_, _, _, ()>(SCHEDULES_KEY, &id, &json).await }
+        })
+        .await?;
+
+    info!(
+        "Created schedule {} for {}@{} ('{}' {})",
+        schedule.id, schedule.repo, schedule.branch, schedule.cron, schedule.timezone
+    );
+    Ok((StatusCode::CREATED, Json(schedule)))
+}
+
+/// `GET /api/schedules`
+pub async fn list(State(state): State<AppState>) -> ApiResult<Json<Vec<Schedule>>> {
+    if !state.redis_available() {
+        return Err(ApiError::Unavailable("Redis is not reachable".to_string()));
+    }
+
+    let stored: HashMap<String, String> = state
+        .with_redis(|mut conn| async move { conn.hgetall(SCHEDULES_KEY).await })
+        .await?;
+
+    let mut schedules = stored
+        .into_iter()
+        .map(|(id, json)| {
+            serde_json::from_str(&json)
+                .map_err(|e| ApiError::Internal(anyhow::anyhow!("Invalid schedule {}: {}", id, e)))
+        })
+        .collect::<ApiResult<Vec<Schedule>>>()?;
+    schedules.sort_by_key(|schedule| schedule.created_at);
+
+    Ok(Json(schedules))
+}
+
+/// `DELETE /api/schedules/:id`
+pub async fn delete(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> ApiResult<StatusCode> {
+    if !state.redis_available() {
+        return Err(ApiError::Unavailable("Redis is not reachable".to_string()));
+    }
+
+    let removed: u64 = state
+        .with_redis(|mut conn| {
+            let id = id.clone();
+            async move { conn.hdel(SCHEDULES_KEY, &id).await }
+        })
+        .await?;
+
+    if removed == 0 {
+        return Err(ApiError::NotFound(format!("Schedule {} not found", id)));
+    }
+
+    info!("Deleted schedule {}", id);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::routes::router;
+    use crate::state::AppState;
+    use crate::ServerConfig;
+    use axum::body::Body;
+    use axum::http::header::CONTENT_TYPE;
+    use axum::http::{Request, StatusCode};
+    use raibid_common::auth::API_KEY_HEADER;
+    use tower::ServiceExt;
+
+    async fn create(body: &str) -> StatusCode {
+        let state = AppState::new(ServerConfig {
+            api_keys: vec!["rbd_secret".to_string()],
+            ..Default::default()
+        })
+        .unwrap();
+        state.set_redis_available(false);
+
+        let request = Request::post("/api/schedules")
+            .header(API_KEY_HEADER, "rbd_secret")
+            .header(CONTENT_TYPE, "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        router(state).oneshot(request).await.unwrap().status()
+    }
+
+    #[tokio::test]
+    async fn test_create_validates_before_redis() {
+        assert_eq!(
+            create(r#"{"repo": "raibid-labs/raibid-cli", "branch": "main", "cron": "0 61 * * *"}"#)
+                .await,
+            StatusCode::BAD_REQUEST
+        );
+        assert_eq!(
+            create(r#"{"repo": "raibid-cli", "branch": "main", "cron": "0 2 * * *"}"#).await,
+            StatusCode::BAD_REQUEST
+        );
+        assert_eq!(
+            create(
+                r#"{"repo": "raibid-labs/raibid-cli", "branch": "main", "cron": "0 2 * * *", "timezone": "Nowhere"}"#
+            )
+            .await,
+            StatusCode::BAD_REQUEST
+        );
+
+        // Valid, then fails on the unavailable Redis
+        assert_eq!(
+            create(r#"{"repo": "raibid-labs/raibid-cli", "branch": "main", "cron": "0 2 * * *"}"#)
+                .await,
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+    }
+}
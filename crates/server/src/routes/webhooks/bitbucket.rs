@@ -0,0 +1,374 @@
+//! Bitbucket Cloud webhooks
+//!
+//! `POST /webhooks/bitbucket` queues a build for each branch updated by a
+//! `repo:push` event. Requests are authenticated by source address and
+//! signature:
+//!
+//! - with [`ServerConfig::bitbucket_allowed_ips`] set, the connecting address
+//!   must be in one of the networks. Bitbucket publishes its outgoing ranges
+//!   at <https://ip-ranges.atlassian.com>. Behind a proxy the connecting
+//!   address is the proxy's, so use the secret there instead.
+//! - with [`ServerConfig::bitbucket_webhook_secret`] set, [`SIGNATURE_HEADER`]
+//!   must hold the HMAC-SHA256 of the body as `sha256=<hex>`.
+//!
+//! Requests are rejected while neither is configured. Other events, tag
+//! pushes, and branch deletions are acknowledged with `200 OK` and ignored.
+//! [`REQUEST_UUID_HEADER`] identifies each delivery for replay protection.
+//!
+//! [`ServerConfig::bitbucket_allowed_ips`]: crate::ServerConfig::bitbucket_allowed_ips
+//! [`ServerConfig::bitbucket_webhook_secret`]: crate::ServerConfig::bitbucket_webhook_secret
+
+use axum::body::Bytes;
+use axum::extract::{ConnectInfo, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use serde::Deserialize;
+use serde_json::json;
+use std::net::SocketAddr;
+
+use super::replay::Delivery;
+use super::{enqueue, hmac_sha256_hex, secrets_match, JobMetadata};
+use crate::error::{ApiError, ApiResult};
+use crate::routes::metrics::record_webhook_request;
+use crate::state::AppState;
+use raibid_common::jobs::JobPriority;
+
+/// Request header naming the event, e.g. `repo:push`
+pub const EVENT_KEY_HEADER: &str = "X-Event-Key";
+
+/// Request header with the HMAC-SHA256 signature of the body
+pub const SIGNATURE_HEADER: &str = "X-Hub-Signature";
+
+/// Request header with the unique ID of each delivery
+pub const REQUEST_UUID_HEADER: &str = "X-Request-UUID";
+
+/// Event key of pushes
+const PUSH_EVENT: &str = "repo:push";
+
+/// `type` of branch refs in push changes
+const BRANCH_TYPE: &str = "branch";
+
+/// Bitbucket `repo:push` payload, limited to the fields needed to queue builds
+#[derive(Debug, Clone, Deserialize)]
+pub struct BitbucketWebhookPayload {
+    /// User who pushed
+    pub actor: BitbucketActor,
+    /// Repository pushed to
+    pub repository: BitbucketRepository,
+    /// Refs updated by the push
+    pub push: BitbucketPush,
+}
+
+/// Actor section of a Bitbucket payload
+#[derive(Debug, Clone, Deserialize)]
+pub struct BitbucketActor {
+    pub display_name: String,
+}
+
+/// Repository section of a Bitbucket payload
+#[derive(Debug, Clone, Deserialize)]
+pub struct BitbucketRepository {
+    /// `workspace/repo_slug`
+    pub full_name: String,
+}
+
+/// Push section of a Bitbucket payload
+#[derive(Debug, Clone, Deserialize)]
+pub struct BitbucketPush {
+    #[serde(default)]
+    pub changes: Vec<BitbucketChange>,
+}
+
+/// One updated ref
+#[derive(Debug, Clone, Deserialize)]
+pub struct BitbucketChange {
+    /// State after the push; `None` when the ref was deleted
+    pub new: Option<BitbucketRef>,
+}
+
+/// A branch or tag
+#[derive(Debug, Clone, Deserialize)]
+pub struct BitbucketRef {
+    /// `branch` or `tag`
+    #[serde(rename = "type")]
+    pub ref_type: String,
+    pub name: String,
+    pub target: BitbucketTarget,
+}
+
+/// Commit a ref points to
+#[derive(Debug, Clone, Deserialize)]
+pub struct BitbucketTarget {
+    pub hash: String,
+}
+
+impl BitbucketWebhookPayload {
+    /// Build details for every branch the push updated
+    pub fn job_metadata(&self) -> Vec<JobMetadata> {
+        self.push
+            .changes
+            .iter()
+            .filter_map(|change| change.new.as_ref())
+            .filter(|new| new.ref_type == BRANCH_TYPE)
+            .map(|new| JobMetadata {
+                repo: self.repository.full_name.clone(),
+                branch: new.name.clone(),
+                commit: new.target.hash.clone(),
+                event_type: PUSH_EVENT.to_string(),
+                priority: JobPriority::Normal,
+                labels: JobMetadata::event_labels("bitbucket", "push"),
+                required_capabilities: Vec::new(),
+                depends_on: Vec::new(),
+            })
+            .collect()
+    }
+}
+
+/// `POST /webhooks/bitbucket`
+pub async fn handle(
+    State(state): State<AppState>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    let peer = connect_info.map(|ConnectInfo(addr)| addr);
+    let response = match handle_event(&state, peer, &headers, &body).await {
+        Ok(response) => response,
+        Err(e) => e.into_response(),
+    };
+    record_webhook_request("bitbucket", response.status());
+    response
+}
+
+async fn handle_event(
+    state: &AppState,
+    peer: Option<SocketAddr>,
+    headers: &HeaderMap,
+    body: &[u8],
+) -> ApiResult<Response> {
+    verify_request(state, peer, headers, body)?;
+
+    let delivery = Delivery::from_headers(headers, REQUEST_UUID_HEADER)?;
+    let attempt = state.replay_guard().begin(&delivery, chrono::Utc::now())?;
+
+    let response = process_event(state, headers, body).await?;
+    attempt.complete();
+    Ok(response)
+}
+
+async fn process_event(state: &AppState, headers: &HeaderMap, body: &[u8]) -> ApiResult<Response> {
+    let event = headers
+        .get(EVENT_KEY_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    if event != PUSH_EVENT {
+        tracing::debug!("Ignoring Bitbucket {:?} event", event);
+        return Ok(Json(json!({ "status": "ignored" })).into_response());
+    }
+
+    let payload: BitbucketWebhookPayload = serde_json::from_slice(body)
+        .map_err(|e| ApiError::BadRequest(format!("Invalid Bitbucket payload: {}", e)))?;
+
+    let metadata = payload.job_metadata();
+    if metadata.is_empty() {
+        tracing::debug!(
+            "Ignoring Bitbucket push to {} without branch updates",
+            payload.repository.full_name
+        );
+        return Ok(Json(json!({ "status": "ignored" })).into_response());
+    }
+
+    let mut job_ids = Vec::with_capacity(metadata.len());
+    for metadata in &metadata {
+        job_ids.push(enqueue(state, metadata).await?.id);
+    }
+    tracing::debug!(
+        "Bitbucket push by {} queued {} jobs",
+        payload.actor.display_name,
+        job_ids.len()
+    );
+    Ok((StatusCode::ACCEPTED, Json(json!({ "job_ids": job_ids }))).into_response())
+}
+
+/// Check the source address and signature against the configuration
+fn verify_request(
+    state: &AppState,
+    peer: Option<SocketAddr>,
+    headers: &HeaderMap,
+    body: &[u8],
+) -> ApiResult<()> {
+    let config = state.config();
+    let secret = config.bitbucket_webhook_secret.as_deref();
+    let allowed_ips = &config.bitbucket_allowed_ips;
+    if secret.is_none() && allowed_ips.is_empty() {
+        return Err(ApiError::Unauthorized(
+            "Bitbucket webhooks are not configured".to_string(),
+        ));
+    }
+
+    if !allowed_ips.is_empty() {
+        let allowed =
+            peer.is_some_and(|peer| allowed_ips.iter().any(|net| net.contains(&peer.ip())));
+        if !allowed {
+            return Err(ApiError::Unauthorized(
+                "Bitbucket webhook from an address outside the allowlist".to_string(),
+            ));
+        }
+    }
+
+    if let Some(secret) = secret {
+        let signature = headers
+            .get(SIGNATURE_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default();
+        if !secrets_match(signature, &sign(secret, body)) {
+            return Err(ApiError::Unauthorized(format!(
+                "Missing or invalid {} header",
+                SIGNATURE_HEADER
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Signature of `body` in the `sha256=<hex>` form Bitbucket sends
+fn sign(secret: &str, body: &[u8]) -> String {
+    format!("sha256={}", hmac_sha256_hex(secret, body))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::routes::router;
+    use crate::ServerConfig;
+    use axum::body::Body;
+    use axum::http::Request;
+    use tower::ServiceExt;
+
+    /// `repo:push` event from the Bitbucket Cloud webhook documentation
+    const PUSH_FIXTURE: &str = include_str!("../../../tests/fixtures/bitbucket_push.json");
+
+    const SECRET: &str = "bitbucket-secret";
+
+    fn state(config: ServerConfig) -> AppState {
+        let state = AppState::new(ServerConfig {
+            metrics_enabled: false,
+            ..config
+        })
+        .unwrap();
+        state.set_redis_available(false);
+        state
+    }
+
+    fn request(peer: &str, signature: Option<&str>, event: &str, body: &str) -> Request<Body> {
+        let mut request = Request::post("/webhooks/bitbucket").header(EVENT_KEY_HEADER, event);
+        if let Some(signature) = signature {
+            request = request.header(SIGNATURE_HEADER, signature);
+        }
+        let mut request = request.body(Body::from(body.to_string())).unwrap();
+        request
+            .extensions_mut()
+            .insert(ConnectInfo(peer.parse::<SocketAddr>().unwrap()));
+        request
+    }
+
+    async fn status(state: AppState, request: Request<Body>) -> StatusCode {
+        router(state).oneshot(request).await.unwrap().status()
+    }
+
+    #[test]
+    fn test_parse_push_fixture() {
+        let payload: BitbucketWebhookPayload = serde_json::from_str(PUSH_FIXTURE).unwrap();
+        assert_eq!(payload.actor.display_name, "Emma");
+
+        let metadata = payload.job_metadata();
+        assert_eq!(
+            metadata,
+            vec![JobMetadata {
+                repo: "team_name/repo_name".to_string(),
+                branch: "main".to_string(),
+                commit: "709d658dc5b6d6afcd46049c2f332ee3f515a67d".to_string(),
+                event_type: "repo:push".to_string(),
+                priority: JobPriority::Normal,
+                labels: JobMetadata::event_labels("bitbucket", "push"),
+                required_capabilities: Vec::new(),
+                depends_on: Vec::new(),
+            }]
+        );
+
+        let job = metadata[0].to_queued_job("job-1").unwrap();
+        assert_eq!(job.trigger.repo, "team_name/repo_name");
+        assert_eq!(
+            job.trigger.commit.as_deref(),
+            Some("709d658dc5b6d6afcd46049c2f332ee3f515a67d")
+        );
+    }
+
+    #[test]
+    fn test_tags_and_deletions_ignored() {
+        let mut payload: serde_json::Value = serde_json::from_str(PUSH_FIXTURE).unwrap();
+        payload["push"]["changes"][0]["new"]["type"] = json!("tag");
+        let tag: BitbucketWebhookPayload = serde_json::from_value(payload.clone()).unwrap();
+        assert!(tag.job_metadata().is_empty());
+
+        payload["push"]["changes"][0]["new"] = json!(null);
+        let deleted: BitbucketWebhookPayload = serde_json::from_value(payload).unwrap();
+        assert!(deleted.job_metadata().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_signature() {
+        let state = || {
+            state(ServerConfig {
+                bitbucket_webhook_secret: Some(SECRET.to_string()),
+                ..Default::default()
+            })
+        };
+        let signature = sign(SECRET, PUSH_FIXTURE.as_bytes());
+
+        let unsigned = request("10.0.0.1:443", None, PUSH_EVENT, PUSH_FIXTURE);
+        assert_eq!(status(state(), unsigned).await, StatusCode::UNAUTHORIZED);
+
+        let tampered = request("10.0.0.1:443", Some(&signature), PUSH_EVENT, "{}");
+        assert_eq!(status(state(), tampered).await, StatusCode::UNAUTHORIZED);
+
+        // Authenticated, then fails on the unavailable Redis
+        let signed = request("10.0.0.1:443", Some(&signature), PUSH_EVENT, PUSH_FIXTURE);
+        assert_eq!(
+            status(state(), signed).await,
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+
+        let other_event = request("10.0.0.1:443", Some(&signature), "repo:fork", PUSH_FIXTURE);
+        assert_eq!(status(state(), other_event).await, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_ip_allowlist() {
+        let state = || {
+            state(ServerConfig {
+                bitbucket_allowed_ips: vec!["104.192.136.0/21".parse().unwrap()],
+                ..Default::default()
+            })
+        };
+
+        let outside = request("203.0.113.7:443", None, PUSH_EVENT, PUSH_FIXTURE);
+        assert_eq!(status(state(), outside).await, StatusCode::UNAUTHORIZED);
+
+        let inside = request("104.192.137.10:443", None, PUSH_EVENT, PUSH_FIXTURE);
+        assert_eq!(
+            status(state(), inside).await,
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+    }
+
+    #[tokio::test]
+    async fn test_rejected_when_not_configured() {
+        let request = request("104.192.137.10:443", None, PUSH_EVENT, PUSH_FIXTURE);
+        assert_eq!(
+            status(state(ServerConfig::default()), request).await,
+            StatusCode::UNAUTHORIZED
+        );
+    }
+}
@@ -0,0 +1,475 @@
+//! Gitea webhooks
+//!
+//! `POST /webhooks/gitea` queues a build for each branch push. Gitea signs
+//! the body with the webhook's secret and sends the hex HMAC-SHA256 in
+//! [`SIGNATURE_HEADER`]; it must match [`ServerConfig::gitea_webhook_secret`],
+//! and requests are rejected while no secret is configured.
+//!
+//! With [`ServerConfig::handle_pr_events`] set, pull request events are
+//! handled too:
+//!
+//! - `opened`, `reopened`, and `synchronized` (new commits pushed) queue a
+//!   build of the head commit on the head branch, labelled with the pull
+//!   request number
+//! - `closed` cancels the jobs of the pull request's head commit that have
+//!   not finished
+//!
+//! Other events and actions, tag pushes, and branch deletions are
+//! acknowledged with `200 OK` and ignored. [`DELIVERY_HEADER`] identifies
+//! each delivery for replay protection.
+//!
+//! [`ServerConfig::gitea_webhook_secret`]: crate::ServerConfig::gitea_webhook_secret
+//! [`ServerConfig::handle_pr_events`]: crate::ServerConfig::handle_pr_events
+
+use axum::body::Bytes;
+use axum::extract::State;
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::HashMap;
+
+use super::replay::Delivery;
+use super::{branch_from_ref, enqueue, hmac_sha256_hex, secrets_match, JobMetadata};
+use crate::error::{ApiError, ApiResult};
+use crate::routes::jobs::cancel_jobs_for_commit;
+use crate::routes::metrics::record_webhook_request;
+use crate::state::AppState;
+use raibid_common::jobs::JobPriority;
+
+/// Request header naming the event, e.g. `push`
+pub const EVENT_HEADER: &str = "X-Gitea-Event";
+
+/// Request header with the hex HMAC-SHA256 signature of the body
+pub const SIGNATURE_HEADER: &str = "X-Gitea-Signature";
+
+/// Request header with the unique ID of each delivery
+pub const DELIVERY_HEADER: &str = "X-Gitea-Delivery";
+
+/// Event name of pushes
+const PUSH_EVENT: &str = "push";
+
+/// Event name of pull request changes
+const PULL_REQUEST_EVENT: &str = "pull_request";
+
+/// Label holding the number of the pull request a job builds
+pub const PULL_REQUEST_LABEL: &str = "pull_request";
+
+/// Commit ID Gitea sends as `after` when a branch is deleted
+const NULL_SHA: &str = "0000000000000000000000000000000000000000";
+
+/// Gitea `push` payload, limited to the fields needed to queue builds
+#[derive(Debug, Clone, Deserialize)]
+pub struct GiteaPushPayload {
+    /// Full ref that was pushed, e.g. `refs/heads/main`
+    #[serde(rename = "ref")]
+    pub git_ref: String,
+    /// Commit the ref points to after the push
+    pub after: String,
+    /// Repository pushed to
+    pub repository: GiteaRepository,
+}
+
+/// Gitea `pull_request` payload, limited to the fields needed to queue and
+/// cancel builds
+#[derive(Debug, Clone, Deserialize)]
+pub struct GiteaPrWebhookPayload {
+    /// What happened to the pull request, e.g. `opened` or `closed`
+    pub action: String,
+    /// The pull request after the change
+    pub pull_request: GiteaPullRequest,
+    /// Repository the pull request targets
+    pub repository: GiteaRepository,
+}
+
+/// Repository section of a Gitea payload
+#[derive(Debug, Clone, Deserialize)]
+pub struct GiteaRepository {
+    /// `owner/name`
+    pub full_name: String,
+}
+
+/// Pull request section of a Gitea payload
+#[derive(Debug, Clone, Deserialize)]
+pub struct GiteaPullRequest {
+    pub number: u64,
+    /// Branch with the changes
+    pub head: GiteaBranch,
+    /// Branch the changes are merged into
+    pub base: GiteaBranch,
+}
+
+/// Head or base branch of a pull request
+#[derive(Debug, Clone, Deserialize)]
+pub struct GiteaBranch {
+    /// Branch name, without `refs/heads/`
+    #[serde(rename = "ref")]
+    pub branch: String,
+    /// Commit the branch points to; Gitea omits it for some base branches
+    #[serde(default)]
+    pub sha: String,
+}
+
+/// What a pull request event does to the builds of its head commit
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PrAction {
+    /// Queue a build
+    Build,
+    /// Cancel the builds that have not finished
+    Cancel,
+    /// Nothing
+    Ignore,
+}
+
+impl GiteaPushPayload {
+    /// Build details for the push, or `None` for tags and branch deletions
+    pub fn job_metadata(&self) -> Option<JobMetadata> {
+        let branch = branch_from_ref(&self.git_ref)?;
+        if self.after == NULL_SHA {
+            return None;
+        }
+        Some(JobMetadata {
+            repo: self.repository.full_name.clone(),
+            branch: branch.to_string(),
+            commit: self.after.clone(),
+            event_type: PUSH_EVENT.to_string(),
+            priority: JobPriority::Normal,
+            labels: JobMetadata::event_labels("gitea", PUSH_EVENT),
+            required_capabilities: Vec::new(),
+            depends_on: Vec::new(),
+        })
+    }
+}
+
+impl GiteaPrWebhookPayload {
+    /// What the event's action does to the builds of the head commit
+    ///
+    /// Gitea reports new commits as `synchronized`; `synchronize`, GitHub's
+    /// name, is accepted too.
+    pub fn pr_action(&self) -> PrAction {
+        match self.action.as_str() {
+            "opened" | "reopened" | "synchronized" | "synchronize" => PrAction::Build,
+            "closed" => PrAction::Cancel,
+            _ => PrAction::Ignore,
+        }
+    }
+
+    /// Labels of the jobs queued for this pull request
+    pub fn labels(&self) -> HashMap<String, String> {
+        let mut labels = JobMetadata::event_labels("gitea", PULL_REQUEST_EVENT);
+        labels.insert(
+            PULL_REQUEST_LABEL.to_string(),
+            self.pull_request.number.to_string(),
+        );
+        labels
+    }
+
+    /// Build details for the head commit of the pull request
+    pub fn job_metadata(&self) -> JobMetadata {
+        JobMetadata {
+            repo: self.repository.full_name.clone(),
+            branch: self.pull_request.head.branch.clone(),
+            commit: self.pull_request.head.sha.clone(),
+            event_type: PULL_REQUEST_EVENT.to_string(),
+            priority: JobPriority::Normal,
+            labels: self.labels(),
+            required_capabilities: Vec::new(),
+            depends_on: Vec::new(),
+        }
+    }
+}
+
+/// `POST /webhooks/gitea`
+pub async fn handle(State(state): State<AppState>, headers: HeaderMap, body: Bytes) -> Response {
+    let response = match handle_event(&state, &headers, &body).await {
+        Ok(response) => response,
+        Err(e) => e.into_response(),
+    };
+    record_webhook_request("gitea", response.status());
+    response
+}
+
+async fn handle_event(state: &AppState, headers: &HeaderMap, body: &[u8]) -> ApiResult<Response> {
+    verify_signature(state, headers, body)?;
+
+    let delivery = Delivery::from_headers(headers, DELIVERY_HEADER)?;
+    let attempt = state.replay_guard().begin(&delivery, chrono::Utc::now())?;
+
+    let response = process_event(state, headers, body).await?;
+    attempt.complete();
+    Ok(response)
+}
+
+async fn process_event(state: &AppState, headers: &HeaderMap, body: &[u8]) -> ApiResult<Response> {
+    let event = headers
+        .get(EVENT_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    match event {
+        PUSH_EVENT => process_push(state, body).await,
+        PULL_REQUEST_EVENT if state.config().handle_pr_events => {
+            process_pull_request(state, body).await
+        }
+        _ => {
+            tracing::debug!("Ignoring Gitea {:?} event", event);
+            Ok(ignored())
+        }
+    }
+}
+
+async fn process_push(state: &AppState, body: &[u8]) -> ApiResult<Response> {
+    let payload: GiteaPushPayload = serde_json::from_slice(body)
+        .map_err(|e| ApiError::BadRequest(format!("Invalid Gitea payload: {}", e)))?;
+
+    let Some(metadata) = payload.job_metadata() else {
+        tracing::debug!(
+            "Ignoring Gitea push of {} to {}",
+            payload.git_ref,
+            payload.repository.full_name
+        );
+        return Ok(ignored());
+    };
+
+    let job = enqueue(state, &metadata).await?;
+    Ok((StatusCode::ACCEPTED, Json(json!({ "job_id": job.id }))).into_response())
+}
+
+async fn process_pull_request(state: &AppState, body: &[u8]) -> ApiResult<Response> {
+    let payload: GiteaPrWebhookPayload = serde_json::from_slice(body)
+        .map_err(|e| ApiError::BadRequest(format!("Invalid Gitea payload: {}", e)))?;
+
+    match payload.pr_action() {
+        PrAction::Build => {
+            let job = enqueue(state, &payload.job_metadata()).await?;
+            Ok((StatusCode::ACCEPTED, Json(json!({ "job_id": job.id }))).into_response())
+        }
+        PrAction::Cancel => {
+            let cancelled =
+                cancel_jobs_for_commit(state, &payload.labels(), &payload.pull_request.head.sha)
+                    .await?;
+            tracing::info!(
+                "Gitea pull request {}#{} closed, cancelled {} jobs",
+                payload.repository.full_name,
+                payload.pull_request.number,
+                cancelled.len()
+            );
+            Ok(Json(json!({ "cancelled": cancelled })).into_response())
+        }
+        PrAction::Ignore => {
+            tracing::debug!(
+                "Ignoring Gitea pull request {}#{} {:?} action",
+                payload.repository.full_name,
+                payload.pull_request.number,
+                payload.action
+            );
+            Ok(ignored())
+        }
+    }
+}
+
+fn ignored() -> Response {
+    Json(json!({ "status": "ignored" })).into_response()
+}
+
+/// Check the request's signature against the configured secret
+fn verify_signature(state: &AppState, headers: &HeaderMap, body: &[u8]) -> ApiResult<()> {
+    let Some(secret) = state.config().gitea_webhook_secret.clone() else {
+        return Err(ApiError::Unauthorized(
+            "Gitea webhooks are not configured".to_string(),
+        ));
+    };
+
+    let signature = headers
+        .get(SIGNATURE_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    if !secrets_match(signature, &hmac_sha256_hex(&secret, body)) {
+        return Err(ApiError::Unauthorized(format!(
+            "Missing or invalid {} header",
+            SIGNATURE_HEADER
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::routes::router;
+    use crate::ServerConfig;
+    use axum::body::Body;
+    use axum::http::Request;
+    use tower::ServiceExt;
+
+    /// `push` event from the Gitea webhook documentation
+    const PUSH_FIXTURE: &str = include_str!("../../../tests/fixtures/gitea_push.json");
+
+    /// `pull_request` event for a newly opened pull request
+    const PR_FIXTURE: &str = include_str!("../../../tests/fixtures/gitea_pull_request.json");
+
+    const SECRET: &str = "gitea-secret";
+
+    const HEAD_SHA: &str = "4f3a1c2e9b8d7a6f5e4d3c2b1a0f9e8d7c6b5a49";
+
+    fn state(handle_pr_events: bool) -> AppState {
+        let state = AppState::new(ServerConfig {
+            metrics_enabled: false,
+            gitea_webhook_secret: Some(SECRET.to_string()),
+            handle_pr_events,
+            ..Default::default()
+        })
+        .unwrap();
+        state.set_redis_available(false);
+        state
+    }
+
+    fn pull_request(action: &str) -> String {
+        let mut payload: serde_json::Value = serde_json::from_str(PR_FIXTURE).unwrap();
+        payload["action"] = json!(action);
+        payload.to_string()
+    }
+
+    fn request(event: &str, body: &str) -> Request<Body> {
+        Request::post("/webhooks/gitea")
+            .header(EVENT_HEADER, event)
+            .header(SIGNATURE_HEADER, hmac_sha256_hex(SECRET, body.as_bytes()))
+            .body(Body::from(body.to_string()))
+            .unwrap()
+    }
+
+    async fn status(state: AppState, request: Request<Body>) -> StatusCode {
+        router(state).oneshot(request).await.unwrap().status()
+    }
+
+    #[test]
+    fn test_parse_push_fixture() {
+        let payload: GiteaPushPayload = serde_json::from_str(PUSH_FIXTURE).unwrap();
+        assert_eq!(
+            payload.job_metadata(),
+            Some(JobMetadata {
+                repo: "gitea/webhooks".to_string(),
+                branch: "develop".to_string(),
+                commit: "bffeb74224043ba2feb48d137756c8a9331c449a".to_string(),
+                event_type: "push".to_string(),
+                priority: JobPriority::Normal,
+                labels: JobMetadata::event_labels("gitea", "push"),
+                required_capabilities: Vec::new(),
+                depends_on: Vec::new(),
+            })
+        );
+
+        let mut tag = payload.clone();
+        tag.git_ref = "refs/tags/v1.0.0".to_string();
+        assert_eq!(tag.job_metadata(), None);
+
+        let mut deleted = payload;
+        deleted.after = NULL_SHA.to_string();
+        assert_eq!(deleted.job_metadata(), None);
+    }
+
+    #[test]
+    fn test_parse_pull_request_fixture() {
+        let payload: GiteaPrWebhookPayload = serde_json::from_str(PR_FIXTURE).unwrap();
+        assert_eq!(payload.pull_request.number, 7);
+        assert_eq!(payload.pull_request.base.branch, "master");
+
+        let metadata = payload.job_metadata();
+        assert_eq!(metadata.repo, "gitea/webhooks");
+        assert_eq!(metadata.branch, "webhook-retries");
+        assert_eq!(metadata.commit, HEAD_SHA);
+        assert_eq!(metadata.event_type, "pull_request");
+        assert_eq!(metadata.labels["source"], "gitea");
+        assert_eq!(metadata.labels["event"], "pull_request");
+        assert_eq!(metadata.labels[PULL_REQUEST_LABEL], "7");
+
+        let job = metadata.to_queued_job("job-1").unwrap();
+        assert_eq!(job.trigger.branch, "webhook-retries");
+        assert_eq!(job.trigger.commit.as_deref(), Some(HEAD_SHA));
+    }
+
+    #[test]
+    fn test_pr_actions() {
+        let action = |action: &str| {
+            serde_json::from_str::<GiteaPrWebhookPayload>(&pull_request(action))
+                .unwrap()
+                .pr_action()
+        };
+        assert_eq!(action("opened"), PrAction::Build);
+        assert_eq!(action("reopened"), PrAction::Build);
+        assert_eq!(action("synchronized"), PrAction::Build);
+        assert_eq!(action("synchronize"), PrAction::Build);
+        assert_eq!(action("closed"), PrAction::Cancel);
+        assert_eq!(action("edited"), PrAction::Ignore);
+        assert_eq!(action("label_updated"), PrAction::Ignore);
+    }
+
+    #[tokio::test]
+    async fn test_signature() {
+        let unsigned = Request::post("/webhooks/gitea")
+            .header(EVENT_HEADER, PUSH_EVENT)
+            .body(Body::from(PUSH_FIXTURE))
+            .unwrap();
+        assert_eq!(
+            status(state(false), unsigned).await,
+            StatusCode::UNAUTHORIZED
+        );
+
+        let mut tampered = request(PUSH_EVENT, PUSH_FIXTURE);
+        *tampered.body_mut() = Body::from("{}");
+        assert_eq!(
+            status(state(false), tampered).await,
+            StatusCode::UNAUTHORIZED
+        );
+
+        // Authenticated, then fails on the unavailable Redis
+        let signed = request(PUSH_EVENT, PUSH_FIXTURE);
+        assert_eq!(
+            status(state(false), signed).await,
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+
+        let other_event = request("issues", PUSH_FIXTURE);
+        assert_eq!(status(state(false), other_event).await, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_rejected_when_not_configured() {
+        let state = AppState::new(ServerConfig {
+            metrics_enabled: false,
+            ..Default::default()
+        })
+        .unwrap();
+        let request = request(PUSH_EVENT, PUSH_FIXTURE);
+        assert_eq!(status(state, request).await, StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_pull_request_events() {
+        // Opening and updating queue a build and closing cancels builds, all
+        // of which fail on the unavailable Redis
+        for action in ["opened", "synchronized", "closed"] {
+            let request = request(PULL_REQUEST_EVENT, &pull_request(action));
+            assert_eq!(
+                status(state(true), request).await,
+                StatusCode::SERVICE_UNAVAILABLE,
+                "{}",
+                action
+            );
+        }
+
+        let edited = request(PULL_REQUEST_EVENT, &pull_request("edited"));
+        assert_eq!(status(state(true), edited).await, StatusCode::OK);
+
+        let invalid = request(PULL_REQUEST_EVENT, "{\"action\":\"opened\"}");
+        assert_eq!(status(state(true), invalid).await, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_pull_request_events_disabled() {
+        for action in ["opened", "synchronized", "closed"] {
+            let request = request(PULL_REQUEST_EVENT, &pull_request(action));
+            assert_eq!(status(state(false), request).await, StatusCode::OK);
+        }
+    }
+}
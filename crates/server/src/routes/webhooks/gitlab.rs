@@ -0,0 +1,307 @@
+//! GitLab webhooks
+//!
+//! `POST /webhooks/gitlab` queues a build for each branch push. GitLab sends
+//! the webhook's secret token in [`TOKEN_HEADER`]; it must match
+//! [`ServerConfig::gitlab_webhook_secret`], and requests are rejected while
+//! no secret is configured.
+//!
+//! Events other than pushes, tag pushes, and branch deletions are
+//! acknowledged with `200 OK` and ignored, so GitLab does not disable the
+//! hook for failing.
+//!
+//! [`EVENT_UUID_HEADER`] identifies each delivery; a delivery seen before, or
+//! one whose `Date` header is more than five minutes off, is rejected with
+//! `400 Bad Request`. A delivery that fails (e.g. while Redis is down) is
+//! forgotten so GitLab can retry it.
+//!
+//! [`ServerConfig::gitlab_webhook_secret`]: crate::ServerConfig::gitlab_webhook_secret
+
+use axum::body::Bytes;
+use axum::extract::State;
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use serde::Deserialize;
+use serde_json::json;
+
+use super::replay::Delivery;
+use super::{branch_from_ref, enqueue, secrets_match, JobMetadata};
+use crate::error::{ApiError, ApiResult};
+use crate::routes::metrics::record_webhook_request;
+use crate::state::AppState;
+use raibid_common::jobs::JobPriority;
+
+/// Request header carrying the webhook's secret token
+pub const TOKEN_HEADER: &str = "X-Gitlab-Token";
+
+/// Request header with the unique ID of each delivery
+pub const EVENT_UUID_HEADER: &str = "X-Gitlab-Event-UUID";
+
+/// `object_kind` of push events
+const PUSH_EVENT: &str = "push";
+
+/// `after` of a push that deleted the branch
+const NULL_SHA: &str = "0000000000000000000000000000000000000000";
+
+/// GitLab webhook payload, limited to the fields needed to queue a build
+#[derive(Debug, Clone, Deserialize)]
+pub struct GitLabWebhookPayload {
+    /// Event kind, e.g. `push`, `tag_push`, or `merge_request`
+    pub object_kind: String,
+    /// Pushed ref, e.g. `refs/heads/main`
+    #[serde(rename = "ref", default)]
+    pub git_ref: String,
+    /// Branch head after the push
+    #[serde(default)]
+    pub after: String,
+    /// Project the event belongs to
+    pub project: GitLabProject,
+    /// Pushed commits, oldest first (at most 20)
+    #[serde(default)]
+    pub commits: Vec<GitLabCommit>,
+}
+
+/// Project section of a GitLab payload
+#[derive(Debug, Clone, Deserialize)]
+pub struct GitLabProject {
+    /// Full project path, e.g. `group/project`
+    pub path_with_namespace: String,
+}
+
+/// A commit in a GitLab push payload
+#[derive(Debug, Clone, Deserialize)]
+pub struct GitLabCommit {
+    pub id: String,
+    pub message: String,
+}
+
+impl GitLabWebhookPayload {
+    /// Build details for a branch push; `None` for other events
+    pub fn job_metadata(&self) -> Option<JobMetadata> {
+        if self.object_kind != PUSH_EVENT || self.after == NULL_SHA {
+            return None;
+        }
+
+        Some(JobMetadata {
+            repo: self.project.path_with_namespace.clone(),
+            branch: branch_from_ref(&self.git_ref)?.to_string(),
+            commit: self.after.clone(),
+            event_type: PUSH_EVENT.to_string(),
+            priority: JobPriority::Normal,
+            labels: JobMetadata::event_labels("gitlab", "push"),
+            required_capabilities: Vec::new(),
+            depends_on: Vec::new(),
+        })
+    }
+}
+
+/// `POST /webhooks/gitlab`
+pub async fn handle(State(state): State<AppState>, headers: HeaderMap, body: Bytes) -> Response {
+    let response = match handle_event(&state, &headers, &body).await {
+        Ok(response) => response,
+        Err(e) => e.into_response(),
+    };
+    record_webhook_request("gitlab", response.status());
+    response
+}
+
+async fn handle_event(state: &AppState, headers: &HeaderMap, body: &[u8]) -> ApiResult<Response> {
+    verify_token(state, headers)?;
+
+    let delivery = Delivery::from_headers(headers, EVENT_UUID_HEADER)?;
+    let attempt = state.replay_guard().begin(&delivery, chrono::Utc::now())?;
+
+    let response = process_event(state, body).await?;
+    attempt.complete();
+    Ok(response)
+}
+
+async fn process_event(state: &AppState, body: &[u8]) -> ApiResult<Response> {
+    let payload: GitLabWebhookPayload = serde_json::from_slice(body)
+        .map_err(|e| ApiError::BadRequest(format!("Invalid GitLab payload: {}", e)))?;
+
+    let Some(metadata) = payload.job_metadata() else {
+        tracing::debug!(
+            "Ignoring GitLab {} event for {}",
+            payload.object_kind,
+            payload.project.path_with_namespace
+        );
+        return Ok(Json(json!({ "status": "ignored" })).into_response());
+    };
+
+    let job = enqueue(state, &metadata).await?;
+    Ok((StatusCode::ACCEPTED, Json(json!({ "job_id": job.id }))).into_response())
+}
+
+/// Check the request's token against the configured secret
+fn verify_token(state: &AppState, headers: &HeaderMap) -> ApiResult<()> {
+    let Some(secret) = state.gitlab_webhook_secret() else {
+        return Err(ApiError::Unauthorized(
+            "GitLab webhooks are not configured".to_string(),
+        ));
+    };
+
+    let token = headers
+        .get(TOKEN_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    if !secrets_match(token, &secret) {
+        return Err(ApiError::Unauthorized(format!(
+            "Missing or invalid {} header",
+            TOKEN_HEADER
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::routes::router;
+    use crate::routes::webhooks::replay::DATE_HEADER;
+    use crate::ServerConfig;
+    use axum::body::Body;
+    use axum::http::Request;
+    use tower::ServiceExt;
+
+    /// Push event from the GitLab webhook documentation
+    const PUSH_FIXTURE: &str = include_str!("../../../tests/fixtures/gitlab_push.json");
+
+    const SECRET: &str = "gitlab-secret";
+
+    fn state() -> AppState {
+        let state = AppState::new(ServerConfig {
+            gitlab_webhook_secret: Some(SECRET.to_string()),
+            metrics_enabled: false,
+            ..Default::default()
+        })
+        .unwrap();
+        state.set_redis_available(false);
+        state
+    }
+
+    async fn post(token: Option<&str>, body: String) -> StatusCode {
+        let mut request = Request::post("/webhooks/gitlab");
+        if let Some(token) = token {
+            request = request.header(TOKEN_HEADER, token);
+        }
+        let request = request.body(Body::from(body)).unwrap();
+        router(state()).oneshot(request).await.unwrap().status()
+    }
+
+    fn delivery(uuid: &str, date: chrono::DateTime<chrono::Utc>, body: &str) -> Request<Body> {
+        Request::post("/webhooks/gitlab")
+            .header(TOKEN_HEADER, SECRET)
+            .header(EVENT_UUID_HEADER, uuid)
+            .header(DATE_HEADER, date.to_rfc2822())
+            .body(Body::from(body.to_string()))
+            .unwrap()
+    }
+
+    fn fixture_with(field: &str, value: serde_json::Value) -> String {
+        let mut payload: serde_json::Value = serde_json::from_str(PUSH_FIXTURE).unwrap();
+        payload[field] = value;
+        payload.to_string()
+    }
+
+    #[test]
+    fn test_parse_push_fixture() {
+        let payload: GitLabWebhookPayload = serde_json::from_str(PUSH_FIXTURE).unwrap();
+        assert_eq!(payload.object_kind, "push");
+        assert_eq!(payload.project.path_with_namespace, "mike/diaspora");
+        assert_eq!(payload.commits.len(), 2);
+        assert_eq!(payload.commits[1].message, "fixed readme");
+
+        let metadata = payload.job_metadata().unwrap();
+        assert_eq!(metadata.repo, "mike/diaspora");
+        assert_eq!(metadata.branch, "master");
+        assert_eq!(metadata.commit, "da1560886d4f094c3e6c9ef40349f7d38b5d27d7");
+        assert_eq!(metadata.event_type, "push");
+        assert_eq!(metadata.labels["source"], "gitlab");
+        assert_eq!(metadata.labels["event"], "push");
+    }
+
+    #[test]
+    fn test_non_branch_pushes_ignored() {
+        for (field, value) in [
+            ("object_kind", json!("tag_push")),
+            ("ref", json!("refs/tags/v1.0.0")),
+            ("after", json!(NULL_SHA)),
+        ] {
+            let payload: GitLabWebhookPayload =
+                serde_json::from_str(&fixture_with(field, value)).unwrap();
+            assert_eq!(payload.job_metadata(), None, "{}", field);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rejects_invalid_token() {
+        let body = PUSH_FIXTURE.to_string();
+        assert_eq!(post(None, body.clone()).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(
+            post(Some("wrong"), body.clone()).await,
+            StatusCode::UNAUTHORIZED
+        );
+
+        // Authenticated, then fails on the unavailable Redis
+        assert_eq!(
+            post(Some(SECRET), body).await,
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+    }
+
+    #[tokio::test]
+    async fn test_rejected_without_secret() {
+        let request = Request::post("/webhooks/gitlab")
+            .header(TOKEN_HEADER, "")
+            .body(Body::from(PUSH_FIXTURE))
+            .unwrap();
+        let state = AppState::new(ServerConfig::default()).unwrap();
+        let response = router(state).oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_ignored_and_invalid_events() {
+        let tag_push = fixture_with("object_kind", json!("tag_push"));
+        assert_eq!(post(Some(SECRET), tag_push).await, StatusCode::OK);
+
+        assert_eq!(
+            post(Some(SECRET), "not json".to_string()).await,
+            StatusCode::BAD_REQUEST
+        );
+    }
+
+    #[tokio::test]
+    async fn test_replayed_delivery_rejected() {
+        let app = router(state());
+        let now = chrono::Utc::now();
+        let tag_push = fixture_with("object_kind", json!("tag_push"));
+
+        let fresh = app.clone().oneshot(delivery("uuid-1", now, &tag_push));
+        assert_eq!(fresh.await.unwrap().status(), StatusCode::OK);
+
+        let replay = app.clone().oneshot(delivery("uuid-1", now, &tag_push));
+        assert_eq!(replay.await.unwrap().status(), StatusCode::BAD_REQUEST);
+
+        let stale = now - chrono::Duration::minutes(10);
+        let expired = app.clone().oneshot(delivery("uuid-2", stale, &tag_push));
+        assert_eq!(expired.await.unwrap().status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_failed_delivery_can_be_retried() {
+        let app = router(state());
+        let now = chrono::Utc::now();
+
+        // Fails on the unavailable Redis, so GitLab's retry is not a replay
+        for _ in 0..2 {
+            let response = app
+                .clone()
+                .oneshot(delivery("uuid-1", now, PUSH_FIXTURE))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        }
+    }
+}
@@ -0,0 +1,270 @@
+//! Webhook routes
+//!
+//! - `POST /webhooks/gitlab`: GitLab push events, see [`gitlab`]
+//! - `POST /webhooks/bitbucket`: Bitbucket Cloud push events, see [`bitbucket`]
+//! - `POST /webhooks/gitea`: Gitea push and pull request events, see [`gitea`]
+//!
+//! Each provider module verifies its request and extracts a [`JobMetadata`];
+//! [`enqueue`] turns that into a job on the queue. Webhooks authenticate with
+//! the provider's own secret (or source address), so they are not behind the
+//! API key layer, and replayed deliveries are rejected by
+//! [`replay::ReplayGuard`]. While the server is draining (see
+//! [`crate::routes::admin`]) no new jobs are queued.
+
+pub mod bitbucket;
+pub mod gitea;
+pub mod gitlab;
+pub mod replay;
+
+use axum::routing::post;
+use axum::Router;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::collections::HashMap;
+
+use crate::error::{ApiError, ApiResult};
+use crate::routes::queue::{self, Enqueued};
+use crate::state::AppState;
+use raibid_common::jobs::{JobBuilder, JobPriority, QueuedJob};
+
+/// Prefix of branch refs in push events
+const BRANCH_REF_PREFIX: &str = "refs/heads/";
+
+/// Webhook routes
+pub fn routes() -> Router<AppState> {
+    Router::new()
+        .route("/webhooks/gitlab", post(gitlab::handle))
+        .route("/webhooks/bitbucket", post(bitbucket::handle))
+        .route("/webhooks/gitea", post(gitea::handle))
+}
+
+/// Build details extracted from a webhook event
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JobMetadata {
+    /// Repository in `owner/name` form
+    pub repo: String,
+    /// Branch that was pushed
+    pub branch: String,
+    /// Commit to build
+    pub commit: String,
+    /// Event that triggered the build, e.g. `push`
+    pub event_type: String,
+    /// Queue priority
+    pub priority: JobPriority,
+    /// Labels of the queued job, see [`JobMetadata::event_labels`]
+    pub labels: HashMap<String, String>,
+    /// Capabilities an agent needs to run the job
+    pub required_capabilities: Vec<String>,
+    /// Jobs that must succeed before this one is queued
+    pub depends_on: Vec<String>,
+}
+
+impl JobMetadata {
+    /// Labels of jobs queued by a `source` webhook for `event`, e.g.
+    /// `source=gitlab` and `event=push`
+    pub fn event_labels(source: &str, event: &str) -> HashMap<String, String> {
+        HashMap::from([
+            ("source".to_string(), source.to_string()),
+            ("event".to_string(), event.to_string()),
+        ])
+    }
+
+    /// Queued job for this event under `job_id`
+    ///
+    /// Fails with `400 Bad Request` if the repository, branch, or commit is
+    /// not something the agents can build.
+    pub fn to_queued_job(&self, job_id: impl Into<String>) -> ApiResult<QueuedJob> {
+        let builder = self
+            .labels
+            .iter()
+            .fold(JobBuilder::new(&self.repo), |builder, (key, value)| {
+                builder.label(key, value)
+            });
+        let builder = self
+            .required_capabilities
+            .iter()
+            .fold(builder, |builder, capability| {
+                builder.require_capability(capability)
+            });
+        let trigger = self
+            .depends_on
+            .iter()
+            .fold(builder, |builder, job_id| builder.depends_on(job_id))
+            .branch(&self.branch)
+            .commit(&self.commit)
+            .priority(self.priority)
+            .build()
+            .map_err(|e| ApiError::BadRequest(format!("{:#}", e)))?;
+        Ok(QueuedJob::new(job_id, trigger))
+    }
+}
+
+/// Queue a build for a webhook event, returning the new job
+///
+/// A commit already queued within [`ServerConfig::dedup_window_secs`] is not
+/// queued again; the job that queued it is returned instead.
+///
+/// Fails with `503 Service Unavailable` while the server is draining.
+///
+/// [`ServerConfig::dedup_window_secs`]: crate::ServerConfig::dedup_window_secs
+pub async fn enqueue(state: &AppState, metadata: &JobMetadata) -> ApiResult<QueuedJob> {
+    if state.is_draining() {
+        return Err(ApiError::Unavailable(
+            "Server is draining and not accepting new jobs".to_string(),
+        ));
+    }
+
+    let job = metadata.to_queued_job(uuid::Uuid::new_v4().to_string())?;
+
+    if !state.redis_available() {
+        return Err(ApiError::Unavailable("Redis is not reachable".to_string()));
+    }
+
+    let stream = state.config().queue_stream.clone();
+    let window_secs = state.config().dedup_window_secs;
+    let queued = state
+        .with_redis(|mut conn| {
+            let stream = stream.clone();
+            let job = job.clone();
+            async move {
+                queue::queue_job_deduplicated(&mut conn, &stream, &job, window_secs).await
+            }
+        })
+        .await?;
+    if let Enqueued::Duplicate(existing) = queued {
+        tracing::info!(
+            "{} {} of {}@{} is already queued as job {}",
+            metadata.event_type,
+            metadata.repo,
+            metadata.branch,
+            metadata.commit,
+            existing
+        );
+        return Ok(QueuedJob {
+            id: existing,
+            ..job
+        });
+    }
+    state.response_cache().invalidate_job(&job.id);
+
+    tracing::info!(
+        "Queued job {} for {} {} of {}@{}",
+        job.id,
+        metadata.event_type,
+        metadata.repo,
+        metadata.branch,
+        metadata.commit
+    );
+    Ok(job)
+}
+
+/// Branch name of a `refs/heads/<branch>` ref; `None` for tags and other refs
+pub fn branch_from_ref(git_ref: &str) -> Option<&str> {
+    git_ref
+        .strip_prefix(BRANCH_REF_PREFIX)
+        .filter(|branch| !branch.is_empty())
+}
+
+/// Compare a received secret with the configured one in constant time
+pub fn secrets_match(received: &str, expected: &str) -> bool {
+    let (received, expected) = (received.as_bytes(), expected.as_bytes());
+    received.len() == expected.len()
+        && received
+            .iter()
+            .zip(expected)
+            .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+/// Hex-encoded HMAC-SHA256 of `body` keyed with `secret`
+pub fn hmac_sha256_hex(secret: &str, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any size");
+    mac.update(body);
+    mac.finalize()
+        .into_bytes()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metadata() -> JobMetadata {
+        JobMetadata {
+            repo: "mike/diaspora".to_string(),
+            branch: "master".to_string(),
+            commit: "da1560886d4f094c3e6c9ef40349f7d38b5d27d7".to_string(),
+            event_type: "push".to_string(),
+            priority: JobPriority::Normal,
+            labels: JobMetadata::event_labels("gitlab", "push"),
+            required_capabilities: Vec::new(),
+            depends_on: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_branch_from_ref() {
+        assert_eq!(branch_from_ref("refs/heads/main"), Some("main"));
+        assert_eq!(branch_from_ref("refs/heads/feature/x"), Some("feature/x"));
+        assert_eq!(branch_from_ref("refs/tags/v1.0.0"), None);
+        assert_eq!(branch_from_ref("refs/heads/"), None);
+    }
+
+    #[test]
+    fn test_secrets_match() {
+        assert!(secrets_match("s3cret", "s3cret"));
+        assert!(!secrets_match("s3cret", "s3creT"));
+        assert!(!secrets_match("s3cre", "s3cret"));
+        assert!(!secrets_match("", "s3cret"));
+    }
+
+    #[test]
+    fn test_hmac_sha256_hex() {
+        assert_eq!(
+            hmac_sha256_hex("key", b"The quick brown fox jumps over the lazy dog"),
+            "f7bc83f430538424b13298e6aa6fb143ef4d59a14946175997479dbc2d1a3cd8"
+        );
+    }
+
+    #[test]
+    fn test_to_queued_job() {
+        let job = metadata().to_queued_job("job-1").unwrap();
+        assert_eq!(job.id, "job-1");
+        assert_eq!(job.trigger.repo, "mike/diaspora");
+        assert_eq!(job.trigger.branch, "master");
+        assert_eq!(
+            job.trigger.commit.as_deref(),
+            Some("da1560886d4f094c3e6c9ef40349f7d38b5d27d7")
+        );
+        assert_eq!(job.trigger.labels["source"], "gitlab");
+        assert_eq!(job.trigger.labels["event"], "push");
+        assert!(job.trigger.required_capabilities.is_empty());
+
+        let gpu = JobMetadata {
+            required_capabilities: vec!["gpu".to_string()],
+            ..metadata()
+        };
+        let job = gpu.to_queued_job("job-3").unwrap();
+        assert_eq!(job.trigger.required_capabilities, vec!["gpu"]);
+
+        let downstream = JobMetadata {
+            depends_on: vec!["job-1".to_string()],
+            ..metadata()
+        };
+        let job = downstream.to_queued_job("job-4").unwrap();
+        assert_eq!(job.trigger.depends_on, vec!["job-1"]);
+
+        // GitLab subgroups have more than two path segments
+        let nested = JobMetadata {
+            repo: "group/subgroup/project".to_string(),
+            ..metadata()
+        };
+        assert!(matches!(
+            nested.to_queued_job("job-2"),
+            Err(ApiError::BadRequest(_))
+        ));
+    }
+}
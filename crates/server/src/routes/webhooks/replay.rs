@@ -0,0 +1,315 @@
+//! Webhook replay protection
+//!
+//! A captured webhook request carries a valid secret, so it could be sent
+//! again to queue the same build repeatedly. [`ReplayGuard`] rejects
+//! deliveries sent more than [`REPLAY_WINDOW`] ago and deliveries whose ID
+//! (nonce) it has already seen. Nonces are kept for [`NONCE_TTL`], longer than
+//! the window, so a replay is caught by one check or the other.
+//!
+//! Providers that omit the delivery time or ID skip that check; the secret
+//! is still verified.
+//!
+//! Handlers hold a [`DeliveryAttempt`] from [`ReplayGuard::begin`] while they
+//! run. Unless it is completed, dropping it forgets the delivery ID, so a
+//! delivery whose handler failed or was cancelled (e.g. because the client
+//! disconnected) can be retried by the provider.
+
+use axum::http::HeaderMap;
+use chrono::{DateTime, Utc};
+use dashmap::mapref::entry::Entry;
+use dashmap::DashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::task::JoinHandle;
+use tracing::debug;
+
+use crate::error::{ApiError, ApiResult};
+
+/// How old a delivery may be before it is rejected
+pub const REPLAY_WINDOW: Duration = Duration::from_secs(5 * 60);
+
+/// How long delivery IDs are remembered
+pub const NONCE_TTL: Duration = Duration::from_secs(10 * 60);
+
+/// Interval between evictions of expired delivery IDs
+pub const NONCE_CLEANUP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Standard header with the time the request was sent
+pub const DATE_HEADER: &str = "Date";
+
+/// Delivery ID and send time of a webhook request
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Delivery {
+    /// Unique ID of the delivery
+    pub nonce: Option<String>,
+    /// When the provider sent the delivery
+    pub sent_at: Option<DateTime<Utc>>,
+}
+
+impl Delivery {
+    /// Read the delivery ID from `nonce_header` and the send time from `Date`
+    ///
+    /// A `Date` header that is not a valid RFC 2822 date is rejected rather
+    /// than ignored, so it cannot be used to skip the window check.
+    pub fn from_headers(headers: &HeaderMap, nonce_header: &str) -> ApiResult<Self> {
+        let nonce = headers
+            .get(nonce_header)
+            .and_then(|value| value.to_str().ok())
+            .map(str::trim)
+            .filter(|nonce| !nonce.is_empty())
+            .map(String::from);
+
+        let sent_at = match headers.get(DATE_HEADER) {
+            Some(value) => {
+                let date = value
+                    .to_str()
+                    .ok()
+                    .and_then(|date| DateTime::parse_from_rfc2822(date).ok())
+                    .ok_or_else(|| {
+                        ApiError::BadRequest(format!("Invalid {} header", DATE_HEADER))
+                    })?;
+                Some(date.with_timezone(&Utc))
+            }
+            None => None,
+        };
+
+        Ok(Self { nonce, sent_at })
+    }
+}
+
+/// Delivery IDs seen within [`NONCE_TTL`]
+#[derive(Debug, Default)]
+pub struct ReplayGuard {
+    nonces: DashMap<String, Instant>,
+}
+
+impl ReplayGuard {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reject stale or repeated deliveries and remember the delivery ID
+    ///
+    /// Both failures are `400 Bad Request`. Deliveries dated too far in the
+    /// future are rejected like stale ones.
+    pub fn check(&self, delivery: &Delivery, now: DateTime<Utc>) -> ApiResult<()> {
+        if let Some(sent_at) = delivery.sent_at {
+            let age = (now - sent_at).abs().to_std().unwrap_or(Duration::MAX);
+            if age > REPLAY_WINDOW {
+                return Err(ApiError::BadRequest(format!(
+                    "Webhook delivery time {} is outside the {} minute window",
+                    sent_at.to_rfc3339(),
+                    REPLAY_WINDOW.as_secs() / 60
+                )));
+            }
+        }
+
+        if let Some(ref nonce) = delivery.nonce {
+            match self.nonces.entry(nonce.clone()) {
+                Entry::Occupied(_) => {
+                    return Err(ApiError::BadRequest(format!(
+                        "Webhook delivery {} was already received",
+                        nonce
+                    )));
+                }
+                Entry::Vacant(entry) => {
+                    entry.insert(Instant::now());
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// [Check](Self::check) a delivery and start handling it
+    ///
+    /// The delivery ID is forgotten again unless the returned attempt is
+    /// [completed](DeliveryAttempt::complete).
+    pub fn begin<'a>(
+        &'a self,
+        delivery: &'a Delivery,
+        now: DateTime<Utc>,
+    ) -> ApiResult<DeliveryAttempt<'a>> {
+        self.check(delivery, now)?;
+        Ok(DeliveryAttempt {
+            guard: self,
+            delivery,
+            handled: false,
+        })
+    }
+
+    /// Forget a delivery ID so the provider can retry a delivery that failed
+    pub fn forget(&self, delivery: &Delivery) {
+        if let Some(ref nonce) = delivery.nonce {
+            self.nonces.remove(nonce);
+        }
+    }
+
+    /// Drop delivery IDs first seen more than [`NONCE_TTL`] before `now`
+    pub fn evict_expired(&self, now: Instant) {
+        let before = self.nonces.len();
+        self.nonces
+            .retain(|_, seen| now.saturating_duration_since(*seen) < NONCE_TTL);
+
+        let evicted = before.saturating_sub(self.nonces.len());
+        if evicted > 0 {
+            debug!("Evicted {} expired webhook delivery IDs", evicted);
+        }
+    }
+
+    /// Number of remembered delivery IDs
+    pub fn len(&self) -> usize {
+        self.nonces.len()
+    }
+
+    /// Whether no delivery IDs are remembered
+    pub fn is_empty(&self) -> bool {
+        self.nonces.is_empty()
+    }
+
+    /// Spawn the background task that evicts expired delivery IDs
+    pub fn spawn_cleanup(self: &Arc<Self>) -> JoinHandle<()> {
+        let guard = Arc::clone(self);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(NONCE_CLEANUP_INTERVAL);
+            loop {
+                interval.tick().await;
+                guard.evict_expired(Instant::now());
+            }
+        })
+    }
+}
+
+/// A delivery being handled, forgotten when dropped before completion
+pub struct DeliveryAttempt<'a> {
+    guard: &'a ReplayGuard,
+    delivery: &'a Delivery,
+    handled: bool,
+}
+
+impl DeliveryAttempt<'_> {
+    /// Keep the delivery ID, rejecting later replays of the delivery
+    pub fn complete(mut self) {
+        self.handled = true;
+    }
+}
+
+impl Drop for DeliveryAttempt<'_> {
+    fn drop(&mut self) {
+        if !self.handled {
+            self.guard.forget(self.delivery);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    fn delivery(nonce: &str, sent_at: DateTime<Utc>) -> Delivery {
+        Delivery {
+            nonce: Some(nonce.to_string()),
+            sent_at: Some(sent_at),
+        }
+    }
+
+    #[test]
+    fn test_fresh_delivery_accepted() {
+        let guard = ReplayGuard::new();
+        let now = Utc::now();
+
+        assert!(guard
+            .check(&delivery("a", now - chrono::Duration::seconds(30)), now)
+            .is_ok());
+        assert!(guard.check(&Delivery::default(), now).is_ok());
+        assert_eq!(guard.len(), 1);
+    }
+
+    #[test]
+    fn test_expired_timestamp_rejected() {
+        let guard = ReplayGuard::new();
+        let now = Utc::now();
+
+        let stale = delivery("a", now - chrono::Duration::minutes(6));
+        assert!(matches!(
+            guard.check(&stale, now),
+            Err(ApiError::BadRequest(_))
+        ));
+
+        let future = delivery("b", now + chrono::Duration::minutes(6));
+        assert!(guard.check(&future, now).is_err());
+
+        // Rejected deliveries are not remembered
+        assert!(guard.is_empty());
+    }
+
+    #[test]
+    fn test_replayed_nonce_rejected() {
+        let guard = ReplayGuard::new();
+        let now = Utc::now();
+
+        assert!(guard.check(&delivery("a", now), now).is_ok());
+        assert!(matches!(
+            guard.check(&delivery("a", now), now),
+            Err(ApiError::BadRequest(_))
+        ));
+
+        // A delivery that failed can be retried
+        guard.forget(&delivery("a", now));
+        assert!(guard.check(&delivery("a", now), now).is_ok());
+    }
+
+    #[test]
+    fn test_unfinished_attempt_forgotten() {
+        let guard = ReplayGuard::new();
+        let now = Utc::now();
+        let failed = delivery("a", now);
+        let handled = delivery("b", now);
+
+        // Dropped mid-handler, e.g. on an error or a cancelled request
+        drop(guard.begin(&failed, now).unwrap());
+        guard.begin(&handled, now).unwrap().complete();
+
+        assert_eq!(guard.len(), 1);
+        assert!(guard.begin(&failed, now).is_ok());
+        assert!(guard.begin(&handled, now).is_err());
+    }
+
+    #[test]
+    fn test_evict_expired() {
+        let guard = ReplayGuard::new();
+        let now = Utc::now();
+        guard.check(&delivery("a", now), now).unwrap();
+
+        guard.evict_expired(Instant::now());
+        assert_eq!(guard.len(), 1);
+
+        guard.evict_expired(Instant::now() + NONCE_TTL);
+        assert!(guard.is_empty());
+    }
+
+    #[test]
+    fn test_delivery_from_headers() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(
+            Delivery::from_headers(&headers, "X-Delivery").unwrap(),
+            Delivery::default()
+        );
+
+        headers.insert("X-Delivery", HeaderValue::from_static("a1b2"));
+        headers.insert(
+            DATE_HEADER,
+            HeaderValue::from_static("Tue, 01 Oct 2024 12:00:00 GMT"),
+        );
+        let parsed = Delivery::from_headers(&headers, "X-Delivery").unwrap();
+        assert_eq!(parsed.nonce.as_deref(), Some("a1b2"));
+        assert_eq!(
+            parsed.sent_at.unwrap().to_rfc3339(),
+            "2024-10-01T12:00:00+00:00"
+        );
+
+        headers.insert(DATE_HEADER, HeaderValue::from_static("yesterday"));
+        assert!(Delivery::from_headers(&headers, "X-Delivery").is_err());
+    }
+}
@@ -0,0 +1,258 @@
+//! WebSocket routes
+//!
+//! `GET /ws/jobs/:job_id/logs` streams a job's build output. Each log line is
+//! sent as a JSON text frame `{"timestamp": "...", "message": "..."}`, starting
+//! with output written before the client connected. The server closes the
+//! socket with a normal close frame once the job reaches a terminal status.
+//!
+//! `GET /ws/agents` streams agent status changes as JSON text frames
+//! `{"agent_id": "...", "status": "busy", "timestamp": "..."}` (see
+//! [`crate::agent_events`]) until the client disconnects.
+//!
+//! Both require an API key, in the `X-Raibid-Api-Key` header or, for
+//! clients that cannot set headers on the upgrade request, an `api_key`
+//! query parameter.
+
+use axum::extract::ws::{close_code, CloseFrame, Message, WebSocket, WebSocketUpgrade};
+use axum::extract::{Path, State};
+use axum::response::Response;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{debug, warn};
+
+use crate::log_stream::LogEvent;
+use crate::state::AppState;
+use raibid_common::jobs::JobLogEntry;
+
+/// Upgrade to a WebSocket streaming the job's logs
+pub async fn job_logs(
+    ws: WebSocketUpgrade,
+    Path(job_id): Path<String>,
+    State(state): State<AppState>,
+) -> Response {
+    ws.on_upgrade(move |socket| stream_job_logs(socket, state, job_id))
+}
+
+/// Forward log events to the client until the job finishes or the client leaves
+async fn stream_job_logs(mut socket: WebSocket, state: AppState, job_id: String) {
+    debug!("Log stream client connected for job {}", job_id);
+    let subscription = state.logs().subscribe(&job_id);
+    let mut events = subscription.events;
+
+    for entry in &subscription.backlog {
+        if send_entry(&mut socket, entry).await.is_err() {
+            return;
+        }
+    }
+
+    loop {
+        tokio::select! {
+            event = events.recv() => match event {
+                Ok(LogEvent::Entry(entry)) => {
+                    if send_entry(&mut socket, &entry).await.is_err() {
+                        break;
+                    }
+                }
+                Ok(LogEvent::Finished(status)) => {
+                    let _ = socket
+                        .send(Message::Close(Some(CloseFrame {
+                            code: close_code::NORMAL,
+                            reason: format!("job {}", status).into(),
+                        })))
+                        .await;
+                    break;
+                }
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!(
+                        "Log stream client for job {} fell behind, skipped {} entries",
+                        job_id, skipped
+                    );
+                }
+                Err(RecvError::Closed) => break,
+            },
+            message = socket.recv() => match message {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                // Clients have nothing to say; ignore pings and stray frames
+                Some(Ok(_)) => {}
+            },
+        }
+    }
+
+    debug!("Log stream client disconnected for job {}", job_id);
+}
+
+/// Upgrade to a WebSocket streaming agent status changes
+pub async fn agent_events(ws: WebSocketUpgrade, State(state): State<AppState>) -> Response {
+    ws.on_upgrade(move |socket| stream_agent_events(socket, state))
+}
+
+/// Forward agent events to the client until it leaves
+async fn stream_agent_events(mut socket: WebSocket, state: AppState) {
+    debug!("Agent event client connected");
+    let mut events = state.agent_events().subscribe();
+
+    loop {
+        tokio::select! {
+            event = events.recv() => match event {
+                Ok(event) => {
+                    let sent = match serde_json::to_string(&event) {
+                        Ok(frame) => socket.send(Message::Text(frame)).await,
+                        Err(e) => Err(axum::Error::new(e)),
+                    };
+                    if sent.is_err() {
+                        break;
+                    }
+                }
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!("Agent event client fell behind, skipped {} events", skipped);
+                }
+                Err(RecvError::Closed) => break,
+            },
+            message = socket.recv() => match message {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => {}
+            },
+        }
+    }
+
+    debug!("Agent event client disconnected");
+}
+
+async fn send_entry(socket: &mut WebSocket, entry: &JobLogEntry) -> Result<(), axum::Error> {
+    let frame = serde_json::to_string(entry).map_err(axum::Error::new)?;
+    socket.send(Message::Text(frame)).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::log_stream::tests::MockLogSource;
+    use crate::routes;
+    use crate::ServerConfig;
+    use futures::StreamExt;
+    use raibid_common::jobs::JobStatus;
+    use std::net::SocketAddr;
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tokio_tungstenite::tungstenite::Message as ClientMessage;
+
+    async fn serve(source: Arc<MockLogSource>) -> (SocketAddr, AppState) {
+        let state = AppState::new(ServerConfig::default())
+            .unwrap()
+            .with_log_source(source);
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let router = routes::router(state.clone());
+        tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+
+        (addr, state)
+    }
+
+    async fn connect(
+        addr: SocketAddr,
+        job_id: &str,
+    ) -> tokio_tungstenite::WebSocketStream<tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>>
+    {
+        let url = format!("ws://{}/ws/jobs/{}/logs", addr, job_id);
+        tokio_tungstenite::connect_async(url).await.unwrap().0
+    }
+
+    async fn next_message<S>(client: &mut S) -> ClientMessage
+    where
+        S: futures::Stream<Item = tokio_tungstenite::tungstenite::Result<ClientMessage>> + Unpin,
+    {
+        tokio::time::timeout(Duration::from_secs(5), client.next())
+            .await
+            .expect("timed out waiting for frame")
+            .unwrap()
+            .unwrap()
+    }
+
+    fn log_message(message: ClientMessage) -> String {
+        let ClientMessage::Text(text) = message else {
+            panic!("expected text frame, got {:?}", message);
+        };
+        let entry: JobLogEntry = serde_json::from_str(&text).unwrap();
+        entry.message
+    }
+
+    #[tokio::test]
+    async fn test_streams_logs_until_job_finishes() {
+        let source = Arc::new(MockLogSource::default());
+        source.push("job-1", "Compiling raibid-cli");
+        source.push("job-1", "Finished dev profile");
+        let (addr, _state) = serve(source.clone()).await;
+
+        let mut client = connect(addr, "job-1").await;
+        assert_eq!(
+            log_message(next_message(&mut client).await),
+            "Compiling raibid-cli"
+        );
+        assert_eq!(
+            log_message(next_message(&mut client).await),
+            "Finished dev profile"
+        );
+
+        source.push("job-1", "test result: ok");
+        source.set_status("job-1", JobStatus::Success);
+        assert_eq!(
+            log_message(next_message(&mut client).await),
+            "test result: ok"
+        );
+
+        let ClientMessage::Close(Some(frame)) = next_message(&mut client).await else {
+            panic!("expected close frame");
+        };
+        assert_eq!(frame.reason, "job success");
+    }
+
+    #[tokio::test]
+    async fn test_clients_share_job_reader() {
+        let source = Arc::new(MockLogSource::default());
+        let (addr, state) = serve(source.clone()).await;
+
+        let mut first = connect(addr, "job-2").await;
+        let mut second = connect(addr, "job-2").await;
+        source.push("job-2", "Running clippy");
+
+        assert_eq!(
+            log_message(next_message(&mut first).await),
+            "Running clippy"
+        );
+        assert_eq!(
+            log_message(next_message(&mut second).await),
+            "Running clippy"
+        );
+        assert_eq!(state.logs().active_jobs(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_streams_agent_events() {
+        use raibid_common::agents::{AgentEvent, AgentStatus};
+
+        let (addr, state) = serve(Arc::new(MockLogSource::default())).await;
+        let url = format!("ws://{}/ws/agents", addr);
+        let mut client = tokio_tungstenite::connect_async(url).await.unwrap().0;
+
+        // The socket subscribes after the handshake, so publish until it has
+        let event = AgentEvent::now("agent-1", AgentStatus::Busy);
+        let payload = serde_json::to_string(&event).unwrap();
+        let publisher = tokio::spawn(async move {
+            loop {
+                state.agent_events().handle_message(&payload);
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+        });
+
+        let message = tokio::time::timeout(Duration::from_millis(500), client.next())
+            .await
+            .expect("event not received within 500ms")
+            .unwrap()
+            .unwrap();
+        publisher.abort();
+        let ClientMessage::Text(text) = message else {
+            panic!("expected text frame, got {:?}", message);
+        };
+        assert_eq!(serde_json::from_str::<AgentEvent>(&text).unwrap(), event);
+    }
+}
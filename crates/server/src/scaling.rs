@@ -0,0 +1,198 @@
+//! Agent scaling
+//!
+//! Agents run as a Deployment scaled by a KEDA ScaledObject on the job queue
+//! depth. `POST /api/agents/scale` changes the replica range by patching the
+//! ScaledObject's `minReplicaCount` and `maxReplicaCount` through a
+//! [`ScaleTarget`]. The server owns the Kubernetes client, so clients without
+//! cluster access can still scale the agents.
+//!
+//! `POST /api/agents/:id/restart` deletes the pod of one agent through an
+//! [`AgentDeployment`]; the Deployment starts a new pod in its place, and the
+//! other agents keep running their jobs.
+
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use kube::api::{Api, ApiResource, DeleteParams, DynamicObject, Patch, PatchParams};
+use kube::core::GroupVersionKind;
+use kube::Client;
+use serde_json::json;
+use tracing::info;
+
+use raibid_common::agents::AgentScale;
+
+/// Where the agent replica range is applied
+#[async_trait]
+pub trait ScaleTarget: Send + Sync + 'static {
+    /// Apply the replica range of `scale`
+    async fn apply(&self, scale: &AgentScale) -> Result<()>;
+}
+
+/// A KEDA ScaledObject, patched through the Kubernetes API
+#[derive(Debug, Clone)]
+pub struct KedaScaledObject {
+    name: String,
+    namespace: String,
+}
+
+impl KedaScaledObject {
+    /// The ScaledObject `name` in `namespace`
+    pub fn new(name: impl Into<String>, namespace: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            namespace: namespace.into(),
+        }
+    }
+
+    fn api_resource() -> ApiResource {
+        ApiResource::from_gvk(&GroupVersionKind::gvk(
+            "keda.sh",
+            "v1alpha1",
+            "ScaledObject",
+        ))
+    }
+}
+
+#[async_trait]
+impl ScaleTarget for KedaScaledObject {
+    async fn apply(&self, scale: &AgentScale) -> Result<()> {
+        // Connecting per call keeps the server usable without cluster access;
+        // scaling is rare enough not to need a shared client
+        let client = Client::try_default()
+            .await
+            .context("Failed to connect to the Kubernetes API")?;
+        let api: Api<DynamicObject> =
+            Api::namespaced_with(client, &self.namespace, &Self::api_resource());
+
+        api.patch(
+            &self.name,
+            &PatchParams::default(),
+            &Patch::Merge(scaled_object_patch(scale)),
+        )
+        .await
+        .with_context(|| {
+            format!(
+                "Failed to patch ScaledObject {}/{}",
+                self.namespace, self.name
+            )
+        })?;
+
+        info!(
+            "Scaled agents to {}-{} replicas ({}/{})",
+            scale.min, scale.max, self.namespace, self.name
+        );
+        Ok(())
+    }
+}
+
+/// Where agents are restarted
+#[async_trait]
+pub trait AgentDeployment: Send + Sync + 'static {
+    /// Replace the agent pod `pod`, leaving the other agents running
+    async fn restart_pod(&self, pod: &str) -> Result<()>;
+}
+
+/// The agents' Deployment, whose pods are deleted through the Kubernetes API
+#[derive(Debug, Clone)]
+pub struct KubeDeployment {
+    name: String,
+    namespace: String,
+}
+
+impl KubeDeployment {
+    /// The Deployment `name` in `namespace`
+    pub fn new(name: impl Into<String>, namespace: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            namespace: namespace.into(),
+        }
+    }
+
+    /// Whether `pod` is named like a pod of this Deployment
+    /// (`<name>-<replicaset hash>-<suffix>`)
+    pub fn owns(&self, pod: &str) -> bool {
+        pod.strip_prefix(&self.name)
+            .and_then(|rest| rest.strip_prefix('-'))
+            .is_some_and(|rest| !rest.is_empty())
+    }
+
+    fn pod_resource() -> ApiResource {
+        ApiResource::from_gvk(&GroupVersionKind::gvk("", "v1", "Pod"))
+    }
+}
+
+#[async_trait]
+impl AgentDeployment for KubeDeployment {
+    async fn restart_pod(&self, pod: &str) -> Result<()> {
+        if !self.owns(pod) {
+            bail!("Pod {} is not part of Deployment {}", pod, self.name);
+        }
+
+        let client = Client::try_default()
+            .await
+            .context("Failed to connect to the Kubernetes API")?;
+        let api: Api<DynamicObject> =
+            Api::namespaced_with(client, &self.namespace, &Self::pod_resource());
+
+        // The Deployment's ReplicaSet starts a replacement for the deleted pod
+        api.delete(pod, &DeleteParams::default())
+            .await
+            .with_context(|| format!("Failed to delete pod {}/{}", self.namespace, pod))?;
+
+        info!("Deleted agent pod {}/{}", self.namespace, pod);
+        Ok(())
+    }
+}
+
+/// Merge patch setting a ScaledObject's replica range
+pub fn scaled_object_patch(scale: &AgentScale) -> serde_json::Value {
+    json!({
+        "spec": {
+            "minReplicaCount": scale.min,
+            "maxReplicaCount": scale.max,
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scaled_object_patch() {
+        let patch = scaled_object_patch(&AgentScale {
+            min: 1,
+            max: 8,
+            desired: Some(4),
+        });
+        assert_eq!(
+            patch,
+            json!({"spec": {"minReplicaCount": 1, "maxReplicaCount": 8}})
+        );
+    }
+
+    #[test]
+    fn test_scaled_object_resource() {
+        let resource = KedaScaledObject::api_resource();
+        assert_eq!(resource.group, "keda.sh");
+        assert_eq!(resource.version, "v1alpha1");
+        assert_eq!(resource.plural, "scaledobjects");
+    }
+
+    #[test]
+    fn test_deployment_owns_pod() {
+        let deployment = KubeDeployment::new("raibid-ci-agent", "raibid-ci");
+        assert!(deployment.owns("raibid-ci-agent-7d9f8b6c5-x2k4p"));
+        assert!(!deployment.owns("raibid-ci-agent"));
+        assert!(!deployment.owns("raibid-ci-agent-"));
+        assert!(!deployment.owns("raibid-ci-agentx-7d9f8b6c5-x2k4p"));
+        assert!(!deployment.owns("redis-master-0"));
+    }
+
+    #[test]
+    fn test_pod_resource() {
+        let resource = KubeDeployment::pod_resource();
+        assert_eq!(resource.group, "");
+        assert_eq!(resource.version, "v1");
+        assert_eq!(resource.plural, "pods");
+    }
+}
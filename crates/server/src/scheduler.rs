@@ -0,0 +1,245 @@
+//! Build scheduler
+//!
+//! Schedules are managed with the `/api/schedules` routes (see
+//! [`crate::routes::schedules`]) and stored in Redis. Every
+//! [`SCHEDULER_INTERVAL`] a background task loads them and queues a build for
+//! each schedule whose cron expression matched since it last ran, or since it
+//! was created. Runs missed while the server was down are collapsed into a
+//! single build.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use redis::AsyncCommands;
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+
+use crate::routes::queue;
+use crate::state::AppState;
+use raibid_common::jobs::{JobBuilder, QueuedJob};
+use raibid_common::schedules::{Schedule, ScheduleRequest, SCHEDULES_KEY};
+
+/// Interval between schedule checks
+pub const SCHEDULER_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Parse a cron expression
+///
+/// Five-field expressions (`0 2 * * *`) run at second 0; six and seven-field
+/// expressions start with seconds (and end with years) as in the `cron` crate.
+pub fn parse_cron(expression: &str) -> std::result::Result<cron::Schedule, String> {
+    let expression = expression.trim();
+    let full = if expression.split_whitespace().count() == 5 {
+        format!("0 {}", expression)
+    } else {
+        expression.to_string()
+    };
+    cron::Schedule::from_str(&full)
+        .map_err(|e| format!("Invalid cron expression '{}': {}", expression, e))
+}
+
+/// Parse an IANA time zone name
+pub fn parse_timezone(name: &str) -> std::result::Result<Tz, String> {
+    name.parse()
+        .map_err(|_| format!("Unknown time zone '{}'", name))
+}
+
+/// Check that a schedule request names a buildable branch, a valid cron
+/// expression and a known time zone
+pub fn validate(request: &ScheduleRequest) -> std::result::Result<(), String> {
+    JobBuilder::new(&request.repo)
+        .branch(&request.branch)
+        .build()
+        .map_err(|e| format!("{:#}", e))?;
+    parse_cron(&request.cron)?;
+    parse_timezone(&request.timezone)?;
+    Ok(())
+}
+
+/// First time the schedule matches after it last ran (or was created)
+pub fn next_run(schedule: &Schedule) -> std::result::Result<Option<DateTime<Utc>>, String> {
+    let cron = parse_cron(&schedule.cron)?;
+    let tz = parse_timezone(&schedule.timezone)?;
+    let since = schedule.last_run.unwrap_or(schedule.created_at);
+
+    Ok(cron
+        .after(&since.with_timezone(&tz))
+        .next()
+        .map(|time| time.with_timezone(&Utc)))
+}
+
+/// Whether a build of the schedule is due at `now`
+pub fn is_due(schedule: &Schedule, now: DateTime<Utc>) -> bool {
+    matches!(next_run(schedule), Ok(Some(time)) if time <= now)
+}
+
+/// Queue a build for every schedule due at `now`, returning how many were
+/// queued
+///
+/// Nothing is queued while the server is draining; due schedules run once
+/// it accepts jobs again.
+pub async fn run_due_schedules(state: &AppState, now: DateTime<Utc>) -> Result<usize> {
+    if state.is_draining() {
+        return Ok(0);
+    }
+
+    let stored: HashMap<String, String> = state
+        .with_redis(|mut conn| async move { conn.hgetall(SCHEDULES_KEY).await })
+        .await?;
+    let stream = state.config().queue_stream.clone();
+
+    let mut queued = 0;
+    for (id, json) in stored {
+        let mut schedule: Schedule = match serde_json::from_str(&json) {
+            Ok(schedule) => schedule,
+            Err(e) => {
+                warn!("Skipping invalid schedule {}: {}", id, e);
+                continue;
+            }
+        };
+        if !is_due(&schedule, now) {
+            continue;
+        }
+
+        let trigger = JobBuilder::new(&schedule.repo)
+            .branch(&schedule.branch)
+            .build()
+            .with_context(|| format!("Invalid schedule {}", id))?;
+        let job = QueuedJob::new(uuid::Uuid::new_v4().to_string(), trigger);
+        schedule.last_run = Some(now);
+        let json = serde_json::to_string(&schedule).context("Failed to encode schedule")?;
+
+        let still_scheduled: bool = state
+            .with_redis(|mut conn| {
+                let (stream, job, id, json) =
+                    (stream.clone(), job.clone(), id.clone(), json.clone());
+                async move {
+                    // Don't resurrect a schedule deleted since it was loaded
+                    let exists: bool = conn.hexists(SCHEDULES_KEY, &id).await?;
+                    if !exists {
+                        return Ok(false);
+                    }
+                    queue::queue_job(&mut conn, &stream, &job).await?;
+                    conn.hset::<_, _, _, ()>(SCHEDULES_KEY, &id, &json).await?;
+                    Ok(true)
+                }
+            })
+            .await?;
+
+        if still_scheduled {
+            state.response_cache().invalidate_job(&job.id);
+            info!(
+                "Queued job {} for schedule {} ({}@{}, '{}')",
+                job.id, id, schedule.repo, schedule.branch, schedule.cron
+            );
+            queued += 1;
+        }
+    }
+
+    Ok(queued)
+}
+
+/// Spawn the background task that queues due schedules
+pub fn spawn_scheduler(state: AppState) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(SCHEDULER_INTERVAL);
+        loop {
+            interval.tick().await;
+            if !state.redis_available() {
+                debug!("Redis unavailable, skipping schedule check");
+                continue;
+            }
+            if let Err(e) = run_due_schedules(&state, Utc::now()).await {
+                warn!("Failed to run scheduled builds: {:#}", e);
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn schedule(cron: &str, timezone: &str, created_at: DateTime<Utc>) -> Schedule {
+        Schedule::new(
+            "sched-1",
+            ScheduleRequest {
+                repo: "raibid-labs/raibid-cli".to_string(),
+                branch: "main".to_string(),
+                cron: cron.to_string(),
+                timezone: timezone.to_string(),
+            },
+            created_at,
+        )
+    }
+
+    #[test]
+    fn test_validate() {
+        let valid = |cron: &str, timezone: &str| {
+            validate(&ScheduleRequest {
+                repo: "raibid-labs/raibid-cli".to_string(),
+                branch: "main".to_string(),
+                cron: cron.to_string(),
+                timezone: timezone.to_string(),
+            })
+        };
+        assert!(valid("0 2 * * *", "UTC").is_ok());
+        assert!(valid("30 0 2 * * MON-FRI", "Europe/Berlin").is_ok());
+        assert!(valid("0 2 * *", "UTC").is_err());
+        assert!(valid("0 25 * * *", "UTC").is_err());
+        assert!(valid("0 2 * * *", "Mars/Olympus").is_err());
+    }
+
+    #[test]
+    fn test_next_run_in_timezone() {
+        let created = Utc.with_ymd_and_hms(2024, 1, 15, 0, 0, 0).unwrap();
+        let berlin = schedule("0 2 * * *", "Europe/Berlin", created);
+        // 02:00 in Berlin is 01:00 UTC in winter
+        assert_eq!(
+            next_run(&berlin).unwrap(),
+            Some(Utc.with_ymd_and_hms(2024, 1, 15, 1, 0, 0).unwrap())
+        );
+    }
+
+    #[test]
+    fn test_fires_within_a_minute_of_trigger_time() {
+        // Fast clock: ticks every scheduler interval from an odd offset
+        let created = Utc.with_ymd_and_hms(2024, 1, 15, 1, 57, 23).unwrap();
+        let trigger = Utc.with_ymd_and_hms(2024, 1, 15, 2, 0, 0).unwrap();
+        let mut schedule = schedule("0 2 * * *", "UTC", created);
+        let interval = chrono::Duration::from_std(SCHEDULER_INTERVAL).unwrap();
+
+        let mut fired = Vec::new();
+        let mut now = created;
+        while now < trigger + chrono::Duration::hours(23) {
+            now += interval;
+            if is_due(&schedule, now) {
+                schedule.last_run = Some(now);
+                fired.push(now);
+            }
+        }
+
+        assert_eq!(fired.len(), 1, "fired at {:?}", fired);
+        let delay = fired[0] - trigger;
+        assert!(delay >= chrono::Duration::zero());
+        assert!(
+            delay <= chrono::Duration::seconds(65),
+            "fired {} late",
+            delay
+        );
+    }
+
+    #[test]
+    fn test_missed_runs_collapse() {
+        let created = Utc.with_ymd_and_hms(2024, 1, 15, 0, 0, 0).unwrap();
+        let mut schedule = schedule("0 * * * *", "UTC", created);
+
+        let later = created + chrono::Duration::hours(5);
+        assert!(is_due(&schedule, later));
+        schedule.last_run = Some(later);
+        assert!(!is_due(&schedule, later + chrono::Duration::minutes(30)));
+    }
+}
@@ -0,0 +1,800 @@
+//! Shared application state
+//!
+//! `AppState` is cloned into every request handler and owns the Redis
+//! connection pool. Redis may restart underneath the server (pod reschedule,
+//! Helm upgrade), so pool access goes through [`AppState::with_redis`], which
+//! flushes stale connections and retries once on connection errors, and a
+//! background task keeps the availability flag current. Repeated failures
+//! open a [`CircuitBreaker`], so requests stop waiting on an unreachable
+//! Redis.
+//!
+//! The configuration can be swapped while the server runs; see
+//! [`AppState::reload_config`]. Handlers read it through [`AppState::config`]
+//! once per request.
+
+use anyhow::{bail, Context, Result};
+use deadpool_redis::{
+    Config as RedisPoolConfig, Connection, Pool, PoolConfig, PoolError, Runtime, Timeouts,
+};
+use metrics_exporter_prometheus::PrometheusHandle;
+use redis::RedisError;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+use tower_http::cors::CorsLayer;
+use tracing::{debug, info, warn};
+
+use crate::agent_events::{publish_agent_event, AgentEventHub};
+use crate::agents::{AgentRegistry, HEALTH_SWEEP_INTERVAL};
+use crate::cache::ResponseCache;
+use crate::circuit_breaker::{CircuitBreaker, CircuitOpen};
+use crate::config_file::ConfigFile;
+use crate::log_stream::{LogMultiplexer, LogSource, RedisLogSource};
+use crate::middleware::{ApiKeys, CorsConfig, WebhookDedup};
+use crate::routes::metrics::prometheus_handle;
+use crate::routes::webhooks::replay::ReplayGuard;
+use crate::scaling::{AgentDeployment, KedaScaledObject, KubeDeployment, ScaleTarget};
+use crate::ServerConfig;
+use raibid_common::agents::{AgentEvent, AgentScale, AgentStatus};
+
+/// Interval between background Redis health checks
+pub const REDIS_HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Minimum supported Redis version
+///
+/// Streams need 5.0; `XAUTOCLAIM`, used to recover jobs from dead agents,
+/// needs 6.2.
+pub const MIN_REDIS_VERSION: RedisVersion = RedisVersion {
+    major: 6,
+    minor: 2,
+    patch: 0,
+};
+
+/// A Redis server version as reported by `INFO server`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct RedisVersion {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+}
+
+impl RedisVersion {
+    /// Extract `redis_version` from an `INFO server` response
+    pub fn from_info(info: &str) -> Option<Self> {
+        let version = info
+            .lines()
+            .find_map(|line| line.trim().strip_prefix("redis_version:"))?;
+
+        let mut parts = version.trim().split('.').map(|p| p.parse::<u32>());
+        Some(Self {
+            major: parts.next()?.ok()?,
+            minor: parts.next().unwrap_or(Ok(0)).ok()?,
+            patch: parts.next().unwrap_or(Ok(0)).ok()?,
+        })
+    }
+}
+
+impl std::fmt::Display for RedisVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+/// Shared state for all request handlers
+#[derive(Clone)]
+pub struct AppState {
+    config: Arc<RwLock<Arc<ServerConfig>>>,
+    redis: Pool,
+    redis_available: Arc<AtomicBool>,
+    redis_circuit: CircuitBreaker,
+    logs: Arc<LogMultiplexer>,
+    metrics: Option<PrometheusHandle>,
+    api_keys: Arc<ApiKeys>,
+    admin_keys: Arc<ApiKeys>,
+    agents: Arc<AgentRegistry>,
+    agent_events: Arc<AgentEventHub>,
+    replay_guard: Arc<ReplayGuard>,
+    webhook_dedup: Arc<WebhookDedup>,
+    cors: Option<CorsLayer>,
+    scale_target: Arc<dyn ScaleTarget>,
+    agent_deployment: Arc<dyn AgentDeployment>,
+    agent_scale: Arc<RwLock<Option<AgentScale>>>,
+    response_cache: Arc<ResponseCache>,
+    shutdown: Arc<Mutex<Option<oneshot::Sender<()>>>>,
+    draining: Arc<AtomicBool>,
+    started: Arc<AtomicBool>,
+}
+
+impl AppState {
+    /// Create application state and the Redis connection pool
+    ///
+    /// The pool connects lazily, so this succeeds even if Redis is down.
+    pub fn new(config: ServerConfig) -> Result<Self> {
+        if config.redis_pool_size == 0 {
+            bail!("The Redis pool needs at least one connection (RAIBID_REDIS_POOL_SIZE)");
+        }
+
+        let mut timeouts = Timeouts::new();
+        timeouts.create = Some(Duration::from_millis(config.redis_connect_timeout_ms));
+        timeouts.wait = Some(Duration::from_millis(config.redis_wait_timeout_ms));
+        let mut pool_config = PoolConfig::new(config.redis_pool_size);
+        pool_config.timeouts = timeouts;
+
+        let mut redis_config = RedisPoolConfig::from_url(config.redis_url.clone());
+        redis_config.pool = Some(pool_config);
+        let redis = redis_config
+            .create_pool(Some(Runtime::Tokio1))
+            .context("Failed to create Redis connection pool")?;
+
+        let logs = Arc::new(LogMultiplexer::new(Arc::new(RedisLogSource::new(
+            redis.clone(),
+        ))));
+
+        let metrics = config.metrics_enabled.then(prometheus_handle);
+        let api_keys = Arc::new(ApiKeys::new(config.api_keys.clone()));
+        let admin_keys = Arc::new(ApiKeys::new(config.admin_keys.clone()));
+        let cors = config
+            .cors
+            .as_ref()
+            .map(CorsConfig::layer)
+            .transpose()
+            .context("Invalid CORS configuration")?;
+        let scale_target = Arc::new(KedaScaledObject::new(
+            config.scaled_object_name.clone(),
+            config.scaled_object_namespace.clone(),
+        ));
+        let agent_deployment = Arc::new(KubeDeployment::new(
+            config.agent_deployment_name.clone(),
+            config.scaled_object_namespace.clone(),
+        ));
+        let response_cache = Arc::new(ResponseCache::new(Duration::from_millis(
+            config.cache_ttl_ms,
+        )));
+
+        Ok(Self {
+            config: Arc::new(RwLock::new(Arc::new(config))),
+            redis,
+            redis_available: Arc::new(AtomicBool::new(true)),
+            redis_circuit: CircuitBreaker::new(),
+            logs,
+            metrics,
+            api_keys,
+            admin_keys,
+            agents: Arc::new(AgentRegistry::new()),
+            agent_events: Arc::new(AgentEventHub::new()),
+            replay_guard: Arc::new(ReplayGuard::new()),
+            webhook_dedup: Arc::new(WebhookDedup::new()),
+            cors,
+            scale_target,
+            agent_deployment,
+            agent_scale: Arc::new(RwLock::new(None)),
+            response_cache,
+            shutdown: Arc::new(Mutex::new(None)),
+            draining: Arc::new(AtomicBool::new(false)),
+            started: Arc::new(AtomicBool::new(false)),
+        })
+    }
+
+    /// Read job logs from `source` instead of Redis
+    pub fn with_log_source(mut self, source: Arc<dyn LogSource>) -> Self {
+        self.logs = Arc::new(LogMultiplexer::new(source));
+        self
+    }
+
+    /// Apply agent scaling to `target` instead of the KEDA ScaledObject
+    pub fn with_scale_target(mut self, target: Arc<dyn ScaleTarget>) -> Self {
+        self.scale_target = target;
+        self
+    }
+
+    /// Restart agents through `deployment` instead of the Kubernetes API
+    pub fn with_agent_deployment(mut self, deployment: Arc<dyn AgentDeployment>) -> Self {
+        self.agent_deployment = deployment;
+        self
+    }
+
+    /// Get the current server configuration
+    pub fn config(&self) -> Arc<ServerConfig> {
+        self.config.read().unwrap().clone()
+    }
+
+    /// Re-read the configuration file and apply it, returning the names of
+    /// the settings it set
+    ///
+    /// API keys, admin keys, webhook credentials and the benchmark threshold
+    /// take effect for the next request. Nothing changes if the file cannot
+    /// be read or is invalid.
+    pub fn reload_config(&self) -> Result<Vec<&'static str>> {
+        let current = self.config();
+        let Some(ref path) = current.config_file else {
+            bail!("No configuration file is configured (RAIBID_SERVER_CONFIG)");
+        };
+
+        let mut config = (*current).clone();
+        let applied = ConfigFile::load(path)?.apply(&mut config)?;
+        self.api_keys.replace(config.api_keys.clone());
+        self.admin_keys.replace(config.admin_keys.clone());
+        *self.config.write().unwrap() = Arc::new(config);
+
+        info!(
+            "Reloaded {} ({})",
+            path.display(),
+            if applied.is_empty() {
+                "no settings".to_string()
+            } else {
+                applied.join(", ")
+            }
+        );
+        Ok(applied)
+    }
+
+    /// Receiver the server waits on for a shutdown request
+    ///
+    /// Replaces the receiver of any earlier call.
+    pub fn shutdown_signal(&self) -> oneshot::Receiver<()> {
+        let (tx, rx) = oneshot::channel();
+        *self.shutdown.lock().unwrap() = Some(tx);
+        rx
+    }
+
+    /// Ask the server to shut down gracefully
+    ///
+    /// Returns false if no server is waiting for a shutdown request, or one
+    /// was already requested.
+    pub fn request_shutdown(&self) -> bool {
+        match self.shutdown.lock().unwrap().take() {
+            Some(tx) => tx.send(()).is_ok(),
+            None => false,
+        }
+    }
+
+    /// Whether the server stopped accepting new jobs from webhooks
+    pub fn is_draining(&self) -> bool {
+        self.draining.load(Ordering::Relaxed)
+    }
+
+    /// Stop accepting new jobs from webhooks
+    ///
+    /// Returns whether the server was already draining.
+    pub fn start_draining(&self) -> bool {
+        self.draining.swap(true, Ordering::Relaxed)
+    }
+
+    /// Whether startup checks passed and background tasks are running
+    pub fn is_started(&self) -> bool {
+        self.started.load(Ordering::Relaxed)
+    }
+
+    /// Record that startup finished, see [`AppState::is_started`]
+    pub fn mark_started(&self) {
+        self.started.store(true, Ordering::Relaxed);
+    }
+
+    /// Get the CORS layer, if CORS is configured
+    pub fn cors(&self) -> Option<&CorsLayer> {
+        self.cors.as_ref()
+    }
+
+    /// Get where agent scaling is applied
+    pub fn scale_target(&self) -> &Arc<dyn ScaleTarget> {
+        &self.scale_target
+    }
+
+    /// Get where agents are restarted
+    pub fn agent_deployment(&self) -> &Arc<dyn AgentDeployment> {
+        &self.agent_deployment
+    }
+
+    /// Agent replica range last applied through this server
+    pub fn agent_scale(&self) -> Option<AgentScale> {
+        *self.agent_scale.read().unwrap()
+    }
+
+    /// Record the agent replica range after applying it
+    pub fn set_agent_scale(&self, scale: AgentScale) {
+        *self.agent_scale.write().unwrap() = Some(scale);
+    }
+
+    /// Get the Redis connection pool
+    ///
+    /// Prefer [`AppState::with_redis`] for request handling; it recovers from
+    /// stale connections after a Redis restart.
+    pub fn redis_pool(&self) -> &Pool {
+        &self.redis
+    }
+
+    /// Get the live job log multiplexer
+    pub fn logs(&self) -> &Arc<LogMultiplexer> {
+        &self.logs
+    }
+
+    /// Get the Prometheus recorder handle, if metrics are enabled
+    pub fn metrics(&self) -> Option<&PrometheusHandle> {
+        self.metrics.as_ref()
+    }
+
+    /// Get the API keys accepted on `/api/` routes
+    pub fn api_keys(&self) -> &Arc<ApiKeys> {
+        &self.api_keys
+    }
+
+    /// Get the admin keys accepted on `/admin/` routes
+    pub fn admin_keys(&self) -> &Arc<ApiKeys> {
+        &self.admin_keys
+    }
+
+    /// Get the registry of agents and their health
+    pub fn agents(&self) -> &Arc<AgentRegistry> {
+        &self.agents
+    }
+
+    /// Get the hub broadcasting agent status changes to clients
+    pub fn agent_events(&self) -> &Arc<AgentEventHub> {
+        &self.agent_events
+    }
+
+    /// Get the secret GitLab webhook requests must carry, if configured
+    pub fn gitlab_webhook_secret(&self) -> Option<String> {
+        self.config().gitlab_webhook_secret.clone()
+    }
+
+    /// Get the cache of job list and status responses
+    pub fn response_cache(&self) -> &Arc<ResponseCache> {
+        &self.response_cache
+    }
+
+    /// Get the webhook delivery IDs seen recently
+    pub fn replay_guard(&self) -> &Arc<ReplayGuard> {
+        &self.replay_guard
+    }
+
+    /// Get the webhook delivery IDs received recently, for deduplication
+    pub fn webhook_dedup(&self) -> &Arc<WebhookDedup> {
+        &self.webhook_dedup
+    }
+
+    /// Whether the last Redis health check succeeded
+    pub fn redis_available(&self) -> bool {
+        self.redis_available.load(Ordering::Relaxed)
+    }
+
+    /// Get the circuit breaker guarding Redis access
+    pub fn redis_circuit(&self) -> &CircuitBreaker {
+        &self.redis_circuit
+    }
+
+    #[cfg(test)]
+    pub(crate) fn set_redis_available(&self, available: bool) {
+        self.redis_available.store(available, Ordering::Relaxed);
+    }
+
+    /// Take a connection from the pool
+    ///
+    /// Waits up to `redis_wait_timeout_ms` for one to be returned when all
+    /// `redis_pool_size` connections are in use. [`AppState::with_redis`]
+    /// should be preferred, as it also recovers from Redis restarts.
+    pub async fn redis_conn(&self) -> Result<Connection> {
+        self.redis
+            .get()
+            .await
+            .context("Failed to get a Redis connection")
+    }
+
+    /// Run a Redis operation, reconnecting once on connection errors
+    ///
+    /// On a connection error every pooled connection is dropped (they all point
+    /// at the same, possibly restarted, Redis instance) and the operation is
+    /// retried with a fresh connection.
+    ///
+    /// Fails with [`CircuitOpen`] without touching Redis while the circuit
+    /// breaker is open.
+    pub async fn with_redis<T, F, Fut>(&self, op: F) -> Result<T>
+    where
+        F: Fn(Connection) -> Fut,
+        Fut: Future<Output = redis::RedisResult<T>>,
+    {
+        if !self.redis_circuit.allow_request() {
+            return Err(CircuitOpen.into());
+        }
+
+        let result = self.try_redis_with_retry(&op).await;
+        match result {
+            // Command errors still mean Redis answered
+            Ok(_) | Err(RedisOpError::Other(_)) => self.redis_circuit.record_success(),
+            Err(RedisOpError::Connection(_) | RedisOpError::Timeout(_)) => {
+                self.redis_circuit.record_failure()
+            }
+        }
+        result.map_err(Into::into)
+    }
+
+    async fn try_redis_with_retry<T, F, Fut>(&self, op: &F) -> std::result::Result<T, RedisOpError>
+    where
+        F: Fn(Connection) -> Fut,
+        Fut: Future<Output = redis::RedisResult<T>>,
+    {
+        match self.try_redis(op).await {
+            Ok(value) => Ok(value),
+            Err(RedisOpError::Connection(reason)) => {
+                warn!(
+                    "Redis connection error ({}), dropping stale connections and retrying",
+                    reason
+                );
+                self.reset_pool();
+
+                let result = self.try_redis(op).await;
+                if result.is_err() {
+                    self.redis_available.store(false, Ordering::Relaxed);
+                }
+                result
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn try_redis<T, F, Fut>(&self, op: &F) -> std::result::Result<T, RedisOpError>
+    where
+        F: Fn(Connection) -> Fut,
+        Fut: Future<Output = redis::RedisResult<T>>,
+    {
+        let conn = self.redis.get().await.map_err(RedisOpError::from_pool)?;
+        op(conn).await.map_err(RedisOpError::from_redis)
+    }
+
+    /// Check that the Redis server supports every command the server relies on
+    ///
+    /// Connecting succeeds against any Redis version, so an old server would
+    /// otherwise only surface as command errors once jobs start flowing.
+    pub async fn validate_redis_version(&self) -> Result<()> {
+        let info: String = self
+            .with_redis(|mut conn| async move {
+                redis::cmd("INFO")
+                    .arg("server")
+                    .query_async(&mut conn)
+                    .await
+            })
+            .await
+            .context("Failed to query Redis server info")?;
+
+        let version = RedisVersion::from_info(&info)
+            .context("Redis INFO response did not include a valid redis_version")?;
+        check_redis_version(version)?;
+
+        info!("Connected to Redis {}", version);
+        Ok(())
+    }
+
+    /// Drop all idle pooled connections so the next checkout reconnects
+    fn reset_pool(&self) {
+        let max_size = self.redis.status().max_size;
+        self.redis.resize(0);
+        self.redis.resize(max_size);
+    }
+
+    /// Spawn the background task that sweeps the agent registry
+    ///
+    /// Agents found dead are published as offline on the agent events
+    /// channel, or only broadcast to this server's clients if Redis is down.
+    pub fn spawn_agent_health_check(&self) -> JoinHandle<()> {
+        let state = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(HEALTH_SWEEP_INTERVAL);
+            loop {
+                interval.tick().await;
+                for agent_id in state.agents.sweep(chrono::Utc::now()) {
+                    let event = AgentEvent::now(agent_id, AgentStatus::Offline);
+                    let published = state
+                        .with_redis(|mut conn| {
+                            let event = event.clone();
+                            async move { publish_agent_event(&mut conn, &event).await }
+                        })
+                        .await;
+                    if let Err(e) = published {
+                        warn!("Failed to publish agent event: {:#}", e);
+                        state.agent_events.broadcast(event);
+                    }
+                }
+            }
+        })
+    }
+
+    /// Spawn the background task that pings Redis and tracks availability
+    pub fn spawn_redis_health_check(&self) -> JoinHandle<()> {
+        let state = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(REDIS_HEALTH_CHECK_INTERVAL);
+            let mut outage_started: Option<Instant> = None;
+
+            loop {
+                interval.tick().await;
+
+                let ping = state
+                    .with_redis(|mut conn| async move {
+                        redis::cmd("PING").query_async::<_, String>(&mut conn).await
+                    })
+                    .await;
+
+                match (ping, outage_started) {
+                    (Ok(_), None) => {
+                        debug!("Redis health check passed");
+                        state.redis_available.store(true, Ordering::Relaxed);
+                    }
+                    (Ok(_), Some(started)) => {
+                        info!(
+                            "Redis connection restored after {:?} outage",
+                            started.elapsed()
+                        );
+                        outage_started = None;
+                        state.redis_available.store(true, Ordering::Relaxed);
+                    }
+                    (Err(e), None) => {
+                        warn!(
+                            "Redis health check failed, marking Redis unavailable: {}",
+                            e
+                        );
+                        outage_started = Some(Instant::now());
+                        state.redis_available.store(false, Ordering::Relaxed);
+                    }
+                    (Err(e), Some(started)) => {
+                        debug!(
+                            "Redis still unavailable after {:?}: {}",
+                            started.elapsed(),
+                            e
+                        );
+                        state.redis_available.store(false, Ordering::Relaxed);
+                    }
+                }
+            }
+        })
+    }
+}
+
+/// Fail if `version` is older than [`MIN_REDIS_VERSION`]
+fn check_redis_version(version: RedisVersion) -> Result<()> {
+    if version < MIN_REDIS_VERSION {
+        bail!(
+            "Redis {} is not supported: raibid-server requires Redis >= {} \
+             (streams need 5.0, XAUTOCLAIM needs 6.2).\n\
+             Upgrade Redis with:\n  helm upgrade redis bitnami/redis --set image.tag=7.x",
+            version,
+            MIN_REDIS_VERSION
+        );
+    }
+    Ok(())
+}
+
+/// Classified failure of a single Redis operation attempt
+#[derive(Debug)]
+enum RedisOpError {
+    /// Connection-level failure; worth reconnecting and retrying
+    Connection(String),
+    /// No pooled connection became available in time
+    Timeout(String),
+    /// Any other failure (bad command, wrong type, ...)
+    Other(anyhow::Error),
+}
+
+impl RedisOpError {
+    fn from_pool(err: PoolError) -> Self {
+        match err {
+            PoolError::Backend(e) => Self::from_redis(e),
+            // Every connection busy (or a connect attempt stalled); dropping
+            // the idle ones would not help
+            PoolError::Timeout(kind) => Self::Timeout(format!("{:?}", kind)),
+            other => Self::Other(anyhow::anyhow!("Redis pool error: {}", other)),
+        }
+    }
+
+    fn from_redis(err: RedisError) -> Self {
+        if is_connection_error(&err) {
+            Self::Connection(err.to_string())
+        } else {
+            Self::Other(err.into())
+        }
+    }
+}
+
+impl From<RedisOpError> for anyhow::Error {
+    fn from(err: RedisOpError) -> Self {
+        match err {
+            RedisOpError::Connection(reason) => {
+                anyhow::anyhow!("Redis connection error: {}", reason)
+            }
+            RedisOpError::Timeout(kind) => {
+                anyhow::anyhow!("Timed out waiting for a Redis connection ({})", kind)
+            }
+            RedisOpError::Other(e) => e,
+        }
+    }
+}
+
+/// Whether a Redis error indicates a broken connection rather than a bad command
+fn is_connection_error(err: &RedisError) -> bool {
+    err.is_connection_dropped()
+        || err.is_connection_refusal()
+        || err.is_io_error()
+        || err.is_timeout()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_app_state_creation_is_lazy() {
+        let state = AppState::new(ServerConfig::default()).unwrap();
+        assert!(state.redis_available());
+        assert_eq!(state.redis_pool().status().size, 0);
+    }
+
+    #[test]
+    fn test_shutdown_requested_once() {
+        let state = AppState::new(ServerConfig::default()).unwrap();
+        assert!(!state.request_shutdown());
+
+        let mut rx = state.shutdown_signal();
+        assert!(rx.try_recv().is_err());
+        assert!(state.request_shutdown());
+        assert!(rx.try_recv().is_ok());
+        assert!(!state.request_shutdown());
+    }
+
+    #[test]
+    fn test_invalid_redis_url() {
+        let config = ServerConfig {
+            redis_url: "not a url".to_string(),
+            ..Default::default()
+        };
+        assert!(AppState::new(config).is_err());
+    }
+
+    #[test]
+    fn test_is_connection_error() {
+        let io = RedisError::from(std::io::Error::new(
+            std::io::ErrorKind::ConnectionReset,
+            "reset",
+        ));
+        assert!(is_connection_error(&io));
+
+        let cmd = RedisError::from((redis::ErrorKind::TypeError, "wrong type"));
+        assert!(!is_connection_error(&cmd));
+    }
+
+    /// Accept connections and answer every command with `+OK`
+    async fn fake_redis() -> std::net::SocketAddr {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                tokio::spawn(async move {
+                    let mut buf = [0u8; 4096];
+                    while let Ok(n) = socket.read(&mut buf).await {
+                        if n == 0 {
+                            break;
+                        }
+                        let commands = String::from_utf8_lossy(&buf[..n])
+                            .split("\r\n")
+                            .filter(|line| line.starts_with('*'))
+                            .count();
+                        let reply = "+OK\r\n".repeat(commands);
+                        if socket.write_all(reply.as_bytes()).await.is_err() {
+                            break;
+                        }
+                    }
+                });
+            }
+        });
+        addr
+    }
+
+    #[tokio::test]
+    async fn test_circuit_opens_on_connection_failures() {
+        use crate::circuit_breaker::{CircuitState, FAILURE_THRESHOLD};
+
+        // Nothing listens on the port once the listener is dropped
+        let addr = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let state = AppState::new(ServerConfig {
+            redis_url: format!("redis://{}", addr),
+            ..Default::default()
+        })
+        .unwrap();
+        let ping = || {
+            state.with_redis(|mut conn| async move {
+                redis::cmd("PING").query_async::<_, String>(&mut conn).await
+            })
+        };
+
+        for _ in 0..FAILURE_THRESHOLD {
+            let err = ping().await.unwrap_err();
+            assert!(err.downcast_ref::<CircuitOpen>().is_none(), "{:#}", err);
+        }
+        assert_eq!(state.redis_circuit().state(), CircuitState::Open);
+
+        let err = ping().await.unwrap_err();
+        assert!(err.downcast_ref::<CircuitOpen>().is_some(), "{:#}", err);
+    }
+
+    #[tokio::test]
+    async fn test_pool_exhaustion_times_out() {
+        let state = AppState::new(ServerConfig {
+            redis_url: format!("redis://{}", fake_redis().await),
+            redis_pool_size: 2,
+            redis_wait_timeout_ms: 100,
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(state.redis_pool().status().max_size, 2);
+
+        let first = state.redis_conn().await.unwrap();
+        let _second = state.redis_conn().await.unwrap();
+        let Err(err) = state.redis_conn().await else {
+            panic!("pool handed out more connections than its size");
+        };
+        assert!(
+            matches!(err.downcast_ref::<PoolError>(), Some(PoolError::Timeout(_))),
+            "{:#}",
+            err
+        );
+
+        // A waiting checkout gets the connection once it is returned
+        let waiting = tokio::spawn({
+            let state = state.clone();
+            async move { state.redis_conn().await.map(|_| ()) }
+        });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        drop(first);
+        waiting.await.unwrap().unwrap();
+    }
+
+    #[test]
+    fn test_empty_pool_rejected() {
+        let config = ServerConfig {
+            redis_pool_size: 0,
+            ..Default::default()
+        };
+        assert!(AppState::new(config).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_reset_pool_preserves_max_size() {
+        let state = AppState::new(ServerConfig::default()).unwrap();
+        let max_size = state.redis_pool().status().max_size;
+        state.reset_pool();
+        assert_eq!(state.redis_pool().status().max_size, max_size);
+    }
+
+    #[test]
+    fn test_redis_version_from_info() {
+        let info = "# Server\r\nredis_version:7.2.4\r\nredis_mode:standalone\r\n";
+        assert_eq!(
+            RedisVersion::from_info(info),
+            Some(RedisVersion {
+                major: 7,
+                minor: 2,
+                patch: 4
+            })
+        );
+        assert_eq!(RedisVersion::from_info("redis_mode:standalone"), None);
+        assert_eq!(RedisVersion::from_info("redis_version:abc"), None);
+    }
+
+    #[test]
+    fn test_check_redis_version() {
+        let version = |v: &str| RedisVersion::from_info(&format!("redis_version:{}", v)).unwrap();
+
+        assert!(check_redis_version(version("6.2.0")).is_ok());
+        assert!(check_redis_version(version("7.0.15")).is_ok());
+
+        let err = check_redis_version(version("6.0.16")).unwrap_err();
+        assert!(err.to_string().contains("helm upgrade redis bitnami/redis"));
+        assert!(check_redis_version(version("5.0.14")).is_err());
+    }
+}
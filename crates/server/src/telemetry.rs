@@ -0,0 +1,206 @@
+//! Logging and distributed tracing
+//!
+//! [`init_tracing`] installs the log subscriber and, when
+//! [`ServerConfig::otel_endpoint`] is set, an OTLP exporter sending spans to
+//! an OpenTelemetry collector. Requests join the trace of their W3C
+//! `traceparent` header or start a new one (see
+//! [`crate::middleware::trace`]); the trace is available to code handling the
+//! request through [`current_trace`], so it can be passed on to agents with
+//! the queued job.
+
+use anyhow::{Context, Result};
+use opentelemetry::propagation::TextMapPropagator;
+use opentelemetry::KeyValue;
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use opentelemetry_sdk::{runtime, trace, Resource};
+use std::collections::HashMap;
+use std::fmt;
+use std::future::Future;
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::EnvFilter;
+
+use crate::ServerConfig;
+
+/// W3C trace context header
+pub const TRACEPARENT_HEADER: &str = "traceparent";
+
+/// Default [`ServerConfig::service_name`]
+pub const DEFAULT_SERVICE_NAME: &str = "raibid-server";
+
+/// W3C trace context of a request
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceContext {
+    /// 32 lowercase hex digits identifying the trace
+    pub trace_id: String,
+    /// 16 lowercase hex digits identifying the calling span
+    pub parent_id: String,
+    /// Whether the caller records the trace
+    pub sampled: bool,
+}
+
+impl TraceContext {
+    /// Start a new sampled trace
+    pub fn generate() -> Self {
+        let parent_id = uuid::Uuid::new_v4().simple().to_string();
+        Self {
+            trace_id: uuid::Uuid::new_v4().simple().to_string(),
+            parent_id: parent_id[..16].to_string(),
+            sampled: true,
+        }
+    }
+
+    /// Parse a `traceparent` header (`00-<trace-id>-<parent-id>-<flags>`)
+    ///
+    /// Returns `None` for malformed headers and the all-zero IDs the
+    /// specification reserves as invalid.
+    pub fn parse(header: &str) -> Option<Self> {
+        let mut parts = header.trim().split('-');
+        let (version, trace_id, parent_id, flags) =
+            (parts.next()?, parts.next()?, parts.next()?, parts.next()?);
+        // Later versions may append fields; version 00 has exactly four
+        if version == "ff" || !is_hex(version, 2) || (version == "00" && parts.next().is_some()) {
+            return None;
+        }
+        if !is_hex(trace_id, 32) || !is_hex(parent_id, 16) || !is_hex(flags, 2) {
+            return None;
+        }
+        if trace_id.bytes().all(|b| b == b'0') || parent_id.bytes().all(|b| b == b'0') {
+            return None;
+        }
+
+        Some(Self {
+            trace_id: trace_id.to_string(),
+            parent_id: parent_id.to_string(),
+            sampled: u8::from_str_radix(flags, 16).ok()? & 1 == 1,
+        })
+    }
+
+    /// OpenTelemetry context with this trace as remote parent
+    pub fn otel_context(&self) -> opentelemetry::Context {
+        let carrier = HashMap::from([(TRACEPARENT_HEADER.to_string(), self.to_string())]);
+        TraceContextPropagator::new().extract(&carrier)
+    }
+}
+
+impl fmt::Display for TraceContext {
+    /// Format as a `traceparent` header
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "00-{}-{}-{:02x}",
+            self.trace_id,
+            self.parent_id,
+            u8::from(self.sampled)
+        )
+    }
+}
+
+fn is_hex(value: &str, len: usize) -> bool {
+    value.len() == len
+        && value
+            .bytes()
+            .all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b))
+}
+
+tokio::task_local! {
+    static CURRENT_TRACE: TraceContext;
+}
+
+/// Run `future` as part of `trace`
+pub async fn with_trace<F: Future>(trace: TraceContext, future: F) -> F::Output {
+    CURRENT_TRACE.scope(trace, future).await
+}
+
+/// Trace of the request being handled, if any
+///
+/// Background tasks such as the scheduler run outside of a trace.
+pub fn current_trace() -> Option<TraceContext> {
+    CURRENT_TRACE.try_with(TraceContext::clone).ok()
+}
+
+/// Install the log subscriber, exporting spans over OTLP when
+/// `config.otel_endpoint` is set
+///
+/// Log verbosity follows `RUST_LOG`, defaulting to `info`.
+pub fn init_tracing(config: &ServerConfig) -> Result<()> {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+
+    let otel = match config.otel_endpoint {
+        Some(ref endpoint) => {
+            let tracer = opentelemetry_otlp::new_pipeline()
+                .tracing()
+                .with_exporter(
+                    opentelemetry_otlp::new_exporter()
+                        .tonic()
+                        .with_endpoint(endpoint),
+                )
+                .with_trace_config(trace::config().with_resource(Resource::new(vec![
+                    KeyValue::new("service.name", config.service_name.clone()),
+                ])))
+                .install_batch(runtime::Tokio)
+                .with_context(|| format!("Failed to install OTLP exporter for {}", endpoint))?;
+            opentelemetry::global::set_text_map_propagator(TraceContextPropagator::new());
+            Some(tracing_opentelemetry::layer().with_tracer(tracer))
+        }
+        None => None,
+    };
+
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(tracing_subscriber::fmt::layer().with_target(false))
+        .with(otel)
+        .try_init()
+        .context("Failed to install the log subscriber")
+}
+
+/// Export the spans still buffered by the OTLP exporter
+pub fn shutdown_tracing() {
+    opentelemetry::global::shutdown_tracer_provider();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEADER: &str = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+
+    #[test]
+    fn test_parse_traceparent() {
+        let trace = TraceContext::parse(HEADER).unwrap();
+        assert_eq!(trace.trace_id, "4bf92f3577b34da6a3ce929d0e0e4736");
+        assert_eq!(trace.parent_id, "00f067aa0ba902b7");
+        assert!(trace.sampled);
+        assert_eq!(trace.to_string(), HEADER);
+
+        let unsampled = TraceContext::parse(&HEADER.replace("-01", "-00")).unwrap();
+        assert!(!unsampled.sampled);
+
+        for invalid in [
+            "",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7",
+            "ff-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+            "00-00000000000000000000000000000000-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-0000000000000000-01",
+            "00-4BF92F3577B34DA6A3CE929D0E0E4736-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-extra",
+        ] {
+            assert_eq!(TraceContext::parse(invalid), None, "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_generate() {
+        let trace = TraceContext::generate();
+        assert_eq!(TraceContext::parse(&trace.to_string()), Some(trace.clone()));
+        assert_ne!(TraceContext::generate().trace_id, trace.trace_id);
+    }
+
+    #[tokio::test]
+    async fn test_current_trace() {
+        assert_eq!(current_trace(), None);
+        let trace = TraceContext::parse(HEADER).unwrap();
+        let inner = with_trace(trace.clone(), async { current_trace() }).await;
+        assert_eq!(inner, Some(trace));
+    }
+}
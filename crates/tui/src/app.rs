@@ -2,16 +2,42 @@
 //!
 //! This module contains the main application state and event handling logic.
 
-use anyhow::Result;
+use anyhow::{Context, Result};
+use crossterm::event::{MouseButton, MouseEvent, MouseEventKind};
+use ratatui::layout::Rect;
+use raibid_common::agents::AgentEvent;
+use raibid_common::dependencies::OutdatedReport;
+use raibid_common::jobs::{ErrorLogEntry, JobBuilder};
+use raibid_common::test_report::TestReport;
+use raibid_common::AsyncApiClient;
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+use std::sync::Arc;
 use std::time::Duration;
+use tokio::runtime::{Handle, Runtime};
+use tokio::sync::{mpsc, watch, Notify};
 
 use super::events::{is_quit_event, Event, EventHandler};
+use super::layout::PanelWidths;
+use super::live::{
+    agent_events_url, agent_status, poll_api, stream_agent_events, ConnectionStats, LiveUpdate,
+    TuiDataSource,
+};
+use super::logs::{job_logs_url, LogStream, LogsState};
 use super::mock_data::{
-    generate_mock_data, JobStatus, MockAgent, MockDataConfig, MockJob, MockQueueData,
+    generate_mock_data, generate_recent_errors, JobStatus, MockAgent, MockDataConfig, MockJob,
+    MockJobLogs, MockQueueData,
 };
+use super::palette::PaletteState;
 use super::terminal::Terminal;
 use super::ui;
 
+/// Number of entries shown in the recent errors popup
+pub const RECENT_ERRORS_LIMIT: usize = 20;
+
+/// Status options at the top of the filter menu, in menu order
+pub const STATUS_FILTER_OPTIONS: [&str; 5] = ["All", "Running", "Success", "Failed", "Pending"];
+
 /// Available tabs in the TUI
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Tab {
@@ -58,21 +84,45 @@ impl Tab {
     }
 }
 
+/// Panels of the Jobs tab
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Panel {
+    #[default]
+    Jobs,
+    Agents,
+    Queue,
+}
+
+impl Panel {
+    /// All panels, left to right
+    pub fn all() -> Vec<Panel> {
+        vec![Panel::Jobs, Panel::Agents, Panel::Queue]
+    }
+}
+
 /// Application configuration
 #[derive(Debug, Clone)]
 pub struct AppConfig {
     /// Refresh interval for updating data
     pub refresh_interval: Duration,
-    /// Panel proportions (jobs, agents, queue) - percentages that sum to 100
-    #[allow(dead_code)]
-    pub panel_proportions: (u16, u16, u16),
+    /// Initial widths of the Jobs tab panels
+    pub panel_widths: PanelWidths,
+    /// File the panel widths are saved to after resizing (None = not saved)
+    pub layout_file: Option<PathBuf>,
+    /// API server URL to poll for live data (None = use mock data)
+    pub api_url: Option<String>,
+    /// API key sent to the server
+    pub api_key: Option<String>,
 }
 
 impl Default for AppConfig {
     fn default() -> Self {
         Self {
             refresh_interval: Duration::from_secs(1),
-            panel_proportions: (60, 20, 20),
+            panel_widths: PanelWidths::default(),
+            layout_file: None,
+            api_url: None,
+            api_key: None,
         }
     }
 }
@@ -86,12 +136,26 @@ pub enum InputMode {
     Search,
     /// Filter selection mode
     Filter,
+    /// Command palette, opened with `:`
+    CommandPalette,
 }
 
 /// Main application state
 pub struct App {
     /// Application configuration
     config: AppConfig,
+    /// Where jobs and agents come from
+    data_source: TuiDataSource,
+    /// Error from the last live data poll, shown in the header
+    connection_error: Option<String>,
+    /// Outcomes of the polls of a live source, shown in the header
+    connection: Option<ConnectionStats>,
+    /// Connection stats published by the live data poller
+    connection_updates: Option<watch::Receiver<ConnectionStats>>,
+    /// Show connection stats popup
+    show_connection_stats: bool,
+    /// Wakes the live data poller for a manual refresh
+    refresh_requested: Arc<Notify>,
     /// Mock data configuration
     mock_config: MockDataConfig,
     /// Current job list
@@ -104,12 +168,26 @@ pub struct App {
     should_quit: bool,
     /// Current active tab
     current_tab: Tab,
+    /// Panel of the Jobs tab receiving scrolling
+    focused_panel: Panel,
+    /// Size of the terminal at the last draw, for mouse hit testing
+    screen: Rect,
+    /// Widths of the Jobs tab panels
+    panel_widths: PanelWidths,
+    /// Border between Jobs tab panels being dragged
+    dragged_border: Option<usize>,
     /// Selected job index (for scrolling/selection)
     selected_job: usize,
     /// Selected agent index (for scrolling/selection)
     selected_agent: usize,
     /// Show job detail popup
     show_detail_popup: bool,
+    /// Test results of the job in the detail popup: `None` if it has none,
+    /// or the error of a failed fetch
+    test_report: Option<Result<TestReport, String>>,
+    /// Outdated dependencies of the job in the detail popup: `None` if it
+    /// has no report, or the error of a failed fetch
+    dependencies: Option<Result<OutdatedReport, String>>,
     /// Show help screen
     show_help: bool,
     /// Show filter menu
@@ -124,10 +202,26 @@ pub struct App {
     search_query: String,
     /// Filter by job status (None = show all)
     filter_status: Option<JobStatus>,
+    /// Filter by a `(key, value)` job label (None = show all)
+    filter_label: Option<(String, String)>,
     /// Selected filter option index
     selected_filter_option: usize,
-    /// Log scroll offset
-    log_scroll_offset: usize,
+    /// Query and selection of the command palette
+    palette: PaletteState,
+    /// Logs of the selected job shown in the Logs tab
+    logs: LogsState,
+    /// Log stream of a live source feeding `logs`
+    log_stream: Option<LogStream>,
+    /// Agent status changes streamed from a live source between polls
+    agent_events: Option<mpsc::UnboundedReceiver<AgentEvent>>,
+    /// Show recent errors popup
+    show_recent_errors: bool,
+    /// Recent error log entries across all jobs
+    recent_errors: Vec<ErrorLogEntry>,
+    /// Error from the last recent errors fetch
+    recent_errors_error: Option<String>,
+    /// Selected recent error index
+    selected_error: usize,
 }
 
 impl App {
@@ -137,21 +231,46 @@ impl App {
     }
 
     /// Create a new application with custom configuration
+    ///
+    /// With an `api_url` the app shows live data from the server; until the
+    /// first poll completes the lists are empty.
     pub fn with_config(config: AppConfig) -> Self {
+        let (data_source, connection_error) = match TuiDataSource::from_config(&config) {
+            Ok(source) => (source, None),
+            Err(e) => (TuiDataSource::Mock, Some(format!("{:#}", e))),
+        };
+
         let mock_config = MockDataConfig::default();
-        let (jobs, agents, queue_data) = generate_mock_data(&mock_config);
+        let (jobs, agents, queue_data) = match data_source {
+            TuiDataSource::Mock => generate_mock_data(&mock_config),
+            TuiDataSource::Live(_) => (Vec::new(), Vec::new(), MockQueueData::default()),
+        };
+        let connection = data_source.client().map(|_| ConnectionStats::default());
+        let panel_widths = config.panel_widths;
 
         Self {
             config,
+            data_source,
+            connection_error,
+            connection,
+            connection_updates: None,
+            show_connection_stats: false,
+            refresh_requested: Arc::new(Notify::new()),
             mock_config,
             jobs,
             agents,
             queue_data,
             should_quit: false,
             current_tab: Tab::Jobs,
+            focused_panel: Panel::Jobs,
+            screen: Rect::default(),
+            panel_widths,
+            dragged_border: None,
             selected_job: 0,
             selected_agent: 0,
             show_detail_popup: false,
+            test_report: None,
+            dependencies: None,
             show_help: false,
             show_filter_menu: false,
             show_confirmation: false,
@@ -159,8 +278,16 @@ impl App {
             input_mode: InputMode::Normal,
             search_query: String::new(),
             filter_status: None,
+            filter_label: None,
             selected_filter_option: 0,
-            log_scroll_offset: 0,
+            palette: PaletteState::default(),
+            logs: LogsState::default(),
+            log_stream: None,
+            agent_events: None,
+            show_recent_errors: false,
+            recent_errors: Vec::new(),
+            recent_errors_error: None,
+            selected_error: 0,
         }
     }
 
@@ -180,6 +307,23 @@ impl App {
         self.current_tab = self.current_tab.previous();
     }
 
+    /// Switch to a tab
+    pub fn select_tab(&mut self, tab: Tab) {
+        self.current_tab = tab;
+    }
+
+    /// Get the focused panel of the Jobs tab
+    #[allow(dead_code)]
+    pub fn focused_panel(&self) -> Panel {
+        self.focused_panel
+    }
+
+    /// Get the widths of the Jobs tab panels
+    #[allow(dead_code)]
+    pub fn panel_widths(&self) -> PanelWidths {
+        self.panel_widths
+    }
+
     /// Get selected job index
     #[allow(dead_code)]
     pub fn selected_job(&self) -> usize {
@@ -199,22 +343,18 @@ impl App {
             if self.selected_filter_option > 0 {
                 self.selected_filter_option -= 1;
             }
+        } else if self.show_recent_errors {
+            self.selected_error = self.selected_error.saturating_sub(1);
         } else {
             match self.current_tab {
-                Tab::Jobs => {
-                    if self.selected_job > 0 {
-                        self.selected_job -= 1;
-                    }
-                }
-                Tab::Agents => {
-                    if self.selected_agent > 0 {
-                        self.selected_agent -= 1;
-                    }
+                Tab::Jobs if self.focused_panel == Panel::Jobs => {
+                    self.selected_job = self.selected_job.saturating_sub(1);
                 }
-                Tab::Logs => {
-                    // Scroll logs up
-                    self.log_scroll_offset = self.log_scroll_offset.saturating_sub(1);
+                Tab::Jobs if self.focused_panel == Panel::Queue => {}
+                Tab::Jobs | Tab::Agents => {
+                    self.selected_agent = self.selected_agent.saturating_sub(1);
                 }
+                Tab::Logs => self.logs.scroll_up(),
                 _ => {}
             }
         }
@@ -224,25 +364,26 @@ impl App {
     pub fn select_next(&mut self) {
         if self.show_filter_menu {
             // Navigate filter options
-            if self.selected_filter_option < 4 {
+            let last_option = STATUS_FILTER_OPTIONS.len() + self.label_filter_options().len() - 1;
+            if self.selected_filter_option < last_option {
                 self.selected_filter_option += 1;
             }
+        } else if self.show_recent_errors {
+            if self.selected_error < self.recent_errors.len().saturating_sub(1) {
+                self.selected_error += 1;
+            }
         } else {
             match self.current_tab {
-                Tab::Jobs => {
-                    if self.selected_job < self.filtered_jobs().len().saturating_sub(1) {
-                        self.selected_job += 1;
-                    }
-                }
-                Tab::Agents => {
-                    if self.selected_agent < self.agents.len().saturating_sub(1) {
-                        self.selected_agent += 1;
-                    }
+                Tab::Jobs if self.focused_panel == Panel::Jobs => {
+                    let last = self.filtered_jobs().len().saturating_sub(1);
+                    self.selected_job = (self.selected_job + 1).min(last);
                 }
-                Tab::Logs => {
-                    // Scroll logs down
-                    self.log_scroll_offset = self.log_scroll_offset.saturating_add(1).min(100);
+                Tab::Jobs if self.focused_panel == Panel::Queue => {}
+                Tab::Jobs | Tab::Agents => {
+                    let last = self.agents.len().saturating_sub(1);
+                    self.selected_agent = (self.selected_agent + 1).min(last);
                 }
+                Tab::Logs => self.logs.scroll_down(),
                 _ => {}
             }
         }
@@ -259,7 +400,13 @@ impl App {
     }
 
     /// Update application state (refresh mock data)
+    ///
+    /// Live data arrives from the background poller instead.
     pub fn update(&mut self) {
+        if let TuiDataSource::Live(_) = self.data_source {
+            return;
+        }
+
         // Regenerate mock data to simulate changes
         let (jobs, agents, _) = generate_mock_data(&self.mock_config);
         self.jobs = jobs;
@@ -274,7 +421,7 @@ impl App {
     pub fn handle_event(&mut self, event: Event) {
         match event {
             Event::Key(key) => {
-                use crossterm::event::KeyCode;
+                use crossterm::event::{KeyCode, KeyModifiers};
 
                 // Handle input mode-specific keys
                 if self.input_mode == InputMode::Search {
@@ -293,6 +440,16 @@ impl App {
                         KeyCode::Down => self.select_next(),
                         _ => {}
                     }
+                } else if self.input_mode == InputMode::CommandPalette {
+                    match key.code {
+                        KeyCode::Esc => self.close_command_palette(),
+                        KeyCode::Enter => self.run_palette_action(),
+                        KeyCode::Up => self.palette.select_previous(),
+                        KeyCode::Down => self.palette.select_next(),
+                        KeyCode::Char(c) => self.palette.push(c),
+                        KeyCode::Backspace => self.palette.backspace(),
+                        _ => {}
+                    }
                 } else {
                     // InputMode::Normal
                     {
@@ -321,9 +478,26 @@ impl App {
                                 KeyCode::Char('r') => self.refresh(),
                                 _ => {}
                             }
+                        } else if self.show_connection_stats {
+                            // Any key closes the connection stats popup
+                            self.toggle_connection_stats();
+                        } else if self.show_recent_errors {
+                            // Handle recent errors popup
+                            match key.code {
+                                KeyCode::Esc | KeyCode::Char('e') => self.toggle_recent_errors(),
+                                KeyCode::Up => self.select_previous(),
+                                KeyCode::Down => self.select_next(),
+                                KeyCode::Enter => self.jump_to_error_job(),
+                                KeyCode::Char('r') => self.fetch_recent_errors(),
+                                _ => {}
+                            }
                         } else if is_quit_event(&key) {
                             // Normal mode key handling
                             self.quit();
+                        } else if key.code == KeyCode::Char('i')
+                            && key.modifiers.contains(KeyModifiers::CONTROL)
+                        {
+                            self.toggle_connection_stats();
                         } else {
                             match key.code {
                                 // Navigation
@@ -338,22 +512,38 @@ impl App {
                                 KeyCode::Char('2') => self.current_tab = Tab::Agents,
                                 KeyCode::Char('3') => self.current_tab = Tab::Config,
                                 KeyCode::Char('4') => self.current_tab = Tab::Logs,
+                                // Log scrolling
+                                KeyCode::Char('k') if self.current_tab == Tab::Logs => {
+                                    self.logs.scroll_up()
+                                }
+                                KeyCode::Char('j') if self.current_tab == Tab::Logs => {
+                                    self.logs.scroll_down()
+                                }
+                                KeyCode::Char('G') if self.current_tab == Tab::Logs => {
+                                    self.logs.jump_to_end()
+                                }
+                                KeyCode::Char('F') if self.current_tab == Tab::Logs => {
+                                    self.logs.toggle_follow()
+                                }
                                 // Actions
                                 KeyCode::Enter => self.toggle_detail_popup(),
                                 KeyCode::Char('?') => self.toggle_help(),
                                 KeyCode::Char('f') => self.toggle_filter_menu(),
                                 KeyCode::Char('/') => self.enter_search_mode(),
+                                KeyCode::Char(':') => self.open_command_palette(),
                                 KeyCode::Char('c') => self.show_cancel_confirmation(),
+                                KeyCode::Char('e') => self.toggle_recent_errors(),
                                 KeyCode::Char('r') => self.refresh(),
-                                KeyCode::Esc => {
-                                    // Clear filters and search
+                                // Clear filters and search
+                                KeyCode::Esc
                                     if self.filter_status.is_some()
-                                        || !self.search_query.is_empty()
-                                    {
-                                        self.filter_status = None;
-                                        self.search_query.clear();
-                                        self.selected_job = 0;
-                                    }
+                                        || self.filter_label.is_some()
+                                        || !self.search_query.is_empty() =>
+                                {
+                                    self.filter_status = None;
+                                    self.filter_label = None;
+                                    self.search_query.clear();
+                                    self.selected_job = 0;
                                 }
                                 _ => {}
                             }
@@ -361,8 +551,10 @@ impl App {
                     }
                 }
             }
-            Event::Resize(_, _) => {
+            Event::Mouse(mouse) => self.handle_mouse(mouse),
+            Event::Resize(width, height) => {
                 // Terminal resize is handled automatically by ratatui
+                self.screen = Rect::new(0, 0, width, height);
             }
             Event::Tick => {
                 // Auto-refresh on tick
@@ -371,15 +563,244 @@ impl App {
         }
     }
 
+    /// Handle a mouse event
+    ///
+    /// The wheel scrolls like the arrow keys: the focused panel on the Jobs
+    /// tab, the logs on the Logs tab, or the open list popup. Clicking a
+    /// panel of the Jobs tab focuses it.
+    fn handle_mouse(&mut self, mouse: MouseEvent) {
+        // Dialogs without a list take no mouse input
+        if self.input_mode == InputMode::Search
+            || self.input_mode == InputMode::CommandPalette
+            || self.show_help
+            || self.show_connection_stats
+            || self.show_detail_popup
+            || self.show_confirmation
+        {
+            return;
+        }
+
+        match mouse.kind {
+            MouseEventKind::ScrollUp => self.select_previous(),
+            MouseEventKind::ScrollDown => self.select_next(),
+            MouseEventKind::Down(MouseButton::Left)
+                if self.current_tab == Tab::Jobs
+                    && !self.show_filter_menu
+                    && !self.show_recent_errors =>
+            {
+                let (screen, widths) = (self.screen, self.panel_widths);
+                if let Some(border) = ui::border_at(screen, widths, mouse.column, mouse.row) {
+                    self.dragged_border = Some(border);
+                } else if let Some(panel) = ui::panel_at(screen, widths, mouse.column, mouse.row)
+                {
+                    self.focused_panel = panel;
+                }
+            }
+            MouseEventKind::Drag(MouseButton::Left) => {
+                if let Some(border) = self.dragged_border {
+                    let pct = ui::content_percent(self.screen, mouse.column);
+                    self.panel_widths.move_border(border, pct);
+                }
+            }
+            MouseEventKind::Up(MouseButton::Left) if self.dragged_border.is_some() => {
+                self.dragged_border = None;
+                self.save_panel_widths();
+            }
+            _ => {}
+        }
+    }
+
+    /// Save the panel widths to the layout file, if one is configured
+    fn save_panel_widths(&self) {
+        if let Some(ref path) = self.config.layout_file {
+            if let Err(e) = self.panel_widths.save(path) {
+                tracing::warn!("Failed to save panel layout: {:#}", e);
+            }
+        }
+    }
+
+    /// Apply the result of a live data poll
+    ///
+    /// A failed poll keeps the last data on screen and shows the error in the
+    /// header until a poll succeeds again.
+    pub fn apply_live_update(&mut self, update: LiveUpdate) {
+        match update {
+            None => {}
+            Some(Ok(data)) => {
+                self.jobs = data.jobs;
+                self.agents = data.agents;
+                self.queue_data.record_stats(&data.queue);
+                self.connection_error = None;
+
+                // Keep selections in range as the lists change
+                let last_job = self.filtered_jobs().len().saturating_sub(1);
+                self.selected_job = self.selected_job.min(last_job);
+                self.selected_agent = self.selected_agent.min(self.agents.len().saturating_sub(1));
+            }
+            Some(Err(e)) => self.connection_error = Some(e),
+        }
+    }
+
+    /// Apply an agent status change received between polls
+    ///
+    /// Agents the last poll didn't report are left for the next one.
+    pub fn apply_agent_event(&mut self, event: &AgentEvent) {
+        if let Some(agent) = self.agents.iter_mut().find(|a| a.id == event.agent_id) {
+            agent.status = agent_status(event.status);
+        }
+    }
+
+    /// Apply agent status changes streamed since the last render
+    pub fn receive_agent_events(&mut self) {
+        let Some(mut events) = self.agent_events.take() else {
+            return;
+        };
+        while let Ok(event) = events.try_recv() {
+            self.apply_agent_event(&event);
+        }
+        self.agent_events = Some(events);
+    }
+
+    /// Error from the last live data poll
+    #[allow(dead_code)]
+    pub fn connection_error(&self) -> Option<&str> {
+        self.connection_error.as_deref()
+    }
+
+    /// Apply the connection stats published since the last render
+    pub fn receive_connection_stats(&mut self) {
+        let Some(ref mut updates) = self.connection_updates else {
+            return;
+        };
+        if updates.has_changed().unwrap_or(false) {
+            self.connection = Some(updates.borrow_and_update().clone());
+        }
+    }
+
+    /// Outcomes of the polls of a live source; `None` for mock data
+    #[allow(dead_code)]
+    pub fn connection(&self) -> Option<&ConnectionStats> {
+        self.connection.as_ref()
+    }
+
+    /// Toggle connection stats popup
+    ///
+    /// Mock data has no connection, so the popup only opens for a live
+    /// source.
+    pub fn toggle_connection_stats(&mut self) {
+        self.show_connection_stats = !self.show_connection_stats && self.connection.is_some();
+    }
+
+    /// Show the logs of the selected job while the Logs tab is active
+    ///
+    /// A live source streams them from the server on `runtime`, restarting
+    /// the stream whenever the selected job changes; mock jobs get generated
+    /// logs.
+    pub fn sync_log_stream(&mut self, runtime: Option<&Handle>) {
+        if self.current_tab != Tab::Logs {
+            return;
+        }
+        let Some(job) = self.get_selected_job().cloned() else {
+            return;
+        };
+        // Mock jobs are regenerated on every tick, so keep the first one's logs
+        let mock = self.data_source.client().is_none();
+        if self.logs.job_id.as_deref() == Some(job.id.as_str())
+            || (mock && self.logs.job_id.is_some())
+        {
+            return;
+        }
+
+        self.logs = LogsState::for_job(job.id.clone());
+        self.log_stream = match (self.data_source.client(), runtime) {
+            (Some(client), Some(runtime)) => Some(LogStream::spawn(
+                runtime,
+                job_logs_url(client.base_url(), &job.id),
+                client.api_key().map(String::from),
+            )),
+            _ => {
+                for entry in MockJobLogs::for_job(&job).entries {
+                    self.logs.push(entry);
+                }
+                None
+            }
+        };
+    }
+
+    /// Apply log lines streamed since the last render
+    pub fn receive_logs(&mut self) {
+        if let Some(ref mut stream) = self.log_stream {
+            self.logs.receive(&mut stream.events);
+        }
+    }
+
+    /// Logs shown in the Logs tab
+    #[allow(dead_code)]
+    pub fn logs(&self) -> &LogsState {
+        &self.logs
+    }
+
+    /// Start polling the API server in the background for a live source,
+    /// and following its agent status changes
+    ///
+    /// Returns the runtime running the poller, which must outlive the
+    /// receiver, or `None` for mock data.
+    fn start_polling(&mut self) -> Result<Option<(Runtime, watch::Receiver<LiveUpdate>)>> {
+        let Some(client) = self.data_source.client() else {
+            return Ok(None);
+        };
+
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .enable_all()
+            .build()
+            .context("Failed to start API polling runtime")?;
+
+        let (updates, receiver) = watch::channel(None);
+        let (connection, connection_updates) = watch::channel(ConnectionStats::default());
+        runtime.spawn(poll_api(
+            AsyncApiClient::try_from(client)?,
+            self.config.refresh_interval,
+            updates,
+            connection,
+            self.refresh_requested.clone(),
+        ));
+        self.connection_updates = Some(connection_updates);
+
+        let (events, agent_events) = mpsc::unbounded_channel();
+        runtime.spawn(stream_agent_events(
+            agent_events_url(client.base_url()),
+            client.api_key().map(String::from),
+            events,
+        ));
+        self.agent_events = Some(agent_events);
+
+        Ok(Some((runtime, receiver)))
+    }
+
     /// Run the main event loop
     pub fn run(&mut self, terminal: &mut Terminal) -> Result<()> {
         let event_handler = EventHandler::new(self.config.refresh_interval);
+        let mut poller = self.start_polling()?;
 
         while !self.should_quit() {
+            // Pick up the latest live data, if any arrived
+            if let Some((_, ref mut updates)) = poller {
+                if updates.has_changed().unwrap_or(false) {
+                    let update = updates.borrow_and_update().clone();
+                    self.apply_live_update(update);
+                }
+            }
+            self.receive_connection_stats();
+            self.receive_agent_events();
+            self.sync_log_stream(poller.as_ref().map(|(runtime, _)| runtime.handle()));
+            self.receive_logs();
+
             // Render the UI
             let filtered_jobs: Vec<MockJob> =
                 self.filtered_jobs().iter().map(|&j| j.clone()).collect();
             let ui_state = self.ui_state();
+            let screen = terminal.size()?;
 
             terminal.draw(|frame| {
                 ui::render(
@@ -393,12 +814,21 @@ impl App {
                     &ui_state,
                 );
             })?;
+            self.screen = screen;
 
             // Handle events
             let event = event_handler.next()?;
             self.handle_event(event);
         }
 
+        // Don't wait for a poll still in flight
+        self.log_stream = None;
+        self.agent_events = None;
+        self.connection_updates = None;
+        if let Some((runtime, _)) = poller {
+            runtime.shutdown_background();
+        }
+
         Ok(())
     }
 
@@ -420,11 +850,107 @@ impl App {
         &self.queue_data
     }
 
-    /// Toggle job detail popup
+    /// Toggle job detail popup, fetching the job's test results and
+    /// outdated dependencies when opening
     pub fn toggle_detail_popup(&mut self) {
         if self.current_tab == Tab::Jobs && !self.filtered_jobs().is_empty() {
             self.show_detail_popup = !self.show_detail_popup;
+            if self.show_detail_popup {
+                self.fetch_test_report();
+                self.fetch_dependencies();
+            }
+        }
+    }
+
+    /// Fetch the test results of the selected job
+    ///
+    /// Mock jobs have no test results.
+    pub fn fetch_test_report(&mut self) {
+        let job_id = self.get_selected_job().map(|job| job.id.clone());
+        self.test_report = match (self.data_source.client(), job_id) {
+            (Some(client), Some(job_id)) => client
+                .test_results(&job_id)
+                .map_err(|e| format!("{:#}", e))
+                .transpose(),
+            _ => None,
+        };
+    }
+
+    /// Test results of the job in the detail popup
+    #[allow(dead_code)]
+    pub fn test_report(&self) -> Option<&Result<TestReport, String>> {
+        self.test_report.as_ref()
+    }
+
+    /// Fetch the outdated dependencies of the selected job
+    ///
+    /// Mock jobs have no dependency report.
+    pub fn fetch_dependencies(&mut self) {
+        let job_id = self.get_selected_job().map(|job| job.id.clone());
+        self.dependencies = match (self.data_source.client(), job_id) {
+            (Some(client), Some(job_id)) => client
+                .dependencies(&job_id)
+                .map_err(|e| format!("{:#}", e))
+                .transpose(),
+            _ => None,
+        };
+    }
+
+    /// Outdated dependencies of the job in the detail popup
+    #[allow(dead_code)]
+    pub fn dependencies(&self) -> Option<&Result<OutdatedReport, String>> {
+        self.dependencies.as_ref()
+    }
+
+    /// Toggle recent errors popup, fetching fresh entries when opening
+    pub fn toggle_recent_errors(&mut self) {
+        self.show_recent_errors = !self.show_recent_errors;
+        if self.show_recent_errors {
+            self.fetch_recent_errors();
+        }
+    }
+
+    /// Fetch the most recent error log entries across all jobs
+    pub fn fetch_recent_errors(&mut self) {
+        let result = match self.data_source.client() {
+            Some(client) => client.recent_errors(RECENT_ERRORS_LIMIT),
+            None => Ok(generate_recent_errors(&self.jobs, RECENT_ERRORS_LIMIT)),
+        };
+
+        match result {
+            Ok(errors) => {
+                self.recent_errors = errors;
+                self.recent_errors_error = None;
+            }
+            Err(e) => {
+                self.recent_errors.clear();
+                self.recent_errors_error = Some(format!("{:#}", e));
+            }
         }
+        self.selected_error = 0;
+    }
+
+    /// Recent error log entries
+    #[allow(dead_code)]
+    pub fn recent_errors(&self) -> &[ErrorLogEntry] {
+        &self.recent_errors
+    }
+
+    /// Close the recent errors popup and show the selected entry's job
+    ///
+    /// The job list is searched by job ID so the job is selected even if it
+    /// was hidden by the current filter.
+    pub fn jump_to_error_job(&mut self) {
+        let Some(entry) = self.recent_errors.get(self.selected_error) else {
+            return;
+        };
+
+        self.search_query = entry.job_id.clone();
+        self.filter_status = None;
+        self.filter_label = None;
+        self.selected_job = 0;
+        self.current_tab = Tab::Jobs;
+        self.show_recent_errors = false;
     }
 
     /// Toggle help screen
@@ -444,16 +970,38 @@ impl App {
         }
     }
 
+    /// Labels of the current jobs offered in the filter menu after the
+    /// status options, sorted
+    pub fn label_filter_options(&self) -> Vec<(String, String)> {
+        self.jobs
+            .iter()
+            .flat_map(|job| job.labels.iter())
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect()
+    }
+
     /// Apply selected filter
+    ///
+    /// Status and label filters combine; "All" clears both.
     pub fn apply_filter(&mut self) {
-        self.filter_status = match self.selected_filter_option {
-            0 => None, // All
-            1 => Some(JobStatus::Running),
-            2 => Some(JobStatus::Success),
-            3 => Some(JobStatus::Failed),
-            4 => Some(JobStatus::Pending),
-            _ => None,
-        };
+        match self.selected_filter_option {
+            0 => {
+                self.filter_status = None;
+                self.filter_label = None;
+            }
+            1 => self.filter_status = Some(JobStatus::Running),
+            2 => self.filter_status = Some(JobStatus::Success),
+            3 => self.filter_status = Some(JobStatus::Failed),
+            4 => self.filter_status = Some(JobStatus::Pending),
+            option => {
+                self.filter_label = self
+                    .label_filter_options()
+                    .into_iter()
+                    .nth(option - STATUS_FILTER_OPTIONS.len());
+            }
+        }
         self.show_filter_menu = false;
         self.input_mode = InputMode::Normal;
         self.selected_job = 0; // Reset selection
@@ -487,6 +1035,33 @@ impl App {
         }
     }
 
+    /// Open the command palette with an empty query
+    pub fn open_command_palette(&mut self) {
+        self.input_mode = InputMode::CommandPalette;
+        self.palette = PaletteState::default();
+    }
+
+    /// Close the command palette without running an action
+    pub fn close_command_palette(&mut self) {
+        self.input_mode = InputMode::Normal;
+        self.palette = PaletteState::default();
+    }
+
+    /// Close the command palette and run the selected action
+    pub fn run_palette_action(&mut self) {
+        let action = self.palette.selected_action();
+        self.close_command_palette();
+        if let Some(action) = action {
+            (action.action_fn)(self);
+        }
+    }
+
+    /// Query and selection of the command palette
+    #[allow(dead_code)]
+    pub fn palette(&self) -> &PaletteState {
+        &self.palette
+    }
+
     /// Get filtered jobs based on status filter and search query
     pub fn filtered_jobs(&self) -> Vec<&MockJob> {
         self.jobs
@@ -499,6 +1074,13 @@ impl App {
                     }
                 }
 
+                // Apply label filter
+                if let Some((ref key, ref value)) = self.filter_label {
+                    if job.labels.get(key) != Some(value) {
+                        return false;
+                    }
+                }
+
                 // Apply search query
                 if !self.search_query.is_empty() {
                     let query = self.search_query.to_lowercase();
@@ -545,15 +1127,59 @@ impl App {
         }
     }
 
+    /// Run the selected job again under a new ID
+    ///
+    /// Mock jobs can't be retried.
+    pub fn retry_selected_job(&mut self) {
+        let result = match (self.data_source.client(), self.get_selected_job()) {
+            (Some(client), Some(job)) => client.retry_job(&job.id),
+            _ => return,
+        };
+
+        match result {
+            Ok(job) => {
+                tracing::info!("Retrying job as {}", job.id);
+                self.refresh();
+            }
+            Err(e) => tracing::warn!("Failed to retry job: {:#}", e),
+        }
+    }
+
+    /// Queue a new build of the selected job's repository and branch
+    ///
+    /// Mock jobs can't be built.
+    pub fn trigger_build(&mut self) {
+        let result = match (self.data_source.client(), self.get_selected_job()) {
+            (Some(client), Some(job)) => JobBuilder::new(&job.repo)
+                .branch(&job.branch)
+                .build()
+                .and_then(|trigger| client.trigger_job(&trigger)),
+            _ => return,
+        };
+
+        match result {
+            Ok(job) => {
+                tracing::info!("Triggered build {}", job.id);
+                self.refresh();
+            }
+            Err(e) => tracing::warn!("Failed to trigger build: {:#}", e),
+        }
+    }
+
     /// Refresh data manually
     pub fn refresh(&mut self) {
-        self.update();
+        match self.data_source {
+            TuiDataSource::Mock => self.update(),
+            TuiDataSource::Live(_) => self.refresh_requested.notify_one(),
+        }
     }
 
     /// Get UI state for rendering
     pub fn ui_state(&self) -> UiState<'_> {
         UiState {
             show_detail_popup: self.show_detail_popup,
+            test_report: self.test_report.as_ref(),
+            dependencies: self.dependencies.as_ref(),
             show_help: self.show_help,
             show_filter_menu: self.show_filter_menu,
             show_confirmation: self.show_confirmation,
@@ -561,8 +1187,23 @@ impl App {
             input_mode: self.input_mode,
             search_query: &self.search_query,
             filter_status: self.filter_status,
+            label_filter_options: self
+                .label_filter_options()
+                .into_iter()
+                .map(|(key, value)| format!("{}={}", key, value))
+                .collect(),
             selected_filter_option: self.selected_filter_option,
-            log_scroll_offset: self.log_scroll_offset,
+            palette: &self.palette,
+            logs: &self.logs,
+            show_recent_errors: self.show_recent_errors,
+            recent_errors: &self.recent_errors,
+            recent_errors_error: self.recent_errors_error.as_deref(),
+            selected_error: self.selected_error,
+            connection_error: self.connection_error.as_deref(),
+            connection: self.connection.as_ref(),
+            show_connection_stats: self.show_connection_stats,
+            focused_panel: self.focused_panel,
+            panel_widths: self.panel_widths,
         }
     }
 }
@@ -570,6 +1211,10 @@ impl App {
 /// UI state for rendering (to avoid passing too many parameters)
 pub struct UiState<'a> {
     pub show_detail_popup: bool,
+    /// Test results of the job in the detail popup
+    pub test_report: Option<&'a Result<TestReport, String>>,
+    /// Outdated dependencies of the job in the detail popup
+    pub dependencies: Option<&'a Result<OutdatedReport, String>>,
     pub show_help: bool,
     pub show_filter_menu: bool,
     pub show_confirmation: bool,
@@ -578,9 +1223,26 @@ pub struct UiState<'a> {
     pub search_query: &'a str,
     #[allow(dead_code)]
     pub filter_status: Option<JobStatus>,
+    /// Job labels offered in the filter menu, as `key=value`
+    pub label_filter_options: Vec<String>,
     pub selected_filter_option: usize,
-    #[allow(dead_code)]
-    pub log_scroll_offset: usize,
+    /// Query and selection of the command palette
+    pub palette: &'a PaletteState,
+    /// Logs of the selected job
+    pub logs: &'a LogsState,
+    pub show_recent_errors: bool,
+    pub recent_errors: &'a [ErrorLogEntry],
+    pub recent_errors_error: Option<&'a str>,
+    pub selected_error: usize,
+    /// Error from the last live data poll
+    pub connection_error: Option<&'a str>,
+    /// Outcomes of the polls of a live source
+    pub connection: Option<&'a ConnectionStats>,
+    pub show_connection_stats: bool,
+    /// Panel of the Jobs tab with a highlighted border
+    pub focused_panel: Panel,
+    /// Widths of the Jobs tab panels
+    pub panel_widths: PanelWidths,
 }
 
 impl Default for App {
@@ -628,19 +1290,23 @@ mod tests {
     fn test_app_config_default() {
         let config = AppConfig::default();
         assert_eq!(config.refresh_interval, Duration::from_secs(1));
-        assert_eq!(config.panel_proportions, (60, 20, 20));
+        assert_eq!(config.panel_widths, PanelWidths::default());
+        assert_eq!(config.layout_file, None);
     }
 
     #[test]
     fn test_app_with_custom_config() {
         let config = AppConfig {
             refresh_interval: Duration::from_millis(500),
-            panel_proportions: (70, 15, 15),
+            panel_widths: PanelWidths::new(70, 15, 15).unwrap(),
+            layout_file: None,
+            api_url: None,
+            api_key: None,
         };
 
         let app = App::with_config(config.clone());
         assert_eq!(app.config.refresh_interval, config.refresh_interval);
-        assert_eq!(app.config.panel_proportions, config.panel_proportions);
+        assert_eq!(app.panel_widths(), config.panel_widths);
     }
 
     #[test]
@@ -665,4 +1331,400 @@ mod tests {
         // Should maintain queue history length
         assert_eq!(app.queue_data().history.len(), initial_queue_len);
     }
+
+    #[test]
+    fn test_label_filter() {
+        let mut app = App::new();
+        let (key, value) = app
+            .label_filter_options()
+            .into_iter()
+            .next()
+            .expect("mock jobs have labels");
+
+        // Label options follow the status options
+        app.toggle_filter_menu();
+        for _ in 0..STATUS_FILTER_OPTIONS.len() {
+            app.select_next();
+        }
+        app.apply_filter();
+
+        let filtered = app.filtered_jobs();
+        assert!(!filtered.is_empty());
+        assert!(filtered
+            .iter()
+            .all(|job| job.labels.get(&key) == Some(&value)));
+
+        // "All" clears the label filter
+        app.toggle_filter_menu();
+        for _ in 0..STATUS_FILTER_OPTIONS.len() {
+            app.select_previous();
+        }
+        app.apply_filter();
+        assert_eq!(app.filtered_jobs().len(), app.jobs().len());
+    }
+
+    #[test]
+    fn test_recent_errors_popup() {
+        use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+        let mut app = App::new();
+        app.handle_event(Event::Key(KeyEvent::new(
+            KeyCode::Char('e'),
+            KeyModifiers::NONE,
+        )));
+        assert!(app.ui_state().show_recent_errors);
+        assert_eq!(app.recent_errors().len(), RECENT_ERRORS_LIMIT);
+
+        app.handle_event(Event::Key(KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE)));
+        assert!(!app.ui_state().show_recent_errors);
+    }
+
+    #[test]
+    fn test_command_palette() {
+        use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+        let key = |code| Event::Key(KeyEvent::new(code, KeyModifiers::NONE));
+        let mut app = App::new();
+
+        app.handle_event(key(KeyCode::Char(':')));
+        assert_eq!(app.ui_state().input_mode, InputMode::CommandPalette);
+
+        // Esc closes the palette without running anything
+        app.handle_event(key(KeyCode::Char('q')));
+        app.handle_event(key(KeyCode::Esc));
+        assert_eq!(app.ui_state().input_mode, InputMode::Normal);
+        assert!(!app.should_quit());
+
+        // Enter runs the selected match and closes the palette
+        app.handle_event(key(KeyCode::Char(':')));
+        assert_eq!(app.palette().query, "");
+        for c in "logs".chars() {
+            app.handle_event(key(KeyCode::Char(c)));
+        }
+        app.handle_event(key(KeyCode::Down));
+        assert_eq!(
+            app.palette().selected_action().unwrap().name,
+            "Switch to Logs Tab"
+        );
+        app.handle_event(key(KeyCode::Enter));
+        assert_eq!(app.current_tab(), Tab::Logs);
+        assert_eq!(app.ui_state().input_mode, InputMode::Normal);
+
+        // Enter without a match just closes the palette
+        app.handle_event(key(KeyCode::Char(':')));
+        for c in "xyz".chars() {
+            app.handle_event(key(KeyCode::Char(c)));
+        }
+        app.handle_event(key(KeyCode::Enter));
+        assert_eq!(app.ui_state().input_mode, InputMode::Normal);
+        assert_eq!(app.current_tab(), Tab::Logs);
+
+        app.handle_event(key(KeyCode::Char(':')));
+        app.handle_event(key(KeyCode::Char('h')));
+        app.handle_event(key(KeyCode::Char('e')));
+        app.handle_event(key(KeyCode::Char('l')));
+        app.handle_event(key(KeyCode::Enter));
+        assert!(app.ui_state().show_help);
+    }
+
+    #[test]
+    fn test_jump_to_error_job() {
+        let mut app = App::new();
+        app.current_tab = Tab::Logs;
+        app.toggle_recent_errors();
+        app.select_next();
+
+        let job_id = app.recent_errors()[1].job_id.clone();
+        app.jump_to_error_job();
+
+        assert!(!app.ui_state().show_recent_errors);
+        assert_eq!(app.current_tab(), Tab::Jobs);
+        assert_eq!(app.get_selected_job().unwrap().id, job_id);
+    }
+
+    #[test]
+    fn test_log_scrolling_keys() {
+        use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+        let key = |c| Event::Key(KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE));
+        let mut app = App::new();
+        app.handle_event(key('4'));
+        app.sync_log_stream(None);
+
+        let job_id = app.get_selected_job().unwrap().id.clone();
+        assert_eq!(app.logs().job_id.as_deref(), Some(job_id.as_str()));
+        let last = app.logs().logs.len() - 1;
+        assert!(app.logs().following);
+        assert_eq!(app.logs().scroll_offset, last);
+
+        app.handle_event(key('k'));
+        app.handle_event(key('k'));
+        app.handle_event(key('j'));
+        assert_eq!(app.logs().scroll_offset, last - 1);
+        assert!(!app.logs().following);
+
+        app.handle_event(key('G'));
+        assert_eq!(app.logs().scroll_offset, last);
+        app.handle_event(key('F'));
+        assert!(app.logs().following);
+
+        // The same job keeps its buffer
+        app.handle_event(key('k'));
+        app.sync_log_stream(None);
+        assert_eq!(app.logs().scroll_offset, last - 1);
+    }
+
+    #[test]
+    fn test_mouse_events() {
+        use crossterm::event::KeyModifiers;
+
+        let mouse = |kind, column, row| {
+            Event::Mouse(MouseEvent {
+                kind,
+                column,
+                row,
+                modifiers: KeyModifiers::NONE,
+            })
+        };
+        let mut app = App::new();
+        app.handle_event(Event::Resize(80, 24));
+        assert_eq!(app.focused_panel(), Panel::Jobs);
+
+        // Wheel on the Jobs panel moves the job selection
+        app.handle_event(mouse(MouseEventKind::ScrollDown, 10, 10));
+        app.handle_event(mouse(MouseEventKind::ScrollDown, 10, 10));
+        app.handle_event(mouse(MouseEventKind::ScrollUp, 10, 10));
+        assert_eq!(app.selected_job(), 1);
+
+        // Clicks focus the panel under the cursor; the header focuses nothing
+        app.handle_event(mouse(MouseEventKind::Down(MouseButton::Left), 55, 10));
+        assert_eq!(app.focused_panel(), Panel::Agents);
+        assert_eq!(app.ui_state().focused_panel, Panel::Agents);
+        app.handle_event(mouse(MouseEventKind::ScrollDown, 55, 10));
+        assert_eq!(app.selected_agent(), 1);
+        assert_eq!(app.selected_job(), 1);
+
+        app.handle_event(mouse(MouseEventKind::Down(MouseButton::Left), 70, 10));
+        assert_eq!(app.focused_panel(), Panel::Queue);
+        app.handle_event(mouse(MouseEventKind::Down(MouseButton::Left), 10, 1));
+        assert_eq!(app.focused_panel(), Panel::Queue);
+
+        // Wheel on the Logs tab scrolls the logs
+        app.current_tab = Tab::Logs;
+        app.sync_log_stream(None);
+        let last = app.logs().logs.len() - 1;
+        app.handle_event(mouse(MouseEventKind::ScrollUp, 10, 10));
+        assert_eq!(app.logs().scroll_offset, last - 1);
+        app.handle_event(mouse(MouseEventKind::ScrollDown, 10, 10));
+        assert_eq!(app.logs().scroll_offset, last);
+    }
+
+    #[test]
+    fn test_drag_panel_border() {
+        use crossterm::event::KeyModifiers;
+
+        let mouse = |kind, column, row| {
+            Event::Mouse(MouseEvent {
+                kind,
+                column,
+                row,
+                modifiers: KeyModifiers::NONE,
+            })
+        };
+        let dir = tempfile::tempdir().unwrap();
+        let layout_file = dir.path().join("tui-layout.json");
+        let mut app = App::with_config(AppConfig {
+            layout_file: Some(layout_file.clone()),
+            ..Default::default()
+        });
+        app.handle_event(Event::Resize(100, 24));
+
+        // The jobs panel ends at column 60, where the agents panel begins
+        app.handle_event(mouse(MouseEventKind::Down(MouseButton::Left), 60, 10));
+        app.handle_event(mouse(MouseEventKind::Drag(MouseButton::Left), 45, 10));
+        assert_eq!(app.panel_widths().as_array(), [45, 35, 20]);
+        assert_eq!(app.ui_state().panel_widths, app.panel_widths());
+        assert_eq!(app.focused_panel(), Panel::Jobs);
+
+        // Widths are clamped, and saved when the button is released
+        app.handle_event(mouse(MouseEventKind::Drag(MouseButton::Left), 5, 10));
+        app.handle_event(mouse(MouseEventKind::Up(MouseButton::Left), 5, 10));
+        assert_eq!(app.panel_widths().as_array(), [15, 65, 20]);
+        assert_eq!(PanelWidths::load(&layout_file), Some(app.panel_widths()));
+
+        // Dragging without grabbing a border resizes nothing
+        app.handle_event(mouse(MouseEventKind::Down(MouseButton::Left), 40, 10));
+        app.handle_event(mouse(MouseEventKind::Drag(MouseButton::Left), 50, 10));
+        assert_eq!(app.panel_widths().as_array(), [15, 65, 20]);
+        assert_eq!(app.focused_panel(), Panel::Agents);
+    }
+
+    #[test]
+    fn test_live_updates() {
+        use crate::live::LiveData;
+        use raibid_common::jobs::QueueStats;
+
+        let mut app = App::with_config(AppConfig {
+            api_url: Some("http://127.0.0.1:8080".to_string()),
+            ..Default::default()
+        });
+        assert!(app.jobs().is_empty());
+
+        // Ticks don't replace live data with mock data
+        app.handle_event(Event::Tick);
+        assert!(app.jobs().is_empty());
+
+        let (jobs, agents, _) = generate_mock_data(&MockDataConfig::default());
+        app.apply_live_update(Some(Ok(LiveData {
+            jobs: jobs.clone(),
+            agents,
+            queue: QueueStats {
+                pending_count: 4,
+                ..Default::default()
+            },
+        })));
+        assert_eq!(app.jobs().len(), jobs.len());
+        assert_eq!(app.queue_data().current, 4);
+        assert_eq!(app.connection_error(), None);
+
+        // A failed poll keeps the last data and shows the error
+        app.apply_live_update(Some(Err("connection refused".to_string())));
+        assert_eq!(app.jobs().len(), jobs.len());
+        assert_eq!(app.ui_state().connection_error, Some("connection refused"));
+    }
+
+    #[test]
+    fn test_connection_stats() {
+        use crate::live::ConnectionState;
+        use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+        let ctrl_i = || Event::Key(KeyEvent::new(KeyCode::Char('i'), KeyModifiers::CONTROL));
+
+        // Mock data has no connection to show
+        let mut app = App::new();
+        assert!(app.connection().is_none());
+        app.handle_event(ctrl_i());
+        assert!(!app.ui_state().show_connection_stats);
+
+        let mut app = App::with_config(AppConfig {
+            api_url: Some("http://127.0.0.1:8080".to_string()),
+            ..Default::default()
+        });
+        assert!(matches!(
+            app.connection().unwrap().state,
+            ConnectionState::Disconnected(_)
+        ));
+
+        let (tx, rx) = watch::channel(ConnectionStats::default());
+        app.connection_updates = Some(rx);
+        let mut stats = ConnectionStats::default();
+        stats.record_success(Duration::from_millis(50), chrono::Local::now());
+        tx.send(stats).unwrap();
+        app.receive_connection_stats();
+        assert_eq!(app.connection().unwrap().state, ConnectionState::Connected);
+        assert_eq!(
+            app.ui_state().connection.unwrap().state,
+            ConnectionState::Connected
+        );
+
+        app.handle_event(ctrl_i());
+        assert!(app.ui_state().show_connection_stats);
+        // Any key closes the popup
+        app.handle_event(Event::Key(KeyEvent::new(
+            KeyCode::Char('x'),
+            KeyModifiers::NONE,
+        )));
+        assert!(!app.ui_state().show_connection_stats);
+    }
+
+    #[test]
+    fn test_agent_events_update_agents() {
+        use crate::live::LiveData;
+        use crate::mock_data::AgentStatus;
+        use raibid_common::agents::AgentStatus as ApiAgentStatus;
+
+        let mut app = App::with_config(AppConfig {
+            api_url: Some("http://127.0.0.1:8080".to_string()),
+            ..Default::default()
+        });
+        let (_, agents, _) = generate_mock_data(&MockDataConfig::default());
+        let agent_id = agents[0].id.clone();
+        app.apply_live_update(Some(Ok(LiveData {
+            jobs: Vec::new(),
+            agents,
+            queue: Default::default(),
+        })));
+
+        let (tx, rx) = mpsc::unbounded_channel();
+        app.agent_events = Some(rx);
+        tx.send(AgentEvent::now(agent_id.clone(), ApiAgentStatus::Busy))
+            .unwrap();
+        tx.send(AgentEvent::now("unknown-agent", ApiAgentStatus::Idle))
+            .unwrap();
+        app.receive_agent_events();
+        assert_eq!(app.agents()[0].status, AgentStatus::Busy);
+
+        tx.send(AgentEvent::now(agent_id, ApiAgentStatus::Offline))
+            .unwrap();
+        app.receive_agent_events();
+        assert_eq!(app.agents()[0].status, AgentStatus::Stopping);
+    }
+
+    #[test]
+    fn test_detail_popup_fetches_test_results() {
+        use crate::live::LiveData;
+
+        let mut server = mockito::Server::new();
+        let mut app = App::with_config(AppConfig {
+            api_url: Some(server.url()),
+            ..Default::default()
+        });
+
+        let (jobs, _, _) = generate_mock_data(&MockDataConfig::default());
+        let job_id = jobs[0].id.clone();
+        server
+            .mock("GET", format!("/api/jobs/{}/test-results", job_id).as_str())
+            .with_body(r#"{"suites":[{"name":"unit","tests":4,"failures":1,"errors":0}]}"#)
+            .create();
+        app.apply_live_update(Some(Ok(LiveData {
+            jobs,
+            agents: Vec::new(),
+            queue: Default::default(),
+        })));
+
+        app.toggle_detail_popup();
+        let report = app.test_report().unwrap().as_ref().unwrap();
+        assert_eq!(report.passed(), 3);
+        assert_eq!(report.failures(), 1);
+    }
+
+    #[test]
+    fn test_detail_popup_fetches_dependencies() {
+        use crate::live::LiveData;
+
+        let mut server = mockito::Server::new();
+        let mut app = App::with_config(AppConfig {
+            api_url: Some(server.url()),
+            ..Default::default()
+        });
+
+        let (jobs, _, _) = generate_mock_data(&MockDataConfig::default());
+        server
+            .mock(
+                "GET",
+                format!("/api/jobs/{}/dependencies", jobs[0].id).as_str(),
+            )
+            .with_body(r#"{"direct":2,"transitive":5}"#)
+            .create();
+        app.apply_live_update(Some(Ok(LiveData {
+            jobs,
+            agents: Vec::new(),
+            queue: Default::default(),
+        })));
+
+        app.toggle_detail_popup();
+        let report = app.dependencies().unwrap().as_ref().unwrap();
+        assert_eq!(report.direct, 2);
+        assert_eq!(report.total(), 7);
+    }
 }
@@ -1,9 +1,11 @@
 //! Event handling for the TUI
 //!
-//! This module manages keyboard and terminal events using crossterm.
+//! This module manages keyboard, mouse, and terminal events using crossterm.
 
 use anyhow::Result;
-use crossterm::event::{self, Event as CrosstermEvent, KeyCode, KeyEvent, KeyModifiers};
+use crossterm::event::{
+    self, Event as CrosstermEvent, KeyCode, KeyEvent, KeyModifiers, MouseEvent,
+};
 use std::time::Duration;
 
 /// Application events
@@ -11,6 +13,8 @@ use std::time::Duration;
 pub enum Event {
     /// Key press event
     Key(KeyEvent),
+    /// Mouse click or wheel event
+    Mouse(MouseEvent),
     /// Terminal resize event
     Resize(u16, u16),
     /// Tick event for periodic updates
@@ -36,6 +40,7 @@ impl EventHandler {
         if event::poll(self.tick_rate)? {
             match event::read()? {
                 CrosstermEvent::Key(key) => Ok(Event::Key(key)),
+                CrosstermEvent::Mouse(mouse) => Ok(Event::Mouse(mouse)),
                 CrosstermEvent::Resize(width, height) => Ok(Event::Resize(width, height)),
                 _ => Ok(Event::Tick),
             }
@@ -0,0 +1,242 @@
+//! Rust compiler output highlighting
+//!
+//! Build logs for Rust projects are mostly rustc diagnostics. This module
+//! recognises the diagnostic structure line by line with a small state machine
+//! and styles it for the Logs tab:
+//!
+//! ```text
+//! error[E0425]: cannot find value `x` in this scope    <- header (code in red bold)
+//!   --> src/main.rs:4:13                               <- location (path in blue underline)
+//!    |                                                 <- source gutter (dim)
+//! 4  |     let y = x + 1;                              <- source gutter (dim)
+//!    |             ^ not found in this scope           <- marker (yellow)
+//! ```
+
+use ratatui::{
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+};
+
+/// Parser state
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    /// Not inside a diagnostic; lines are plain text
+    Text,
+    /// Inside a diagnostic block (after an `error`/`warning` header)
+    Diagnostic,
+}
+
+/// Stateful highlighter for a stream of log lines
+#[derive(Debug, Clone)]
+pub struct RustDiagnosticHighlighter {
+    state: State,
+}
+
+impl Default for RustDiagnosticHighlighter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RustDiagnosticHighlighter {
+    /// Create a highlighter in the initial (plain text) state
+    pub fn new() -> Self {
+        Self { state: State::Text }
+    }
+
+    /// Highlight the next log line
+    pub fn highlight(&mut self, line: &str) -> Line<'static> {
+        if let Some(spans) = header_spans(line) {
+            self.state = State::Diagnostic;
+            return Line::from(spans);
+        }
+
+        if self.state == State::Diagnostic {
+            if let Some(spans) = location_spans(line) {
+                return Line::from(spans);
+            }
+            if let Some(spans) = gutter_spans(line) {
+                return Line::from(spans);
+            }
+            // Anything else ends the diagnostic block
+            self.state = State::Text;
+        }
+
+        Line::from(line.to_string())
+    }
+
+    /// Highlight a full block of lines
+    #[cfg(test)]
+    pub fn highlight_all<'a>(
+        &mut self,
+        lines: impl IntoIterator<Item = &'a str>,
+    ) -> Vec<Line<'static>> {
+        lines.into_iter().map(|line| self.highlight(line)).collect()
+    }
+}
+
+fn error_style() -> Style {
+    Style::default().fg(Color::Red).add_modifier(Modifier::BOLD)
+}
+
+fn warning_style() -> Style {
+    Style::default()
+        .fg(Color::Yellow)
+        .add_modifier(Modifier::BOLD)
+}
+
+fn path_style() -> Style {
+    Style::default()
+        .fg(Color::Blue)
+        .add_modifier(Modifier::UNDERLINED)
+}
+
+fn dim_style() -> Style {
+    Style::default().add_modifier(Modifier::DIM)
+}
+
+fn suggestion_style() -> Style {
+    Style::default().fg(Color::Yellow)
+}
+
+/// `error[E0425]: ...`, `error: ...`, `warning: ...`
+fn header_spans(line: &str) -> Option<Vec<Span<'static>>> {
+    let (label_len, style) = if line.starts_with("error") {
+        (diagnostic_label_len(line, "error")?, error_style())
+    } else if line.starts_with("warning") {
+        (diagnostic_label_len(line, "warning")?, warning_style())
+    } else {
+        return None;
+    };
+
+    let (label, message) = line.split_at(label_len);
+    Some(vec![
+        Span::styled(label.to_string(), style),
+        Span::styled(
+            message.to_string(),
+            Style::default().add_modifier(Modifier::BOLD),
+        ),
+    ])
+}
+
+/// Length of `error` / `error[E0000]` when followed by `:`
+fn diagnostic_label_len(line: &str, kind: &str) -> Option<usize> {
+    let rest = &line[kind.len()..];
+    if rest.starts_with(':') {
+        return Some(kind.len());
+    }
+
+    let code = rest.strip_prefix('[')?;
+    let end = code.find(']')?;
+    let (letter, digits) = code[..end].split_at(1.min(end));
+    let valid_code = letter.chars().all(|c| c.is_ascii_uppercase())
+        && !digits.is_empty()
+        && digits.chars().all(|c| c.is_ascii_digit());
+
+    if valid_code && code[end + 1..].starts_with(':') {
+        Some(kind.len() + end + 2)
+    } else {
+        None
+    }
+}
+
+/// `  --> src/main.rs:4:13`
+fn location_spans(line: &str) -> Option<Vec<Span<'static>>> {
+    let trimmed = line.trim_start();
+    let path = trimmed.strip_prefix("--> ")?;
+    let prefix_len = line.len() - path.len();
+
+    Some(vec![
+        Span::styled(line[..prefix_len].to_string(), dim_style()),
+        Span::styled(path.to_string(), path_style()),
+    ])
+}
+
+/// `   |`, `12 |     code`, `   |     ^^^ consider ...`, `   = note: ...`
+fn gutter_spans(line: &str) -> Option<Vec<Span<'static>>> {
+    let bar = line.find(['|', '='])?;
+    if !line[..bar].chars().all(|c| c.is_ascii_digit() || c == ' ') {
+        return None;
+    }
+
+    let (gutter, rest) = line.split_at(bar + 1);
+    let marker_start = rest.find(|c: char| c != ' ');
+
+    match marker_start {
+        Some(start)
+            if rest[start..].starts_with(['^', '-'])
+                && !line[..bar].trim().chars().any(|c| c.is_ascii_digit()) =>
+        {
+            Some(vec![
+                Span::styled(format!("{}{}", gutter, &rest[..start]), dim_style()),
+                Span::styled(rest[start..].to_string(), suggestion_style()),
+            ])
+        }
+        _ => Some(vec![Span::styled(line.to_string(), dim_style())]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn styles(line: &Line) -> Vec<(String, Style)> {
+        line.spans
+            .iter()
+            .map(|s| (s.content.to_string(), s.style))
+            .collect()
+    }
+
+    #[test]
+    fn test_error_header() {
+        let mut hl = RustDiagnosticHighlighter::new();
+        let line = hl.highlight("error[E0425]: cannot find value `x` in this scope");
+        let spans = styles(&line);
+        assert_eq!(spans[0], ("error[E0425]".to_string(), error_style()));
+        assert_eq!(hl.state, State::Diagnostic);
+    }
+
+    #[test]
+    fn test_warning_header() {
+        let mut hl = RustDiagnosticHighlighter::new();
+        let line = hl.highlight("warning: unused variable: `y`");
+        assert_eq!(styles(&line)[0], ("warning".to_string(), warning_style()));
+    }
+
+    #[test]
+    fn test_full_diagnostic() {
+        let mut hl = RustDiagnosticHighlighter::new();
+        let lines = hl.highlight_all([
+            "error[E0425]: cannot find value `x` in this scope",
+            "  --> src/main.rs:4:13",
+            "   |",
+            "4  |     let y = x + 1;",
+            "   |             ^ consider declaring `x`",
+            "",
+            "Compiling raibid-cli v0.1.0",
+        ]);
+
+        assert_eq!(
+            styles(&lines[1])[1],
+            ("src/main.rs:4:13".to_string(), path_style())
+        );
+        assert_eq!(styles(&lines[2])[0].1, dim_style());
+        assert_eq!(styles(&lines[3])[0].1, dim_style());
+        assert_eq!(
+            styles(&lines[4])[1],
+            ("^ consider declaring `x`".to_string(), suggestion_style())
+        );
+        assert_eq!(styles(&lines[6])[0].1, Style::default());
+    }
+
+    #[test]
+    fn test_plain_lines_outside_diagnostic() {
+        let mut hl = RustDiagnosticHighlighter::new();
+        // Gutter-looking lines are not styled outside a diagnostic
+        let line = hl.highlight("   | not rustc output");
+        assert_eq!(styles(&line)[0].1, Style::default());
+
+        let line = hl.highlight("errors were found");
+        assert_eq!(styles(&line)[0].1, Style::default());
+    }
+}
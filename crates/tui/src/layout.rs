@@ -0,0 +1,178 @@
+//! Panel widths of the Jobs tab
+//!
+//! The borders between the jobs, agents and queue panels can be dragged with
+//! the mouse. The widths last used are saved to `~/.raibid/tui-layout.json`
+//! and restored on the next launch.
+
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// File in `~/.raibid` holding the last used panel widths
+pub const LAYOUT_FILE: &str = "tui-layout.json";
+
+/// Narrowest a panel can be made, in percent of the screen width
+pub const MIN_PANEL_PCT: u16 = 15;
+
+/// Widest a panel can be made, in percent of the screen width
+pub const MAX_PANEL_PCT: u16 = 70;
+
+/// Widths of the Jobs tab panels in percent, summing to 100
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PanelWidths {
+    pub jobs: u16,
+    pub agents: u16,
+    pub queue: u16,
+}
+
+impl Default for PanelWidths {
+    fn default() -> Self {
+        Self {
+            jobs: 60,
+            agents: 20,
+            queue: 20,
+        }
+    }
+}
+
+impl PanelWidths {
+    /// Widths summing to 100, each between [`MIN_PANEL_PCT`] and
+    /// [`MAX_PANEL_PCT`]
+    pub fn new(jobs: u16, agents: u16, queue: u16) -> Result<Self> {
+        let widths = Self {
+            jobs,
+            agents,
+            queue,
+        };
+        widths.validate()?;
+        Ok(widths)
+    }
+
+    fn validate(&self) -> Result<()> {
+        let total: u32 = self.as_array().into_iter().map(u32::from).sum();
+        if total != 100 {
+            return Err(anyhow!("Panel widths must add up to 100%, got {}%", total));
+        }
+        for (name, pct) in [
+            ("jobs", self.jobs),
+            ("agents", self.agents),
+            ("queue", self.queue),
+        ] {
+            if !(MIN_PANEL_PCT..=MAX_PANEL_PCT).contains(&pct) {
+                return Err(anyhow!(
+                    "The {} panel must be {}-{}% wide, got {}%",
+                    name,
+                    MIN_PANEL_PCT,
+                    MAX_PANEL_PCT,
+                    pct
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Widths left to right
+    pub fn as_array(&self) -> [u16; 3] {
+        [self.jobs, self.agents, self.queue]
+    }
+
+    /// Move a border between panels to `pct` percent of the screen width,
+    /// resizing the two panels next to it
+    ///
+    /// Border 0 is between the jobs and agents panels, border 1 between the
+    /// agents and queue panels. Neither panel is made narrower than
+    /// [`MIN_PANEL_PCT`] or wider than [`MAX_PANEL_PCT`].
+    pub fn move_border(&mut self, border: usize, pct: u16) {
+        let (left_start, left, right) = match border {
+            0 => (0, &mut self.jobs, &mut self.agents),
+            1 => (self.jobs, &mut self.agents, &mut self.queue),
+            _ => return,
+        };
+
+        let pair = *left + *right;
+        let min = MIN_PANEL_PCT.max(pair.saturating_sub(MAX_PANEL_PCT));
+        let max = MAX_PANEL_PCT.min(pair.saturating_sub(MIN_PANEL_PCT));
+        if min > max {
+            return;
+        }
+
+        *left = pct.saturating_sub(left_start).clamp(min, max);
+        *right = pair - *left;
+    }
+
+    /// `~/.raibid/tui-layout.json`, or `None` without a home directory
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::home_dir().map(|home| home.join(".raibid").join(LAYOUT_FILE))
+    }
+
+    /// Read saved widths, or `None` if the file is missing or invalid
+    pub fn load(path: &Path) -> Option<Self> {
+        let contents = fs::read_to_string(path).ok()?;
+        let widths: Self = serde_json::from_str(&contents).ok()?;
+        widths.validate().ok()?;
+        Some(widths)
+    }
+
+    /// Save the widths, creating the parent directory if needed
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+        let contents = serde_json::to_string_pretty(self)?;
+        fs::write(path, contents).with_context(|| format!("Failed to write {}", path.display()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_new_validates() {
+        assert_eq!(
+            PanelWidths::new(60, 25, 15).unwrap().as_array(),
+            [60, 25, 15]
+        );
+        assert!(PanelWidths::new(60, 25, 20).is_err());
+        assert!(PanelWidths::new(80, 10, 10).is_err());
+        assert!(PanelWidths::new(10, 45, 45).is_err());
+    }
+
+    #[test]
+    fn test_move_border() {
+        let mut widths = PanelWidths::default();
+
+        widths.move_border(0, 50);
+        assert_eq!(widths.as_array(), [50, 30, 20]);
+
+        // Clamped so the agents panel keeps its minimum width
+        widths.move_border(0, 75);
+        assert_eq!(widths.as_array(), [65, 15, 20]);
+
+        widths.move_border(0, 40);
+        widths.move_border(1, 70);
+        assert_eq!(widths.as_array(), [40, 30, 30]);
+
+        widths.move_border(1, 45);
+        assert_eq!(widths.as_array(), [40, 15, 45]);
+
+        widths.move_border(2, 10);
+        assert_eq!(widths.as_array(), [40, 15, 45]);
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(".raibid").join(LAYOUT_FILE);
+        assert_eq!(PanelWidths::load(&path), None);
+
+        let widths = PanelWidths::new(50, 30, 20).unwrap();
+        widths.save(&path).unwrap();
+        assert_eq!(PanelWidths::load(&path), Some(widths));
+
+        fs::write(&path, r#"{"jobs": 90, "agents": 5, "queue": 5}"#).unwrap();
+        assert_eq!(PanelWidths::load(&path), None);
+    }
+}
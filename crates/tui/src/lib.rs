@@ -5,19 +5,30 @@
 
 mod app;
 mod events;
+mod highlight;
+mod layout;
+mod live;
+mod logs;
 mod mock_data;
+mod palette;
 mod terminal;
 mod ui;
 
 #[allow(unused_imports)]
-pub use app::{App, AppConfig, InputMode, Tab};
+pub use app::{App, AppConfig, InputMode, Panel, Tab};
 #[allow(unused_imports)]
 pub use events::Event;
 #[allow(unused_imports)]
+pub use layout::{PanelWidths, LAYOUT_FILE, MAX_PANEL_PCT, MIN_PANEL_PCT};
+#[allow(unused_imports)]
+pub use live::TuiDataSource;
+#[allow(unused_imports)]
 pub use mock_data::{
     generate_mock_data, AgentStatus, JobStatus, MockAgent, MockDataConfig, MockJob, MockQueueData,
 };
 #[allow(unused_imports)]
+pub use palette::{PaletteAction, PALETTE_ACTIONS};
+#[allow(unused_imports)]
 pub use terminal::{Terminal, MIN_HEIGHT, MIN_WIDTH};
 
 use anyhow::Result;
@@ -46,7 +57,6 @@ pub fn launch() -> Result<()> {
 }
 
 /// Launch the TUI application with custom configuration
-#[allow(dead_code)]
 pub fn launch_with_config(config: AppConfig) -> Result<()> {
     // Initialize terminal
     let mut terminal = terminal::init()?;
@@ -0,0 +1,567 @@
+//! Live data from raibid-server
+//!
+//! With an API URL configured the TUI shows jobs and agents from the server
+//! instead of mock data. A background task polls [`AsyncApiClient`] and publishes
+//! each result on a [`watch`] channel; [`App::run`](crate::App::run) applies
+//! the latest one before every render, so the UI never blocks on the network.
+//!
+//! Every poll is also recorded in [`ConnectionStats`], published on a second
+//! channel, from which the header shows whether the server is reachable and
+//! responsive.
+//!
+//! Agent status changes also arrive between polls: [`stream_agent_events`]
+//! follows `GET /ws/agents` and forwards each [`AgentEvent`], which the app
+//! applies to its agents list right away.
+
+use anyhow::Result;
+use chrono::{DateTime, Local};
+use futures::StreamExt;
+use raibid_common::agents::{AgentEvent, AgentInfo, AgentStatus as ApiAgentStatus};
+use raibid_common::jobs::{Job, JobStatus as ApiJobStatus, QueueStats};
+use raibid_common::{ApiClient, AsyncApiClient};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, watch, Notify};
+use tokio_tungstenite::tungstenite::Message;
+
+use super::app::AppConfig;
+use super::logs::{websocket_request, websocket_url};
+use super::mock_data::{AgentStatus, JobStatus, MockAgent, MockJob};
+
+/// Where the TUI gets its data
+#[derive(Debug, Clone)]
+pub enum TuiDataSource {
+    /// Generated mock data (offline mode and tests)
+    Mock,
+    /// Polled from the API server
+    Live(ApiClient),
+}
+
+impl TuiDataSource {
+    /// Live source when an API URL is configured, otherwise mock data
+    pub fn from_config(config: &AppConfig) -> Result<Self> {
+        let Some(ref url) = config.api_url else {
+            return Ok(TuiDataSource::Mock);
+        };
+
+        let client = ApiClient::new(url)?;
+        Ok(TuiDataSource::Live(match config.api_key {
+            Some(ref key) => client.with_api_key(key),
+            None => client,
+        }))
+    }
+
+    /// API client of a live source
+    pub fn client(&self) -> Option<&ApiClient> {
+        match self {
+            TuiDataSource::Mock => None,
+            TuiDataSource::Live(client) => Some(client),
+        }
+    }
+}
+
+/// One poll of the server
+#[derive(Debug, Clone)]
+pub struct LiveData {
+    pub jobs: Vec<MockJob>,
+    pub agents: Vec<MockAgent>,
+    pub queue: QueueStats,
+}
+
+/// Latest poll result: `None` until the first poll completes, then the data
+/// or the error message of a failed poll
+pub type LiveUpdate = Option<Result<LiveData, String>>;
+
+/// Latency above which a successful poll counts as degraded
+pub const DEGRADED_LATENCY: Duration = Duration::from_secs(2);
+
+/// Consecutive failed polls after which the server counts as disconnected
+pub const DISCONNECTED_AFTER_FAILURES: u32 = 3;
+
+/// Number of poll latencies kept for the connection stats popup
+pub const LATENCY_HISTORY: usize = 100;
+
+/// Upper bounds of the latency histogram buckets, in milliseconds; the last
+/// bucket holds everything slower
+pub const LATENCY_BUCKETS_MS: [u64; 4] = [250, 500, 1000, 2000];
+
+/// Health of the connection to the server, from the most recent polls
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConnectionState {
+    /// The last poll succeeded quickly
+    Connected,
+    /// The last poll was slow, or a few polls in a row failed
+    Degraded(String),
+    /// [`DISCONNECTED_AFTER_FAILURES`] polls in a row failed, or none has
+    /// succeeded yet
+    Disconnected(String),
+}
+
+/// Outcomes of the polls of the server
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConnectionStats {
+    /// Current connection health
+    pub state: ConnectionState,
+    /// Failed polls since the last successful one
+    pub consecutive_failures: u32,
+    /// Polls made so far
+    pub polls: u64,
+    /// Polls that failed so far
+    pub failures: u64,
+    /// When the last successful poll completed
+    pub last_success: Option<DateTime<Local>>,
+    /// Latencies of the most recent successful polls, oldest first
+    pub latencies: VecDeque<Duration>,
+}
+
+impl Default for ConnectionStats {
+    fn default() -> Self {
+        Self {
+            state: ConnectionState::Disconnected("Waiting for the first poll".to_string()),
+            consecutive_failures: 0,
+            polls: 0,
+            failures: 0,
+            last_success: None,
+            latencies: VecDeque::new(),
+        }
+    }
+}
+
+impl ConnectionStats {
+    /// Record a poll that succeeded after `latency`
+    pub fn record_success(&mut self, latency: Duration, at: DateTime<Local>) {
+        self.polls += 1;
+        self.consecutive_failures = 0;
+        self.last_success = Some(at);
+        if self.latencies.len() == LATENCY_HISTORY {
+            self.latencies.pop_front();
+        }
+        self.latencies.push_back(latency);
+
+        self.state = if latency > DEGRADED_LATENCY {
+            ConnectionState::Degraded(format!("Slow response ({:.1}s)", latency.as_secs_f64()))
+        } else {
+            ConnectionState::Connected
+        };
+    }
+
+    /// Record a poll that failed with `error`
+    ///
+    /// The connection only counts as disconnected after
+    /// [`DISCONNECTED_AFTER_FAILURES`] failures in a row, or if no poll has
+    /// succeeded yet; until then it is degraded.
+    pub fn record_failure(&mut self, error: &str) {
+        self.polls += 1;
+        self.failures += 1;
+        self.consecutive_failures += 1;
+
+        let error = error.lines().next().unwrap_or(error).to_string();
+        self.state = if self.last_success.is_none()
+            || self.consecutive_failures >= DISCONNECTED_AFTER_FAILURES
+        {
+            ConnectionState::Disconnected(error)
+        } else {
+            ConnectionState::Degraded(error)
+        };
+    }
+
+    /// Number of recent latencies in each bucket of [`LATENCY_BUCKETS_MS`],
+    /// labelled by their upper bound
+    pub fn latency_histogram(&self) -> Vec<(String, u64)> {
+        let mut counts = [0u64; LATENCY_BUCKETS_MS.len() + 1];
+        for latency in &self.latencies {
+            let millis = latency.as_millis() as u64;
+            let bucket = LATENCY_BUCKETS_MS
+                .iter()
+                .position(|&bound| millis < bound)
+                .unwrap_or(LATENCY_BUCKETS_MS.len());
+            counts[bucket] += 1;
+        }
+
+        let labels = LATENCY_BUCKETS_MS
+            .iter()
+            .map(|&bound| format!("<{}", format_millis(bound)))
+            .chain(std::iter::once(format!(
+                ">{}",
+                format_millis(LATENCY_BUCKETS_MS[LATENCY_BUCKETS_MS.len() - 1])
+            )));
+        labels.zip(counts).collect()
+    }
+
+    /// Mean of the recent latencies
+    pub fn average_latency(&self) -> Option<Duration> {
+        let count = u32::try_from(self.latencies.len())
+            .ok()
+            .filter(|&n| n > 0)?;
+        Some(self.latencies.iter().sum::<Duration>() / count)
+    }
+}
+
+/// `250ms` or `2s`
+fn format_millis(millis: u64) -> String {
+    if millis >= 1000 && millis.is_multiple_of(1000) {
+        format!("{}s", millis / 1000)
+    } else {
+        format!("{}ms", millis)
+    }
+}
+
+/// Fetch jobs, agents, and queue statistics from the server
+pub async fn fetch_live_data(client: &AsyncApiClient) -> Result<LiveData> {
+    let labels = HashMap::new();
+    let (jobs, agents, queue) = tokio::try_join!(
+        client.list_jobs(&labels),
+        client.list_agents(),
+        client.queue_stats(),
+    )?;
+
+    Ok(LiveData {
+        jobs: jobs.iter().map(MockJob::from).collect(),
+        agents: agents.iter().map(MockAgent::from).collect(),
+        queue,
+    })
+}
+
+/// Poll the server every `interval` until all receivers are dropped
+///
+/// Each poll is recorded in the [`ConnectionStats`] sent on `connection`.
+/// `refresh` wakes the poller early for a manual refresh.
+pub async fn poll_api(
+    client: AsyncApiClient,
+    interval: Duration,
+    updates: watch::Sender<LiveUpdate>,
+    connection: watch::Sender<ConnectionStats>,
+    refresh: Arc<Notify>,
+) {
+    let mut stats = ConnectionStats::default();
+    loop {
+        let started = Instant::now();
+        let result = fetch_live_data(&client)
+            .await
+            .map_err(|e| format!("{:#}", e));
+        match result {
+            Ok(_) => stats.record_success(started.elapsed(), Local::now()),
+            Err(ref e) => stats.record_failure(e),
+        }
+
+        if updates.send(Some(result)).is_err() {
+            return;
+        }
+        // The app may not follow the connection, so a closed channel is fine
+        let _ = connection.send(stats.clone());
+
+        tokio::select! {
+            _ = tokio::time::sleep(interval) => {}
+            _ = refresh.notified() => {}
+        }
+    }
+}
+
+/// Delay before reconnecting to the agent event stream
+const AGENT_EVENTS_RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+/// WebSocket URL streaming agent status changes from the server at `base_url`
+pub fn agent_events_url(base_url: &str) -> String {
+    websocket_url(base_url, "/ws/agents")
+}
+
+/// Follow the agent event stream at `url`, authenticated with `api_key`,
+/// forwarding every event until `events` is dropped
+///
+/// The stream is reconnected whenever it fails; the regular polls keep the
+/// agents list current in the meantime.
+pub async fn stream_agent_events(
+    url: String,
+    api_key: Option<String>,
+    events: mpsc::UnboundedSender<AgentEvent>,
+) {
+    while !events.is_closed() {
+        let connected = match websocket_request(&url, api_key.as_deref()) {
+            Ok(request) => tokio_tungstenite::connect_async(request)
+                .await
+                .map_err(Into::into),
+            Err(e) => Err(e),
+        };
+        match connected {
+            Ok((mut socket, _)) => {
+                while let Some(Ok(message)) = socket.next().await {
+                    let Message::Text(text) = message else {
+                        continue;
+                    };
+                    match serde_json::from_str(&text) {
+                        Ok(event) => {
+                            if events.send(event).is_err() {
+                                return;
+                            }
+                        }
+                        Err(e) => tracing::debug!("Skipping invalid agent event: {}", e),
+                    }
+                }
+            }
+            Err(e) => tracing::debug!("Agent event stream unavailable: {}", e),
+        }
+        tokio::time::sleep(AGENT_EVENTS_RECONNECT_DELAY).await;
+    }
+}
+
+/// How the TUI shows an agent status reported by the server
+pub fn agent_status(status: ApiAgentStatus) -> AgentStatus {
+    match status {
+        ApiAgentStatus::Idle => AgentStatus::Idle,
+        ApiAgentStatus::Busy => AgentStatus::Busy,
+        ApiAgentStatus::Starting => AgentStatus::Starting,
+        ApiAgentStatus::Stopping | ApiAgentStatus::Offline => AgentStatus::Stopping,
+    }
+}
+
+impl From<&Job> for MockJob {
+    fn from(job: &Job) -> Self {
+        let status = match job.status {
+            ApiJobStatus::Pending | ApiJobStatus::Retrying => JobStatus::Pending,
+            ApiJobStatus::Running => JobStatus::Running,
+            ApiJobStatus::Success => JobStatus::Success,
+            ApiJobStatus::Failed | ApiJobStatus::Cancelled => JobStatus::Failed,
+        };
+
+        Self {
+            id: job.id.clone(),
+            repo: job.repo.clone(),
+            branch: job.branch.clone(),
+            status,
+            // The server does not report progress, only completion
+            progress: if status == JobStatus::Success { 100 } else { 0 },
+            start_time: job.created_at,
+            duration: None,
+            labels: job.labels.clone(),
+        }
+    }
+}
+
+impl From<&AgentInfo> for MockAgent {
+    fn from(agent: &AgentInfo) -> Self {
+        Self {
+            id: agent.id.clone(),
+            name: agent.id.clone(),
+            status: agent_status(agent.status),
+            cpu: agent.cpu_percent.clamp(0.0, 100.0).round() as u8,
+            memory: agent.memory_percent.clamp(0.0, 100.0).round() as u8,
+            uptime: agent.uptime_secs,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    #[test]
+    fn test_from_config() {
+        let source = TuiDataSource::from_config(&AppConfig::default()).unwrap();
+        assert!(source.client().is_none());
+
+        let config = AppConfig {
+            api_url: Some("http://127.0.0.1:8080/".to_string()),
+            ..Default::default()
+        };
+        let source = TuiDataSource::from_config(&config).unwrap();
+        assert_eq!(source.client().unwrap().base_url(), "http://127.0.0.1:8080");
+    }
+
+    #[test]
+    fn test_job_conversion() {
+        let job = Job {
+            id: "job-1".to_string(),
+            repo: "raibid-labs/raibid-cli".to_string(),
+            branch: "main".to_string(),
+            commit: None,
+            status: ApiJobStatus::Cancelled,
+            created_at: Utc::now(),
+            labels: HashMap::from([("source".to_string(), "gitlab".to_string())]),
+        };
+
+        let mock = MockJob::from(&job);
+        assert_eq!(mock.id, "job-1");
+        assert_eq!(mock.labels, job.labels);
+        assert_eq!(mock.status, JobStatus::Failed);
+        assert_eq!(mock.start_time, job.created_at);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_live_data() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("GET", "/api/jobs")
+            .with_body(
+                r#"[{"id":"job-1","repo":"a/b","branch":"main","status":"running",
+                     "created_at":"2024-01-01T00:00:00Z"}]"#,
+            )
+            .create_async()
+            .await;
+        server
+            .mock("GET", "/api/agents")
+            .with_body(
+                r#"[{"id":"agent-1","status":"busy","cpu_percent":71.6,
+                     "memory_percent":40.0,"uptime_secs":60}]"#,
+            )
+            .create_async()
+            .await;
+        server
+            .mock("GET", "/api/queue/stats")
+            .with_body(
+                r#"{"total_messages":3,"pending_count":1,"consumer_count":1,
+                    "lag":2,"oldest_pending_ms":null}"#,
+            )
+            .create_async()
+            .await;
+
+        let client = AsyncApiClient::new(server.url()).unwrap();
+        let data = fetch_live_data(&client).await.unwrap();
+        assert_eq!(data.jobs[0].status, JobStatus::Running);
+        assert_eq!(data.agents[0].cpu, 72);
+        assert_eq!(data.queue.pending_count, 1);
+    }
+
+    #[tokio::test]
+    async fn test_stream_agent_events() {
+        use futures::SinkExt;
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let event = AgentEvent::now("agent-1", ApiAgentStatus::Busy);
+        let frame = serde_json::to_string(&event).unwrap();
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut socket = tokio_tungstenite::accept_async(stream).await.unwrap();
+            socket.send(Message::Text("not json".into())).await.unwrap();
+            socket.send(Message::Text(frame)).await.unwrap();
+            // Keep the socket open until the client is done
+            let _ = socket.next().await;
+        });
+
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let url = agent_events_url(&format!("http://{}", addr));
+        assert!(url.ends_with("/ws/agents"));
+        let task = tokio::spawn(stream_agent_events(url, None, tx));
+
+        let received = tokio::time::timeout(Duration::from_millis(500), rx.recv())
+            .await
+            .expect("event not received within 500ms")
+            .unwrap();
+        assert_eq!(received, event);
+        task.abort();
+    }
+
+    #[tokio::test]
+    async fn test_poll_api_reports_errors() {
+        // Nothing listens on the discard port
+        let client = AsyncApiClient::new("http://127.0.0.1:9").unwrap();
+        let (tx, mut rx) = watch::channel(None);
+        let (connection, _) = watch::channel(ConnectionStats::default());
+        let task = tokio::spawn(poll_api(
+            client,
+            Duration::from_secs(60),
+            tx,
+            connection,
+            Arc::new(Notify::new()),
+        ));
+
+        rx.changed().await.unwrap();
+        assert!(matches!(*rx.borrow(), Some(Err(_))));
+        task.abort();
+    }
+
+    #[tokio::test]
+    async fn test_poll_api_disconnects_after_failures() {
+        let client = AsyncApiClient::new("http://127.0.0.1:9").unwrap();
+        let (tx, _updates) = watch::channel(None);
+        let (connection, mut rx) = watch::channel(ConnectionStats::default());
+        let task = tokio::spawn(poll_api(
+            client,
+            Duration::from_millis(10),
+            tx,
+            connection,
+            Arc::new(Notify::new()),
+        ));
+
+        while rx.borrow_and_update().polls < u64::from(DISCONNECTED_AFTER_FAILURES) {
+            rx.changed().await.unwrap();
+        }
+        let stats = rx.borrow().clone();
+        assert_eq!(stats.failures, stats.polls);
+        assert!(matches!(stats.state, ConnectionState::Disconnected(_)));
+        assert_eq!(stats.last_success, None);
+        task.abort();
+    }
+
+    #[test]
+    fn test_connection_state_transitions() {
+        let mut stats = ConnectionStats::default();
+        assert!(matches!(stats.state, ConnectionState::Disconnected(_)));
+
+        // Failures before the first success leave it disconnected
+        stats.record_failure("connection refused");
+        assert_eq!(
+            stats.state,
+            ConnectionState::Disconnected("connection refused".to_string())
+        );
+
+        let now = Local::now();
+        stats.record_success(Duration::from_millis(120), now);
+        assert_eq!(stats.state, ConnectionState::Connected);
+        assert_eq!(stats.last_success, Some(now));
+        assert_eq!(stats.consecutive_failures, 0);
+
+        stats.record_success(Duration::from_millis(2500), now);
+        assert_eq!(
+            stats.state,
+            ConnectionState::Degraded("Slow response (2.5s)".to_string())
+        );
+
+        // A few failures degrade the connection, the third disconnects it
+        stats.record_failure("timed out\ncaused by: ...");
+        assert_eq!(
+            stats.state,
+            ConnectionState::Degraded("timed out".to_string())
+        );
+        stats.record_failure("timed out");
+        assert!(matches!(stats.state, ConnectionState::Degraded(_)));
+        stats.record_failure("timed out");
+        assert_eq!(
+            stats.state,
+            ConnectionState::Disconnected("timed out".to_string())
+        );
+        assert_eq!(stats.last_success, Some(now));
+
+        stats.record_success(Duration::from_millis(80), Local::now());
+        assert_eq!(stats.state, ConnectionState::Connected);
+        assert_eq!((stats.polls, stats.failures), (7, 4));
+    }
+
+    #[test]
+    fn test_latency_histogram() {
+        let mut stats = ConnectionStats::default();
+        assert_eq!(stats.average_latency(), None);
+        for millis in [100, 200, 300, 900, 1500, 3000] {
+            stats.record_success(Duration::from_millis(millis), Local::now());
+        }
+
+        assert_eq!(
+            stats.latency_histogram(),
+            vec![
+                ("<250ms".to_string(), 2),
+                ("<500ms".to_string(), 1),
+                ("<1s".to_string(), 1),
+                ("<2s".to_string(), 1),
+                (">2s".to_string(), 1),
+            ]
+        );
+        assert_eq!(stats.average_latency(), Some(Duration::from_secs(1)));
+
+        for _ in 0..LATENCY_HISTORY {
+            stats.record_success(Duration::from_millis(10), Local::now());
+        }
+        assert_eq!(stats.latencies.len(), LATENCY_HISTORY);
+    }
+}
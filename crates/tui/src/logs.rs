@@ -0,0 +1,389 @@
+//! Job log streaming for the Logs tab
+//!
+//! With a live data source the Logs tab streams the selected job's build
+//! output from `GET /ws/jobs/:id/logs` on raibid-server. A task on the polling
+//! runtime reads the WebSocket and forwards each frame on a channel;
+//! [`LogsState::receive`] drains it before every render. The upgrade request
+//! carries the configured API key like every other request.
+
+use futures::StreamExt;
+use raibid_common::auth::API_KEY_HEADER;
+use raibid_common::jobs::JobLogEntry;
+use tokio::runtime::Handle;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::handshake::client::Request;
+use tokio_tungstenite::tungstenite::http::HeaderValue;
+use tokio_tungstenite::tungstenite::Message;
+
+use super::mock_data::{LogLevel, MockLogEntry};
+
+/// Something that happened on a job's log stream
+#[derive(Debug, Clone, PartialEq)]
+pub enum LogStreamEvent {
+    /// A line of build output
+    Entry(JobLogEntry),
+    /// The server closed the stream, with its reason (e.g. `job success`)
+    Closed(String),
+    /// The stream failed
+    Error(String),
+}
+
+/// Scrollable log buffer of the Logs tab
+#[derive(Debug, Clone)]
+pub struct LogsState {
+    /// Index of the bottom visible line
+    pub scroll_offset: usize,
+    /// Buffered log lines, oldest first
+    pub logs: Vec<MockLogEntry>,
+    /// Keep the newest line in view as lines arrive
+    pub following: bool,
+    /// Job whose logs are buffered
+    pub job_id: Option<String>,
+    /// Why the stream ended, if it did
+    pub status: Option<String>,
+}
+
+impl Default for LogsState {
+    fn default() -> Self {
+        Self {
+            scroll_offset: 0,
+            logs: Vec::new(),
+            following: true,
+            job_id: None,
+            status: None,
+        }
+    }
+}
+
+impl LogsState {
+    /// Empty buffer for the logs of `job_id`, following new lines
+    pub fn for_job(job_id: impl Into<String>) -> Self {
+        Self {
+            job_id: Some(job_id.into()),
+            ..Default::default()
+        }
+    }
+
+    /// Append a line, scrolling to it when following
+    pub fn push(&mut self, entry: MockLogEntry) {
+        self.logs.push(entry);
+        if self.following {
+            self.scroll_offset = self.last_line();
+        }
+    }
+
+    /// Apply an event from the log stream
+    pub fn apply(&mut self, event: LogStreamEvent) {
+        match event {
+            LogStreamEvent::Entry(entry) => self.push(MockLogEntry::from(&entry)),
+            LogStreamEvent::Closed(reason) => self.status = Some(reason),
+            LogStreamEvent::Error(e) => self.status = Some(format!("error: {}", e)),
+        }
+    }
+
+    /// Apply every event waiting on `events`
+    pub fn receive(&mut self, events: &mut mpsc::UnboundedReceiver<LogStreamEvent>) {
+        while let Ok(event) = events.try_recv() {
+            self.apply(event);
+        }
+    }
+
+    /// Scroll one line towards older output, leaving follow mode
+    pub fn scroll_up(&mut self) {
+        self.following = false;
+        self.scroll_offset = self.scroll_offset.saturating_sub(1);
+    }
+
+    /// Scroll one line towards newer output
+    pub fn scroll_down(&mut self) {
+        self.scroll_offset = (self.scroll_offset + 1).min(self.last_line());
+    }
+
+    /// Scroll to the newest line
+    pub fn jump_to_end(&mut self) {
+        self.scroll_offset = self.last_line();
+    }
+
+    /// Toggle follow mode, scrolling to the newest line when turned on
+    pub fn toggle_follow(&mut self) {
+        self.following = !self.following;
+        if self.following {
+            self.jump_to_end();
+        }
+    }
+
+    /// Lines visible in a panel `height` lines tall
+    ///
+    /// The line at the scroll offset is at the bottom of the panel unless
+    /// there are fewer lines above it than fit.
+    pub fn visible(&self, height: usize) -> &[MockLogEntry] {
+        let end = (self.scroll_offset + 1).min(self.logs.len());
+        let start = end.saturating_sub(height);
+        let end = (start + height).min(self.logs.len());
+        &self.logs[start..end]
+    }
+
+    fn last_line(&self) -> usize {
+        self.logs.len().saturating_sub(1)
+    }
+}
+
+impl From<&JobLogEntry> for MockLogEntry {
+    fn from(entry: &JobLogEntry) -> Self {
+        // Build output has no levels; pick out rustc and cargo diagnostics
+        let level = if entry.message.starts_with("error") {
+            LogLevel::Error
+        } else if entry.message.starts_with("warning") {
+            LogLevel::Warn
+        } else {
+            LogLevel::Info
+        };
+
+        Self {
+            timestamp: entry.timestamp,
+            level,
+            message: entry.message.clone(),
+        }
+    }
+}
+
+/// WebSocket URL streaming the logs of `job_id` from the server at `base_url`
+pub fn job_logs_url(base_url: &str, job_id: &str) -> String {
+    websocket_url(base_url, &format!("/ws/jobs/{}/logs", job_id))
+}
+
+/// WebSocket URL of `path` on the server at `base_url`
+pub fn websocket_url(base_url: &str, path: &str) -> String {
+    let base = base_url.trim_end_matches('/');
+    let base = match base.split_once("://") {
+        Some(("https", rest)) => format!("wss://{}", rest),
+        Some((_, rest)) => format!("ws://{}", rest),
+        None => format!("ws://{}", base),
+    };
+    format!("{}{}", base, path)
+}
+
+/// Upgrade request for the WebSocket at `url`, sending `api_key` if given
+pub fn websocket_request(url: &str, api_key: Option<&str>) -> anyhow::Result<Request> {
+    let mut request = url.into_client_request()?;
+    if let Some(key) = api_key {
+        request
+            .headers_mut()
+            .insert(API_KEY_HEADER, HeaderValue::from_str(key)?);
+    }
+    Ok(request)
+}
+
+/// Read the log stream at `url`, forwarding every event until the stream
+/// ends or `events` is dropped
+pub async fn stream_job_logs(
+    url: String,
+    api_key: Option<String>,
+    events: mpsc::UnboundedSender<LogStreamEvent>,
+) {
+    let connected = match websocket_request(&url, api_key.as_deref()) {
+        Ok(request) => tokio_tungstenite::connect_async(request)
+            .await
+            .map_err(Into::into),
+        Err(e) => Err(e),
+    };
+    let mut socket = match connected {
+        Ok((socket, _)) => socket,
+        Err(e) => {
+            let _ = events.send(LogStreamEvent::Error(e.to_string()));
+            return;
+        }
+    };
+
+    while let Some(message) = socket.next().await {
+        let event = match message {
+            Ok(Message::Text(text)) => match serde_json::from_str(&text) {
+                Ok(entry) => LogStreamEvent::Entry(entry),
+                Err(e) => LogStreamEvent::Error(format!("invalid log frame: {}", e)),
+            },
+            Ok(Message::Close(frame)) => LogStreamEvent::Closed(
+                frame
+                    .map(|f| f.reason.to_string())
+                    .unwrap_or_else(|| "closed".to_string()),
+            ),
+            Ok(_) => continue,
+            Err(e) => LogStreamEvent::Error(e.to_string()),
+        };
+
+        let done = !matches!(event, LogStreamEvent::Entry(_));
+        if events.send(event).is_err() || done {
+            return;
+        }
+    }
+}
+
+/// A log stream running on the polling runtime
+///
+/// Dropping it stops the stream.
+pub struct LogStream {
+    pub events: mpsc::UnboundedReceiver<LogStreamEvent>,
+    task: JoinHandle<()>,
+}
+
+impl LogStream {
+    /// Start streaming from `url` on `runtime`, authenticated with `api_key`
+    pub fn spawn(runtime: &Handle, url: String, api_key: Option<String>) -> Self {
+        let (tx, events) = mpsc::unbounded_channel();
+        Self {
+            events,
+            task: runtime.spawn(stream_job_logs(url, api_key, tx)),
+        }
+    }
+}
+
+impl Drop for LogStream {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use futures::SinkExt;
+    use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+    use tokio_tungstenite::tungstenite::protocol::CloseFrame;
+
+    fn entry(message: &str) -> JobLogEntry {
+        JobLogEntry {
+            timestamp: Utc::now(),
+            message: message.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_scroll_offset_follows_stream() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let mut state = LogsState::for_job("job-1");
+
+        for i in 0..5 {
+            tx.send(LogStreamEvent::Entry(entry(&format!("line {}", i))))
+                .unwrap();
+        }
+        state.receive(&mut rx);
+        assert_eq!(state.logs.len(), 5);
+        assert_eq!(state.scroll_offset, 4);
+
+        // Scrolling back stops following; new lines don't move the view
+        state.scroll_up();
+        state.scroll_up();
+        assert!(!state.following);
+        assert_eq!(state.scroll_offset, 2);
+        tx.send(LogStreamEvent::Entry(entry("line 5"))).unwrap();
+        state.receive(&mut rx);
+        assert_eq!(state.scroll_offset, 2);
+
+        state.jump_to_end();
+        assert_eq!(state.scroll_offset, 5);
+        assert!(!state.following);
+
+        state.toggle_follow();
+        for i in 6..9 {
+            tx.send(LogStreamEvent::Entry(entry(&format!("line {}", i))))
+                .unwrap();
+        }
+        tx.send(LogStreamEvent::Closed("job success".to_string()))
+            .unwrap();
+        state.receive(&mut rx);
+        assert_eq!(state.scroll_offset, 8);
+        assert_eq!(state.status.as_deref(), Some("job success"));
+
+        // Scrolling down stops at the newest line
+        state.scroll_down();
+        assert_eq!(state.scroll_offset, 8);
+    }
+
+    #[test]
+    fn test_visible_window() {
+        let mut state = LogsState::for_job("job-1");
+        for i in 0..10 {
+            state.apply(LogStreamEvent::Entry(entry(&format!("line {}", i))));
+        }
+
+        let messages =
+            |lines: &[MockLogEntry]| lines.iter().map(|l| l.message.clone()).collect::<Vec<_>>();
+        assert_eq!(messages(state.visible(3)), ["line 7", "line 8", "line 9"]);
+
+        state.scroll_offset = 1;
+        assert_eq!(messages(state.visible(3)), ["line 0", "line 1", "line 2"]);
+        assert!(LogsState::default().visible(3).is_empty());
+    }
+
+    #[test]
+    fn test_level_from_message() {
+        let level = |message| MockLogEntry::from(&entry(message)).level;
+        assert_eq!(level("error[E0425]: cannot find value"), LogLevel::Error);
+        assert_eq!(level("warning: unused variable"), LogLevel::Warn);
+        assert_eq!(level("   Compiling raibid-cli v0.1.0"), LogLevel::Info);
+    }
+
+    #[test]
+    fn test_job_logs_url() {
+        assert_eq!(
+            job_logs_url("http://127.0.0.1:8080/", "job-1"),
+            "ws://127.0.0.1:8080/ws/jobs/job-1/logs"
+        );
+        assert_eq!(
+            job_logs_url("https://ci.example.com", "job-1"),
+            "wss://ci.example.com/ws/jobs/job-1/logs"
+        );
+    }
+
+    #[test]
+    fn test_websocket_request_sends_api_key() {
+        let url = "ws://127.0.0.1:8080/ws/agents";
+        let request = websocket_request(url, Some("rbd_secret")).unwrap();
+        assert_eq!(request.headers()[API_KEY_HEADER], "rbd_secret");
+
+        let request = websocket_request(url, None).unwrap();
+        assert!(!request.headers().contains_key(API_KEY_HEADER));
+        assert!(websocket_request(url, Some("bad\nkey")).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_stream_job_logs() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut socket = tokio_tungstenite::accept_async(stream).await.unwrap();
+            for message in ["Compiling", "Finished"] {
+                let frame = serde_json::to_string(&entry(message)).unwrap();
+                socket.send(Message::Text(frame)).await.unwrap();
+            }
+            socket
+                .send(Message::Close(Some(CloseFrame {
+                    code: CloseCode::Normal,
+                    reason: "job success".into(),
+                })))
+                .await
+                .unwrap();
+        });
+
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let url = job_logs_url(&format!("http://{}", addr), "job-1");
+        stream_job_logs(url, None, tx).await;
+
+        let mut state = LogsState::for_job("job-1");
+        state.receive(&mut rx);
+        let messages: Vec<_> = state.logs.iter().map(|l| l.message.as_str()).collect();
+        assert_eq!(messages, ["Compiling", "Finished"]);
+        assert_eq!(state.status.as_deref(), Some("job success"));
+    }
+
+    #[tokio::test]
+    async fn test_stream_connect_error() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        // Nothing listens on the discard port
+        stream_job_logs(job_logs_url("http://127.0.0.1:9", "job-1"), None, tx).await;
+        assert!(matches!(rx.try_recv(), Ok(LogStreamEvent::Error(_))));
+    }
+}
@@ -4,8 +4,10 @@
 //! CI/CD job execution, agent status, and queue metrics.
 
 use chrono::{DateTime, Duration, Utc};
+use raibid_common::jobs::{ErrorLogEntry, QueueStats};
 use rand::Rng;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
 /// Job execution status
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -59,6 +61,9 @@ pub struct MockJob {
     pub start_time: DateTime<Utc>,
     /// Job duration in seconds (if completed)
     pub duration: Option<u64>,
+    /// Labels, e.g. `source=gitlab`
+    #[serde(default)]
+    pub labels: HashMap<String, String>,
 }
 
 impl MockJob {
@@ -120,6 +125,13 @@ impl MockJob {
         let start_offset = rng.gen_range(0..3600);
         let start_time = Utc::now() - Duration::seconds(start_offset);
 
+        let sources = [("gitlab", "push"), ("bitbucket", "push"), ("api", "manual")];
+        let (source, event) = sources[rng.gen_range(0..sources.len())];
+        let labels = HashMap::from([
+            ("source".to_string(), source.to_string()),
+            ("event".to_string(), event.to_string()),
+        ]);
+
         Self {
             id: format!("job-{}", rng.gen_range(1000..9999)),
             repo: repos[rng.gen_range(0..repos.len())].to_string(),
@@ -128,6 +140,7 @@ impl MockJob {
             progress,
             start_time,
             duration,
+            labels,
         }
     }
 }
@@ -205,7 +218,7 @@ impl MockAgent {
 }
 
 /// Mock queue depth data for sparkline visualization
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct MockQueueData {
     /// Historical queue depth values (60 data points for 1 minute of history)
     pub history: Vec<u64>,
@@ -243,6 +256,16 @@ impl MockQueueData {
         self.history.push(new_value);
         self.current = new_value;
     }
+
+    /// Record a sample from the server's `GET /api/queue/stats` endpoint
+    pub fn record_stats(&mut self, stats: &QueueStats) {
+        if self.history.len() >= 60 {
+            self.history.remove(0);
+        }
+
+        self.current = stats.depth();
+        self.history.push(self.current);
+    }
 }
 
 /// Configuration for mock data generation
@@ -467,37 +490,49 @@ impl MockJobLogs {
     }
 }
 
-/// Generate system logs for the Logs tab
-pub fn generate_system_logs() -> Vec<MockLogEntry> {
-    let mut logs = Vec::new();
-    let mut current_time = Utc::now() - Duration::minutes(30);
-
-    let log_messages = [
-        (LogLevel::Info, "Redis connection pool initialized"),
-        (LogLevel::Info, "Flux sync completed: 3 deployments updated"),
-        (LogLevel::Info, "KEDA autoscaler triggered: scaling to 5 agents"),
-        (LogLevel::Error, "Job job-4518 failed: build timeout exceeded"),
-        (LogLevel::Info, "Job job-4521 dispatched to agent dgx-agent-002"),
-        (LogLevel::Warn, "High queue depth detected: 15 jobs pending"),
-        (LogLevel::Info, "Job job-4523 completed successfully"),
-        (LogLevel::Info, "Agent dgx-agent-001 started successfully"),
-        (LogLevel::Info, "Agent dgx-agent-003 idle for 300s, scheduling shutdown"),
-        (LogLevel::Warn, "Memory usage high on dgx-agent-002: 85%"),
-        (LogLevel::Info, "Cache hit rate: 78% (last hour)"),
-        (LogLevel::Info, "Repository sync: raibid-cli updated"),
+/// Generate recent error log entries for the given jobs
+///
+/// Failed jobs are used first so the entries point at jobs shown in the
+/// dashboard. Entries are ordered most recent first.
+pub fn generate_recent_errors(jobs: &[MockJob], limit: usize) -> Vec<ErrorLogEntry> {
+    let errors = [
+        (
+            "clippy",
+            "error: this `if` has identical blocks\n  --> src/lib.rs:88:5",
+        ),
+        (
+            "test",
+            "test result: FAILED. 243 passed; 2 failed; 0 ignored",
+        ),
+        (
+            "build",
+            "error[E0425]: cannot find value `undefined_var` in this scope\n  --> src/commands/status.rs:42:13",
+        ),
+        ("clone", "fatal: could not read Username for 'https://github.com'"),
+        ("fmt", "Diff in src/main.rs at line 12"),
     ];
 
-    for (level, message) in log_messages.iter() {
-        current_time = current_time + Duration::minutes(2) + Duration::seconds(15);
-        logs.push(MockLogEntry {
-            timestamp: current_time,
-            level: *level,
-            message: message.to_string(),
-        });
-    }
-
-    logs.reverse(); // Most recent first
-    logs
+    let mut failed: Vec<&MockJob> = jobs
+        .iter()
+        .filter(|j| j.status == JobStatus::Failed)
+        .collect();
+    failed.extend(jobs.iter().filter(|j| j.status != JobStatus::Failed));
+
+    let mut timestamp = Utc::now();
+    failed
+        .iter()
+        .zip(errors.iter().cycle())
+        .take(limit)
+        .map(|(job, (step, message))| {
+            timestamp -= Duration::minutes(3);
+            ErrorLogEntry {
+                timestamp,
+                job_id: job.id.clone(),
+                step: step.to_string(),
+                message: message.to_string(),
+            }
+        })
+        .collect()
 }
 
 #[cfg(test)]
@@ -555,6 +590,23 @@ mod tests {
         assert!(queue_data.current <= 30);
     }
 
+    #[test]
+    fn test_queue_data_record_stats() {
+        let mut rng = rand::thread_rng();
+        let mut queue = MockQueueData::random(&mut rng);
+
+        let stats = QueueStats {
+            pending_count: 3,
+            lag: Some(4),
+            ..Default::default()
+        };
+        queue.record_stats(&stats);
+
+        assert_eq!(queue.current, 7);
+        assert_eq!(queue.history.len(), 60);
+        assert_eq!(*queue.history.last().unwrap(), 7);
+    }
+
     #[test]
     fn test_generate_mock_data() {
         let config = MockDataConfig::default();
@@ -563,4 +615,15 @@ mod tests {
         assert_eq!(jobs.len(), config.job_count);
         assert_eq!(agents.len(), config.agent_count);
     }
+
+    #[test]
+    fn test_generate_recent_errors() {
+        let mut rng = rand::thread_rng();
+        let jobs: Vec<MockJob> = (0..30).map(|_| MockJob::random(&mut rng)).collect();
+
+        let errors = generate_recent_errors(&jobs, 20);
+        assert_eq!(errors.len(), 20);
+        assert!(errors.iter().all(|e| jobs.iter().any(|j| j.id == e.job_id)));
+        assert!(errors.windows(2).all(|w| w[0].timestamp > w[1].timestamp));
+    }
 }
@@ -0,0 +1,247 @@
+//! Command palette
+//!
+//! `:` opens a popup listing every action of the TUI. Typing narrows the
+//! list with a fuzzy match on the action names, Enter runs the selected
+//! action and Esc closes the palette.
+
+use super::app::{App, Tab};
+
+/// An action that can be run from the command palette
+#[derive(Clone, Copy)]
+pub struct PaletteAction {
+    /// Name shown and matched in the palette
+    pub name: &'static str,
+    /// Key running the action outside the palette, empty if there is none
+    pub shortcut: &'static str,
+    /// Run the action
+    pub action_fn: fn(&mut App),
+}
+
+/// Every action of the command palette, in the order listed
+pub const PALETTE_ACTIONS: &[PaletteAction] = &[
+    PaletteAction {
+        name: "Cancel Job",
+        shortcut: "c",
+        action_fn: App::show_cancel_confirmation,
+    },
+    PaletteAction {
+        name: "Retry Job",
+        shortcut: "",
+        action_fn: App::retry_selected_job,
+    },
+    PaletteAction {
+        name: "Trigger Build",
+        shortcut: "",
+        action_fn: App::trigger_build,
+    },
+    PaletteAction {
+        name: "View Logs",
+        shortcut: "4",
+        action_fn: |app| app.select_tab(Tab::Logs),
+    },
+    PaletteAction {
+        name: "Switch to Jobs Tab",
+        shortcut: "1",
+        action_fn: |app| app.select_tab(Tab::Jobs),
+    },
+    PaletteAction {
+        name: "Switch to Agents Tab",
+        shortcut: "2",
+        action_fn: |app| app.select_tab(Tab::Agents),
+    },
+    PaletteAction {
+        name: "Switch to Config Tab",
+        shortcut: "3",
+        action_fn: |app| app.select_tab(Tab::Config),
+    },
+    PaletteAction {
+        name: "Switch to Logs Tab",
+        shortcut: "4",
+        action_fn: |app| app.select_tab(Tab::Logs),
+    },
+    PaletteAction {
+        name: "Show Recent Errors",
+        shortcut: "e",
+        action_fn: App::toggle_recent_errors,
+    },
+    PaletteAction {
+        name: "Show Connection Stats",
+        shortcut: "Ctrl+I",
+        action_fn: App::toggle_connection_stats,
+    },
+    PaletteAction {
+        name: "Refresh",
+        shortcut: "r",
+        action_fn: App::refresh,
+    },
+    PaletteAction {
+        name: "Open Help",
+        shortcut: "?",
+        action_fn: App::toggle_help,
+    },
+    PaletteAction {
+        name: "Quit",
+        shortcut: "q",
+        action_fn: App::quit,
+    },
+];
+
+/// Query and selection of the open command palette
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PaletteState {
+    /// Text typed so far
+    pub query: String,
+    /// Index of the selected entry in [`PaletteState::matches`]
+    pub selected: usize,
+}
+
+impl PaletteState {
+    /// Actions matching the query, best match first
+    pub fn matches(&self) -> Vec<&'static PaletteAction> {
+        filter_actions(PALETTE_ACTIONS, &self.query)
+    }
+
+    /// The action Enter would run
+    pub fn selected_action(&self) -> Option<&'static PaletteAction> {
+        self.matches().get(self.selected).copied()
+    }
+
+    /// Add a character to the query, selecting the best match
+    pub fn push(&mut self, c: char) {
+        self.query.push(c);
+        self.selected = 0;
+    }
+
+    /// Remove the last character of the query, selecting the best match
+    pub fn backspace(&mut self) {
+        self.query.pop();
+        self.selected = 0;
+    }
+
+    /// Select the previous match
+    pub fn select_previous(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+
+    /// Select the next match
+    pub fn select_next(&mut self) {
+        if self.selected + 1 < self.matches().len() {
+            self.selected += 1;
+        }
+    }
+}
+
+/// Actions whose names fuzzy-match `query`, best match first
+///
+/// An empty query matches every action in its original order.
+pub fn filter_actions<'a>(actions: &'a [PaletteAction], query: &str) -> Vec<&'a PaletteAction> {
+    let mut matches: Vec<(usize, &PaletteAction)> = actions
+        .iter()
+        .filter_map(|action| fuzzy_score(query, action.name).map(|score| (score, action)))
+        .collect();
+    // Stable, so equally good matches keep their order
+    matches.sort_by_key(|(score, _)| *score);
+    matches.into_iter().map(|(_, action)| action).collect()
+}
+
+/// How well `query` matches `name`, lower is better; `None` if it doesn't
+///
+/// Case is ignored. A query contained in the name scores by where it
+/// starts; otherwise the query's characters must appear in the name in
+/// order, and the characters skipped between them add to the score.
+fn fuzzy_score(query: &str, name: &str) -> Option<usize> {
+    let query = query.to_lowercase();
+    let name = name.to_lowercase();
+    if let Some(start) = name.find(&query) {
+        return Some(start);
+    }
+
+    let mut chars = name.chars();
+    let mut skipped = 0;
+    for wanted in query.chars().filter(|c| !c.is_whitespace()) {
+        loop {
+            match chars.next() {
+                Some(c) if c == wanted => break,
+                Some(_) => skipped += 1,
+                None => return None,
+            }
+        }
+    }
+    // Rank below every substring match
+    Some(name.len() + skipped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(actions: Vec<&PaletteAction>) -> Vec<&'static str> {
+        actions.into_iter().map(|action| action.name).collect()
+    }
+
+    #[test]
+    fn test_fuzzy_score() {
+        assert_eq!(fuzzy_score("", "Quit"), Some(0));
+        assert_eq!(fuzzy_score("job", "Cancel Job"), Some(7));
+        assert_eq!(fuzzy_score("JOB", "cancel job"), Some(7));
+        assert!(fuzzy_score("cj", "Cancel Job").unwrap() > "Cancel Job".len());
+        assert_eq!(fuzzy_score("jc", "Cancel Job"), None);
+        assert_eq!(fuzzy_score("deploy", "Quit"), None);
+    }
+
+    #[test]
+    fn test_filter_actions() {
+        assert_eq!(
+            filter_actions(PALETTE_ACTIONS, "").len(),
+            PALETTE_ACTIONS.len()
+        );
+
+        assert_eq!(
+            names(filter_actions(PALETTE_ACTIONS, "job")),
+            vec!["Retry Job", "Cancel Job", "Switch to Jobs Tab"]
+        );
+        assert_eq!(
+            names(filter_actions(PALETTE_ACTIONS, "logs")),
+            vec!["View Logs", "Switch to Logs Tab"]
+        );
+        // Substring matches rank above scattered ones
+        assert_eq!(
+            names(filter_actions(PALETTE_ACTIONS, "re")),
+            vec![
+                "Retry Job",
+                "Refresh",
+                "Show Recent Errors",
+                "Trigger Build"
+            ]
+        );
+        assert_eq!(
+            names(filter_actions(PALETTE_ACTIONS, "swag")),
+            vec!["Switch to Agents Tab"]
+        );
+        assert!(filter_actions(PALETTE_ACTIONS, "deploy").is_empty());
+    }
+
+    #[test]
+    fn test_palette_selection() {
+        let mut palette = PaletteState::default();
+        palette.select_previous();
+        assert_eq!(palette.selected, 0);
+
+        palette.push('l');
+        palette.push('o');
+        palette.push('g');
+        assert_eq!(palette.selected_action().unwrap().name, "View Logs");
+
+        palette.select_next();
+        palette.select_next();
+        assert_eq!(palette.selected, 1);
+        assert_eq!(
+            palette.selected_action().unwrap().name,
+            "Switch to Logs Tab"
+        );
+
+        palette.backspace();
+        assert_eq!(palette.query, "lo");
+        assert_eq!(palette.selected, 0);
+    }
+}
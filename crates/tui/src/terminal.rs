@@ -4,6 +4,7 @@
 
 use anyhow::{Context, Result};
 use crossterm::{
+    event::{DisableMouseCapture, EnableMouseCapture},
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
@@ -23,6 +24,7 @@ pub type Terminal = RatatuiTerminal<CrosstermBackend<Stdout>>;
 /// - Checks minimum terminal size
 /// - Enables raw mode
 /// - Enters alternate screen
+/// - Captures mouse clicks and wheel scrolling
 /// - Creates and returns a configured terminal
 pub fn init() -> Result<Terminal> {
     // Check terminal size
@@ -44,6 +46,9 @@ pub fn init() -> Result<Terminal> {
     // Enter alternate screen
     execute!(io::stdout(), EnterAlternateScreen).context("Failed to enter alternate screen")?;
 
+    // Capture mouse events
+    execute!(io::stdout(), EnableMouseCapture).context("Failed to enable mouse capture")?;
+
     // Create terminal
     let backend = CrosstermBackend::new(io::stdout());
     let terminal = RatatuiTerminal::new(backend).context("Failed to create terminal")?;
@@ -54,9 +59,13 @@ pub fn init() -> Result<Terminal> {
 /// Restore the terminal to its original state
 ///
 /// This function:
+/// - Releases the mouse
 /// - Leaves alternate screen
 /// - Disables raw mode
 pub fn restore() -> Result<()> {
+    // Release the mouse
+    execute!(io::stdout(), DisableMouseCapture).context("Failed to disable mouse capture")?;
+
     // Leave alternate screen
     execute!(io::stdout(), LeaveAlternateScreen).context("Failed to leave alternate screen")?;
 
@@ -3,19 +3,30 @@
 //! This module contains the rendering logic for the 3-panel dashboard.
 
 use chrono::Local;
+use std::rc::Rc;
 use ratatui::{
     layout::{Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
     text::{Line, Span},
-    widgets::{Block, Borders, Cell, List, ListItem, Paragraph, Row, Sparkline, Table, Tabs},
+    widgets::{
+        BarChart, Block, Borders, Cell, Clear, List, ListItem, ListState, Paragraph, Row,
+        Sparkline, Table, Tabs,
+    },
     Frame,
 };
 
-use super::app::{InputMode, Tab, UiState};
+use raibid_common::dependencies::OutdatedReport;
+use raibid_common::test_report::TestReport;
+
+use super::app::{InputMode, Panel, Tab, UiState, STATUS_FILTER_OPTIONS};
+use super::highlight::RustDiagnosticHighlighter;
+use super::layout::PanelWidths;
+use super::live::{ConnectionState, ConnectionStats};
+use super::logs::LogsState;
 use super::mock_data::{
-    generate_system_logs, AgentStatus, JobStatus, LogLevel, MockAgent, MockJob, MockJobLogs,
-    MockQueueData,
+    AgentStatus, JobStatus, LogLevel, MockAgent, MockJob, MockJobLogs, MockQueueData,
 };
+use super::palette::PaletteState;
 
 /// Main render function for the dashboard
 #[allow(clippy::too_many_arguments)]
@@ -32,18 +43,15 @@ pub fn render(
     let size = frame.size();
 
     // Create main layout with header, tabs, content, and footer
-    let main_chunks = Layout::default()
-        .direction(Direction::Vertical)
-        .constraints([
-            Constraint::Length(3), // Header
-            Constraint::Length(3), // Tabs
-            Constraint::Min(0),    // Content
-            Constraint::Length(3), // Footer
-        ])
-        .split(size);
+    let main_chunks = main_layout(size);
 
     // Render header
-    render_header(frame, main_chunks[0]);
+    render_header(
+        frame,
+        main_chunks[0],
+        ui_state.connection_error,
+        ui_state.connection,
+    );
 
     // Render tabs
     render_tabs(frame, main_chunks[1], current_tab);
@@ -57,22 +65,38 @@ pub fn render(
             agents,
             queue_data,
             selected_job,
+            ui_state.focused_panel,
+            ui_state.panel_widths,
         ),
         Tab::Agents => render_agents_tab(frame, main_chunks[2], agents, selected_agent),
         Tab::Config => render_config_tab(frame, main_chunks[2]),
-        Tab::Logs => render_logs_tab(frame, main_chunks[2]),
+        Tab::Logs => render_logs_tab(frame, main_chunks[2], ui_state.logs),
     }
 
     // Render footer
     render_footer(frame, main_chunks[3], ui_state);
 
     // Render overlays (popups, help screen, etc.)
-    if ui_state.show_help {
+    if ui_state.input_mode == InputMode::CommandPalette {
+        render_command_palette(frame, size, ui_state.palette);
+    } else if ui_state.show_help {
         render_help_screen(frame, size);
     } else if ui_state.show_detail_popup {
         if let Some(job) = jobs.get(selected_job) {
-            render_job_detail_popup(frame, size, job);
+            render_job_detail_popup(
+                frame,
+                size,
+                job,
+                ui_state.test_report,
+                ui_state.dependencies,
+            );
         }
+    } else if ui_state.show_connection_stats {
+        if let Some(connection) = ui_state.connection {
+            render_connection_stats_popup(frame, size, connection);
+        }
+    } else if ui_state.show_recent_errors {
+        render_recent_errors_popup(frame, size, ui_state);
     } else if ui_state.show_filter_menu {
         render_filter_menu(frame, size, ui_state);
     } else if ui_state.show_confirmation {
@@ -80,17 +104,103 @@ pub fn render(
     }
 }
 
+/// Split the screen into header, tabs, content, and footer
+fn main_layout(size: Rect) -> Rc<[Rect]> {
+    Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(3), // Header
+            Constraint::Length(3), // Tabs
+            Constraint::Min(0),    // Content
+            Constraint::Length(3), // Footer
+        ])
+        .split(size)
+}
+
+/// Split the Jobs tab content into the jobs, agents, and queue panels
+fn jobs_tab_layout(area: Rect, widths: PanelWidths) -> Rc<[Rect]> {
+    Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints(widths.as_array().map(Constraint::Percentage))
+        .split(area)
+}
+
+/// Panel of the Jobs tab at a screen position, for mouse clicks
+pub fn panel_at(size: Rect, widths: PanelWidths, column: u16, row: u16) -> Option<Panel> {
+    let panels = jobs_tab_layout(main_layout(size)[2], widths);
+    Panel::all().into_iter().zip(panels.iter()).find_map(|(panel, area)| {
+        let inside = (area.left()..area.right()).contains(&column)
+            && (area.top()..area.bottom()).contains(&row);
+        inside.then_some(panel)
+    })
+}
+
+/// Border between Jobs tab panels at a screen position, for mouse drags
+///
+/// Returns 0 for the border between the jobs and agents panels and 1 for
+/// the one between the agents and queue panels, matching either panel's edge.
+pub fn border_at(size: Rect, widths: PanelWidths, column: u16, row: u16) -> Option<usize> {
+    let content = main_layout(size)[2];
+    if !(content.top()..content.bottom()).contains(&row) {
+        return None;
+    }
+
+    let panels = jobs_tab_layout(content, widths);
+    panels.windows(2).position(|pair| {
+        let (left, right) = (pair[0], pair[1]);
+        left.width > 0 && (column == left.right() - 1 || column == right.left())
+    })
+}
+
+/// Position of a screen column in the Jobs tab content, in percent of its width
+pub fn content_percent(size: Rect, column: u16) -> u16 {
+    let content = main_layout(size)[2];
+    if content.width == 0 {
+        return 0;
+    }
+    let offset = u32::from(column.saturating_sub(content.left()));
+    (offset * 100 / u32::from(content.width)).min(100) as u16
+}
+
+/// Border style of a Jobs tab panel, brighter when it has focus
+fn panel_border_style(focused: bool) -> Style {
+    if focused {
+        Style::default().fg(Color::White).add_modifier(Modifier::BOLD)
+    } else {
+        Style::default().fg(Color::Gray)
+    }
+}
+
 /// Render the header with title and system info
-fn render_header(frame: &mut Frame, area: Rect) {
+fn render_header(
+    frame: &mut Frame,
+    area: Rect,
+    connection_error: Option<&str>,
+    connection: Option<&ConnectionStats>,
+) {
     let now = Local::now();
     let time_str = now.format("%Y-%m-%d %H:%M:%S").to_string();
 
+    // Turn the header red while the API server is unreachable
+    let border_color = if connection_error.is_some() {
+        Color::Red
+    } else {
+        Color::Cyan
+    };
     let header = Block::default()
         .borders(Borders::ALL)
-        .border_style(Style::default().fg(Color::Cyan))
+        .border_style(Style::default().fg(border_color))
         .style(Style::default().bg(Color::Black));
 
-    let header_text = vec![Line::from(vec![
+    let status = match connection_error {
+        Some(error) => Span::styled(
+            format!("⚠ API unavailable: {}", error.lines().next().unwrap_or(error)),
+            Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+        ),
+        None => Span::styled("DGX Spark Agent Pool", Style::default().fg(Color::Gray)),
+    };
+
+    let mut spans = vec![
         Span::styled(
             " Raibid CI Dashboard ",
             Style::default()
@@ -98,10 +208,17 @@ fn render_header(frame: &mut Frame, area: Rect) {
                 .add_modifier(Modifier::BOLD),
         ),
         Span::raw(" | "),
-        Span::styled("DGX Spark Agent Pool", Style::default().fg(Color::Gray)),
+    ];
+    if let Some(connection) = connection {
+        spans.extend(connection_indicator_spans(connection));
+        spans.push(Span::raw(" | "));
+    }
+    spans.extend([
+        status,
         Span::raw(" | "),
         Span::styled(time_str, Style::default().fg(Color::Yellow)),
-    ])];
+    ]);
+    let header_text = vec![Line::from(spans)];
 
     let paragraph = ratatui::widgets::Paragraph::new(header_text)
         .block(header)
@@ -110,6 +227,32 @@ fn render_header(frame: &mut Frame, area: Rect) {
     frame.render_widget(paragraph, area);
 }
 
+/// Colored dot and name of the connection state
+fn connection_label(state: &ConnectionState) -> (Color, &'static str) {
+    match state {
+        ConnectionState::Connected => (Color::Green, "Connected"),
+        ConnectionState::Degraded(_) => (Color::Yellow, "Degraded"),
+        ConnectionState::Disconnected(_) => (Color::Red, "Disconnected"),
+    }
+}
+
+/// Connection state of a live source, with the time of the last successful
+/// poll
+fn connection_indicator_spans(connection: &ConnectionStats) -> Vec<Span<'static>> {
+    let (color, label) = connection_label(&connection.state);
+    let mut spans = vec![Span::styled(
+        format!("● {}", label),
+        Style::default().fg(color).add_modifier(Modifier::BOLD),
+    )];
+    if let Some(last_success) = connection.last_success {
+        spans.push(Span::styled(
+            format!(" (updated {})", last_success.format("%H:%M:%S")),
+            Style::default().fg(Color::Gray),
+        ));
+    }
+    spans
+}
+
 /// Render the tab bar
 fn render_tabs(frame: &mut Frame, area: Rect, current_tab: Tab) {
     let all_tabs = Tab::all();
@@ -169,6 +312,19 @@ fn render_footer(frame: &mut Frame, area: Rect, ui_state: &UiState) {
                 Span::raw(" Cancel"),
             ]);
         }
+        InputMode::CommandPalette => {
+            footer_spans.extend(vec![
+                Span::styled("Command: ", Style::default().fg(Color::Cyan)),
+                Span::raw(ui_state.palette.query.as_str()),
+                Span::raw(" | "),
+                Span::styled("Up/Down", Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)),
+                Span::raw(" Select | "),
+                Span::styled("Enter", Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)),
+                Span::raw(" Run | "),
+                Span::styled("Esc", Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)),
+                Span::raw(" Cancel"),
+            ]);
+        }
         InputMode::Normal => {
             if ui_state.show_confirmation {
                 footer_spans.extend(vec![
@@ -177,6 +333,17 @@ fn render_footer(frame: &mut Frame, area: Rect, ui_state: &UiState) {
                     Span::styled("N/Esc", Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)),
                     Span::raw(" Cancel"),
                 ]);
+            } else if ui_state.show_recent_errors {
+                footer_spans.extend(vec![
+                    Span::styled("↑/↓", Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)),
+                    Span::raw(" Navigate | "),
+                    Span::styled("Enter", Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)),
+                    Span::raw(" Go to job | "),
+                    Span::styled("r", Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)),
+                    Span::raw(" Refresh | "),
+                    Span::styled("Esc", Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)),
+                    Span::raw(" Close"),
+                ]);
             } else if ui_state.show_help || ui_state.show_detail_popup {
                 footer_spans.extend(vec![
                     Span::styled("Esc", Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)),
@@ -194,6 +361,10 @@ fn render_footer(frame: &mut Frame, area: Rect, ui_state: &UiState) {
                     Span::raw(" Filter | "),
                     Span::styled("/", Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)),
                     Span::raw(" Search | "),
+                    Span::styled("e", Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)),
+                    Span::raw(" Errors | "),
+                    Span::styled(":", Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)),
+                    Span::raw(" Commands | "),
                     Span::styled("?", Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)),
                     Span::raw(" Help | "),
                     Span::styled("q", Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)),
@@ -213,7 +384,7 @@ fn render_footer(frame: &mut Frame, area: Rect, ui_state: &UiState) {
 }
 
 /// Render the jobs panel with status table
-fn render_jobs_panel(frame: &mut Frame, area: Rect, jobs: &[MockJob]) {
+fn render_jobs_panel(frame: &mut Frame, area: Rect, jobs: &[MockJob], focused: bool) {
     let block = Block::default()
         .title(format!(" Jobs ({}) ", jobs.len()))
         .title_style(
@@ -222,7 +393,7 @@ fn render_jobs_panel(frame: &mut Frame, area: Rect, jobs: &[MockJob]) {
                 .add_modifier(Modifier::BOLD),
         )
         .borders(Borders::ALL)
-        .border_style(Style::default().fg(Color::White));
+        .border_style(panel_border_style(focused));
 
     // Create table header
     let header = Row::new(vec![
@@ -294,7 +465,7 @@ fn render_jobs_panel(frame: &mut Frame, area: Rect, jobs: &[MockJob]) {
 }
 
 /// Render the agents panel with agent list and resource usage
-fn render_agents_panel(frame: &mut Frame, area: Rect, agents: &[MockAgent]) {
+fn render_agents_panel(frame: &mut Frame, area: Rect, agents: &[MockAgent], focused: bool) {
     let block = Block::default()
         .title(format!(" Agents ({}) ", agents.len()))
         .title_style(
@@ -303,7 +474,7 @@ fn render_agents_panel(frame: &mut Frame, area: Rect, agents: &[MockAgent]) {
                 .add_modifier(Modifier::BOLD),
         )
         .borders(Borders::ALL)
-        .border_style(Style::default().fg(Color::White));
+        .border_style(panel_border_style(focused));
 
     let items: Vec<ListItem> = agents
         .iter()
@@ -361,9 +532,13 @@ fn render_agents_panel(frame: &mut Frame, area: Rect, agents: &[MockAgent]) {
 }
 
 /// Render the queue panel with sparkline chart
-#[allow(dead_code)]
-fn render_queue_panel(frame: &mut Frame, area: Rect, queue_data: &MockQueueData) {
-    let _block = Block::default()
+fn render_queue_panel(
+    frame: &mut Frame,
+    area: Rect,
+    queue_data: &MockQueueData,
+    focused: bool,
+) {
+    let block = Block::default()
         .title(format!(" Queue Depth ({}) ", queue_data.current))
         .title_style(
             Style::default()
@@ -371,7 +546,9 @@ fn render_queue_panel(frame: &mut Frame, area: Rect, queue_data: &MockQueueData)
                 .add_modifier(Modifier::BOLD),
         )
         .borders(Borders::ALL)
-        .border_style(Style::default().fg(Color::White));
+        .border_style(panel_border_style(focused));
+    let inner = block.inner(area);
+    frame.render_widget(block, area);
 
     // Split the queue panel into info and sparkline sections
     let chunks = Layout::default()
@@ -380,7 +557,7 @@ fn render_queue_panel(frame: &mut Frame, area: Rect, queue_data: &MockQueueData)
             Constraint::Min(3),     // Info section
             Constraint::Length(10), // Sparkline chart
         ])
-        .split(area);
+        .split(inner);
 
     // Render queue info
     let max_depth = *queue_data.history.iter().max().unwrap_or(&0);
@@ -502,28 +679,23 @@ fn render_jobs_tab(
     agents: &[MockAgent],
     queue_data: &MockQueueData,
     _selected: usize,
+    focused: Panel,
+    widths: PanelWidths,
 ) {
     // Create 3-panel layout for content
-    let content_chunks = Layout::default()
-        .direction(Direction::Horizontal)
-        .constraints([
-            Constraint::Percentage(60), // Jobs panel
-            Constraint::Percentage(20), // Agents panel
-            Constraint::Percentage(20), // Queue panel
-        ])
-        .split(area);
+    let content_chunks = jobs_tab_layout(area, widths);
 
     // Render panels
-    render_jobs_panel(frame, content_chunks[0], jobs);
-    render_agents_panel(frame, content_chunks[1], agents);
-    render_queue_panel(frame, content_chunks[2], queue_data);
+    render_jobs_panel(frame, content_chunks[0], jobs, focused == Panel::Jobs);
+    render_agents_panel(frame, content_chunks[1], agents, focused == Panel::Agents);
+    render_queue_panel(frame, content_chunks[2], queue_data, focused == Panel::Queue);
 }
 
 /// Render the Agents tab (detailed view)
 #[allow(dead_code)]
 fn render_agents_tab(frame: &mut Frame, area: Rect, agents: &[MockAgent], _selected: usize) {
     // For now, delegate to the agents panel implementation
-    render_agents_panel(frame, area, agents);
+    render_agents_panel(frame, area, agents, true);
 }
 
 /// Render the Config tab (placeholder)
@@ -638,22 +810,48 @@ fn render_config_tab(frame: &mut Frame, area: Rect) {
     frame.render_widget(paragraph, area);
 }
 
-/// Render the Logs tab with scrolling system logs
-#[allow(dead_code)]
-fn render_logs_tab(frame: &mut Frame, area: Rect) {
-    let block = Block::default()
-        .title(" System Logs (Use ↑/↓ to scroll) ")
-        .title_style(
+/// Render the Logs tab with the selected job's logs
+fn render_logs_tab(frame: &mut Frame, area: Rect, logs: &LogsState) {
+    let mut title = vec![Span::styled(
+        match logs.job_id {
+            Some(ref job_id) => format!(" Logs: {} ", job_id),
+            None => " Logs (select a job on the Jobs tab) ".to_string(),
+        },
+        Style::default()
+            .fg(Color::Cyan)
+            .add_modifier(Modifier::BOLD),
+    )];
+    if logs.following {
+        title.push(Span::styled(
+            "[LIVE] ",
             Style::default()
                 .fg(Color::Cyan)
                 .add_modifier(Modifier::BOLD),
-        )
+        ));
+    }
+    if let Some(ref status) = logs.status {
+        title.push(Span::styled(
+            format!("({}) ", status),
+            Style::default().fg(Color::Gray),
+        ));
+    }
+    title.push(Span::styled(
+        "j/k scroll, G end, F follow ",
+        Style::default().fg(Color::Gray),
+    ));
+
+    let block = Block::default()
+        .title(Line::from(title))
         .borders(Borders::ALL)
         .border_style(Style::default().fg(Color::White));
 
-    let system_logs = generate_system_logs();
+    // Highlighter state carries across entries so multi-line rustc
+    // diagnostics are recognised as a block
+    let mut highlighter = RustDiagnosticHighlighter::new();
 
-    let log_entries: Vec<Line> = system_logs
+    let height = area.height.saturating_sub(2) as usize;
+    let log_entries: Vec<Line> = logs
+        .visible(height)
         .iter()
         .map(|entry| {
             let level_color = match entry.level {
@@ -662,7 +860,7 @@ fn render_logs_tab(frame: &mut Frame, area: Rect) {
                 LogLevel::Error => Color::Red,
             };
 
-            Line::from(vec![
+            let mut spans = vec![
                 Span::styled(
                     format!("[{}]", entry.timestamp.format("%Y-%m-%d %H:%M:%S")),
                     Style::default().fg(Color::Gray),
@@ -673,8 +871,10 @@ fn render_logs_tab(frame: &mut Frame, area: Rect) {
                     Style::default().fg(level_color).add_modifier(Modifier::BOLD),
                 ),
                 Span::raw("  "),
-                Span::raw(&entry.message),
-            ])
+            ];
+            spans.extend(highlighter.highlight(&entry.message).spans);
+
+            Line::from(spans)
         })
         .collect();
 
@@ -747,6 +947,26 @@ fn render_help_screen(frame: &mut Frame, area: Rect) {
             Span::styled("  Up / Down", Style::default().fg(Color::Green)),
             Span::raw("            Navigate items in lists"),
         ]),
+        Line::from(vec![
+            Span::styled("  j / k", Style::default().fg(Color::Green)),
+            Span::raw("                Scroll logs (on Logs tab)"),
+        ]),
+        Line::from(vec![
+            Span::styled("  G / F", Style::default().fg(Color::Green)),
+            Span::raw("                Jump to newest log line / Toggle follow"),
+        ]),
+        Line::from(vec![
+            Span::styled("  Click", Style::default().fg(Color::Green)),
+            Span::raw("                 Focus a panel (on Jobs tab)"),
+        ]),
+        Line::from(vec![
+            Span::styled("  Mouse wheel", Style::default().fg(Color::Green)),
+            Span::raw("           Scroll the focused panel or logs"),
+        ]),
+        Line::from(vec![
+            Span::styled("  Drag border", Style::default().fg(Color::Green)),
+            Span::raw("           Resize panels (on Jobs tab)"),
+        ]),
         Line::from(""),
         Line::from(vec![Span::styled(
             "ACTIONS",
@@ -775,6 +995,14 @@ fn render_help_screen(frame: &mut Frame, area: Rect) {
             Span::styled("  /", Style::default().fg(Color::Green)),
             Span::raw("                     Search jobs (by repo/branch/ID)"),
         ]),
+        Line::from(vec![
+            Span::styled("  e", Style::default().fg(Color::Green)),
+            Span::raw("                     Show recent errors across all jobs"),
+        ]),
+        Line::from(vec![
+            Span::styled("  Ctrl+I", Style::default().fg(Color::Green)),
+            Span::raw("                Show API connection stats"),
+        ]),
         Line::from(vec![
             Span::styled("  Esc", Style::default().fg(Color::Green)),
             Span::raw("                   Close popup / Clear filters"),
@@ -787,6 +1015,10 @@ fn render_help_screen(frame: &mut Frame, area: Rect) {
                 .add_modifier(Modifier::BOLD | Modifier::UNDERLINED),
         )]),
         Line::from(""),
+        Line::from(vec![
+            Span::styled("  :", Style::default().fg(Color::Green)),
+            Span::raw("                     Open the command palette"),
+        ]),
         Line::from(vec![
             Span::styled("  ?", Style::default().fg(Color::Green)),
             Span::raw("                     Show this help screen"),
@@ -824,8 +1056,76 @@ fn render_help_screen(frame: &mut Frame, area: Rect) {
     frame.render_widget(paragraph, popup_area);
 }
 
+/// Render the command palette: the query and the actions matching it
+fn render_command_palette(frame: &mut Frame, area: Rect, palette: &PaletteState) {
+    let popup_area = centered_rect(60, 50, area);
+
+    let block = Block::default()
+        .title(" Command Palette ")
+        .title_style(
+            Style::default()
+                .fg(Color::Cyan)
+                .add_modifier(Modifier::BOLD),
+        )
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Cyan))
+        .style(Style::default().bg(Color::Black));
+    let inner = block.inner(popup_area);
+    frame.render_widget(Clear, popup_area);
+    frame.render_widget(block, popup_area);
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(2), Constraint::Min(0)])
+        .split(inner);
+
+    let input = Paragraph::new(Line::from(vec![
+        Span::styled("> ", Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)),
+        Span::raw(palette.query.as_str()),
+        Span::styled("_", Style::default().fg(Color::DarkGray)),
+    ]))
+    .block(Block::default().borders(Borders::BOTTOM).border_style(Style::default().fg(Color::DarkGray)));
+    frame.render_widget(input, chunks[0]);
+
+    let matches = palette.matches();
+    if matches.is_empty() {
+        let empty = Paragraph::new(Span::styled(
+            "  No matching commands",
+            Style::default().fg(Color::DarkGray),
+        ));
+        frame.render_widget(empty, chunks[1]);
+        return;
+    }
+
+    let items: Vec<ListItem> = matches
+        .iter()
+        .map(|action| {
+            ListItem::new(Line::from(vec![
+                Span::raw(format!("  {:<24}", action.name)),
+                Span::styled(action.shortcut, Style::default().fg(Color::Green)),
+            ]))
+        })
+        .collect();
+    let list = List::new(items).highlight_style(
+        Style::default()
+            .fg(Color::Black)
+            .bg(Color::Cyan)
+            .add_modifier(Modifier::BOLD),
+    );
+
+    let mut state = ListState::default();
+    state.select(Some(palette.selected));
+    frame.render_stateful_widget(list, chunks[1], &mut state);
+}
+
 /// Render job detail popup
-fn render_job_detail_popup(frame: &mut Frame, area: Rect, job: &MockJob) {
+fn render_job_detail_popup(
+    frame: &mut Frame,
+    area: Rect,
+    job: &MockJob,
+    test_report: Option<&Result<TestReport, String>>,
+    dependencies: Option<&Result<OutdatedReport, String>>,
+) {
     let popup_area = centered_rect(90, 85, area);
 
     // Clear the background
@@ -876,7 +1176,7 @@ fn render_job_detail_popup(frame: &mut Frame, area: Rect, job: &MockJob) {
         }
     };
 
-    let info_text = vec![
+    let mut info_text = vec![
         Line::from(""),
         Line::from(vec![
             Span::styled("Repository: ", Style::default().fg(Color::Yellow)),
@@ -907,6 +1207,13 @@ fn render_job_detail_popup(frame: &mut Frame, area: Rect, job: &MockJob) {
         ]),
     ];
 
+    if let Some(test_report) = test_report {
+        info_text.push(Line::from(test_summary_spans(test_report)));
+    }
+    if let Some(dependencies) = dependencies {
+        info_text.push(Line::from(dependency_summary_spans(dependencies)));
+    }
+
     let info_para = Paragraph::new(info_text);
     frame.render_widget(info_para, chunks[0]);
 
@@ -931,12 +1238,249 @@ fn render_job_detail_popup(frame: &mut Frame, area: Rect, job: &MockJob) {
     frame.render_widget(logs_para, chunks[1]);
 }
 
+/// Pass/fail counts of a job's test results
+fn test_summary_spans(test_report: &Result<TestReport, String>) -> Vec<Span<'static>> {
+    let label = Span::styled("Tests:      ", Style::default().fg(Color::Yellow));
+    let report = match test_report {
+        Ok(report) => report,
+        Err(e) => {
+            return vec![
+                label,
+                Span::styled(format!("unavailable ({})", e), Style::default().fg(Color::Red)),
+            ]
+        }
+    };
+
+    let failed = report.failures() + report.errors();
+    let mut spans = vec![
+        label,
+        Span::styled(
+            format!("{} passed", report.passed()),
+            Style::default().fg(Color::Green),
+        ),
+        Span::raw(", "),
+        Span::styled(
+            format!("{} failed", failed),
+            if failed > 0 {
+                Style::default().fg(Color::Red)
+            } else {
+                Style::default()
+            },
+        ),
+    ];
+    if report.skipped() > 0 {
+        spans.push(Span::raw(format!(", {} skipped", report.skipped())));
+    }
+    spans
+}
+
+/// Outdated dependency counts of a job
+fn dependency_summary_spans(dependencies: &Result<OutdatedReport, String>) -> Vec<Span<'static>> {
+    let label = Span::styled("Outdated:   ", Style::default().fg(Color::Yellow));
+    match dependencies {
+        Ok(report) if report.total() == 0 => vec![
+            label,
+            Span::styled("none", Style::default().fg(Color::Green)),
+        ],
+        Ok(report) => vec![
+            label,
+            Span::styled(
+                format!("{} dependencies", report.total()),
+                Style::default().fg(Color::Yellow),
+            ),
+            Span::raw(format!(
+                " ({} direct, {} transitive)",
+                report.direct, report.transitive
+            )),
+        ],
+        Err(e) => vec![
+            label,
+            Span::styled(format!("unavailable ({})", e), Style::default().fg(Color::Red)),
+        ],
+    }
+}
+
+/// Render connection stats popup with a histogram of recent poll latencies
+fn render_connection_stats_popup(frame: &mut Frame, area: Rect, connection: &ConnectionStats) {
+    let popup_area = centered_rect(60, 60, area);
+    frame.render_widget(Clear, popup_area);
+
+    let (color, label) = connection_label(&connection.state);
+    let block = Block::default()
+        .title(" Connection Stats - Press any key to close ")
+        .title_style(
+            Style::default()
+                .fg(Color::Cyan)
+                .add_modifier(Modifier::BOLD),
+        )
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(color))
+        .style(Style::default().bg(Color::Black));
+    let inner = block.inner(popup_area);
+    frame.render_widget(block, popup_area);
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(5), Constraint::Min(0)])
+        .split(inner);
+
+    let mut state = vec![Span::styled(
+        format!("● {}", label),
+        Style::default().fg(color).add_modifier(Modifier::BOLD),
+    )];
+    if let ConnectionState::Degraded(reason) | ConnectionState::Disconnected(reason) =
+        &connection.state
+    {
+        state.push(Span::styled(
+            format!(" - {}", reason),
+            Style::default().fg(Color::Gray),
+        ));
+    }
+    let last_success = connection
+        .last_success
+        .map(|time| time.format("%Y-%m-%d %H:%M:%S").to_string())
+        .unwrap_or_else(|| "never".to_string());
+    let average = connection
+        .average_latency()
+        .map(|latency| format!("{} ms", latency.as_millis()))
+        .unwrap_or_else(|| "-".to_string());
+    let field = |name: &'static str| Span::styled(name, Style::default().fg(Color::Yellow));
+    let summary = vec![
+        Line::from(state),
+        Line::from(vec![field("Last success: "), Span::raw(last_success)]),
+        Line::from(vec![
+            field("Polls:        "),
+            Span::raw(format!(
+                "{} ({} failed, {} in a row)",
+                connection.polls, connection.failures, connection.consecutive_failures
+            )),
+        ]),
+        Line::from(vec![field("Avg latency:  "), Span::raw(average)]),
+    ];
+    frame.render_widget(Paragraph::new(summary), chunks[0]);
+
+    let histogram = connection.latency_histogram();
+    let data: Vec<(&str, u64)> = histogram
+        .iter()
+        .map(|(bucket, count)| (bucket.as_str(), *count))
+        .collect();
+    let chart = BarChart::default()
+        .block(
+            Block::default()
+                .title(format!(
+                    " Latency (last {} polls) ",
+                    connection.latencies.len()
+                ))
+                .borders(Borders::TOP)
+                .border_style(Style::default().fg(Color::Gray)),
+        )
+        .data(data.as_slice())
+        .bar_width(7)
+        .bar_gap(2)
+        .bar_style(Style::default().fg(Color::Cyan))
+        .value_style(Style::default().fg(Color::Black).bg(Color::Cyan));
+    frame.render_widget(chart, chunks[1]);
+}
+
+/// Render recent errors popup
+fn render_recent_errors_popup(frame: &mut Frame, area: Rect, ui_state: &UiState) {
+    let popup_area = centered_rect(80, 70, area);
+
+    // Clear the background
+    let clear_block = Block::default()
+        .style(Style::default().bg(Color::Black));
+    frame.render_widget(clear_block, area);
+
+    let block = Block::default()
+        .title(format!(
+            " Recent Errors ({}) - Enter to go to job ",
+            ui_state.recent_errors.len()
+        ))
+        .title_style(
+            Style::default()
+                .fg(Color::Red)
+                .add_modifier(Modifier::BOLD),
+        )
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Red))
+        .style(Style::default().bg(Color::Black));
+
+    if let Some(error) = ui_state.recent_errors_error {
+        let text = vec![
+            Line::from(""),
+            Line::from(Span::styled(
+                "Failed to fetch recent errors",
+                Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+            )),
+            Line::from(Span::styled(error, Style::default().fg(Color::Gray))),
+        ];
+        let paragraph = Paragraph::new(text)
+            .block(block)
+            .alignment(ratatui::layout::Alignment::Center);
+        frame.render_widget(paragraph, popup_area);
+        return;
+    }
+
+    if ui_state.recent_errors.is_empty() {
+        let paragraph = Paragraph::new(vec![
+            Line::from(""),
+            Line::from(Span::styled(
+                "No recent errors",
+                Style::default().fg(Color::Green),
+            )),
+        ])
+        .block(block)
+        .alignment(ratatui::layout::Alignment::Center);
+        frame.render_widget(paragraph, popup_area);
+        return;
+    }
+
+    let items: Vec<ListItem> = ui_state
+        .recent_errors
+        .iter()
+        .map(|entry| {
+            ListItem::new(Line::from(vec![
+                Span::styled(
+                    entry.timestamp.with_timezone(&Local).format("%H:%M:%S").to_string(),
+                    Style::default().fg(Color::Gray),
+                ),
+                Span::raw("  "),
+                Span::styled(
+                    format!("{:<10}", entry.job_id),
+                    Style::default()
+                        .fg(Color::Cyan)
+                        .add_modifier(Modifier::UNDERLINED),
+                ),
+                Span::raw("  "),
+                Span::styled(
+                    format!("{:<8}", entry.step),
+                    Style::default().fg(Color::Yellow),
+                ),
+                Span::raw("  "),
+                Span::styled(entry.summary().to_string(), Style::default().fg(Color::White)),
+            ]))
+        })
+        .collect();
+
+    let list = List::new(items)
+        .block(block)
+        .highlight_style(
+            Style::default()
+                .bg(Color::DarkGray)
+                .add_modifier(Modifier::BOLD),
+        )
+        .highlight_symbol("> ");
+
+    let mut state = ListState::default().with_selected(Some(ui_state.selected_error));
+    frame.render_stateful_widget(list, popup_area, &mut state);
+}
+
 /// Render filter menu
 fn render_filter_menu(frame: &mut Frame, area: Rect, ui_state: &UiState) {
-    let popup_area = centered_rect(30, 30, area);
+    let popup_area = centered_rect(30, 40, area);
 
     let block = Block::default()
-        .title(" Filter by Status ")
+        .title(" Filter Jobs ")
         .title_style(
             Style::default()
                 .fg(Color::Cyan)
@@ -946,9 +1490,11 @@ fn render_filter_menu(frame: &mut Frame, area: Rect, ui_state: &UiState) {
         .border_style(Style::default().fg(Color::Cyan))
         .style(Style::default().bg(Color::Black));
 
-    let options = ["All", "Running", "Success", "Failed", "Pending"];
-    let items: Vec<ListItem> = options
+    let options = STATUS_FILTER_OPTIONS
         .iter()
+        .map(|option| option.to_string())
+        .chain(ui_state.label_filter_options.iter().cloned());
+    let items: Vec<ListItem> = options
         .enumerate()
         .map(|(i, option)| {
             let style = if i == ui_state.selected_filter_option {
@@ -1048,10 +1594,51 @@ mod tests {
         assert_eq!(memory_color(90), Color::Red);
     }
 
+    #[test]
+    fn test_connection_indicator_spans() {
+        let mut connection = ConnectionStats::default();
+        let spans = connection_indicator_spans(&connection);
+        assert_eq!(spans.len(), 1);
+        assert_eq!(spans[0].content, "● Disconnected");
+        assert_eq!(spans[0].style.fg, Some(Color::Red));
+
+        connection.record_success(std::time::Duration::from_millis(50), Local::now());
+        let spans = connection_indicator_spans(&connection);
+        assert_eq!(spans[0].content, "● Connected");
+        assert_eq!(spans[0].style.fg, Some(Color::Green));
+        assert!(spans[1].content.starts_with(" (updated "));
+
+        connection.record_failure("connection refused");
+        let spans = connection_indicator_spans(&connection);
+        assert_eq!(spans[0].style.fg, Some(Color::Yellow));
+    }
+
     #[test]
     fn test_format_uptime() {
         assert_eq!(format_uptime(30), "30s");
         assert_eq!(format_uptime(90), "1m 30s");
         assert_eq!(format_uptime(3661), "1h 01m 01s");
     }
+
+    #[test]
+    fn test_test_summary_spans() {
+        let text = |spans: Vec<Span>| {
+            spans
+                .iter()
+                .map(|s| s.content.to_string())
+                .collect::<String>()
+        };
+
+        let report: TestReport = serde_json::from_str(
+            r#"{"suites":[{"name":"unit","tests":10,"failures":1,"errors":1,"skipped":2}]}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            text(test_summary_spans(&Ok(report))),
+            "Tests:      6 passed, 2 failed, 2 skipped"
+        );
+
+        let error = Err("connection refused".to_string());
+        assert!(text(test_summary_spans(&error)).contains("unavailable (connection refused)"));
+    }
 }